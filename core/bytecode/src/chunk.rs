@@ -1,15 +1,27 @@
-use crate::InstructionReader;
+use crate::{ConstantValue, InstructionReader, LookupCache};
 use koto_memory::Ptr;
-use koto_parser::{ConstantPool, Span};
+use koto_parser::{ConstantIndex, ConstantPool, Span};
 use std::{
+    collections::BTreeMap,
     fmt::{self, Write},
     path::PathBuf,
 };
 
+/// The values exported by a module that are known at compile time, keyed by their IDs
+///
+/// See [CompilerSettings::enable_constant_inlining](crate::CompilerSettings::enable_constant_inlining).
+pub type ExportedConstants = BTreeMap<String, ConstantValue>;
+
+/// The constants exported by imported modules, keyed by the names used to import the modules
+///
+/// See [CompilerSettings::imported_constants](crate::CompilerSettings::imported_constants).
+pub type ImportedConstants = BTreeMap<String, ExportedConstants>;
+
 /// Debug information for a Koto program
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct DebugInfo {
-    source_map: Vec<(u32, Span)>,
+    pub(crate) source_map: Vec<(u32, Span)>,
+    pub(crate) local_scopes: Vec<LocalScope>,
     /// The source of the program that the debug info was derived from
     pub source: String,
 }
@@ -33,21 +45,73 @@ impl DebugInfo {
 
     /// Returns a source span for a given instruction pointer
     pub fn get_source_span(&self, ip: u32) -> Option<Span> {
-        // Find the last entry with an ip less than or equal to the input
-        // an upper_bound would nice here, but this isn't currently a performance sensitive function
-        // so a scan through the entries will do.
-        let mut result = None;
-        for entry in self.source_map.iter() {
-            if entry.0 <= ip {
-                result = Some(entry.1);
-            } else {
-                break;
-            }
-        }
-        result
+        // Find the last entry with an ip less than or equal to the input.
+        // Entries are pushed in ip order, and the profiler looks up a span for each executed
+        // instruction, so a binary search is used.
+        let end = self
+            .source_map
+            .partition_point(|(entry_ip, _)| *entry_ip <= ip);
+        end.checked_sub(1).map(|index| self.source_map[index].1)
+    }
+
+    /// Returns the scopes of local values for each function in the program
+    ///
+    /// Scopes of nested functions are contained within the ip range of the enclosing function,
+    /// and appear before the enclosing function's scope.
+    pub fn local_scopes(&self) -> &[LocalScope] {
+        &self.local_scopes
+    }
+
+    /// Returns the scope of local values for the innermost function containing the given ip
+    pub fn local_scope_at(&self, ip: u32) -> Option<&LocalScope> {
+        self.local_scopes
+            .iter()
+            .filter(|scope| scope.contains(ip))
+            .max_by_key(|scope| scope.start_ip)
+    }
+
+    /// Returns the named local values that are available at a given instruction pointer
+    ///
+    /// Only the locals of the innermost function containing the ip are returned.
+    pub fn locals_at(&self, ip: u32) -> impl Iterator<Item = &LocalName> {
+        self.local_scope_at(ip).into_iter().flat_map(move |scope| {
+            scope
+                .locals
+                .iter()
+                .filter(move |local| local.start_ip <= ip)
+        })
+    }
+}
+
+/// The named local values of a function, along with the range of its instructions
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LocalScope {
+    /// The ip of the function's first instruction
+    pub start_ip: u32,
+    /// The ip following the function's last instruction
+    pub end_ip: u32,
+    /// The function's named locals
+    pub locals: Vec<LocalName>,
+}
+
+impl LocalScope {
+    /// Returns true if the given ip is within the scope's range of instructions
+    pub fn contains(&self, ip: u32) -> bool {
+        self.start_ip <= ip && ip < self.end_ip
     }
 }
 
+/// A named local value that's assigned to a register in a function's frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocalName {
+    /// The local's name, stored in the chunk's constant pool
+    pub name: ConstantIndex,
+    /// The frame register that contains the local's value
+    pub register: u8,
+    /// The ip from which the local is available
+    pub start_ip: u32,
+}
+
 /// A compiled chunk of bytecode, along with its associated constants and metadata
 #[derive(Clone, Default, PartialEq)]
 pub struct Chunk {
@@ -59,6 +123,15 @@ pub struct Chunk {
     pub source_path: Option<PathBuf>,
     /// Debug information associated with the chunk's bytecode
     pub debug_info: DebugInfo,
+    /// The map lookups that have been cached by the runtime while running the chunk
+    pub lookup_cache: LookupCache,
+    /// The chunk's exported values that are known at compile time
+    pub exported_constants: ExportedConstants,
+    /// The constants of imported modules that were available for inlining when the chunk was
+    /// compiled
+    ///
+    /// The [Loader](crate::Loader) recompiles cached chunks when the constants no longer match.
+    pub imported_constants: ImportedConstants,
 }
 
 impl Chunk {
@@ -74,6 +147,9 @@ impl Chunk {
             constants,
             source_path,
             debug_info,
+            lookup_cache: LookupCache::default(),
+            exported_constants: ExportedConstants::default(),
+            imported_constants: ImportedConstants::default(),
         }
     }
 
@@ -151,3 +227,56 @@ impl fmt::Debug for Chunk {
         write!(f, "Chunk ({self:p})")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{CompilerSettings, Loader};
+
+    fn local_names(script: &str, scope_index: usize, ip_offset: u32) -> Vec<String> {
+        let chunk = Loader::default()
+            .compile_script(script, &None, CompilerSettings::default())
+            .unwrap();
+        let scope = &chunk.debug_info.local_scopes()[scope_index];
+        let ip = scope.end_ip - ip_offset;
+
+        chunk
+            .debug_info
+            .locals_at(ip)
+            .map(|local| chunk.constants.get_str(local.name).to_string())
+            .collect()
+    }
+
+    const SCRIPT: &str = "
+x = 1
+f = |a|
+  b = a + x
+  b
+y = f x
+";
+
+    #[test]
+    fn top_level_locals() {
+        // The top-level scope is last, after the scope of the nested function
+        assert_eq!(local_names(SCRIPT, 1, 1), ["x", "f", "y"]);
+    }
+
+    #[test]
+    fn function_locals() {
+        // Captured values are assigned to registers following the function's args
+        assert_eq!(local_names(SCRIPT, 0, 1), ["a", "x", "b"]);
+    }
+
+    #[test]
+    fn locals_are_available_from_their_assignment() {
+        let chunk = Loader::default()
+            .compile_script(SCRIPT, &None, CompilerSettings::default())
+            .unwrap();
+
+        let locals: Vec<_> = chunk
+            .debug_info
+            .locals_at(0)
+            .map(|local| chunk.constants.get_str(local.name))
+            .collect();
+        assert_eq!(locals, ["x"]);
+    }
+}
@@ -1,8 +1,15 @@
-use crate::{DebugInfo, FunctionFlags, Op, TypeId};
+use crate::{
+    chunk::{LocalName, LocalScope},
+    optimizer::{
+        evaluate_constant, find_exported_constants, optimize_bytecode, AddedConstants,
+        ConstantValue, OptimizerError,
+    },
+    DebugInfo, ExportedConstants, FunctionFlags, ImportedConstants, Op, StringFormatFlags, TypeId,
+};
 use koto_parser::{
-    Ast, AstBinaryOp, AstFor, AstIf, AstIndex, AstNode, AstTry, AstUnaryOp, ConstantIndex,
-    Function, ImportItemNode, LookupNode, MapKey, MatchArm, MetaKeyId, Node, Span, StringNode,
-    SwitchArm,
+    Ast, AstBinaryOp, AstFor, AstIf, AstIndex, AstNode, AstString, AstTry, AstUnaryOp, Constant,
+    ConstantIndex, ConstantPool, Function, ImportItemNode, LookupNode, MapKey, MapPatternEntry,
+    MatchArm, MetaKeyId, Node, Span, StringFormatOptions, StringNode, SwitchArm,
 };
 use smallvec::SmallVec;
 use std::collections::HashSet;
@@ -20,6 +27,8 @@ enum ErrorKind {
     InvalidBinaryOp { kind: String, op: AstBinaryOp },
     #[error("`{0}` used outside of loop")]
     InvalidLoopKeyword(String),
+    #[error("invalid map pattern key, expected an id or a string literal")]
+    InvalidMapPatternKey,
     #[error("invalid match pattern (found '{0}')")]
     InvalidMatchPattern(Node),
     #[error("args with ellipses are only allowed in first or last position")]
@@ -75,6 +84,8 @@ enum ErrorKind {
     UnexpectedEllipsis,
     #[error("unexpected Wildcard")]
     UnexpectedWildcard,
+    #[error("unexpected Type")]
+    UnexpectedType,
     #[error("expected {expected}, found {unexpected}")]
     UnexpectedNode { expected: String, unexpected: Node },
     #[error("expected {expected} patterns in match arm, found {unexpected}")]
@@ -84,6 +95,8 @@ enum ErrorKind {
 
     #[error(transparent)]
     FrameError(#[from] FrameError),
+    #[error(transparent)]
+    OptimizerError(#[from] OptimizerError),
 }
 
 /// The error type used to report errors during compilation
@@ -162,6 +175,8 @@ struct Frame {
     loop_stack: Vec<Loop>,
     register_stack: Vec<u8>,
     local_registers: Vec<LocalRegister>,
+    // The ips at which each of the local registers were assigned, used for debug info
+    local_start_ips: Vec<u32>,
     exported_ids: HashSet<ConstantIndex>,
     temporary_base: u8,
     temporary_count: u8,
@@ -171,6 +186,15 @@ struct Frame {
     // This is a coarse check, e.g. we currently don't check if the last expression
     // returns in all branches, but it'll do for now as an optimization for simple cases.
     last_node_was_return: bool,
+    // The function's output type hint, checked when values are returned (or yielded from
+    // generators) when type checks are enabled.
+    output_type: Option<AstIndex>,
+    is_generator: bool,
+    // True when the next node to be compiled is in tail position, i.e. its result will be
+    // returned from the function, and calls in tail position are compiled as tail calls.
+    // The flag is taken by the next node that's compiled, with nodes that pass the tail position
+    // on to their children (e.g. blocks and `if` expressions) setting it again as needed.
+    tail_position: bool,
 }
 
 impl Frame {
@@ -216,6 +240,32 @@ impl Frame {
         }
     }
 
+    // Records the given ip as the start ip for any local registers that have been added
+    fn record_local_start_ips(&mut self, ip: u32) {
+        let new_locals = self.local_registers.len() - self.local_start_ips.len();
+        self.local_start_ips
+            .extend(std::iter::repeat_n(ip, new_locals));
+    }
+
+    // Returns the frame's named locals, for use in debug info
+    fn named_locals(&self) -> Vec<LocalName> {
+        self.local_registers
+            .iter()
+            .zip(self.local_start_ips.iter())
+            .enumerate()
+            .filter_map(
+                |(register, (local_register, start_ip))| match local_register {
+                    LocalRegister::Assigned(name) => Some(LocalName {
+                        name: *name,
+                        register: register as u8,
+                        start_ip: *start_ip,
+                    }),
+                    _ => None,
+                },
+            )
+            .collect()
+    }
+
     fn push_register(&mut self) -> Result<u8, FrameError> {
         let new_register = self.temporary_base + self.temporary_count;
         self.temporary_count += 1;
@@ -435,6 +485,48 @@ pub struct CompilerSettings {
     /// This is used by the REPL to automatically export values so that they're available between
     /// chunks.
     pub export_top_level_ids: bool,
+    /// Enables runtime checks of type hints
+    ///
+    /// Type hints are ignored by default. When enabled, an error is thrown when a function
+    /// argument, a function's output, or a value being assigned doesn't match its type hint.
+    pub enable_type_checks: bool,
+    /// Enables optimization of the compiled bytecode
+    ///
+    /// When enabled, expressions made up of numeric and string literals are evaluated at compile
+    /// time, branches of `if` expressions with constant conditions are removed, jumps are
+    /// redirected past intermediate jumps, and redundant copies are removed.
+    pub enable_optimizer: bool,
+    /// Enables the inlining of constants that are exported by imported modules
+    ///
+    /// When enabled, the script's exported values that are known at compile time are included
+    /// in the compiler's output, see [CompilerOutput::exported_constants].
+    ///
+    /// Imports of items that are found in [imported_constants](Self::imported_constants) are
+    /// replaced with the items' values, e.g. `from config import max_size`. The module isn't
+    /// imported at runtime when all of the imported items are inlined.
+    pub enable_constant_inlining: bool,
+    /// The constants exported by modules that are imported by the script
+    ///
+    /// The [Loader](crate::Loader) finds the constants of imported modules when a script is
+    /// compiled with constant inlining enabled.
+    pub imported_constants: ImportedConstants,
+}
+
+/// The output of the [Compiler]
+pub struct CompilerOutput {
+    /// The compiled bytecode
+    pub bytes: Box<[u8]>,
+    /// Debug information for the compiled bytecode
+    pub debug_info: DebugInfo,
+    /// The constants that are referred to by the bytecode
+    ///
+    /// The AST's constants are extended with any constants that were produced by the optimizer.
+    pub constants: ConstantPool,
+    /// The script's exported values that are known at compile time
+    ///
+    /// Exported constants are only found when
+    /// [CompilerSettings::enable_constant_inlining] is enabled.
+    pub exported_constants: ExportedConstants,
 }
 
 /// The compiler used by the Koto language
@@ -445,16 +537,20 @@ pub struct Compiler {
     frame_stack: Vec<Frame>,
     span_stack: Vec<Span>,
     settings: CompilerSettings,
+    added_constants: AddedConstants,
 }
 
 impl Compiler {
     /// Compiles an [Ast]
     ///
-    /// Returns compiled bytecode along with corresponding debug information
-    pub fn compile(
-        ast: &Ast,
-        settings: CompilerSettings,
-    ) -> Result<(Box<[u8]>, DebugInfo), CompilerError> {
+    /// Returns compiled bytecode along with corresponding debug information and constants
+    pub fn compile(ast: &Ast, settings: CompilerSettings) -> Result<CompilerOutput, CompilerError> {
+        let exported_constants = if settings.enable_constant_inlining {
+            find_exported_constants(ast)
+        } else {
+            ExportedConstants::default()
+        };
+
         let mut compiler = Compiler {
             settings,
             ..Default::default()
@@ -464,10 +560,32 @@ impl Compiler {
             compiler.compile_node(ResultRegister::None, entry_point, ast)?;
         }
 
-        if compiler.bytes.len() <= u32::MAX as usize {
-            Ok((compiler.bytes.into(), compiler.debug_info))
+        let optimizer_error = |error: OptimizerError| CompilerError {
+            error: error.into(),
+            span: Span::default(),
+        };
+
+        let bytes = if compiler.settings.enable_optimizer {
+            optimize_bytecode(&compiler.bytes, &mut compiler.debug_info).map_err(optimizer_error)?
         } else {
-            compiler.error(ErrorKind::ResultingBytecodeIsTooLarge(compiler.bytes.len()))
+            compiler.bytes
+        };
+
+        if bytes.len() <= u32::MAX as usize {
+            Ok(CompilerOutput {
+                bytes: bytes.into(),
+                debug_info: compiler.debug_info,
+                constants: compiler
+                    .added_constants
+                    .build(ast)
+                    .map_err(optimizer_error)?,
+                exported_constants,
+            })
+        } else {
+            Err(CompilerError {
+                error: ErrorKind::ResultingBytecodeIsTooLarge(bytes.len()),
+                span: Span::default(),
+            })
         }
     }
 
@@ -485,6 +603,8 @@ impl Compiler {
             self.frame_mut().last_node_was_return = matches!(&node.node, Node::Return(_));
         }
 
+        let tail_position = self.take_tail_position();
+
         let result = match &node.node {
             Node::Null => {
                 let result = self.get_result_register(result_register)?;
@@ -493,9 +613,13 @@ impl Compiler {
                 }
                 result
             }
-            Node::Nested(nested) => self.compile_node(result_register, ast.node(*nested), ast)?,
-            Node::Id(index) => self.compile_load_id(result_register, *index)?,
+            Node::Nested(nested) => {
+                self.set_tail_position(tail_position);
+                self.compile_node(result_register, ast.node(*nested), ast)?
+            }
+            Node::Id(index, ..) => self.compile_load_id(result_register, *index)?,
             Node::Lookup(lookup) => {
+                self.set_tail_position(tail_position);
                 self.compile_lookup(result_register, lookup, None, None, None, ast)?
             }
             Node::BoolTrue => {
@@ -515,14 +639,7 @@ impl Compiler {
             Node::SmallInt(n) => {
                 let result = self.get_result_register(result_register)?;
                 if let Some(result) = result {
-                    match *n {
-                        0 => self.push_op(Set0, &[result.register]),
-                        1 => self.push_op(Set1, &[result.register]),
-                        n if n >= 0 => self.push_op(SetNumberU8, &[result.register, n as u8]),
-                        n => {
-                            self.push_op(SetNumberNegU8, &[result.register, n.unsigned_abs() as u8])
-                        }
-                    }
+                    self.compile_load_integer(result.register, *n as i64, ast)?;
                 }
                 result
             }
@@ -540,7 +657,10 @@ impl Compiler {
                 }
                 result
             }
-            Node::Str(string) => self.compile_string(result_register, &string.nodes, ast)?,
+            Node::Str(string) => match self.fold_constant(node, ast) {
+                Some(value) => self.compile_constant_value(result_register, value, ast)?,
+                None => self.compile_string(result_register, &string.nodes, ast)?,
+            },
             Node::List(elements) => {
                 self.compile_make_sequence(result_register, elements, Op::SequenceToList, ast)?
             }
@@ -639,10 +759,23 @@ impl Compiler {
                 result
             }
             Node::MainBlock { body, local_count } => {
-                self.compile_frame(*local_count as u8, body, &[], &[], ast, true)?;
+                self.compile_frame(
+                    FrameParameters {
+                        local_count: *local_count as u8,
+                        expressions: body,
+                        args: &[],
+                        captures: &[],
+                        output_type: None,
+                        allow_implicit_return: true,
+                    },
+                    ast,
+                )?;
                 None
             }
-            Node::Block(expressions) => self.compile_block(result_register, expressions, ast)?,
+            Node::Block(expressions) => {
+                self.set_tail_position(tail_position);
+                self.compile_block(result_register, expressions, ast)?
+            }
             Node::Tuple(elements) => {
                 self.compile_make_sequence(result_register, elements, Op::SequenceToTuple, ast)?
             }
@@ -651,6 +784,7 @@ impl Compiler {
             }
             Node::Function(f) => self.compile_function(result_register, f, ast)?,
             Node::NamedCall { id, args } => {
+                self.set_tail_position(tail_position);
                 self.compile_named_call(result_register, *id, args, None, ast)?
             }
             Node::Import { from, items } => {
@@ -664,19 +798,32 @@ impl Compiler {
                 targets,
                 expression,
             } => self.compile_multi_assign(result_register, targets, *expression, ast)?,
-            Node::UnaryOp { op, value } => {
-                self.compile_unary_op(result_register, *op, *value, ast)?
-            }
-            Node::BinaryOp { op, lhs, rhs } => {
-                self.compile_binary_op(result_register, *op, *lhs, *rhs, ast)?
+            Node::UnaryOp { op, value } => match self.fold_constant(node, ast) {
+                Some(folded) => self.compile_constant_value(result_register, folded, ast)?,
+                None => self.compile_unary_op(result_register, *op, *value, ast)?,
+            },
+            Node::BinaryOp { op, lhs, rhs } => match self.fold_constant(node, ast) {
+                Some(folded) => self.compile_constant_value(result_register, folded, ast)?,
+                None => self.compile_binary_op(result_register, *op, *lhs, *rhs, ast)?,
+            },
+            Node::If(ast_if) => {
+                self.set_tail_position(tail_position);
+                self.compile_if(result_register, ast_if, ast)?
             }
-            Node::If(ast_if) => self.compile_if(result_register, ast_if, ast)?,
             Node::Match { expression, arms } => {
+                self.set_tail_position(tail_position);
                 self.compile_match(result_register, *expression, arms, ast)?
             }
-            Node::Switch(arms) => self.compile_switch(result_register, arms, ast)?,
+            Node::Switch(arms) => {
+                self.set_tail_position(tail_position);
+                self.compile_switch(result_register, arms, ast)?
+            }
             Node::Ellipsis(_) => return self.error(ErrorKind::UnexpectedEllipsis),
             Node::Wildcard(_) => return self.error(ErrorKind::UnexpectedWildcard),
+            Node::Type(_) => return self.error(ErrorKind::UnexpectedType),
+            Node::MapPattern { .. } => {
+                return self.error(ErrorKind::InvalidMatchPattern(node.node.clone()))
+            }
             Node::For(ast_for) => self.compile_for(result_register, ast_for, ast)?,
             Node::While { condition, body } => {
                 self.compile_loop(result_register, Some((*condition, false)), *body, ast)?
@@ -729,22 +876,33 @@ impl Compiler {
             Node::Return(None) => match self.get_result_register(result_register)? {
                 Some(result) => {
                     self.push_op(SetNull, &[result.register]);
+                    if !self.frame().is_generator {
+                        self.compile_assert_output_type(result.register, ast)?;
+                    }
                     self.push_op(Return, &[result.register]);
                     Some(result)
                 }
                 None => {
                     let register = self.push_register()?;
                     self.push_op(SetNull, &[register]);
+                    if !self.frame().is_generator {
+                        self.compile_assert_output_type(register, ast)?;
+                    }
                     self.push_op(Return, &[register]);
                     self.pop_register()?;
                     None
                 }
             },
             Node::Return(Some(expression)) => {
+                self.set_tail_position(self.tail_calls_allowed());
                 let expression_register = self
                     .compile_node(ResultRegister::Any, ast.node(*expression), ast)?
                     .unwrap();
 
+                if !self.frame().is_generator {
+                    self.compile_assert_output_type(expression_register.register, ast)?;
+                }
+
                 match result_register {
                     ResultRegister::Any => {
                         self.push_op(Return, &[expression_register.register]);
@@ -774,6 +932,7 @@ impl Compiler {
                     .compile_node(ResultRegister::Any, ast.node(*expression), ast)?
                     .unwrap();
 
+                self.compile_assert_output_type(expression_register.register, ast)?;
                 self.push_op(Yield, &[expression_register.register]);
 
                 if let Some(result) = result {
@@ -853,18 +1012,24 @@ impl Compiler {
 
     fn compile_frame(
         &mut self,
-        local_count: u8,
-        expressions: &[AstIndex],
-        args: &[AstIndex],
-        captures: &[ConstantIndex],
+        frame_parameters: FrameParameters,
         ast: &Ast,
-        allow_implicit_return: bool,
     ) -> Result<(), CompilerError> {
-        self.frame_stack.push(Frame::new(
+        let FrameParameters {
             local_count,
-            &self.collect_args(args, ast)?,
+            expressions,
+            args,
             captures,
-        ));
+            output_type,
+            allow_implicit_return,
+        } = frame_parameters;
+
+        let start_ip = self.bytes.len() as u32;
+        let mut frame = Frame::new(local_count, &self.collect_args(args, ast)?, captures);
+        frame.record_local_start_ips(start_ip);
+        frame.output_type = output_type;
+        frame.is_generator = !allow_implicit_return;
+        self.frame_stack.push(frame);
 
         // unpack nested args
         for (arg_index, arg) in args.iter().enumerate() {
@@ -885,6 +1050,12 @@ impl Compiler {
                     self.push_op(size_op, &[tuple_register, nested_args.len() as u8]);
                     self.compile_unpack_nested_args(tuple_register, nested_args, ast)?;
                 }
+                Node::MapPattern { entries, rest } => {
+                    self.compile_unpack_map(arg_index as u8 + 1, entries, *rest, false, ast)?;
+                }
+                Node::Id(_, Some(type_hint)) => {
+                    self.compile_assert_type(arg_index as u8 + 1, *type_hint, ast)?;
+                }
                 _ => {}
             }
             self.span_stack.pop();
@@ -896,10 +1067,15 @@ impl Compiler {
             ResultRegister::None
         };
 
+        if allow_implicit_return {
+            self.set_tail_position(self.tail_calls_allowed());
+        }
+
         let block_result = self.compile_block(result_register, expressions, ast)?;
 
         if let Some(result) = block_result {
             if !self.frame().last_node_was_return {
+                self.compile_assert_output_type(result.register, ast)?;
                 self.push_op_without_span(Op::Return, &[result.register]);
             }
             if result.is_temporary {
@@ -908,15 +1084,64 @@ impl Compiler {
         } else {
             let register = self.push_register()?;
             self.push_op(Op::SetNull, &[register]);
+            if !self.frame().is_generator {
+                self.compile_assert_output_type(register, ast)?;
+            }
             self.push_op_without_span(Op::Return, &[register]);
             self.pop_register()?;
         }
 
+        let locals = self.frame().named_locals();
+        self.debug_info.local_scopes.push(LocalScope {
+            start_ip,
+            end_ip: self.bytes.len() as u32,
+            locals,
+        });
+
         self.frame_stack.pop();
 
         Ok(())
     }
 
+    // Checks that a value matches a type hint, if type checks are enabled
+    fn compile_assert_type(
+        &mut self,
+        value_register: u8,
+        type_hint: AstIndex,
+        ast: &Ast,
+    ) -> Result<(), CompilerError> {
+        if !self.settings.enable_type_checks {
+            return Ok(());
+        }
+
+        let type_node = ast.node(type_hint);
+        match &type_node.node {
+            Node::Type(type_index) => {
+                self.span_stack.push(*ast.span(type_node.span));
+                self.push_op(Op::AssertType, &[value_register]);
+                self.push_var_u32(*type_index);
+                self.span_stack.pop();
+                Ok(())
+            }
+            unexpected => self.error(ErrorKind::UnexpectedNode {
+                expected: "Type".into(),
+                unexpected: unexpected.clone(),
+            }),
+        }
+    }
+
+    // Checks that a returned value matches the function's output type hint, if present
+    fn compile_assert_output_type(
+        &mut self,
+        value_register: u8,
+        ast: &Ast,
+    ) -> Result<(), CompilerError> {
+        match self.frame().output_type {
+            Some(output_type) => self.compile_assert_type(value_register, output_type, ast),
+            None => Ok(()),
+        }
+    }
+
     fn collect_args(&self, args: &[AstIndex], ast: &Ast) -> Result<Vec<Arg>, CompilerError> {
         // Collect args for local assignment in the new frame
         // Top-level args need to match the arguments as they appear in the arg list, with
@@ -936,12 +1161,16 @@ impl Compiler {
 
         for arg in args.iter() {
             match &ast.node(*arg).node {
-                Node::Id(id_index) => result.push(Arg::Local(*id_index)),
+                Node::Id(id_index, ..) => result.push(Arg::Local(*id_index)),
                 Node::Wildcard(_) => result.push(Arg::Placeholder),
                 Node::List(nested) | Node::Tuple(nested) => {
                     result.push(Arg::Placeholder);
                     nested_args.extend(self.collect_nested_args(nested, ast)?);
                 }
+                Node::MapPattern { .. } => {
+                    result.push(Arg::Placeholder);
+                    nested_args.extend(self.collect_nested_args(&[*arg], ast)?);
+                }
                 unexpected => {
                     return self.error(ErrorKind::UnexpectedNode {
                         expected: "ID in function args".into(),
@@ -960,11 +1189,25 @@ impl Compiler {
 
         for arg in args.iter() {
             match &ast.node(*arg).node {
-                Node::Id(id) => result.push(Arg::Unpacked(*id)),
+                Node::Id(id, ..) => result.push(Arg::Unpacked(*id)),
                 Node::Wildcard(_) => {}
                 Node::List(nested_args) | Node::Tuple(nested_args) => {
                     result.extend(self.collect_nested_args(nested_args, ast)?);
                 }
+                Node::MapPattern { entries, rest } => {
+                    for entry in entries {
+                        match (&entry.key, entry.pattern) {
+                            (_, Some(pattern)) => {
+                                result.extend(self.collect_nested_args(&[pattern], ast)?)
+                            }
+                            (MapKey::Id(id), None) => result.push(Arg::Unpacked(*id)),
+                            _ => return self.error(ErrorKind::InvalidMapPatternKey),
+                        }
+                    }
+                    if let Some(rest) = rest {
+                        result.extend(self.collect_nested_args(&[*rest], ast)?);
+                    }
+                }
                 Node::Ellipsis(Some(id)) => result.push(Arg::Unpacked(*id)),
                 Node::Ellipsis(None) => {}
                 unexpected => {
@@ -1000,7 +1243,7 @@ impl Compiler {
 
             match &ast.node(*arg).node {
                 Node::Wildcard(_) => {}
-                Node::Id(constant_index) => {
+                Node::Id(constant_index, ..) => {
                     let local_register = self.assign_local_register(*constant_index)?;
                     self.push_op(TempIndex, &[local_register, container_register, arg_index]);
                 }
@@ -1022,6 +1265,12 @@ impl Compiler {
                     self.compile_unpack_nested_args(tuple_register, nested_args, ast)?;
                     self.pop_register()?; // tuple_register
                 }
+                Node::MapPattern { entries, rest } => {
+                    let map_register = self.push_register()?;
+                    self.push_op(TempIndex, &[map_register, container_register, arg_index]);
+                    self.compile_unpack_map(map_register, entries, *rest, false, ast)?;
+                    self.pop_register()?; // map_register
+                }
                 Node::Ellipsis(maybe_id) if is_first_arg => {
                     if let Some(id) = maybe_id {
                         // e.g. [first..., x, y]
@@ -1060,6 +1309,8 @@ impl Compiler {
     ) -> CompileNodeResult {
         use Op::SetNull;
 
+        let tail_position = self.take_tail_position();
+
         let result = match expressions {
             [] => match self.get_result_register(result_register)? {
                 Some(result) => {
@@ -1068,12 +1319,16 @@ impl Compiler {
                 }
                 None => return self.error(ErrorKind::MissingResultRegister),
             },
-            [expression] => self.compile_node(result_register, ast.node(*expression), ast)?,
+            [expression] => {
+                self.set_tail_position(tail_position);
+                self.compile_node(result_register, ast.node(*expression), ast)?
+            }
             [expressions @ .., last_expression] => {
                 for expression in expressions.iter() {
                     self.compile_node(ResultRegister::None, ast.node(*expression), ast)?;
                 }
 
+                self.set_tail_position(tail_position);
                 self.compile_node(result_register, ast.node(*last_expression), ast)?
             }
         };
@@ -1091,8 +1346,10 @@ impl Compiler {
         ast: &Ast,
     ) -> Result<Option<u8>, CompilerError> {
         let result = match &ast.node(target).node {
-            Node::Id(constant_index) => Some(self.reserve_local_register(*constant_index)?),
-            Node::Meta { .. } | Node::Lookup(_) | Node::Wildcard(_) => None,
+            Node::Id(constant_index, ..) => Some(self.reserve_local_register(*constant_index)?),
+            Node::Meta { .. } | Node::Lookup(_) | Node::Wildcard(_) | Node::MapPattern { .. } => {
+                None
+            }
             unexpected => {
                 return self.error(ErrorKind::UnexpectedNode {
                     expected: "ID".into(),
@@ -1128,7 +1385,11 @@ impl Compiler {
         self.span_stack.push(*ast.span(target_node.span));
 
         match &target_node.node {
-            Node::Id(id_index) => {
+            Node::Id(id_index, type_hint) => {
+                if let Some(type_hint) = type_hint {
+                    self.compile_assert_type(value_register.register, *type_hint, ast)?;
+                }
+
                 if !value_register.is_temporary {
                     // To ensure that exported rhs ids with the same name as a local that's
                     // currently being assigned can be loaded correctly, only commit the
@@ -1153,6 +1414,22 @@ impl Compiler {
             Node::Meta(meta_id, name) => {
                 self.compile_meta_export(*meta_id, *name, value_register.register)?;
             }
+            Node::MapPattern { entries, rest } => {
+                // e.g. `{x, y} = foo`
+                // Locals are unpacked from a copy of the map register, in case the map's own
+                // register is reassigned during unpacking, e.g. `{x, foo} = foo`.
+                let map_register = if value_register.is_temporary {
+                    value_register.register
+                } else {
+                    let map_register = self.push_register()?;
+                    self.push_op(Copy, &[map_register, value_register.register]);
+                    map_register
+                };
+                self.compile_unpack_map(map_register, entries, *rest, export_assignment, ast)?;
+                if !value_register.is_temporary {
+                    self.pop_register()?; // map_register
+                }
+            }
             Node::Wildcard(_) => {}
             unexpected => {
                 return self.error(ErrorKind::UnexpectedNode {
@@ -1228,7 +1505,7 @@ impl Compiler {
             targets.iter().zip(target_registers.iter()).enumerate()
         {
             match &ast.node(*target).node {
-                Node::Id(id_index) => {
+                Node::Id(id_index, ..) => {
                     let target_register =
                         target_register.expect("Missing target register for assignment");
                     if rhs_is_temp_tuple {
@@ -1274,6 +1551,23 @@ impl Compiler {
 
                     self.pop_register()?; // value_register
                 }
+                Node::MapPattern { entries, rest } => {
+                    let map_register = self.push_register()?;
+
+                    if rhs_is_temp_tuple {
+                        self.push_op(TempIndex, &[map_register, iter_register, i as u8]);
+                    } else {
+                        self.push_op(IterUnpack, &[map_register, iter_register]);
+                    }
+
+                    self.compile_unpack_map(map_register, entries, *rest, false, ast)?;
+
+                    if result.is_some() {
+                        self.push_op(SequencePush, &[map_register]);
+                    }
+
+                    self.pop_register()?; // map_register
+                }
                 Node::Wildcard(_) => {
                     if result.is_some() {
                         let value_register = self.push_register()?;
@@ -1374,6 +1668,84 @@ impl Compiler {
         Ok(())
     }
 
+    // Evaluates an expression at compile time when the optimizer is enabled
+    fn fold_constant(&self, node: &AstNode, ast: &Ast) -> Option<ConstantValue> {
+        if !self.settings.enable_optimizer {
+            return None;
+        }
+
+        // Strings without interpolated expressions are already loaded directly as constants
+        if let Node::Str(string) = &node.node {
+            if !string
+                .nodes
+                .iter()
+                .any(|node| !matches!(node, StringNode::Literal(_)))
+            {
+                return None;
+            }
+        }
+
+        evaluate_constant(ast, &node.node)
+    }
+
+    fn compile_constant_value(
+        &mut self,
+        result_register: ResultRegister,
+        value: ConstantValue,
+        ast: &Ast,
+    ) -> CompileNodeResult {
+        let result = self.get_result_register(result_register)?;
+
+        if let Some(result) = result {
+            let register = result.register;
+            match value {
+                ConstantValue::Null => self.push_op(Op::SetNull, &[register]),
+                ConstantValue::Bool(true) => self.push_op(Op::SetTrue, &[register]),
+                ConstantValue::Bool(false) => self.push_op(Op::SetFalse, &[register]),
+                ConstantValue::Int(n) => self.compile_load_integer(register, n, ast)?,
+                ConstantValue::Float(n) => {
+                    let constant = self.add_constant(Constant::F64(n), ast)?;
+                    self.compile_constant_op(register, constant, Op::LoadFloat);
+                }
+                ConstantValue::Str(s) => {
+                    let constant = self.add_constant(Constant::Str(&s), ast)?;
+                    self.compile_load_string_constant(register, constant);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn compile_load_integer(
+        &mut self,
+        register: u8,
+        n: i64,
+        ast: &Ast,
+    ) -> Result<(), CompilerError> {
+        match n {
+            0 => self.push_op(Op::Set0, &[register]),
+            1 => self.push_op(Op::Set1, &[register]),
+            2..=255 => self.push_op(Op::SetNumberU8, &[register, n as u8]),
+            -255..=-1 => self.push_op(Op::SetNumberNegU8, &[register, n.unsigned_abs() as u8]),
+            _ => {
+                let constant = self.add_constant(Constant::I64(n), ast)?;
+                self.compile_constant_op(register, constant, Op::LoadInt);
+            }
+        }
+        Ok(())
+    }
+
+    fn add_constant(
+        &mut self,
+        constant: Constant,
+        ast: &Ast,
+    ) -> Result<ConstantIndex, CompilerError> {
+        self.added_constants
+            .add(ast, constant)
+            .map_err(|e| self.make_error(e))
+    }
+
     fn compile_load_string_constant(&mut self, result_register: u8, index: ConstantIndex) {
         self.compile_constant_op(result_register, index, Op::LoadString);
     }
@@ -1387,6 +1759,28 @@ impl Compiler {
         self.push_var_u32(id);
     }
 
+    fn push_string_format_options(&mut self, options: &StringFormatOptions) {
+        let flags = StringFormatFlags {
+            alignment: options.alignment,
+            fill_character: options.fill_character.is_some(),
+            min_width: options.min_width.is_some(),
+            precision: options.precision.is_some(),
+            plus_sign: options.plus_sign,
+            alternate_form: options.alternate_form,
+            zero_padding: options.zero_padding,
+            grouping: options.grouping,
+            representation: options.representation,
+        };
+        self.push_bytes(&flags.as_u16().to_le_bytes());
+
+        for n in [options.fill_character, options.min_width, options.precision]
+            .into_iter()
+            .flatten()
+        {
+            self.push_var_u32(n);
+        }
+    }
+
     fn push_var_u32(&mut self, mut n: u32) {
         loop {
             let mut byte = (n & 0x7f) as u8;
@@ -1456,11 +1850,15 @@ impl Compiler {
                 };
             }
         } else {
-            let from_register = self.push_register()?;
+            // When all of the imported items can be inlined, their values are loaded as constants
+            // rather than being accessed from the module. The module is still imported so that
+            // its main block and tests are run as usual.
+            let inlined_values = self.inlined_import_values(from, items, ast);
 
+            let from_register = self.push_register()?;
             self.compile_from(from_register, from, ast)?;
 
-            for item in items.iter() {
+            for (item_index, item) in items.iter().enumerate() {
                 match item {
                     ImportItemNode::Id(import_id) => {
                         let import_register = if result.is_some() {
@@ -1472,8 +1870,19 @@ impl Compiler {
                             self.assign_local_register(*import_id)?
                         };
 
-                        // Access the item from from_register
-                        self.compile_access_id(import_register, from_register, *import_id);
+                        match &inlined_values {
+                            Some(values) => {
+                                self.compile_constant_value(
+                                    ResultRegister::Fixed(import_register),
+                                    values[item_index].clone(),
+                                    ast,
+                                )?;
+                            }
+                            // Access the item from from_register
+                            None => {
+                                self.compile_access_id(import_register, from_register, *import_id)
+                            }
+                        }
 
                         if result.is_some() {
                             imported.push(import_register);
@@ -1520,6 +1929,41 @@ impl Compiler {
         Ok(result)
     }
 
+    // Returns the values of the imported items if they can all be inlined
+    //
+    // See CompilerSettings::enable_constant_inlining.
+    fn inlined_import_values(
+        &self,
+        from: &[ImportItemNode],
+        items: &[ImportItemNode],
+        ast: &Ast,
+    ) -> Option<Vec<ConstantValue>> {
+        if !self.settings.enable_constant_inlining {
+            return None;
+        }
+
+        let module_name = match from {
+            // Imports from local values can't be inlined
+            [ImportItemNode::Id(id)] if self.frame().get_local_assigned_register(*id).is_none() => {
+                ast.constants().get_str(*id)
+            }
+            [ImportItemNode::Str(string)] => match string.nodes.as_slice() {
+                [StringNode::Literal(literal)] => ast.constants().get_str(*literal),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        let constants = self.settings.imported_constants.get(module_name)?;
+        items
+            .iter()
+            .map(|item| match item {
+                ImportItemNode::Id(id) => constants.get(ast.constants().get_str(*id)).cloned(),
+                ImportItemNode::Str(_) => None,
+            })
+            .collect()
+    }
+
     fn compile_export(
         &mut self,
         result_register: ResultRegister,
@@ -1624,7 +2068,7 @@ impl Compiler {
         // The argument register for the catch block needs to be assigned now
         // so that it can be included in the TryStart op.
         let (catch_register, pop_catch_register) = match &ast.node(*catch_arg).node {
-            Node::Id(id) => (self.assign_local_register(*id)?, false),
+            Node::Id(id, ..) => (self.assign_local_register(*id)?, false),
             Node::Wildcard(_) => {
                 // The catch argument is being ignored, so just use a dummy register
                 (self.push_register()?, true)
@@ -1843,7 +2287,7 @@ impl Compiler {
             self.push_op(op, &[lhs.register, rhs.register]);
 
             // If the LHS is a top-level ID and the export flag is enabled, then export the result
-            if let Node::Id(id) = lhs_node.node {
+            if let Node::Id(id, ..) = lhs_node.node {
                 if self.settings.export_top_level_ids && self.frame_stack.len() == 1 {
                     self.compile_value_export(id, lhs.register)?;
                 }
@@ -2026,7 +2470,7 @@ impl Compiler {
                 StringNode::Literal(constant_index) => {
                     result + ast.constants().get_str(*constant_index).len()
                 }
-                StringNode::Expr(_) => {
+                StringNode::Expr(_) | StringNode::FormattedExpr(..) => {
                     // Q. Why use '1' here?
                     // A. The expression can result in a displayed string of any length,
                     //    We can make an assumption that the expression will almost always produce
@@ -2065,7 +2509,8 @@ impl Compiler {
                                 self.pop_register()?;
                             }
                         }
-                        StringNode::Expr(expression_node) => {
+                        StringNode::Expr(expression_node)
+                        | StringNode::FormattedExpr(expression_node, _) => {
                             if result.is_some() {
                                 let expression_result = self
                                     .compile_node(
@@ -2075,10 +2520,18 @@ impl Compiler {
                                     )?
                                     .unwrap();
 
-                                self.push_op_without_span(
-                                    Op::StringPush,
-                                    &[expression_result.register],
-                                );
+                                if let StringNode::FormattedExpr(_, options) = node {
+                                    self.push_op_without_span(
+                                        Op::StringPushFormat,
+                                        &[expression_result.register],
+                                    );
+                                    self.push_string_format_options(options);
+                                } else {
+                                    self.push_op_without_span(
+                                        Op::StringPush,
+                                        &[expression_result.register],
+                                    );
+                                }
 
                                 if expression_result.is_temporary {
                                     self.pop_register()?;
@@ -2356,12 +2809,15 @@ impl Compiler {
             };
 
             self.compile_frame(
-                local_count,
-                function_body,
-                &function.args,
-                &captures,
+                FrameParameters {
+                    local_count,
+                    expressions: function_body,
+                    args: &function.args,
+                    captures: &captures,
+                    output_type: function.output_type,
+                    allow_implicit_return,
+                },
                 ast,
-                allow_implicit_return,
             )?;
 
             self.update_offset_placeholder(function_size_ip)?;
@@ -2426,6 +2882,9 @@ impl Compiler {
             return self.error(ErrorKind::MissingNextLookupNode);
         }
 
+        // A call at the end of the chain can be a tail call if it's a plain call
+        let tail_call = self.take_tail_position() && rhs.is_none() && piped_arg_register.is_none();
+
         // If the result is going into a temporary register then assign it now as the first step.
         let result = self.get_result_register(result_register)?;
 
@@ -2453,6 +2912,9 @@ impl Compiler {
 
         let mut lookup_node = root_node.clone();
 
+        // The key of an access that's deferred until the following call, see Op::AccessCall
+        let mut access_call_key = None;
+
         while next_node_index.is_some() {
             match &lookup_node {
                 LookupNode::Root(root_node) => {
@@ -2479,7 +2941,14 @@ impl Compiler {
 
                     let node_register = self.push_register()?;
                     node_registers.push(node_register);
-                    self.compile_access_id(node_register, parent_register, *id);
+
+                    // Calls in tail position are compiled as tail calls rather than being fused
+                    let is_last_call = rhs.is_none() && piped_arg_register.is_none() && !tail_call;
+                    if self.call_can_be_fused(next_node_index, is_last_call, ast) {
+                        access_call_key = Some(*id);
+                    } else {
+                        self.compile_access_id(node_register, parent_register, *id);
+                    }
                 }
                 LookupNode::Str(ref lookup_string) => {
                     // Access by string
@@ -2536,14 +3005,27 @@ impl Compiler {
                     let call_result_register = self.push_register()?;
                     node_registers.push(call_result_register);
 
-                    self.compile_call(
-                        ResultRegister::Fixed(call_result_register),
-                        function_register,
-                        args,
-                        None,
-                        parent_register,
-                        ast,
-                    )?;
+                    match (access_call_key.take(), parent_register) {
+                        (Some(key), Some(instance)) => {
+                            self.compile_access_call(
+                                call_result_register,
+                                instance,
+                                key,
+                                args,
+                                ast,
+                            )?;
+                        }
+                        _ => {
+                            self.compile_call(
+                                ResultRegister::Fixed(call_result_register),
+                                function_register,
+                                args,
+                                None,
+                                parent_register,
+                                ast,
+                            )?;
+                        }
+                    }
                 }
             }
 
@@ -2647,14 +3129,25 @@ impl Compiler {
                         None => ResultRegister::None,
                     };
 
-                    self.compile_call(
-                        call_result_register,
-                        function_register,
-                        args,
-                        None,
-                        parent_register,
-                        ast,
-                    )?;
+                    match (access_call_key.take(), parent_register) {
+                        (Some(key), Some(instance)) => {
+                            // The result of the call isn't needed,
+                            // so it can be placed in the unused function register.
+                            let result = chain_result_register.unwrap_or(function_register);
+                            self.compile_access_call(result, instance, key, args, ast)?;
+                        }
+                        _ => {
+                            self.set_tail_position(tail_call);
+                            self.compile_call(
+                                call_result_register,
+                                function_register,
+                                args,
+                                None,
+                                parent_register,
+                                ast,
+                            )?;
+                        }
+                    }
                 }
             }
             _ => {}
@@ -2833,6 +3326,88 @@ impl Compiler {
         self.push_var_u32(key);
     }
 
+    // Returns true if the next node in a lookup chain is a call that can be fused with the
+    // preceding access, see Op::AccessCall
+    //
+    // The call's args are compiled before the function is accessed, so the args must be free of
+    // side effects. If the call is the last node in the chain, then it must be a plain call,
+    // without a piped arg or an assignment.
+    fn call_can_be_fused(
+        &self,
+        next_node_index: Option<AstIndex>,
+        is_last_call: bool,
+        ast: &Ast,
+    ) -> bool {
+        let Some(next_node) = next_node_index.map(|index| &ast.node(index).node) else {
+            return false;
+        };
+
+        match next_node {
+            Node::Lookup((LookupNode::Call { args, .. }, next)) => {
+                (next.is_some() || is_last_call)
+                    && args.len() < u8::MAX as usize
+                    && args
+                        .iter()
+                        .all(|arg| self.is_free_of_side_effects(&ast.node(*arg).node))
+            }
+            _ => false,
+        }
+    }
+
+    // Returns true if evaluating the node can't have any side effects
+    fn is_free_of_side_effects(&self, node: &Node) -> bool {
+        match node {
+            Node::Null
+            | Node::BoolTrue
+            | Node::BoolFalse
+            | Node::SmallInt(_)
+            | Node::Int(_)
+            | Node::Float(_) => true,
+            Node::Str(string) => string
+                .nodes
+                .iter()
+                .all(|node| matches!(node, StringNode::Literal(_))),
+            Node::Id(id, None) => self.frame().get_local_assigned_register(*id).is_some(),
+            _ => false,
+        }
+    }
+
+    // Compiles a call to a function that's accessed from an instance using a constant key
+    fn compile_access_call(
+        &mut self,
+        result_register: u8,
+        instance_register: u8,
+        key: ConstantIndex,
+        args: &[AstIndex],
+        ast: &Ast,
+    ) -> Result<(), CompilerError> {
+        let stack_count = self.frame().register_stack.len();
+
+        // The frame base is an empty register that may be used for the instance value,
+        // see compile_call.
+        let frame_base = self.push_register()?;
+
+        for arg in args.iter() {
+            let arg_register = self.push_register()?;
+            self.compile_node(ResultRegister::Fixed(arg_register), ast.node(*arg), ast)?;
+        }
+
+        self.push_op(
+            Op::AccessCall,
+            &[
+                result_register,
+                instance_register,
+                frame_base,
+                args.len() as u8,
+            ],
+        );
+        self.push_var_u32(key);
+
+        self.truncate_register_stack(stack_count)?;
+
+        Ok(())
+    }
+
     fn compile_access_string(
         &mut self,
         result_register: u8,
@@ -2882,7 +3457,7 @@ impl Compiler {
                 Some(piped_value.register),
                 ast,
             ),
-            Node::Id(id) => {
+            Node::Id(id, ..) => {
                 // Compile a call with the piped arg using the id to access the function
                 self.compile_named_call(result_register, *id, &[], Some(piped_value.register), ast)
             }
@@ -2979,6 +3554,7 @@ impl Compiler {
     ) -> CompileNodeResult {
         use Op::*;
 
+        let tail_call = self.take_tail_position();
         let result = self.get_result_register(result_register)?;
         let stack_count = self.frame().register_stack.len();
 
@@ -3011,7 +3587,11 @@ impl Compiler {
         match instance {
             Some(instance_register) => {
                 self.push_op(
-                    CallInstance,
+                    if tail_call {
+                        TailCallInstance
+                    } else {
+                        CallInstance
+                    },
                     &[
                         call_result_register,
                         function_register,
@@ -3023,7 +3603,7 @@ impl Compiler {
             }
             None => {
                 self.push_op(
-                    Call,
+                    if tail_call { TailCall } else { Call },
                     &[
                         call_result_register,
                         function_register,
@@ -3054,6 +3634,7 @@ impl Compiler {
             else_node,
         } = ast_if;
 
+        let tail_position = self.take_tail_position();
         let result = self.get_result_register(result_register)?;
         let expression_result_register = if let Some(result) = result {
             ResultRegister::Fixed(result.register)
@@ -3062,17 +3643,9 @@ impl Compiler {
         };
 
         // If
-        let condition_register = self
-            .compile_node(ResultRegister::Any, ast.node(*condition), ast)?
-            .unwrap();
-
-        self.push_op_without_span(JumpIfFalse, &[condition_register.register]);
-        let condition_jump_ip = self.push_offset_placeholder();
-
-        if condition_register.is_temporary {
-            self.pop_register()?;
-        }
+        let condition_jump_ip = self.compile_if_condition(*condition, ast)?;
 
+        self.set_tail_position(tail_position);
         self.compile_node(expression_result_register, ast.node(*then_node), ast)?;
 
         let if_jump_ip = {
@@ -3085,30 +3658,26 @@ impl Compiler {
         };
 
         // A failing condition for the if jumps to here, at the start of the else if / else blocks
-        self.update_offset_placeholder(condition_jump_ip)?;
+        if let Some(condition_jump_ip) = condition_jump_ip {
+            self.update_offset_placeholder(condition_jump_ip)?;
+        }
 
         // Iterate through the else if blocks and collect their end jump placeholders
         let else_if_jump_ips = else_if_blocks
             .iter()
             .map(
                 |(else_if_condition, else_if_node)| -> Result<usize, CompilerError> {
-                    let condition = self
-                        .compile_node(ResultRegister::Any, ast.node(*else_if_condition), ast)?
-                        .unwrap();
-
-                    self.push_op_without_span(JumpIfFalse, &[condition.register]);
-                    let conditon_jump_ip = self.push_offset_placeholder();
-
-                    if condition.is_temporary {
-                        self.pop_register()?;
-                    }
+                    let condition_jump_ip = self.compile_if_condition(*else_if_condition, ast)?;
 
+                    self.set_tail_position(tail_position);
                     self.compile_node(expression_result_register, ast.node(*else_if_node), ast)?;
 
                     self.push_op_without_span(Jump, &[]);
                     let else_if_jump_ip = self.push_offset_placeholder();
 
-                    self.update_offset_placeholder(conditon_jump_ip)?;
+                    if let Some(condition_jump_ip) = condition_jump_ip {
+                        self.update_offset_placeholder(condition_jump_ip)?;
+                    }
 
                     Ok(else_if_jump_ip)
                 },
@@ -3117,6 +3686,7 @@ impl Compiler {
 
         // Else - either compile the else block, or set the result to empty
         if let Some(else_node) = else_node {
+            self.set_tail_position(tail_position);
             self.compile_node(expression_result_register, ast.node(*else_node), ast)?;
         } else if let Some(result) = result {
             self.push_op_without_span(SetNull, &[result.register]);
@@ -3134,12 +3704,49 @@ impl Compiler {
         Ok(result)
     }
 
+    // Compiles the condition of an if or else if block, followed by a jump past the block when
+    // the condition fails
+    //
+    // Returns the ip of the jump's offset placeholder, or None if the condition is known to be true
+    // at compile time. Constant conditions are replaced with unconditional jumps, with the
+    // optimizer then removing the unreachable blocks.
+    fn compile_if_condition(
+        &mut self,
+        condition: AstIndex,
+        ast: &Ast,
+    ) -> Result<Option<usize>, CompilerError> {
+        let condition_node = ast.node(condition);
+
+        match self.fold_constant(condition_node, ast) {
+            Some(value) if value.is_truthy() => Ok(None),
+            Some(_) => {
+                self.push_op_without_span(Op::Jump, &[]);
+                Ok(Some(self.push_offset_placeholder()))
+            }
+            None => {
+                let condition_register = self
+                    .compile_node(ResultRegister::Any, condition_node, ast)?
+                    .unwrap();
+
+                self.push_op_without_span(Op::JumpIfFalse, &[condition_register.register]);
+                let condition_jump_ip = self.push_offset_placeholder();
+
+                if condition_register.is_temporary {
+                    self.pop_register()?;
+                }
+
+                Ok(Some(condition_jump_ip))
+            }
+        }
+    }
+
     fn compile_switch(
         &mut self,
         result_register: ResultRegister,
         arms: &[SwitchArm],
         ast: &Ast,
     ) -> CompileNodeResult {
+        let tail_position = self.take_tail_position();
         let result = self.get_result_register(result_register)?;
 
         let stack_count = self.frame().register_stack.len();
@@ -3169,6 +3776,7 @@ impl Compiler {
                 None
             };
 
+            self.set_tail_position(tail_position);
             self.compile_node(body_result_register, ast.node(arm.expression), ast)?;
 
             // Add a jump instruction if this anything other than an `else` arm
@@ -3206,6 +3814,7 @@ impl Compiler {
         arms: &[MatchArm],
         ast: &Ast,
     ) -> CompileNodeResult {
+        let tail_position = self.take_tail_position();
         let result = self.get_result_register(result_register)?;
 
         let stack_count = self.frame().register_stack.len();
@@ -3222,7 +3831,16 @@ impl Compiler {
         // Compile the match arms, collecting their jump offset placeholders
         let arm_jump_placeholders = arms
             .iter()
-            .map(|arm| self.compile_match_arm(result, match_register.register, match_len, arm, ast))
+            .map(|arm| {
+                self.compile_match_arm(
+                    result,
+                    match_register.register,
+                    match_len,
+                    arm,
+                    tail_position,
+                    ast,
+                )
+            })
             .collect::<Result<Vec<_>, _>>()?;
 
         // Set the result to Null in case there was no matching arm
@@ -3249,6 +3867,7 @@ impl Compiler {
         match_register: u8,
         match_len: usize,
         arm: &MatchArm,
+        tail_position: bool,
         ast: &Ast,
     ) -> Result<Option<usize>, CompilerError> {
         let mut jumps = MatchJumpPlaceholders::default();
@@ -3360,6 +3979,7 @@ impl Compiler {
             ResultRegister::None
         };
 
+        self.set_tail_position(tail_position);
         self.compile_node(body_result_register, ast.node(arm.expression), ast)?;
 
         // Jump to the end of the match expression, unless this is an `else` arm
@@ -3446,7 +4066,7 @@ impl Compiler {
                     self.pop_register()?; // comparison_register
                     self.pop_register()?; // pattern_register
                 }
-                Node::Id(id) => {
+                Node::Id(id, type_hint) => {
                     let id_register = self.assign_local_register(*id)?;
                     if match_is_container {
                         self.push_op(
@@ -3457,7 +4077,27 @@ impl Compiler {
                         self.push_op(Copy, &[id_register, params.match_register]);
                     }
 
-                    if is_last_pattern && !params.is_last_alternative {
+                    if let Some(type_hint) = type_hint {
+                        // e.g. `x: String then ...`
+                        let type_node = ast.node(*type_hint);
+                        let Node::Type(type_index) = &type_node.node else {
+                            return self.error(ErrorKind::UnexpectedNode {
+                                expected: "Type".into(),
+                                unexpected: type_node.node.clone(),
+                            });
+                        };
+                        let matches_type = self.push_register()?;
+                        self.push_op(MatchesType, &[matches_type, id_register]);
+                        self.push_var_u32(*type_index);
+                        self.push_match_failure_jump(
+                            matches_type,
+                            params.is_last_alternative,
+                            params.jumps,
+                        );
+                        self.pop_register()?; // matches_type
+                    }
+
+                    if params.has_last_pattern && is_last_pattern && !params.is_last_alternative {
                         // Ids match unconditionally, so if we're at the end of a
                         // multi-expression pattern, skip over the remaining alternatives
                         self.push_op(Jump, &[]);
@@ -3465,7 +4105,7 @@ impl Compiler {
                     }
                 }
                 Node::Wildcard(_) => {
-                    if is_last_pattern && !params.is_last_alternative {
+                    if params.has_last_pattern && is_last_pattern && !params.is_last_alternative {
                         // Wildcards match unconditionally, so if we're at the end of a
                         // multi-expression pattern, skip over the remaining alternatives
                         // e.g. x, 0, _ or x, 1, y if foo x then
@@ -3484,15 +4124,29 @@ impl Compiler {
                         MatchArmParameters {
                             match_register: params.match_register,
                             is_last_alternative: params.is_last_alternative,
-                            has_last_pattern: params.has_last_pattern,
+                            has_last_pattern: params.has_last_pattern && is_last_pattern,
                             jumps: params.jumps,
                         },
-                        Some(pattern_index),
+                        match_is_container.then_some(pattern_index),
                         patterns,
                         type_check_op,
                         ast,
                     )?;
                 }
+                Node::MapPattern { entries, rest } => {
+                    self.compile_map_match_pattern(
+                        MatchArmParameters {
+                            match_register: params.match_register,
+                            is_last_alternative: params.is_last_alternative,
+                            has_last_pattern: params.has_last_pattern && is_last_pattern,
+                            jumps: params.jumps,
+                        },
+                        match_is_container.then_some(pattern_index),
+                        entries,
+                        *rest,
+                        ast,
+                    )?;
+                }
                 Node::Ellipsis(maybe_id) => {
                     if is_last_pattern {
                         if let Some(id) = maybe_id {
@@ -3506,7 +4160,7 @@ impl Compiler {
                             );
                         }
 
-                        if !params.is_last_alternative {
+                        if params.has_last_pattern && !params.is_last_alternative {
                             // Ellipses match unconditionally in last position,
                             // multi-expression pattern, skip over the remaining alternatives
                             // e.g. (x, 0, rest...) or (x, 1, y) if rest.size() > 0 then
@@ -3538,6 +4192,268 @@ impl Compiler {
         Ok(())
     }
 
+    fn compile_map_match_pattern(
+        &mut self,
+        params: MatchArmParameters,
+        pattern_index: Option<i8>,
+        entries: &[MapPatternEntry],
+        rest: Option<AstIndex>,
+        ast: &Ast,
+    ) -> Result<(), CompilerError> {
+        use Op::*;
+
+        let value_register = if let Some(pattern_index) = pattern_index {
+            // Place the nested map into a register
+            let value_register = self.push_register()?;
+            self.push_op(
+                TempIndex,
+                &[value_register, params.match_register, pattern_index as u8],
+            );
+            value_register
+        } else {
+            params.match_register
+        };
+
+        let temp_register = self.push_register()?;
+
+        // Check that the value is a map
+        self.push_op(IsMap, &[temp_register, value_register]);
+        self.push_match_failure_jump(temp_register, params.is_last_alternative, params.jumps);
+
+        self.pop_register()?; // temp_register
+
+        let keys = self.map_pattern_keys(entries)?;
+        let mut last_pattern_is_nested = false;
+
+        for (entry_index, (entry, key)) in entries.iter().zip(keys.iter()).enumerate() {
+            let is_last_entry = rest.is_none() && entry_index == entries.len() - 1;
+
+            match entry.pattern {
+                Some(pattern) => {
+                    // e.g. `{x: 0}`
+                    let entry_register = self.push_register()?;
+                    self.push_map_pattern_entry_lookup(
+                        entry_register,
+                        value_register,
+                        *key,
+                        params.is_last_alternative,
+                        params.jumps,
+                    );
+                    self.compile_match_arm_patterns(
+                        MatchArmParameters {
+                            match_register: entry_register,
+                            is_last_alternative: params.is_last_alternative,
+                            has_last_pattern: params.has_last_pattern && is_last_entry,
+                            jumps: params.jumps,
+                        },
+                        false, // match_is_container
+                        &[pattern],
+                        ast,
+                    )?;
+                    self.pop_register()?; // entry_register
+                    last_pattern_is_nested = is_last_entry;
+                }
+                None => {
+                    // e.g. `{x}`
+                    let id_register = self.assign_local_register(*key)?;
+                    self.push_map_pattern_entry_lookup(
+                        id_register,
+                        value_register,
+                        *key,
+                        params.is_last_alternative,
+                        params.jumps,
+                    );
+                }
+            }
+        }
+
+        if let Some(rest) = rest {
+            self.compile_map_pattern_rest(value_register, rest, &keys, ast)?;
+        }
+
+        if params.has_last_pattern && !last_pattern_is_nested && !params.is_last_alternative {
+            // The map's entries have been matched, so skip over the remaining alternatives
+            self.push_op(Jump, &[]);
+            params.jumps.match_end.push(self.push_offset_placeholder());
+        }
+
+        if pattern_index.is_some() {
+            self.pop_register()?; // value_register
+        }
+
+        Ok(())
+    }
+
+    // Assigns the entries of a map to the targets in a map pattern
+    //
+    // e.g. `{x, y: {z}, w = 0, rest...} = foo`
+    fn compile_unpack_map(
+        &mut self,
+        map_register: u8,
+        entries: &[MapPatternEntry],
+        rest: Option<AstIndex>,
+        export_assignment: bool,
+        ast: &Ast,
+    ) -> Result<(), CompilerError> {
+        use Op::*;
+
+        let keys = self.map_pattern_keys(entries)?;
+        let export_assignment = export_assignment || self.force_export_assignment();
+
+        for (entry, key) in entries.iter().zip(keys.iter()) {
+            let pattern_node = entry.pattern.map(|pattern| &ast.node(pattern).node);
+
+            let (target_register, target_id) = match pattern_node {
+                // e.g. `{x}`
+                None => (self.assign_local_register(*key)?, Some(*key)),
+                // e.g. `{x: y}`
+                Some(Node::Id(id, ..)) => (self.assign_local_register(*id)?, Some(*id)),
+                // e.g. `{x: _}` or `{x: {y}}`, the entry still needs to be present in the map
+                Some(Node::Wildcard(_) | Node::MapPattern { .. }) => (self.push_register()?, None),
+                Some(unexpected) => {
+                    return self.error(ErrorKind::UnexpectedNode {
+                        expected: "ID, Wildcard, or MapPattern".into(),
+                        unexpected: unexpected.clone(),
+                    })
+                }
+            };
+
+            if let Some(default) = entry.default {
+                // e.g. `{x = 0}`
+                // The default is only evaluated when the key is missing
+                self.push_op(AccessKeyOrJump, &[target_register, map_register]);
+                self.push_var_u32(*key);
+                let default_jump = self.push_offset_placeholder();
+                self.push_op(Jump, &[]);
+                let end_jump = self.push_offset_placeholder();
+                self.update_offset_placeholder(default_jump)?;
+                self.compile_node(
+                    ResultRegister::Fixed(target_register),
+                    ast.node(default),
+                    ast,
+                )?;
+                self.update_offset_placeholder(end_jump)?;
+            } else {
+                self.push_op(AccessKey, &[target_register, map_register]);
+                self.push_var_u32(*key);
+            }
+
+            match pattern_node {
+                Some(Node::MapPattern { entries, rest }) => {
+                    self.compile_unpack_map(
+                        target_register,
+                        entries,
+                        *rest,
+                        export_assignment,
+                        ast,
+                    )?;
+                    self.pop_register()?; // target_register
+                }
+                Some(Node::Wildcard(_)) => {
+                    self.pop_register()?; // target_register
+                }
+                _ => {
+                    if let (Some(id), true) = (target_id, export_assignment) {
+                        self.compile_value_export(id, target_register)?;
+                    }
+                }
+            }
+        }
+
+        if let Some(rest) = rest {
+            self.compile_map_pattern_rest(map_register, rest, &keys, ast)?;
+
+            if let (Node::Ellipsis(Some(id)), true) = (&ast.node(rest).node, export_assignment) {
+                let id_register = self.assign_local_register(*id)?;
+                self.compile_value_export(*id, id_register)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns the constant indices of the keys in a map pattern
+    fn map_pattern_keys(
+        &self,
+        entries: &[MapPatternEntry],
+    ) -> Result<Vec<ConstantIndex>, CompilerError> {
+        entries
+            .iter()
+            .map(|entry| match &entry.key {
+                MapKey::Id(id) => Ok(*id),
+                MapKey::Str(AstString { nodes, .. }) => match nodes.as_slice() {
+                    [StringNode::Literal(constant)] => Ok(*constant),
+                    _ => self.error(ErrorKind::InvalidMapPatternKey),
+                },
+                MapKey::Meta(..) => self.error(ErrorKind::InvalidMapPatternKey),
+            })
+            .collect()
+    }
+
+    // Assigns the remaining entries of a map to the id in a map pattern's rest, e.g. `{x, rest...}`
+    fn compile_map_pattern_rest(
+        &mut self,
+        map_register: u8,
+        rest: AstIndex,
+        keys: &[ConstantIndex],
+        ast: &Ast,
+    ) -> Result<(), CompilerError> {
+        if let Node::Ellipsis(Some(id)) = &ast.node(rest).node {
+            // The remaining entries are copied into a new map, with the pattern's keys removed
+            let stack_count = self.frame().register_stack.len();
+            let keys_start = self.frame().next_temporary_register();
+            for key in keys.iter() {
+                let key_register = self.push_register()?;
+                self.compile_constant_op(key_register, *key, Op::LoadString);
+            }
+            let id_register = self.assign_local_register(*id)?;
+            self.push_op(
+                Op::MapRest,
+                &[id_register, map_register, keys_start, keys.len() as u8],
+            );
+            self.truncate_register_stack(stack_count)?;
+        }
+
+        Ok(())
+    }
+
+    // Looks up an entry in a map that's being matched against a map pattern
+    //
+    // Entries are looked up in the same way as when destructuring a map, with a missing entry
+    // causing a jump to the next match alternative, or to the end of the arm.
+    fn push_map_pattern_entry_lookup(
+        &mut self,
+        result: u8,
+        map: u8,
+        key: ConstantIndex,
+        is_last_alternative: bool,
+        jumps: &mut MatchJumpPlaceholders,
+    ) {
+        self.push_op(Op::AccessKeyOrJump, &[result, map]);
+        self.push_var_u32(key);
+        if is_last_alternative {
+            jumps.arm_end.push(self.push_offset_placeholder());
+        } else {
+            jumps.alternative_end.push(self.push_offset_placeholder());
+        }
+    }
+
+    // Pushes a jump to the next match alternative, or to the end of the arm, when the condition
+    // is false
+    fn push_match_failure_jump(
+        &mut self,
+        condition: u8,
+        is_last_alternative: bool,
+        jumps: &mut MatchJumpPlaceholders,
+    ) {
+        self.push_op(Op::JumpIfFalse, &[condition]);
+        if is_last_alternative {
+            jumps.arm_end.push(self.push_offset_placeholder());
+        } else {
+            jumps.alternative_end.push(self.push_offset_placeholder());
+        }
+    }
+
     fn compile_nested_match_arm_patterns(
         &mut self,
         params: MatchArmParameters,
@@ -3707,7 +4623,7 @@ impl Compiler {
             [] => return self.error(ErrorKind::MissingArgumentInForLoop),
             [single_arg] => {
                 match &ast.node(*single_arg).node {
-                    Node::Id(id) => {
+                    Node::Id(id, ..) => {
                         // e.g. for i in 0..10
                         let arg_register = self.assign_local_register(*id)?;
                         self.push_op_without_span(IterNext, &[arg_register, iterator_register]);
@@ -3741,7 +4657,7 @@ impl Compiler {
 
                 for arg in args.iter() {
                     match &ast.node(*arg).node {
-                        Node::Id(id) => {
+                        Node::Id(id, ..) => {
                             let arg_register = self.assign_local_register(*id)?;
                             self.push_op_without_span(IterUnpack, &[arg_register, temp_register]);
                         }
@@ -3784,7 +4700,7 @@ impl Compiler {
 
         if self.settings.export_top_level_ids && self.frame_stack.len() == 1 {
             for arg in args {
-                if let Node::Id(id) = &ast.node(*arg).node {
+                if let Node::Id(id, ..) = &ast.node(*arg).node {
                     let arg_register = match self.frame().get_local_assigned_register(*id) {
                         Some(register) => register,
                         None => return self.error(ErrorKind::MissingArgRegister),
@@ -3946,6 +4862,28 @@ impl Compiler {
         self.frame_stack.last_mut().expect("Frame stack is empty")
     }
 
+    // Takes the frame's tail position flag, see Frame::tail_position
+    fn take_tail_position(&mut self) -> bool {
+        self.frame_stack
+            .last_mut()
+            .is_some_and(|frame| std::mem::take(&mut frame.tail_position))
+    }
+
+    fn set_tail_position(&mut self, tail_position: bool) {
+        if let Some(frame) = self.frame_stack.last_mut() {
+            frame.tail_position = tail_position;
+        }
+    }
+
+    // Tail calls are only made from functions, and aren't made when the function's output needs
+    // to be checked after the call.
+    fn tail_calls_allowed(&self) -> bool {
+        let frame = self.frame();
+        self.frame_stack.len() > 1
+            && !frame.is_generator
+            && !(self.settings.enable_type_checks && frame.output_type.is_some())
+    }
+
     fn push_register(&mut self) -> Result<u8, CompilerError> {
         self.frame_mut()
             .push_register()
@@ -3971,15 +4909,19 @@ impl Compiler {
     }
 
     fn assign_local_register(&mut self, local: ConstantIndex) -> Result<u8, CompilerError> {
-        self.frame_mut()
-            .assign_local_register(local)
-            .map_err(|e| self.make_error(e))
+        let ip = self.bytes.len() as u32;
+        let frame = self.frame_mut();
+        let result = frame.assign_local_register(local);
+        frame.record_local_start_ips(ip);
+        result.map_err(|e| self.make_error(e))
     }
 
     fn reserve_local_register(&mut self, local: ConstantIndex) -> Result<u8, CompilerError> {
-        self.frame_mut()
-            .reserve_local_register(local)
-            .map_err(|e| self.make_error(e))
+        let ip = self.bytes.len() as u32;
+        let frame = self.frame_mut();
+        let result = frame.reserve_local_register(local);
+        frame.record_local_start_ips(ip);
+        result.map_err(|e| self.make_error(e))
     }
 
     fn commit_local_register(&mut self, register: u8) -> Result<u8, CompilerError> {
@@ -4036,6 +4978,16 @@ struct MatchJumpPlaceholders {
     alternative_end: Vec<usize>,
 }
 
+struct FrameParameters<'a> {
+    local_count: u8,
+    expressions: &'a [AstIndex],
+    args: &'a [AstIndex],
+    captures: &'a [ConstantIndex],
+    // The frame's output type hint, checked when type checks are enabled
+    output_type: Option<AstIndex>,
+    allow_implicit_return: bool,
+}
+
 struct MatchArmParameters<'a> {
     match_register: u8,
     is_last_alternative: bool,
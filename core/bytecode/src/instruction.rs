@@ -1,6 +1,6 @@
 use std::fmt;
 
-use koto_parser::MetaKeyId;
+use koto_parser::{MetaKeyId, StringAlignment, StringFormatOptions, StringRepresentation};
 
 /// Decoded instructions produced by an [InstructionReader](crate::InstructionReader) for execution
 /// in the runtime
@@ -273,6 +273,10 @@ pub enum Instruction {
         register: u8,
         value: u8,
     },
+    IsMap {
+        register: u8,
+        value: u8,
+    },
     Index {
         register: u8,
         value: u8,
@@ -288,6 +292,19 @@ pub enum Instruction {
         key: u8,
         value: u8,
     },
+    MapRest {
+        register: u8,
+        map: u8,
+        start: u8,
+        count: u8,
+    },
+    AccessKey {
+        register: u8,
+        map: u8,
+        key: u32,
+        // If present, the offset to jump by when the key is missing
+        jump_offset: Option<u16>,
+    },
     MetaInsert {
         register: u8,
         value: u8,
@@ -318,6 +335,26 @@ pub enum Instruction {
         value: u8,
         key: u8,
     },
+    AccessCall {
+        result: u8,
+        instance: u8,
+        frame_base: u8,
+        arg_count: u8,
+        key: u32,
+    },
+    TailCall {
+        result: u8,
+        function: u8,
+        frame_base: u8,
+        arg_count: u8,
+    },
+    TailCallInstance {
+        result: u8,
+        function: u8,
+        frame_base: u8,
+        arg_count: u8,
+        instance: u8,
+    },
     TryStart {
         arg_register: u8,
         catch_offset: u16,
@@ -339,12 +376,25 @@ pub enum Instruction {
         register: u8,
         size: usize,
     },
+    AssertType {
+        register: u8,
+        type_constant: u32,
+    },
+    MatchesType {
+        register: u8,
+        value: u8,
+        type_constant: u32,
+    },
     StringStart {
         size_hint: u32,
     },
     StringPush {
         value: u8,
     },
+    StringPushFormat {
+        value: u8,
+        options: StringFormatOptions,
+    },
     StringFinish {
         register: u8,
     },
@@ -644,6 +694,9 @@ impl fmt::Debug for Instruction {
             IsList { register, value } => {
                 write!(f, "IsList\t\tresult: {register}\tvalue: {value}")
             }
+            IsMap { register, value } => {
+                write!(f, "IsMap\t\tresult: {register}\tvalue: {value}")
+            }
             Index {
                 register,
                 value,
@@ -668,6 +721,25 @@ impl fmt::Debug for Instruction {
                 f,
                 "MapInsert\tmap: {register}\t\tvalue: {value}\tkey: {key}"
             ),
+            MapRest {
+                register,
+                map,
+                start,
+                count,
+            } => write!(
+                f,
+                "MapRest\t\tresult: {register}\tmap: {map}\tstart: {start}\tcount: {count}"
+            ),
+            AccessKey {
+                register,
+                map,
+                key,
+                jump_offset,
+            } => write!(
+                f,
+                "AccessKey\tresult: {register}\tmap: {map}\tkey: {key}{}",
+                jump_offset.map_or(String::new(), |offset| format!("\tjump: {offset}")),
+            ),
             MetaInsert {
                 register,
                 value,
@@ -706,6 +778,38 @@ impl fmt::Debug for Instruction {
                 f,
                 "AccessString\tresult: {register}\tvalue: {value}\tkey: {key}"
             ),
+            AccessCall {
+                result,
+                instance,
+                frame_base,
+                arg_count,
+                key,
+            } => write!(
+                f,
+                "AccessCall\tresult: {result}\tinstance: {instance}\tframe_base: {frame_base}
+                 \t\t\targs: {arg_count}\t\tkey: {key}",
+            ),
+            TailCall {
+                result,
+                function,
+                frame_base,
+                arg_count,
+            } => write!(
+                f,
+                "TailCall\tresult: {result}\tfunction: {function}\t\
+                 frame base: {frame_base}\targs: {arg_count}",
+            ),
+            TailCallInstance {
+                result,
+                function,
+                frame_base,
+                arg_count,
+                instance,
+            } => write!(
+                f,
+                "TailCallInstance\tresult: {result}\tfunction: {function}\tframe_base: {frame_base}
+                 \t\t\targs: {arg_count}\t\tinstance: {instance}",
+            ),
             TryStart {
                 arg_register,
                 catch_offset,
@@ -726,15 +830,170 @@ impl fmt::Debug for Instruction {
             CheckSizeMin { register, size } => {
                 write!(f, "CheckSizeMin\tregister: {register}\tsize: {size}")
             }
+            AssertType {
+                register,
+                type_constant,
+            } => write!(f, "AssertType\tregister: {register}\ttype: {type_constant}"),
+            MatchesType {
+                register,
+                value,
+                type_constant,
+            } => write!(
+                f,
+                "MatchesType\tresult: {register}\tvalue: {value}\ttype: {type_constant}"
+            ),
             StringStart { size_hint } => {
                 write!(f, "StringStart\tsize hint: {size_hint}")
             }
             StringPush { value } => {
                 write!(f, "StringPush\tvalue: {value}")
             }
+            StringPushFormat { value, options } => {
+                write!(f, "StringPushFormat\tvalue: {value}\toptions: {options:?}")
+            }
             StringFinish { register } => {
                 write!(f, "StringFinish\tregister: {register}")
             }
         }
     }
 }
+
+/// Flags used to define the formatting options for a
+/// [StringPushFormat](crate::Op::StringPushFormat) instruction
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StringFormatFlags {
+    /// The alignment of the formatted value
+    pub alignment: StringAlignment,
+    /// True if a fill character constant follows the flags
+    pub fill_character: bool,
+    /// True if a minimum width follows the flags
+    pub min_width: bool,
+    /// True if a precision follows the flags
+    pub precision: bool,
+    /// Corresponding to [StringFormatOptions::plus_sign]
+    pub plus_sign: bool,
+    /// Corresponding to [StringFormatOptions::alternate_form]
+    pub alternate_form: bool,
+    /// Corresponding to [StringFormatOptions::zero_padding]
+    pub zero_padding: bool,
+    /// Corresponding to [StringFormatOptions::grouping]
+    pub grouping: Option<char>,
+    /// Corresponding to [StringFormatOptions::representation]
+    pub representation: StringRepresentation,
+}
+
+impl StringFormatFlags {
+    /// The bits used to store the alignment
+    pub const ALIGNMENT_MASK: u16 = 0b11;
+    /// Corresponding to [StringAlignment::Left]
+    pub const ALIGN_LEFT: u16 = 1;
+    /// Corresponding to [StringAlignment::Center]
+    pub const ALIGN_CENTER: u16 = 2;
+    /// Corresponding to [StringAlignment::Right]
+    pub const ALIGN_RIGHT: u16 = 3;
+    /// Corresponding to [StringFormatFlags::fill_character]
+    pub const FILL_CHARACTER: u16 = 1 << 2;
+    /// Corresponding to [StringFormatFlags::min_width]
+    pub const MIN_WIDTH: u16 = 1 << 3;
+    /// Corresponding to [StringFormatFlags::precision]
+    pub const PRECISION: u16 = 1 << 4;
+    /// Corresponding to [StringFormatFlags::plus_sign]
+    pub const PLUS_SIGN: u16 = 1 << 5;
+    /// Corresponding to [StringFormatFlags::alternate_form]
+    pub const ALTERNATE_FORM: u16 = 1 << 6;
+    /// Corresponding to [StringFormatFlags::zero_padding]
+    pub const ZERO_PADDING: u16 = 1 << 7;
+    /// Corresponding to a `,` separator in [StringFormatFlags::grouping]
+    pub const GROUPING_COMMA: u16 = 1 << 8;
+    /// Corresponding to a `_` separator in [StringFormatFlags::grouping]
+    pub const GROUPING_UNDERSCORE: u16 = 1 << 9;
+    /// The offset of the bits used to store the representation
+    pub const REPRESENTATION_SHIFT: u16 = 10;
+    /// The bits used to store the representation, after shifting
+    pub const REPRESENTATION_MASK: u16 = 0b111;
+
+    /// Initializes a flags struct from a u16
+    pub fn from_u16(flags: u16) -> Self {
+        let alignment = match flags & Self::ALIGNMENT_MASK {
+            Self::ALIGN_LEFT => StringAlignment::Left,
+            Self::ALIGN_CENTER => StringAlignment::Center,
+            Self::ALIGN_RIGHT => StringAlignment::Right,
+            _ => StringAlignment::Default,
+        };
+
+        let grouping = if flags & Self::GROUPING_COMMA != 0 {
+            Some(',')
+        } else if flags & Self::GROUPING_UNDERSCORE != 0 {
+            Some('_')
+        } else {
+            None
+        };
+
+        use StringRepresentation::*;
+        let representation = match (flags >> Self::REPRESENTATION_SHIFT) & Self::REPRESENTATION_MASK
+        {
+            1 => Hex,
+            2 => HexUpper,
+            3 => Binary,
+            4 => Octal,
+            5 => Exponent,
+            6 => ExponentUpper,
+            _ => Default,
+        };
+
+        Self {
+            alignment,
+            fill_character: flags & Self::FILL_CHARACTER != 0,
+            min_width: flags & Self::MIN_WIDTH != 0,
+            precision: flags & Self::PRECISION != 0,
+            plus_sign: flags & Self::PLUS_SIGN != 0,
+            alternate_form: flags & Self::ALTERNATE_FORM != 0,
+            zero_padding: flags & Self::ZERO_PADDING != 0,
+            grouping,
+            representation,
+        }
+    }
+
+    /// Returns a u16 containing the packed flags
+    pub fn as_u16(&self) -> u16 {
+        let mut result = match self.alignment {
+            StringAlignment::Default => 0,
+            StringAlignment::Left => Self::ALIGN_LEFT,
+            StringAlignment::Center => Self::ALIGN_CENTER,
+            StringAlignment::Right => Self::ALIGN_RIGHT,
+        };
+        if self.fill_character {
+            result |= Self::FILL_CHARACTER;
+        }
+        if self.min_width {
+            result |= Self::MIN_WIDTH;
+        }
+        if self.precision {
+            result |= Self::PRECISION;
+        }
+        if self.plus_sign {
+            result |= Self::PLUS_SIGN;
+        }
+        if self.alternate_form {
+            result |= Self::ALTERNATE_FORM;
+        }
+        if self.zero_padding {
+            result |= Self::ZERO_PADDING;
+        }
+        match self.grouping {
+            Some(',') => result |= Self::GROUPING_COMMA,
+            Some('_') => result |= Self::GROUPING_UNDERSCORE,
+            _ => {}
+        }
+        let representation = match self.representation {
+            StringRepresentation::Default => 0,
+            StringRepresentation::Hex => 1,
+            StringRepresentation::HexUpper => 2,
+            StringRepresentation::Binary => 3,
+            StringRepresentation::Octal => 4,
+            StringRepresentation::Exponent => 5,
+            StringRepresentation::ExponentUpper => 6,
+        };
+        result | (representation << Self::REPRESENTATION_SHIFT)
+    }
+}
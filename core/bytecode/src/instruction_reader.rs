@@ -1,5 +1,6 @@
-use crate::{Chunk, FunctionFlags, Instruction, Op, TypeId};
+use crate::{Chunk, FunctionFlags, Instruction, Op, StringFormatFlags, TypeId};
 use koto_memory::Ptr;
+use koto_parser::StringFormatOptions;
 
 /// An iterator that converts bytecode into a series of [Instruction]s
 #[derive(Clone, Default)]
@@ -388,6 +389,33 @@ impl Iterator for InstructionReader {
                 register: get_u8!(),
                 value: get_u8!(),
             }),
+            Op::IsMap => Some(IsMap {
+                register: get_u8!(),
+                value: get_u8!(),
+            }),
+            Op::MatchesType => Some(MatchesType {
+                register: get_u8!(),
+                value: get_u8!(),
+                type_constant: get_var_u32!(),
+            }),
+            Op::MapRest => Some(MapRest {
+                register: get_u8!(),
+                map: get_u8!(),
+                start: get_u8!(),
+                count: get_u8!(),
+            }),
+            Op::AccessKey => Some(AccessKey {
+                register: get_u8!(),
+                map: get_u8!(),
+                key: get_var_u32!(),
+                jump_offset: None,
+            }),
+            Op::AccessKeyOrJump => Some(AccessKey {
+                register: get_u8!(),
+                map: get_u8!(),
+                key: get_var_u32!(),
+                jump_offset: Some(get_u16!()),
+            }),
             Op::Index => Some(Index {
                 register: get_u8!(),
                 value: get_u8!(),
@@ -478,6 +506,26 @@ impl Iterator for InstructionReader {
                 value: get_u8!(),
                 key: get_u8!(),
             }),
+            Op::AccessCall => Some(AccessCall {
+                result: get_u8!(),
+                instance: get_u8!(),
+                frame_base: get_u8!(),
+                arg_count: get_u8!(),
+                key: get_var_u32!(),
+            }),
+            Op::TailCall => Some(TailCall {
+                result: get_u8!(),
+                function: get_u8!(),
+                frame_base: get_u8!(),
+                arg_count: get_u8!(),
+            }),
+            Op::TailCallInstance => Some(TailCallInstance {
+                result: get_u8!(),
+                function: get_u8!(),
+                frame_base: get_u8!(),
+                arg_count: get_u8!(),
+                instance: get_u8!(),
+            }),
             Op::TryStart => Some(TryStart {
                 arg_register: get_u8!(),
                 catch_offset: get_u16!(),
@@ -504,10 +552,37 @@ impl Iterator for InstructionReader {
                 register: get_u8!(),
                 size: get_u8!() as usize,
             }),
+            Op::AssertType => Some(AssertType {
+                register: get_u8!(),
+                type_constant: get_var_u32!(),
+            }),
             Op::StringStart => Some(StringStart {
                 size_hint: get_var_u32!(),
             }),
             Op::StringPush => Some(StringPush { value: get_u8!() }),
+            Op::StringPushFormat => {
+                let value = get_u8!();
+                let flags = StringFormatFlags::from_u16(get_u16!());
+                let mut options = StringFormatOptions {
+                    alignment: flags.alignment,
+                    plus_sign: flags.plus_sign,
+                    alternate_form: flags.alternate_form,
+                    zero_padding: flags.zero_padding,
+                    grouping: flags.grouping,
+                    representation: flags.representation,
+                    ..Default::default()
+                };
+                if flags.fill_character {
+                    options.fill_character = Some(get_var_u32!());
+                }
+                if flags.min_width {
+                    options.min_width = Some(get_var_u32!());
+                }
+                if flags.precision {
+                    options.precision = Some(get_var_u32!());
+                }
+                Some(StringPushFormat { value, options })
+            }
             Op::StringFinish => Some(StringFinish {
                 register: get_u8!(),
             }),
@@ -8,13 +8,21 @@ mod compiler;
 mod instruction;
 mod instruction_reader;
 mod loader;
+mod lookup_cache;
 mod op;
+mod optimizer;
+mod serialization;
+mod verifier;
 
 pub use crate::{
-    chunk::{Chunk, DebugInfo},
-    compiler::{Compiler, CompilerError, CompilerSettings},
-    instruction::{FunctionFlags, Instruction, TypeId},
+    chunk::{Chunk, DebugInfo, ExportedConstants, ImportedConstants, LocalName, LocalScope},
+    compiler::{Compiler, CompilerError, CompilerOutput, CompilerSettings},
+    instruction::{FunctionFlags, Instruction, StringFormatFlags, TypeId},
     instruction_reader::InstructionReader,
-    loader::{Loader, LoaderError},
+    loader::{ImportPolicy, Loader, LoaderError},
+    lookup_cache::{CachedLookup, CachedLookupEntry, CachedLookupStep, LookupCache},
     op::Op,
+    optimizer::ConstantValue,
+    serialization::{DeserializeError, SerializeSettings, BYTECODE_FORMAT_VERSION},
+    verifier::{VerifyError, VerifyErrorKind},
 };
@@ -1,9 +1,20 @@
-use crate::{Chunk, Compiler, CompilerError, CompilerSettings};
+use crate::{
+    serialization::COMPILER_VERSION, Chunk, Compiler, CompilerError, CompilerSettings,
+    DeserializeError, ExportedConstants, ImportedConstants, VerifyError,
+};
 use dunce::canonicalize;
 use koto_memory::Ptr;
-use koto_parser::{format_source_excerpt, Parser, ParserError, Span};
+use koto_parser::{
+    format_source_excerpt, Ast, ImportItemNode, Node, Parser, ParserError, Span, StringNode,
+};
 use rustc_hash::FxHasher;
-use std::{collections::HashMap, error, fmt, hash::BuildHasherDefault, io, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    error, fmt, fs,
+    hash::{BuildHasherDefault, Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+};
 use thiserror::Error;
 
 /// Errors that can be returned from [Loader] operations
@@ -12,14 +23,24 @@ use thiserror::Error;
 pub enum LoaderErrorKind {
     #[error("{0}")]
     Parser(#[from] ParserError),
+    #[error("{} errors were found while parsing", .0.len())]
+    ParserErrors(Vec<ParserError>),
     #[error("{0}")]
     Compiler(#[from] CompilerError),
     #[error(transparent)]
     Io(#[from] io::Error),
+    #[error("{0}")]
+    Deserialize(#[from] DeserializeError),
+    #[error("{0}")]
+    Verify(#[from] VerifyError),
     #[error("Failed to get parent of path ('{0}')")]
     FailedToGetPathParent(PathBuf),
     #[error("Unable to find module '{0}'")]
     UnableToFindModule(String),
+    #[error("Permission denied, importing modules is disabled")]
+    ImportsDisabled,
+    #[error("Permission denied, '{0}' is outside of the allowed directory")]
+    ImportOutsideOfRoot(PathBuf),
 }
 
 /// The error type used by the [Loader]
@@ -53,6 +74,22 @@ impl LoaderError {
         }
     }
 
+    pub(crate) fn from_parser_errors(
+        errors: Vec<ParserError>,
+        source: &str,
+        source_path: Option<PathBuf>,
+    ) -> Self {
+        let source = LoaderErrorSource {
+            contents: source.into(),
+            span: errors.first().map(|error| error.span).unwrap_or_default(),
+            path: source_path,
+        };
+        Self {
+            error: Box::new(LoaderErrorKind::ParserErrors(errors)),
+            source: Some(Box::new(source)),
+        }
+    }
+
     pub(crate) fn from_compiler_error(
         error: CompilerError,
         source: &str,
@@ -69,6 +106,18 @@ impl LoaderError {
         }
     }
 
+    /// Returns the kind of error that occurred
+    pub fn kind(&self) -> &LoaderErrorKind {
+        &self.error
+    }
+
+    /// Returns the span in the source where the error occurred, if available
+    ///
+    /// When multiple parser errors are reported, the span of the first error is returned.
+    pub fn span(&self) -> Option<Span> {
+        self.source.as_ref().map(|source| source.span)
+    }
+
     /// Returns true if the error was caused by the expectation of indentation during parsing
     pub fn is_indentation_error(&self) -> bool {
         match self.error.as_ref() {
@@ -80,6 +129,24 @@ impl LoaderError {
 
 impl fmt::Display for LoaderError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let (LoaderErrorKind::ParserErrors(errors), Some(source)) =
+            (self.error.as_ref(), &self.source)
+        {
+            // Each error is displayed with its own source excerpt
+            for (i, error) in errors.iter().enumerate() {
+                if i > 0 {
+                    writeln!(f)?;
+                }
+                writeln!(f, "{error}.")?;
+                write!(
+                    f,
+                    "{}",
+                    format_source_excerpt(&source.contents, &error.span, &source.path)
+                )?;
+            }
+            return Ok(());
+        }
+
         writeln!(f, "{}.", self.error)?;
         if let Some(source) = &self.source {
             write!(
@@ -112,32 +179,203 @@ impl From<LoaderErrorKind> for LoaderError {
     }
 }
 
+// The extension used for Koto source files
+const SOURCE_EXTENSION: &str = "koto";
+// The extension used for precompiled modules, see [Chunk::serialize]
+const BYTECODE_EXTENSION: &str = "kbc";
+
+/// Defines which modules a [Loader] is allowed to load
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ImportPolicy {
+    /// Modules can be loaded from anywhere
+    #[default]
+    Unrestricted,
+    /// Modules can only be loaded from within the given directory
+    ///
+    /// Modules imported from scripts without a path are searched for in the directory,
+    /// rather than in the current working directory.
+    WithinDirectory(PathBuf),
+    /// Modules can't be loaded
+    Disabled,
+}
+
 /// Helper for loading, compiling, and caching Koto modules
 #[derive(Clone, Default)]
 pub struct Loader {
     chunks: HashMap<PathBuf, Ptr<Chunk>, BuildHasherDefault<FxHasher>>,
+    import_policy: ImportPolicy,
+    cache_dir: Option<PathBuf>,
+    enable_type_checks: bool,
+    enable_optimizer: bool,
+    enable_constant_inlining: bool,
+    // The modules that are currently being compiled, used to prevent infinite recursion when
+    // modules that import each other are compiled with constant inlining enabled.
+    modules_in_progress: HashSet<PathBuf>,
 }
 
 impl Loader {
+    /// Creates a loader that only loads modules that are allowed by the given policy
+    pub fn with_import_policy(import_policy: ImportPolicy) -> Self {
+        Self {
+            chunks: HashMap::default(),
+            import_policy,
+            cache_dir: None,
+            enable_type_checks: false,
+            enable_optimizer: false,
+            enable_constant_inlining: false,
+            modules_in_progress: HashSet::new(),
+        }
+    }
+
+    /// The policy that's consulted when loading modules
+    pub fn import_policy(&self) -> &ImportPolicy {
+        &self.import_policy
+    }
+
+    /// Sets a directory that's used to cache compiled modules between runs
+    ///
+    /// Compiled modules are stored in the directory keyed by a hash of their source and the
+    /// version of the compiler, so a module is only recompiled when its source changes.
+    ///
+    /// The directory is created when the first module is cached. Errors while reading from or
+    /// writing to the cache are ignored, with the module being compiled as usual.
+    pub fn set_cache_dir(&mut self, cache_dir: Option<PathBuf>) {
+        self.cache_dir = cache_dir;
+    }
+
+    /// The directory that's used to cache compiled modules, if one has been set
+    pub fn cache_dir(&self) -> Option<&Path> {
+        self.cache_dir.as_deref()
+    }
+
+    /// Enables runtime checks of type hints in imported modules
+    ///
+    /// See [CompilerSettings::enable_type_checks].
+    pub fn set_type_checks_enabled(&mut self, enabled: bool) {
+        self.enable_type_checks = enabled;
+    }
+
+    /// Enables the bytecode optimizer for imported modules
+    ///
+    /// See [CompilerSettings::enable_optimizer].
+    pub fn set_optimizer_enabled(&mut self, enabled: bool) {
+        self.enable_optimizer = enabled;
+    }
+
+    /// Enables the inlining of constants exported by modules that are imported by other modules
+    ///
+    /// See [CompilerSettings::enable_constant_inlining].
+    pub fn set_constant_inlining_enabled(&mut self, enabled: bool) {
+        self.enable_constant_inlining = enabled;
+    }
+
     fn compile(
         &mut self,
         script: &str,
         script_path: Option<PathBuf>,
-        compiler_settings: CompilerSettings,
+        mut compiler_settings: CompilerSettings,
     ) -> Result<Ptr<Chunk>, LoaderError> {
-        match Parser::parse(script) {
-            Ok(ast) => {
-                let (bytes, mut debug_info) = match Compiler::compile(&ast, compiler_settings) {
-                    Ok((bytes, debug_info)) => (bytes, debug_info),
-                    Err(e) => return Err(LoaderError::from_compiler_error(e, script, script_path)),
-                };
+        // Parsing continues after errors so that all of the script's syntax errors get reported
+        let (ast, mut errors) = Parser::parse_with_recovery(script);
+        match errors.len() {
+            0 => {}
+            1 => {
+                let error = errors.remove(0);
+                return Err(LoaderError::from_parser_error(error, script, script_path));
+            }
+            _ => return Err(LoaderError::from_parser_errors(errors, script, script_path)),
+        }
+
+        if compiler_settings.enable_constant_inlining {
+            for (module_name, constants) in self.find_imported_constants(&ast, &script_path) {
+                compiler_settings
+                    .imported_constants
+                    .entry(module_name)
+                    .or_insert(constants);
+            }
+        }
+        let imported_constants = compiler_settings.imported_constants.clone();
 
-                debug_info.source = script.to_string();
+        let mut output = match Compiler::compile(&ast, compiler_settings) {
+            Ok(output) => output,
+            Err(e) => return Err(LoaderError::from_compiler_error(e, script, script_path)),
+        };
+
+        output.debug_info.source = script.to_string();
+
+        let mut chunk = Chunk::new(
+            output.bytes,
+            output.constants,
+            script_path,
+            output.debug_info,
+        );
+        chunk.exported_constants = output.exported_constants;
+        chunk.imported_constants = imported_constants;
+
+        Ok(chunk.into())
+    }
 
-                Ok(Chunk::new(bytes, ast.consume_constants(), script_path, debug_info).into())
+    // Finds the constants exported by the modules that are imported with `from` in the script
+    //
+    // Modules that can't be loaded are skipped, with the error being reported when the import is
+    // performed at runtime.
+    fn find_imported_constants(
+        &mut self,
+        ast: &Ast,
+        script_path: &Option<PathBuf>,
+    ) -> ImportedConstants {
+        let mut result = ImportedConstants::new();
+
+        for node in ast.nodes() {
+            let Node::Import { from, .. } = &node.node else {
+                continue;
+            };
+            let module_name = match from.as_slice() {
+                [ImportItemNode::Id(id)] => ast.constants().get_str(*id),
+                [ImportItemNode::Str(string)] => match string.nodes.as_slice() {
+                    [StringNode::Literal(literal)] => ast.constants().get_str(*literal),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            if result.contains_key(module_name) {
+                continue;
+            }
+
+            if let Some(constants) = self.module_exported_constants(module_name, script_path) {
+                if !constants.is_empty() {
+                    result.insert(module_name.into(), constants);
+                }
             }
-            Err(e) => Err(LoaderError::from_parser_error(e, script, script_path)),
         }
+
+        result
+    }
+
+    // Returns the constants exported by a module, or None if the module couldn't be compiled
+    fn module_exported_constants(
+        &mut self,
+        module_name: &str,
+        load_from_path: &Option<PathBuf>,
+    ) -> Option<ExportedConstants> {
+        let module_path = self.find_module(module_name, load_from_path.clone()).ok()?;
+        if self.modules_in_progress.contains(&module_path) {
+            return None;
+        }
+        self.compile_module(module_name, load_from_path.clone())
+            .ok()
+            .map(|module| module.chunk.exported_constants.clone())
+    }
+
+    // Returns true if the constants that were inlined in a chunk still match the module exports
+    fn imported_constants_are_current(&mut self, chunk: &Chunk) -> bool {
+        chunk
+            .imported_constants
+            .iter()
+            .all(|(module_name, constants)| {
+                self.module_exported_constants(module_name, &chunk.source_path)
+                    .is_some_and(|exported| exported == *constants)
+            })
     }
 
     /// Compiles a script
@@ -156,6 +394,57 @@ impl Loader {
         name: &str,
         load_from_path: Option<PathBuf>,
     ) -> Result<CompileModuleResult, LoaderError> {
+        let module_path = self.find_module(name, load_from_path)?;
+
+        match self.chunks.get(&module_path) {
+            Some(chunk) => Ok(CompileModuleResult {
+                chunk: chunk.clone(),
+                path: module_path,
+                loaded_from_cache: true,
+            }),
+            None => {
+                let chunk = if module_path.extension() == Some(BYTECODE_EXTENSION.as_ref()) {
+                    let data = std::fs::read(&module_path)?;
+                    let mut chunk = Chunk::deserialize(&data).map_err(LoaderErrorKind::from)?;
+                    // Imports in the precompiled module are relative to its new location
+                    chunk.source_path = Some(module_path.clone());
+                    let chunk = Ptr::from(chunk);
+                    Chunk::verify(chunk.clone()).map_err(LoaderErrorKind::from)?;
+                    chunk
+                } else {
+                    let script = std::fs::read_to_string(&module_path)?;
+                    self.modules_in_progress.insert(module_path.clone());
+                    let chunk = self.compile_module_source(&script, &module_path);
+                    self.modules_in_progress.remove(&module_path);
+                    chunk?
+                };
+
+                self.chunks.insert(module_path.clone(), chunk.clone());
+
+                Ok(CompileModuleResult {
+                    chunk,
+                    path: module_path,
+                    loaded_from_cache: false,
+                })
+            }
+        }
+    }
+
+    /// Finds a module from its name, returning the canonicalized path of the module's file
+    ///
+    /// The module is searched for in the directory of `load_from_path`, or in the current
+    /// directory if no path is provided. The loader's [ImportPolicy] is respected.
+    pub fn find_module(
+        &self,
+        name: &str,
+        load_from_path: Option<PathBuf>,
+    ) -> Result<PathBuf, LoaderError> {
+        let root = match &self.import_policy {
+            ImportPolicy::Unrestricted => None,
+            ImportPolicy::WithinDirectory(root) => Some(canonicalize(root)?),
+            ImportPolicy::Disabled => return Err(LoaderErrorKind::ImportsDisabled.into()),
+        };
+
         // Get either the directory of the provided path, or the current working directory
         let search_folder = match &load_from_path {
             Some(path) => match canonicalize(path)? {
@@ -165,65 +454,370 @@ impl Loader {
                 },
                 canonicalized => canonicalized,
             },
-            None => std::env::current_dir()?,
+            None => match &root {
+                Some(root) => root.clone(),
+                None => std::env::current_dir()?,
+            },
         };
 
-        let mut load_module_from_path = |module_path: PathBuf| {
-            let module_path = module_path.canonicalize()?;
+        let named_path = search_folder.join(name);
 
-            match self.chunks.get(&module_path) {
-                Some(chunk) => Ok(CompileModuleResult {
-                    chunk: chunk.clone(),
-                    path: module_path,
-                    loaded_from_cache: true,
-                }),
-                None => {
-                    let script = std::fs::read_to_string(&module_path)?;
+        // First, check for a neighbouring file with a matching name.
+        // Alternatively, check for a neighbouring directory with a matching name,
+        // that also contains a main file.
+        // Source files take priority over precompiled modules.
+        let candidates = [named_path.clone(), named_path.join("main")];
+        let module_path = candidates.iter().find_map(|candidate| {
+            [SOURCE_EXTENSION, BYTECODE_EXTENSION]
+                .iter()
+                .map(|extension| candidate.with_extension(extension))
+                .find(|path| path.exists())
+        });
 
-                    let chunk = self.compile(
-                        &script,
-                        Some(module_path.clone()),
-                        CompilerSettings::default(),
-                    )?;
+        let Some(module_path) = module_path else {
+            return Err(LoaderErrorKind::UnableToFindModule(name.into()).into());
+        };
 
-                    self.chunks.insert(module_path.clone(), chunk.clone());
+        let module_path = canonicalize(module_path)?;
 
-                    Ok(CompileModuleResult {
-                        chunk,
-                        path: module_path,
-                        loaded_from_cache: false,
-                    })
-                }
+        if let Some(root) = &root {
+            if !module_path.starts_with(root) {
+                return Err(LoaderErrorKind::ImportOutsideOfRoot(module_path).into());
             }
+        }
+
+        Ok(module_path)
+    }
+
+    fn module_compiler_settings(&self) -> CompilerSettings {
+        CompilerSettings {
+            enable_type_checks: self.enable_type_checks,
+            enable_optimizer: self.enable_optimizer,
+            enable_constant_inlining: self.enable_constant_inlining,
+            ..Default::default()
+        }
+    }
+
+    // Compiles a module's source, making use of the cache directory if one has been set
+    fn compile_module_source(
+        &mut self,
+        script: &str,
+        module_path: &Path,
+    ) -> Result<Ptr<Chunk>, LoaderError> {
+        let Some(cache_dir) = self.cache_dir.clone() else {
+            return self.compile(
+                script,
+                Some(module_path.into()),
+                self.module_compiler_settings(),
+            );
         };
 
-        let extension = "koto";
-        let named_path = search_folder.join(name);
+        let cache_path = cache_dir.join(format!(
+            "{:016x}.{BYTECODE_EXTENSION}",
+            cache_key(script, &self.module_compiler_settings())
+        ));
 
-        // First, check for a neighbouring file with a matching name.
-        let module_path = named_path.with_extension(extension);
-        if module_path.exists() {
-            load_module_from_path(module_path)
-        } else {
-            // Alternatively, check for a neighbouring directory with a matching name,
-            // that also contains a main file.
-            let module_path = named_path.join("main").with_extension(extension);
-            if module_path.exists() {
-                load_module_from_path(module_path)
-            } else {
-                Err(LoaderErrorKind::UnableToFindModule(name.into()).into())
+        if let Some(mut chunk) = fs::read(&cache_path)
+            .ok()
+            .and_then(|data| Chunk::deserialize(&data).ok())
+        {
+            // The cached chunk contains the module's source, comparing it guards against collisions
+            if chunk.debug_info.source == script {
+                chunk.source_path = Some(module_path.into());
+                let chunk = Ptr::from(chunk);
+                // Cached chunks that fail verification are replaced, as are chunks containing
+                // constants from other modules that are no longer up to date.
+                if Chunk::verify(chunk.clone()).is_ok()
+                    && self.imported_constants_are_current(&chunk)
+                {
+                    return Ok(chunk);
+                }
             }
         }
+
+        let chunk = self.compile(
+            script,
+            Some(module_path.into()),
+            self.module_compiler_settings(),
+        )?;
+
+        // The chunk is written to a temporary file first so that other processes don't see
+        // partially written data. Failing to write to the cache isn't an error.
+        let temp_path = cache_path.with_extension(format!("{}.tmp", std::process::id()));
+        let written = fs::create_dir_all(&cache_dir)
+            .and_then(|_| fs::write(&temp_path, chunk.serialize()))
+            .and_then(|_| fs::rename(&temp_path, &cache_path));
+        if written.is_err() {
+            fs::remove_file(&temp_path).ok();
+        }
+
+        Ok(chunk)
     }
 
     /// Clears the compiled module cache
+    ///
+    /// Modules stored in the cache directory aren't affected.
     pub fn clear_cache(&mut self) {
         self.chunks.clear();
     }
 }
 
+// Returns the key used for a module in the cache directory
+//
+// FxHasher is used because its output is stable between runs, unlike the default hasher.
+fn cache_key(script: &str, settings: &CompilerSettings) -> u64 {
+    let mut hasher = FxHasher::default();
+    COMPILER_VERSION.hash(&mut hasher);
+    settings.enable_type_checks.hash(&mut hasher);
+    settings.enable_optimizer.hash(&mut hasher);
+    settings.enable_constant_inlining.hash(&mut hasher);
+    script.hash(&mut hasher);
+    hasher.finish()
+}
+
 pub struct CompileModuleResult {
     pub chunk: Ptr<Chunk>,
     pub path: PathBuf,
     pub loaded_from_cache: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConstantValue;
+
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("{name}_{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn cached_chunk_count(&self) -> usize {
+            fs::read_dir(self.0.join("cache"))
+                .map(|entries| entries.count())
+                .unwrap_or(0)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    fn load_module(dir: &TestDir) -> CompileModuleResult {
+        let mut loader = Loader::default();
+        loader.set_cache_dir(Some(dir.0.join("cache")));
+        loader.compile_module("foo", Some(dir.0.clone())).unwrap()
+    }
+
+    #[test]
+    fn compiled_modules_are_cached() {
+        let dir = TestDir::new("koto_loader_cache");
+        fs::write(dir.0.join("foo.koto"), "export foo = 42").unwrap();
+
+        let compiled = load_module(&dir);
+        assert_eq!(dir.cached_chunk_count(), 1);
+
+        let cached = load_module(&dir);
+        assert_eq!(dir.cached_chunk_count(), 1);
+        assert_eq!(compiled.chunk, cached.chunk);
+        assert_eq!(cached.chunk.source_path.as_ref(), Some(&cached.path));
+        assert!(!cached.loaded_from_cache);
+    }
+
+    #[test]
+    fn cached_modules_are_invalidated_when_the_source_changes() {
+        let dir = TestDir::new("koto_loader_cache_invalidation");
+        fs::write(dir.0.join("foo.koto"), "export foo = 42").unwrap();
+        let before = load_module(&dir);
+
+        fs::write(dir.0.join("foo.koto"), "export foo = 99").unwrap();
+        let after = load_module(&dir);
+
+        assert_eq!(dir.cached_chunk_count(), 2);
+        assert_ne!(before.chunk, after.chunk);
+        assert_eq!(after.chunk.debug_info.source, "export foo = 99");
+    }
+
+    #[test]
+    fn type_checks_are_cached_separately() {
+        let dir = TestDir::new("koto_loader_cache_type_checks");
+        fs::write(dir.0.join("foo.koto"), "export foo: Number = 42").unwrap();
+        let without_checks = load_module(&dir);
+
+        let mut loader = Loader::default();
+        loader.set_cache_dir(Some(dir.0.join("cache")));
+        loader.set_type_checks_enabled(true);
+        let with_checks = loader.compile_module("foo", Some(dir.0.clone())).unwrap();
+
+        assert_eq!(dir.cached_chunk_count(), 2);
+        assert_ne!(without_checks.chunk, with_checks.chunk);
+    }
+
+    #[test]
+    fn optimized_modules_are_cached_separately() {
+        let dir = TestDir::new("koto_loader_cache_optimizer");
+        fs::write(dir.0.join("foo.koto"), "export foo = 1 + 2").unwrap();
+        let unoptimized = load_module(&dir);
+
+        let mut loader = Loader::default();
+        loader.set_cache_dir(Some(dir.0.join("cache")));
+        loader.set_optimizer_enabled(true);
+        let optimized = loader.compile_module("foo", Some(dir.0.clone())).unwrap();
+
+        assert_eq!(dir.cached_chunk_count(), 2);
+        assert_ne!(unoptimized.chunk, optimized.chunk);
+    }
+
+    #[test]
+    fn invalid_cache_entries_are_replaced() {
+        let dir = TestDir::new("koto_loader_cache_invalid_entry");
+        let script = "export foo = 42";
+        fs::write(dir.0.join("foo.koto"), script).unwrap();
+        let cache_path = dir.0.join("cache").join(format!(
+            "{:016x}.{BYTECODE_EXTENSION}",
+            cache_key(script, &CompilerSettings::default())
+        ));
+        fs::create_dir_all(cache_path.parent().unwrap()).unwrap();
+        fs::write(&cache_path, "not bytecode").unwrap();
+
+        let result = load_module(&dir);
+
+        assert_eq!(result.chunk.debug_info.source, script);
+        assert_eq!(
+            Chunk::deserialize(&fs::read(&cache_path).unwrap()).unwrap(),
+            *result.chunk
+        );
+    }
+
+    fn load_module_with_inlining(dir: &TestDir, name: &str) -> CompileModuleResult {
+        let mut loader = Loader::default();
+        loader.set_cache_dir(Some(dir.0.join("cache")));
+        loader.set_constant_inlining_enabled(true);
+        loader.compile_module(name, Some(dir.0.clone())).unwrap()
+    }
+
+    fn inlined_constants(imported: &[(&str, ConstantValue)]) -> ExportedConstants {
+        imported
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn constants_from_imported_modules_are_inlined() {
+        let dir = TestDir::new("koto_loader_inlining");
+        fs::write(
+            dir.0.join("config.koto"),
+            "export max_size = 100\nexport {name: 'abc'}\nexport f = || 42",
+        )
+        .unwrap();
+        fs::write(dir.0.join("main.koto"), "from config import max_size, name").unwrap();
+
+        let result = load_module_with_inlining(&dir, "main");
+
+        let config = &result.chunk.imported_constants["config"];
+        assert_eq!(
+            *config,
+            inlined_constants(&[
+                ("max_size", ConstantValue::Int(100)),
+                ("name", ConstantValue::Str("abc".into())),
+            ])
+        );
+        // The module is still imported, with the imported items loaded from constants
+        let instructions = Chunk::instructions_as_string(result.chunk.clone(), &[]);
+        assert!(instructions.contains("Import"), "{instructions}");
+        assert!(!instructions.contains("Access"), "{instructions}");
+    }
+
+    #[test]
+    fn modules_that_modify_their_exports_are_not_inlined() {
+        let dir = TestDir::new("koto_loader_inlining_exports");
+        fs::write(
+            dir.0.join("config.koto"),
+            "export max_size = 100\nkoto.exports().max_size = 5",
+        )
+        .unwrap();
+        fs::write(
+            dir.0.join("settings.koto"),
+            "export max_size = 100\nkey = 'max_size'\nexport {'${key}': 5}",
+        )
+        .unwrap();
+        fs::write(
+            dir.0.join("main.koto"),
+            "from config import max_size\nfrom settings import max_size",
+        )
+        .unwrap();
+
+        let result = load_module_with_inlining(&dir, "main");
+
+        assert!(result.chunk.imported_constants.is_empty());
+    }
+
+    #[test]
+    fn cached_modules_are_recompiled_when_inlined_constants_change() {
+        let dir = TestDir::new("koto_loader_inlining_invalidation");
+        fs::write(dir.0.join("config.koto"), "export max_size = 100").unwrap();
+        fs::write(dir.0.join("main.koto"), "from config import max_size").unwrap();
+        let before = load_module_with_inlining(&dir, "main");
+        assert_eq!(dir.cached_chunk_count(), 2);
+
+        fs::write(dir.0.join("config.koto"), "export max_size = 200").unwrap();
+        let after = load_module_with_inlining(&dir, "main");
+
+        // The updated config module is cached, and the main module's entry is replaced
+        assert_eq!(dir.cached_chunk_count(), 3);
+        assert_ne!(before.chunk, after.chunk);
+        assert_eq!(
+            after.chunk.imported_constants["config"],
+            inlined_constants(&[("max_size", ConstantValue::Int(200))])
+        );
+    }
+
+    #[test]
+    fn modules_that_import_each_other_are_inlined() {
+        let dir = TestDir::new("koto_loader_inlining_recursive");
+        fs::write(dir.0.join("a.koto"), "from b import y\nexport x = 1").unwrap();
+        fs::write(dir.0.join("b.koto"), "from a import x\nexport y = 2").unwrap();
+
+        let result = load_module_with_inlining(&dir, "a");
+
+        assert_eq!(
+            result.chunk.imported_constants["b"],
+            inlined_constants(&[("y", ConstantValue::Int(2))])
+        );
+    }
+
+    #[test]
+    fn all_parser_errors_are_reported() {
+        let script = "\
+x = 1 +
+y = 2
+z = * 3
+";
+        let error = Loader::default()
+            .compile_script(script, &None, CompilerSettings::default())
+            .unwrap_err();
+
+        assert!(!error.is_indentation_error());
+        assert_eq!(
+            error.to_string(),
+            "\
+Expected expression after binary operator.
+1:8
+   |
+ 1 | x = 1 +
+ 2 | y = 2
+
+Expected expression after assignment operator.
+3:5
+   |
+ 3 | z = * 3
+   |     ^"
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use rustc_hash::FxHasher;
+use smallvec::SmallVec;
+use std::{
+    cell::{RefCell, RefMut},
+    collections::HashMap,
+    hash::BuildHasherDefault,
+};
+
+/// Map lookups that have been cached while running a chunk's instructions
+///
+/// The runtime caches the steps of lookups that use constant keys, e.g. `foo.bar`, keyed by the
+/// ip of the lookup's instruction. Each step refers to the version of a map that was visited
+/// during a lookup, and is only reused while the map is unmodified. Steps for shared `@base` maps
+/// are cached separately from the accessed map, so lookups via a base map can skip hashing the
+/// key even when the accessed map differs.
+///
+/// The cache is discarded when a chunk is cloned, and is ignored when chunks are compared.
+#[derive(Default)]
+pub struct LookupCache {
+    entries: RefCell<HashMap<u32, CachedLookup, BuildHasherDefault<FxHasher>>>,
+}
+
+impl LookupCache {
+    /// Returns the lookups that were cached for the given ip, inserting an empty entry if needed
+    ///
+    /// The entry is borrowed mutably from the cache, so it should be released before the cache
+    /// is accessed again.
+    pub fn entry(&self, ip: u32) -> RefMut<'_, CachedLookup> {
+        RefMut::map(self.entries.borrow_mut(), |entries| {
+            entries.entry(ip).or_default()
+        })
+    }
+
+    /// Returns the number of cached lookups
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    /// Returns true if no lookups have been cached
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+}
+
+impl Clone for LookupCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl PartialEq for LookupCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+/// The steps of the map lookups that have been cached for an instruction
+///
+/// See [LookupCache].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CachedLookup {
+    /// The step for the map that was most recently accessed by the instruction
+    accessed_map: CachedLookupStep,
+    /// The steps for `@base` maps that were visited during lookups, most recently cached first
+    ///
+    /// Base maps are often shared between many maps, so their steps are cached separately.
+    base_maps: SmallVec<[CachedLookupStep; MAX_CACHED_BASE_MAPS]>,
+}
+
+const MAX_CACHED_BASE_MAPS: usize = 4;
+
+impl CachedLookup {
+    /// Returns the cached entry for a map with the given versions, if one is available
+    ///
+    /// `is_accessed_map` should be true when the map is the one being accessed by the
+    /// instruction, and false when the map was reached via `@base` entries.
+    pub fn get(
+        &self,
+        is_accessed_map: bool,
+        data_version: u64,
+        meta_version: u64,
+    ) -> Option<CachedLookupEntry> {
+        let matches = |step: &&CachedLookupStep| {
+            step.data_version == data_version && step.meta_version == meta_version
+        };
+
+        if is_accessed_map {
+            Some(&self.accessed_map).filter(matches)
+        } else {
+            self.base_maps.iter().find(matches)
+        }
+        .map(|step| step.entry)
+    }
+
+    /// Caches the entry that was used in a map with the given versions
+    pub fn insert(&mut self, is_accessed_map: bool, step: CachedLookupStep) {
+        if is_accessed_map {
+            self.accessed_map = step;
+        } else {
+            self.base_maps.truncate(MAX_CACHED_BASE_MAPS - 1);
+            self.base_maps.insert(0, step);
+        }
+    }
+}
+
+/// A step of a map lookup, referring to the version of a map that was visited
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CachedLookupStep {
+    /// The version of the map's data when the step was cached
+    ///
+    /// Map versions start at 1, so a version of 0 doesn't match any map.
+    pub data_version: u64,
+    /// The version of the map's meta map when the step was cached, or 0 if there was none
+    pub meta_version: u64,
+    /// The entry in the map that was used by the lookup
+    pub entry: CachedLookupEntry,
+}
+
+/// The entry in a map that was used during a step of a [CachedLookup]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CachedLookupEntry {
+    /// The value was found in the map's data at the given index
+    Data(u32),
+    /// The value was found in the map's meta map at the given index
+    Meta(u32),
+    /// The lookup continued with the `@base` map found in the meta map at the given index
+    Base(u32),
+}
+
+impl Default for CachedLookupEntry {
+    fn default() -> Self {
+        Self::Data(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(version: u64, entry: CachedLookupEntry) -> CachedLookupStep {
+        CachedLookupStep {
+            data_version: version,
+            meta_version: version,
+            entry,
+        }
+    }
+
+    #[test]
+    fn accessed_map_is_replaced() {
+        let mut lookup = CachedLookup::default();
+        assert_eq!(lookup.get(true, 0, 0), Some(CachedLookupEntry::Data(0)));
+        assert_eq!(lookup.get(true, 1, 1), None);
+
+        lookup.insert(true, step(1, CachedLookupEntry::Meta(2)));
+        assert_eq!(lookup.get(true, 1, 1), Some(CachedLookupEntry::Meta(2)));
+        assert_eq!(lookup.get(false, 1, 1), None);
+
+        lookup.insert(true, step(2, CachedLookupEntry::Base(3)));
+        assert_eq!(lookup.get(true, 1, 1), None);
+        assert_eq!(lookup.get(true, 2, 2), Some(CachedLookupEntry::Base(3)));
+    }
+
+    #[test]
+    fn oldest_base_map_is_evicted() {
+        let mut lookup = CachedLookup::default();
+        for version in 1..=MAX_CACHED_BASE_MAPS as u64 + 1 {
+            lookup.insert(
+                false,
+                step(version, CachedLookupEntry::Data(version as u32)),
+            );
+        }
+
+        assert_eq!(lookup.get(false, 1, 1), None);
+        for version in 2..=MAX_CACHED_BASE_MAPS as u64 + 1 {
+            assert_eq!(
+                lookup.get(false, version, version),
+                Some(CachedLookupEntry::Data(version as u32))
+            );
+        }
+    }
+}
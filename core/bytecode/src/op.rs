@@ -505,17 +505,86 @@ pub enum Op {
     /// `[*value, size]`
     CheckSizeMin,
 
+    /// Throws an error if the value doesn't match a type hint
+    ///
+    /// Used when type checks are enabled in the compiler,
+    /// see [CompilerSettings::enable_type_checks](crate::CompilerSettings::enable_type_checks).
+    ///
+    /// `[*value, @type constant]`
+    AssertType,
+
+    /// Accesses a function via a constant key, and then calls it with the accessed value as the
+    /// instance
+    ///
+    /// Used in place of an [Access](Op::Access) followed by a
+    /// [CallInstance](Op::CallInstance) when the call's args can be prepared before the access,
+    /// e.g. `foo.bar(x, 42)`.
+    ///
+    /// `[*result, *instance, *frame base, arg count, @key]`
+    AccessCall,
+
+    /// Calls a function in tail position, reusing the current frame if possible
+    ///
+    /// Koto functions are called by replacing the current frame, other callables are called as
+    /// with [Call](Op::Call), with the result then returned by the instructions that follow.
+    ///
+    /// `[*result, *function, *first arg, arg count]`
+    TailCall,
+
+    /// Calls an instance function in tail position, reusing the current frame if possible
+    ///
+    /// See [TailCall](Op::TailCall).
+    ///
+    /// `[*result, *function, *first arg, arg count, *instance]`
+    TailCallInstance,
+
+    /// Formats a value and then pushes it to the end of the current string
+    ///
+    /// The flags are a bitfield constructed from [StringFormatFlags](crate::StringFormatFlags).
+    /// The fill character, minimum width, and precision are only present when enabled by the
+    /// flags.
+    ///
+    /// `[*value, flags[2], @fill character, @min width, @precision]`
+    StringPushFormat,
+
+    /// Sets the result register to true if the value is a Map
+    ///
+    /// `[*result, *value]`
+    IsMap,
+
+    /// Sets the result register to true if the value matches a type hint
+    ///
+    /// Used when matching typed ids in match patterns, e.g. `x: String then ...`
+    ///
+    /// `[*result, *value, @type constant]`
+    MatchesType,
+
+    /// Makes a copy of a map with the given keys removed
+    ///
+    /// Used to capture the remaining entries in a map pattern, e.g. `{x, rest...}`
+    ///
+    /// `[*result, *map, *first key, key count]`
+    MapRest,
+
+    /// Accesses a map entry when destructuring, e.g. `{x, y} = foo`
+    ///
+    /// The map's entries are checked first, followed by its metamap (including `@base`),
+    /// and then the map's `@[]` override. An error is thrown if the key is missing.
+    ///
+    /// `[*result, *map, @key]`
+    AccessKey,
+
+    /// Accesses a map entry that has a default value when destructuring, e.g. `{x = 0} = foo`
+    ///
+    /// The entry is accessed as with [AccessKey](Op::AccessKey), and if the key is missing then
+    /// the instruction jumps forward by the given offset.
+    ///
+    /// Also used when matching map patterns, with the jump skipping to the next match alternative.
+    ///
+    /// `[*result, *map, @key, offset[2]]`
+    AccessKeyOrJump,
+
     // Unused opcodes, allowing for a direct transmutation from a byte to an Op.
-    Unused86,
-    Unused87,
-    Unused88,
-    Unused89,
-    Unused90,
-    Unused91,
-    Unused92,
-    Unused93,
-    Unused94,
-    Unused95,
     Unused96,
     Unused97,
     Unused98,
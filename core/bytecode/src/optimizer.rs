@@ -0,0 +1,958 @@
+use crate::{Chunk, DebugInfo, ExportedConstants, Instruction, InstructionReader, Op};
+use koto_parser::{
+    Ast, AstBinaryOp, AstIndex, AstUnaryOp, Constant, ConstantIndex, ConstantPool, MapKey, Node,
+    StringNode,
+};
+use smallvec::SmallVec;
+use std::{cmp::Ordering, collections::HashMap};
+use thiserror::Error;
+
+/// The errors that can occur while optimizing compiled bytecode
+#[derive(Error, Clone, Debug)]
+pub(crate) enum OptimizerError {
+    #[error("invalid instruction ({0})")]
+    InvalidInstruction(String),
+    #[error("the instruction at ip {0} has an invalid jump target")]
+    InvalidJumpTarget(usize),
+    #[error("the optimized jump offset at ip {0} is too large")]
+    JumpOffsetIsTooLarge(usize),
+    #[error("the constant pool is full")]
+    TooManyConstants,
+}
+
+/// A value that's known at compile time
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConstantValue {
+    /// The null value
+    Null,
+    /// A bool
+    Bool(bool),
+    /// An integer
+    Int(i64),
+    /// A float
+    Float(f64),
+    /// A string
+    Str(String),
+}
+
+impl ConstantValue {
+    /// Returns false for the values that are treated as false in conditions, i.e. null and false
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Self::Null | Self::Bool(false))
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Int(n) => Some(*n as f64),
+            Self::Float(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+/// Evaluates an expression that's made up of literals, e.g. `1 + 2 * 3`
+///
+/// None is returned if the expression's result isn't known at compile time, or if the operation
+/// should be left to the runtime, e.g. an integer operation that would overflow.
+pub(crate) fn evaluate_constant(ast: &Ast, node: &Node) -> Option<ConstantValue> {
+    use ConstantValue::*;
+
+    let result = match node {
+        Node::Null => Null,
+        Node::BoolTrue => Bool(true),
+        Node::BoolFalse => Bool(false),
+        Node::SmallInt(n) => Int(*n as i64),
+        Node::Int(constant) => Int(ast.constants().get_i64(*constant)),
+        Node::Float(constant) => Float(ast.constants().get_f64(*constant)),
+        Node::Str(string) => {
+            let mut result = String::new();
+            for node in string.nodes.iter() {
+                match node {
+                    StringNode::Literal(constant) => {
+                        result.push_str(ast.constants().get_str(*constant))
+                    }
+                    // Only values that have a simple display format get inserted into the string
+                    StringNode::Expr(expression) => match evaluate_node(ast, *expression)? {
+                        Bool(b) => result.push_str(&b.to_string()),
+                        Int(n) => result.push_str(&n.to_string()),
+                        Str(s) => result.push_str(&s),
+                        _ => return None,
+                    },
+                    // Formatting is left to the runtime
+                    StringNode::FormattedExpr(..) => return None,
+                }
+            }
+            Str(result)
+        }
+        Node::Nested(nested) => evaluate_node(ast, *nested)?,
+        Node::UnaryOp { op, value } => {
+            let value = evaluate_node(ast, *value)?;
+            match op {
+                AstUnaryOp::Negate => match value {
+                    Int(n) => Int(n.checked_neg()?),
+                    Float(n) => Float(-n),
+                    _ => return None,
+                },
+                AstUnaryOp::Not => Bool(!value.is_truthy()),
+            }
+        }
+        Node::BinaryOp { op, lhs, rhs } => {
+            use AstBinaryOp::*;
+
+            let lhs = evaluate_node(ast, *lhs)?;
+            match op {
+                // The rhs of a logic op isn't evaluated when the lhs determines the result
+                And if !lhs.is_truthy() => lhs,
+                Or if lhs.is_truthy() => lhs,
+                And | Or => evaluate_node(ast, *rhs)?,
+                Add | Subtract | Multiply | Divide | Remainder => {
+                    evaluate_arithmetic_op(*op, lhs, evaluate_node(ast, *rhs)?)?
+                }
+                Less | LessOrEqual | Greater | GreaterOrEqual | Equal | NotEqual => {
+                    Bool(evaluate_comparison_op(ast, *op, &lhs, *rhs)?)
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+fn evaluate_node(ast: &Ast, index: AstIndex) -> Option<ConstantValue> {
+    evaluate_constant(ast, &ast.node(index).node)
+}
+
+/// Finds the values exported by a script that are known at compile time
+///
+/// Only exports in the script's main block are included, e.g. `export max_size = 1000`,
+/// or `export {name: 'abc'}`. IDs that are exported more than once are excluded, given that
+/// their exported values depend on the order of execution.
+///
+/// Scripts that refer to `exports` (e.g. `koto.exports().max_size = 5`), or that export
+/// entries with names that are only known at runtime, can overwrite any of their exports,
+/// so no values are returned for them.
+pub(crate) fn find_exported_constants(ast: &Ast) -> ExportedConstants {
+    let mut result = ExportedConstants::new();
+
+    let Some(Node::MainBlock { body, .. }) = ast.entry_point().map(|entry| &entry.node) else {
+        return result;
+    };
+
+    let constants = ast.constants();
+    if constants
+        .iter()
+        .any(|constant| matches!(constant, Constant::Str("exports")))
+    {
+        return result;
+    }
+
+    let mut export_counts = HashMap::<String, usize>::new();
+    let mut add_export = |name: &str, value: Option<ConstantValue>| {
+        let count = export_counts.entry(name.into()).or_default();
+        *count += 1;
+        match value {
+            Some(value) if *count == 1 => result.insert(name.into(), value),
+            _ => result.remove(name),
+        };
+    };
+
+    // All exports are checked, including those outside of the main block
+    for (index, node) in ast.nodes().iter().enumerate() {
+        let Node::Export(expression) = &node.node else {
+            continue;
+        };
+        let is_top_level = body.contains(&(index as AstIndex));
+
+        match &ast.node(*expression).node {
+            Node::Assign { target, expression } => {
+                if let Node::Id(id, _) = &ast.node(*target).node {
+                    let value = is_top_level
+                        .then(|| evaluate_node(ast, *expression))
+                        .flatten();
+                    add_export(constants.get_str(*id), value);
+                }
+            }
+            Node::Map(entries) => {
+                for (key, value) in entries.iter() {
+                    let name = match key {
+                        MapKey::Id(id) => constants.get_str(*id),
+                        MapKey::Str(string) => match string.nodes.as_slice() {
+                            [StringNode::Literal(literal)] => constants.get_str(*literal),
+                            // The entry's name is only known at runtime
+                            _ => return ExportedConstants::new(),
+                        },
+                        MapKey::Meta(..) => continue,
+                    };
+                    let value = value
+                        .filter(|_| is_top_level)
+                        .and_then(|value| evaluate_node(ast, value));
+                    add_export(name, value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    result
+}
+
+fn evaluate_arithmetic_op(
+    op: AstBinaryOp,
+    lhs: ConstantValue,
+    rhs: ConstantValue,
+) -> Option<ConstantValue> {
+    use AstBinaryOp::*;
+    use ConstantValue::*;
+
+    let result = match (lhs, rhs) {
+        (Int(a), Int(b)) => match op {
+            Add => Int(a.checked_add(b)?),
+            Subtract => Int(a.checked_sub(b)?),
+            Multiply => Int(a.checked_mul(b)?),
+            // Integer division produces a float, matching the runtime's behaviour
+            Divide => Float(a as f64 / b as f64),
+            // The runtime returns NaN for a zero divisor, so it's left for the runtime to handle
+            Remainder => Int(a.checked_rem(b)?),
+            _ => return None,
+        },
+        (Str(a), Str(b)) if op == Add => Str(a + &b),
+        (a, b) => {
+            let a = a.as_f64()?;
+            let b = b.as_f64()?;
+            match op {
+                Add => Float(a + b),
+                Subtract => Float(a - b),
+                Multiply => Float(a * b),
+                Divide => Float(a / b),
+                Remainder => Float(a % b),
+                _ => return None,
+            }
+        }
+    };
+
+    Some(result)
+}
+
+// Comparisons are chained when the rhs is also a comparison,
+// e.g. `a < b < c` is evaluated as `a < b and b < c`.
+fn evaluate_comparison_op(
+    ast: &Ast,
+    op: AstBinaryOp,
+    lhs: &ConstantValue,
+    rhs: AstIndex,
+) -> Option<bool> {
+    if let Node::BinaryOp {
+        op: rhs_op,
+        lhs: rhs_lhs,
+        rhs: rhs_rhs,
+    } = &ast.node(rhs).node
+    {
+        if is_comparison_op(*rhs_op) {
+            let rhs_lhs = evaluate_node(ast, *rhs_lhs)?;
+            return if compare_constants(op, lhs, &rhs_lhs)? {
+                evaluate_comparison_op(ast, *rhs_op, &rhs_lhs, *rhs_rhs)
+            } else {
+                Some(false)
+            };
+        }
+    }
+
+    compare_constants(op, lhs, &evaluate_node(ast, rhs)?)
+}
+
+fn is_comparison_op(op: AstBinaryOp) -> bool {
+    use AstBinaryOp::*;
+    matches!(
+        op,
+        Less | LessOrEqual | Greater | GreaterOrEqual | Equal | NotEqual
+    )
+}
+
+fn compare_constants(op: AstBinaryOp, lhs: &ConstantValue, rhs: &ConstantValue) -> Option<bool> {
+    use AstBinaryOp::*;
+    use ConstantValue::*;
+
+    if matches!(op, Equal | NotEqual) {
+        let equal = match (lhs, rhs) {
+            (Null, Null) => true,
+            (Bool(a), Bool(b)) => a == b,
+            (Int(a), Int(b)) => a == b,
+            (Str(a), Str(b)) => a == b,
+            (Int(_) | Float(_), Int(_) | Float(_)) => lhs.as_f64() == rhs.as_f64(),
+            // Values with different types are never equal
+            _ => false,
+        };
+        return Some(if op == Equal { equal } else { !equal });
+    }
+
+    let ordering = match (lhs, rhs) {
+        (Int(a), Int(b)) => a.cmp(b),
+        (Str(a), Str(b)) => a.cmp(b),
+        // The runtime gives NaN a position in the ordering of numbers, so comparisons with NaN
+        // are left for the runtime.
+        _ => lhs.as_f64()?.partial_cmp(&rhs.as_f64()?)?,
+    };
+
+    let result = match op {
+        Less => ordering == Ordering::Less,
+        LessOrEqual => ordering != Ordering::Greater,
+        Greater => ordering == Ordering::Greater,
+        GreaterOrEqual => ordering != Ordering::Less,
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+/// Constants that are added to an [Ast]'s constant pool by the optimizer
+///
+/// Added constants are given indices following the AST's constants, and constants that are
+/// already in the AST's pool are reused.
+#[derive(Default)]
+pub(crate) struct AddedConstants {
+    // A map of the available constants to their indices, prepared when the first constant is added
+    indices: Option<HashMap<ConstantKey, ConstantIndex>>,
+    added: Vec<ConstantKey>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ConstantKey {
+    F64(u64),
+    I64(i64),
+    Str(String),
+}
+
+impl ConstantKey {
+    fn new(constant: Constant) -> Self {
+        match constant {
+            Constant::F64(n) => Self::F64(n.to_bits()),
+            Constant::I64(n) => Self::I64(n),
+            Constant::Str(s) => Self::Str(s.into()),
+        }
+    }
+
+    fn as_constant(&self) -> Constant<'_> {
+        match self {
+            Self::F64(n) => Constant::F64(f64::from_bits(*n)),
+            Self::I64(n) => Constant::I64(*n),
+            Self::Str(s) => Constant::Str(s),
+        }
+    }
+}
+
+impl AddedConstants {
+    /// Returns the index of a constant, adding it to the list of added constants if necessary
+    pub fn add(&mut self, ast: &Ast, constant: Constant) -> Result<ConstantIndex, OptimizerError> {
+        let indices = self.indices.get_or_insert_with(|| {
+            (0..)
+                .zip(ast.constants().iter())
+                .map(|(index, constant)| (ConstantKey::new(constant), index))
+                .collect()
+        });
+
+        let key = ConstantKey::new(constant);
+        if let Some(index) = indices.get(&key) {
+            return Ok(*index);
+        }
+
+        let index = ConstantIndex::try_from(ast.constants().size() + self.added.len())
+            .map_err(|_| OptimizerError::TooManyConstants)?;
+        indices.insert(key.clone(), index);
+        self.added.push(key);
+        Ok(index)
+    }
+
+    /// Returns the AST's constant pool, extended with the added constants
+    pub fn build(&self, ast: &Ast) -> Result<ConstantPool, OptimizerError> {
+        if self.added.is_empty() {
+            return Ok(ast.constants().clone());
+        }
+
+        let constants = ast
+            .constants()
+            .iter()
+            .chain(self.added.iter().map(ConstantKey::as_constant));
+        ConstantPool::from_constants(constants).ok_or(OptimizerError::TooManyConstants)
+    }
+}
+
+// The maximum number of jumps that will be followed when threading a jump
+const MAX_THREADED_JUMPS: usize = 16;
+
+/// Optimizes compiled bytecode, updating the debug info to match the optimized instructions
+///
+/// The following optimizations are performed:
+///   - Jumps that lead to other jumps are redirected to their final destinations.
+///   - Unreachable instructions are removed, e.g. the branches of an `if` expression with a
+///     constant condition that are jumped over.
+///   - Jumps to the following instruction are removed.
+///   - Redundant copies are removed, e.g. copying a value into a register before returning it.
+pub(crate) fn optimize_bytecode(
+    bytes: &[u8],
+    debug_info: &mut DebugInfo,
+) -> Result<Vec<u8>, OptimizerError> {
+    let mut optimizer = BytecodeOptimizer::new(bytes)?;
+    optimizer.thread_jumps();
+    optimizer.remove_unreachable_instructions();
+    optimizer.remove_redundant_jumps();
+    optimizer.remove_redundant_copies();
+    optimizer.finish(debug_info)
+}
+
+struct OptimizedInstruction {
+    // The instruction's bytes, starting with its op
+    bytes: SmallVec<[u8; 8]>,
+    // The instruction's ip in the unoptimized bytecode
+    ip: usize,
+    // The index of the instruction that this instruction jumps to
+    //
+    // For Function instructions the target is the instruction following the function's body.
+    // An index equal to the number of instructions refers to the end of the bytecode.
+    target: Option<usize>,
+    removed: bool,
+}
+
+impl OptimizedInstruction {
+    fn op(&self) -> Op {
+        Op::from(self.bytes[0])
+    }
+
+    // The register that's used as the first argument, e.g. a conditional jump's condition
+    fn register(&self) -> Option<u8> {
+        self.bytes.get(1).copied()
+    }
+}
+
+struct BytecodeOptimizer {
+    instructions: Vec<OptimizedInstruction>,
+}
+
+impl BytecodeOptimizer {
+    fn new(bytes: &[u8]) -> Result<Self, OptimizerError> {
+        let chunk = Chunk {
+            bytes: bytes.into(),
+            ..Default::default()
+        };
+        let mut reader = InstructionReader::new(chunk.into());
+        let mut instructions = Vec::new();
+        // The absolute jump targets of each instruction, converted into indices below
+        let mut target_ips = Vec::new();
+
+        let mut ip = 0;
+        while let Some(instruction) = reader.next() {
+            if let Instruction::Error { message } = instruction {
+                return Err(OptimizerError::InvalidInstruction(message));
+            }
+
+            let instruction_bytes = &bytes[ip..reader.ip];
+            let end_ip = reader.ip;
+            let offset = || {
+                let offset_bytes = &instruction_bytes[instruction_bytes.len() - 2..];
+                u16::from_le_bytes([offset_bytes[0], offset_bytes[1]]) as usize
+            };
+            let target_ip = match Op::from(instruction_bytes[0]) {
+                Op::Jump
+                | Op::JumpIfTrue
+                | Op::JumpIfFalse
+                | Op::IterNext
+                | Op::IterNextTemp
+                | Op::IterNextQuiet
+                | Op::AccessKeyOrJump
+                | Op::TryStart
+                | Op::Function => Some(end_ip + offset()),
+                Op::JumpBack => Some(
+                    end_ip
+                        .checked_sub(offset())
+                        .ok_or(OptimizerError::InvalidJumpTarget(ip))?,
+                ),
+                _ => None,
+            };
+
+            instructions.push(OptimizedInstruction {
+                bytes: instruction_bytes.into(),
+                ip,
+                target: None,
+                removed: false,
+            });
+            target_ips.push(target_ip);
+            ip = end_ip;
+        }
+
+        let ips: Vec<usize> = instructions
+            .iter()
+            .map(|instruction| instruction.ip)
+            .collect();
+        for (instruction, target_ip) in instructions.iter_mut().zip(target_ips) {
+            if let Some(target_ip) = target_ip {
+                let target = match ips.binary_search(&target_ip) {
+                    Ok(index) => index,
+                    Err(index) if index == ips.len() && target_ip == bytes.len() => index,
+                    Err(_) => return Err(OptimizerError::InvalidJumpTarget(instruction.ip)),
+                };
+                instruction.target = Some(target);
+            }
+        }
+
+        Ok(Self { instructions })
+    }
+
+    // Redirects jumps that lead to other jumps
+    fn thread_jumps(&mut self) {
+        use Op::*;
+
+        for index in 0..self.instructions.len() {
+            let instruction = &self.instructions[index];
+            let op = instruction.op();
+            let Some(mut target) = instruction.target else {
+                continue;
+            };
+            let is_unconditional = matches!(op, Jump | JumpBack);
+            if !(is_unconditional
+                || matches!(
+                    op,
+                    JumpIfTrue
+                        | JumpIfFalse
+                        | IterNext
+                        | IterNextTemp
+                        | IterNextQuiet
+                        | AccessKeyOrJump
+                ))
+            {
+                continue;
+            }
+
+            let mut threaded_target = None;
+            for _ in 0..MAX_THREADED_JUMPS {
+                match self.threaded_target(index, target) {
+                    Some(next_target) => target = next_target,
+                    None => break,
+                }
+                // Conditional jumps can only jump forward
+                if is_unconditional || target > index {
+                    threaded_target = Some(target);
+                }
+            }
+
+            if let Some(target) = threaded_target {
+                self.instructions[index].target = Some(target);
+            }
+        }
+    }
+
+    // Returns the instruction that a jump can be redirected to when the jump's target is a jump
+    fn threaded_target(&self, jump: usize, target: usize) -> Option<usize> {
+        use Op::*;
+
+        let jump = &self.instructions[jump];
+        let target_instruction = self.instructions.get(target)?;
+
+        match (jump.op(), target_instruction.op()) {
+            (_, Jump | JumpBack) => target_instruction.target,
+            // A conditional jump that leads to a conditional jump on the same register will have
+            // the same outcome when the condition is checked again.
+            (JumpIfTrue, JumpIfTrue) | (JumpIfFalse, JumpIfFalse)
+                if jump.register() == target_instruction.register() =>
+            {
+                target_instruction.target
+            }
+            (JumpIfTrue, JumpIfFalse) | (JumpIfFalse, JumpIfTrue)
+                if jump.register() == target_instruction.register() =>
+            {
+                Some(target + 1)
+            }
+            _ => None,
+        }
+    }
+
+    // Removes instructions that can't be reached from the start of the bytecode
+    fn remove_unreachable_instructions(&mut self) {
+        use Op::*;
+
+        let mut reachable = vec![false; self.instructions.len()];
+        let mut pending = vec![0];
+
+        while let Some(index) = pending.pop() {
+            let Some(instruction) = self.instructions.get(index) else {
+                continue;
+            };
+            if reachable[index] {
+                continue;
+            }
+            reachable[index] = true;
+
+            match instruction.op() {
+                Jump | JumpBack => pending.extend(instruction.target),
+                Return | Throw => {}
+                // Function instructions continue with the function's body,
+                // and the instruction that follows the body.
+                _ => {
+                    pending.push(index + 1);
+                    pending.extend(instruction.target);
+                }
+            }
+        }
+
+        for (instruction, reachable) in self.instructions.iter_mut().zip(reachable) {
+            if !reachable {
+                instruction.removed = true;
+            }
+        }
+    }
+
+    // Removes jumps that lead to the instruction that follows them
+    fn remove_redundant_jumps(&mut self) {
+        let mut next_instruction = self.instructions.len();
+
+        for index in (0..self.instructions.len()).rev() {
+            let instruction = &self.instructions[index];
+            if instruction.removed {
+                continue;
+            }
+
+            if instruction.op() == Op::Jump
+                && instruction
+                    .target
+                    .is_some_and(|target| self.resolve(target) == next_instruction)
+            {
+                self.instructions[index].removed = true;
+            } else {
+                next_instruction = index;
+            }
+        }
+    }
+
+    // Removes copies that don't have any effect
+    fn remove_redundant_copies(&mut self) {
+        let jump_targets = self.jump_targets();
+        let mut previous_copy: Option<(u8, u8)> = None;
+
+        for index in 0..self.instructions.len() {
+            if self.instructions[index].removed {
+                continue;
+            }
+
+            let copy = match self.instructions[index].bytes.as_slice() {
+                [op, target, source] if Op::from(*op) == Op::Copy => Some((*target, *source)),
+                _ => None,
+            };
+
+            let Some((target, source)) = copy else {
+                previous_copy = None;
+                continue;
+            };
+
+            let is_jump_target = jump_targets[index];
+            let redundant = if target == source {
+                true
+            } else if !is_jump_target
+                && previous_copy.is_some_and(|previous| {
+                    previous == (target, source) || previous == (source, target)
+                })
+            {
+                // The registers already contain the same value
+                true
+            } else if let Some(next) = self.next_instruction(index) {
+                // A copy into a register that's then returned can be skipped by returning the
+                // copy's source directly.
+                let next_instruction = &mut self.instructions[next];
+                if next_instruction.op() == Op::Return
+                    && next_instruction.register() == Some(target)
+                    && !jump_targets[next]
+                {
+                    next_instruction.bytes[1] = source;
+                    true
+                } else {
+                    false
+                }
+            } else {
+                false
+            };
+
+            if redundant {
+                self.instructions[index].removed = true;
+            } else {
+                previous_copy = Some((target, source));
+            }
+        }
+    }
+
+    // Returns a list of flags that show which instructions are the targets of jumps
+    fn jump_targets(&self) -> Vec<bool> {
+        let mut result = vec![false; self.instructions.len() + 1];
+        for instruction in self.instructions.iter().filter(|i| !i.removed) {
+            if let Some(target) = instruction.target {
+                result[self.resolve(target)] = true;
+            }
+        }
+        result
+    }
+
+    // Returns the index of the next instruction that hasn't been removed
+    fn next_instruction(&self, index: usize) -> Option<usize> {
+        let next = self.resolve(index + 1);
+        (next < self.instructions.len()).then_some(next)
+    }
+
+    // Returns the index of the first instruction at or after the given index that hasn't been
+    // removed
+    fn resolve(&self, index: usize) -> usize {
+        (index..self.instructions.len())
+            .find(|i| !self.instructions[*i].removed)
+            .unwrap_or(self.instructions.len())
+    }
+
+    // Produces the optimized bytecode, and updates the debug info's instruction pointers
+    fn finish(self, debug_info: &mut DebugInfo) -> Result<Vec<u8>, OptimizerError> {
+        // The optimized ip of each instruction,
+        // removed instructions are given the ip of the following instruction.
+        let mut ips = Vec::with_capacity(self.instructions.len() + 1);
+        let mut ip = 0;
+        for instruction in self.instructions.iter() {
+            ips.push(ip);
+            if !instruction.removed {
+                ip += instruction.bytes.len();
+            }
+        }
+        ips.push(ip);
+
+        let mut bytes = Vec::with_capacity(ip);
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            if instruction.removed {
+                continue;
+            }
+
+            let mut instruction_bytes = instruction.bytes.clone();
+            if let Some(target) = instruction.target {
+                let end_ip = ips[index] + instruction_bytes.len();
+                let target_ip = ips[target];
+                let op = instruction.op();
+                let (op, offset) = if target_ip >= end_ip {
+                    let op = if op == Op::JumpBack { Op::Jump } else { op };
+                    (op, target_ip - end_ip)
+                } else if matches!(op, Op::Jump | Op::JumpBack) {
+                    (Op::JumpBack, end_ip - target_ip)
+                } else {
+                    return Err(OptimizerError::InvalidJumpTarget(instruction.ip));
+                };
+                let offset = u16::try_from(offset)
+                    .map_err(|_| OptimizerError::JumpOffsetIsTooLarge(instruction.ip))?;
+
+                let size = instruction_bytes.len();
+                instruction_bytes[0] = op as u8;
+                instruction_bytes[size - 2..].copy_from_slice(&offset.to_le_bytes());
+            }
+
+            bytes.extend_from_slice(&instruction_bytes);
+        }
+
+        // Maps an unoptimized ip to the optimized ip of the instruction at or following it
+        let optimized_ip = |ip: u32| -> u32 {
+            let index = self
+                .instructions
+                .partition_point(|instruction| instruction.ip < ip as usize);
+            ips[index] as u32
+        };
+
+        let mut source_map: Vec<(u32, _)> = Vec::with_capacity(debug_info.source_map.len());
+        for (ip, span) in debug_info.source_map.iter() {
+            let ip = optimized_ip(*ip);
+            match source_map.last_mut() {
+                // The span of a removed instruction is replaced by the span of the instruction
+                // that follows it
+                Some(last) if last.0 == ip => last.1 = *span,
+                _ => source_map.push((ip, *span)),
+            }
+        }
+        source_map.dedup_by(|entry, previous| entry.1 == previous.1);
+        debug_info.source_map = source_map;
+
+        for scope in debug_info.local_scopes.iter_mut() {
+            scope.start_ip = optimized_ip(scope.start_ip);
+            scope.end_ip = optimized_ip(scope.end_ip);
+            for local in scope.locals.iter_mut() {
+                local.start_ip = optimized_ip(local.start_ip);
+            }
+        }
+        // The scopes of removed functions are discarded
+        debug_info
+            .local_scopes
+            .retain(|scope| scope.start_ip < scope.end_ip);
+
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Chunk, CompilerSettings, Instruction, InstructionReader, Loader};
+    use koto_memory::Ptr;
+    use koto_parser::ConstantIndex;
+
+    fn compile(script: &str, enable_optimizer: bool) -> Ptr<Chunk> {
+        Loader::default()
+            .compile_script(
+                script,
+                &None,
+                CompilerSettings {
+                    enable_optimizer,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+    }
+
+    // Returns the script's optimized instructions as strings, with whitespace condensed
+    fn optimized_instructions(script: &str) -> Vec<String> {
+        InstructionReader::new(compile(script, true))
+            .map(|instruction| {
+                format!("{instruction:?}")
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    fn last_constant(chunk: &Chunk) -> ConstantIndex {
+        chunk.constants.size() as ConstantIndex - 1
+    }
+
+    fn check_instructions(script: &str, expected: &[&str]) {
+        assert_eq!(optimized_instructions(script), expected, "\n{script}");
+    }
+
+    mod constant_folding {
+        use super::*;
+
+        #[test]
+        fn arithmetic() {
+            check_instructions(
+                "1 + 2 * 3",
+                &["SetNumber result: 1 value: 7", "Return result: 1"],
+            );
+        }
+
+        #[test]
+        fn negative_numbers() {
+            check_instructions(
+                "-(1 + 2)",
+                &["SetNumber result: 1 value: -3", "Return result: 1"],
+            );
+        }
+
+        #[test]
+        fn large_ints_are_added_to_the_constant_pool() {
+            let chunk = compile("1000 * 1000", true);
+            assert_eq!(chunk.constants.get_i64(last_constant(&chunk)), 1_000_000);
+        }
+
+        #[test]
+        fn overflowing_ints_are_left_to_the_runtime() {
+            let script = "9223372036854775807 + 1";
+            assert_eq!(
+                optimized_instructions(script).len(),
+                InstructionReader::new(compile(script, false)).count()
+            );
+        }
+
+        #[test]
+        fn string_concatenation() {
+            let chunk = compile("'abc' + 'def'", true);
+            assert_eq!(chunk.constants.get_str(last_constant(&chunk)), "abcdef");
+        }
+
+        #[test]
+        fn string_interpolation() {
+            let chunk = compile("'x: ${1 + 2}, ${true}'", true);
+            assert_eq!(chunk.constants.get_str(last_constant(&chunk)), "x: 3, true");
+        }
+
+        #[test]
+        fn comparisons() {
+            check_instructions(
+                "1 < 2 < 3",
+                &["SetBool result: 1 value: true", "Return result: 1"],
+            );
+            check_instructions(
+                "'a' == 1",
+                &["SetBool result: 1 value: false", "Return result: 1"],
+            );
+        }
+    }
+
+    mod dead_branches {
+        use super::*;
+
+        #[test]
+        fn constant_if_condition() {
+            let script = "
+x = if 1 > 2
+  'yes'
+else
+  'no'
+";
+            let chunk = compile(script, true);
+            let instructions = InstructionReader::new(chunk.clone()).collect::<Vec<_>>();
+            // Only the else branch remains
+            match instructions.as_slice() {
+                [Instruction::LoadString { constant, .. }, Instruction::Return { .. }] => {
+                    assert_eq!(chunk.constants.get_str(*constant), "no");
+                }
+                other => panic!("Unexpected instructions: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn constant_else_if_condition() {
+            let script = "
+f = |x|
+  if x
+    1
+  else if true
+    2
+  else
+    3
+f false
+";
+            // The else block is removed, along with the jump past it
+            assert!(!optimized_instructions(script)
+                .iter()
+                .any(|instruction| instruction == "SetNumber result: 1 value: 3"));
+        }
+    }
+
+    mod jumps {
+        use super::*;
+
+        #[test]
+        fn jump_threading() {
+            let script = "
+for i in 0..3
+  if i == 1
+    x = i
+  else
+    continue
+";
+            // The jump to the end of the `if` block is redirected to the loop's start
+            let instructions = optimized_instructions(script);
+            assert!(!instructions.iter().any(|op| op.starts_with("Jump ")));
+        }
+    }
+
+    mod copies {
+        use super::*;
+
+        #[test]
+        fn copy_before_return() {
+            let script = "
+f = |a|
+  b = a
+  b
+";
+            let instructions = optimized_instructions(script);
+            assert!(instructions.iter().any(|op| op == "Return result: 1"));
+        }
+    }
+}
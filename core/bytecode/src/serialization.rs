@@ -0,0 +1,621 @@
+use crate::{
+    chunk::{LocalName, LocalScope},
+    Chunk, ConstantValue, DebugInfo, ExportedConstants,
+};
+use koto_parser::{Constant, ConstantPool, Position, Span};
+use std::path::PathBuf;
+use thiserror::Error;
+
+// The bytes that start every serialized chunk
+const MAGIC: &[u8; 4] = b"KBC\0";
+
+/// The version of the binary format produced by [Chunk::serialize]
+///
+/// The version is incremented whenever the layout of serialized data changes.
+pub const BYTECODE_FORMAT_VERSION: u32 = 4;
+
+// The version of the compiler that produced the bytecode
+//
+// The instruction set can change between releases, so chunks are only accepted when they were
+// produced by a matching version of the compiler.
+pub(crate) const COMPILER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const CONSTANT_F64: u8 = 0;
+const CONSTANT_I64: u8 = 1;
+const CONSTANT_STR: u8 = 2;
+
+const VALUE_NULL: u8 = 0;
+const VALUE_FALSE: u8 = 1;
+const VALUE_TRUE: u8 = 2;
+const VALUE_INT: u8 = 3;
+const VALUE_FLOAT: u8 = 4;
+const VALUE_STR: u8 = 5;
+
+/// Errors that can be returned from [Chunk::deserialize]
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum DeserializeError {
+    #[error("The data doesn't contain Koto bytecode")]
+    NotBytecode,
+    #[error("Unsupported bytecode format version ({found}), expected version {expected}")]
+    UnsupportedFormatVersion { found: u32, expected: u32 },
+    #[error("The bytecode was compiled by Koto {found}, expected Koto {expected}")]
+    CompilerVersionMismatch { found: String, expected: String },
+    #[error("Unexpected end of bytecode data")]
+    UnexpectedEnd,
+    #[error("Invalid bytecode data ({0})")]
+    InvalidData(&'static str),
+}
+
+type Result<T> = std::result::Result<T, DeserializeError>;
+
+/// The settings used by [Chunk::serialize_with_settings]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SerializeSettings {
+    /// Leaves the script's source and source path out of the serialized data
+    ///
+    /// The source map is kept, so errors still report line and column positions,
+    /// but without source excerpts.
+    pub strip_source: bool,
+}
+
+impl Chunk {
+    /// Serializes the chunk into a versioned binary format
+    ///
+    /// The chunk's source is included, see [Chunk::serialize_with_settings] for stripping it.
+    ///
+    /// The chunk can be restored with [Chunk::deserialize].
+    pub fn serialize(&self) -> Vec<u8> {
+        self.serialize_with_settings(SerializeSettings::default())
+    }
+
+    /// Serializes the chunk into a versioned binary format, using the provided settings
+    ///
+    /// The chunk can be restored with [Chunk::deserialize].
+    pub fn serialize_with_settings(&self, settings: SerializeSettings) -> Vec<u8> {
+        let mut writer = Writer::default();
+
+        writer.bytes(MAGIC);
+        writer.u32(BYTECODE_FORMAT_VERSION);
+        writer.str(COMPILER_VERSION);
+
+        writer.sized_bytes(&self.bytes);
+
+        writer.len(self.constants.size());
+        for constant in self.constants.iter() {
+            match constant {
+                Constant::F64(n) => {
+                    writer.u8(CONSTANT_F64);
+                    writer.u64(n.to_bits());
+                }
+                Constant::I64(n) => {
+                    writer.u8(CONSTANT_I64);
+                    writer.u64(n as u64);
+                }
+                Constant::Str(s) => {
+                    writer.u8(CONSTANT_STR);
+                    writer.str(s);
+                }
+            }
+        }
+
+        match &self.source_path {
+            Some(path) if !settings.strip_source => {
+                writer.u8(1);
+                writer.str(&path.to_string_lossy());
+            }
+            _ => writer.u8(0),
+        }
+
+        writer.debug_info(&self.debug_info, settings.strip_source);
+
+        writer.exported_constants(&self.exported_constants);
+        writer.len(self.imported_constants.len());
+        for (module_name, constants) in self.imported_constants.iter() {
+            writer.str(module_name);
+            writer.exported_constants(constants);
+        }
+
+        writer.data
+    }
+
+    /// Deserializes a chunk that was serialized with [Chunk::serialize]
+    ///
+    /// An error is returned if the data is invalid, or if it was produced by a different version
+    /// of the compiler.
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader { data, position: 0 };
+
+        if reader.bytes(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(DeserializeError::NotBytecode);
+        }
+
+        let format_version = reader.u32()?;
+        if format_version != BYTECODE_FORMAT_VERSION {
+            return Err(DeserializeError::UnsupportedFormatVersion {
+                found: format_version,
+                expected: BYTECODE_FORMAT_VERSION,
+            });
+        }
+
+        let compiler_version = reader.str()?;
+        if compiler_version != COMPILER_VERSION {
+            return Err(DeserializeError::CompilerVersionMismatch {
+                found: compiler_version.into(),
+                expected: COMPILER_VERSION.into(),
+            });
+        }
+
+        let bytes = reader.sized_bytes()?.into();
+
+        let constant_count = reader.len()?;
+        let mut constants = Vec::with_capacity(constant_count.min(reader.remaining()));
+        for _ in 0..constant_count {
+            let constant = match reader.u8()? {
+                CONSTANT_F64 => Constant::F64(f64::from_bits(reader.u64()?)),
+                CONSTANT_I64 => Constant::I64(reader.u64()? as i64),
+                CONSTANT_STR => Constant::Str(reader.str()?),
+                _ => return Err(DeserializeError::InvalidData("unknown constant type")),
+            };
+            constants.push(constant);
+        }
+        let constants = ConstantPool::from_constants(constants)
+            .ok_or(DeserializeError::InvalidData("too many constants"))?;
+        // Duplicate constants are merged when building the pool, which would shift the indexes
+        // of any following constants.
+        if constants.size() != constant_count {
+            return Err(DeserializeError::InvalidData("duplicate constants"));
+        }
+
+        let source_path = match reader.u8()? {
+            0 => None,
+            1 => Some(PathBuf::from(reader.str()?)),
+            _ => return Err(DeserializeError::InvalidData("invalid source path")),
+        };
+
+        let debug_info = reader.debug_info()?;
+
+        let mut chunk = Self::new(bytes, constants, source_path, debug_info);
+
+        chunk.exported_constants = reader.exported_constants()?;
+        let module_count = reader.len()?;
+        for _ in 0..module_count {
+            let module_name = reader.str()?.into();
+            let constants = reader.exported_constants()?;
+            chunk.imported_constants.insert(module_name, constants);
+        }
+
+        if reader.remaining() > 0 {
+            return Err(DeserializeError::InvalidData("unexpected trailing data"));
+        }
+
+        Ok(chunk)
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    fn u8(&mut self, n: u8) {
+        self.data.push(n);
+    }
+
+    fn u32(&mut self, n: u32) {
+        self.bytes(&n.to_le_bytes());
+    }
+
+    fn u64(&mut self, n: u64) {
+        self.bytes(&n.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u32(len as u32);
+    }
+
+    fn sized_bytes(&mut self, bytes: &[u8]) {
+        self.len(bytes.len());
+        self.bytes(bytes);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.sized_bytes(s.as_bytes());
+    }
+
+    fn position(&mut self, position: Position) {
+        self.u32(position.line);
+        self.u32(position.column);
+    }
+
+    fn span(&mut self, span: Span) {
+        self.position(span.start);
+        self.position(span.end);
+    }
+
+    fn debug_info(&mut self, debug_info: &DebugInfo, strip_source: bool) {
+        self.len(debug_info.source_map.len());
+        for (ip, span) in debug_info.source_map.iter() {
+            self.u32(*ip);
+            self.span(*span);
+        }
+        self.len(debug_info.local_scopes.len());
+        for frame in debug_info.local_scopes.iter() {
+            self.u32(frame.start_ip);
+            self.u32(frame.end_ip);
+            self.len(frame.locals.len());
+            for local in frame.locals.iter() {
+                self.u32(local.name);
+                self.u8(local.register);
+                self.u32(local.start_ip);
+            }
+        }
+        if strip_source {
+            self.str("");
+        } else {
+            self.str(&debug_info.source);
+        }
+    }
+
+    fn exported_constants(&mut self, constants: &ExportedConstants) {
+        self.len(constants.len());
+        for (name, value) in constants.iter() {
+            self.str(name);
+            match value {
+                ConstantValue::Null => self.u8(VALUE_NULL),
+                ConstantValue::Bool(false) => self.u8(VALUE_FALSE),
+                ConstantValue::Bool(true) => self.u8(VALUE_TRUE),
+                ConstantValue::Int(n) => {
+                    self.u8(VALUE_INT);
+                    self.u64(*n as u64);
+                }
+                ConstantValue::Float(n) => {
+                    self.u8(VALUE_FLOAT);
+                    self.u64(n.to_bits());
+                }
+                ConstantValue::Str(s) => {
+                    self.u8(VALUE_STR);
+                    self.str(s);
+                }
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8]> {
+        if count > self.remaining() {
+            return Err(DeserializeError::UnexpectedEnd);
+        }
+        let result = &self.data[self.position..self.position + count];
+        self.position += count;
+        Ok(result)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let bytes = self.bytes(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        self.u32().map(|n| n as usize)
+    }
+
+    fn sized_bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.len()?;
+        self.bytes(len)
+    }
+
+    fn str(&mut self) -> Result<&'a str> {
+        std::str::from_utf8(self.sized_bytes()?)
+            .map_err(|_| DeserializeError::InvalidData("invalid string"))
+    }
+
+    fn position(&mut self) -> Result<Position> {
+        Ok(Position {
+            line: self.u32()?,
+            column: self.u32()?,
+        })
+    }
+
+    fn span(&mut self) -> Result<Span> {
+        Ok(Span {
+            start: self.position()?,
+            end: self.position()?,
+        })
+    }
+
+    fn debug_info(&mut self) -> Result<DebugInfo> {
+        let mut result = DebugInfo::default();
+
+        let entry_count = self.len()?;
+        for _ in 0..entry_count {
+            let ip = self.u32()?;
+            let span = self.span()?;
+            result.source_map.push((ip, span));
+        }
+
+        let frame_count = self.len()?;
+        for _ in 0..frame_count {
+            let start_ip = self.u32()?;
+            let end_ip = self.u32()?;
+            let local_count = self.len()?;
+            let mut locals = Vec::with_capacity(local_count.min(self.remaining()));
+            for _ in 0..local_count {
+                locals.push(LocalName {
+                    name: self.u32()?,
+                    register: self.u8()?,
+                    start_ip: self.u32()?,
+                });
+            }
+            result.local_scopes.push(LocalScope {
+                start_ip,
+                end_ip,
+                locals,
+            });
+        }
+
+        result.source = self.str()?.into();
+
+        // Spans are used when rendering source excerpts, so they need to be checked against the
+        // source. The source might have been stripped, in which case only the span's ordering
+        // can be checked.
+        let line_count = match result.source.lines().count() {
+            0 => None,
+            count => Some(count as u32),
+        };
+        for (_, span) in result.source_map.iter() {
+            if !span_is_valid(span, line_count) {
+                return Err(DeserializeError::InvalidData("invalid span"));
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn exported_constants(&mut self) -> Result<ExportedConstants> {
+        let mut result = ExportedConstants::new();
+
+        let constant_count = self.len()?;
+        for _ in 0..constant_count {
+            let name = self.str()?.into();
+            let value = match self.u8()? {
+                VALUE_NULL => ConstantValue::Null,
+                VALUE_FALSE => ConstantValue::Bool(false),
+                VALUE_TRUE => ConstantValue::Bool(true),
+                VALUE_INT => ConstantValue::Int(self.u64()? as i64),
+                VALUE_FLOAT => ConstantValue::Float(f64::from_bits(self.u64()?)),
+                VALUE_STR => ConstantValue::Str(self.str()?.into()),
+                _ => {
+                    return Err(DeserializeError::InvalidData(
+                        "unknown exported constant type",
+                    ))
+                }
+            };
+            result.insert(name, value);
+        }
+
+        Ok(result)
+    }
+}
+
+// Checks that a span's end isn't before its start, and that its lines are within the source
+fn span_is_valid(span: &Span, line_count: Option<u32>) -> bool {
+    let Span { start, end } = span;
+
+    start.line > 0
+        && (end.line, end.column) >= (start.line, start.column)
+        && line_count.is_none_or(|count| end.line <= count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{loader::LoaderErrorKind, CompilerSettings, Loader, Op};
+
+    fn compile(script: &str) -> Chunk {
+        let chunk = Loader::default()
+            .compile_script(script, &None, CompilerSettings::default())
+            .unwrap();
+        Chunk::clone(&chunk)
+    }
+
+    #[test]
+    fn round_trip() {
+        let chunk = compile(
+            "
+x = [1, 2.5, 'three']
+f = |n| n * 2
+f x.size()
+",
+        );
+
+        let deserialized = Chunk::deserialize(&chunk.serialize()).unwrap();
+
+        assert_eq!(chunk, deserialized);
+    }
+
+    #[test]
+    fn round_trip_with_exported_constants() {
+        let chunk = Loader::default()
+            .compile_script(
+                "export {a: null, b: true, c: -1, d: 1.5, e: 'abc'}",
+                &None,
+                CompilerSettings {
+                    enable_constant_inlining: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let mut chunk = Chunk::clone(&chunk);
+        chunk
+            .imported_constants
+            .insert("foo".into(), chunk.exported_constants.clone());
+
+        let deserialized = Chunk::deserialize(&chunk.serialize()).unwrap();
+
+        assert_eq!(deserialized.exported_constants.len(), 5);
+        assert_eq!(deserialized.exported_constants, chunk.exported_constants);
+        assert_eq!(deserialized.imported_constants, chunk.imported_constants);
+    }
+
+    #[test]
+    fn round_trip_with_source_path() {
+        let mut chunk = compile("42");
+        chunk.source_path = Some(PathBuf::from("foo/bar.koto"));
+
+        let deserialized = Chunk::deserialize(&chunk.serialize()).unwrap();
+
+        assert_eq!(deserialized.source_path, chunk.source_path);
+    }
+
+    #[test]
+    fn strip_source() {
+        let mut chunk = compile("secret = 'hunter2'\nsecret.size()");
+        chunk.source_path = Some(PathBuf::from("/home/foo/secret.koto"));
+
+        let data = chunk.serialize_with_settings(SerializeSettings { strip_source: true });
+        let contains = |needle: &str| data.windows(needle.len()).any(|w| w == needle.as_bytes());
+        assert!(!contains("secret.size()"));
+        assert!(!contains("/home/foo"));
+
+        let deserialized = Chunk::deserialize(&data).unwrap();
+        assert_eq!(deserialized.bytes, chunk.bytes);
+        assert_eq!(deserialized.source_path, None);
+        assert_eq!(deserialized.debug_info.source, "");
+        assert_eq!(
+            deserialized.debug_info.get_source_span(0),
+            chunk.debug_info.get_source_span(0)
+        );
+    }
+
+    #[test]
+    fn loader_imports_precompiled_module() {
+        let dir = std::env::temp_dir().join(format!("koto_kbc_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let chunk = compile("export foo = 42");
+        std::fs::write(dir.join("foo.kbc"), chunk.serialize()).unwrap();
+
+        let result = Loader::default().compile_module("foo", Some(dir.clone()));
+        std::fs::remove_dir_all(&dir).ok();
+
+        let result = result.unwrap();
+        assert_eq!(result.chunk.bytes, chunk.bytes);
+        assert_eq!(result.path.extension(), Some("kbc".as_ref()));
+        assert_eq!(result.chunk.source_path.as_ref(), Some(&result.path));
+    }
+
+    #[test]
+    fn loader_rejects_invalid_precompiled_module() {
+        let dir = std::env::temp_dir().join(format!("koto_kbc_invalid_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut chunk = Chunk::clone(&compile("export foo = 42"));
+        // Return a register that's never written to
+        chunk.bytes = vec![Op::Return as u8, 99].into();
+        std::fs::write(dir.join("foo.kbc"), chunk.serialize()).unwrap();
+
+        let result = Loader::default().compile_module("foo", Some(dir.clone()));
+        std::fs::remove_dir_all(&dir).ok();
+
+        match result {
+            Err(error) => assert!(
+                matches!(error.kind(), LoaderErrorKind::Verify(_)),
+                "{error}"
+            ),
+            Ok(_) => panic!("Expected the module to fail verification"),
+        }
+    }
+
+    #[test]
+    fn invalid_magic() {
+        assert_eq!(
+            Chunk::deserialize(b"print 'hello'"),
+            Err(DeserializeError::NotBytecode)
+        );
+    }
+
+    #[test]
+    fn unsupported_format_version() {
+        let mut data = compile("42").serialize();
+        data[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&999u32.to_le_bytes());
+
+        assert_eq!(
+            Chunk::deserialize(&data),
+            Err(DeserializeError::UnsupportedFormatVersion {
+                found: 999,
+                expected: BYTECODE_FORMAT_VERSION
+            })
+        );
+    }
+
+    #[test]
+    fn truncated_data() {
+        let data = compile("x = 'hello'").serialize();
+
+        for len in 0..data.len() {
+            assert!(Chunk::deserialize(&data[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn span_with_end_before_start() {
+        let mut chunk = compile("x = 'hello'");
+        let span = &mut chunk.debug_info.source_map[0].1;
+        span.end = Position {
+            line: span.start.line,
+            column: span.start.column - 1,
+        };
+
+        assert_eq!(
+            Chunk::deserialize(&chunk.serialize()),
+            Err(DeserializeError::InvalidData("invalid span"))
+        );
+    }
+
+    #[test]
+    fn span_past_the_end_of_the_source() {
+        let mut chunk = compile("x = 'hello'");
+        chunk.debug_info.source_map[0].1.end.line = 2;
+
+        assert_eq!(
+            Chunk::deserialize(&chunk.serialize()),
+            Err(DeserializeError::InvalidData("invalid span"))
+        );
+    }
+
+    #[test]
+    fn duplicate_constants() {
+        let mut data = compile("x = 'abc'\ny = 'abd'").serialize();
+        // Rename 'abd' to 'abc', producing a pool with a duplicate constant
+        let index = data.windows(3).position(|w| w == b"abd").unwrap();
+        data[index + 2] = b'c';
+
+        assert_eq!(
+            Chunk::deserialize(&data),
+            Err(DeserializeError::InvalidData("duplicate constants"))
+        );
+    }
+}
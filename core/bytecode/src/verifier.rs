@@ -0,0 +1,1136 @@
+use crate::{Chunk, Instruction, InstructionReader};
+use koto_memory::Ptr;
+use koto_parser::Constant;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// The kinds of error that can be returned from [Chunk::verify]
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum VerifyErrorKind {
+    #[error("{0}")]
+    InvalidInstruction(String),
+    #[error("The instruction extends past the end of its function")]
+    InstructionCrossesFunctionEnd,
+    #[error("Register {register} is used, but only {register_count} registers are available")]
+    RegisterOutOfRange {
+        register: usize,
+        register_count: usize,
+    },
+    #[error("Capture {index} is out of range, the function has {capture_count} captures")]
+    CaptureOutOfRange { index: u8, capture_count: u8 },
+    #[error("Constant {index} is out of range, the chunk contains {constant_count} constants")]
+    ConstantOutOfRange { index: u32, constant_count: usize },
+    #[error("Expected a {expected} constant at index {index}")]
+    UnexpectedConstantType { index: u32, expected: &'static str },
+    #[error("The jump target {target} is outside of the function")]
+    JumpOutOfRange { target: usize },
+    #[error("The jump target {target} isn't at the start of an instruction")]
+    JumpIntoInstruction { target: usize },
+    #[error("The function's size ({size}) extends past the end of the enclosing function")]
+    FunctionSizeOutOfRange { size: u16 },
+}
+
+/// The error type returned from [Chunk::verify]
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+#[error("{kind} (at instruction {ip})")]
+pub struct VerifyError {
+    /// The kind of error that was found
+    pub kind: VerifyErrorKind,
+    /// The position in the chunk's bytes of the instruction that caused the error
+    pub ip: usize,
+}
+
+type Result<T> = std::result::Result<T, VerifyError>;
+
+impl Chunk {
+    /// Checks that the chunk's bytecode is well-formed
+    ///
+    /// The runtime trusts that the chunk's instructions are valid, so chunks that come from
+    /// untrusted sources (e.g. from a cache, or from [Chunk::deserialize]) should be verified
+    /// before they're run.
+    ///
+    /// The following checks are performed:
+    ///   - Each instruction can be decoded.
+    ///   - Registers that are read by an instruction are either arguments or captures of the
+    ///     function, or have been written to on every path that leads to the instruction.
+    ///   - Captures are in range of the capture count of the function they're assigned to.
+    ///   - Constants are in range of the chunk's constant pool, and have the expected type.
+    ///   - Jumps land at the start of an instruction within the same function.
+    ///   - Function bodies don't extend past the end of their enclosing function.
+    pub fn verify(chunk: Ptr<Chunk>) -> Result<()> {
+        Verifier::new(&chunk).verify(InstructionReader::new(chunk.clone()))
+    }
+}
+
+// A function body, or the chunk's top-level instructions
+struct Scope {
+    id: usize,
+    end: usize,
+    // The number of registers that are initialized when the function is called
+    initial_register_count: usize,
+    // The function's instructions, in the order that they appear in the chunk
+    steps: Vec<Step>,
+    // The capture counts of functions that have been created in registers,
+    // updated as instructions are read
+    function_captures: HashMap<u8, u8>,
+}
+
+// The registers that are read and written by an instruction, along with its control flow
+#[derive(Default)]
+struct Step {
+    ip: usize,
+    reads: Vec<usize>,
+    // Changes to the available registers when execution continues to the next instruction
+    effects: Vec<Effect>,
+    // The instruction's jump target, with the number of registers that are available after
+    // jumping if it differs from the count before the instruction
+    jump: Option<(usize, Option<usize>)>,
+    // False if the instruction never continues to the next instruction
+    continues: bool,
+}
+
+enum Effect {
+    Write(usize),
+    // Registers from the call's frame base are dropped when the call returns
+    Call { result: usize, frame_base: usize },
+}
+
+impl Step {
+    fn register_count_after(&self, register_count: usize) -> usize {
+        self.effects
+            .iter()
+            .fold(register_count, |count, effect| match effect {
+                Effect::Write(register) => count.max(register + 1),
+                Effect::Call { result, frame_base } => count.max(result + 1).min(*frame_base),
+            })
+    }
+}
+
+impl Scope {
+    fn new(id: usize, end: usize, initial_register_count: usize) -> Self {
+        Self {
+            id,
+            end,
+            initial_register_count,
+            steps: Vec::new(),
+            function_captures: HashMap::new(),
+        }
+    }
+
+    // Checks that each read register is available on every path that reaches the instruction
+    //
+    // The number of registers that are guaranteed to be available is propagated along the
+    // function's control flow, taking the smallest count where paths meet.
+    fn finish(self) -> Result<()> {
+        let step_indices: HashMap<usize, usize> = self
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(index, step)| (step.ip, index))
+            .collect();
+
+        // The available register count for each instruction, None if it's unreachable
+        let mut register_counts = vec![None; self.steps.len()];
+        let mut pending = Vec::new();
+        if !self.steps.is_empty() {
+            register_counts[0] = Some(self.initial_register_count);
+            pending.push(0);
+        }
+
+        while let Some(index) = pending.pop() {
+            let step = &self.steps[index];
+            let register_count = register_counts[index].unwrap();
+
+            let mut successors = Vec::with_capacity(2);
+            if step.continues && index + 1 < self.steps.len() {
+                successors.push((index + 1, step.register_count_after(register_count)));
+            }
+            // Jumps that leave the function are reported by verify_jumps
+            if let Some((target, jump_register_count)) = step.jump {
+                if let Some(&target_index) = step_indices.get(&target) {
+                    successors.push((target_index, jump_register_count.unwrap_or(register_count)));
+                }
+            }
+
+            for (successor, count) in successors {
+                match register_counts[successor] {
+                    Some(existing) if existing <= count => {}
+                    _ => {
+                        register_counts[successor] = Some(count);
+                        pending.push(successor);
+                    }
+                }
+            }
+        }
+
+        for (step, register_count) in self.steps.iter().zip(register_counts) {
+            let Some(register_count) = register_count else {
+                continue;
+            };
+
+            if let Some(&register) = step
+                .reads
+                .iter()
+                .find(|register| **register >= register_count)
+            {
+                return Err(VerifyError {
+                    kind: VerifyErrorKind::RegisterOutOfRange {
+                        register,
+                        register_count,
+                    },
+                    ip: step.ip,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Jump {
+    ip: usize,
+    target: usize,
+    scope: usize,
+    scope_end: usize,
+}
+
+struct Verifier<'a> {
+    chunk: &'a Chunk,
+    scopes: Vec<Scope>,
+    next_scope_id: usize,
+    // The start of each instruction, mapped to the id of the scope that contains it
+    instruction_starts: HashMap<usize, usize>,
+    jumps: Vec<Jump>,
+    // The ip of the instruction that's currently being verified
+    ip: usize,
+}
+
+impl<'a> Verifier<'a> {
+    fn new(chunk: &'a Chunk) -> Self {
+        Self {
+            chunk,
+            scopes: vec![Scope::new(0, chunk.bytes.len(), 0)],
+            next_scope_id: 1,
+            instruction_starts: HashMap::new(),
+            jumps: Vec::new(),
+            ip: 0,
+        }
+    }
+
+    fn verify(mut self, mut reader: InstructionReader) -> Result<()> {
+        loop {
+            self.ip = reader.ip;
+
+            // Finish any function bodies that end at the current instruction
+            while self.scopes.len() > 1 && self.scope().end == self.ip {
+                self.scopes.pop().unwrap().finish()?;
+            }
+
+            let Some(instruction) = reader.next() else {
+                break;
+            };
+
+            let scope_end = self.scope().end;
+            if reader.ip > scope_end {
+                return self.error(VerifyErrorKind::InstructionCrossesFunctionEnd);
+            }
+
+            let ip = self.ip;
+            let scope = self.scope();
+            scope.steps.push(Step {
+                ip,
+                continues: true,
+                ..Default::default()
+            });
+            let scope_id = scope.id;
+            self.instruction_starts.insert(ip, scope_id);
+
+            self.verify_instruction(instruction, reader.ip)?;
+        }
+
+        // Only the top-level scope should remain after all instructions have been read
+        debug_assert_eq!(self.scopes.len(), 1);
+        if let Some(scope) = self.scopes.pop() {
+            scope.finish()?;
+        }
+
+        self.verify_jumps()
+    }
+
+    fn verify_instruction(&mut self, instruction: Instruction, next_ip: usize) -> Result<()> {
+        use Instruction::*;
+
+        match instruction {
+            Error { message } => return self.error(VerifyErrorKind::InvalidInstruction(message)),
+            Copy { target, source } => {
+                self.write(target);
+                self.read(source);
+            }
+            SetNull { register } | SetBool { register, .. } | SetNumber { register, .. } => {
+                self.write(register)
+            }
+            LoadFloat { register, constant } => {
+                self.write(register);
+                self.constant(constant, "float", |c| matches!(c, Constant::F64(_)))?;
+            }
+            LoadInt { register, constant } => {
+                self.write(register);
+                self.constant(constant, "integer", |c| matches!(c, Constant::I64(_)))?;
+            }
+            LoadString { register, constant } | LoadNonLocal { register, constant } => {
+                self.write(register);
+                self.string_constant(constant)?;
+            }
+            ValueExport { name, value } => {
+                self.read(name);
+                self.read(value);
+            }
+            Import { register } => {
+                self.read(register);
+                self.write(register);
+            }
+            MakeTempTuple {
+                register,
+                start,
+                count,
+            } => {
+                self.write(register);
+                self.read_range(start, count);
+            }
+            TempTupleToTuple { register, source } => {
+                self.write(register);
+                self.read(source);
+            }
+            MakeMap { register, .. } => self.write(register),
+            SequenceStart { .. } | StringStart { .. } | TryEnd => {}
+            SequencePush { value } | StringPush { value } => self.read(value),
+            StringPushFormat { value, options } => {
+                self.read(value);
+                if let Some(fill_character) = options.fill_character {
+                    self.string_constant(fill_character)?;
+                }
+            }
+            SequencePushN { start, count } => self.read_range(start, count),
+            SequenceToList { register }
+            | SequenceToTuple { register }
+            | StringFinish { register }
+            | RangeFull { register } => self.write(register),
+            Range {
+                register,
+                start,
+                end,
+            }
+            | RangeInclusive {
+                register,
+                start,
+                end,
+            } => {
+                self.write(register);
+                self.read(start);
+                self.read(end);
+            }
+            RangeTo { register, end } | RangeToInclusive { register, end } => {
+                self.write(register);
+                self.read(end);
+            }
+            RangeFrom { register, start } => {
+                self.write(register);
+                self.read(start);
+            }
+            MakeIterator { register, iterable } => {
+                self.write(register);
+                self.read(iterable);
+            }
+            Function {
+                register,
+                arg_count,
+                capture_count,
+                size,
+                ..
+            } => {
+                self.write(register);
+                self.scope()
+                    .function_captures
+                    .insert(register, capture_count);
+
+                let end = next_ip + size as usize;
+                if end > self.scope().end {
+                    return self.error(VerifyErrorKind::FunctionSizeOutOfRange { size });
+                }
+
+                // The function's instance is placed in the first register, followed by the
+                // function's args and captures.
+                let initial_register_count = 1 + arg_count as usize + capture_count as usize;
+                let id = self.next_scope_id;
+                self.next_scope_id += 1;
+                self.scopes
+                    .push(Scope::new(id, end, initial_register_count));
+            }
+            Capture {
+                function,
+                target,
+                source,
+            } => {
+                // The function register isn't checked, the runtime reports an error if a
+                // temporary function has already been dropped when its captures are assigned.
+                self.read(source);
+
+                // Captures follow the function's creation, so the function's capture count
+                // is taken from the last function that was created in the register.
+                let capture_count = self
+                    .scope()
+                    .function_captures
+                    .get(&function)
+                    .copied()
+                    .unwrap_or(0);
+                if target >= capture_count {
+                    return self.error(VerifyErrorKind::CaptureOutOfRange {
+                        index: target,
+                        capture_count,
+                    });
+                }
+            }
+            Negate { register, value }
+            | Not { register, value }
+            | Size { register, value }
+            | IsTuple { register, value }
+            | IsList { register, value }
+            | IsMap { register, value }
+            | TempIndex {
+                register, value, ..
+            }
+            | SliceFrom {
+                register, value, ..
+            }
+            | SliceTo {
+                register, value, ..
+            } => {
+                self.write(register);
+                self.read(value);
+            }
+            Add { register, lhs, rhs }
+            | Subtract { register, lhs, rhs }
+            | Multiply { register, lhs, rhs }
+            | Divide { register, lhs, rhs }
+            | Remainder { register, lhs, rhs }
+            | Less { register, lhs, rhs }
+            | LessOrEqual { register, lhs, rhs }
+            | Greater { register, lhs, rhs }
+            | GreaterOrEqual { register, lhs, rhs }
+            | Equal { register, lhs, rhs }
+            | NotEqual { register, lhs, rhs } => {
+                self.write(register);
+                self.read(lhs);
+                self.read(rhs);
+            }
+            AddAssign { lhs, rhs }
+            | SubtractAssign { lhs, rhs }
+            | MultiplyAssign { lhs, rhs }
+            | DivideAssign { lhs, rhs }
+            | RemainderAssign { lhs, rhs } => {
+                self.read(lhs);
+                self.read(rhs);
+                self.write(lhs);
+            }
+            Jump { offset } => {
+                self.jump(next_ip + offset as usize);
+                self.stop();
+            }
+            JumpBack { offset } => match next_ip.checked_sub(offset as usize) {
+                Some(target) => {
+                    self.jump(target);
+                    self.stop();
+                }
+                None => {
+                    return self.error(VerifyErrorKind::JumpOutOfRange {
+                        target: next_ip.wrapping_sub(offset as usize),
+                    })
+                }
+            },
+            JumpIfTrue { register, offset } | JumpIfFalse { register, offset } => {
+                self.read(register);
+                self.jump(next_ip + offset as usize);
+            }
+            Call {
+                result,
+                function,
+                frame_base,
+                arg_count,
+            }
+            | TailCall {
+                result,
+                function,
+                frame_base,
+                arg_count,
+            } => {
+                self.read(function);
+                self.call(result, frame_base, arg_count);
+            }
+            CallInstance {
+                result,
+                function,
+                frame_base,
+                arg_count,
+                instance,
+            }
+            | TailCallInstance {
+                result,
+                function,
+                frame_base,
+                arg_count,
+                instance,
+            } => {
+                self.read(function);
+                self.read(instance);
+                self.call(result, frame_base, arg_count);
+            }
+            Return { register } | Throw { register } => {
+                self.read(register);
+                self.stop();
+            }
+            Yield { register } => self.read(register),
+            IterNext {
+                result,
+                iterator,
+                jump_offset,
+                ..
+            } => {
+                self.read(iterator);
+                if let Some(result) = result {
+                    self.write(result);
+                }
+                self.jump(next_ip + jump_offset as usize);
+            }
+            Index {
+                register,
+                value,
+                index,
+            } => {
+                self.write(register);
+                self.read(value);
+                self.read(index);
+            }
+            SetIndex {
+                register,
+                index,
+                value,
+            }
+            | MapInsert {
+                register,
+                key: index,
+                value,
+            } => {
+                self.read(register);
+                self.read(index);
+                self.read(value);
+            }
+            MetaInsert {
+                register, value, ..
+            } => {
+                self.read(register);
+                self.read(value);
+            }
+            MetaInsertNamed {
+                register,
+                value,
+                name,
+                ..
+            } => {
+                self.read(register);
+                self.read(value);
+                self.read(name);
+            }
+            MetaExport { value, .. } => self.read(value),
+            MetaExportNamed { name, value, .. } => {
+                self.read(name);
+                self.read(value);
+            }
+            Access {
+                register,
+                value,
+                key,
+            } => {
+                self.write(register);
+                self.read(value);
+                self.string_constant(key)?;
+            }
+            AccessString {
+                register,
+                value,
+                key,
+            } => {
+                self.write(register);
+                self.read(value);
+                self.read(key);
+            }
+            AccessCall {
+                result,
+                instance,
+                frame_base,
+                arg_count,
+                key,
+            } => {
+                self.read(instance);
+                self.call(result, frame_base, arg_count);
+                self.string_constant(key)?;
+            }
+            TryStart {
+                arg_register,
+                catch_offset,
+            } => {
+                // The error is placed in the arg register when jumping to the catch block,
+                // any registers following it may have been dropped by calls in the try block.
+                self.add_jump(
+                    next_ip + catch_offset as usize,
+                    Some(arg_register as usize + 1),
+                );
+            }
+            Debug { register, constant } => {
+                self.read(register);
+                self.string_constant(constant)?;
+            }
+            CheckType { register, .. }
+            | CheckSizeEqual { register, .. }
+            | CheckSizeMin { register, .. } => self.read(register),
+            AssertType {
+                register,
+                type_constant,
+            } => {
+                self.read(register);
+                self.string_constant(type_constant)?;
+            }
+            MatchesType {
+                register,
+                value,
+                type_constant,
+            } => {
+                self.write(register);
+                self.read(value);
+                self.string_constant(type_constant)?;
+            }
+            MapRest {
+                register,
+                map,
+                start,
+                count,
+            } => {
+                self.write(register);
+                self.read(map);
+                self.read_range(start, count);
+            }
+            AccessKey {
+                register,
+                map,
+                key,
+                jump_offset,
+            } => {
+                self.read(map);
+                self.string_constant(key)?;
+                self.write(register);
+                if let Some(jump_offset) = jump_offset {
+                    self.jump(next_ip + jump_offset as usize);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn scope(&mut self) -> &mut Scope {
+        // The top-level scope is only removed once all instructions have been verified
+        self.scopes.last_mut().unwrap()
+    }
+
+    // The step of the instruction that's currently being verified
+    fn current_step(&mut self) -> &mut Step {
+        // A step is added for each instruction before it's verified
+        self.scope().steps.last_mut().unwrap()
+    }
+
+    fn read(&mut self, register: u8) {
+        self.current_step().reads.push(register as usize);
+    }
+
+    fn read_range(&mut self, start: u8, count: u8) {
+        let step = self.current_step();
+        step.reads
+            .extend(start as usize..start as usize + count as usize);
+    }
+
+    fn write(&mut self, register: u8) {
+        self.scope().function_captures.remove(&register);
+        self.current_step()
+            .effects
+            .push(Effect::Write(register as usize));
+    }
+
+    // Calls place the instance in the frame base register, followed by the call's args
+    fn call(&mut self, result: u8, frame_base: u8, arg_count: u8) {
+        self.read_range(frame_base.saturating_add(1), arg_count);
+        self.scope().function_captures.remove(&result);
+        self.current_step().effects.push(Effect::Call {
+            result: result as usize,
+            frame_base: frame_base as usize,
+        });
+    }
+
+    // Marks the current instruction as never continuing to the next instruction
+    fn stop(&mut self) {
+        self.current_step().continues = false;
+    }
+
+    fn jump(&mut self, target: usize) {
+        self.add_jump(target, None);
+    }
+
+    // Adds a jump from the current instruction, along with the number of registers that are
+    // available after jumping if it differs from the count before the instruction.
+    fn add_jump(&mut self, target: usize, register_count: Option<usize>) {
+        let ip = self.ip;
+        self.current_step().jump = Some((target, register_count));
+        let scope = self.scope();
+        let jump = Jump {
+            ip,
+            target,
+            scope: scope.id,
+            scope_end: scope.end,
+        };
+        self.jumps.push(jump);
+    }
+
+    fn constant(
+        &self,
+        index: u32,
+        expected: &'static str,
+        matches_type: impl Fn(&Constant) -> bool,
+    ) -> Result<()> {
+        match self.chunk.constants.get(index as usize) {
+            Some(constant) if matches_type(&constant) => Ok(()),
+            Some(_) => self.error(VerifyErrorKind::UnexpectedConstantType { index, expected }),
+            None => self.error(VerifyErrorKind::ConstantOutOfRange {
+                index,
+                constant_count: self.chunk.constants.size(),
+            }),
+        }
+    }
+
+    fn string_constant(&self, index: u32) -> Result<()> {
+        self.constant(index, "string", |c| matches!(c, Constant::Str(_)))
+    }
+
+    fn verify_jumps(&self) -> Result<()> {
+        for jump in self.jumps.iter() {
+            let error = |kind| Err(VerifyError { kind, ip: jump.ip });
+
+            match self.instruction_starts.get(&jump.target) {
+                Some(scope) if *scope == jump.scope => {}
+                // Jumping to the end of the chunk finishes execution
+                None if jump.scope == 0 && jump.target == jump.scope_end => {}
+                Some(_) => {
+                    return error(VerifyErrorKind::JumpOutOfRange {
+                        target: jump.target,
+                    })
+                }
+                None if jump.target >= jump.scope_end => {
+                    return error(VerifyErrorKind::JumpOutOfRange {
+                        target: jump.target,
+                    })
+                }
+                None => {
+                    return error(VerifyErrorKind::JumpIntoInstruction {
+                        target: jump.target,
+                    })
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn error<T>(&self, kind: VerifyErrorKind) -> Result<T> {
+        Err(VerifyError { kind, ip: self.ip })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CompilerSettings, DebugInfo, Loader, Op, StringFormatFlags};
+    use koto_parser::ConstantPool;
+
+    fn verify(bytes: &[u8], constants: &[Constant]) -> Result<()> {
+        let constants = ConstantPool::from_constants(constants.iter().cloned()).unwrap();
+        let chunk = Chunk::new(bytes.into(), constants, None, DebugInfo::default());
+        Chunk::verify(chunk.into())
+    }
+
+    fn check_error(bytes: &[u8], constants: &[Constant], expected: VerifyErrorKind, ip: usize) {
+        assert_eq!(
+            verify(bytes, constants),
+            Err(VerifyError { kind: expected, ip })
+        );
+    }
+
+    #[test]
+    fn compiled_script() {
+        let script = "
+f = |x, y...|
+  for i in 0..x
+    if i > 2 then return y
+  try
+    g = || x + 1.5
+    g()
+  catch e
+    debug e
+z = {foo: 42, bar: 'abc'}
+f z.foo, z.bar
+";
+        let chunk = Loader::default()
+            .compile_script(script, &None, CompilerSettings::default())
+            .unwrap();
+        assert_eq!(Chunk::verify(chunk), Ok(()));
+    }
+
+    #[test]
+    fn invalid_opcode() {
+        let result = verify(&[Op::Unused255 as u8], &[]);
+        assert!(
+            matches!(
+                result,
+                Err(VerifyError {
+                    kind: VerifyErrorKind::InvalidInstruction(_),
+                    ip: 0
+                })
+            ),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn truncated_instruction() {
+        let result = verify(&[Op::SetNull as u8, 0, Op::Copy as u8, 1], &[]);
+        assert!(
+            matches!(
+                result,
+                Err(VerifyError {
+                    kind: VerifyErrorKind::InvalidInstruction(_),
+                    ip: 2
+                })
+            ),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn register_read_before_being_written() {
+        check_error(
+            &[Op::SetNull as u8, 0, Op::Add as u8, 1, 0, 4],
+            &[],
+            VerifyErrorKind::RegisterOutOfRange {
+                register: 4,
+                register_count: 1,
+            },
+            2,
+        );
+    }
+
+    #[test]
+    fn register_written_after_being_read() {
+        check_error(
+            &[
+                Op::Copy as u8,
+                0,
+                3,
+                Op::SetNull as u8,
+                3,
+                Op::Return as u8,
+                0,
+            ],
+            &[],
+            VerifyErrorKind::RegisterOutOfRange {
+                register: 3,
+                register_count: 0,
+            },
+            0,
+        );
+    }
+
+    #[test]
+    fn register_write_skipped_by_jump() {
+        let bytes = [
+            Op::SetFalse as u8,
+            0,
+            Op::JumpIfFalse as u8,
+            0,
+            2, // offset
+            0,
+            Op::SetNull as u8,
+            1,
+            Op::Return as u8,
+            1,
+        ];
+        check_error(
+            &bytes,
+            &[],
+            VerifyErrorKind::RegisterOutOfRange {
+                register: 1,
+                register_count: 1,
+            },
+            8,
+        );
+    }
+
+    #[test]
+    fn registers_after_frame_base_are_dropped_by_calls() {
+        let bytes = [
+            Op::SetNull as u8,
+            0,
+            Op::SetNull as u8,
+            1,
+            Op::Call as u8,
+            0, // result
+            0, // function
+            1, // frame base
+            0, // arg count
+            Op::Return as u8,
+            1,
+        ];
+        check_error(
+            &bytes,
+            &[],
+            VerifyErrorKind::RegisterOutOfRange {
+                register: 1,
+                register_count: 1,
+            },
+            9,
+        );
+    }
+
+    #[test]
+    fn function_args_are_available() {
+        let bytes = [
+            Op::Function as u8,
+            0, // register
+            2, // arg count
+            1, // capture count
+            0, // flags
+            2, // size
+            0,
+            Op::Return as u8,
+            3, // The function's captured value
+            Op::Return as u8,
+            0,
+        ];
+        assert_eq!(verify(&bytes, &[]), Ok(()));
+    }
+
+    #[test]
+    fn register_outside_of_function() {
+        let bytes = [
+            Op::Function as u8,
+            0, // register
+            1, // arg count
+            0, // capture count
+            0, // flags
+            2, // size
+            0,
+            Op::Return as u8,
+            2,
+        ];
+        check_error(
+            &bytes,
+            &[],
+            VerifyErrorKind::RegisterOutOfRange {
+                register: 2,
+                register_count: 2,
+            },
+            7,
+        );
+    }
+
+    #[test]
+    fn capture_out_of_range() {
+        let bytes = [
+            Op::SetNull as u8,
+            1,
+            Op::Function as u8,
+            0, // register
+            0, // arg count
+            1, // capture count
+            0, // flags
+            2, // size
+            0,
+            Op::Return as u8,
+            1, // The function's captured value
+            Op::Capture as u8,
+            0, // function
+            1, // capture index
+            1, // source
+        ];
+        check_error(
+            &bytes,
+            &[],
+            VerifyErrorKind::CaptureOutOfRange {
+                index: 1,
+                capture_count: 1,
+            },
+            11,
+        );
+    }
+
+    #[test]
+    fn constant_out_of_range() {
+        check_error(
+            &[Op::LoadString as u8, 0, 1],
+            &[Constant::Str("abc")],
+            VerifyErrorKind::ConstantOutOfRange {
+                index: 1,
+                constant_count: 1,
+            },
+            0,
+        );
+    }
+
+    #[test]
+    fn unexpected_constant_type() {
+        check_error(
+            &[Op::SetNull as u8, 0, Op::Access as u8, 1, 0, 0],
+            &[Constant::F64(1.0)],
+            VerifyErrorKind::UnexpectedConstantType {
+                index: 0,
+                expected: "string",
+            },
+            2,
+        );
+    }
+
+    #[test]
+    fn string_format_fill_character_must_be_a_string() {
+        check_error(
+            &[
+                Op::SetNull as u8,
+                0,
+                Op::StringStart as u8,
+                1,
+                Op::StringPushFormat as u8,
+                0,
+                StringFormatFlags::FILL_CHARACTER as u8,
+                0,
+                0,
+            ],
+            &[Constant::F64(1.0)],
+            VerifyErrorKind::UnexpectedConstantType {
+                index: 0,
+                expected: "string",
+            },
+            4,
+        );
+    }
+
+    #[test]
+    fn jump_past_the_end_of_the_chunk() {
+        check_error(
+            &[Op::Jump as u8, 1, 0],
+            &[],
+            VerifyErrorKind::JumpOutOfRange { target: 4 },
+            0,
+        );
+    }
+
+    #[test]
+    fn jump_to_the_end_of_the_chunk() {
+        assert_eq!(
+            verify(&[Op::Jump as u8, 2, 0, Op::SetNull as u8, 0], &[]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn jump_back_before_the_start_of_the_chunk() {
+        check_error(
+            &[Op::JumpBack as u8, 4, 0],
+            &[],
+            VerifyErrorKind::JumpOutOfRange {
+                target: 3usize.wrapping_sub(4),
+            },
+            0,
+        );
+    }
+
+    #[test]
+    fn jump_into_instruction() {
+        check_error(
+            &[Op::Jump as u8, 1, 0, Op::SetNull as u8, 0],
+            &[],
+            VerifyErrorKind::JumpIntoInstruction { target: 4 },
+            0,
+        );
+    }
+
+    #[test]
+    fn jump_out_of_function() {
+        let bytes = [
+            Op::Function as u8,
+            0, // register
+            0, // arg count
+            0, // capture count
+            0, // flags
+            3, // size
+            0,
+            Op::Jump as u8,
+            0,
+            0,
+            Op::SetNull as u8,
+            1,
+        ];
+        check_error(
+            &bytes,
+            &[],
+            VerifyErrorKind::JumpOutOfRange { target: 10 },
+            7,
+        );
+    }
+
+    #[test]
+    fn function_size_out_of_range() {
+        let bytes = [
+            Op::Function as u8,
+            0, // register
+            0, // arg count
+            0, // capture count
+            0, // flags
+            3, // size
+            0,
+            Op::Return as u8,
+            0,
+        ];
+        check_error(
+            &bytes,
+            &[],
+            VerifyErrorKind::FunctionSizeOutOfRange { size: 3 },
+            0,
+        );
+    }
+
+    #[test]
+    fn instruction_crosses_function_end() {
+        let bytes = [
+            Op::Function as u8,
+            0, // register
+            0, // arg count
+            0, // capture count
+            0, // flags
+            1, // size
+            0,
+            Op::Return as u8,
+            0,
+        ];
+        check_error(
+            &bytes,
+            &[],
+            VerifyErrorKind::InstructionCrossesFunctionEnd,
+            7,
+        );
+    }
+}
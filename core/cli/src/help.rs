@@ -1,5 +1,4 @@
 use indexmap::IndexMap;
-use std::iter::Peekable;
 
 const HELP_RESULT_STR: &str = "➝ ";
 const HELP_INDENT: usize = 2;
@@ -16,61 +15,18 @@ pub struct Help {
 
 impl Help {
     pub fn new() -> Self {
-        macro_rules! include_doc {
-            ($doc:expr) => {
-                // Including via a symlink to the top-level docs folder to ensure cargo-package
-                // can find it during packaging.
-                include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/docs/", $doc))
-            };
-        }
-
-        let guide_files = [
-            include_doc!("language/basics.md"),
-            include_doc!("language/conditional_expressions.md"),
-            include_doc!("language/core_library.md"),
-            include_doc!("language/errors.md"),
-            include_doc!("language/functions.md"),
-            include_doc!("language/functions_advanced.md"),
-            include_doc!("language/generators.md"),
-            include_doc!("language/iterators.md"),
-            include_doc!("language/lists.md"),
-            include_doc!("language/loops.md"),
-            include_doc!("language/maps.md"),
-            include_doc!("language/meta_maps.md"),
-            include_doc!("language/modules.md"),
-            include_doc!("language/prelude.md"),
-            include_doc!("language/ranges.md"),
-            include_doc!("language/strings.md"),
-            include_doc!("language/testing.md"),
-            include_doc!("language/tuples.md"),
-            include_doc!("language/value_unpacking.md"),
-        ];
-
-        let reference_files = [
-            include_doc!("core_lib/io.md"),
-            include_doc!("core_lib/iterator.md"),
-            include_doc!("core_lib/koto.md"),
-            include_doc!("core_lib/list.md"),
-            include_doc!("core_lib/map.md"),
-            include_doc!("core_lib/number.md"),
-            include_doc!("core_lib/os.md"),
-            include_doc!("core_lib/range.md"),
-            include_doc!("core_lib/string.md"),
-            include_doc!("core_lib/test.md"),
-            include_doc!("core_lib/tuple.md"),
-        ];
-
         let mut result = Self {
             help_map: IndexMap::new(),
             module_names: Vec::new(),
         };
 
-        for file_contents in guide_files.iter() {
-            result.add_help_from_guide(file_contents);
+        for doc in koto_help::language_guide() {
+            result.add_help_from_doc(doc);
         }
 
-        for file_contents in reference_files.iter() {
-            result.add_help_from_reference(file_contents);
+        for doc in koto_help::core_library() {
+            result.module_names.push(doc.name.clone());
+            result.add_help_from_doc(doc);
         }
 
         result
@@ -194,56 +150,19 @@ impl Help {
         }
     }
 
-    fn add_help_from_guide(&mut self, markdown: &str) {
-        let mut parser = pulldown_cmark::Parser::new(markdown).peekable();
-
-        // Consume the module overview section
-        let (file_name, help) = consume_help_section(&mut parser, None);
-        if !help.trim().is_empty() {
-            self.help_map.insert(
-                text_to_key(&file_name),
-                HelpEntry {
-                    name: file_name,
-                    help,
-                },
-            );
-        }
-
-        // Add sub-topics
-        while parser.peek().is_some() {
-            let (entry_name, help) = consume_help_section(&mut parser, None);
-            self.help_map.insert(
-                text_to_key(&entry_name),
-                HelpEntry {
-                    name: entry_name,
-                    help,
-                },
-            );
-        }
-    }
-
-    fn add_help_from_reference(&mut self, markdown: &str) {
-        let mut parser = pulldown_cmark::Parser::new(markdown).peekable();
+    fn add_help_from_doc(&mut self, doc: koto_help::HelpDoc) {
+        for (i, section) in doc.sections.into_iter().enumerate() {
+            let help = render_markdown(&section.markdown);
 
-        let (module_name, help) = consume_help_section(&mut parser, None);
-        if !help.trim().is_empty() {
-            self.help_map.insert(
-                text_to_key(&module_name),
-                HelpEntry {
-                    name: module_name.clone(),
-                    help,
-                },
-            );
-        }
-        self.module_names.push(module_name.clone());
+            // Skip overviews that only contain a title
+            if i == 0 && help.trim().is_empty() {
+                continue;
+            }
 
-        // Consume each module entry
-        while parser.peek().is_some() {
-            let (entry_name, help) = consume_help_section(&mut parser, Some(&module_name));
             self.help_map.insert(
-                text_to_key(&entry_name),
+                text_to_key(&section.name),
                 HelpEntry {
-                    name: entry_name,
+                    name: section.name,
                     help,
                 },
             );
@@ -255,14 +174,12 @@ fn text_to_key(text: &str) -> String {
     text.trim().to_lowercase().replace(' ', "_")
 }
 
-fn consume_help_section(
-    parser: &mut Peekable<pulldown_cmark::Parser>,
-    module_name: Option<&str>,
-) -> (String, String) {
-    use pulldown_cmark::{CodeBlockKind, Event::*, HeadingLevel, Tag::*};
+// Renders a help section's markdown as indented text
+//
+// The section's heading is omitted, given that it's shown with the section's name.
+fn render_markdown(markdown: &str) -> String {
+    use pulldown_cmark::{CodeBlockKind, Event::*, Tag::*};
 
-    let mut section_level = None;
-    let mut section_name = String::new();
     let indent = " ".repeat(HELP_INDENT);
     let mut result = indent.clone();
 
@@ -273,26 +190,14 @@ fn consume_help_section(
     let mut in_koto_code = false;
     let mut in_type_declaration = false;
 
-    while let Some(peeked) = parser.peek() {
-        match peeked {
-            Start(Heading(level, _, _)) => {
-                match section_level {
-                    Some(HeadingLevel::H1) => {
-                        // We've reached the end of the title section, so break out
-                        break;
-                    }
-                    Some(section_level) if section_level >= *level => {
-                        // We've reached the end of the section, so break out
-                        break;
-                    }
-                    Some(_) => {
-                        // Start a new subsection
-                        result.push_str("\n\n");
-                    }
-                    None => {
-                        in_section_heading = true;
-                        section_level = Some(*level);
-                    }
+    for event in pulldown_cmark::Parser::new(markdown) {
+        match event {
+            Start(Heading(..)) => {
+                if first_heading {
+                    in_section_heading = true;
+                } else {
+                    // Start a new subsection
+                    result.push_str("\n\n");
                 }
                 heading_start = result.len();
             }
@@ -307,7 +212,7 @@ fn consume_help_section(
                 in_section_heading = false;
                 first_heading = false;
             }
-            Start(Link(_type, _url, title)) => result.push_str(title),
+            Start(Link(_type, _url, title)) => result.push_str(&title),
             End(Link(_, _, _)) => {}
             Start(List(_)) => {
                 if list_indent == 0 {
@@ -342,51 +247,35 @@ fn consume_help_section(
             End(Emphasis) => result.push('_'),
             Start(Strong) => result.push('*'),
             End(Strong) => result.push('*'),
+            // The section's name is shown in place of its heading
+            Text(_) | Code(_) if in_section_heading => {}
             Text(text) => {
-                if in_section_heading {
-                    section_name.push_str(text);
-                } else if in_koto_code {
+                if in_koto_code {
                     for (i, line) in text.split('\n').enumerate() {
                         if i == 0 {
                             result.push('|');
                         }
                         result.push_str("\n|  ");
-                        let processed_line = line.trim_start_matches("print! ").replacen(
-                            "check! ",
-                            HELP_RESULT_STR,
-                            1,
-                        );
-                        result.push_str(&processed_line);
+                        result.push_str(&line.replacen("check! ", HELP_RESULT_STR, 1));
                     }
                 } else if in_type_declaration {
                     result.push('`');
                     result.push_str(text.trim_end());
                     result.push('`');
                 } else {
-                    result.push_str(text);
+                    result.push_str(&text);
                 }
             }
             Code(code) => {
-                if in_section_heading {
-                    section_name.push_str(code);
-                } else {
-                    result.push('`');
-                    result.push_str(code);
-                    result.push('`');
-                }
+                result.push('`');
+                result.push_str(&code);
+                result.push('`');
             }
             SoftBreak => result.push(' '),
             HardBreak => result.push('\n'),
             _other => {}
         }
-
-        parser.next();
-    }
-
-    if let Some(module_name) = module_name {
-        section_name = format!("{module_name}.{section_name}");
     }
-    let result = result.replace('\n', &format!("\n{indent}"));
 
-    (section_name, result)
+    result.replace('\n', &format!("\n{indent}"))
 }
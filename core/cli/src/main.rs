@@ -1,12 +1,24 @@
 mod help;
 mod repl;
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use crossterm::tty::IsTty;
-use koto::prelude::*;
+use koto::{
+    bytecode::{CompilerSettings, SerializeSettings},
+    parser::format_source_excerpt,
+    prelude::*,
+    runtime::ProfileMetric,
+};
+use koto_format::{FormatError, FormatOptions};
+use koto_lint::LintOptions;
 use repl::{Repl, ReplSettings};
 use rustyline::EditMode;
-use std::{env, error::Error, fs, io, path::PathBuf};
+use std::{
+    env,
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 #[global_allocator]
 static ALLOC: mimalloc::MiMalloc = mimalloc::MiMalloc;
@@ -24,12 +36,29 @@ FLAGS:
     -b, --show_bytecode      Show the script's compiled bytecode
     -t, --tests              Run the script's tests before running the script
     -T, --import_tests       Run tests when importing modules
+        --type_checks        Check type hints at runtime, throwing an error on mismatches
+    -O, --optimize           Optimize the compiled bytecode, inlining constants from imported modules,
+                             with --show_instructions showing the instructions before and after
     -c, --config PATH        Config file to load when using the REPL
+        --compile PATH       Compile the script to a bytecode file instead of running it,
+                             leaving out the script's source unless --keep_source is set
+        --keep_source        Include the script's source in the bytecode file for error excerpts
+        --cache_dir PATH     Cache compiled modules in the given directory between runs
+        --profile PATH       Profile the script, writing a report to PATH,
+                             and stacks for flame graph tools to PATH.folded
+        --coverage PATH      Record code coverage, writing it to PATH in the lcov format
+        --format             Format the given scripts in place,
+                             or format standard input when no scripts are given
+        --check              Check that the given scripts are formatted, without modifying them
+        --lint               Check the given scripts for issues like unused or undefined values,
+                             or check standard input when no scripts are given
     -v, --version            Prints version information
     -h, --help               Prints help information
 
 ARGS:
     <script>     The koto script to run, as a file path, or as a string when --eval is set
+                 All arguments are treated as scripts when --format, --check, or --lint is set
+                 Precompiled scripts with a .kbc extension are loaded as bytecode
     <args>...    Arguments to pass into the script
 
 REPL CONFIGURATION:
@@ -58,6 +87,12 @@ fn version_string() -> String {
     format!("Koto {}", env!("CARGO_PKG_VERSION"))
 }
 
+// A script to be run, either as source code or as precompiled bytecode
+enum Script {
+    Source(String),
+    Bytecode(Vec<u8>),
+}
+
 #[derive(Debug, Default)]
 struct KotoArgs {
     help: bool,
@@ -65,11 +100,21 @@ struct KotoArgs {
     eval_script: bool,
     run_tests: bool,
     run_import_tests: bool,
+    type_checks: bool,
+    optimize: bool,
     show_bytecode: bool,
     show_instructions: bool,
     script: Option<String>,
     script_args: Vec<String>,
     config_file: Option<String>,
+    compile_output: Option<String>,
+    keep_source: bool,
+    cache_dir: Option<PathBuf>,
+    profile_output: Option<PathBuf>,
+    coverage_output: Option<PathBuf>,
+    format: bool,
+    check_format: bool,
+    lint: bool,
 }
 
 fn parse_arguments() -> Result<KotoArgs> {
@@ -80,9 +125,19 @@ fn parse_arguments() -> Result<KotoArgs> {
     let show_bytecode = args.contains(["-b", "--show_bytecode"]);
     let run_tests = args.contains(["-t", "--tests"]);
     let run_import_tests = args.contains(["-T", "--import_tests"]);
+    let type_checks = args.contains("--type_checks");
+    let optimize = args.contains(["-O", "--optimize"]);
     let help = args.contains(["-h", "--help"]);
     let version = args.contains(["-v", "--version"]);
     let config_file = args.opt_value_from_str(["-c", "--config"])?;
+    let compile_output = args.opt_value_from_str("--compile")?;
+    let keep_source = args.contains("--keep_source");
+    let cache_dir = args.opt_value_from_str("--cache_dir")?;
+    let profile_output = args.opt_value_from_str("--profile")?;
+    let coverage_output = args.opt_value_from_str("--coverage")?;
+    let format = args.contains("--format");
+    let check_format = args.contains("--check");
+    let lint = args.contains("--lint");
 
     let script = args.subcommand()?;
 
@@ -102,11 +157,21 @@ fn parse_arguments() -> Result<KotoArgs> {
         eval_script,
         run_tests,
         run_import_tests,
+        type_checks,
+        optimize,
         show_bytecode,
         show_instructions,
         script,
         script_args,
         config_file,
+        compile_output,
+        keep_source,
+        cache_dir,
+        profile_output,
+        coverage_output,
+        format,
+        check_format,
+        lint,
     })
 }
 
@@ -128,9 +193,23 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    if args.format || args.check_format {
+        return format_scripts(&args);
+    }
+
+    if args.lint {
+        return lint_scripts(&args);
+    }
+
     let koto_settings = KotoSettings {
         run_tests: args.run_tests,
         run_import_tests: args.run_import_tests,
+        enable_type_checks: args.type_checks,
+        enable_optimizer: args.optimize,
+        enable_constant_inlining: args.optimize,
+        module_cache_dir: args.cache_dir,
+        enable_profiler: args.profile_output.is_some(),
+        enable_coverage: args.coverage_output.is_some(),
         ..Default::default()
     };
 
@@ -138,23 +217,27 @@ fn main() -> Result<()> {
 
     let (script, script_path) = if let Some(script) = args.script {
         if args.eval_script {
-            (Some(script), None)
+            (Some(Script::Source(script)), None)
         } else {
             let script_path = script;
-            let script_contents = match fs::read_to_string(&script_path) {
-                Ok(contents) => contents,
+            let script_contents = if Path::new(&script_path).extension() == Some("kbc".as_ref()) {
+                fs::read(&script_path).map(Script::Bytecode)
+            } else {
+                fs::read_to_string(&script_path).map(Script::Source)
+            };
+            match script_contents {
+                Ok(contents) => (Some(contents), Some(script_path)),
                 Err(e) => {
                     bail!("Error while loading script: {e}");
                 }
-            };
-            (Some(script_contents), Some(script_path))
+            }
         }
     } else if stdin.is_tty() {
         (None, None)
     } else {
         let script =
             io::read_to_string(&mut stdin).expect("Failed to read script from standard input");
-        (Some(script), None)
+        (Some(Script::Source(script)), None)
     };
 
     if let Some(script) = script {
@@ -165,21 +248,56 @@ fn main() -> Result<()> {
 
         add_modules(&koto);
 
-        match koto.compile(&script) {
+        let compile_result = match &script {
+            Script::Source(source) => koto.compile(source),
+            Script::Bytecode(data) => koto.load_bytecode(data),
+        };
+
+        match compile_result {
             Ok(chunk) => {
+                if let Some(output_path) = &args.compile_output {
+                    let serialize_settings = SerializeSettings {
+                        strip_source: !args.keep_source,
+                    };
+                    let data = chunk.serialize_with_settings(serialize_settings);
+                    if let Err(e) = fs::write(output_path, data) {
+                        bail!("Error while writing bytecode: {e}");
+                    }
+                    return Ok(());
+                }
                 if args.show_bytecode {
                     println!("{}\n", &Chunk::bytes_as_string(&chunk));
                 }
                 if args.show_instructions {
-                    println!("Constants\n---------\n{}\n", chunk.constants);
-
-                    let script_lines = script.lines().collect::<Vec<_>>();
-                    println!(
-                        "Instructions\n------------\n{}",
-                        Chunk::instructions_as_string(chunk, &script_lines)
-                    );
+                    match &script {
+                        // Show the unoptimized instructions first so that the optimizer's
+                        // changes can be compared
+                        Script::Source(source) if args.optimize => {
+                            let unoptimized = Loader::default().compile_script(
+                                source,
+                                &chunk.source_path,
+                                CompilerSettings {
+                                    enable_type_checks: args.type_checks,
+                                    ..Default::default()
+                                },
+                            )?;
+                            print_instructions(unoptimized, "Instructions (unoptimized)");
+                            print_instructions(chunk, "Instructions (optimized)");
+                        }
+                        _ => print_instructions(chunk, "Instructions"),
+                    }
                 }
-                match koto.run_with_args(&args.script_args) {
+                let run_result = koto.run_with_args(&args.script_args);
+                if let Some(profile_path) = &args.profile_output {
+                    write_profile(&koto, profile_path)?;
+                }
+                if let Some(coverage_path) = &args.coverage_output {
+                    if let Some(coverage) = koto.coverage() {
+                        fs::write(coverage_path, coverage.to_lcov())
+                            .context("Failed to write coverage")?;
+                    }
+                }
+                match run_result {
                     Ok(_) => {}
                     Err(error) if error.source().is_some() => {
                         bail!("{error}\n{}", error.source().unwrap())
@@ -211,6 +329,156 @@ fn main() -> Result<()> {
     }
 }
 
+// Prints a chunk's constants and instructions, annotated with source lines
+fn print_instructions(chunk: Ptr<Chunk>, title: &str) {
+    println!("Constants\n---------\n{}\n", chunk.constants);
+
+    let source = chunk.debug_info.source.clone();
+    let script_lines = source.lines().collect::<Vec<_>>();
+    println!(
+        "{title}\n{}\n{}",
+        "-".repeat(title.len()),
+        Chunk::instructions_as_string(chunk, &script_lines)
+    );
+}
+
+// Writes the profiler's report to the given path, along with folded stacks for flame graph tools
+fn write_profile(koto: &Koto, path: &Path) -> Result<()> {
+    let Some(profile) = koto.profile() else {
+        return Ok(());
+    };
+
+    let report = format!("{}\n{}", profile.flat_report(), profile.call_tree_report());
+    fs::write(path, report).context("Failed to write the profile report")?;
+
+    let mut folded_path = path.as_os_str().to_owned();
+    folded_path.push(".folded");
+    fs::write(folded_path, profile.folded_stacks(ProfileMetric::Time))
+        .context("Failed to write the profile's folded stacks")?;
+
+    Ok(())
+}
+
+// Formats the scripts provided as arguments in place, or standard input if no scripts are given
+//
+// When --check is set the scripts are left unmodified,
+// and an error is returned if any of them would be changed by formatting.
+fn format_scripts(args: &KotoArgs) -> Result<()> {
+    let paths = script_paths(args);
+
+    if paths.is_empty() {
+        let source =
+            io::read_to_string(io::stdin()).context("Failed to read script from standard input")?;
+        let formatted = format_source(&source, None)?;
+        if !args.check_format {
+            print!("{formatted}");
+        } else if formatted != source {
+            bail!("The script isn't formatted");
+        }
+        return Ok(());
+    }
+
+    let mut unformatted = Vec::new();
+    for path in paths {
+        let source =
+            fs::read_to_string(path).with_context(|| format!("Failed to load '{path}'"))?;
+        let formatted = format_source(&source, Some(path))?;
+        if formatted == source {
+            continue;
+        }
+        if args.check_format {
+            unformatted.push(path.as_str());
+        } else {
+            fs::write(path, formatted).with_context(|| format!("Failed to write '{path}'"))?;
+        }
+    }
+
+    if !unformatted.is_empty() {
+        bail!(
+            "The following scripts aren't formatted:\n  {}",
+            unformatted.join("\n  ")
+        );
+    }
+
+    Ok(())
+}
+
+fn format_source(source: &str, path: Option<&String>) -> Result<String> {
+    koto_format::format(source, FormatOptions::default()).map_err(|error| {
+        let path = path.map(PathBuf::from);
+        match error {
+            FormatError::ParserError(error) => anyhow!(
+                "{error}.\n{}",
+                format_source_excerpt(source, &error.span, &path)
+            ),
+            error => match path {
+                Some(path) => anyhow!("{error} ('{}')", path.display()),
+                None => anyhow!("{error}"),
+            },
+        }
+    })
+}
+
+// Checks the scripts provided as arguments for issues, or standard input if no scripts are given
+fn lint_scripts(args: &KotoArgs) -> Result<()> {
+    let koto = Koto::new();
+    add_modules(&koto);
+    let options = LintOptions {
+        prelude: Some(
+            koto.prelude()
+                .data()
+                .keys()
+                .map(|key| key.to_string())
+                .collect(),
+        ),
+    };
+
+    let scripts = match script_paths(args) {
+        paths if paths.is_empty() => {
+            let source = io::read_to_string(io::stdin())
+                .context("Failed to read script from standard input")?;
+            vec![(source, None)]
+        }
+        paths => paths
+            .into_iter()
+            .map(|path| {
+                fs::read_to_string(path)
+                    .with_context(|| format!("Failed to load '{path}'"))
+                    .map(|source| (source, Some(PathBuf::from(path))))
+            })
+            .collect::<Result<_>>()?,
+    };
+
+    let mut lint_count = 0;
+    for (source, path) in scripts {
+        let lints = match koto_lint::lint(&source, &options) {
+            Ok(lints) => lints,
+            Err(error) => bail!(
+                "{error}.\n{}",
+                format_source_excerpt(&source, &error.span, &path)
+            ),
+        };
+        for lint in lints {
+            println!(
+                "{lint}.\n{}\n",
+                format_source_excerpt(&source, &lint.span, &path)
+            );
+            lint_count += 1;
+        }
+    }
+
+    match lint_count {
+        0 => Ok(()),
+        1 => bail!("1 issue found"),
+        _ => bail!("{lint_count} issues found"),
+    }
+}
+
+// Returns the script and script arguments, which are treated as paths by --format and --lint
+fn script_paths(args: &KotoArgs) -> Vec<&String> {
+    args.script.iter().chain(args.script_args.iter()).collect()
+}
+
 fn add_modules(koto: &Koto) {
     let prelude = koto.prelude();
     prelude.add_map("color", koto_color::make_module());
@@ -75,6 +75,10 @@ impl Repl {
         let koto = Koto::with_settings(koto_settings);
         super::add_modules(&koto);
 
+        // Pressing Ctrl-C while a script is running interrupts the script rather than exiting.
+        // While waiting for input the terminal is in raw mode, so Ctrl-C is handled by the editor.
+        signal_hook::flag::register(signal_hook::consts::SIGINT, koto.interrupt_handle().flag())?;
+
         let mut editor = DefaultEditor::with_config(
             Config::builder()
                 .max_history_size(MAX_HISTORY_ENTRIES)?
@@ -0,0 +1,71 @@
+use std::{
+    env, fs,
+    path::Path,
+    process::{Command, Output},
+};
+
+fn run_koto(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_koto"))
+        .args(args)
+        .output()
+        .expect("failed to execute child")
+}
+
+fn contains(data: &[u8], needle: &str) -> bool {
+    data.windows(needle.len())
+        .any(|window| window == needle.as_bytes())
+}
+
+const SCRIPT: &str = "\
+secret_api_key = 'hunter2'
+x = [secret_api_key.size()]
+print x[5]
+";
+
+fn compile_script(dir: &Path, extra_args: &[&str]) -> (Vec<u8>, String) {
+    let script = dir.join("script.koto");
+    let output_path = dir.join("script.kbc");
+    fs::write(&script, SCRIPT).unwrap();
+
+    let mut args = vec![
+        "--compile",
+        output_path.to_str().unwrap(),
+        script.to_str().unwrap(),
+    ];
+    args.extend_from_slice(extra_args);
+    let output = run_koto(&args);
+    assert!(output.status.success());
+
+    let run_output = run_koto(&[output_path.to_str().unwrap()]);
+    assert!(!run_output.status.success());
+
+    (
+        fs::read(&output_path).unwrap(),
+        String::from_utf8(run_output.stderr).unwrap(),
+    )
+}
+
+mod compile_tests {
+    use super::*;
+
+    #[test]
+    fn source_is_stripped_by_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let (bytecode, error) = compile_script(dir.path(), &[]);
+
+        assert!(!contains(&bytecode, "secret_api_key = 'hunter2'"));
+        assert!(!contains(&bytecode, "script.koto"));
+        // The error's position is still reported, without a source excerpt
+        assert!(error.contains("script.kbc - 3:7"), "{error}");
+        assert!(!error.contains("print x[5]"), "{error}");
+    }
+
+    #[test]
+    fn keep_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let (bytecode, error) = compile_script(dir.path(), &["--keep_source"]);
+
+        assert!(contains(&bytecode, "secret_api_key = 'hunter2'"));
+        assert!(error.contains("print x[5]"), "{error}");
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    env, fs,
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+const UNFORMATTED: &str = "x=[1,2,3]\nf = |a,b|   a+b\n";
+const FORMATTED: &str = "x = [1, 2, 3]\nf = |a, b| a + b\n";
+
+fn run_koto(args: &[&str], input: &str) -> Output {
+    let mut process = Command::new(env!("CARGO_BIN_EXE_koto"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to execute child");
+
+    let stdin = process.stdin.as_mut().expect("failed to get stdin");
+    stdin
+        .write_all(input.as_bytes())
+        .expect("Failed to write to stdin");
+
+    process.wait_with_output().expect("Failed to get output")
+}
+
+mod format_tests {
+    use super::*;
+
+    #[test]
+    fn format_stdin() {
+        let output = run_koto(&["--format"], UNFORMATTED);
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), FORMATTED);
+    }
+
+    #[test]
+    fn format_files_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.koto");
+        let b = dir.path().join("b.koto");
+        fs::write(&a, UNFORMATTED).unwrap();
+        fs::write(&b, FORMATTED).unwrap();
+
+        let output = run_koto(&["--format", a.to_str().unwrap(), b.to_str().unwrap()], "");
+        assert!(output.status.success());
+        assert_eq!(fs::read_to_string(&a).unwrap(), FORMATTED);
+        assert_eq!(fs::read_to_string(&b).unwrap(), FORMATTED);
+    }
+
+    #[test]
+    fn check_formatted_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("script.koto");
+        fs::write(&script, FORMATTED).unwrap();
+
+        let output = run_koto(&["--check", script.to_str().unwrap()], "");
+        assert!(output.status.success());
+    }
+
+    #[test]
+    fn check_unformatted_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let formatted = dir.path().join("formatted.koto");
+        let unformatted = dir.path().join("unformatted.koto");
+        fs::write(&formatted, FORMATTED).unwrap();
+        fs::write(&unformatted, UNFORMATTED).unwrap();
+
+        let output = run_koto(
+            &[
+                "--check",
+                formatted.to_str().unwrap(),
+                unformatted.to_str().unwrap(),
+            ],
+            "",
+        );
+        assert!(!output.status.success());
+
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("unformatted.koto"));
+        assert!(!stderr.contains("/formatted.koto"));
+
+        // The script should be left unmodified
+        assert_eq!(fs::read_to_string(&unformatted).unwrap(), UNFORMATTED);
+    }
+
+    #[test]
+    fn check_stdin() {
+        assert!(run_koto(&["--check"], FORMATTED).status.success());
+        assert!(!run_koto(&["--check"], UNFORMATTED).status.success());
+    }
+
+    #[test]
+    fn parser_error() {
+        let output = run_koto(&["--format"], "x = [1, 2");
+        assert!(!output.status.success());
+        assert!(output.stdout.is_empty());
+    }
+}
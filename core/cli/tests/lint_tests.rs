@@ -0,0 +1,86 @@
+use std::{
+    env, fs,
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn run_koto(args: &[&str], input: &str) -> Output {
+    let mut process = Command::new(env!("CARGO_BIN_EXE_koto"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to execute child");
+
+    let stdin = process.stdin.as_mut().expect("failed to get stdin");
+    stdin
+        .write_all(input.as_bytes())
+        .expect("Failed to write to stdin");
+
+    process.wait_with_output().expect("Failed to get output")
+}
+
+mod lint_tests {
+    use super::*;
+
+    #[test]
+    fn no_issues() {
+        let output = run_koto(&["--lint"], "x = [1, 2, 3]\nprint x.size()\n");
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty());
+    }
+
+    #[test]
+    fn issues_in_stdin() {
+        let script = "\
+x = 1
+print y
+";
+        let output = run_koto(&["--lint"], script);
+        assert!(!output.status.success());
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let expected = "\
+'x' is never used.
+1:1
+   |
+ 1 | x = 1
+   | ^
+
+'y' isn't defined.
+2:7
+   |
+ 2 | print y
+   |       ^
+
+";
+        assert_eq!(stdout, expected);
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("2 issues found"));
+    }
+
+    #[test]
+    fn modules_added_by_the_cli_are_defined() {
+        let output = run_koto(&["--lint"], "print json.to_string [1, 2, 3]\n");
+        assert!(output.status.success());
+    }
+
+    #[test]
+    fn issues_in_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.koto");
+        let b = dir.path().join("b.koto");
+        fs::write(&a, "print 'hello'\n").unwrap();
+        fs::write(&b, "f = ||\n  return 1\n  print 'unreachable'\nf()\n").unwrap();
+
+        let output = run_koto(&["--lint", a.to_str().unwrap(), b.to_str().unwrap()], "");
+        assert!(!output.status.success());
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.starts_with("Unreachable code.\n"));
+        assert!(stdout.contains("b.koto - 3:3"));
+        assert!(!stdout.contains("a.koto"));
+    }
+}
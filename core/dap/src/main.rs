@@ -0,0 +1,538 @@
+//! A Debug Adapter Protocol server for Koto
+//!
+//! The server communicates with the client over stdin and stdout, and runs the script that's
+//! provided in the client's `launch` request, e.g.
+//!
+//! ```json
+//! {"program": "/path/to/script.koto", "args": ["foo"], "stopOnEntry": true}
+//! ```
+//!
+//! Output from the script is forwarded to the client as `output` events.
+
+mod protocol;
+mod session;
+
+use koto::{prelude::*, runtime::Result as RuntimeResult};
+use protocol::{read_message, Client};
+use serde_json::json;
+use session::Session;
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    rc::Rc,
+    sync::mpsc,
+    thread,
+};
+
+fn main() {
+    run(BufReader::new(io::stdin()), io::stdout());
+}
+
+// Runs a debugging session, returning when the client disconnects or ends the session
+fn run(mut input: impl BufRead + Send + 'static, output: impl Write + 'static) {
+    let (sender, requests) = mpsc::channel();
+    thread::spawn(move || {
+        while let Ok(Some(request)) = read_message(&mut input) {
+            if sender.send(request).is_err() {
+                break;
+            }
+        }
+    });
+
+    let client = Rc::new(Client::new(requests, output));
+    let mut session = Session::new(client.clone());
+
+    // Wait until the client has launched the script and finished its configuration
+    let mut launch_request = None;
+    let mut configuration_done = false;
+    while launch_request.is_none() || !configuration_done {
+        let Some(request) = client.next_request() else {
+            return;
+        };
+        match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                client.respond(
+                    &request,
+                    json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsTerminateRequest": true,
+                    }),
+                );
+                client.send_event("initialized", json!({}));
+            }
+            "launch" => launch_request = Some(request),
+            "configurationDone" => {
+                client.respond(&request, json!({}));
+                configuration_done = true;
+            }
+            _ => {
+                if !session.handle_request(&request) {
+                    client.respond_with_error(&request, "The script hasn't been launched");
+                }
+            }
+        }
+    }
+
+    let launch_request = launch_request.unwrap();
+    let arguments = &launch_request["arguments"];
+    let Some(program) = arguments["program"].as_str() else {
+        client.respond_with_error(&launch_request, "Missing 'program' in launch arguments");
+        client.send_event("terminated", json!({}));
+        return;
+    };
+    let script_args: Vec<String> = arguments["args"]
+        .as_array()
+        .map(|args| {
+            args.iter()
+                .filter_map(|arg| arg.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+    session.set_stop_on_entry(arguments["stopOnEntry"].as_bool().unwrap_or(false));
+    client.respond(&launch_request, json!({}));
+
+    let settings = KotoSettings {
+        debug_hook: Some(Box::new(session)),
+        ..Default::default()
+    }
+    .with_stdout(OutputEvents::new(client.clone(), "stdout"))
+    .with_stderr(OutputEvents::new(client.clone(), "stderr"));
+    let mut koto = Koto::with_settings(settings);
+
+    let exit_code = match run_script(&mut koto, program, &script_args) {
+        Ok(_) => 0,
+        // The script was interrupted after the session ended
+        Err(_) if client.is_closed() => return,
+        Err(error) => {
+            send_output(&client, "stderr", &format!("{error}\n"));
+            1
+        }
+    };
+
+    client.send_event("exited", json!({"exitCode": exit_code}));
+    client.send_event("terminated", json!({}));
+
+    // Wait for the client to disconnect
+    while let Some(request) = client.next_request() {
+        client.respond(&request, json!({}));
+        if matches!(
+            request["command"].as_str(),
+            Some("disconnect" | "terminate")
+        ) {
+            return;
+        }
+    }
+}
+
+fn run_script(koto: &mut Koto, program: &str, args: &[String]) -> Result<(), String> {
+    let script =
+        fs::read_to_string(program).map_err(|e| format!("Error while loading script: {e}"))?;
+    koto.set_script_path(Some(program.into()))
+        .and_then(|_| koto.compile(&script))
+        .and_then(|_| koto.run_with_args(args))
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+fn send_output(client: &Client, category: &str, output: &str) {
+    client.send_event("output", json!({"category": category, "output": output}));
+}
+
+// Forwards output from the script to the client
+struct OutputEvents {
+    client: Rc<Client>,
+    category: &'static str,
+}
+
+impl OutputEvents {
+    fn new(client: Rc<Client>, category: &'static str) -> Self {
+        Self { client, category }
+    }
+}
+
+impl KotoFile for OutputEvents {
+    fn id(&self) -> KString {
+        format!("_{}_", self.category).into()
+    }
+}
+
+impl KotoRead for OutputEvents {}
+
+impl KotoWrite for OutputEvents {
+    fn write(&self, bytes: &[u8]) -> RuntimeResult<()> {
+        send_output(&self.client, self.category, &String::from_utf8_lossy(bytes));
+        Ok(())
+    }
+
+    fn write_line(&self, text: &str) -> RuntimeResult<()> {
+        send_output(&self.client, self.category, &format!("{text}\n"));
+        Ok(())
+    }
+
+    fn flush(&self) -> RuntimeResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value as Json;
+    use std::{
+        io::Read,
+        path::PathBuf,
+        sync::{mpsc::Receiver, Arc, Mutex},
+        thread::JoinHandle,
+        time::{Duration, Instant},
+    };
+
+    // Input for the server that blocks until the test sends the next request
+    struct RequestInput {
+        requests: Receiver<Vec<u8>>,
+        buffer: Vec<u8>,
+        position: usize,
+    }
+
+    impl Read for RequestInput {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.position == self.buffer.len() {
+                match self.requests.recv() {
+                    Ok(request) => {
+                        self.buffer = request;
+                        self.position = 0;
+                    }
+                    // The test has finished sending requests
+                    Err(_) => return Ok(0),
+                }
+            }
+
+            let count = buf.len().min(self.buffer.len() - self.position);
+            buf[..count].copy_from_slice(&self.buffer[self.position..self.position + count]);
+            self.position += count;
+            Ok(count)
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // A client that's connected to a server running on another thread
+    struct TestClient {
+        requests: Option<mpsc::Sender<Vec<u8>>>,
+        output: SharedOutput,
+        server: Option<JoinHandle<()>>,
+        // The messages that have been received from the server
+        messages: Vec<Json>,
+        // The number of output bytes that have been parsed into messages
+        output_position: usize,
+        seq: u64,
+    }
+
+    impl TestClient {
+        fn new() -> Self {
+            let (sender, requests) = mpsc::channel();
+            let output = SharedOutput::default();
+
+            let input = RequestInput {
+                requests,
+                buffer: Vec::new(),
+                position: 0,
+            };
+            let server_output = output.clone();
+            let server = thread::spawn(move || run(BufReader::new(input), server_output));
+
+            Self {
+                requests: Some(sender),
+                output,
+                server: Some(server),
+                messages: Vec::new(),
+                output_position: 0,
+                seq: 0,
+            }
+        }
+
+        // Sends a request, returning the response once it's been received
+        fn request(&mut self, command: &str, arguments: Json) -> Json {
+            let seq = self.send(command, arguments);
+            self.wait_for_response(seq)
+        }
+
+        // Sends a request without waiting for the response, returning the request's seq
+        fn send(&mut self, command: &str, arguments: Json) -> u64 {
+            self.seq += 1;
+            let seq = self.seq;
+            let content = json!({
+                "seq": seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            })
+            .to_string();
+            let message = format!("Content-Length: {}\r\n\r\n{content}", content.len());
+            self.requests
+                .as_ref()
+                .unwrap()
+                .send(message.into_bytes())
+                .unwrap();
+            seq
+        }
+
+        fn wait_for_response(&mut self, seq: u64) -> Json {
+            self.wait_for(|message| message["type"] == "response" && message["request_seq"] == seq)
+        }
+
+        fn wait_for_event(&mut self, event: &str) -> Json {
+            self.wait_for(|message| message["type"] == "event" && message["event"] == event)
+        }
+
+        // Waits for a message that hasn't been seen before that matches the predicate
+        fn wait_for(&mut self, predicate: impl Fn(&Json) -> bool) -> Json {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            loop {
+                self.read_messages();
+                if let Some(index) = self.messages.iter().position(&predicate) {
+                    return self.messages.remove(index);
+                }
+                assert!(
+                    Instant::now() < deadline,
+                    "Timed out while waiting for a message, received: {:#?}",
+                    self.messages
+                );
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        fn read_messages(&mut self) {
+            let output = self.output.0.lock().unwrap();
+            while let Some(header_end) = output[self.output_position..]
+                .windows(4)
+                .position(|window| window == b"\r\n\r\n")
+            {
+                let header_start = self.output_position;
+                let header =
+                    str::from_utf8(&output[header_start..header_start + header_end]).unwrap();
+                let length: usize = header
+                    .trim_start_matches("Content-Length: ")
+                    .parse()
+                    .unwrap();
+                let content_start = header_start + header_end + 4;
+                let content = &output[content_start..content_start + length];
+                self.messages.push(serde_json::from_slice(content).unwrap());
+                self.output_position = content_start + length;
+            }
+        }
+
+        // Initializes the session, and launches the script once the breakpoints have been set
+        fn launch(&mut self, script: &TestScript, breakpoints: &[u32], stop_on_entry: bool) {
+            self.request("initialize", json!({"adapterID": "koto"}));
+            self.wait_for_event("initialized");
+            self.request(
+                "setBreakpoints",
+                json!({
+                    "source": {"path": script.0},
+                    "breakpoints": breakpoints
+                        .iter()
+                        .map(|line| json!({"line": line}))
+                        .collect::<Vec<_>>(),
+                }),
+            );
+            // The launch request is responded to once configuration is done
+            let launch = self.send(
+                "launch",
+                json!({"program": script.0, "stopOnEntry": stop_on_entry}),
+            );
+            self.request("configurationDone", json!({}));
+            self.wait_for_response(launch);
+        }
+
+        fn wait_for_stop(&mut self, expected_reason: &str) {
+            let stopped = self.wait_for_event("stopped");
+            assert_eq!(stopped["body"]["reason"], expected_reason);
+        }
+
+        // Returns the line of the top frame in the paused script
+        fn current_line(&mut self) -> u64 {
+            let response = self.request("stackTrace", json!({"threadId": 1}));
+            response["body"]["stackFrames"][0]["line"].as_u64().unwrap()
+        }
+
+        // Ends the session, and checks that the server has returned
+        fn disconnect(mut self) {
+            self.request("disconnect", json!({}));
+            self.requests = None;
+            self.server.take().unwrap().join().unwrap();
+        }
+    }
+
+    struct TestScript(PathBuf);
+
+    impl TestScript {
+        fn new(name: &str, script: &str) -> Self {
+            let dir = dunce::canonicalize(std::env::temp_dir()).unwrap();
+            let path = dir.join(format!("koto_dap_{name}_{}.koto", std::process::id()));
+            fs::write(&path, script).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TestScript {
+        fn drop(&mut self) {
+            fs::remove_file(&self.0).ok();
+        }
+    }
+
+    #[test]
+    fn initialize() {
+        let mut client = TestClient::new();
+
+        let response = client.request("initialize", json!({"adapterID": "koto"}));
+        assert_eq!(response["success"], true);
+        assert_eq!(response["body"]["supportsConfigurationDoneRequest"], true);
+        client.wait_for_event("initialized");
+
+        client.disconnect();
+    }
+
+    #[test]
+    fn set_breakpoints() {
+        let script = TestScript::new("breakpoints", "x = 1\ny = 2\nprint x + y\n");
+        let mut client = TestClient::new();
+
+        client.request("initialize", json!({}));
+        let response = client.request(
+            "setBreakpoints",
+            json!({
+                "source": {"path": script.0},
+                "breakpoints": [{"line": 2}],
+            }),
+        );
+        assert_eq!(
+            response["body"]["breakpoints"],
+            json!([{"verified": true, "line": 2}])
+        );
+        let launch = client.send("launch", json!({"program": script.0}));
+        client.request("configurationDone", json!({}));
+        client.wait_for_response(launch);
+
+        client.wait_for_stop("breakpoint");
+        assert_eq!(client.current_line(), 2);
+
+        client.request("continue", json!({"threadId": 1}));
+        let output = client.wait_for_event("output");
+        assert_eq!(output["body"]["output"], "3\n");
+        let exited = client.wait_for_event("exited");
+        assert_eq!(exited["body"]["exitCode"], 0);
+
+        client.disconnect();
+    }
+
+    #[test]
+    fn stepping() {
+        let script = TestScript::new(
+            "stepping",
+            "\
+f = |n|
+  n + 1
+x = f 1
+print x
+",
+        );
+        let mut client = TestClient::new();
+        client.launch(&script, &[], true);
+
+        client.wait_for_stop("entry");
+        assert_eq!(client.current_line(), 1);
+
+        client.request("next", json!({"threadId": 1}));
+        client.wait_for_stop("step");
+        assert_eq!(client.current_line(), 3);
+
+        client.request("stepIn", json!({"threadId": 1}));
+        client.wait_for_stop("step");
+        assert_eq!(client.current_line(), 2);
+
+        client.request("stepOut", json!({"threadId": 1}));
+        client.wait_for_stop("step");
+        assert_eq!(client.current_line(), 4);
+
+        client.request("continue", json!({"threadId": 1}));
+        client.wait_for_event("terminated");
+
+        client.disconnect();
+    }
+
+    #[test]
+    fn variables() {
+        let script = TestScript::new("variables", "x = 42\ny = 'abc'\nprint x\n");
+        let mut client = TestClient::new();
+        client.launch(&script, &[3], false);
+        client.wait_for_stop("breakpoint");
+
+        let stack_trace = client.request("stackTrace", json!({"threadId": 1}));
+        let frame_id = stack_trace["body"]["stackFrames"][0]["id"].clone();
+        let scopes = client.request("scopes", json!({"frameId": frame_id}));
+        let reference = scopes["body"]["scopes"][0]["variablesReference"].clone();
+        let variables = client.request("variables", json!({"variablesReference": reference}));
+
+        assert_eq!(
+            variables["body"]["variables"],
+            json!([
+                {"name": "x", "value": "42", "type": "Int", "variablesReference": 0},
+                {"name": "y", "value": "abc", "type": "String", "variablesReference": 0},
+            ])
+        );
+
+        client.disconnect();
+    }
+
+    #[test]
+    fn disconnect_while_paused() {
+        let script = TestScript::new("disconnect", "loop\n  x = 1\n");
+        let mut client = TestClient::new();
+        client.launch(&script, &[2], false);
+        client.wait_for_stop("breakpoint");
+
+        // The server returns once the script has been interrupted
+        client.disconnect();
+    }
+
+    // A script that runs indefinitely on a single line, with tail calls keeping the depth fixed
+    const SINGLE_LINE_LOOP: &str = "\
+f = |n| if n > 0 then f n - 1 else f 1000000
+f 1
+";
+
+    #[test]
+    fn pause_while_running_on_a_single_line() {
+        let script = TestScript::new("pause", SINGLE_LINE_LOOP);
+        let mut client = TestClient::new();
+        client.launch(&script, &[], false);
+
+        client.request("pause", json!({"threadId": 1}));
+        client.wait_for_stop("pause");
+        assert_eq!(client.current_line(), 1);
+
+        client.disconnect();
+    }
+
+    #[test]
+    fn disconnect_while_running_on_a_single_line() {
+        let script = TestScript::new("disconnect_running", SINGLE_LINE_LOOP);
+        let mut client = TestClient::new();
+        client.launch(&script, &[], false);
+
+        // The server returns once the script has been interrupted
+        client.disconnect();
+    }
+}
@@ -0,0 +1,148 @@
+use serde_json::{json, Value as Json};
+use std::{
+    cell::{Cell, RefCell},
+    io::{self, BufRead, Write},
+    sync::mpsc::{Receiver, TryRecvError},
+};
+
+// Reads a message from the client
+//
+// Messages are JSON objects preceded by a `Content-Length` header.
+// None is returned when the client has closed the connection.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut content_length = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(length) = header.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse::<usize>().ok();
+        }
+    }
+
+    let Some(content_length) = content_length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Missing Content-Length header",
+        ));
+    };
+
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content)?;
+
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+// The connection to the client
+//
+// Requests are received from a channel that's fed by a reader thread,
+// and responses and events are written to the output.
+pub struct Client {
+    requests: Receiver<Json>,
+    output: RefCell<Box<dyn Write>>,
+    seq: Cell<u64>,
+    // Set when the client has disconnected, or has asked for the session to end
+    closed: Cell<bool>,
+}
+
+impl Client {
+    pub fn new(requests: Receiver<Json>, output: impl Write + 'static) -> Self {
+        Self {
+            requests,
+            output: RefCell::new(Box::new(output)),
+            seq: Cell::new(0),
+            closed: Cell::new(false),
+        }
+    }
+
+    // Waits for the next request, returning None if the connection has been closed
+    pub fn next_request(&self) -> Option<Json> {
+        if self.is_closed() {
+            return None;
+        }
+
+        match self.requests.recv() {
+            Ok(request) => Some(request),
+            Err(_) => {
+                self.close();
+                None
+            }
+        }
+    }
+
+    // Returns the next request if one is available
+    pub fn try_next_request(&self) -> Option<Json> {
+        if self.is_closed() {
+            return None;
+        }
+
+        match self.requests.try_recv() {
+            Ok(request) => Some(request),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.close();
+                None
+            }
+        }
+    }
+
+    // Ends the session, no further requests will be returned
+    pub fn close(&self) {
+        self.closed.set(true);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.get()
+    }
+
+    pub fn respond(&self, request: &Json, body: Json) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }))
+    }
+
+    pub fn respond_with_error(&self, request: &Json, message: &str) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+        }))
+    }
+
+    pub fn send_event(&self, event: &str, body: Json) {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }))
+    }
+
+    fn send(&self, mut message: Json) {
+        let seq = self.seq.get() + 1;
+        self.seq.set(seq);
+        message["seq"] = seq.into();
+
+        let content = message.to_string();
+        let mut output = self.output.borrow_mut();
+        // If the output is unavailable then the client has gone away,
+        // and the reader thread will close the request channel.
+        write!(output, "Content-Length: {}\r\n\r\n{content}", content.len()).ok();
+        output.flush().ok();
+    }
+}
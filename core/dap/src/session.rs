@@ -0,0 +1,267 @@
+use crate::protocol::Client;
+use dunce::canonicalize;
+use koto::{
+    prelude::*,
+    runtime::{DebugFrame, DebugHook, PauseReason, StepMode},
+};
+use serde_json::{json, Value as Json};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+// The id of the only thread that's reported to the client
+const THREAD_ID: u64 = 1;
+
+// The state of a debugging session, shared with the runtime as its debug hook
+pub struct Session {
+    client: Rc<Client>,
+    breakpoints: HashMap<PathBuf, Vec<u32>>,
+    // Set when the script should pause on its first line
+    stop_on_entry: bool,
+    // Set when the client has asked for execution to be paused
+    pause_requested: bool,
+    // The reason for the next pause, when it wasn't caused by a breakpoint or step
+    pending_stop_reason: Option<&'static str>,
+}
+
+impl Session {
+    pub fn new(client: Rc<Client>) -> Self {
+        Self {
+            client,
+            breakpoints: HashMap::new(),
+            stop_on_entry: false,
+            pause_requested: false,
+            pending_stop_reason: None,
+        }
+    }
+
+    pub fn set_stop_on_entry(&mut self, stop_on_entry: bool) {
+        self.stop_on_entry = stop_on_entry;
+    }
+
+    // Handles requests that are supported whether or not execution is paused
+    //
+    // Returns false if the request wasn't handled.
+    pub fn handle_request(&mut self, request: &Json) -> bool {
+        match request["command"].as_str().unwrap_or_default() {
+            "setBreakpoints" => self.set_breakpoints(request),
+            "threads" => self.client.respond(
+                request,
+                json!({"threads": [{"id": THREAD_ID, "name": "main"}]}),
+            ),
+            "pause" => {
+                self.pause_requested = true;
+                self.client.respond(request, json!({}));
+            }
+            "disconnect" | "terminate" => {
+                self.client.respond(request, json!({}));
+                self.client.close();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn set_breakpoints(&mut self, request: &Json) {
+        let arguments = &request["arguments"];
+        let Some(path) = arguments["source"]["path"].as_str() else {
+            self.client
+                .respond_with_error(request, "Missing source path");
+            return;
+        };
+        let path = canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+
+        let lines: Vec<u32> = arguments["breakpoints"]
+            .as_array()
+            .map(|breakpoints| {
+                breakpoints
+                    .iter()
+                    .filter_map(|breakpoint| breakpoint["line"].as_u64())
+                    .map(|line| line as u32)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let verified_breakpoints: Vec<Json> = lines
+            .iter()
+            .map(|line| json!({"verified": true, "line": line}))
+            .collect();
+        self.breakpoints.insert(path, lines);
+
+        self.client
+            .respond(request, json!({"breakpoints": verified_breakpoints}));
+    }
+
+    fn stack_trace(&self, request: &Json, frames: &[DebugFrame]) {
+        let stack_frames: Vec<Json> = frames
+            .iter()
+            .enumerate()
+            .map(|(id, frame)| {
+                let span = frame.span().unwrap_or_default();
+                let name = if id == frames.len() - 1 {
+                    "<main>"
+                } else {
+                    "<function>"
+                };
+                let source = frame.source_path().map(|path| {
+                    json!({
+                        "name": path.file_name().map(|name| name.to_string_lossy()),
+                        "path": path.to_string_lossy(),
+                    })
+                });
+                json!({
+                    "id": id,
+                    "name": name,
+                    "source": source,
+                    "line": span.start.line,
+                    "column": span.start.column,
+                })
+            })
+            .collect();
+
+        self.client.respond(
+            request,
+            json!({"stackFrames": stack_frames, "totalFrames": frames.len()}),
+        );
+    }
+
+    fn scopes(&self, request: &Json) {
+        // Each frame has a single scope containing its locals,
+        // variable references start at 1 so the frame index is offset by 1.
+        let frame_id = request["arguments"]["frameId"].as_u64().unwrap_or(0);
+        self.client.respond(
+            request,
+            json!({"scopes": [{
+                "name": "Locals",
+                "variablesReference": frame_id + 1,
+                "expensive": false,
+            }]}),
+        );
+    }
+
+    fn variables(&self, request: &Json, frames: &[DebugFrame], vm: &mut Vm) {
+        let reference = request["arguments"]["variablesReference"]
+            .as_u64()
+            .unwrap_or(0) as usize;
+
+        let variables: Vec<Json> = match reference.checked_sub(1).and_then(|i| frames.get(i)) {
+            Some(frame) => frame
+                .locals
+                .iter()
+                .map(|local| {
+                    let value = vm
+                        .value_to_string(&local.value)
+                        .unwrap_or_else(|error| format!("<{error}>"));
+                    json!({
+                        "name": local.name,
+                        "value": value,
+                        "type": local.value.type_as_string().as_str(),
+                        "variablesReference": 0,
+                    })
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        self.client
+            .respond(request, json!({"variables": variables}));
+    }
+}
+
+impl DebugHook for Session {
+    fn is_breakpoint(&mut self, path: Option<&Path>, line: u32) -> bool {
+        if self.stop_on_entry {
+            self.stop_on_entry = false;
+            self.pending_stop_reason = Some("entry");
+            return true;
+        }
+
+        if self.poll() {
+            return true;
+        }
+
+        match path.and_then(|path| self.breakpoints.get(path)) {
+            Some(lines) => lines.contains(&line),
+            None => false,
+        }
+    }
+
+    fn poll(&mut self) -> bool {
+        // Handle any requests that arrived while the script was running
+        while let Some(request) = self.client.try_next_request() {
+            if !self.handle_request(&request) {
+                self.client
+                    .respond_with_error(&request, "The script is running");
+            }
+        }
+
+        // Pause so that the script can be stopped, see `paused`
+        if self.client.is_closed() {
+            return true;
+        }
+
+        if self.pause_requested {
+            self.pause_requested = false;
+            self.pending_stop_reason = Some("pause");
+            return true;
+        }
+
+        false
+    }
+
+    fn paused(&mut self, reason: PauseReason, frames: &[DebugFrame], vm: &mut Vm) -> StepMode {
+        // The session has ended, so the script is interrupted rather than being paused
+        if self.client.is_closed() {
+            vm.interrupt_handle().interrupt();
+            return StepMode::Continue;
+        }
+
+        let reason = self.pending_stop_reason.take().unwrap_or(match reason {
+            PauseReason::Breakpoint => "breakpoint",
+            PauseReason::Step => "step",
+            PauseReason::Requested => "pause",
+        });
+        self.client.send_event(
+            "stopped",
+            json!({"reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true}),
+        );
+
+        while let Some(request) = self.client.next_request() {
+            let step_mode = match request["command"].as_str().unwrap_or_default() {
+                "continue" => StepMode::Continue,
+                "next" => StepMode::StepOver,
+                "stepIn" => StepMode::StepIn,
+                "stepOut" => StepMode::StepOut,
+                "stackTrace" => {
+                    self.stack_trace(&request, frames);
+                    continue;
+                }
+                "scopes" => {
+                    self.scopes(&request);
+                    continue;
+                }
+                "variables" => {
+                    self.variables(&request, frames, vm);
+                    continue;
+                }
+                _ => {
+                    if !self.handle_request(&request) {
+                        self.client
+                            .respond_with_error(&request, "Unsupported request");
+                    }
+                    continue;
+                }
+            };
+
+            self.client
+                .respond(&request, json!({"allThreadsContinued": true}));
+            return step_mode;
+        }
+
+        // The session ended while paused
+        vm.interrupt_handle().interrupt();
+        StepMode::Continue
+    }
+}
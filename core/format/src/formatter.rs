@@ -0,0 +1,1539 @@
+use crate::FormatOptions;
+use koto_lexer::{Lexer, Position, Span, Token};
+use koto_parser::{
+    Ast, AstBinaryOp, AstIndex, AstString, AstUnaryOp, ImportItemNode, LookupNode, MapKey,
+    MatchArm, MetaKeyId, Node, QuotationMark, StringAlignment, StringFormatOptions, StringNode,
+    StringRepresentation,
+};
+
+// A token from the lexer, along with its location in the source
+struct TokenInfo<'a> {
+    token: Token,
+    span: Span,
+    slice: &'a str,
+    // The byte position in the source where the token starts
+    start: usize,
+}
+
+// Prints an AST back out as source code
+//
+// Layout decisions that aren't captured in the AST (e.g. whether a function's body was written as
+// an indented block, or whether a list was spread over several lines) are taken from the
+// original source via the lexer's tokens, which are also used to find the comments that should be
+// placed around the printed expressions.
+pub(crate) struct Formatter<'a> {
+    source: &'a str,
+    ast: &'a Ast,
+    options: FormatOptions,
+    tokens: Vec<TokenInfo<'a>>,
+    // The indices of the comment tokens, in source order
+    comments: Vec<usize>,
+    // The index in `comments` of the next comment that should be emitted
+    next_comment: usize,
+    // A cache of node start positions, see node_token()
+    node_starts: Vec<Option<Position>>,
+    output: String,
+    // The indentation level of the current output line
+    indent: usize,
+    // Blank lines are preserved between lines, but not at the start of a block
+    allow_blank_line: bool,
+    // The indentation of continuation lines in a chain of binary operations
+    binary_op_continuation: Option<usize>,
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new(source: &'a str, ast: &'a Ast, options: FormatOptions) -> Self {
+        let mut lexer = Lexer::new(source);
+        let mut tokens = Vec::new();
+        let mut comments = Vec::new();
+
+        while let Some(token) = lexer.next() {
+            let slice = lexer.slice();
+            if matches!(token, Token::CommentSingle | Token::CommentMulti) {
+                comments.push(tokens.len());
+            }
+            tokens.push(TokenInfo {
+                token,
+                span: lexer.span(),
+                slice,
+                start: lexer.source_position() - slice.len(),
+            });
+        }
+
+        Self {
+            source,
+            ast,
+            options,
+            tokens,
+            comments,
+            next_comment: 0,
+            node_starts: vec![None; ast.nodes().len()],
+            output: String::with_capacity(source.len()),
+            indent: 0,
+            allow_blank_line: false,
+            binary_op_continuation: None,
+        }
+    }
+
+    pub fn format(mut self) -> String {
+        let ast = self.ast;
+        if let Some(Node::MainBlock { body, .. }) = ast.entry_point().map(|entry| &entry.node) {
+            for line in body {
+                let line_start = self.node_token(*line);
+                self.start_line(line_start);
+                self.expression(*line);
+            }
+        }
+
+        // Emit any remaining comments at the end of the script
+        while self.next_comment < self.comments.len() {
+            self.emit_comment();
+        }
+
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+
+        self.output
+    }
+
+    fn expression(&mut self, index: AstIndex) {
+        let ast = self.ast;
+        let continuation_indent = self.binary_op_continuation.take();
+
+        match &ast.node(index).node {
+            Node::Null => {
+                if matches!(
+                    self.token_at_node_span(index),
+                    Some(Token::RoundOpen | Token::RoundClose)
+                ) {
+                    self.push("()");
+                } else {
+                    self.push("null");
+                }
+            }
+            Node::Nested(expression) => {
+                self.push("(");
+                self.expression(*expression);
+                self.push(")");
+            }
+            Node::Id(id, type_hint) => {
+                self.push_constant(*id);
+                if let Some(type_hint) = type_hint {
+                    self.push(": ");
+                    self.expression(*type_hint);
+                }
+            }
+            Node::Meta(key, name) => self.meta_key(*key, *name),
+            Node::Lookup(_) => self.lookup(index),
+            Node::NamedCall { id, args } => {
+                self.push_constant(*id);
+                self.push(" ");
+                self.comma_separated(args);
+            }
+            Node::BoolTrue => self.push("true"),
+            Node::BoolFalse => self.push("false"),
+            Node::SmallInt(n) => self.number(index, *n < 0, || n.to_string()),
+            Node::Int(constant) => {
+                let n = ast.constants().get_i64(*constant);
+                self.number(index, n < 0, || n.to_string());
+            }
+            Node::Float(constant) => {
+                let n = ast.constants().get_f64(*constant);
+                self.number(index, n.is_sign_negative(), || format!("{n:?}"));
+            }
+            Node::Str(string) => {
+                let open_quote = self.token_index_at(ast.span(ast.node(index).span).start);
+                self.string(string, open_quote);
+            }
+            Node::List(elements) => {
+                let open = self.token_index_at(ast.span(ast.node(index).span).start);
+                self.container("[", "]", open, elements.len(), false, |f, i, _| {
+                    f.expression(elements[i])
+                });
+            }
+            Node::Tuple(elements) => {
+                if self.token_at_node_span(index) == Some(Token::RoundOpen) {
+                    if elements.is_empty() {
+                        self.push("(,)");
+                    } else {
+                        let open = self.token_index_at(ast.span(ast.node(index).span).start);
+                        self.container("(", ")", open, elements.len(), true, |f, i, _| {
+                            f.expression(elements[i])
+                        });
+                    }
+                } else {
+                    // An implicit tuple, e.g. `x = 1, 2, 3`
+                    self.comma_separated(elements);
+                    if elements.len() == 1 {
+                        self.push(",");
+                    }
+                }
+            }
+            Node::TempTuple(elements) => self.comma_separated(elements),
+            Node::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                self.expression(*start);
+                self.push(if *inclusive { "..=" } else { ".." });
+                self.expression(*end);
+            }
+            Node::RangeFrom { start } => {
+                self.expression(*start);
+                self.push("..");
+            }
+            Node::RangeTo { end, inclusive } => {
+                self.push(if *inclusive { "..=" } else { ".." });
+                self.expression(*end);
+            }
+            Node::RangeFull => self.push(".."),
+            Node::Map(entries) => {
+                if self.token_at_node_span(index) == Some(Token::CurlyOpen) {
+                    let open = self.token_index_at(ast.span(ast.node(index).span).start);
+                    self.container("{", "}", open, entries.len(), false, |f, i, entry_token| {
+                        let (key, value) = &entries[i];
+                        f.map_key(key, entry_token);
+                        if let Some(value) = value {
+                            f.push(":");
+                            f.assigned_value(*value);
+                        }
+                    });
+                } else {
+                    self.map_block(index, entries);
+                }
+            }
+            Node::MapPattern { entries, rest } => {
+                let open = self.token_index_at(ast.span(ast.node(index).span).start);
+                let count = entries.len() + usize::from(rest.is_some());
+                self.container(
+                    "{",
+                    "}",
+                    open,
+                    count,
+                    false,
+                    |f, i, entry_token| match entries.get(i) {
+                        Some(entry) => {
+                            f.map_key(&entry.key, entry_token);
+                            if let Some(pattern) = entry.pattern {
+                                f.push(": ");
+                                f.pattern(pattern);
+                            }
+                            if let Some(default) = entry.default {
+                                f.push(" = ");
+                                f.expression(default);
+                            }
+                        }
+                        None => {
+                            if let Some(rest) = rest {
+                                f.expression(*rest);
+                            }
+                        }
+                    },
+                );
+            }
+            Node::Self_ => self.push("self"),
+            Node::MainBlock { .. } => {}
+            Node::Block(_) => self.block(index),
+            Node::Function(function) => {
+                self.push("|");
+                for (i, arg) in function.args.iter().enumerate() {
+                    if i > 0 {
+                        self.push(", ");
+                    }
+                    self.pattern(*arg);
+                }
+                if function.is_variadic {
+                    self.push("...");
+                }
+                self.push("|");
+                if let Some(output_type) = function.output_type {
+                    self.push(" -> ");
+                    self.expression(output_type);
+                }
+                self.body(function.body);
+            }
+            Node::Import { from, items } => {
+                let mut string_tokens = self.import_string_tokens(index).into_iter();
+                let mut import_item = |f: &mut Self, item: &ImportItemNode| match item {
+                    ImportItemNode::Id(id) => f.push_constant(*id),
+                    ImportItemNode::Str(string) => f.string(string, string_tokens.next()),
+                };
+
+                if !from.is_empty() {
+                    self.push("from ");
+                    for (i, item) in from.iter().enumerate() {
+                        if i > 0 {
+                            self.push(".");
+                        }
+                        import_item(self, item);
+                    }
+                    self.push(" ");
+                }
+                self.push("import ");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.push(", ");
+                    }
+                    import_item(self, item);
+                }
+            }
+            Node::Export(expression) => {
+                // Meta key assignments (e.g. `@main = ...`) are exported without a keyword
+                if self.token_at_node_span(index) == Some(Token::Export) {
+                    self.push("export ");
+                }
+                self.expression(*expression);
+            }
+            Node::Assign { target, expression } => {
+                self.expression(*target);
+                self.push(" =");
+                self.assigned_value(*expression);
+            }
+            Node::MultiAssign {
+                targets,
+                expression,
+            } => {
+                self.comma_separated(targets);
+                self.push(" =");
+                self.assigned_value(*expression);
+            }
+            Node::UnaryOp { op, value } => {
+                self.push(match op {
+                    AstUnaryOp::Negate => "-",
+                    AstUnaryOp::Not => "not ",
+                });
+                self.expression(*value);
+            }
+            Node::BinaryOp { op, lhs, rhs } => {
+                // Line breaks around operators are preserved, with the continuation lines of a
+                // chain of operations sharing the same indentation.
+                let indent = self.indent;
+                let continuation_indent = continuation_indent.unwrap_or(indent + 1);
+
+                self.binary_op_continuation = Some(continuation_indent);
+                self.expression(*lhs);
+
+                let op_token = self.token_index_at(ast.span(ast.node(index).span).start);
+                if let Some(op_token) = op_token.filter(|token| self.starts_line(*token)) {
+                    self.indent = continuation_indent;
+                    self.start_line(op_token);
+                } else {
+                    self.push(" ");
+                }
+                self.push(binary_op_str(*op));
+
+                let rhs_token = self.node_token(*rhs);
+                if self.starts_line(rhs_token) {
+                    self.indent = continuation_indent;
+                    self.start_line(rhs_token);
+                } else {
+                    self.push(" ");
+                }
+
+                self.binary_op_continuation = Some(continuation_indent);
+                self.expression(*rhs);
+                self.indent = indent;
+            }
+            Node::If(ast_if) => {
+                self.push("if ");
+                self.expression(ast_if.condition);
+
+                if ast_if.else_if_blocks.is_empty() && !self.is_block(ast_if.then_node) {
+                    self.push(" then ");
+                    self.expression(ast_if.then_node);
+                    if let Some(else_node) = ast_if.else_node {
+                        self.push(" else ");
+                        self.expression(else_node);
+                    }
+                } else {
+                    self.block(ast_if.then_node);
+                    for (condition, block) in ast_if.else_if_blocks.iter() {
+                        self.start_line_before_node(*condition);
+                        self.push("else if ");
+                        self.expression(*condition);
+                        self.block(*block);
+                    }
+                    if let Some(else_node) = ast_if.else_node {
+                        self.start_line_before_node(else_node);
+                        self.push("else");
+                        self.block(else_node);
+                    }
+                }
+            }
+            Node::Match { expression, arms } => {
+                self.push("match ");
+                self.expression(*expression);
+                self.indented_lines(
+                    arms.len(),
+                    |f, i| {
+                        let arm = &arms[i];
+                        match (arm.patterns.first(), arm.condition) {
+                            (Some(first_pattern), _) => f.node_token(*first_pattern),
+                            (None, Some(condition)) => f.token_before_node(condition),
+                            (None, None) => f.token_before_node(arm.expression),
+                        }
+                    },
+                    |f, i| f.match_arm(&arms[i]),
+                );
+            }
+            Node::Switch(arms) => {
+                self.push("switch");
+                self.indented_lines(
+                    arms.len(),
+                    |f, i| match arms[i].condition {
+                        Some(condition) => f.node_token(condition),
+                        None => f.token_before_node(arms[i].expression),
+                    },
+                    |f, i| {
+                        let arm = &arms[i];
+                        match arm.condition {
+                            Some(condition) => {
+                                f.expression(condition);
+                                f.push(" then");
+                            }
+                            None => f.push("else"),
+                        }
+                        f.body(arm.expression);
+                    },
+                );
+            }
+            Node::Wildcard(name) => {
+                self.push("_");
+                if let Some(name) = name {
+                    self.push_constant(*name);
+                }
+            }
+            Node::Ellipsis(name) => {
+                if let Some(name) = name {
+                    self.push_constant(*name);
+                }
+                self.push("...");
+            }
+            Node::For(ast_for) => {
+                self.push("for ");
+                self.comma_separated(&ast_for.args);
+                self.push(" in ");
+                self.expression(ast_for.iterable);
+                self.block(ast_for.body);
+            }
+            Node::Loop { body } => {
+                self.push("loop");
+                self.block(*body);
+            }
+            Node::While { condition, body } => {
+                self.push("while ");
+                self.expression(*condition);
+                self.block(*body);
+            }
+            Node::Until { condition, body } => {
+                self.push("until ");
+                self.expression(*condition);
+                self.block(*body);
+            }
+            Node::Break(value) => self.keyword_with_value("break", *value),
+            Node::Continue => self.push("continue"),
+            Node::Return(value) => self.keyword_with_value("return", *value),
+            Node::Try(ast_try) => {
+                self.push("try");
+                self.block(ast_try.try_block);
+                self.start_line_before_node(ast_try.catch_arg);
+                self.push("catch ");
+                self.expression(ast_try.catch_arg);
+                self.block(ast_try.catch_block);
+                if let Some(finally_block) = ast_try.finally_block {
+                    self.start_line_before_node(finally_block);
+                    self.push("finally");
+                    self.block(finally_block);
+                }
+            }
+            Node::Throw(value) => self.keyword_with_value("throw", Some(*value)),
+            Node::Yield(value) => self.keyword_with_value("yield", Some(*value)),
+            Node::Type(type_index) => self.push_constant(*type_index),
+            Node::Debug {
+                expression_string, ..
+            } => {
+                // The expression's original source is kept as it's displayed when the script runs
+                self.push("debug ");
+                self.push_constant(*expression_string);
+            }
+        }
+    }
+
+    fn lookup(&mut self, index: AstIndex) {
+        let ast = self.ast;
+        let indent = self.indent;
+        let mut next = Some(index);
+        let mut previous_was_call_without_parens = false;
+
+        while let Some(current) = next {
+            let Node::Lookup((lookup_node, next_node)) = &ast.node(current).node else {
+                break;
+            };
+            next = *next_node;
+
+            match lookup_node {
+                LookupNode::Root(root) => self.expression(*root),
+                LookupNode::Id(_) | LookupNode::Str(_) => {
+                    let key_token = self.token_index_at(ast.span(ast.node(current).span).start);
+                    let dot = key_token.and_then(|key_token| self.token_before(key_token));
+
+                    // Accesses that started a new line in the source are kept on their own line,
+                    // which is also required after a call without parentheses.
+                    let dot_starts_line = dot.is_some_and(|dot| self.starts_line(dot));
+                    if dot_starts_line || previous_was_call_without_parens {
+                        self.indent = indent + 1;
+                        match dot {
+                            Some(dot) => self.start_line(dot),
+                            None => self.newline(false),
+                        }
+                    }
+
+                    self.push(".");
+                    match lookup_node {
+                        LookupNode::Id(id) => self.push_constant(*id),
+                        LookupNode::Str(string) => self.string(string, key_token),
+                        _ => unreachable!(),
+                    }
+                }
+                LookupNode::Index(expression) => {
+                    self.push("[");
+                    self.expression(*expression);
+                    self.push("]");
+                }
+                LookupNode::Call {
+                    args,
+                    with_parens: true,
+                } => {
+                    let open = args.first().map(|arg| self.token_before_node(*arg));
+                    self.container("(", ")", open, args.len(), false, |f, i, _| {
+                        f.expression(args[i])
+                    });
+                }
+                LookupNode::Call {
+                    args,
+                    with_parens: false,
+                } => {
+                    self.push(" ");
+                    self.comma_separated(args);
+                }
+            }
+
+            previous_was_call_without_parens = matches!(
+                lookup_node,
+                LookupNode::Call {
+                    with_parens: false,
+                    ..
+                }
+            );
+        }
+
+        self.indent = indent;
+    }
+
+    // Prints comma-separated items enclosed by brackets, e.g. a list or the arguments of a call
+    //
+    // If the items were spread over several lines in the source with the first item starting on
+    // a new line, then each item is placed on its own line.
+    fn container(
+        &mut self,
+        open: &str,
+        close: &str,
+        open_token: Option<usize>,
+        count: usize,
+        single_item_needs_comma: bool,
+        mut item: impl FnMut(&mut Self, usize, Option<usize>),
+    ) {
+        let close_token = open_token.and_then(|open_token| self.matching_close(open_token));
+        let item_tokens = match (open_token, close_token) {
+            (Some(open_token), Some(close_token)) => {
+                self.container_item_tokens(open_token, close_token)
+            }
+            _ => Vec::new(),
+        };
+
+        self.push(open);
+
+        let one_item_per_line = match (open_token, item_tokens.first()) {
+            (Some(open_token), Some(first_item)) => {
+                item_tokens.len() == count
+                    && self.tokens[*first_item].span.start.line
+                        > self.tokens[open_token].span.start.line
+            }
+            _ => false,
+        };
+
+        if one_item_per_line {
+            self.indent += 1;
+            self.allow_blank_line = false;
+            for (i, item_token) in item_tokens.iter().enumerate() {
+                self.start_line(*item_token);
+                item(self, i, Some(*item_token));
+                self.push(",");
+            }
+            if let Some(close_token) = close_token {
+                self.emit_comments_before(close_token);
+            }
+            self.indent -= 1;
+            self.newline(false);
+        } else {
+            let items_start = self.output.len();
+            for i in 0..count {
+                if i > 0 {
+                    self.push(", ");
+                }
+                item(self, i, item_tokens.get(i).copied());
+            }
+            if count == 1 && single_item_needs_comma {
+                self.push(",");
+            }
+            // Place the closing bracket on a new line if the items included an indented block
+            if self.output[items_start..].contains('\n') {
+                if let Some(close_token) = close_token {
+                    self.emit_comments_before(close_token);
+                }
+                self.newline(false);
+            }
+        }
+
+        self.push(close);
+    }
+
+    // Prints a map block, i.e. a map with its entries on indented lines without braces
+    fn map_block(&mut self, index: AstIndex, entries: &[(MapKey, Option<AstIndex>)]) {
+        if self.current_line_is_empty() {
+            // The map is at the start of a line, so the entries can be placed on the current line
+            // and on the lines that follow, e.g. when the map is in an indented block
+            for (i, entry) in entries.iter().enumerate() {
+                let entry_start = self.map_block_entry_start(index, entry);
+                if i > 0 {
+                    self.start_line(entry_start);
+                }
+                self.map_block_entry(entry, entry_start);
+            }
+        } else {
+            self.trim_end();
+            self.indented_lines(
+                entries.len(),
+                |f, i| f.map_block_entry_start(index, &entries[i]),
+                |f, i| {
+                    let entry_start = f.map_block_entry_start(index, &entries[i]);
+                    f.map_block_entry(&entries[i], entry_start);
+                },
+            );
+        }
+    }
+
+    // Returns the index of the token that starts a map block entry
+    fn map_block_entry_start(
+        &mut self,
+        map: AstIndex,
+        (_, value): &(MapKey, Option<AstIndex>),
+    ) -> usize {
+        match value {
+            Some(value) => {
+                // The key precedes the value's `:` at the start of the line
+                let colon = self.token_before_node(*value);
+                self.first_token_in_line(colon)
+            }
+            None => self.node_token(map),
+        }
+    }
+
+    fn map_block_entry(&mut self, (key, value): &(MapKey, Option<AstIndex>), key_token: usize) {
+        self.map_key(key, Some(key_token));
+        if let Some(value) = value {
+            self.push(":");
+            self.assigned_value(*value);
+        }
+    }
+
+    fn map_key(&mut self, key: &MapKey, key_token: Option<usize>) {
+        match key {
+            MapKey::Id(id) => self.push_constant(*id),
+            MapKey::Str(string) => self.string(string, key_token),
+            MapKey::Meta(key, name) => self.meta_key(*key, *name),
+        }
+    }
+
+    fn meta_key(&mut self, key: MetaKeyId, name: Option<u32>) {
+        self.push("@");
+        self.push(meta_key_str(key));
+        if let Some(name) = name {
+            self.push(" ");
+            self.push_constant(name);
+        }
+    }
+
+    // Prints the body of a function or arm, either inline or as an indented block,
+    // following the layout of the original source
+    fn body(&mut self, body: AstIndex) {
+        if self.is_block(body) {
+            self.block(body);
+        } else {
+            self.push(" ");
+            self.expression(body);
+        }
+    }
+
+    // Returns true if the node was written as an indented block in the source
+    fn is_block(&mut self, index: AstIndex) -> bool {
+        if matches!(self.ast.node(index).node, Node::Block(_)) {
+            true
+        } else {
+            let first_token = self.node_token(index);
+            self.starts_line(first_token)
+        }
+    }
+
+    // Prints an indented block
+    //
+    // Single-expression blocks are represented in the AST by the expression itself.
+    fn block(&mut self, index: AstIndex) {
+        let ast = self.ast;
+        let lines = match &ast.node(index).node {
+            Node::Block(lines) => lines.as_slice(),
+            _ => std::slice::from_ref(&index),
+        };
+
+        self.indented_lines(
+            lines.len(),
+            |f, i| f.node_token(lines[i]),
+            |f, i| f.expression(lines[i]),
+        );
+    }
+
+    // Prints a series of lines with an increased level of indentation
+    //
+    // `line_start` provides the index of the token that starts the line in the source.
+    fn indented_lines(
+        &mut self,
+        count: usize,
+        line_start: impl Fn(&mut Self, usize) -> usize,
+        mut line: impl FnMut(&mut Self, usize),
+    ) {
+        if count == 0 {
+            return;
+        }
+
+        self.indent += 1;
+        self.allow_blank_line = false;
+
+        let first_line_start = line_start(self, 0);
+        for i in 0..count {
+            let start = line_start(self, i);
+            self.start_line(start);
+            line(self, i);
+        }
+
+        self.emit_block_end_comments(first_line_start);
+        self.indent -= 1;
+    }
+
+    fn match_arm(&mut self, arm: &MatchArm) {
+        if arm.is_else() && arm.condition.is_none() {
+            self.push("else");
+        } else {
+            for (i, pattern) in arm.patterns.iter().enumerate() {
+                if i > 0 {
+                    self.push(" or ");
+                }
+                self.pattern(*pattern);
+            }
+            if let Some(condition) = arm.condition {
+                if !arm.patterns.is_empty() {
+                    self.push(" ");
+                }
+                self.push("if ");
+                self.expression(condition);
+            }
+            self.push(" then");
+        }
+
+        self.body(arm.expression);
+    }
+
+    // Prints a match pattern or function argument
+    fn pattern(&mut self, index: AstIndex) {
+        let ast = self.ast;
+        match &ast.node(index).node {
+            Node::List(patterns) => {
+                self.push("[");
+                self.patterns(patterns);
+                self.push("]");
+            }
+            Node::Tuple(patterns) => {
+                self.push("(");
+                self.patterns(patterns);
+                self.push(")");
+            }
+            Node::TempTuple(patterns) => self.patterns(patterns),
+            _ => self.expression(index),
+        }
+    }
+
+    fn patterns(&mut self, patterns: &[AstIndex]) {
+        for (i, pattern) in patterns.iter().enumerate() {
+            if i > 0 {
+                self.push(", ");
+            }
+            self.pattern(*pattern);
+        }
+    }
+
+    // Prints comma-separated expressions, e.g. call arguments or the elements of a tuple
+    //
+    // Expressions that started a new line in the source are placed on new indented lines.
+    fn comma_separated(&mut self, expressions: &[AstIndex]) {
+        let indent = self.indent;
+        // Items that start new lines are indented, unless the first item started its own line
+        let continuation_indent = if self.current_line_is_empty() {
+            indent
+        } else {
+            indent + 1
+        };
+
+        for (i, expression) in expressions.iter().enumerate() {
+            let first_token = self.node_token(*expression);
+            if self.starts_line(first_token) && !self.current_line_is_empty() {
+                if i > 0 {
+                    self.push(",");
+                }
+                self.indent = continuation_indent;
+                self.start_line(first_token);
+            } else if i > 0 {
+                self.push(", ");
+            }
+            self.expression(*expression);
+        }
+
+        self.indent = indent;
+    }
+
+    // Prints the value in an assignment or map entry
+    //
+    // The value is placed on a new indented line if it started a new line in the source.
+    fn assigned_value(&mut self, value: AstIndex) {
+        if matches!(self.ast.node(value).node, Node::Block(_)) {
+            self.block(value);
+            return;
+        }
+
+        let first_token = self.node_token(value);
+        if self.starts_line(first_token) {
+            self.indent += 1;
+            self.start_line(first_token);
+            self.expression(value);
+            self.indent -= 1;
+        } else {
+            self.push(" ");
+            self.expression(value);
+        }
+    }
+
+    fn keyword_with_value(&mut self, keyword: &str, value: Option<AstIndex>) {
+        self.push(keyword);
+        if let Some(value) = value {
+            self.push(" ");
+            self.expression(value);
+        }
+    }
+
+    // Returns the indices of the opening quotes of an import expression's string items
+    fn import_string_tokens(&mut self, index: AstIndex) -> Vec<usize> {
+        let Node::Import { from, items } = &self.ast.node(index).node else {
+            return Vec::new();
+        };
+        let string_count = from
+            .iter()
+            .chain(items.iter())
+            .filter(|item| matches!(item, ImportItemNode::Str(_)))
+            .count();
+
+        let mut result = Vec::with_capacity(string_count);
+        let mut token = self.node_token(index);
+        while result.len() < string_count && token < self.tokens.len() {
+            if matches!(
+                self.tokens[token].token,
+                Token::SingleQuote | Token::DoubleQuote
+            ) {
+                result.push(token);
+                match self.string_end(token) {
+                    Some(end) => token = end,
+                    None => break,
+                }
+            }
+            token += 1;
+        }
+        result
+    }
+
+    // Prints a number, using the number's representation in the source if it's available
+    fn number(&mut self, index: AstIndex, negative: bool, fallback: impl FnOnce() -> String) {
+        let ast = self.ast;
+        match self.token_index_at(ast.span(ast.node(index).span).start) {
+            Some(token) if self.tokens[token].token == Token::Number => {
+                if negative {
+                    self.push("-");
+                }
+                let number: &'a str = self.tokens[token].slice;
+                self.push(number);
+            }
+            _ => self.push(&fallback()),
+        }
+    }
+
+    // Prints a string
+    //
+    // The string is copied from the source when its opening quote is available, which preserves
+    // the string's escape codes and template expressions.
+    fn string(&mut self, string: &AstString, open_quote: Option<usize>) {
+        let source_range = open_quote
+            .filter(|open| {
+                matches!(
+                    self.tokens[*open].token,
+                    Token::SingleQuote | Token::DoubleQuote
+                )
+            })
+            .and_then(|open| {
+                self.string_end(open).map(|close| {
+                    let close = &self.tokens[close];
+                    self.tokens[open].start..close.start + close.slice.len()
+                })
+            });
+
+        if let Some(source_range) = source_range {
+            let source: &'a str = self.source;
+            self.push(&source[source_range]);
+            return;
+        }
+
+        let quote = match string.quotation_mark {
+            QuotationMark::Double => '"',
+            QuotationMark::Single => '\'',
+        };
+
+        self.output.push(quote);
+        for node in string.nodes.iter() {
+            match node {
+                StringNode::Literal(constant) => {
+                    let literal = self.ast.constants().get_str(*constant);
+                    for c in literal.chars() {
+                        match c {
+                            '\\' => self.output.push_str("\\\\"),
+                            '$' => self.output.push_str("\\$"),
+                            '\n' => self.output.push_str("\\n"),
+                            '\r' => self.output.push_str("\\r"),
+                            '\t' => self.output.push_str("\\t"),
+                            c if c == quote => {
+                                self.output.push('\\');
+                                self.output.push(c);
+                            }
+                            c if c.is_control() => {
+                                self.output.push_str(&format!("\\u{{{:x}}}", c as u32))
+                            }
+                            c => self.output.push(c),
+                        }
+                    }
+                }
+                StringNode::Expr(expression) => {
+                    self.push("${");
+                    self.expression(*expression);
+                    self.push("}");
+                }
+                StringNode::FormattedExpr(expression, options) => {
+                    self.push("${");
+                    self.expression(*expression);
+                    self.push(":");
+                    self.string_format_options(options);
+                    self.push("}");
+                }
+            }
+        }
+        self.output.push(quote);
+    }
+
+    fn string_format_options(&mut self, options: &StringFormatOptions) {
+        if let Some(fill) = options.fill_character {
+            self.push_constant(fill);
+        }
+        match options.alignment {
+            StringAlignment::Default => {}
+            StringAlignment::Left => self.push("<"),
+            StringAlignment::Center => self.push("^"),
+            StringAlignment::Right => self.push(">"),
+        }
+        if options.plus_sign {
+            self.push("+");
+        }
+        if options.alternate_form {
+            self.push("#");
+        }
+        if let Some(min_width) = options.min_width {
+            if options.zero_padding {
+                self.push("0");
+            }
+            self.push(&min_width.to_string());
+        }
+        if let Some(grouping) = options.grouping {
+            self.output.push(grouping);
+        }
+        if let Some(precision) = options.precision {
+            self.push(&format!(".{precision}"));
+        }
+        self.push(match options.representation {
+            StringRepresentation::Default => "",
+            StringRepresentation::Hex => "x",
+            StringRepresentation::HexUpper => "X",
+            StringRepresentation::Binary => "b",
+            StringRepresentation::Octal => "o",
+            StringRepresentation::Exponent => "e",
+            StringRepresentation::ExponentUpper => "E",
+        });
+    }
+
+    // Starts a new output line for the source line that starts with the given token
+    //
+    // Any comments that precede the token are emitted first, and a blank line is inserted if the
+    // source line was preceded by one.
+    fn start_line(&mut self, token: usize) {
+        self.emit_comments_before(token);
+        let blank_line = self.blank_line_before(token);
+        self.newline(blank_line);
+    }
+
+    fn start_line_before_node(&mut self, index: AstIndex) {
+        let token = self.token_before_node(index);
+        self.start_line(token);
+    }
+
+    fn newline(&mut self, blank_line: bool) {
+        if !self.output.is_empty() {
+            self.trim_end();
+            if blank_line && self.allow_blank_line {
+                self.output.push('\n');
+            }
+            self.output.push('\n');
+        }
+        self.allow_blank_line = true;
+        for _ in 0..self.indent * self.options.indent_width {
+            self.output.push(' ');
+        }
+    }
+
+    fn emit_comments_before(&mut self, token: usize) {
+        while self
+            .comments
+            .get(self.next_comment)
+            .is_some_and(|comment| *comment < token)
+        {
+            self.emit_comment();
+        }
+    }
+
+    // Emits the comments that follow the last line of an indented block
+    //
+    // Comments that are indented at least as far as the block's first line are kept in the block,
+    // the remaining comments will be emitted before the line that follows the block.
+    fn emit_block_end_comments(&mut self, block_start: usize) {
+        let Position { line, column } = self.tokens[block_start].span.start;
+        let block_end = self.tokens[block_start..]
+            .iter()
+            .position(|token| {
+                !is_trivia(token.token)
+                    && token.span.start.line > line
+                    && token.span.start.column < column
+            })
+            .map_or(self.tokens.len(), |end| block_start + end);
+
+        while let Some(&comment) = self.comments.get(self.next_comment) {
+            if comment > block_end
+                || (self.tokens[comment].span.start.column < column
+                    && !self.is_trailing_comment(comment))
+            {
+                break;
+            }
+            self.emit_comment();
+        }
+    }
+
+    // Emits the next comment, either at the end of the current line or on a new line
+    fn emit_comment(&mut self) {
+        let comment = self.comments[self.next_comment];
+        self.next_comment += 1;
+
+        if self.is_trailing_comment(comment) && !self.current_line_is_empty() {
+            self.output.push(' ');
+        } else {
+            let blank_line = self.blank_line_before(comment);
+            self.newline(blank_line);
+        }
+
+        let text: &'a str = self.tokens[comment].slice;
+        self.push(text.trim_end());
+    }
+
+    // Returns true if the comment follows some code on the same line
+    fn is_trailing_comment(&self, comment: usize) -> bool {
+        self.tokens[..comment]
+            .iter()
+            .rev()
+            .find(|token| token.token != Token::Whitespace)
+            .is_some_and(|token| !token.token.is_newline())
+    }
+
+    // Returns true if the token is the first token on its line
+    fn starts_line(&self, token: usize) -> bool {
+        self.tokens[..token]
+            .iter()
+            .rev()
+            .find(|token| token.token != Token::Whitespace)
+            .is_none_or(|token| token.token.is_newline())
+    }
+
+    // Returns true if there's a blank line between the token and the token that precedes it
+    fn blank_line_before(&self, token: usize) -> bool {
+        let line = self.tokens[token].span.start.line;
+        self.tokens[..token]
+            .iter()
+            .rev()
+            .find(|token| !matches!(token.token, Token::Whitespace) && !token.token.is_newline())
+            .is_some_and(|previous| line > previous.span.end.line + 1)
+    }
+
+    // Returns the index of the first non-whitespace, non-comment token on the token's line
+    fn first_token_in_line(&self, token: usize) -> usize {
+        let mut result = token;
+        for (i, previous) in self.tokens[..token].iter().enumerate().rev() {
+            if previous.token.is_newline() {
+                break;
+            } else if !is_trivia(previous.token) {
+                result = i;
+            }
+        }
+        result
+    }
+
+    // Returns the index of the first token of the node in the source
+    fn node_token(&mut self, index: AstIndex) -> usize {
+        let start = self.node_start(index);
+        self.first_token_from(start)
+    }
+
+    // Returns the start position of the node, including its child nodes
+    //
+    // A node's span doesn't necessarily start at the beginning of the expression,
+    // e.g. the span of a binary operation starts at the operator, and the span of a return
+    // expression starts at its value.
+    fn node_start(&mut self, index: AstIndex) -> Position {
+        if let Some(start) = self.node_starts[index as usize] {
+            return start;
+        }
+
+        let ast = self.ast;
+        let node = ast.node(index);
+        let mut start = ast.span(node.span).start;
+
+        if let Node::Map(entries) = &node.node {
+            // The span of a map block starts before its first key
+            if let (Some((_, Some(value))), false) = (
+                entries.first(),
+                self.token_at_node_span(index) == Some(Token::CurlyOpen),
+            ) {
+                let colon = self.token_before_node(*value);
+                start = self.tokens[self.first_token_in_line(colon)].span.start;
+            }
+        }
+
+        for child in children(&node.node) {
+            let child_start = self.node_start(child);
+            if is_before(child_start, start) {
+                start = child_start;
+            }
+        }
+
+        let prefix = match &node.node {
+            Node::Return(_) => Some(Token::Return),
+            Node::Yield(_) => Some(Token::Yield),
+            Node::Loop { .. } => Some(Token::Loop),
+            Node::While { .. } => Some(Token::While),
+            Node::Until { .. } => Some(Token::Until),
+            Node::UnaryOp { op, .. } => Some(match op {
+                AstUnaryOp::Negate => Token::Subtract,
+                AstUnaryOp::Not => Token::Not,
+            }),
+            Node::Meta(..) => Some(Token::At),
+            // Negative numbers are parsed as a single node, with a span that excludes the `-`
+            Node::SmallInt(_) | Node::Int(_) | Node::Float(_) => Some(Token::Subtract),
+            _ => None,
+        };
+        if let Some(prefix) = prefix {
+            // Include the node's keyword or operator if it precedes the start of the span
+            let mut previous = self.token_before(self.first_token_from(start));
+            if matches!(node.node, Node::Meta(_, Some(_)))
+                && previous.is_some_and(|previous| self.tokens[previous].token != prefix)
+            {
+                // Skip over the meta key's name, e.g. `@test foo`
+                previous = previous.and_then(|previous| self.token_before(previous));
+            }
+            let is_number = matches!(node.node, Node::SmallInt(_) | Node::Int(_) | Node::Float(_));
+            if let Some(previous) = previous.filter(|previous| {
+                let previous = &self.tokens[*previous];
+                previous.token == prefix && (!is_number || previous.span.end == start)
+            }) {
+                start = self.tokens[previous].span.start;
+            }
+        }
+
+        self.node_starts[index as usize] = Some(start);
+        start
+    }
+
+    // Returns the index of the first token that starts at or after the given position,
+    // ignoring whitespace and comments
+    fn first_token_from(&self, position: Position) -> usize {
+        let first = self
+            .tokens
+            .partition_point(|token| is_before(token.span.start, position));
+        self.tokens[first..]
+            .iter()
+            .position(|token| !is_trivia(token.token))
+            .map_or(self.tokens.len().saturating_sub(1), |i| first + i)
+    }
+
+    // Returns the index of the token that precedes the node, ignoring whitespace and comments
+    fn token_before_node(&mut self, index: AstIndex) -> usize {
+        let first_token = self.node_token(index);
+        self.token_before(first_token).unwrap_or(first_token)
+    }
+
+    fn token_before(&self, token: usize) -> Option<usize> {
+        self.tokens[..token]
+            .iter()
+            .rposition(|token| !is_trivia(token.token))
+    }
+
+    // Returns the index of the token that starts at the given position
+    fn token_index_at(&self, position: Position) -> Option<usize> {
+        let index = self
+            .tokens
+            .partition_point(|token| is_before(token.span.start, position));
+        self.tokens[index..]
+            .iter()
+            .position(|token| !is_trivia(token.token))
+            .map(|i| index + i)
+            .filter(|i| self.tokens[*i].span.start == position)
+    }
+
+    fn token_at_node_span(&self, index: AstIndex) -> Option<Token> {
+        let ast = self.ast;
+        self.token_index_at(ast.span(ast.node(index).span).start)
+            .map(|token| self.tokens[token].token)
+    }
+
+    // Returns the index of the bracket that closes the given opening bracket
+    fn matching_close(&self, open: usize) -> Option<usize> {
+        let mut depth = 0;
+        for (i, token) in self.tokens.iter().enumerate().skip(open) {
+            if is_open_bracket(token.token) {
+                depth += 1;
+            } else if is_close_bracket(token.token) {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+        }
+        None
+    }
+
+    // Returns the indices of the tokens that start each comma-separated item between brackets
+    fn container_item_tokens(&self, open: usize, close: usize) -> Vec<usize> {
+        let mut result = Vec::new();
+        let mut depth = 0;
+        let mut expecting_item = true;
+
+        for (i, token) in self.tokens.iter().enumerate().take(close).skip(open + 1) {
+            if is_trivia(token.token) {
+                continue;
+            }
+            if depth == 0 && expecting_item {
+                result.push(i);
+                expecting_item = false;
+            }
+            if is_open_bracket(token.token) {
+                depth += 1;
+            } else if is_close_bracket(token.token) {
+                depth -= 1;
+            } else if token.token == Token::Comma && depth == 0 {
+                expecting_item = true;
+            }
+        }
+
+        result
+    }
+
+    // Returns the index of the quote that closes the string opened with the given quote
+    fn string_end(&self, open: usize) -> Option<usize> {
+        let quote = self.tokens[open].token;
+        let mut depth = 0;
+        for (i, token) in self.tokens.iter().enumerate().skip(open + 1) {
+            if token.token == Token::CurlyOpen {
+                depth += 1;
+            } else if token.token == Token::CurlyClose {
+                depth -= 1;
+            } else if token.token == quote && depth == 0 {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    fn push(&mut self, s: &str) {
+        self.output.push_str(s);
+    }
+
+    fn push_constant(&mut self, constant: u32) {
+        let ast = self.ast;
+        self.push(ast.constants().get_str(constant));
+    }
+
+    fn trim_end(&mut self) {
+        let trimmed_len = self.output.trim_end_matches(' ').len();
+        self.output.truncate(trimmed_len);
+    }
+
+    fn current_line_is_empty(&self) -> bool {
+        let line_start = self.output.rfind('\n').map_or(0, |i| i + 1);
+        self.output[line_start..].trim().is_empty()
+    }
+}
+
+// Returns the indices of the node's children
+fn children(node: &Node) -> Vec<AstIndex> {
+    use Node::*;
+
+    match node {
+        Null
+        | Id(_, None)
+        | Meta(..)
+        | BoolTrue
+        | BoolFalse
+        | SmallInt(_)
+        | Int(_)
+        | Float(_)
+        | RangeFull
+        | Self_
+        | Import { .. }
+        | Wildcard(_)
+        | Ellipsis(_)
+        | Continue
+        | Break(None)
+        | Return(None)
+        | Type(_) => Vec::new(),
+        Id(_, Some(type_hint)) => vec![*type_hint],
+        Nested(expression)
+        | Export(expression)
+        | Throw(expression)
+        | Yield(expression)
+        | Break(Some(expression))
+        | Return(Some(expression))
+        | RangeFrom { start: expression }
+        | RangeTo {
+            end: expression, ..
+        }
+        | Loop { body: expression }
+        | UnaryOp {
+            value: expression, ..
+        }
+        | Debug { expression, .. } => vec![*expression],
+        Lookup((lookup_node, next)) => {
+            let mut result = match lookup_node {
+                LookupNode::Root(expression) | LookupNode::Index(expression) => vec![*expression],
+                LookupNode::Id(_) => Vec::new(),
+                LookupNode::Str(string) => string_expressions(string),
+                LookupNode::Call { args, .. } => args.clone(),
+            };
+            result.extend(next);
+            result
+        }
+        NamedCall { args, .. } => args.clone(),
+        Str(string) => string_expressions(string),
+        List(expressions) | Tuple(expressions) | TempTuple(expressions) | Block(expressions) => {
+            expressions.clone()
+        }
+        MainBlock { body, .. } => body.clone(),
+        Range { start, end, .. } => vec![*start, *end],
+        MapPattern { entries, rest } => entries
+            .iter()
+            .flat_map(|entry| entry.pattern.into_iter().chain(entry.default))
+            .chain(*rest)
+            .collect(),
+        Map(entries) => entries
+            .iter()
+            .flat_map(|(key, value)| {
+                let mut result = match key {
+                    MapKey::Str(string) => string_expressions(string),
+                    _ => Vec::new(),
+                };
+                result.extend(value);
+                result
+            })
+            .collect(),
+        Function(function) => {
+            let mut result = function.args.clone();
+            result.extend(function.output_type);
+            result.push(function.body);
+            result
+        }
+        Assign { target, expression } => vec![*target, *expression],
+        MultiAssign {
+            targets,
+            expression,
+        } => {
+            let mut result = targets.clone();
+            result.push(*expression);
+            result
+        }
+        BinaryOp { lhs, rhs, .. } => vec![*lhs, *rhs],
+        If(ast_if) => {
+            let mut result = vec![ast_if.condition, ast_if.then_node];
+            for (condition, block) in ast_if.else_if_blocks.iter() {
+                result.push(*condition);
+                result.push(*block);
+            }
+            result.extend(ast_if.else_node);
+            result
+        }
+        Match { expression, arms } => {
+            let mut result = vec![*expression];
+            for arm in arms.iter() {
+                result.extend(arm.patterns.iter());
+                result.extend(arm.condition);
+                result.push(arm.expression);
+            }
+            result
+        }
+        Switch(arms) => arms
+            .iter()
+            .flat_map(|arm| arm.condition.into_iter().chain(Some(arm.expression)))
+            .collect(),
+        For(ast_for) => {
+            let mut result = ast_for.args.clone();
+            result.push(ast_for.iterable);
+            result.push(ast_for.body);
+            result
+        }
+        While { condition, body } | Until { condition, body } => vec![*condition, *body],
+        Try(ast_try) => {
+            let mut result = vec![ast_try.try_block, ast_try.catch_arg, ast_try.catch_block];
+            result.extend(ast_try.finally_block);
+            result
+        }
+    }
+}
+
+fn string_expressions(string: &AstString) -> Vec<AstIndex> {
+    string
+        .nodes
+        .iter()
+        .filter_map(|node| match node {
+            StringNode::Expr(expression) | StringNode::FormattedExpr(expression, _) => {
+                Some(*expression)
+            }
+            StringNode::Literal(_) => None,
+        })
+        .collect()
+}
+
+fn is_trivia(token: Token) -> bool {
+    use Token::*;
+    matches!(
+        token,
+        Whitespace | NewLine | NewLineIndented | CommentSingle | CommentMulti
+    )
+}
+
+fn is_open_bracket(token: Token) -> bool {
+    matches!(
+        token,
+        Token::RoundOpen | Token::SquareOpen | Token::CurlyOpen
+    )
+}
+
+fn is_close_bracket(token: Token) -> bool {
+    matches!(
+        token,
+        Token::RoundClose | Token::SquareClose | Token::CurlyClose
+    )
+}
+
+fn is_before(a: Position, b: Position) -> bool {
+    (a.line, a.column) < (b.line, b.column)
+}
+
+fn binary_op_str(op: AstBinaryOp) -> &'static str {
+    use AstBinaryOp::*;
+    match op {
+        Add => "+",
+        Subtract => "-",
+        Multiply => "*",
+        Divide => "/",
+        Remainder => "%",
+        AddAssign => "+=",
+        SubtractAssign => "-=",
+        MultiplyAssign => "*=",
+        DivideAssign => "/=",
+        RemainderAssign => "%=",
+        Equal => "==",
+        NotEqual => "!=",
+        Less => "<",
+        LessOrEqual => "<=",
+        Greater => ">",
+        GreaterOrEqual => ">=",
+        And => "and",
+        Or => "or",
+        Pipe => ">>",
+    }
+}
+
+fn meta_key_str(key: MetaKeyId) -> &'static str {
+    use MetaKeyId::*;
+    match key {
+        Add => "+",
+        Subtract => "-",
+        Multiply => "*",
+        Divide => "/",
+        Remainder => "%",
+        AddAssign => "+=",
+        SubtractAssign => "-=",
+        MultiplyAssign => "*=",
+        DivideAssign => "/=",
+        RemainderAssign => "%=",
+        Less => "<",
+        LessOrEqual => "<=",
+        Greater => ">",
+        GreaterOrEqual => ">=",
+        Equal => "==",
+        NotEqual => "!=",
+        Index => "[]",
+        Display => "display",
+        Format => "format",
+        Iterator => "iterator",
+        Next => "next",
+        NextBack => "next_back",
+        Negate => "negate",
+        Not => "not",
+        Type => "type",
+        Base => "base",
+        Call => "||",
+        Tests => "tests",
+        Test => "test",
+        PreTest => "pre_test",
+        PostTest => "post_test",
+        Main => "main",
+        Named => "meta",
+        Invalid => "",
+    }
+}
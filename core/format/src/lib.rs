@@ -0,0 +1,85 @@
+//! A source code formatter for the Koto programming language
+//!
+//! Scripts are parsed, and then the resulting [Ast](koto_parser::Ast) is printed back out as
+//! source code with consistent indentation and spacing. Comments are preserved.
+//!
+//! ## Example
+//!
+//! ```
+//! use koto_format::{format, FormatOptions};
+//!
+//! let script = "x=[1,2,3] # A list\nf = |a,b|   a+b\n";
+//! let formatted = format(script, FormatOptions::default()).unwrap();
+//! assert_eq!(formatted, "x = [1, 2, 3] # A list\nf = |a, b| a + b\n");
+//! ```
+
+#![warn(missing_docs)]
+
+mod formatter;
+
+use crate::formatter::Formatter;
+use koto_parser::{Ast, Node, Parser, ParserError};
+use thiserror::Error;
+
+/// Options that control the output of [format]
+#[derive(Clone, Copy, Debug)]
+pub struct FormatOptions {
+    /// The number of spaces to use for each level of indentation
+    ///
+    /// Default: 2
+    pub indent_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self { indent_width: 2 }
+    }
+}
+
+/// An error that can be returned by [format]
+#[derive(Error, Clone, Debug)]
+pub enum FormatError {
+    /// The script couldn't be parsed
+    #[error(transparent)]
+    ParserError(#[from] ParserError),
+    /// The formatted output would change the meaning of the script
+    ///
+    /// The output is parsed and compared against the original script before being returned,
+    /// this error indicates a bug in the formatter.
+    #[error("The formatted script doesn't match the original script")]
+    OutputMismatch,
+}
+
+/// Formats a Koto script
+///
+/// The formatted script is checked to make sure that it's equivalent to the original script
+/// before it's returned.
+pub fn format(source: &str, options: FormatOptions) -> Result<String, FormatError> {
+    let ast = Parser::parse(source)?;
+    let output = Formatter::new(source, &ast, options).format();
+
+    match Parser::parse(&output) {
+        Ok(formatted_ast) if is_same_program(&ast, &formatted_ast) => Ok(output),
+        _ => Err(FormatError::OutputMismatch),
+    }
+}
+
+// Returns true if the two ASTs contain the same nodes and constants, ignoring spans
+fn is_same_program(a: &Ast, b: &Ast) -> bool {
+    a.nodes().len() == b.nodes().len()
+        && a.nodes()
+            .iter()
+            .zip(b.nodes())
+            .all(|(a, b)| match (&a.node, &b.node) {
+                // The order of a function's non-local accesses isn't stable between parses
+                (Node::Function(a), Node::Function(b)) => {
+                    let mut a = a.clone();
+                    let mut b = b.clone();
+                    a.accessed_non_locals.sort_unstable();
+                    b.accessed_non_locals.sort_unstable();
+                    a == b
+                }
+                (a, b) => a == b,
+            })
+        && a.constants().iter().eq(b.constants().iter())
+}
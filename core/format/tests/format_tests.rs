@@ -0,0 +1,360 @@
+use koto_format::{format, FormatError, FormatOptions};
+use std::{fs, path::PathBuf};
+
+fn check_format_output(source: &str, expected: &str) {
+    let output = match format(source, FormatOptions::default()) {
+        Ok(output) => output,
+        Err(error) => panic!("Failed to format script: {error}\n{source}"),
+    };
+    assert_eq!(output, expected);
+
+    // Formatting the output again should produce the same result
+    let reformatted = format(&output, FormatOptions::default()).unwrap();
+    assert_eq!(reformatted, expected, "Formatting isn't idempotent");
+}
+
+mod format_tests {
+    use super::*;
+
+    mod spacing {
+        use super::*;
+
+        #[test]
+        fn binary_ops() {
+            check_format_output("a=1+2*3\nb   =  a>=  1\n", "a = 1 + 2 * 3\nb = a >= 1\n");
+        }
+
+        #[test]
+        fn containers() {
+            check_format_output(
+                "x = [ 1,2 , 3 ]\ny = ( 1,2 )\nz = {foo:1,  bar}\n",
+                "x = [1, 2, 3]\ny = (1, 2)\nz = {foo: 1, bar}\n",
+            );
+        }
+
+        #[test]
+        fn single_item_tuple() {
+            check_format_output("x = (1 ,)\n", "x = (1,)\n");
+        }
+
+        #[test]
+        fn function() {
+            check_format_output("f = | a,b ,c...|a+b\n", "f = |a, b, c...| a + b\n");
+        }
+
+        #[test]
+        fn type_hints() {
+            check_format_output(
+                "f = |x:Number,y| ->String\n  x\nz :Int= f 1, 2\n",
+                "f = |x: Number, y| -> String\n  x\nz: Int = f 1, 2\n",
+            );
+        }
+
+        #[test]
+        fn map_destructuring() {
+            check_format_output(
+                "{ x,y:z ,w=1 }= foo\nf = |{a,b=2}|a+b\n",
+                "{x, y: z, w = 1} = foo\nf = |{a, b = 2}| a + b\n",
+            );
+        }
+
+        #[test]
+        fn call_and_lookups() {
+            check_format_output(
+                "x = foo.bar( 1,2 ).baz[ 0 ]\n",
+                "x = foo.bar(1, 2).baz[0]\n",
+            );
+        }
+
+        #[test]
+        fn trailing_whitespace_is_removed() {
+            check_format_output("x = 1   \ny = 2 \n", "x = 1\ny = 2\n");
+        }
+    }
+
+    mod indentation {
+        use super::*;
+
+        #[test]
+        fn nested_blocks() {
+            let source = "\
+f = |n|
+    if n > 0
+            n
+    else
+            -n
+";
+            let expected = "\
+f = |n|
+  if n > 0
+    n
+  else
+    -n
+";
+            check_format_output(source, expected);
+        }
+
+        #[test]
+        fn indent_width_option() {
+            let source = "\
+for x in y
+  print x
+";
+            let expected = "\
+for x in y
+    print x
+";
+            let output = format(source, FormatOptions { indent_width: 4 }).unwrap();
+            assert_eq!(output, expected);
+        }
+
+        #[test]
+        fn map_block() {
+            let source = "\
+x =
+      foo: 42
+      bar:
+         baz: 99
+";
+            let expected = "\
+x =
+  foo: 42
+  bar:
+    baz: 99
+";
+            check_format_output(source, expected);
+        }
+
+        #[test]
+        fn match_arms() {
+            let source = "\
+match x
+    0 or 1 then 'small'
+    (a, b) if a > b then 'descending'
+    else
+        'other'
+";
+            let expected = "\
+match x
+  0 or 1 then 'small'
+  (a, b) if a > b then 'descending'
+  else
+    'other'
+";
+            check_format_output(source, expected);
+        }
+
+        #[test]
+        fn match_map_and_type_patterns() {
+            let source = "\
+match event
+    {type:'click',x,  y} then x + y
+    {  'pos':(a, b),rest...} then a
+    n:Number then n
+";
+            let expected = "\
+match event
+  {type: 'click', x, y} then x + y
+  {'pos': (a, b), rest...} then a
+  n: Number then n
+";
+            check_format_output(source, expected);
+        }
+
+        #[test]
+        fn chained_calls() {
+            let source = "\
+x = (1..10)
+        .each |n| n * 2
+        .to_list()
+";
+            let expected = "\
+x = (1..10)
+  .each |n| n * 2
+  .to_list()
+";
+            check_format_output(source, expected);
+        }
+
+        #[test]
+        fn binary_op_continuation() {
+            let source = "\
+x = 1
+       + 2
+       + 3
+";
+            let expected = "\
+x = 1
+  + 2
+  + 3
+";
+            check_format_output(source, expected);
+        }
+    }
+
+    mod layout {
+        use super::*;
+
+        #[test]
+        fn inline_if_is_preserved() {
+            check_format_output("x = if a then b else c\n", "x = if a then b else c\n");
+        }
+
+        #[test]
+        fn single_line_block_is_preserved() {
+            let source = "\
+f = ||
+  return 42
+";
+            check_format_output(source, source);
+        }
+
+        #[test]
+        fn blank_lines_are_preserved() {
+            let source = "\
+a = 1
+
+b = 2
+";
+            check_format_output(source, source);
+        }
+
+        #[test]
+        fn repeated_blank_lines_are_collapsed() {
+            check_format_output("a = 1\n\n\n\nb = 2\n\n\n", "a = 1\n\nb = 2\n");
+        }
+
+        #[test]
+        fn multiline_call_args() {
+            let source = "\
+foo
+    1,
+    2, 3
+";
+            let expected = "\
+foo
+  1,
+  2, 3
+";
+            check_format_output(source, expected);
+        }
+    }
+
+    mod comments {
+        use super::*;
+
+        #[test]
+        fn line_comments() {
+            let source = "\
+# A comment
+x = 1 # A trailing comment
+    # An indented comment
+y = 2
+";
+            let expected = "\
+# A comment
+x = 1 # A trailing comment
+# An indented comment
+y = 2
+";
+            check_format_output(source, expected);
+        }
+
+        #[test]
+        fn multiline_comment() {
+            let source = "\
+#-
+A multiline comment
+-#
+x = 1
+";
+            check_format_output(source, source);
+        }
+
+        #[test]
+        fn comment_at_end_of_block() {
+            let source = "\
+f = ||
+  x = 1
+  # The end of the block
+y = 2
+";
+            check_format_output(source, source);
+        }
+    }
+
+    mod literals {
+        use super::*;
+
+        #[test]
+        fn numbers_are_preserved() {
+            let source = "x = 0xff, 0b101, 1.5e3, -0.25\n";
+            check_format_output(source, source);
+        }
+
+        #[test]
+        fn strings_are_preserved() {
+            let source = "\
+x = 'hello\\n', \"${1 + 1} $y\"
+import \"$module_name\"
+";
+            check_format_output(source, source);
+        }
+
+        #[test]
+        fn string_format_options_are_preserved() {
+            let source = "x = '${a:_>8.2} ${b + 1:^5} ${c:.3} ${d:+#010_x} ${e:,.2e}'\n";
+            check_format_output(source, source);
+        }
+    }
+
+    mod errors {
+        use super::*;
+
+        #[test]
+        fn parser_error() {
+            let result = format("x = [1, 2", FormatOptions::default());
+            assert!(matches!(result, Err(FormatError::ParserError(_))));
+        }
+    }
+
+    // Formats the scripts in the koto/tests directory, checking that the formatted scripts are
+    // equivalent to the originals and that formatting is idempotent.
+    #[test]
+    fn koto_test_scripts() {
+        let mut tests_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        tests_dir.push("../../koto/tests");
+
+        let mut scripts = Vec::new();
+        let mut dirs = vec![tests_dir];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path
+                    .extension()
+                    .is_some_and(|extension| extension == "koto")
+                {
+                    scripts.push(path);
+                }
+            }
+        }
+        assert!(!scripts.is_empty());
+
+        for script_path in scripts {
+            let source = fs::read_to_string(&script_path).unwrap();
+            let output = match format(&source, FormatOptions::default()) {
+                Ok(output) => output,
+                Err(error) => panic!("Failed to format {}: {error}", script_path.display()),
+            };
+            let reformatted = format(&output, FormatOptions::default()).unwrap();
+            assert_eq!(
+                output,
+                reformatted,
+                "Formatting {} isn't idempotent",
+                script_path.display()
+            );
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! Koto's language guide and core library documentation
+//!
+//! The documentation's markdown files are embedded in the crate, and split into sections that
+//! can be looked up by the CLI's `help` command, or shown as hover documentation in editors.
+
+macro_rules! include_doc {
+    ($doc:expr) => {
+        // Including via a symlink to the top-level docs folder to ensure cargo-package
+        // can find it during packaging.
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/docs/", $doc))
+    };
+}
+
+const GUIDE_FILES: &[&str] = &[
+    include_doc!("language/basics.md"),
+    include_doc!("language/conditional_expressions.md"),
+    include_doc!("language/core_library.md"),
+    include_doc!("language/errors.md"),
+    include_doc!("language/functions.md"),
+    include_doc!("language/functions_advanced.md"),
+    include_doc!("language/generators.md"),
+    include_doc!("language/iterators.md"),
+    include_doc!("language/lists.md"),
+    include_doc!("language/loops.md"),
+    include_doc!("language/maps.md"),
+    include_doc!("language/meta_maps.md"),
+    include_doc!("language/modules.md"),
+    include_doc!("language/prelude.md"),
+    include_doc!("language/ranges.md"),
+    include_doc!("language/strings.md"),
+    include_doc!("language/testing.md"),
+    include_doc!("language/tuples.md"),
+    include_doc!("language/value_unpacking.md"),
+];
+
+const CORE_LIB_FILES: &[&str] = &[
+    include_doc!("core_lib/io.md"),
+    include_doc!("core_lib/iterator.md"),
+    include_doc!("core_lib/koto.md"),
+    include_doc!("core_lib/list.md"),
+    include_doc!("core_lib/map.md"),
+    include_doc!("core_lib/number.md"),
+    include_doc!("core_lib/os.md"),
+    include_doc!("core_lib/range.md"),
+    include_doc!("core_lib/string.md"),
+    include_doc!("core_lib/test.md"),
+    include_doc!("core_lib/tuple.md"),
+];
+
+/// A documentation file, e.g. a chapter of the language guide, or a core library module
+pub struct HelpDoc {
+    /// The document's title, or the module's name for core library documents
+    pub name: String,
+    /// The document's overview, followed by a section for each of its topics
+    pub sections: Vec<HelpSection>,
+}
+
+/// A section of a [HelpDoc]
+///
+/// Sections start with a `#` or `##` heading, with any deeper headings included in the section.
+pub struct HelpSection {
+    /// The section's name
+    ///
+    /// Core library items are prefixed with their module's name, e.g. `list.first`.
+    pub name: String,
+    /// The section's markdown, starting with its heading
+    ///
+    /// Core library items are shown with their module's name, e.g. `## list.first`.
+    ///
+    /// `print!` is removed from the lines of Koto examples, while lines that show the result
+    /// of an expression keep their `check! ` prefix, so that it can be replaced as needed.
+    pub markdown: String,
+    /// The signature of a core library item, taken from its first `kototype` code block
+    pub signature: Option<String>,
+}
+
+/// Returns the chapters of the language guide
+pub fn language_guide() -> Vec<HelpDoc> {
+    GUIDE_FILES
+        .iter()
+        .map(|markdown| parse_doc(markdown, false))
+        .collect()
+}
+
+/// Returns the documentation for each of the core library's modules
+pub fn core_library() -> Vec<HelpDoc> {
+    CORE_LIB_FILES
+        .iter()
+        .map(|markdown| parse_doc(markdown, true))
+        .collect()
+}
+
+// Splits a markdown file into sections at its `#` and `##` headings
+//
+// Core library files start with a `# module` heading, followed by a `## item` section for each
+// of the module's items.
+fn parse_doc(markdown: &str, is_module: bool) -> HelpDoc {
+    let mut doc = HelpDoc {
+        name: String::new(),
+        sections: Vec::new(),
+    };
+    let mut section: Option<HelpSection> = None;
+    let mut code_block = None;
+
+    for line in markdown.lines() {
+        let mut line = line.to_string();
+
+        if let Some(language) = line.strip_prefix("```") {
+            code_block = match code_block {
+                Some(_) => None,
+                None => {
+                    // Code block options are removed, e.g. `koto,skip_run`
+                    let language = language.split(',').next().unwrap_or_default().to_string();
+                    line = format!("```{language}");
+                    Some(language)
+                }
+            };
+        } else if code_block.is_none() {
+            let heading = line
+                .strip_prefix("# ")
+                .map(|heading| (heading, true))
+                .or_else(|| line.strip_prefix("## ").map(|heading| (heading, false)));
+
+            if let Some((heading, is_title)) = heading {
+                doc.sections.extend(section.take());
+
+                let heading = heading.trim().replace('`', "");
+                let name = if is_title {
+                    doc.name = heading.clone();
+                    heading
+                } else if is_module {
+                    let name = format!("{}.{heading}", doc.name);
+                    line = format!("## {name}");
+                    name
+                } else {
+                    heading
+                };
+
+                section = Some(HelpSection {
+                    name,
+                    markdown: line + "\n",
+                    signature: None,
+                });
+                continue;
+            }
+        }
+
+        // Any text before the first heading is ignored
+        let Some(section) = section.as_mut() else {
+            continue;
+        };
+
+        match code_block.as_deref() {
+            Some("koto") => {
+                section
+                    .markdown
+                    .push_str(line.trim_start_matches("print! "));
+            }
+            Some("kototype") if is_module && !line.starts_with("```") => {
+                if section.signature.is_none() {
+                    section.signature = Some(line.trim().to_string());
+                }
+                section.markdown.push_str(&line);
+            }
+            _ => section.markdown.push_str(&line),
+        }
+        section.markdown.push('\n');
+    }
+
+    doc.sections.extend(section);
+    for section in doc.sections.iter_mut() {
+        section.markdown.truncate(section.markdown.trim_end().len());
+    }
+
+    doc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_sections() {
+        let doc = parse_doc(
+            "\
+# list
+
+Functions for lists.
+
+## first
+
+```kototype
+|List| -> Value
+```
+
+Returns the first value.
+
+```koto
+print! [1, 2].first()
+check! 1
+```
+
+## last
+",
+            true,
+        );
+
+        assert_eq!(doc.name, "list");
+        let names: Vec<_> = doc.sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["list", "list.first", "list.last"]);
+
+        let first = &doc.sections[1];
+        assert_eq!(first.signature.as_deref(), Some("|List| -> Value"));
+        assert_eq!(
+            first.markdown,
+            "\
+## list.first
+
+```kototype
+|List| -> Value
+```
+
+Returns the first value.
+
+```koto
+[1, 2].first()
+check! 1
+```"
+        );
+    }
+
+    #[test]
+    fn headings_in_code_blocks_are_ignored() {
+        let doc = parse_doc(
+            "\
+# Basics
+
+## `import`
+
+```koto
+# A comment
+## Another comment
+```
+",
+            false,
+        );
+
+        let names: Vec<_> = doc.sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Basics", "import"]);
+        assert!(doc.sections[1]
+            .markdown
+            .ends_with("## Another comment\n```"));
+    }
+
+    #[test]
+    fn all_docs_have_sections() {
+        for doc in language_guide().into_iter().chain(core_library()) {
+            assert!(!doc.name.is_empty());
+            assert_eq!(doc.sections[0].name, doc.name);
+        }
+    }
+}
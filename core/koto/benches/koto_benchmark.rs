@@ -95,6 +95,15 @@ pub fn koto_benchmark(c: &mut Criterion) {
             runner.run();
         })
     });
+    c.bench_function("object_lookups", |b| {
+        let mut runner = BenchmarkRunner::setup(
+            "object_lookups.koto",
+            &["100".to_string(), "quiet".to_string()],
+        );
+        b.iter(|| {
+            runner.run();
+        })
+    });
 }
 
 criterion_group!(benches, koto_benchmark);
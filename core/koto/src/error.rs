@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use koto_bytecode::LoaderError;
+use koto_bytecode::{DeserializeError, LoaderError, VerifyError};
 use koto_runtime::Error as RuntimeError;
 
 use thiserror::Error;
@@ -29,6 +29,12 @@ pub enum Error {
 
     #[error(transparent)]
     RuntimeError(#[from] RuntimeError),
+
+    #[error("Failed to load bytecode: {0}")]
+    BytecodeError(#[from] DeserializeError),
+
+    #[error("Invalid bytecode: {0}")]
+    InvalidBytecode(#[from] VerifyError),
 }
 
 impl Error {
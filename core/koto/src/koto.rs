@@ -1,8 +1,11 @@
 use crate::{prelude::*, Error, Result};
 use dunce::canonicalize;
 use koto_bytecode::CompilerSettings;
-use koto_runtime::ModuleImportedCallback;
-use std::{path::PathBuf, rc::Rc};
+use koto_runtime::{
+    Capabilities, CollectionStats, Coverage, DebugHook, InterruptHandle, ModuleImportedCallback,
+    Profile,
+};
+use std::{path::PathBuf, rc::Rc, time::Duration};
 
 /// The main interface for the Koto language.
 ///
@@ -30,6 +33,9 @@ pub struct Koto {
     runtime: Vm,
     run_tests: bool,
     export_top_level_ids: bool,
+    enable_type_checks: bool,
+    enable_optimizer: bool,
+    enable_constant_inlining: bool,
     script_path: Option<PathBuf>,
     chunk: Option<Ptr<Chunk>>,
 }
@@ -48,16 +54,38 @@ impl Koto {
 
     /// Creates a new instance of Koto with the given settings
     pub fn with_settings(settings: KotoSettings) -> Self {
+        let runtime = Vm::with_settings(VmSettings {
+            stdin: settings.stdin,
+            stdout: settings.stdout,
+            stderr: settings.stderr,
+            run_import_tests: settings.run_import_tests,
+            module_imported_callback: settings.module_imported_callback,
+            enable_cycle_collector: settings.enable_cycle_collector,
+            instruction_limit: settings.instruction_limit,
+            execution_time_limit: settings.execution_time_limit,
+            memory_limit: settings.memory_limit,
+            capabilities: settings.capabilities,
+            include_locals_in_error_traces: settings.include_locals_in_error_traces,
+            debug_hook: settings.debug_hook,
+            enable_profiler: settings.enable_profiler,
+            enable_coverage: settings.enable_coverage,
+            strict_integer_overflow: settings.strict_integer_overflow,
+        });
+        {
+            let mut loader = runtime.loader().borrow_mut();
+            loader.set_cache_dir(settings.module_cache_dir);
+            loader.set_type_checks_enabled(settings.enable_type_checks);
+            loader.set_optimizer_enabled(settings.enable_optimizer);
+            loader.set_constant_inlining_enabled(settings.enable_constant_inlining);
+        }
+
         Self {
-            runtime: Vm::with_settings(VmSettings {
-                stdin: settings.stdin,
-                stdout: settings.stdout,
-                stderr: settings.stderr,
-                run_import_tests: settings.run_import_tests,
-                module_imported_callback: settings.module_imported_callback,
-            }),
+            runtime,
             run_tests: settings.run_tests,
             export_top_level_ids: settings.export_top_level_ids,
+            enable_type_checks: settings.enable_type_checks,
+            enable_optimizer: settings.enable_optimizer,
+            enable_constant_inlining: settings.enable_constant_inlining,
             chunk: None,
             script_path: None,
         }
@@ -82,6 +110,10 @@ impl Koto {
             &self.script_path,
             CompilerSettings {
                 export_top_level_ids: self.export_top_level_ids,
+                enable_type_checks: self.enable_type_checks,
+                enable_optimizer: self.enable_optimizer,
+                enable_constant_inlining: self.enable_constant_inlining,
+                ..Default::default()
             },
         )?;
 
@@ -89,6 +121,26 @@ impl Koto {
         Ok(chunk)
     }
 
+    /// Loads a precompiled chunk that was serialized with [Chunk::serialize]
+    ///
+    /// The chunk is checked with [Chunk::verify] before being accepted.
+    ///
+    /// On success, the chunk is cached as the current chunk for subsequent calls to [Koto::run].
+    ///
+    /// If a script path has been set with [set_script_path](Koto::set_script_path), then it
+    /// replaces the chunk's original source path so that imports are relative to the script.
+    pub fn load_bytecode(&mut self, data: &[u8]) -> Result<Ptr<Chunk>> {
+        let mut chunk = Chunk::deserialize(data)?;
+        if self.script_path.is_some() {
+            chunk.source_path = self.script_path.clone();
+        }
+
+        let chunk = Ptr::from(chunk);
+        Chunk::verify(chunk.clone())?;
+        self.chunk = Some(chunk.clone());
+        Ok(chunk)
+    }
+
     /// Runs the chunk last compiled with [compile](Koto::compile)
     pub fn run(&mut self) -> Result<Value> {
         let chunk = self.chunk.clone();
@@ -152,6 +204,34 @@ impl Koto {
         self.runtime.value_to_string(&value).map_err(|e| e.into())
     }
 
+    /// Returns a handle that can be used to interrupt a running script from another thread
+    ///
+    /// See [InterruptHandle].
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.runtime.interrupt_handle()
+    }
+
+    /// Reclaims values that are only referenced by each other in unreachable cycles
+    ///
+    /// See [KotoSettings::enable_cycle_collector].
+    pub fn collect_garbage(&self) -> CollectionStats {
+        self.runtime.collect_garbage()
+    }
+
+    /// Returns the results that have been recorded by the profiler
+    ///
+    /// See [KotoSettings::enable_profiler].
+    pub fn profile(&self) -> Option<Profile> {
+        self.runtime.profile()
+    }
+
+    /// Returns the code coverage that has been recorded by the runtime
+    ///
+    /// See [KotoSettings::enable_coverage].
+    pub fn coverage(&self) -> Option<Coverage> {
+        self.runtime.coverage()
+    }
+
     /// Clears the loader's cached modules
     ///
     /// This is useful when a script's dependencies may have changed and need to be recompiled.
@@ -261,6 +341,19 @@ pub struct KotoSettings {
     /// This is used by the REPL, allowing for incremental compilation and execution of expressions
     /// that need to share declared values.
     pub export_top_level_ids: bool,
+    /// Whether or not type hints should be checked at runtime
+    ///
+    /// Type hints are ignored by default, see
+    /// [CompilerSettings::enable_type_checks](koto_bytecode::CompilerSettings::enable_type_checks).
+    pub enable_type_checks: bool,
+    /// Whether or not the compiled bytecode should be optimized
+    ///
+    /// See [CompilerSettings::enable_optimizer](koto_bytecode::CompilerSettings::enable_optimizer).
+    pub enable_optimizer: bool,
+    /// Whether or not constants exported by imported modules should be inlined
+    ///
+    /// See [CompilerSettings::enable_constant_inlining](koto_bytecode::CompilerSettings::enable_constant_inlining).
+    pub enable_constant_inlining: bool,
     /// The runtime's stdin
     pub stdin: Rc<dyn KotoFile>,
     /// The runtime's stdout
@@ -272,6 +365,50 @@ pub struct KotoSettings {
     /// This allows you to track the runtime's dependencies, which might be useful if you want to
     /// reload the script when one of its dependencies has changed.
     pub module_imported_callback: Option<Box<dyn ModuleImportedCallback>>,
+    /// Whether or not values should be tracked by the cycle collector
+    ///
+    /// See [VmSettings::enable_cycle_collector] and [Koto::collect_garbage].
+    pub enable_cycle_collector: bool,
+    /// An optional limit on the number of instructions that can be executed in a single run
+    ///
+    /// See [VmSettings::instruction_limit].
+    pub instruction_limit: Option<u64>,
+    /// An optional limit on the time that can be spent executing a single run
+    ///
+    /// See [VmSettings::execution_time_limit].
+    pub execution_time_limit: Option<Duration>,
+    /// An optional limit (in bytes) on the memory that can be allocated for values
+    ///
+    /// See [VmSettings::memory_limit].
+    pub memory_limit: Option<usize>,
+    /// The capabilities that are made available to scripts
+    ///
+    /// See [VmSettings::capabilities].
+    pub capabilities: Capabilities,
+    /// An optional directory in which compiled modules are cached between runs
+    ///
+    /// See [Loader::set_cache_dir](koto_bytecode::Loader::set_cache_dir).
+    pub module_cache_dir: Option<PathBuf>,
+    /// Whether or not runtime errors should include the values of locals in the failing frame
+    ///
+    /// See [VmSettings::include_locals_in_error_traces].
+    pub include_locals_in_error_traces: bool,
+    /// An optional hook that's used to debug scripts
+    ///
+    /// See [VmSettings::debug_hook].
+    pub debug_hook: Option<Box<dyn DebugHook>>,
+    /// Whether or not the runtime should profile the scripts that it runs
+    ///
+    /// See [VmSettings::enable_profiler] and [Koto::profile].
+    pub enable_profiler: bool,
+    /// Whether or not the runtime should record code coverage
+    ///
+    /// See [VmSettings::enable_coverage] and [Koto::coverage].
+    pub enable_coverage: bool,
+    /// Whether or not integer overflow should be treated as an error
+    ///
+    /// See [VmSettings::strict_integer_overflow].
+    pub strict_integer_overflow: bool,
 }
 
 impl KotoSettings {
@@ -322,10 +459,24 @@ impl Default for KotoSettings {
             run_tests: true,
             run_import_tests: true,
             export_top_level_ids: false,
+            enable_type_checks: false,
+            enable_optimizer: false,
+            enable_constant_inlining: false,
             stdin: default_vm_settings.stdin,
             stdout: default_vm_settings.stdout,
             stderr: default_vm_settings.stderr,
             module_imported_callback: None,
+            enable_cycle_collector: default_vm_settings.enable_cycle_collector,
+            instruction_limit: default_vm_settings.instruction_limit,
+            execution_time_limit: default_vm_settings.execution_time_limit,
+            memory_limit: default_vm_settings.memory_limit,
+            capabilities: default_vm_settings.capabilities,
+            module_cache_dir: None,
+            include_locals_in_error_traces: default_vm_settings.include_locals_in_error_traces,
+            debug_hook: default_vm_settings.debug_hook,
+            enable_profiler: default_vm_settings.enable_profiler,
+            enable_coverage: default_vm_settings.enable_coverage,
+            strict_integer_overflow: default_vm_settings.strict_integer_overflow,
         }
     }
 }
@@ -89,6 +89,197 @@ mod cli {
         }
     }
 
+    mod compile {
+        use super::*;
+
+        #[test]
+        fn compile_and_run_bytecode() {
+            let script_path: PathBuf = [
+                env!("CARGO_MANIFEST_DIR"),
+                "..",
+                "..",
+                "koto",
+                "benches",
+                "string_formatting.koto",
+            ]
+            .iter()
+            .collect();
+            let bytecode_path = std::env::temp_dir().join(format!(
+                "koto_cli_test_{}_string_formatting.kbc",
+                std::process::id()
+            ));
+
+            check_output(
+                test_bin::get_test_bin("koto")
+                    .arg("--compile")
+                    .arg(&bytecode_path)
+                    .arg(&script_path)
+                    .output()
+                    .expect("Failed to run CLI"),
+                "",
+                "",
+            );
+
+            let output = test_bin::get_test_bin("koto")
+                .arg(&bytecode_path)
+                .arg("1")
+                .output()
+                .expect("Failed to run CLI");
+            std::fs::remove_file(&bytecode_path).ok();
+
+            check_output(output, "('minus one', 'zero', 'one')\n", "");
+        }
+    }
+
+    mod optimize {
+        use super::*;
+
+        fn check_cli_run_modules(modules: &[(&str, &str)], expected_stdout: &str) {
+            let dir = std::env::temp_dir().join(format!(
+                "koto_cli_test_{}_{}",
+                std::process::id(),
+                modules[0].0
+            ));
+            std::fs::create_dir_all(&dir).unwrap();
+            for (name, script) in modules {
+                std::fs::write(dir.join(format!("{name}.koto")), script).unwrap();
+            }
+
+            let output = test_bin::get_test_bin("koto")
+                .arg("--optimize")
+                .arg(dir.join(format!("{}.koto", modules[0].0)))
+                .output()
+                .expect("Failed to run CLI");
+            std::fs::remove_dir_all(&dir).ok();
+
+            check_output(output, expected_stdout, "");
+        }
+
+        #[test]
+        fn inlined_modules_are_imported() {
+            check_cli_run_modules(
+                &[
+                    (
+                        "inlining_main",
+                        "from inlining_config import max_size\nprint max_size",
+                    ),
+                    (
+                        "inlining_config",
+                        "print 'config loaded'\nexport max_size = 100",
+                    ),
+                ],
+                "config loaded\n100\n",
+            );
+        }
+
+        #[test]
+        fn exports_modified_by_modules_are_not_inlined() {
+            check_cli_run_modules(
+                &[
+                    (
+                        "exports_main",
+                        "from exports_config import max_size\nprint max_size",
+                    ),
+                    (
+                        "exports_config",
+                        "export max_size = 100\nkoto.exports().max_size = 5",
+                    ),
+                ],
+                "5\n",
+            );
+        }
+    }
+
+    mod profile {
+        use super::*;
+
+        #[test]
+        fn profile_report_and_folded_stacks() {
+            let script_path: PathBuf = [
+                env!("CARGO_MANIFEST_DIR"),
+                "..",
+                "..",
+                "koto",
+                "benches",
+                "string_formatting.koto",
+            ]
+            .iter()
+            .collect();
+            let profile_path = std::env::temp_dir()
+                .join(format!("koto_cli_test_{}_profile.txt", std::process::id()));
+            let folded_path = profile_path.with_extension("txt.folded");
+
+            let output = test_bin::get_test_bin("koto")
+                .arg("--profile")
+                .arg(&profile_path)
+                .arg(&script_path)
+                .arg("1")
+                .output()
+                .expect("Failed to run CLI");
+            let report = std::fs::read_to_string(&profile_path);
+            let folded = std::fs::read_to_string(&folded_path);
+            std::fs::remove_file(&profile_path).ok();
+            std::fs::remove_file(&folded_path).ok();
+
+            check_output(output, "('minus one', 'zero', 'one')\n", "");
+
+            let report = report.expect("Missing profile report");
+            assert!(report.starts_with("Functions\n"), "{report}");
+            assert!(report.contains("\nLines\n"), "{report}");
+            assert!(report.contains("\nCall tree\n"), "{report}");
+            assert!(report.contains("<main> ("), "{report}");
+
+            let folded = folded.expect("Missing folded stacks");
+            assert!(folded.starts_with("<main> ("), "{folded}");
+            for line in folded.lines() {
+                let (_stack, weight) = line.rsplit_once(' ').expect("Missing stack weight");
+                assert!(weight.parse::<u64>().is_ok(), "{folded}");
+            }
+        }
+    }
+
+    mod coverage {
+        use super::*;
+
+        #[test]
+        fn coverage_after_running_tests() {
+            let script_path: PathBuf = [
+                env!("CARGO_MANIFEST_DIR"),
+                "..",
+                "..",
+                "koto",
+                "benches",
+                "string_formatting.koto",
+            ]
+            .iter()
+            .collect();
+            let coverage_path = std::env::temp_dir().join(format!(
+                "koto_cli_test_{}_coverage.lcov",
+                std::process::id()
+            ));
+
+            let output = test_bin::get_test_bin("koto")
+                .arg("--tests")
+                .arg("--coverage")
+                .arg(&coverage_path)
+                .arg(&script_path)
+                .arg("1")
+                .output()
+                .expect("Failed to run CLI");
+            let coverage = std::fs::read_to_string(&coverage_path);
+            std::fs::remove_file(&coverage_path).ok();
+
+            check_output(output, "('minus one', 'zero', 'one')\n", "");
+
+            let coverage = coverage.expect("Missing coverage");
+            assert!(coverage.starts_with("TN:\nSF:"), "{coverage}");
+            assert!(coverage.contains("string_formatting.koto\n"), "{coverage}");
+            // The function that's defined in the script's tests has been called
+            assert!(coverage.contains("\nFNH:4\n"), "{coverage}");
+            assert!(coverage.ends_with("end_of_record\n"), "{coverage}");
+        }
+    }
+
     mod piped_input {
         use super::*;
 
@@ -6,12 +6,18 @@ use std::{
     rc::Rc,
 };
 
-fn run_script(script: &str, script_path: Option<PathBuf>, expected_module_paths: &[PathBuf]) {
+fn run_script(
+    script: &str,
+    script_path: Option<PathBuf>,
+    expected_module_paths: &[PathBuf],
+    enable_optimizer: bool,
+) {
     let loaded_module_paths = Rc::new(RefCell::new(vec![]));
 
     let mut koto = Koto::with_settings(
         KotoSettings {
             run_tests: true,
+            enable_optimizer,
             ..Default::default()
         }
         .with_module_imported_callback({
@@ -78,7 +84,15 @@ fn load_and_run_script(script_file_name: &str, imported_modules: &[&str]) {
         })
         .collect::<Vec<_>>();
 
-    run_script(&script, Some(script_path), &expected_module_paths);
+    // Scripts are run with and without the optimizer to check that the results match
+    for enable_optimizer in [false, true] {
+        run_script(
+            &script,
+            Some(script_path.clone()),
+            &expected_module_paths,
+            enable_optimizer,
+        );
+    }
 }
 
 macro_rules! koto_test {
@@ -20,8 +20,11 @@ pub enum Token {
     SingleQuote,
     DoubleQuote,
     StringLiteral,
+    // The formatting options that follow a ':' in a string template, e.g. '${x:>8.2}'
+    StringFormat,
 
     // Symbols
+    Arrow,
     At,
     Colon,
     Comma,
@@ -120,6 +123,8 @@ enum StringMode {
     // Inside an inline map in a template expression, e.g. '${foo({bar: 42})}'
     // A closing '}' will end the map rather than the template expression.
     TemplateExpressionInlineMap,
+    // After a ':' in a template expression, formatting options will follow, e.g. '${x:>8.2}'
+    TemplateFormat,
 }
 
 // Separates the input source into Tokens
@@ -292,6 +297,29 @@ impl<'a> TokenLexer<'a> {
         }
     }
 
+    // Consumes the formatting options in a string template, up to the template's closing '}'
+    fn consume_string_format(&mut self, mut chars: Peekable<Chars>) -> Token {
+        let string_quote = self
+            .string_mode_stack
+            .iter()
+            .rev()
+            .find_map(|mode| match mode {
+                StringMode::Literal(quote) => Some(*quote),
+                _ => None,
+            });
+
+        let (format_bytes, format_width) = consume_and_count_utf8(&mut chars, |c| {
+            !matches!(c, '}' | '\r' | '\n') && Some(c) != string_quote
+        });
+        self.advance_line_utf8(format_bytes, format_width);
+
+        if chars.peek() == Some(&'}') {
+            Token::StringFormat
+        } else {
+            Token::Error
+        }
+    }
+
     fn consume_string_literal(&mut self, mut chars: Peekable<Chars>) -> Token {
         use Token::*;
 
@@ -527,6 +555,7 @@ impl<'a> TokenLexer<'a> {
         check_symbol!("..", Range);
 
         check_symbol!(">>", Pipe);
+        check_symbol!("->", Arrow);
 
         check_symbol!("==", Equal);
         check_symbol!("!=", NotEqual);
@@ -608,6 +637,18 @@ impl<'a> TokenLexer<'a> {
                         }
                         _ => Error,
                     },
+                    Some(StringMode::TemplateFormat) => {
+                        self.string_mode_stack.pop();
+                        match next_char {
+                            // The formatting options are empty, e.g. '${x:}'
+                            '}' => {
+                                self.advance_line(1);
+                                self.string_mode_stack.pop();
+                                CurlyClose
+                            }
+                            _ => self.consume_string_format(chars),
+                        }
+                    }
                     _ => match next_char {
                         c if is_whitespace(c) => {
                             let count = consume_and_count(&mut chars, is_whitespace);
@@ -640,6 +681,11 @@ impl<'a> TokenLexer<'a> {
 
                             use StringMode::*;
                             match result {
+                                Colon => {
+                                    if matches!(string_mode, Some(TemplateExpression)) {
+                                        self.string_mode_stack.push(TemplateFormat);
+                                    }
+                                }
                                 CurlyOpen => {
                                     if matches!(string_mode, Some(TemplateExpression)) {
                                         self.string_mode_stack.push(TemplateExpressionInlineMap);
@@ -1119,6 +1165,50 @@ false #
         );
     }
 
+    #[test]
+    fn interpolated_string_formats() {
+        let input = r#"
+'${x:>8.2} ${y + 1:*^10}'
+"${{a: 1}:}"
+"#;
+        check_lexer_output(
+            input,
+            &[
+                (NewLine, None, 2),
+                (SingleQuote, None, 2),
+                (Dollar, None, 2),
+                (CurlyOpen, None, 2),
+                (Id, Some("x"), 2),
+                (Colon, None, 2),
+                (StringFormat, Some(">8.2"), 2),
+                (CurlyClose, None, 2),
+                (StringLiteral, Some(" "), 2),
+                (Dollar, None, 2),
+                (CurlyOpen, None, 2),
+                (Id, Some("y"), 2),
+                (Add, None, 2),
+                (Number, Some("1"), 2),
+                (Colon, None, 2),
+                (StringFormat, Some("*^10"), 2),
+                (CurlyClose, None, 2),
+                (SingleQuote, None, 2),
+                (NewLine, None, 3),
+                (DoubleQuote, None, 3),
+                (Dollar, None, 3),
+                (CurlyOpen, None, 3),
+                (CurlyOpen, None, 3),
+                (Id, Some("a"), 3),
+                (Colon, None, 3),
+                (Number, Some("1"), 3),
+                (CurlyClose, None, 3),
+                (Colon, None, 3),
+                (CurlyClose, None, 3),
+                (DoubleQuote, None, 3),
+                (NewLine, None, 4),
+            ],
+        );
+    }
+
     #[test]
     fn operators() {
         let input = r#"
@@ -1305,6 +1395,27 @@ f()";
         );
     }
 
+    #[test]
+    fn function_with_type_hints() {
+        let input = "|a: Number, b: String| -> String";
+        check_lexer_output(
+            input,
+            &[
+                (Function, None, 1),
+                (Id, Some("a"), 1),
+                (Colon, None, 1),
+                (Id, Some("Number"), 1),
+                (Comma, None, 1),
+                (Id, Some("b"), 1),
+                (Colon, None, 1),
+                (Id, Some("String"), 1),
+                (Function, None, 1),
+                (Arrow, None, 1),
+                (Id, Some("String"), 1),
+            ],
+        );
+    }
+
     #[test]
     fn if_inline() {
         let input = "1 + if true then 0 else 1";
@@ -0,0 +1,129 @@
+//! A linter for the Koto programming language
+//!
+//! Scripts are parsed, and then the resulting [Ast](koto_parser::Ast) is checked for common
+//! mistakes that the compiler doesn't catch, like unused values, unreachable code, references
+//! to ids that haven't been defined, or values that don't match their type hints.
+//!
+//! ## Example
+//!
+//! ```
+//! use koto_lint::{lint, LintKind, LintOptions};
+//!
+//! let script = "
+//! f = |x|
+//!   y = 42
+//!   x
+//! f 99
+//! ";
+//! let lints = lint(script, &LintOptions::default()).unwrap();
+//! assert_eq!(lints.len(), 1);
+//! assert_eq!(lints[0].kind, LintKind::UnusedVariable("y".into()));
+//! assert_eq!(lints[0].to_string(), "'y' is never used");
+//! ```
+
+#![warn(missing_docs)]
+
+mod linter;
+mod types;
+
+use crate::linter::Linter;
+use koto_parser::{Ast, Parser, ParserError, Span};
+use std::{collections::HashSet, fmt};
+
+/// Options that control the checks performed by [lint]
+#[derive(Clone, Debug, Default)]
+pub struct LintOptions {
+    /// The ids that are available in the prelude
+    ///
+    /// When provided, references to ids that aren't defined in the script, exported by the
+    /// script, or available in the prelude, are reported with [LintKind::UndefinedId].
+    ///
+    /// Default: `None`
+    pub prelude: Option<HashSet<String>>,
+}
+
+/// A kind of issue that can be found by the linter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LintKind {
+    /// A value was assigned to an id that's never used
+    UnusedVariable(String),
+    /// An imported item is never used
+    UnusedImport(String),
+    /// A value was assigned to an id that's used elsewhere, but the assigned value is never read
+    UnusedAssignment(String),
+    /// A value was assigned in a function to an id that's already defined in an enclosing scope
+    ///
+    /// Values are captured by functions when they're created, so the assignment creates a new
+    /// local value rather than modifying the captured value.
+    ShadowedVariable(String),
+    /// An expression can never be reached
+    UnreachableCode,
+    /// A match expression doesn't have an `else` arm or a catch-all pattern
+    NonExhaustiveMatch,
+    /// An id that isn't defined in the script or in the prelude
+    UndefinedId(String),
+    /// A value doesn't match its type hint
+    ///
+    /// Type hints are checked for assigned values, for the arguments of calls to functions
+    /// defined in the script, and for the values returned from functions.
+    TypeMismatch {
+        /// The type hint's type
+        expected: String,
+        /// The value's type
+        found: String,
+    },
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use LintKind::*;
+        match self {
+            UnusedVariable(id) => write!(f, "'{id}' is never used"),
+            UnusedImport(id) => write!(f, "'{id}' is imported but never used"),
+            UnusedAssignment(id) => write!(f, "The value assigned to '{id}' is never read"),
+            ShadowedVariable(id) => write!(
+                f,
+                "'{id}' shadows a captured value, the captured value won't be modified"
+            ),
+            UnreachableCode => write!(f, "Unreachable code"),
+            NonExhaustiveMatch => write!(
+                f,
+                "The match expression doesn't have an 'else' arm or a catch-all pattern"
+            ),
+            UndefinedId(id) => write!(f, "'{id}' isn't defined"),
+            TypeMismatch { expected, found } => {
+                write!(f, "Expected {expected}, but found {found}")
+            }
+        }
+    }
+}
+
+/// An issue found by the linter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lint {
+    /// The kind of issue that was found
+    pub kind: LintKind,
+    /// The span in the source string where the issue was found
+    pub span: Span,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+/// Checks a Koto script for issues
+///
+/// The lints are returned in the order in which they appear in the script.
+pub fn lint(source: &str, options: &LintOptions) -> Result<Vec<Lint>, ParserError> {
+    let ast = Parser::parse(source)?;
+    Ok(lint_ast(&ast, options))
+}
+
+/// Checks a script's [Ast] for issues
+///
+/// See [lint].
+pub fn lint_ast(ast: &Ast, options: &LintOptions) -> Vec<Lint> {
+    Linter::new(ast, options).lint()
+}
@@ -0,0 +1,1019 @@
+use crate::{
+    types::{literal_type, StaticType},
+    Lint, LintKind, LintOptions,
+};
+use koto_parser::{
+    Ast, AstBinaryOp, AstFor, AstIf, AstIndex, AstString, AstTry, ConstantIndex, Function,
+    ImportItemNode, LookupNode, MapKey, MatchArm, Node, Span, StringNode,
+};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+// The assignments that can reach a point in a function, for each of the function's variables
+//
+// `None` is used for points in the function that can't be reached.
+type Flow = Option<HashMap<usize, BTreeSet<usize>>>;
+
+// A value that has been assigned to an id in a function
+struct Variable {
+    name: ConstantIndex,
+    // The span of the first definition of the variable
+    span: Span,
+    is_import: bool,
+    // True if the variable is read anywhere, or is exported
+    used: bool,
+    // The indices of the variable's assignments
+    assignments: Vec<usize>,
+}
+
+struct Assignment {
+    span: Span,
+    read: bool,
+    // The type of the assigned value, if known
+    static_type: Option<StaticType>,
+    // The function node that was assigned, if known
+    function: Option<AstIndex>,
+}
+
+// The flow of assignments at the `break` and `continue` expressions in a loop
+#[derive(Default)]
+struct LoopFlow {
+    breaks: Flow,
+    continues: Flow,
+}
+
+// A function's scope, or the script's main scope
+#[derive(Default)]
+struct Scope {
+    // The variables that have been defined so far in the scope
+    variables: HashMap<ConstantIndex, usize>,
+    // The assignments that reach the current point in the scope
+    flow: Flow,
+    // The flow in each of the loops that are currently being visited
+    loops: Vec<LoopFlow>,
+    // The assignments made in each of the try blocks that are currently being visited
+    //
+    // An error can be thrown at any point in a try block, so all of the assignments made in the
+    // block can reach the catch block.
+    tries: Vec<HashMap<usize, BTreeSet<usize>>>,
+    // The function's output type hint, e.g. `|| -> Number`
+    output_type: Option<AstIndex>,
+    // True if the scope belongs to a generator, with the output type applying to yielded values
+    is_generator: bool,
+}
+
+// The ways in which an id can be defined
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Definition {
+    // An assignment, e.g. `x = 1`
+    Assignment,
+    // An exported assignment, e.g. `export x = 1`
+    Export,
+    // A function arg, or a binding in a match arm, a loop, or a catch block
+    Binding,
+    // An imported item
+    Import,
+}
+
+// Walks the AST in execution order, tracking which assignments can be read at each point
+//
+// Loop bodies are visited twice so that reads of values assigned in a previous iteration are
+// taken into account, with lints suppressed during the second visit.
+pub(crate) struct Linter<'a> {
+    ast: &'a Ast,
+    options: &'a LintOptions,
+    // The ids that are exported anywhere in the script
+    exports: HashSet<ConstantIndex>,
+    scopes: Vec<Scope>,
+    variables: Vec<Variable>,
+    assignments: Vec<Assignment>,
+    // The assignment that was created for each AST node and id, see define()
+    assignment_nodes: HashMap<(AstIndex, ConstantIndex), usize>,
+    // Greater than zero while loop bodies are being revisited
+    replaying: usize,
+    lints: Vec<Lint>,
+}
+
+impl<'a> Linter<'a> {
+    pub fn new(ast: &'a Ast, options: &'a LintOptions) -> Self {
+        let exports = ast
+            .nodes()
+            .iter()
+            .filter_map(|node| match &node.node {
+                Node::Export(exported) => Some(*exported),
+                _ => None,
+            })
+            .flat_map(|exported| exported_ids(ast, exported))
+            .collect();
+
+        Self {
+            ast,
+            options,
+            exports,
+            scopes: Vec::new(),
+            variables: Vec::new(),
+            assignments: Vec::new(),
+            assignment_nodes: HashMap::new(),
+            replaying: 0,
+            lints: Vec::new(),
+        }
+    }
+
+    pub fn lint(mut self) -> Vec<Lint> {
+        let ast = self.ast;
+        if let Some(Node::MainBlock { body, .. }) = ast.entry_point().map(|entry| &entry.node) {
+            self.push_scope();
+            self.visit_lines(body);
+            self.pop_scope();
+        }
+
+        self.lints
+            .sort_by_key(|lint| (lint.span.start.line, lint.span.start.column));
+        self.lints
+    }
+
+    fn visit(&mut self, index: AstIndex) {
+        let ast = self.ast;
+
+        match &ast.node(index).node {
+            Node::Id(id, ..) => self.read(*id, index),
+            Node::Nested(nested) => self.visit(*nested),
+            Node::Lookup((lookup_node, next)) => {
+                match lookup_node {
+                    LookupNode::Root(root) => self.visit(*root),
+                    LookupNode::Str(string) => self.visit_string(string),
+                    LookupNode::Index(index) => self.visit(*index),
+                    LookupNode::Call { args, .. } => self.visit_nodes(args),
+                    LookupNode::Id(_) => {}
+                }
+                if let Some(next) = next {
+                    self.visit(*next);
+                }
+                // e.g. `f(1, 2)`
+                if let Some((function, args)) = self.called_function(index) {
+                    self.check_call_args(function, args);
+                }
+            }
+            Node::NamedCall { id, args } => {
+                self.read(*id, index);
+                self.visit_nodes(args);
+                if let Some(function) = self.known_function(*id) {
+                    self.check_call_args(function, args);
+                }
+            }
+            Node::Str(string) => self.visit_string(string),
+            Node::List(elements) | Node::Tuple(elements) | Node::TempTuple(elements) => {
+                self.visit_nodes(elements)
+            }
+            Node::Block(lines) => self.visit_lines(lines),
+            Node::Range { start, end, .. } => {
+                self.visit(*start);
+                self.visit(*end);
+            }
+            Node::RangeFrom { start } => self.visit(*start),
+            Node::RangeTo { end, .. } => self.visit(*end),
+            Node::Map(entries) => {
+                for (key, value) in entries {
+                    if let MapKey::Str(string) = key {
+                        self.visit_string(string);
+                    }
+                    match (key, value) {
+                        (_, Some(value)) => self.visit(*value),
+                        // e.g. `{foo}`, the value of `foo` is inserted into the map
+                        (MapKey::Id(id), None) => self.read(*id, index),
+                        _ => {}
+                    }
+                }
+            }
+            Node::Function(function) => {
+                self.push_scope();
+                let scope = self.scope_mut();
+                scope.output_type = function.output_type;
+                scope.is_generator = function.is_generator;
+                for arg in function.args.iter() {
+                    self.define_pattern(*arg);
+                }
+                let last_expression = match &ast.node(function.body).node {
+                    Node::Block(lines) => {
+                        self.visit_lines(lines);
+                        lines.last().copied()
+                    }
+                    _ => {
+                        self.visit(function.body);
+                        Some(function.body)
+                    }
+                };
+                // The last expression in the function is its implicit output
+                if let (Some(output_type), Some(last_expression), false) = (
+                    function.output_type,
+                    last_expression,
+                    function.is_generator || self.flow().is_none(),
+                ) {
+                    let found = self.static_type(last_expression);
+                    self.check_type(found, output_type, last_expression);
+                }
+                self.pop_scope();
+            }
+            // Imports are visited here when they're used as values, e.g. `x = import foo`
+            Node::Import { .. } => self.visit_import(index, true),
+            Node::Export(exported) => match &ast.node(*exported).node {
+                Node::Assign { target, expression } => {
+                    self.assign(*target, *expression, Definition::Export)
+                }
+                Node::MultiAssign {
+                    targets,
+                    expression,
+                } => self.multi_assign(targets, *expression, Definition::Export),
+                _ => self.visit(*exported),
+            },
+            Node::Assign { target, expression } => {
+                self.assign(*target, *expression, Definition::Assignment)
+            }
+            Node::MultiAssign {
+                targets,
+                expression,
+            } => self.multi_assign(targets, *expression, Definition::Assignment),
+            Node::UnaryOp { value, .. } => self.visit(*value),
+            Node::BinaryOp { op, lhs, rhs } => {
+                self.visit(*rhs);
+                self.visit(*lhs);
+                if is_compound_assignment(*op) {
+                    if let Node::Id(id, ..) = &ast.node(*lhs).node {
+                        self.define(*id, index, Definition::Assignment);
+                    }
+                }
+            }
+            Node::If(ast_if) => self.visit_if(ast_if),
+            Node::Match { expression, arms } => self.visit_match(index, *expression, arms),
+            Node::Switch(arms) => {
+                let mut exits = Vec::with_capacity(arms.len() + 1);
+                let mut has_else = false;
+                for arm in arms {
+                    match arm.condition {
+                        Some(condition) => self.visit(condition),
+                        None => has_else = true,
+                    }
+                    let arm_entry = self.flow().clone();
+                    self.visit(arm.expression);
+                    exits.push(self.set_flow(arm_entry));
+                }
+                if !has_else {
+                    exits.push(self.flow().clone());
+                }
+                self.set_flow(merge_all(exits));
+            }
+            Node::For(AstFor {
+                args,
+                iterable,
+                body,
+            }) => {
+                self.visit(*iterable);
+                self.visit_loop(None, args, *body, true);
+            }
+            Node::Loop { body } => self.visit_loop(None, &[], *body, false),
+            Node::While { condition, body } | Node::Until { condition, body } => {
+                self.visit_loop(Some(*condition), &[], *body, true)
+            }
+            Node::Break(value) => {
+                if let Some(value) = value {
+                    self.visit(*value);
+                }
+                let flow = self.set_flow(None);
+                if let Some(loop_flow) = self.scope_mut().loops.last_mut() {
+                    loop_flow.breaks = merge(loop_flow.breaks.take(), flow);
+                }
+            }
+            Node::Continue => {
+                let flow = self.set_flow(None);
+                if let Some(loop_flow) = self.scope_mut().loops.last_mut() {
+                    loop_flow.continues = merge(loop_flow.continues.take(), flow);
+                }
+            }
+            Node::Return(value) => {
+                if let Some(value) = value {
+                    self.visit(*value);
+                }
+                if let (Some(output_type), false) =
+                    (self.scope().output_type, self.scope().is_generator)
+                {
+                    match value {
+                        Some(value) => {
+                            let found = self.static_type(*value);
+                            self.check_type(found, output_type, *value);
+                        }
+                        None => {
+                            let found = Some(StaticType::from_hint("Null"));
+                            self.check_type(found, output_type, index);
+                        }
+                    }
+                }
+                self.set_flow(None);
+            }
+            Node::Throw(value) => {
+                self.visit(*value);
+                self.set_flow(None);
+            }
+            Node::Yield(value) => {
+                self.visit(*value);
+                if let Some(output_type) = self.scope().output_type {
+                    let found = self.static_type(*value);
+                    self.check_type(found, output_type, *value);
+                }
+            }
+            Node::Try(ast_try) => self.visit_try(ast_try),
+            Node::Debug { expression, .. } => self.visit(*expression),
+            Node::MainBlock { .. }
+            | Node::Null
+            | Node::Meta(_, _)
+            | Node::BoolTrue
+            | Node::BoolFalse
+            | Node::SmallInt(_)
+            | Node::Int(_)
+            | Node::Float(_)
+            | Node::RangeFull
+            | Node::Self_
+            | Node::Wildcard(_)
+            | Node::Ellipsis(_)
+            | Node::MapPattern { .. }
+            | Node::Type(_) => {}
+        }
+    }
+
+    fn visit_nodes(&mut self, nodes: &[AstIndex]) {
+        for node in nodes {
+            self.visit(*node);
+        }
+    }
+
+    // Visits the lines of a block, reporting the first line that can't be reached
+    fn visit_lines(&mut self, lines: &[AstIndex]) {
+        let mut unreachable_reported = false;
+        for line in lines {
+            if self.flow().is_none() && !unreachable_reported {
+                self.push_lint(LintKind::UnreachableCode, *line);
+                unreachable_reported = true;
+            }
+            match &self.ast.node(*line).node {
+                Node::Import { .. } => self.visit_import(*line, false),
+                _ => self.visit(*line),
+            }
+        }
+    }
+
+    fn visit_import(&mut self, index: AstIndex, used_as_value: bool) {
+        let Node::Import { from, items } = &self.ast.node(index).node else {
+            return;
+        };
+
+        for item in from.iter().chain(items.iter()) {
+            if let ImportItemNode::Str(string) = item {
+                self.visit_string(string);
+            }
+        }
+        // Modules can be imported from local values, e.g. `from foo import bar`
+        if let Some(ImportItemNode::Id(id)) = from.first().or(items.first()) {
+            if self.find_variable(*id).is_some() {
+                self.read(*id, index);
+            }
+        }
+
+        for item in items.iter() {
+            if let ImportItemNode::Id(id) = item {
+                self.define(*id, index, Definition::Import);
+                if used_as_value {
+                    self.read(*id, index);
+                }
+            }
+        }
+    }
+
+    fn visit_string(&mut self, string: &AstString) {
+        for node in string.nodes.iter() {
+            if let StringNode::Expr(expression) | StringNode::FormattedExpr(expression, _) = node {
+                self.visit(*expression);
+            }
+        }
+    }
+
+    fn visit_if(&mut self, ast_if: &AstIf) {
+        self.visit(ast_if.condition);
+        let mut after_conditions = self.flow().clone();
+
+        self.visit(ast_if.then_node);
+        let mut exits = vec![self.set_flow(after_conditions.clone())];
+
+        for (condition, block) in ast_if.else_if_blocks.iter() {
+            self.visit(*condition);
+            after_conditions = self.flow().clone();
+            self.visit(*block);
+            exits.push(self.set_flow(after_conditions.clone()));
+        }
+
+        if let Some(else_node) = ast_if.else_node {
+            self.visit(else_node);
+            exits.push(self.set_flow(None));
+        } else {
+            exits.push(after_conditions);
+        }
+
+        self.set_flow(merge_all(exits));
+    }
+
+    fn visit_match(&mut self, index: AstIndex, expression: AstIndex, arms: &[MatchArm]) {
+        self.visit(expression);
+        let entry = self.flow().clone();
+
+        let mut exits = Vec::with_capacity(arms.len() + 1);
+        for arm in arms {
+            self.set_flow(entry.clone());
+            for pattern in arm.patterns.iter() {
+                self.define_pattern(*pattern);
+            }
+            if let Some(condition) = arm.condition {
+                self.visit(condition);
+            }
+            self.visit(arm.expression);
+            exits.push(self.flow().clone());
+        }
+
+        if !self.is_exhaustive(arms) {
+            self.push_lint(LintKind::NonExhaustiveMatch, index);
+            exits.push(entry);
+        }
+
+        self.set_flow(merge_all(exits));
+    }
+
+    // Returns true if the match arms will match any value
+    fn is_exhaustive(&self, arms: &[MatchArm]) -> bool {
+        let mut matches_true = false;
+        let mut matches_false = false;
+
+        for arm in arms.iter().filter(|arm| arm.condition.is_none()) {
+            if arm.patterns.is_empty() {
+                // An `else` arm
+                return true;
+            }
+            for pattern in arm.patterns.iter() {
+                match &self.ast.node(*pattern).node {
+                    Node::Id(_, None) | Node::Wildcard(_) => return true,
+                    Node::BoolTrue => matches_true = true,
+                    Node::BoolFalse => matches_false = true,
+                    _ => {}
+                }
+            }
+        }
+
+        matches_true && matches_false
+    }
+
+    // Visits a loop's body
+    //
+    // The body is visited a second time with the assignments that reach the start of the loop
+    // from the end of the body, so that assignments that are read in the next iteration are
+    // marked as read.
+    //
+    // `can_exit_at_start` should be true for loops that can finish without a `break`.
+    fn visit_loop(
+        &mut self,
+        condition: Option<AstIndex>,
+        args: &[AstIndex],
+        body: AstIndex,
+        can_exit_at_start: bool,
+    ) {
+        let entry = self.flow().clone();
+        self.scope_mut().loops.push(LoopFlow::default());
+
+        let mut exit = None;
+        for pass in 0..2 {
+            if pass == 1 {
+                let continues = self.current_loop().continues.take();
+                let end = self.set_flow(None);
+                self.set_flow(merge_all([entry.clone(), end, continues]));
+                self.replaying += 1;
+            }
+            if let Some(condition) = condition {
+                self.visit(condition);
+            }
+            if can_exit_at_start {
+                exit = merge(exit, self.flow().clone());
+            }
+            for arg in args {
+                self.define_pattern(*arg);
+            }
+            self.visit(body);
+        }
+        self.replaying -= 1;
+
+        let loop_flow = self.scope_mut().loops.pop().unwrap_or_default();
+        if can_exit_at_start {
+            let end = self.set_flow(None);
+            exit = merge_all([exit, end, loop_flow.continues]);
+        }
+        self.set_flow(merge(exit, loop_flow.breaks));
+    }
+
+    fn visit_try(&mut self, ast_try: &AstTry) {
+        let entry = self.flow().clone();
+
+        self.scope_mut().tries.push(HashMap::new());
+        self.visit(ast_try.try_block);
+        let try_assignments = Some(self.scope_mut().tries.pop().unwrap_or_default());
+
+        // The catch block can be reached from any point in the try block
+        let after_try = self.flow().clone();
+        let catch_entry = merge_all([entry, try_assignments, after_try.clone()]);
+        self.set_flow(catch_entry.clone());
+        self.define_pattern(ast_try.catch_arg);
+        self.visit(ast_try.catch_block);
+        let after_catch = self.set_flow(None);
+
+        let exit = merge(after_try, after_catch);
+        match ast_try.finally_block {
+            Some(finally_block) => {
+                // The finally block is run even when the try and catch blocks don't finish
+                let finally_entry = exit.clone().or(catch_entry);
+                self.set_flow(finally_entry);
+                self.visit(finally_block);
+                if exit.is_none() {
+                    self.set_flow(None);
+                }
+            }
+            None => {
+                self.set_flow(exit);
+            }
+        }
+    }
+
+    fn assign(&mut self, target: AstIndex, expression: AstIndex, definition: Definition) {
+        let ast = self.ast;
+        match &ast.node(target).node {
+            Node::Id(id, type_hint) => {
+                let assignment = if matches!(ast.node(expression).node, Node::Function(_)) {
+                    // Functions can refer to themselves, so the id is defined before the function
+                    let assignment = self.define(*id, target, definition);
+                    self.assignments[assignment].function = Some(expression);
+                    self.visit(expression);
+                    assignment
+                } else {
+                    self.visit(expression);
+                    let function = self.function_value(expression);
+                    let assignment = self.define(*id, target, definition);
+                    self.assignments[assignment].function = function;
+                    assignment
+                };
+
+                let found = self.static_type(expression);
+                let static_type = match type_hint {
+                    Some(type_hint) => {
+                        self.check_type(found, *type_hint, expression);
+                        self.type_hint(*type_hint)
+                    }
+                    None => found,
+                };
+                self.assignments[assignment].static_type = static_type;
+            }
+            Node::Wildcard(_) | Node::Meta(_, _) => self.visit(expression),
+            Node::MapPattern { .. } => {
+                self.visit(expression);
+                self.define_map_pattern(target, definition);
+            }
+            _ => {
+                self.visit(target);
+                self.visit(expression);
+            }
+        }
+    }
+
+    fn multi_assign(&mut self, targets: &[AstIndex], expression: AstIndex, definition: Definition) {
+        self.visit(expression);
+        for target in targets {
+            match &self.ast.node(*target).node {
+                Node::Id(id, ..) => {
+                    self.define(*id, *target, definition);
+                }
+                Node::Wildcard(_) => {}
+                Node::MapPattern { .. } => self.define_map_pattern(*target, definition),
+                _ => self.visit(*target),
+            }
+        }
+    }
+
+    // Defines the ids in a function arg or match pattern
+    fn define_pattern(&mut self, pattern: AstIndex) {
+        match &self.ast.node(pattern).node {
+            Node::Id(id, type_hint) => {
+                let assignment = self.define(*id, pattern, Definition::Binding);
+                self.assignments[assignment].static_type =
+                    type_hint.and_then(|type_hint| self.type_hint(type_hint));
+            }
+            Node::Ellipsis(Some(id)) => {
+                self.define(*id, pattern, Definition::Binding);
+            }
+            Node::Nested(nested) => self.define_pattern(*nested),
+            Node::Tuple(elements) | Node::TempTuple(elements) | Node::List(elements) => {
+                for element in elements {
+                    self.define_pattern(*element);
+                }
+            }
+            Node::MapPattern { .. } => self.define_map_pattern(pattern, Definition::Binding),
+            Node::Wildcard(_) | Node::Ellipsis(None) => {}
+            _ => self.visit(pattern),
+        }
+    }
+
+    // Defines the ids in a map pattern, visiting any default values
+    fn define_map_pattern(&mut self, pattern: AstIndex, definition: Definition) {
+        let ast = self.ast;
+        let Node::MapPattern { entries, rest } = &ast.node(pattern).node else {
+            return;
+        };
+
+        for entry in entries {
+            if let Some(default) = entry.default {
+                self.visit(default);
+            }
+            match (&entry.key, entry.pattern) {
+                (_, Some(nested)) => match &ast.node(nested).node {
+                    Node::Id(id, ..) => {
+                        self.define(*id, nested, definition);
+                    }
+                    Node::MapPattern { .. } => self.define_map_pattern(nested, definition),
+                    _ => self.define_pattern(nested),
+                },
+                // e.g. `{x}`, the entry's value is assigned to `x`
+                (MapKey::Id(id), None) => {
+                    self.define(*id, pattern, definition);
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(rest) = rest {
+            if let Node::Ellipsis(Some(id)) = &ast.node(*rest).node {
+                self.define(*id, *rest, definition);
+            }
+        }
+    }
+
+    // Assigns a value to an id in the current scope, returning the assignment's index
+    fn define(&mut self, id: ConstantIndex, node: AstIndex, definition: Definition) -> usize {
+        let span = *self.ast.span(self.ast.node(node).span);
+
+        let variable = match self.scope().variables.get(&id) {
+            Some(variable) => *variable,
+            None => {
+                let shadows_captured_value = self.scopes.len() > 1
+                    && definition == Definition::Assignment
+                    && self.find_variable(id).is_some();
+                if shadows_captured_value {
+                    let name = self.name(id);
+                    self.push_lint(LintKind::ShadowedVariable(name), node);
+                }
+
+                self.variables.push(Variable {
+                    name: id,
+                    span,
+                    is_import: definition == Definition::Import,
+                    used: false,
+                    assignments: Vec::new(),
+                });
+                let variable = self.variables.len() - 1;
+                self.scope_mut().variables.insert(id, variable);
+                variable
+            }
+        };
+
+        // Loop bodies are visited twice, so assignments are shared between visits
+        let assignment = match self.assignment_nodes.get(&(node, id)) {
+            Some(assignment) => *assignment,
+            None => {
+                self.assignments.push(Assignment {
+                    span,
+                    read: false,
+                    static_type: None,
+                    function: None,
+                });
+                let assignment = self.assignments.len() - 1;
+                self.assignment_nodes.insert((node, id), assignment);
+                self.variables[variable].assignments.push(assignment);
+                assignment
+            }
+        };
+
+        if definition == Definition::Export {
+            self.variables[variable].used = true;
+            self.assignments[assignment].read = true;
+        }
+
+        let scope = self.scope_mut();
+        if let Some(flow) = &mut scope.flow {
+            flow.insert(variable, BTreeSet::from([assignment]));
+        }
+        for try_assignments in scope.tries.iter_mut() {
+            try_assignments
+                .entry(variable)
+                .or_default()
+                .insert(assignment);
+        }
+
+        assignment
+    }
+
+    // Reads the value of an id, marking the assignments that reach this point as read
+    fn read(&mut self, id: ConstantIndex, node: AstIndex) {
+        match self.find_variable(id) {
+            Some((scope, variable)) => {
+                self.variables[variable].used = true;
+                let reaching = self.scopes[scope]
+                    .flow
+                    .as_ref()
+                    .and_then(|flow| flow.get(&variable));
+                for assignment in reaching.into_iter().flatten() {
+                    self.assignments[*assignment].read = true;
+                }
+            }
+            None => {
+                if self.exports.contains(&id) {
+                    return;
+                }
+                if let Some(prelude) = &self.options.prelude {
+                    let name = self.name(id);
+                    if !prelude.contains(&name) {
+                        self.push_lint(LintKind::UndefinedId(name), node);
+                    }
+                }
+            }
+        }
+    }
+
+    // Returns the assignments of an id that reach the current point
+    fn reaching_assignments(&self, id: ConstantIndex) -> Vec<usize> {
+        self.find_variable(id)
+            .and_then(|(scope, variable)| {
+                self.scopes[scope]
+                    .flow
+                    .as_ref()
+                    .and_then(|flow| flow.get(&variable))
+            })
+            .map(|assignments| assignments.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    // Returns the type of an expression, if it's known
+    fn static_type(&self, index: AstIndex) -> Option<StaticType> {
+        let ast = self.ast;
+        match &ast.node(index).node {
+            Node::Nested(nested) => self.static_type(*nested),
+            Node::Id(id, ..) => {
+                // The type is known if all of the reaching assignments have the same type
+                let assignments = self.reaching_assignments(*id);
+                let (first, rest) = assignments.split_first()?;
+                let result = self.assignments[*first].static_type.clone()?;
+                rest.iter()
+                    .all(|assignment| {
+                        self.assignments[*assignment].static_type == Some(result.clone())
+                    })
+                    .then_some(result)
+            }
+            Node::NamedCall { id, .. } => self.output_type(self.known_function(*id)?),
+            Node::Lookup(_) => {
+                let (function, _) = self.called_function(index)?;
+                self.output_type(function)
+            }
+            _ => literal_type(ast, index),
+        }
+    }
+
+    // Returns the type of a type hint node
+    fn type_hint(&self, type_hint: AstIndex) -> Option<StaticType> {
+        match &self.ast.node(type_hint).node {
+            Node::Type(type_index) => Some(StaticType::from_hint(
+                self.ast.constants().get_str(*type_index),
+            )),
+            _ => None,
+        }
+    }
+
+    // Returns the output type of a function, if it has an output type hint
+    fn output_type(&self, function: &Function) -> Option<StaticType> {
+        if function.is_generator {
+            Some(StaticType::from_hint("Generator"))
+        } else {
+            self.type_hint(function.output_type?)
+        }
+    }
+
+    // Returns the function node that's produced by an expression, if known
+    fn function_value(&self, index: AstIndex) -> Option<AstIndex> {
+        match &self.ast.node(index).node {
+            Node::Function(_) => Some(index),
+            Node::Nested(nested) => self.function_value(*nested),
+            Node::Id(id, ..) => self.reaching_function(*id),
+            _ => None,
+        }
+    }
+
+    // Returns the function that's assigned to an id, if known
+    fn known_function(&self, id: ConstantIndex) -> Option<&'a Function> {
+        let ast = self.ast;
+        match &ast.node(self.reaching_function(id)?).node {
+            Node::Function(function) => Some(function),
+            _ => None,
+        }
+    }
+
+    // Returns the function node that's assigned to an id, if all of the reaching assignments
+    // assign the same function
+    fn reaching_function(&self, id: ConstantIndex) -> Option<AstIndex> {
+        let assignments = self.reaching_assignments(id);
+        let (first, rest) = assignments.split_first()?;
+        let result = self.assignments[*first].function?;
+        rest.iter()
+            .all(|assignment| self.assignments[*assignment].function == Some(result))
+            .then_some(result)
+    }
+
+    // Returns the function and args for a call lookup on an id, e.g. `f(1, 2)`
+    fn called_function(&self, index: AstIndex) -> Option<(&'a Function, &'a [AstIndex])> {
+        let ast = self.ast;
+        let Node::Lookup((LookupNode::Root(root), Some(next))) = &ast.node(index).node else {
+            return None;
+        };
+        let (Node::Id(id, ..), Node::Lookup((LookupNode::Call { args, .. }, None))) =
+            (&ast.node(*root).node, &ast.node(*next).node)
+        else {
+            return None;
+        };
+        Some((self.known_function(*id)?, args))
+    }
+
+    // Checks call args against the type hints of the called function's args
+    fn check_call_args(&mut self, function: &Function, args: &[AstIndex]) {
+        let ast = self.ast;
+        for (i, (arg, function_arg)) in args.iter().zip(function.args.iter()).enumerate() {
+            // The type hint of a variadic arg applies to the tuple of remaining args
+            if function.is_variadic && i == function.args.len() - 1 {
+                break;
+            }
+            if let Node::Id(_, Some(type_hint)) = &ast.node(*function_arg).node {
+                let found = self.static_type(*arg);
+                self.check_type(found, *type_hint, *arg);
+            }
+        }
+    }
+
+    // Reports a mismatch if a type is known and doesn't match a type hint
+    fn check_type(&mut self, found: Option<StaticType>, type_hint: AstIndex, node: AstIndex) {
+        let Node::Type(type_index) = &self.ast.node(type_hint).node else {
+            return;
+        };
+        let expected = self.name(*type_index);
+        if let Some(found) = found {
+            if found.mismatches(&expected) {
+                self.push_lint(
+                    LintKind::TypeMismatch {
+                        expected,
+                        found: found.name,
+                    },
+                    node,
+                );
+            }
+        }
+    }
+
+    // Finds a variable in the current scope or in an enclosing scope
+    fn find_variable(&self, id: ConstantIndex) -> Option<(usize, usize)> {
+        self.scopes
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, scope)| scope.variables.get(&id).map(|variable| (i, *variable)))
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(Scope {
+            flow: Some(HashMap::new()),
+            ..Default::default()
+        });
+    }
+
+    // Removes the current scope, reporting any unused variables or assignments
+    fn pop_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        if self.replaying > 0 {
+            return;
+        }
+
+        let mut variables = scope.variables.into_values().collect::<Vec<_>>();
+        variables.sort_unstable();
+
+        for variable in variables {
+            let variable = &self.variables[variable];
+            let name = self.ast.constants().get_str(variable.name).to_string();
+            if !variable.used {
+                let kind = if variable.is_import {
+                    LintKind::UnusedImport(name)
+                } else {
+                    LintKind::UnusedVariable(name)
+                };
+                self.lints.push(Lint {
+                    kind,
+                    span: variable.span,
+                });
+            } else {
+                for assignment in variable.assignments.iter() {
+                    let assignment = &self.assignments[*assignment];
+                    if !assignment.read {
+                        self.lints.push(Lint {
+                            kind: LintKind::UnusedAssignment(name.clone()),
+                            span: assignment.span,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    fn scope(&self) -> &Scope {
+        self.scopes.last().expect("Missing scope")
+    }
+
+    fn scope_mut(&mut self) -> &mut Scope {
+        self.scopes.last_mut().expect("Missing scope")
+    }
+
+    fn current_loop(&mut self) -> &mut LoopFlow {
+        self.scope_mut().loops.last_mut().expect("Missing loop")
+    }
+
+    fn flow(&self) -> &Flow {
+        &self.scope().flow
+    }
+
+    // Sets the flow for the current point in the scope, returning the previous flow
+    fn set_flow(&mut self, flow: Flow) -> Flow {
+        std::mem::replace(&mut self.scope_mut().flow, flow)
+    }
+
+    fn name(&self, id: ConstantIndex) -> String {
+        self.ast.constants().get_str(id).to_string()
+    }
+
+    fn push_lint(&mut self, kind: LintKind, node: AstIndex) {
+        if self.replaying == 0 {
+            self.lints.push(Lint {
+                kind,
+                span: *self.ast.span(self.ast.node(node).span),
+            });
+        }
+    }
+}
+
+// Returns the ids that are exported by an export expression
+fn exported_ids(ast: &Ast, exported: AstIndex) -> Vec<ConstantIndex> {
+    let target_id = |target: &AstIndex| match &ast.node(*target).node {
+        Node::Id(id, ..) => Some(*id),
+        _ => None,
+    };
+
+    match &ast.node(exported).node {
+        Node::Assign { target, .. } => target_id(target).into_iter().collect(),
+        Node::MultiAssign { targets, .. } => targets.iter().filter_map(target_id).collect(),
+        Node::Map(entries) => entries
+            .iter()
+            .filter_map(|(key, _)| match key {
+                MapKey::Id(id) => Some(*id),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn is_compound_assignment(op: AstBinaryOp) -> bool {
+    use AstBinaryOp::*;
+    matches!(
+        op,
+        AddAssign | SubtractAssign | MultiplyAssign | DivideAssign | RemainderAssign
+    )
+}
+
+// Combines the assignments that reach a point from two different paths
+fn merge(a: Flow, b: Flow) -> Flow {
+    match (a, b) {
+        (Some(mut a), Some(b)) => {
+            for (variable, assignments) in b {
+                a.entry(variable).or_default().extend(assignments);
+            }
+            Some(a)
+        }
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+fn merge_all(flows: impl IntoIterator<Item = Flow>) -> Flow {
+    flows.into_iter().fold(None, merge)
+}
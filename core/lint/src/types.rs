@@ -0,0 +1,142 @@
+use koto_parser::{Ast, AstIndex, MapKey, MetaKeyId, Node, StringNode};
+
+// A type that's known while checking a script
+//
+// Types are either inferred from literal values, e.g. `42` is an `Int`, or they're taken from type
+// hints, e.g. a function arg declared as `x: Number` is either an `Int` or a `Float`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct StaticType {
+    // The type's name, as it would appear in a type hint
+    pub name: String,
+    // The types that a value of this type can have at runtime
+    //
+    // `None` is used for types that can't be narrowed down, e.g. a `Map` type hint also matches
+    // objects that define their own `@type`.
+    variants: Option<Vec<RuntimeType>>,
+}
+
+impl StaticType {
+    // Makes a type from a type hint, e.g. `Number` in `x: Number`
+    pub fn from_hint(name: &str) -> Self {
+        let variants = match name {
+            "Number" => Some(vec![RuntimeType::new("Int"), RuntimeType::new("Float")]),
+            "Function" => Some(vec![
+                RuntimeType::new("Function"),
+                RuntimeType::new("Generator"),
+            ]),
+            "Null" | "Bool" | "Int" | "Float" | "String" | "List" | "Tuple" | "Range"
+            | "Generator" | "Iterator" => Some(vec![RuntimeType::new(name)]),
+            _ => None,
+        };
+
+        Self {
+            name: name.into(),
+            variants,
+        }
+    }
+
+    fn value(name: &str) -> Self {
+        Self::from_runtime_type(RuntimeType::new(name))
+    }
+
+    fn map(name: &str) -> Self {
+        Self::from_runtime_type(RuntimeType {
+            name: name.into(),
+            is_map: true,
+        })
+    }
+
+    fn from_runtime_type(runtime_type: RuntimeType) -> Self {
+        Self {
+            name: runtime_type.name.clone(),
+            variants: Some(vec![runtime_type]),
+        }
+    }
+
+    // Returns true if a value of this type will never match the given type hint
+    pub fn mismatches(&self, type_hint: &str) -> bool {
+        match &self.variants {
+            Some(variants) => !variants.iter().any(|variant| variant.matches(type_hint)),
+            None => false,
+        }
+    }
+}
+
+// A type as it would be returned by `koto.type`
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct RuntimeType {
+    name: String,
+    is_map: bool,
+}
+
+impl RuntimeType {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            is_map: false,
+        }
+    }
+
+    // Follows the rules used for type hints at runtime, see `Value::matches_type`
+    fn matches(&self, type_hint: &str) -> bool {
+        let name = self.name.as_str();
+        match type_hint {
+            "Any" => true,
+            "Number" => matches!(name, "Int" | "Float"),
+            "Map" => self.is_map,
+            "Function" => matches!(name, "Function" | "Generator"),
+            "Iterable" => {
+                self.is_map || matches!(name, "Range" | "List" | "Tuple" | "String" | "Iterator")
+            }
+            _ => name == type_hint,
+        }
+    }
+}
+
+// Returns the type of a literal value, or None if the node isn't a literal
+pub(crate) fn literal_type(ast: &Ast, index: AstIndex) -> Option<StaticType> {
+    let result = match &ast.node(index).node {
+        Node::Null => StaticType::value("Null"),
+        Node::BoolTrue | Node::BoolFalse => StaticType::value("Bool"),
+        Node::SmallInt(_) | Node::Int(_) => StaticType::value("Int"),
+        Node::Float(_) => StaticType::value("Float"),
+        Node::Str(_) => StaticType::value("String"),
+        Node::List(_) => StaticType::value("List"),
+        Node::Tuple(_) | Node::TempTuple(_) => StaticType::value("Tuple"),
+        Node::Range { .. } | Node::RangeFrom { .. } | Node::RangeTo { .. } | Node::RangeFull => {
+            StaticType::value("Range")
+        }
+        Node::Function(function) if function.is_generator => StaticType::value("Generator"),
+        Node::Function(_) => StaticType::value("Function"),
+        Node::Map(entries) => {
+            let mut has_meta_keys = false;
+            for (key, value) in entries {
+                match (key, value) {
+                    (MapKey::Meta(MetaKeyId::Type, _), Some(value)) => {
+                        // The map's type is only known if `@type` is a plain string
+                        return match &ast.node(*value).node {
+                            Node::Str(string) => match string.nodes.as_slice() {
+                                [StringNode::Literal(name)] => {
+                                    Some(StaticType::map(ast.constants().get_str(*name)))
+                                }
+                                _ => None,
+                            },
+                            _ => None,
+                        };
+                    }
+                    (MapKey::Meta(..), _) => has_meta_keys = true,
+                    _ => {}
+                }
+            }
+            // Maps with meta keys but without a `@type` are shown as objects
+            if has_meta_keys {
+                StaticType::map("Object")
+            } else {
+                StaticType::map("Map")
+            }
+        }
+        _ => return None,
+    };
+
+    Some(result)
+}
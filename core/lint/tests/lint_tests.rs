@@ -0,0 +1,571 @@
+use koto_lint::{lint, LintKind, LintOptions};
+
+fn check_lints(script: &str, expected: &[(LintKind, u32)]) {
+    check_lints_with_options(script, &LintOptions::default(), expected)
+}
+
+fn check_lints_with_options(script: &str, options: &LintOptions, expected: &[(LintKind, u32)]) {
+    let lints = match lint(script, options) {
+        Ok(lints) => lints,
+        Err(error) => panic!("Failed to parse script: {error}\n{script}"),
+    };
+    let lints = lints
+        .into_iter()
+        .map(|lint| (lint.kind, lint.span.start.line))
+        .collect::<Vec<_>>();
+    assert_eq!(lints, expected, "Unexpected lints in script:\n{script}");
+}
+
+fn unused_variable(id: &str) -> LintKind {
+    LintKind::UnusedVariable(id.into())
+}
+
+fn unused_assignment(id: &str) -> LintKind {
+    LintKind::UnusedAssignment(id.into())
+}
+
+mod lint_tests {
+    use super::*;
+
+    mod unused_values {
+        use super::*;
+
+        #[test]
+        fn unused_local() {
+            let script = "
+f = ||
+  x = 1
+  y = 2
+  y
+f()
+";
+            check_lints(script, &[(unused_variable("x"), 3)]);
+        }
+
+        #[test]
+        fn unused_function_arg() {
+            let script = "
+f = |a, b, _c| a
+f 1, 2, 3
+";
+            check_lints(script, &[(unused_variable("b"), 2)]);
+        }
+
+        #[test]
+        fn unused_loop_arg() {
+            let script = "
+for i, x in 'abc'.enumerate()
+  print x
+";
+            check_lints(script, &[(unused_variable("i"), 2)]);
+        }
+
+        #[test]
+        fn unused_destructured_values() {
+            let script = "
+f = |{a, b}| a
+{x, y: z, w = 0} = f {a: 1, b: 2}
+w
+";
+            check_lints(
+                script,
+                &[
+                    (unused_variable("b"), 2),
+                    (unused_variable("x"), 3),
+                    (unused_variable("z"), 3),
+                ],
+            );
+        }
+
+        #[test]
+        fn value_used_in_string() {
+            let script = "
+x = 42
+print 'x: $x'
+";
+            check_lints(script, &[]);
+        }
+
+        #[test]
+        fn value_used_in_map_shorthand() {
+            let script = "
+x = 42
+print {x}
+";
+            check_lints(script, &[]);
+        }
+
+        #[test]
+        fn exported_values_are_used() {
+            let script = "
+export x = 1
+y = 2
+export {y}
+";
+            check_lints(script, &[]);
+        }
+
+        #[test]
+        fn unused_import() {
+            let script = "
+from number import pi, tau
+print tau
+";
+            check_lints(script, &[(LintKind::UnusedImport("pi".into()), 2)]);
+        }
+
+        #[test]
+        fn imported_value_used_as_expression() {
+            let script = "
+export
+  foo: import bar
+";
+            check_lints(script, &[]);
+        }
+    }
+
+    mod unused_assignments {
+        use super::*;
+
+        #[test]
+        fn overwritten_value() {
+            let script = "
+x = 1
+x = 2
+print x
+";
+            check_lints(script, &[(unused_assignment("x"), 2)]);
+        }
+
+        #[test]
+        fn value_read_in_branch() {
+            let script = "
+x = 1
+if foo()
+  print x
+x = 2
+print x
+";
+            check_lints(script, &[]);
+        }
+
+        #[test]
+        fn values_assigned_in_branches() {
+            let script = "
+x = if foo() then 1 else 2
+y = null
+if foo()
+  y = 1
+else
+  y = 2
+print x, y
+";
+            check_lints(script, &[(unused_assignment("y"), 3)]);
+        }
+
+        #[test]
+        fn value_read_in_next_loop_iteration() {
+            let script = "
+previous = null
+for x in 0..10
+  print previous
+  previous = x
+";
+            check_lints(script, &[]);
+        }
+
+        #[test]
+        fn value_overwritten_in_loop() {
+            let script = "
+x = 0
+for i in 0..10
+  x = i
+  x = i * 2
+print x
+";
+            check_lints(script, &[(unused_assignment("x"), 4)]);
+        }
+
+        #[test]
+        fn value_read_after_break() {
+            let script = "
+x = 0
+loop
+  x = foo()
+  if x > 10
+    break
+print x
+";
+            check_lints(script, &[(unused_assignment("x"), 2)]);
+        }
+
+        #[test]
+        fn value_read_in_catch_block() {
+            let script = "
+x = 0
+try
+  x = 1
+  foo()
+  x = 2
+catch error
+  print x, error
+print x
+";
+            check_lints(script, &[]);
+        }
+
+        #[test]
+        fn captured_value() {
+            let script = "
+x = 1
+f = || x
+x = 2
+print f(), x
+";
+            check_lints(script, &[]);
+        }
+
+        #[test]
+        fn compound_assignment() {
+            let script = "
+x = 1
+x += 1
+";
+            check_lints(script, &[(unused_assignment("x"), 3)]);
+        }
+    }
+
+    mod shadowing {
+        use super::*;
+
+        #[test]
+        fn assignment_in_function() {
+            let script = "
+x = 1
+f = ||
+  x = 2
+  x
+print f(), x
+";
+            check_lints(script, &[(LintKind::ShadowedVariable("x".into()), 4)]);
+        }
+
+        #[test]
+        fn function_args_are_ignored() {
+            let script = "
+x = 1
+f = |x| x
+print f(x)
+";
+            check_lints(script, &[]);
+        }
+    }
+
+    mod unreachable_code {
+        use super::*;
+
+        #[test]
+        fn after_return() {
+            let script = "
+f = ||
+  return 1
+  print 'unreachable'
+  print 'also unreachable'
+f()
+";
+            check_lints(script, &[(LintKind::UnreachableCode, 4)]);
+        }
+
+        #[test]
+        fn after_if_with_returns_in_all_branches() {
+            let script = "
+f = |x|
+  if x
+    return 1
+  else
+    throw 'error'
+  print 'unreachable'
+f true
+";
+            check_lints(script, &[(LintKind::UnreachableCode, 7)]);
+        }
+
+        #[test]
+        fn after_loop_without_break() {
+            let script = "
+loop
+  foo()
+print 'unreachable'
+";
+            check_lints(script, &[(LintKind::UnreachableCode, 4)]);
+        }
+
+        #[test]
+        fn after_break_and_continue() {
+            let script = "
+for x in y
+  if x
+    continue
+    print 'unreachable'
+  break
+  print 'also unreachable'
+";
+            check_lints(
+                script,
+                &[
+                    (LintKind::UnreachableCode, 5),
+                    (LintKind::UnreachableCode, 7),
+                ],
+            );
+        }
+
+        #[test]
+        fn return_in_branch() {
+            let script = "
+f = |x|
+  if x
+    return 1
+  2
+f true
+";
+            check_lints(script, &[]);
+        }
+    }
+
+    mod matches {
+        use super::*;
+
+        #[test]
+        fn non_exhaustive_match() {
+            let script = "
+print match foo()
+  0 then 'zero'
+  1 then 'one'
+";
+            check_lints(script, &[(LintKind::NonExhaustiveMatch, 2)]);
+        }
+
+        #[test]
+        fn match_with_else() {
+            let script = "
+print match foo()
+  0 then 'zero'
+  else 'other'
+";
+            check_lints(script, &[]);
+        }
+
+        #[test]
+        fn match_with_wildcard() {
+            let script = "
+print match foo()
+  0 then 'zero'
+  _ then 'other'
+";
+            check_lints(script, &[]);
+        }
+
+        #[test]
+        fn match_with_binding() {
+            let script = "
+print match foo()
+  0 then 'zero'
+  x if x > 0 then 'positive'
+  x then x
+";
+            check_lints(script, &[]);
+        }
+
+        #[test]
+        fn match_on_bools() {
+            let script = "
+print match foo()
+  true then 'yes'
+  false then 'no'
+";
+            check_lints(script, &[]);
+        }
+
+        #[test]
+        fn unused_binding_in_match_arm() {
+            let script = "
+print match foo()
+  (a, b) then a
+  else null
+";
+            check_lints(script, &[(unused_variable("b"), 3)]);
+        }
+
+        #[test]
+        fn typed_binding_is_non_exhaustive() {
+            let script = "
+print match foo()
+  n: Number then n
+";
+            check_lints(script, &[(LintKind::NonExhaustiveMatch, 2)]);
+        }
+
+        #[test]
+        fn unused_binding_in_map_pattern() {
+            let script = "
+print match foo()
+  {x, y, rest...} then x + y
+  else null
+";
+            check_lints(script, &[(unused_variable("rest"), 3)]);
+        }
+    }
+
+    mod type_hints {
+        use super::*;
+
+        fn type_mismatch(expected: &str, found: &str) -> LintKind {
+            LintKind::TypeMismatch {
+                expected: expected.into(),
+                found: found.into(),
+            }
+        }
+
+        #[test]
+        fn assignment() {
+            let script = "
+x: Int = 1.5
+y: Number = 2.5
+print x, y
+";
+            check_lints(script, &[(type_mismatch("Int", "Float"), 2)]);
+        }
+
+        #[test]
+        fn call_args() {
+            let script = "
+f = |x: String, y: Map| '$x $y'
+print f 'abc', {}
+print f 42, {}
+";
+            check_lints(script, &[(type_mismatch("String", "Int"), 4)]);
+        }
+
+        #[test]
+        fn value_passed_to_typed_arg() {
+            let script = "
+f = |x: List| x
+a = (1, 2)
+print f a
+";
+            check_lints(script, &[(type_mismatch("List", "Tuple"), 4)]);
+        }
+
+        #[test]
+        fn function_output() {
+            let script = "
+f = |x| -> String
+  if x
+    return 42
+  x
+g = || -> Int
+  yield 1.5
+h = || -> Bool
+  null
+print f(true), g(), h()
+";
+            check_lints(
+                script,
+                &[
+                    (type_mismatch("String", "Int"), 4),
+                    (type_mismatch("Int", "Float"), 7),
+                    (type_mismatch("Bool", "Null"), 9),
+                ],
+            );
+        }
+
+        #[test]
+        fn object_types() {
+            let script = "
+f = |x: Foo| x
+print f {@type: 'Foo'}
+print f {@type: 'Bar'}
+";
+            check_lints(script, &[(type_mismatch("Foo", "Bar"), 4)]);
+        }
+
+        #[test]
+        fn matching_types() {
+            let script = "
+a: Number = 1
+b: Map = {@type: 'Foo'}
+c: Any = null
+d: Iterable = 'abc'
+e: Function = || 42
+f: Foo = foo()
+print a, b, c, d, e, f
+";
+            check_lints(script, &[]);
+        }
+    }
+
+    mod undefined_ids {
+        use super::*;
+
+        fn prelude_options() -> LintOptions {
+            LintOptions {
+                prelude: Some(["print", "io"].into_iter().map(String::from).collect()),
+            }
+        }
+
+        #[test]
+        fn undefined_ids_are_ignored_without_a_prelude() {
+            check_lints("print foo", &[]);
+        }
+
+        #[test]
+        fn undefined_id() {
+            let script = "
+x = 1
+print y
+io.print x
+";
+            check_lints_with_options(
+                script,
+                &prelude_options(),
+                &[(LintKind::UndefinedId("y".into()), 3)],
+            );
+        }
+
+        #[test]
+        fn value_assigned_after_function_is_created() {
+            let script = "
+f = || g()
+g = || 42
+print f()
+";
+            check_lints_with_options(
+                script,
+                &prelude_options(),
+                &[
+                    (LintKind::UndefinedId("g".into()), 2),
+                    (unused_variable("g"), 3),
+                ],
+            );
+        }
+
+        #[test]
+        fn exported_values_are_defined() {
+            let script = "
+f = || g()
+export g = || 42
+print f()
+";
+            check_lints_with_options(script, &prelude_options(), &[]);
+        }
+
+        #[test]
+        fn recursive_function() {
+            let script = "
+f = |n| if n > 0 then f n - 1 else n
+print f 10
+";
+            check_lints_with_options(script, &prelude_options(), &[]);
+        }
+    }
+}
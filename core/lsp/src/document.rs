@@ -0,0 +1,694 @@
+use koto_bytecode::{CompilerSettings, Loader};
+use koto_lexer::{Lexer, Position, Span, Token};
+use koto_parser::{
+    Ast, AstFor, AstIf, AstIndex, AstString, AstTry, ImportItemNode, LookupNode, MapKey, Node,
+    Parser, StringNode,
+};
+use serde_json::{json, Value as Json};
+use std::path::PathBuf;
+
+// An error that should be reported to the client
+pub struct Diagnostic {
+    pub span: Span,
+    pub message: String,
+}
+
+// A value that's assigned to a name in the document
+pub struct Definition {
+    pub name: String,
+    // The span of the name in the definition
+    pub span: Span,
+    // Set when the definition was brought into scope with an `import` expression
+    pub import: Option<ImportSource>,
+    // The index of the scope that the definition belongs to
+    scope: usize,
+}
+
+// The source of an imported definition
+//
+// e.g.
+//   `import foo` has the module `["foo"]` and no item,
+//   `from foo.bar import baz` has the module `["foo", "bar"]` and the item `baz`.
+pub struct ImportSource {
+    pub module: Vec<String>,
+    pub item: Option<String>,
+}
+
+// An identifier in the document that's referred to by a client request
+pub struct Reference {
+    pub name: String,
+    pub span: Span,
+    // Set when the identifier is accessed with `.`
+    //
+    // The ids in the lookup chain that precede the identifier are included, e.g. hovering over
+    // `baz` in `foo.bar.baz` produces `["foo", "bar"]`. If the chain starts with an expression
+    // other than an id then the chain will be empty.
+    pub member_of: Option<Vec<String>>,
+}
+
+// A Koto script that's open in the client, along with the results of analyzing it
+pub struct Document {
+    pub path: Option<PathBuf>,
+    pub diagnostics: Vec<Diagnostic>,
+    source: String,
+    tokens: Vec<TokenInfo>,
+    scopes: Vec<Scope>,
+    definitions: Vec<Definition>,
+    // The spans of top-level `export` expressions, and the exported expressions
+    exports: Vec<(Span, Export)>,
+}
+
+impl Document {
+    pub fn new(source: String, path: Option<PathBuf>) -> Self {
+        let tokens = tokenize(&source);
+
+        let (ast, errors) = Parser::parse_with_recovery(&source);
+        let mut diagnostics: Vec<Diagnostic> = errors
+            .iter()
+            .map(|error| Diagnostic {
+                span: error.span,
+                message: error.to_string(),
+            })
+            .collect();
+
+        // The loader is used to check for compilation errors once the script has been parsed
+        if diagnostics.is_empty() {
+            if let Err(error) =
+                Loader::default().compile_script(&source, &path, CompilerSettings::default())
+            {
+                diagnostics.push(Diagnostic {
+                    span: error.span().unwrap_or_default(),
+                    message: error.kind().to_string(),
+                });
+            }
+        }
+
+        let mut analyzer = Analyzer {
+            ast: &ast,
+            tokens: &tokens,
+            scopes: vec![Scope {
+                span: Span {
+                    start: Position { line: 0, column: 0 },
+                    end: Position {
+                        line: u32::MAX,
+                        column: u32::MAX,
+                    },
+                },
+                parent: None,
+            }],
+            definitions: Vec::new(),
+            exports: Vec::new(),
+        };
+        if let Some(Node::MainBlock { body, .. }) = ast.entry_point().map(|entry| &entry.node) {
+            for expression in body {
+                if let Node::Export(exported) = &ast.node(*expression).node {
+                    let export = match &ast.node(*exported).node {
+                        Node::Map(entries) => Export::Map(
+                            entries
+                                .iter()
+                                .filter_map(|(key, _)| match key {
+                                    MapKey::Id(id) => Some(ast.constants().get_str(*id).into()),
+                                    _ => None,
+                                })
+                                .collect(),
+                        ),
+                        _ => Export::Expression,
+                    };
+                    analyzer.exports.push((analyzer.span(*expression), export));
+                }
+                analyzer.visit(*expression, 0);
+            }
+        }
+
+        let Analyzer {
+            scopes,
+            definitions,
+            exports,
+            ..
+        } = analyzer;
+
+        Self {
+            path,
+            diagnostics,
+            source,
+            tokens,
+            scopes,
+            definitions,
+            exports,
+        }
+    }
+
+    // Returns the source line with the given line number, counting from 1
+    pub fn line(&self, line: u32) -> &str {
+        self.source
+            .lines()
+            .nth(line.saturating_sub(1) as usize)
+            .unwrap_or_default()
+    }
+
+    // Returns the identifier at the given position
+    pub fn reference_at(&self, position: Position) -> Option<Reference> {
+        let index = self
+            .tokens
+            .iter()
+            .position(|token| token.token == Token::Id && span_contains(&token.span, position))?;
+        let token = &self.tokens[index];
+
+        Some(Reference {
+            name: token.slice.clone(),
+            span: token.span,
+            member_of: self.lookup_chain_before(index),
+        })
+    }
+
+    // Returns the lookup chain that's being completed at the given position
+    //
+    // e.g. with the cursor at the end of `foo.bar.ba`, `["foo", "bar"]` is returned.
+    pub fn completion_chain_at(&self, position: Position) -> Option<Vec<String>> {
+        let index = self
+            .tokens
+            .iter()
+            .rposition(|token| position_key(token.span.end) <= position_key(position))?;
+        let token = &self.tokens[index];
+        if token.span.end != position {
+            return None;
+        }
+
+        match token.token {
+            Token::Dot => self.lookup_chain_before(index + 1),
+            // A partially typed id will be replaced by the completion
+            Token::Id => self.lookup_chain_before(index),
+            _ => None,
+        }
+    }
+
+    // Returns the ids of the lookup chain preceding the token at the given index
+    fn lookup_chain_before(&self, index: usize) -> Option<Vec<String>> {
+        let is_token = |index: Option<usize>, expected: Token| {
+            index
+                .and_then(|index| self.tokens.get(index))
+                .is_some_and(|token| token.token == expected)
+        };
+
+        if !is_token(index.checked_sub(1), Token::Dot) {
+            return None;
+        }
+
+        let mut chain = Vec::new();
+        let mut index = index;
+        while is_token(index.checked_sub(1), Token::Dot) {
+            if !is_token(index.checked_sub(2), Token::Id) {
+                // The chain starts with something other than an id
+                return Some(Vec::new());
+            }
+            index -= 2;
+            chain.push(self.tokens[index].slice.clone());
+        }
+        chain.reverse();
+
+        Some(chain)
+    }
+
+    // Finds the definition of a name that's visible at the given position
+    //
+    // Scopes are searched from the innermost outwards. Within a scope, the closest definition
+    // that precedes the position is preferred, otherwise the scope's first definition is used.
+    pub fn find_definition(&self, name: &str, position: Position) -> Option<&Definition> {
+        let mut scope = Some(self.scope_at(position));
+
+        while let Some(scope_index) = scope {
+            let mut candidates = self
+                .definitions
+                .iter()
+                .filter(|definition| definition.scope == scope_index && definition.name == name);
+            let first = candidates.clone().next();
+            let preceding = candidates
+                .rfind(|definition| position_key(definition.span.start) <= position_key(position));
+
+            if let Some(definition) = preceding.or(first) {
+                return Some(definition);
+            }
+
+            scope = self.scopes[scope_index].parent;
+        }
+
+        None
+    }
+
+    // Returns the definitions that are visible at the given position, innermost scope first
+    pub fn definitions_at(&self, position: Position) -> Vec<&Definition> {
+        let mut result: Vec<&Definition> = Vec::new();
+        let mut scope = Some(self.scope_at(position));
+
+        while let Some(scope_index) = scope {
+            for definition in self
+                .definitions
+                .iter()
+                .filter(|definition| definition.scope == scope_index)
+            {
+                if !result
+                    .iter()
+                    .any(|existing| existing.name == definition.name)
+                {
+                    result.push(definition);
+                }
+            }
+            scope = self.scopes[scope_index].parent;
+        }
+
+        result
+    }
+
+    // Finds where a name is exported from the document
+    pub fn find_export(&self, name: &str) -> Option<Span> {
+        for (export_span, export) in self.exports.iter() {
+            match export {
+                Export::Map(keys) if keys.iter().any(|key| key == name) => {
+                    // Map keys don't have their own spans, so the key's token is found
+                    let key_token = self.tokens.iter().enumerate().find(|(i, token)| {
+                        token.token == Token::Id
+                            && token.slice == name
+                            && span_contains(export_span, token.span.start)
+                            && self.tokens.get(i + 1).is_none_or(|next| {
+                                matches!(
+                                    next.token,
+                                    Token::Colon
+                                        | Token::Comma
+                                        | Token::CurlyClose
+                                        | Token::NewLine
+                                        | Token::NewLineIndented
+                                )
+                            })
+                    });
+                    if let Some((_, token)) = key_token {
+                        return Some(token.span);
+                    }
+                }
+                Export::Expression => {
+                    if let Some(definition) = self.definitions.iter().find(|definition| {
+                        definition.scope == 0
+                            && definition.name == name
+                            && span_contains(export_span, definition.span.start)
+                    }) {
+                        return Some(definition.span);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Fall back to a top-level definition with a matching name
+        self.definitions
+            .iter()
+            .find(|definition| definition.scope == 0 && definition.name == name)
+            .map(|definition| definition.span)
+    }
+
+    // Converts a position provided by the client into a Koto source position
+    //
+    // LSP positions count lines and UTF-16 code units from 0, while Koto positions count lines
+    // and characters from 1.
+    pub fn position_from_lsp(&self, position: &Json) -> Position {
+        let line = position["line"].as_u64().unwrap_or(0) as u32 + 1;
+        let character = position["character"].as_u64().unwrap_or(0) as usize;
+
+        let mut utf16_count = 0;
+        let mut column = 1;
+        for c in self.line(line).chars() {
+            if utf16_count >= character {
+                break;
+            }
+            utf16_count += c.len_utf16();
+            column += 1;
+        }
+
+        Position { line, column }
+    }
+
+    // Converts a Koto source position into an LSP position, see position_from_lsp()
+    pub fn position_to_lsp(&self, position: Position) -> Json {
+        let character: usize = self
+            .line(position.line)
+            .chars()
+            .take(position.column.saturating_sub(1) as usize)
+            .map(char::len_utf16)
+            .sum();
+
+        json!({"line": position.line.saturating_sub(1), "character": character})
+    }
+
+    pub fn span_to_lsp(&self, span: &Span) -> Json {
+        json!({
+            "start": self.position_to_lsp(span.start),
+            "end": self.position_to_lsp(span.end),
+        })
+    }
+
+    // Returns the index of the innermost scope containing the position
+    fn scope_at(&self, position: Position) -> usize {
+        // Nested scopes are always added after the scope that contains them
+        self.scopes
+            .iter()
+            .rposition(|scope| span_contains(&scope.span, position))
+            .unwrap_or(0)
+    }
+}
+
+// A token produced by the lexer
+struct TokenInfo {
+    token: Token,
+    span: Span,
+    // The token's source, only stored for ids
+    slice: String,
+}
+
+// Produces the document's tokens, whitespace and comments are skipped
+fn tokenize(source: &str) -> Vec<TokenInfo> {
+    let mut lexer = Lexer::new(source);
+    let mut result = Vec::new();
+
+    while let Some(token) = lexer.next() {
+        match token {
+            Token::Whitespace | Token::CommentSingle | Token::CommentMulti => {}
+            _ => result.push(TokenInfo {
+                token,
+                span: lexer.span(),
+                slice: if token == Token::Id {
+                    lexer.slice().into()
+                } else {
+                    String::new()
+                },
+            }),
+        }
+    }
+
+    result
+}
+
+// A function's scope, or the main scope at index 0
+struct Scope {
+    span: Span,
+    parent: Option<usize>,
+}
+
+// A top-level export
+enum Export {
+    // A map of exported values, with the ids of the map's keys
+    Map(Vec<String>),
+    // Any other exported expression, e.g. `export foo = 42`
+    Expression,
+}
+
+// Walks the AST, collecting the document's scopes and definitions
+struct Analyzer<'a> {
+    ast: &'a Ast,
+    tokens: &'a [TokenInfo],
+    scopes: Vec<Scope>,
+    definitions: Vec<Definition>,
+    exports: Vec<(Span, Export)>,
+}
+
+impl<'a> Analyzer<'a> {
+    fn visit(&mut self, index: AstIndex, scope: usize) {
+        match &self.ast.node(index).node {
+            Node::Nested(nested) => self.visit(*nested, scope),
+            Node::Lookup((lookup_node, next)) => {
+                match lookup_node {
+                    LookupNode::Root(root) => self.visit(*root, scope),
+                    LookupNode::Str(string) => self.visit_string(string, scope),
+                    LookupNode::Index(index) => self.visit(*index, scope),
+                    LookupNode::Call { args, .. } => self.visit_nodes(args, scope),
+                    LookupNode::Id(_) => {}
+                }
+                if let Some(next) = next {
+                    self.visit(*next, scope);
+                }
+            }
+            Node::NamedCall { args, .. } => self.visit_nodes(args, scope),
+            Node::Str(string) => self.visit_string(string, scope),
+            Node::List(elements)
+            | Node::Tuple(elements)
+            | Node::TempTuple(elements)
+            | Node::Block(elements)
+            | Node::MainBlock { body: elements, .. } => self.visit_nodes(elements, scope),
+            Node::Range { start, end, .. } => {
+                self.visit(*start, scope);
+                self.visit(*end, scope);
+            }
+            Node::RangeFrom { start } => self.visit(*start, scope),
+            Node::RangeTo { end, .. } => self.visit(*end, scope),
+            Node::Map(entries) => {
+                for (key, value) in entries {
+                    if let MapKey::Str(string) = key {
+                        self.visit_string(string, scope);
+                    }
+                    if let Some(value) = value {
+                        self.visit(*value, scope);
+                    }
+                }
+            }
+            Node::Function(function) => {
+                self.scopes.push(Scope {
+                    span: self.span(index),
+                    parent: Some(scope),
+                });
+                let function_scope = self.scopes.len() - 1;
+                for arg in function.args.iter() {
+                    self.define_pattern(*arg, function_scope);
+                }
+                self.visit(function.body, function_scope);
+            }
+            Node::Import { from, items } => {
+                let from: Vec<String> = from
+                    .iter()
+                    .filter_map(|item| self.import_id(item))
+                    .collect();
+                let import_span = self.span(index);
+
+                for item in items.iter() {
+                    let Some(name) = self.import_id(item) else {
+                        continue;
+                    };
+                    // Import items don't have their own spans, so the item's token is found
+                    let span = self
+                        .tokens
+                        .iter()
+                        .rfind(|token| {
+                            token.token == Token::Id
+                                && token.slice == name
+                                && span_contains(&import_span, token.span.start)
+                        })
+                        .map_or(import_span, |token| token.span);
+
+                    let import = if from.is_empty() {
+                        ImportSource {
+                            module: vec![name.clone()],
+                            item: None,
+                        }
+                    } else {
+                        ImportSource {
+                            module: from.clone(),
+                            item: Some(name.clone()),
+                        }
+                    };
+
+                    self.definitions.push(Definition {
+                        name,
+                        span,
+                        import: Some(import),
+                        scope,
+                    });
+                }
+            }
+            Node::Export(exported) => self.visit(*exported, scope),
+            Node::Assign { target, expression } => {
+                self.define_target(*target, scope);
+                self.visit(*expression, scope);
+            }
+            Node::MultiAssign {
+                targets,
+                expression,
+            } => {
+                for target in targets {
+                    self.define_target(*target, scope);
+                }
+                self.visit(*expression, scope);
+            }
+            Node::UnaryOp { value, .. } => self.visit(*value, scope),
+            Node::BinaryOp { lhs, rhs, .. } => {
+                self.visit(*lhs, scope);
+                self.visit(*rhs, scope);
+            }
+            Node::If(AstIf {
+                condition,
+                then_node,
+                else_if_blocks,
+                else_node,
+            }) => {
+                self.visit(*condition, scope);
+                self.visit(*then_node, scope);
+                for (condition, block) in else_if_blocks {
+                    self.visit(*condition, scope);
+                    self.visit(*block, scope);
+                }
+                if let Some(else_node) = else_node {
+                    self.visit(*else_node, scope);
+                }
+            }
+            Node::Match { expression, arms } => {
+                self.visit(*expression, scope);
+                for arm in arms {
+                    for pattern in arm.patterns.iter() {
+                        self.define_pattern(*pattern, scope);
+                    }
+                    if let Some(condition) = arm.condition {
+                        self.visit(condition, scope);
+                    }
+                    self.visit(arm.expression, scope);
+                }
+            }
+            Node::Switch(arms) => {
+                for arm in arms {
+                    if let Some(condition) = arm.condition {
+                        self.visit(condition, scope);
+                    }
+                    self.visit(arm.expression, scope);
+                }
+            }
+            Node::For(AstFor {
+                args,
+                iterable,
+                body,
+            }) => {
+                for arg in args {
+                    self.define_pattern(*arg, scope);
+                }
+                self.visit(*iterable, scope);
+                self.visit(*body, scope);
+            }
+            Node::Loop { body } => self.visit(*body, scope),
+            Node::While { condition, body } | Node::Until { condition, body } => {
+                self.visit(*condition, scope);
+                self.visit(*body, scope);
+            }
+            Node::Break(value) | Node::Return(value) => {
+                if let Some(value) = value {
+                    self.visit(*value, scope);
+                }
+            }
+            Node::Try(AstTry {
+                try_block,
+                catch_arg,
+                catch_block,
+                finally_block,
+            }) => {
+                self.visit(*try_block, scope);
+                self.define_pattern(*catch_arg, scope);
+                self.visit(*catch_block, scope);
+                if let Some(finally_block) = finally_block {
+                    self.visit(*finally_block, scope);
+                }
+            }
+            Node::Throw(value) | Node::Yield(value) => self.visit(*value, scope),
+            Node::Debug { expression, .. } => self.visit(*expression, scope),
+            Node::Null
+            | Node::Id(..)
+            | Node::Meta(_, _)
+            | Node::BoolTrue
+            | Node::BoolFalse
+            | Node::SmallInt(_)
+            | Node::Int(_)
+            | Node::Float(_)
+            | Node::RangeFull
+            | Node::Self_
+            | Node::Wildcard(_)
+            | Node::Ellipsis(_)
+            | Node::MapPattern { .. }
+            | Node::Continue
+            | Node::Type(_) => {}
+        }
+    }
+
+    fn visit_nodes(&mut self, nodes: &[AstIndex], scope: usize) {
+        for node in nodes {
+            self.visit(*node, scope);
+        }
+    }
+
+    fn visit_string(&mut self, string: &AstString, scope: usize) {
+        for node in string.nodes.iter() {
+            if let StringNode::Expr(expression) | StringNode::FormattedExpr(expression, _) = node {
+                self.visit(*expression, scope);
+            }
+        }
+    }
+
+    // Adds a definition for an assignment target, other targets (e.g. `foo.bar = 1`) are visited
+    fn define_target(&mut self, target: AstIndex, scope: usize) {
+        match &self.ast.node(target).node {
+            Node::Id(id, ..) => self.define(*id, target, scope),
+            Node::MapPattern { .. } => self.define_pattern(target, scope),
+            Node::Wildcard(_) => {}
+            _ => self.visit(target, scope),
+        }
+    }
+
+    // Adds definitions for the ids in a function argument or match pattern
+    fn define_pattern(&mut self, pattern: AstIndex, scope: usize) {
+        match &self.ast.node(pattern).node {
+            Node::Id(id, ..) | Node::Ellipsis(Some(id)) => self.define(*id, pattern, scope),
+            Node::Nested(nested) => self.define_pattern(*nested, scope),
+            Node::Tuple(elements) | Node::TempTuple(elements) | Node::List(elements) => {
+                for element in elements {
+                    self.define_pattern(*element, scope);
+                }
+            }
+            Node::MapPattern { entries, rest } => {
+                for entry in entries {
+                    if let Some(default) = entry.default {
+                        self.visit(default, scope);
+                    }
+                    match (&entry.key, entry.pattern) {
+                        (_, Some(nested)) => self.define_pattern(nested, scope),
+                        (MapKey::Id(id), None) => self.define(*id, pattern, scope),
+                        _ => {}
+                    }
+                }
+                if let Some(rest) = rest {
+                    self.define_pattern(*rest, scope);
+                }
+            }
+            Node::Wildcard(_) | Node::Ellipsis(None) => {}
+            _ => self.visit(pattern, scope),
+        }
+    }
+
+    fn define(&mut self, id: u32, node: AstIndex, scope: usize) {
+        self.definitions.push(Definition {
+            name: self.ast.constants().get_str(id).into(),
+            span: self.span(node),
+            import: None,
+            scope,
+        });
+    }
+
+    fn import_id(&self, item: &ImportItemNode) -> Option<String> {
+        match item {
+            ImportItemNode::Id(id) => Some(self.ast.constants().get_str(*id).into()),
+            ImportItemNode::Str(_) => None,
+        }
+    }
+
+    fn span(&self, node: AstIndex) -> Span {
+        *self.ast.span(self.ast.node(node).span)
+    }
+}
+
+fn position_key(position: Position) -> (u32, u32) {
+    (position.line, position.column)
+}
+
+fn span_contains(span: &Span, position: Position) -> bool {
+    position_key(span.start) <= position_key(position)
+        && position_key(position) <= position_key(span.end)
+}
@@ -0,0 +1,94 @@
+use koto_runtime::{Value, Vm};
+use std::collections::HashMap;
+
+// A core library module, along with the names of its members
+pub struct CoreModule {
+    pub name: String,
+    pub members: Vec<String>,
+}
+
+// Documentation for the core library, drawn from the same markdown sections as the CLI's help
+pub struct Help {
+    // Markdown sections, keyed by `module` or `module.item`
+    entries: HashMap<String, String>,
+    // The `kototype` signatures of module items, keyed by `module.item`
+    signatures: HashMap<String, String>,
+    modules: Vec<CoreModule>,
+    // Functions that are made available in the prelude, mapped to their documentation keys
+    prelude_functions: HashMap<String, String>,
+}
+
+impl Help {
+    pub fn new() -> Self {
+        let mut result = Self {
+            entries: HashMap::new(),
+            signatures: HashMap::new(),
+            modules: Vec::new(),
+            prelude_functions: HashMap::new(),
+        };
+
+        for section in koto_help::core_library()
+            .into_iter()
+            .flat_map(|doc| doc.sections)
+        {
+            if let Some(signature) = section.signature {
+                result.signatures.insert(section.name.clone(), signature);
+            }
+            // Example results are shown as comments
+            let markdown = section.markdown.replace("\ncheck! ", "\n# ➝ ");
+            result.entries.insert(section.name, markdown);
+        }
+
+        // The modules and their members are taken from the runtime's prelude
+        let vm = Vm::default();
+        let mut prelude_functions = Vec::new();
+        for (key, value) in vm.prelude().data().iter() {
+            match value {
+                Value::Map(module) => result.modules.push(CoreModule {
+                    name: key.to_string(),
+                    members: module.data().keys().map(|key| key.to_string()).collect(),
+                }),
+                _ => prelude_functions.push(key.to_string()),
+            }
+        }
+        for function in prelude_functions {
+            if let Some(module) = result
+                .modules
+                .iter()
+                .find(|module| module.members.contains(&function))
+            {
+                let key = format!("{}.{function}", module.name);
+                result.prelude_functions.insert(function, key);
+            }
+        }
+
+        result
+    }
+
+    // Returns the markdown documentation for a module or module item, e.g. `list` or `list.first`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(String::as_str)
+    }
+
+    // Returns the signature of a module item, e.g. `|List| -> Value` for `list.first`
+    pub fn signature(&self, key: &str) -> Option<&str> {
+        self.signatures.get(key).map(String::as_str)
+    }
+
+    pub fn modules(&self) -> &[CoreModule] {
+        &self.modules
+    }
+
+    pub fn module(&self, name: &str) -> Option<&CoreModule> {
+        self.modules.iter().find(|module| module.name == name)
+    }
+
+    // Returns the documentation key for a function that's available in the prelude
+    pub fn prelude_function(&self, name: &str) -> Option<&str> {
+        self.prelude_functions.get(name).map(String::as_str)
+    }
+
+    pub fn prelude_functions(&self) -> impl Iterator<Item = &str> {
+        self.prelude_functions.keys().map(String::as_str)
+    }
+}
@@ -0,0 +1,32 @@
+//! A Language Server Protocol server for Koto
+//!
+//! The server communicates with the client over stdin and stdout, and provides:
+//!
+//! - Diagnostics for parser and compiler errors.
+//! - Go-to-definition for local values, and for the exports of imported modules.
+//! - Hover documentation for the core library.
+//! - Completion of core library module members, along with the values that are in scope.
+
+mod document;
+mod help;
+mod protocol;
+mod server;
+
+use protocol::{read_message, Connection};
+use server::Server;
+use std::{io, process};
+
+fn main() {
+    let mut server = Server::new(Connection::new(io::stdout()));
+
+    let mut input = io::stdin().lock();
+    while let Ok(Some(message)) = read_message(&mut input) {
+        if !server.handle_message(&message) {
+            break;
+        }
+    }
+
+    // The exit code indicates whether or not the client shut down the server before exiting
+    let exit_code = if server.shutdown_requested() { 0 } else { 1 };
+    process::exit(exit_code);
+}
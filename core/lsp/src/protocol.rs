@@ -0,0 +1,95 @@
+use serde_json::{json, Value as Json};
+use std::io::{self, BufRead, Write};
+
+// Reads a message from the client
+//
+// Messages are JSON-RPC objects preceded by a `Content-Length` header.
+// None is returned when the client has closed the connection.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut content_length = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(length) = header.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse::<usize>().ok();
+        }
+    }
+
+    let Some(content_length) = content_length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Missing Content-Length header",
+        ));
+    };
+
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content)?;
+
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+// The JSON-RPC error code used when a request isn't supported by the server
+pub const METHOD_NOT_FOUND: i64 = -32601;
+// The JSON-RPC error code used when a request is received after shutdown
+pub const INVALID_REQUEST: i64 = -32600;
+
+// The connection to the client, responses and notifications are written to the output
+pub struct Connection {
+    output: Box<dyn Write>,
+}
+
+impl Connection {
+    pub fn new(output: impl Write + 'static) -> Self {
+        Self {
+            output: Box::new(output),
+        }
+    }
+
+    pub fn respond(&mut self, request: &Json, result: Json) {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": result,
+        }))
+    }
+
+    pub fn respond_with_error(&mut self, request: &Json, code: i64, message: &str) {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "error": {"code": code, "message": message},
+        }))
+    }
+
+    pub fn send_notification(&mut self, method: &str, params: Json) {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        }))
+    }
+
+    fn send(&mut self, message: Json) {
+        let content = message.to_string();
+        // If the output is unavailable then the client has gone away,
+        // and the server will exit when the input is closed.
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{content}",
+            content.len()
+        )
+        .ok();
+        self.output.flush().ok();
+    }
+}
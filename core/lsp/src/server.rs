@@ -0,0 +1,439 @@
+use crate::{
+    document::{Definition, Document},
+    help::Help,
+    protocol::{Connection, INVALID_REQUEST, METHOD_NOT_FOUND},
+};
+use koto_bytecode::Loader;
+use koto_lexer::{Position, Span};
+use serde_json::{json, Value as Json};
+use std::{collections::HashMap, fs, path::PathBuf};
+
+// The LSP `CompletionItemKind` values used by the server
+const COMPLETION_KIND_FUNCTION: u32 = 3;
+const COMPLETION_KIND_VARIABLE: u32 = 6;
+const COMPLETION_KIND_MODULE: u32 = 9;
+
+// The LSP `TextDocumentSyncKind` value for full document syncing
+const TEXT_DOCUMENT_SYNC_FULL: u32 = 1;
+
+// The LSP `DiagnosticSeverity` value for errors
+const DIAGNOSTIC_SEVERITY_ERROR: u32 = 1;
+
+// The state of the language server
+pub struct Server {
+    connection: Connection,
+    // The documents that are open in the client, keyed by URI
+    documents: HashMap<String, Document>,
+    help: Help,
+    shutdown_requested: bool,
+}
+
+impl Server {
+    pub fn new(connection: Connection) -> Self {
+        Self {
+            connection,
+            documents: HashMap::new(),
+            help: Help::new(),
+            shutdown_requested: false,
+        }
+    }
+
+    pub fn shutdown_requested(&self) -> bool {
+        self.shutdown_requested
+    }
+
+    // Handles a message from the client
+    //
+    // Returns false when the client has asked for the server to exit.
+    pub fn handle_message(&mut self, message: &Json) -> bool {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let is_request = message.get("id").is_some();
+
+        if self.shutdown_requested && is_request {
+            self.connection.respond_with_error(
+                message,
+                INVALID_REQUEST,
+                "The server has been shut down",
+            );
+            return true;
+        }
+
+        match method {
+            "initialize" => self.connection.respond(
+                message,
+                json!({
+                    "capabilities": {
+                        "textDocumentSync": TEXT_DOCUMENT_SYNC_FULL,
+                        "definitionProvider": true,
+                        "hoverProvider": true,
+                        "completionProvider": {"triggerCharacters": ["."]},
+                    },
+                    "serverInfo": {
+                        "name": "koto_lsp",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }),
+            ),
+            "shutdown" => {
+                self.shutdown_requested = true;
+                self.connection.respond(message, Json::Null);
+            }
+            "exit" => return false,
+            "textDocument/didOpen" => {
+                let document = &params["textDocument"];
+                self.update_document(
+                    document["uri"].as_str().unwrap_or_default(),
+                    document["text"].as_str().unwrap_or_default(),
+                );
+            }
+            "textDocument/didChange" => {
+                // Full syncing is used, so the last change contains the document's contents
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                if let Some(text) = text {
+                    self.update_document(
+                        params["textDocument"]["uri"].as_str().unwrap_or_default(),
+                        text,
+                    );
+                }
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                self.documents.remove(uri);
+                self.connection.send_notification(
+                    "textDocument/publishDiagnostics",
+                    json!({"uri": uri, "diagnostics": []}),
+                );
+            }
+            "textDocument/definition" => {
+                let result = self.definition(params).unwrap_or(Json::Null);
+                self.connection.respond(message, result);
+            }
+            "textDocument/hover" => {
+                let result = self.hover(params).unwrap_or(Json::Null);
+                self.connection.respond(message, result);
+            }
+            "textDocument/completion" => {
+                let result = self.completion(params);
+                self.connection.respond(message, result);
+            }
+            _ => {
+                // Unsupported notifications are ignored
+                if is_request {
+                    self.connection.respond_with_error(
+                        message,
+                        METHOD_NOT_FOUND,
+                        &format!("Unsupported request '{method}'"),
+                    );
+                }
+            }
+        }
+
+        true
+    }
+
+    fn update_document(&mut self, uri: &str, text: &str) {
+        let document = Document::new(text.into(), uri_to_path(uri));
+
+        let diagnostics: Vec<Json> = document
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                json!({
+                    "range": document.span_to_lsp(&diagnostic.span),
+                    "severity": DIAGNOSTIC_SEVERITY_ERROR,
+                    "source": "koto",
+                    "message": diagnostic.message,
+                })
+            })
+            .collect();
+        self.connection.send_notification(
+            "textDocument/publishDiagnostics",
+            json!({"uri": uri, "diagnostics": diagnostics}),
+        );
+
+        self.documents.insert(uri.into(), document);
+    }
+
+    // Returns the document and position referred to by a request's parameters
+    fn document_position<'a>(
+        &'a self,
+        params: &'a Json,
+    ) -> Option<(&'a str, &'a Document, Position)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let document = self.documents.get(uri)?;
+        let position = document.position_from_lsp(&params["position"]);
+        Some((uri, document, position))
+    }
+
+    fn definition(&self, params: &Json) -> Option<Json> {
+        let (uri, document, position) = self.document_position(params)?;
+        let reference = document.reference_at(position)?;
+
+        match &reference.member_of {
+            None => {
+                let definition = document.find_definition(&reference.name, position)?;
+                // Imported items are resolved to the definition of their export in the module
+                self.find_import(document, definition)
+                    .or_else(|| Some(location(uri, document, &definition.span)))
+            }
+            // e.g. `foo.bar`, where `foo` is an imported module
+            Some(chain) if chain.len() == 1 => {
+                let root = document.find_definition(&chain[0], position)?;
+                let import = root.import.as_ref()?;
+                if import.item.is_some() {
+                    return None;
+                }
+                self.find_export(document, &import.module[0], &reference.name)
+            }
+            Some(_) => None,
+        }
+    }
+
+    // Finds the export in a module that corresponds to an imported definition
+    fn find_import(&self, document: &Document, definition: &Definition) -> Option<Json> {
+        let import = definition.import.as_ref()?;
+        let module_name = import.module.first()?;
+        // In nested imports like `from foo.bar import baz` the module's `bar` export is used
+        let export_name = import.module.get(1).or(import.item.as_ref());
+
+        match export_name {
+            Some(export_name) => self.find_export(document, module_name, export_name),
+            None => {
+                let path = find_module(document, module_name)?;
+                let start = json!({"line": 0, "character": 0});
+                Some(json!({
+                    "uri": path_to_uri(&path),
+                    "range": {"start": start, "end": start},
+                }))
+            }
+        }
+    }
+
+    // Finds an export in the module with the given name, relative to the document
+    fn find_export(&self, document: &Document, module_name: &str, name: &str) -> Option<Json> {
+        let path = find_module(document, module_name)?;
+        let uri = path_to_uri(&path);
+
+        // The client's version of the module is used if it's open
+        match self.documents.get(&uri) {
+            Some(module) => {
+                let span = module.find_export(name)?;
+                Some(location(&uri, module, &span))
+            }
+            None => {
+                let source = fs::read_to_string(&path).ok()?;
+                let module = Document::new(source, Some(path));
+                let span = module.find_export(name)?;
+                Some(location(&uri, &module, &span))
+            }
+        }
+    }
+
+    fn hover(&self, params: &Json) -> Option<Json> {
+        let (_, document, position) = self.document_position(params)?;
+        let reference = document.reference_at(position)?;
+
+        let contents = match &reference.member_of {
+            None => match document.find_definition(&reference.name, position) {
+                Some(definition) => {
+                    let core_lib_help = definition.import.as_ref().and_then(|import| {
+                        let module = self.help.module(import.module.first()?)?;
+                        match &import.item {
+                            Some(item) => self.help.get(&format!("{}.{item}", module.name)),
+                            None => self.help.get(&module.name),
+                        }
+                    });
+                    match core_lib_help {
+                        Some(help) => help.to_string(),
+                        // Local values are shown with the line where they're defined
+                        None => format!(
+                            "```koto\n{}\n```",
+                            document.line(definition.span.start.line).trim()
+                        ),
+                    }
+                }
+                None => {
+                    let key = match self.help.prelude_function(&reference.name) {
+                        Some(key) => key,
+                        None => &reference.name,
+                    };
+                    self.help.get(key)?.to_string()
+                }
+            },
+            Some(chain) if chain.len() == 1 => {
+                let module = self.core_module(document, &chain[0], position)?;
+                self.help
+                    .get(&format!("{module}.{}", reference.name))?
+                    .to_string()
+            }
+            Some(_) => return None,
+        };
+
+        Some(json!({
+            "contents": {"kind": "markdown", "value": contents},
+            "range": document.span_to_lsp(&reference.span),
+        }))
+    }
+
+    fn completion(&self, params: &Json) -> Json {
+        let Some((_, document, position)) = self.document_position(params) else {
+            return json!([]);
+        };
+
+        let items: Vec<Json> = match document.completion_chain_at(position) {
+            Some(chain) => match chain.as_slice() {
+                [module_name] => match self.core_module(document, module_name, position) {
+                    Some(module_name) => self
+                        .help
+                        .module(module_name)
+                        .map(|module| {
+                            module
+                                .members
+                                .iter()
+                                .map(|member| {
+                                    self.core_lib_completion(
+                                        member,
+                                        &format!("{module_name}.{member}"),
+                                        COMPLETION_KIND_FUNCTION,
+                                    )
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    None => Vec::new(),
+                },
+                _ => Vec::new(),
+            },
+            None => {
+                let definitions = document.definitions_at(position);
+                let locals = definitions.iter().map(|definition| {
+                    json!({
+                        "label": definition.name,
+                        "kind": COMPLETION_KIND_VARIABLE,
+                    })
+                });
+                let modules = self
+                    .help
+                    .modules()
+                    .iter()
+                    .filter(|module| !definitions.iter().any(|local| local.name == module.name))
+                    .map(|module| {
+                        self.core_lib_completion(&module.name, &module.name, COMPLETION_KIND_MODULE)
+                    });
+                let mut prelude_functions: Vec<_> = self.help.prelude_functions().collect();
+                prelude_functions.sort();
+                let prelude_functions = prelude_functions
+                    .into_iter()
+                    .filter(|name| !definitions.iter().any(|local| local.name == *name))
+                    .map(|name| {
+                        let key = self.help.prelude_function(name).unwrap_or(name);
+                        self.core_lib_completion(name, key, COMPLETION_KIND_FUNCTION)
+                    });
+
+                locals.chain(modules).chain(prelude_functions).collect()
+            }
+        };
+
+        json!(items)
+    }
+
+    fn core_lib_completion(&self, label: &str, help_key: &str, kind: u32) -> Json {
+        let mut item = json!({"label": label, "kind": kind});
+        if let Some(signature) = self.help.signature(help_key) {
+            item["detail"] = signature.into();
+        }
+        if let Some(help) = self.help.get(help_key) {
+            item["documentation"] = json!({"kind": "markdown", "value": help});
+        }
+        item
+    }
+
+    // Returns the name of the core library module that an id refers to at the given position
+    //
+    // Core library modules are available by name unless they've been shadowed by a local value,
+    // and can also be imported, e.g. `import list`.
+    fn core_module<'a>(
+        &'a self,
+        document: &Document,
+        name: &'a str,
+        position: Position,
+    ) -> Option<&'a str> {
+        let module_name = match document.find_definition(name, position) {
+            Some(definition) => match &definition.import {
+                Some(import) if import.item.is_none() => import.module.first()?.as_str(),
+                _ => return None,
+            },
+            None => name,
+        };
+
+        self.help
+            .module(module_name)
+            .map(|module| module.name.as_str())
+    }
+}
+
+// Finds a module that's imported by the document
+fn find_module(document: &Document, module_name: &str) -> Option<PathBuf> {
+    // The document's directory is used rather than its path,
+    // given that the document might not have been saved yet.
+    let search_dir = document
+        .path
+        .as_ref()
+        .and_then(|path| path.parent())
+        .map(PathBuf::from);
+    Loader::default().find_module(module_name, search_dir).ok()
+}
+
+fn location(uri: &str, document: &Document, span: &Span) -> Json {
+    json!({"uri": uri, "range": document.span_to_lsp(span)})
+}
+
+// Converts a `file://` URI into a path, percent-encoded characters are decoded
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut input = path.bytes();
+    while let Some(byte) = input.next() {
+        if byte == b'%' {
+            let hex: Vec<u8> = input.by_ref().take(2).collect();
+            let decoded = std::str::from_utf8(&hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())?;
+            bytes.push(decoded);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    let path = String::from_utf8(bytes).ok()?;
+
+    // Windows paths are provided as e.g. `file:///C:/foo`
+    let path = match path.strip_prefix('/') {
+        Some(stripped) if stripped.chars().nth(1) == Some(':') => stripped.to_string(),
+        _ => path,
+    };
+
+    Some(path.into())
+}
+
+// Converts a path into a `file://` URI, see uri_to_path()
+fn path_to_uri(path: &std::path::Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut result = String::from("file://");
+    if !path.starts_with('/') {
+        result.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                result.push(byte as char)
+            }
+            _ => result.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    result
+}
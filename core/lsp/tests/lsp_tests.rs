@@ -0,0 +1,332 @@
+use serde_json::{json, Value as Json};
+use std::{
+    env, fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+// Runs the server with the given messages, returning the messages that the server sent back
+//
+// The session is started with an `initialize` request, and ended with `shutdown` and `exit`.
+fn run_session(messages: &[Json]) -> Vec<Json> {
+    let mut process = Command::new(env!("CARGO_BIN_EXE_koto_lsp"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to execute child");
+
+    let mut input = Vec::new();
+    let session = [json!({"id": 0, "method": "initialize", "params": {}})]
+        .into_iter()
+        .chain(messages.iter().cloned())
+        .chain([
+            json!({"id": 1000, "method": "shutdown"}),
+            json!({"method": "exit"}),
+        ]);
+    for message in session {
+        let mut message = message;
+        message["jsonrpc"] = "2.0".into();
+        let content = message.to_string();
+        write!(input, "Content-Length: {}\r\n\r\n{content}", content.len()).unwrap();
+    }
+    process
+        .stdin
+        .as_mut()
+        .expect("failed to get stdin")
+        .write_all(&input)
+        .expect("Failed to write to stdin");
+
+    let output = process.wait_with_output().expect("Failed to get output");
+    assert!(output.status.success());
+    let mut output = String::from_utf8(output.stdout).expect("Failed to get output");
+
+    let mut result = Vec::new();
+    while let Some(header_end) = output.find("\r\n\r\n") {
+        let length: usize = output[..header_end]
+            .trim_start_matches("Content-Length: ")
+            .parse()
+            .unwrap();
+        let content_start = header_end + 4;
+        result.push(serde_json::from_str(&output[content_start..content_start + length]).unwrap());
+        output.drain(..content_start + length);
+    }
+    result
+}
+
+fn response(messages: &[Json], id: u64) -> &Json {
+    let message = messages
+        .iter()
+        .find(|message| message["id"] == id)
+        .expect("Missing response");
+    &message["result"]
+}
+
+fn diagnostics<'a>(messages: &'a [Json], uri: &str) -> Vec<&'a Json> {
+    messages
+        .iter()
+        .filter(|message| {
+            message["method"] == "textDocument/publishDiagnostics"
+                && message["params"]["uri"] == uri
+        })
+        .map(|message| &message["params"]["diagnostics"])
+        .collect()
+}
+
+fn open(uri: &str, text: &str) -> Json {
+    json!({
+        "method": "textDocument/didOpen",
+        "params": {"textDocument": {"uri": uri, "languageId": "koto", "version": 1, "text": text}},
+    })
+}
+
+fn request(id: u64, method: &str, uri: &str, line: u32, character: u32) -> Json {
+    json!({
+        "id": id,
+        "method": method,
+        "params": {
+            "textDocument": {"uri": uri},
+            "position": {"line": line, "character": character},
+        },
+    })
+}
+
+fn range(start: (u32, u32), end: (u32, u32)) -> Json {
+    json!({
+        "start": {"line": start.0, "character": start.1},
+        "end": {"line": end.0, "character": end.1},
+    })
+}
+
+struct TestDir(PathBuf);
+
+impl TestDir {
+    fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("{name}_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        Self(dunce::canonicalize(dir).unwrap())
+    }
+
+    fn uri(&self, file_name: &str) -> String {
+        format!("file://{}", self.0.join(file_name).display())
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+mod lsp {
+    use super::*;
+
+    #[test]
+    fn initialize() {
+        let messages = run_session(&[]);
+        let capabilities = &response(&messages, 0)["capabilities"];
+
+        assert_eq!(capabilities["definitionProvider"], true);
+        assert_eq!(capabilities["hoverProvider"], true);
+        assert_eq!(response(&messages, 1000), &Json::Null);
+    }
+
+    #[test]
+    fn diagnostics_for_parser_errors() {
+        let uri = "file:///test.koto";
+        let script = "\
+x = 1 +
+y = 2
+z = * 3
+";
+        let messages = run_session(&[open(uri, script)]);
+
+        let diagnostics = diagnostics(&messages, uri);
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostics = diagnostics[0].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0]["message"],
+            "Expected expression after binary operator"
+        );
+        assert_eq!(diagnostics[1]["range"], range((2, 4), (2, 5)));
+    }
+
+    #[test]
+    fn diagnostics_for_compiler_errors() {
+        let uri = "file:///test.koto";
+        let messages = run_session(&[open(uri, "x = 1\nbreak\n")]);
+
+        let diagnostics = diagnostics(&messages, uri)[0].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+    }
+
+    #[test]
+    fn diagnostics_are_updated_when_the_document_changes() {
+        let uri = "file:///test.koto";
+        let messages = run_session(&[
+            open(uri, "x = ("),
+            json!({
+                "method": "textDocument/didChange",
+                "params": {
+                    "textDocument": {"uri": uri, "version": 2},
+                    "contentChanges": [{"text": "x = (1)"}],
+                },
+            }),
+        ]);
+
+        let diagnostics = diagnostics(&messages, uri);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].as_array().unwrap().len(), 1);
+        assert!(diagnostics[1].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn go_to_definition_of_locals() {
+        let uri = "file:///test.koto";
+        let script = "\
+x = 1
+f = |x|
+  y = x * 2
+  y + x
+z = f x
+";
+        let messages = run_session(&[
+            open(uri, script),
+            // The function's `x` argument
+            request(1, "textDocument/definition", uri, 3, 6),
+            // The top-level `x`
+            request(2, "textDocument/definition", uri, 4, 6),
+            // `f`
+            request(3, "textDocument/definition", uri, 4, 4),
+            // `y`
+            request(4, "textDocument/definition", uri, 3, 2),
+        ]);
+
+        assert_eq!(response(&messages, 1)["range"], range((1, 5), (1, 6)));
+        assert_eq!(response(&messages, 2)["range"], range((0, 0), (0, 1)));
+        assert_eq!(response(&messages, 3)["range"], range((1, 0), (1, 1)));
+        assert_eq!(response(&messages, 4)["range"], range((2, 2), (2, 3)));
+    }
+
+    #[test]
+    fn go_to_definition_of_imported_module_exports() {
+        let dir = TestDir::new("koto_lsp_definition");
+        let module = "\
+a = 1
+export foo = |x| x + a
+export
+  bar: 99
+";
+        fs::write(dir.0.join("my_module.koto"), module).unwrap();
+        let uri = dir.uri("main.koto");
+        let script = "\
+from my_module import foo
+import my_module
+foo my_module.bar
+";
+        let messages = run_session(&[
+            open(&uri, script),
+            // `foo`
+            request(1, "textDocument/definition", &uri, 2, 1),
+            // `bar`
+            request(2, "textDocument/definition", &uri, 2, 15),
+        ]);
+
+        let module_uri = dir.uri("my_module.koto");
+        assert_eq!(response(&messages, 1)["uri"], module_uri.as_str());
+        assert_eq!(response(&messages, 1)["range"], range((1, 7), (1, 10)));
+        assert_eq!(response(&messages, 2)["uri"], module_uri.as_str());
+        assert_eq!(response(&messages, 2)["range"], range((3, 2), (3, 5)));
+    }
+
+    #[test]
+    fn hover_over_core_library_items() {
+        let uri = "file:///test.koto";
+        let script = "\
+from list import first
+x = list.last [1, 2]
+print first [x]
+";
+        let messages = run_session(&[
+            open(uri, script),
+            request(1, "textDocument/hover", uri, 1, 10),
+            request(2, "textDocument/hover", uri, 2, 7),
+            request(3, "textDocument/hover", uri, 2, 1),
+            request(4, "textDocument/hover", uri, 1, 5),
+            request(5, "textDocument/hover", uri, 1, 0),
+        ]);
+
+        let hover_text = |id| {
+            response(&messages, id)["contents"]["value"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+        assert!(hover_text(1).starts_with("## list.last\n"));
+        assert!(hover_text(2).starts_with("## list.first\n"));
+        assert!(hover_text(3).starts_with("## io.print\n"));
+        assert!(hover_text(4).starts_with("# list"));
+        assert_eq!(hover_text(5), "```koto\nx = list.last [1, 2]\n```");
+        assert_eq!(response(&messages, 1)["range"], range((1, 9), (1, 13)));
+    }
+
+    #[test]
+    fn completion_of_core_library_module_members() {
+        let uri = "file:///test.koto";
+        let script = "\
+x = string.
+y = tuple.fi
+";
+        let messages = run_session(&[
+            open(uri, script),
+            request(1, "textDocument/completion", uri, 0, 11),
+            request(2, "textDocument/completion", uri, 1, 12),
+        ]);
+
+        let labels = |id| -> Vec<String> {
+            response(&messages, id)
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|item| item["label"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert!(labels(1).contains(&"trim".to_string()));
+        assert!(labels(1).contains(&"to_uppercase".to_string()));
+        assert!(labels(2).contains(&"first".to_string()));
+
+        let first = response(&messages, 2)
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|item| item["label"] == "first")
+            .unwrap();
+        assert_eq!(first["detail"], "|Tuple| -> Value");
+    }
+
+    #[test]
+    fn completion_of_values_in_scope() {
+        let uri = "file:///test.koto";
+        let script = "\
+foo = 42
+f = |bar|
+  x = b
+";
+        let messages = run_session(&[
+            open(uri, script),
+            request(1, "textDocument/completion", uri, 2, 7),
+        ]);
+
+        let labels: Vec<&str> = response(&messages, 1)
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect();
+        assert_eq!(&labels[..4], &["bar", "x", "foo", "f"]);
+        assert!(labels.contains(&"string"));
+        assert!(labels.contains(&"print"));
+    }
+}
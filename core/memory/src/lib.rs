@@ -1,6 +1,7 @@
 //! Memory management utilities for Koto
 //!
-//! Currently, only reference-counted pointers without cycle detection are implemented.
+//! Currently, only reference-counted pointers are implemented, with an optional cycle collector
+//! (see [cycle_collector]) that can reclaim unreachable cycles of tracked values.
 //! The intent is that this crate can be expanded in the future with implementations of
 //! `Ptr` and `PtrMut` that offer alternative memory management strategies.
 //!
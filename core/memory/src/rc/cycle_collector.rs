@@ -0,0 +1,474 @@
+//! A cycle collector for values allocated via [Ptr] and [PtrMut]
+//!
+//! Reference counting alone can't reclaim values that refer to each other in a cycle, e.g. a map
+//! that contains itself. When tracking is enabled (see [enable_tracking]), pointers that are
+//! registered with the collector are held as weak references, and [collect_cycles] looks for
+//! groups of tracked values that are only referenced by each other using trial deletion:
+//!
+//! 1. For each tracked value, the references held by other tracked values are subtracted from the
+//!    value's strong count.
+//! 2. Values with a remaining count are referenced from outside of the tracked graph (e.g. from a
+//!    VM's registers), so they're marked as live along with all values that are reachable from
+//!    them.
+//! 3. Any unmarked values are unreachable, and are cleared, breaking their cycles.
+//!
+//! Tracked values that can't be inspected (e.g. because they're currently mutably borrowed) are
+//! treated as live, so the collector is always conservative.
+
+use super::{Address, Ptr, PtrMut};
+use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap},
+    rc::{Rc, Weak},
+};
+
+/// Statistics returned from a call to [collect_cycles]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CollectionStats {
+    /// The number of live values that were being tracked when the collection started
+    pub tracked: usize,
+    /// The number of values that were found to be in unreachable cycles and were reclaimed
+    pub reclaimed: usize,
+}
+
+/// Provided to trace functions, used to report the pointers that a value holds
+///
+/// See [track_ptr] and [track_ptr_mut].
+#[derive(Default)]
+pub struct Tracer {
+    children: Vec<Address>,
+}
+
+impl Tracer {
+    /// Reports that the traced value holds the given [Ptr]
+    pub fn visit_ptr<T: ?Sized>(&mut self, ptr: &Ptr<T>) {
+        self.children.push(Ptr::address(ptr));
+    }
+
+    /// Reports that the traced value holds the given [PtrMut]
+    pub fn visit_ptr_mut<T: ?Sized>(&mut self, ptr: &PtrMut<T>) {
+        self.children.push(PtrMut::address(ptr));
+    }
+}
+
+/// Keeps cycle tracking enabled for the current thread while it's alive
+///
+/// See [enable_tracking].
+pub struct TrackingGuard {
+    // The guard is tied to the thread's collector, so it shouldn't be sent to other threads
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl Drop for TrackingGuard {
+    fn drop(&mut self) {
+        COLLECTOR.with(|collector| {
+            let mut collector = collector.borrow_mut();
+            collector.enabled_count -= 1;
+            if collector.enabled_count == 0 {
+                collector.nodes.clear();
+            }
+        });
+    }
+}
+
+/// Enables the tracking of pointers for the current thread
+///
+/// Tracking remains enabled until all of the returned guards have been dropped, at which point
+/// any tracked pointers are forgotten.
+pub fn enable_tracking() -> TrackingGuard {
+    COLLECTOR.with(|collector| collector.borrow_mut().enabled_count += 1);
+    TrackingGuard {
+        _not_send: Default::default(),
+    }
+}
+
+/// Returns true if tracking is enabled for the current thread
+pub fn is_tracking() -> bool {
+    COLLECTOR.with(|collector| collector.borrow().enabled_count > 0)
+}
+
+/// Registers an immutable pointer with the collector
+///
+/// The `trace` function should report each [Ptr] or [PtrMut] that the value holds.
+///
+/// Immutable values can't be cleared, so cycles that pass through them are broken by clearing the
+/// mutable values that are also in the cycle.
+pub fn track_ptr<T: ?Sized + 'static>(ptr: &Ptr<T>, trace: fn(&T, &mut Tracer)) {
+    register(|| {
+        Box::new(TrackedPtr {
+            weak: Rc::downgrade(&ptr.0),
+            trace,
+        })
+    });
+}
+
+/// Registers a mutable pointer with the collector
+///
+/// The `trace` function should report each [Ptr] or [PtrMut] that the value holds,
+/// and `clear` should drop the value's contents when it's found to be unreachable.
+pub fn track_ptr_mut<T: ?Sized + 'static>(
+    ptr: &PtrMut<T>,
+    trace: fn(&T, &mut Tracer),
+    clear: fn(&mut T),
+) {
+    register(|| {
+        Box::new(TrackedPtrMut {
+            weak: Rc::downgrade(&ptr.0),
+            trace,
+            clear,
+        })
+    });
+}
+
+/// Finds and reclaims unreachable cycles of tracked values in the current thread
+pub fn collect_cycles() -> CollectionStats {
+    // The nodes are taken out of the collector while the collection is running,
+    // which allows clear functions to allocate new tracked values.
+    let tracked = COLLECTOR.with(|collector| std::mem::take(&mut collector.borrow_mut().nodes));
+
+    let mut live_nodes = Vec::with_capacity(tracked.len());
+    let mut node_indices = HashMap::with_capacity(tracked.len());
+    for node in tracked.iter() {
+        if let Some(live_node) = node.upgrade() {
+            let address = live_node.address();
+            // Pointers could be registered more than once, only the first entry is needed
+            if let Entry::Vacant(entry) = node_indices.entry(address) {
+                entry.insert(live_nodes.len());
+                live_nodes.push(live_node);
+            }
+        }
+    }
+
+    // Subtract internal references from each node's strong count.
+    // The strong reference taken while upgrading is excluded from the count.
+    let mut counts: Vec<usize> = live_nodes.iter().map(|n| n.strong_count() - 1).collect();
+    let mut children = Vec::with_capacity(live_nodes.len());
+    let mut tracer = Tracer::default();
+    for node in live_nodes.iter() {
+        if node.trace(&mut tracer) {
+            let node_children = tracer
+                .children
+                .drain(..)
+                .filter_map(|address| node_indices.get(&address).copied())
+                .collect::<Vec<_>>();
+            for child in node_children.iter() {
+                counts[*child] = counts[*child].saturating_sub(1);
+            }
+            children.push(Some(node_children));
+        } else {
+            children.push(None);
+        }
+    }
+
+    // Nodes that are referenced externally, or that couldn't be traced, are roots.
+    // Everything that's reachable from the roots is live.
+    let mut live = vec![false; live_nodes.len()];
+    let mut to_visit = (0..live_nodes.len())
+        .filter(|&i| counts[i] > 0 || children[i].is_none())
+        .collect::<Vec<_>>();
+    while let Some(i) = to_visit.pop() {
+        if !live[i] {
+            live[i] = true;
+            if let Some(node_children) = &children[i] {
+                to_visit.extend(node_children.iter().filter(|&&child| !live[child]));
+            }
+        }
+    }
+
+    let garbage = live
+        .iter()
+        .enumerate()
+        .filter_map(|(i, is_live)| (!is_live).then_some(i))
+        .collect::<Vec<_>>();
+    for i in garbage.iter() {
+        live_nodes[*i].clear();
+    }
+    let garbage_nodes = garbage
+        .iter()
+        .map(|i| live_nodes[*i].downgrade())
+        .collect::<Vec<_>>();
+
+    let stats_tracked = live_nodes.len();
+    // Drop the strong references to the nodes, allowing the cleared cycles to be freed
+    drop(live_nodes);
+    let reclaimed = garbage_nodes
+        .iter()
+        .filter(|node| node.upgrade().is_none())
+        .count();
+
+    // Return the remaining live nodes to the collector, along with any nodes that were registered
+    // during the collection.
+    COLLECTOR.with(|collector| {
+        let mut collector = collector.borrow_mut();
+        let mut nodes = tracked;
+        nodes.retain(|node| node.is_alive());
+        nodes.append(&mut collector.nodes);
+        collector.prune_threshold = (nodes.len() * 2).max(MIN_PRUNE_THRESHOLD);
+        collector.nodes = nodes;
+    });
+
+    CollectionStats {
+        tracked: stats_tracked,
+        reclaimed,
+    }
+}
+
+fn register(make_node: impl FnOnce() -> Box<dyn TrackedNode>) {
+    COLLECTOR.with(|collector| {
+        let mut collector = collector.borrow_mut();
+        if collector.enabled_count == 0 {
+            return;
+        }
+
+        // Dropped values leave dead weak references behind,
+        // so the node list is pruned when it grows beyond the threshold.
+        if collector.nodes.len() >= collector.prune_threshold {
+            collector.nodes.retain(|node| node.is_alive());
+            collector.prune_threshold = (collector.nodes.len() * 2).max(MIN_PRUNE_THRESHOLD);
+        }
+
+        collector.nodes.push(make_node());
+    });
+}
+
+const MIN_PRUNE_THRESHOLD: usize = 1024;
+
+struct Collector {
+    // The number of active TrackingGuards
+    enabled_count: usize,
+    // The tracked nodes
+    nodes: Vec<Box<dyn TrackedNode>>,
+    // The node count that triggers pruning of dead nodes
+    prune_threshold: usize,
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self {
+            enabled_count: 0,
+            nodes: Vec::new(),
+            prune_threshold: MIN_PRUNE_THRESHOLD,
+        }
+    }
+}
+
+thread_local! {
+    static COLLECTOR: RefCell<Collector> = RefCell::default();
+}
+
+// A weakly held node in the collector's graph
+trait TrackedNode {
+    fn is_alive(&self) -> bool;
+    fn upgrade(&self) -> Option<Box<dyn LiveNode>>;
+}
+
+// A strongly held node, used while a collection is running
+trait LiveNode {
+    fn address(&self) -> Address;
+    fn strong_count(&self) -> usize;
+    // Returns false if the node's value isn't available for tracing
+    fn trace(&self, tracer: &mut Tracer) -> bool;
+    fn clear(&self);
+    fn downgrade(&self) -> Box<dyn TrackedNode>;
+}
+
+struct TrackedPtr<T: ?Sized> {
+    weak: Weak<T>,
+    trace: fn(&T, &mut Tracer),
+}
+
+impl<T: ?Sized + 'static> TrackedNode for TrackedPtr<T> {
+    fn is_alive(&self) -> bool {
+        self.weak.strong_count() > 0
+    }
+
+    fn upgrade(&self) -> Option<Box<dyn LiveNode>> {
+        self.weak.upgrade().map(|ptr| {
+            Box::new(LivePtr {
+                ptr,
+                trace: self.trace,
+            }) as Box<dyn LiveNode>
+        })
+    }
+}
+
+struct LivePtr<T: ?Sized> {
+    ptr: Rc<T>,
+    trace: fn(&T, &mut Tracer),
+}
+
+impl<T: ?Sized + 'static> LiveNode for LivePtr<T> {
+    fn address(&self) -> Address {
+        Rc::as_ptr(&self.ptr).into()
+    }
+
+    fn strong_count(&self) -> usize {
+        Rc::strong_count(&self.ptr)
+    }
+
+    fn trace(&self, tracer: &mut Tracer) -> bool {
+        (self.trace)(&self.ptr, tracer);
+        true
+    }
+
+    fn clear(&self) {}
+
+    fn downgrade(&self) -> Box<dyn TrackedNode> {
+        Box::new(TrackedPtr {
+            weak: Rc::downgrade(&self.ptr),
+            trace: self.trace,
+        })
+    }
+}
+
+struct TrackedPtrMut<T: ?Sized> {
+    weak: Weak<RefCell<T>>,
+    trace: fn(&T, &mut Tracer),
+    clear: fn(&mut T),
+}
+
+impl<T: ?Sized + 'static> TrackedNode for TrackedPtrMut<T> {
+    fn is_alive(&self) -> bool {
+        self.weak.strong_count() > 0
+    }
+
+    fn upgrade(&self) -> Option<Box<dyn LiveNode>> {
+        self.weak.upgrade().map(|ptr| {
+            Box::new(LivePtrMut {
+                ptr,
+                trace: self.trace,
+                clear: self.clear,
+            }) as Box<dyn LiveNode>
+        })
+    }
+}
+
+struct LivePtrMut<T: ?Sized> {
+    ptr: Rc<RefCell<T>>,
+    trace: fn(&T, &mut Tracer),
+    clear: fn(&mut T),
+}
+
+impl<T: ?Sized + 'static> LiveNode for LivePtrMut<T> {
+    fn address(&self) -> Address {
+        Rc::as_ptr(&self.ptr).into()
+    }
+
+    fn strong_count(&self) -> usize {
+        Rc::strong_count(&self.ptr)
+    }
+
+    fn trace(&self, tracer: &mut Tracer) -> bool {
+        match self.ptr.try_borrow() {
+            Ok(value) => {
+                (self.trace)(&value, tracer);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut value) = self.ptr.try_borrow_mut() {
+            (self.clear)(&mut value);
+        }
+    }
+
+    fn downgrade(&self) -> Box<dyn TrackedNode> {
+        Box::new(TrackedPtrMut {
+            weak: Rc::downgrade(&self.ptr),
+            trace: self.trace,
+            clear: self.clear,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Node {
+        children: Vec<PtrMut<Node>>,
+    }
+
+    fn make_node() -> PtrMut<Node> {
+        let node = PtrMut::new(Node::default());
+        track_ptr_mut(
+            &node,
+            |node, tracer| {
+                for child in node.children.iter() {
+                    tracer.visit_ptr_mut(child);
+                }
+            },
+            |node| node.children.clear(),
+        );
+        node
+    }
+
+    #[test]
+    fn self_reference_is_reclaimed() {
+        let _guard = enable_tracking();
+
+        let a = make_node();
+        a.borrow_mut().children.push(a.clone());
+        let weak_a = Rc::downgrade(&a.0);
+        drop(a);
+
+        let stats = collect_cycles();
+        assert_eq!(stats.reclaimed, 1);
+        assert!(weak_a.upgrade().is_none());
+    }
+
+    #[test]
+    fn externally_referenced_cycle_is_retained() {
+        let _guard = enable_tracking();
+
+        let a = make_node();
+        let b = make_node();
+        a.borrow_mut().children.push(b.clone());
+        b.borrow_mut().children.push(a.clone());
+        let weak_b = Rc::downgrade(&b.0);
+        drop(b);
+
+        let stats = collect_cycles();
+        assert_eq!(stats.tracked, 2);
+        assert_eq!(stats.reclaimed, 0);
+        assert_eq!(a.borrow().children.len(), 1);
+
+        drop(a);
+        let stats = collect_cycles();
+        assert_eq!(stats.reclaimed, 2);
+        assert!(weak_b.upgrade().is_none());
+    }
+
+    #[test]
+    fn values_reachable_from_roots_are_retained() {
+        let _guard = enable_tracking();
+
+        // root -> a <-> b
+        let root = make_node();
+        let a = make_node();
+        let b = make_node();
+        a.borrow_mut().children.push(b.clone());
+        b.borrow_mut().children.push(a.clone());
+        root.borrow_mut().children.push(a);
+        drop(b);
+
+        let stats = collect_cycles();
+        assert_eq!(stats.reclaimed, 0);
+        let a = root.borrow().children[0].clone();
+        assert_eq!(a.borrow().children.len(), 1);
+    }
+
+    #[test]
+    fn nothing_is_tracked_when_disabled() {
+        let a = make_node();
+        a.borrow_mut().children.push(a.clone());
+
+        let stats = collect_cycles();
+        assert_eq!(stats, CollectionStats::default());
+
+        a.borrow_mut().children.clear();
+    }
+}
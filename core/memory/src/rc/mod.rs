@@ -1,5 +1,6 @@
 //! Owned pointers that wrap the standard Rc and RefCell types
 
+pub mod cycle_collector;
 mod ptr;
 mod ptr_mut;
 
@@ -7,7 +8,7 @@ pub use ptr::*;
 pub use ptr_mut::*;
 
 /// A wrapper for comparing pointer addresses
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Address(*const u8);
 
 impl<T: ?Sized> From<*const T> for Address {
@@ -10,7 +10,7 @@ use super::Address;
 
 /// An immutable pointer to a value in allocated memory
 #[derive(Debug, Default)]
-pub struct Ptr<T: ?Sized>(Rc<T>);
+pub struct Ptr<T: ?Sized>(pub(super) Rc<T>);
 
 impl<T> Ptr<T> {
     /// Moves the value into newly allocated memory
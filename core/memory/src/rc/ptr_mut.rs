@@ -9,7 +9,7 @@ use super::Address;
 
 /// A mutable pointer to a value in allocated memory
 #[derive(Debug, Default)]
-pub struct PtrMut<T: ?Sized>(Rc<RefCell<T>>);
+pub struct PtrMut<T: ?Sized>(pub(super) Rc<RefCell<T>>);
 
 impl<T> PtrMut<T> {
     /// Moves the value into newly allocated memory
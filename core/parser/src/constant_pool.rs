@@ -125,6 +125,27 @@ impl ConstantPool {
     pub fn iter(&self) -> ConstantPoolIterator {
         ConstantPoolIterator::new(self)
     }
+
+    /// Builds a pool from a sequence of constants
+    ///
+    /// Duplicate constants are merged, so the constants from a pool that was prepared by the
+    /// parser will produce an identical pool, e.g. when loading a previously serialized pool.
+    ///
+    /// None is returned if there are more constants than the pool can support.
+    pub fn from_constants<'a>(constants: impl IntoIterator<Item = Constant<'a>>) -> Option<Self> {
+        let mut builder = ConstantPoolBuilder::default();
+
+        for constant in constants {
+            let result = match constant {
+                Constant::F64(n) => builder.add_f64(n),
+                Constant::I64(n) => builder.add_i64(n),
+                Constant::Str(s) => builder.add_string(s),
+            };
+            result.ok()?;
+        }
+
+        Some(builder.build())
+    }
 }
 
 /// An iterator that iterates over a [ConstantPool]'s constants
@@ -345,6 +366,23 @@ mod tests {
         assert_eq!(4, pool.size());
     }
 
+    #[test]
+    fn test_from_constants() {
+        let mut builder = ConstantPoolBuilder::default();
+
+        builder.add_i64(42).unwrap();
+        builder.add_string("O_o").unwrap();
+        builder.add_f64(-1.5).unwrap();
+
+        let pool = builder.build();
+        let rebuilt = ConstantPool::from_constants(pool.iter()).unwrap();
+
+        assert_eq!(pool, rebuilt);
+        assert_eq!(rebuilt.get_i64(0), 42);
+        assert_eq!(rebuilt.get_str(1), "O_o");
+        assert!(floats_are_equal(rebuilt.get_f64(2), -1.5));
+    }
+
     #[test]
     fn test_iter() {
         let mut builder = ConstantPoolBuilder::default();
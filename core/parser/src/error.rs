@@ -121,6 +121,10 @@ pub enum SyntaxError {
     ExpectedMapKey,
     #[error("Expected value after ':' in Map")]
     ExpectedMapValue,
+    #[error("Expected id, wildcard, or map pattern after ':' in map pattern")]
+    ExpectedMapPatternTarget,
+    #[error("Expected default value after '=' in map pattern")]
+    ExpectedMapPatternDefault,
     #[error("Expected expression in match arm")]
     ExpectedMatchArmExpression,
     #[error("Expected expression after then in match arm")]
@@ -145,6 +149,8 @@ pub enum SyntaxError {
     ExpectedTestName,
     #[error("Expected expression after 'then'")]
     ExpectedThenExpression,
+    #[error("Expected type after ':' or '->'")]
+    ExpectedType,
     #[error("Expected condition in until loop")]
     ExpectedUntilCondition,
     #[error("Expected condition in while loop")]
@@ -153,6 +159,10 @@ pub enum SyntaxError {
     IfBlockNotAllowedInThisContext,
     #[error("Too many items listed after 'from' in import expression")]
     ImportFromExpressionHasTooManyItems,
+    #[error("Invalid formatting options in string template")]
+    InvalidStringFormat,
+    #[error("Map pattern keys must be ids or strings without template expressions")]
+    InvalidMapPatternKey,
     #[error("Found an unexpected token while lexing input")]
     LexerError,
     #[error("Ellipsis found outside of nested match patterns")]
@@ -224,16 +234,39 @@ impl ParserError {
 }
 
 /// Renders the excerpt of the source corresponding to the given span
+///
+/// If the source isn't available (e.g. when it has been stripped from precompiled bytecode),
+/// then only the span's position is rendered.
 pub fn format_source_excerpt(source: &str, span: &Span, source_path: &Option<PathBuf>) -> String {
     let Span { start, end } = span;
 
-    let (excerpt, padding) = {
-        let excerpt_lines = source
-            .lines()
-            .skip((start.line - 1) as usize)
-            .take((end.line - start.line + 1) as usize)
-            .collect::<Vec<_>>();
+    let position_info = if let Some(path) = source_path {
+        let display_path = if let Ok(current_dir) = std::env::current_dir() {
+            if let Ok(stripped) = path.strip_prefix(current_dir) {
+                stripped.display()
+            } else {
+                path.display()
+            }
+        } else {
+            path.display()
+        };
+
+        format!("{display_path} - {}:{}", start.line, start.column)
+    } else {
+        format!("{}:{}", start.line, start.column)
+    };
+
+    let excerpt_lines = source
+        .lines()
+        .skip((start.line - 1) as usize)
+        .take((end.line - start.line + 1) as usize)
+        .collect::<Vec<_>>();
 
+    if excerpt_lines.is_empty() {
+        return position_info;
+    }
+
+    let (excerpt, padding) = {
         let line_numbers = (start.line..=end.line)
             .map(|n| n.to_string())
             .collect::<Vec<_>>();
@@ -269,21 +302,5 @@ pub fn format_source_excerpt(source: &str, span: &Span, source_path: &Option<Pat
         }
     };
 
-    let position_info = if let Some(path) = source_path {
-        let display_path = if let Ok(current_dir) = std::env::current_dir() {
-            if let Ok(stripped) = path.strip_prefix(current_dir) {
-                stripped.display()
-            } else {
-                path.display()
-            }
-        } else {
-            path.display()
-        };
-
-        format!("{display_path} - {}:{}", start.line, start.column)
-    } else {
-        format!("{}:{}", start.line, start.column)
-    };
-
     format!("{position_info}\n{padding}|\n{excerpt}")
 }
@@ -13,8 +13,8 @@ pub enum Node {
     /// A single expression wrapped in parentheses
     Nested(AstIndex),
 
-    /// An identifer
-    Id(ConstantIndex),
+    /// An identifer, and optionally the type hint node that follows it
+    Id(ConstantIndex, Option<AstIndex>),
 
     /// A meta identifier, e.g. `@display` or `@test my_test`
     Meta(MetaKeyId, Option<u32>),
@@ -106,6 +106,16 @@ pub enum Node {
     /// Values are optional for inline maps.
     Map(Vec<(MapKey, Option<AstIndex>)>),
 
+    /// A map pattern, used when matching against or destructuring the entries of a map
+    ///
+    /// e.g. `{type: 'click', x, y, rest...}`
+    MapPattern {
+        /// The map entries that are required for the pattern to match
+        entries: Vec<MapPatternEntry>,
+        /// An optional [Ellipsis](Node::Ellipsis) node that captures the map's remaining entries
+        rest: Option<AstIndex>,
+    },
+
     /// The `self` keyword
     Self_,
 
@@ -264,6 +274,11 @@ pub enum Node {
         /// The expression that should be debugged
         expression: AstIndex,
     },
+
+    /// A type hint
+    ///
+    /// e.g. `String` in `x: String = foo()`, or `Number` in `|n: Number| -> Number`
+    Type(ConstantIndex),
 }
 
 impl fmt::Display for Node {
@@ -272,7 +287,7 @@ impl fmt::Display for Node {
         match self {
             Null => write!(f, "Null"),
             Nested(_) => write!(f, "Nested"),
-            Id(_) => write!(f, "Id"),
+            Id(..) => write!(f, "Id"),
             Meta(_, _) => write!(f, "Meta"),
             Lookup(_) => write!(f, "Lookup"),
             BoolTrue => write!(f, "BoolTrue"),
@@ -289,6 +304,7 @@ impl fmt::Display for Node {
             RangeTo { .. } => write!(f, "RangeTo"),
             RangeFull => write!(f, "RangeFull"),
             Map(_) => write!(f, "Map"),
+            MapPattern { .. } => write!(f, "MapPattern"),
             MainBlock { .. } => write!(f, "MainBlock"),
             Block(_) => write!(f, "Block"),
             Function(_) => write!(f, "Function"),
@@ -316,6 +332,7 @@ impl fmt::Display for Node {
             Throw(_) => write!(f, "Throw"),
             Yield { .. } => write!(f, "Yield"),
             Debug { .. } => write!(f, "Debug"),
+            Type(_) => write!(f, "Type"),
         }
     }
 }
@@ -343,6 +360,8 @@ pub struct Function {
     ///
     /// The presence of a `yield` expression in the function body will set this to true.
     pub is_generator: bool,
+    /// The optional type hint for the function's output, e.g. `|x| -> Number`
+    pub output_type: Option<AstIndex>,
 }
 
 /// A string definition
@@ -364,6 +383,71 @@ pub enum StringNode {
     Literal(u32),
     /// An expression that should be evaluated and inserted into the string
     Expr(AstIndex),
+    /// An expression with formatting options, e.g. `'${x:>8.2}'`
+    FormattedExpr(AstIndex, StringFormatOptions),
+}
+
+/// Formatting options for an expression in a string template
+///
+/// The options follow the same rules as the format strings used in `string.format`,
+/// e.g. `'${x:_>8.2}'` places `x` right-aligned in 8 characters with a precision of 2,
+/// using `_` to fill the remaining space.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StringFormatOptions {
+    /// The alignment of the formatted value
+    pub alignment: StringAlignment,
+    /// The minimum width of the formatted value
+    pub min_width: Option<u32>,
+    /// The number of decimal places for numbers, or the maximum width for other values
+    pub precision: Option<u32>,
+    /// The character used to fill the formatted value up to the minimum width
+    ///
+    /// The character is stored as a string constant.
+    pub fill_character: Option<ConstantIndex>,
+    /// True if positive numbers should be shown with a `+` sign
+    pub plus_sign: bool,
+    /// True if numbers should use their alternate form, e.g. `0x` prefixes for hex numbers
+    pub alternate_form: bool,
+    /// True if numbers should be padded with zeros up to the minimum width
+    pub zero_padding: bool,
+    /// The separator used for grouping digits, either `,` or `_`
+    pub grouping: Option<char>,
+    /// The representation used for numbers
+    pub representation: StringRepresentation,
+}
+
+/// The alignment used for formatted values, see [StringFormatOptions]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StringAlignment {
+    /// Numbers are right-aligned, while other values are left-aligned
+    #[default]
+    Default,
+    /// `<`
+    Left,
+    /// `^`
+    Center,
+    /// `>`
+    Right,
+}
+
+/// The representation used for formatted numbers, see [StringFormatOptions]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StringRepresentation {
+    /// Numbers are displayed in decimal notation
+    #[default]
+    Default,
+    /// `x`
+    Hex,
+    /// `X`
+    HexUpper,
+    /// `b`
+    Binary,
+    /// `o`
+    Octal,
+    /// `e`
+    Exponent,
+    /// `E`
+    ExponentUpper,
 }
 
 /// A for loop definition
@@ -476,6 +560,23 @@ pub enum LookupNode {
     },
 }
 
+/// An entry in a [map pattern](Node::MapPattern)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MapPatternEntry {
+    /// The entry's key
+    pub key: MapKey,
+    /// The pattern that the entry's value is matched against
+    ///
+    /// If `None`, then the value is assigned to an id with the same name as the key.
+    pub pattern: Option<AstIndex>,
+    /// An optional default value, used when the key is missing from the map
+    ///
+    /// e.g. `{x, y = 0} = point`
+    ///
+    /// Defaults are only available when destructuring, not in match patterns.
+    pub default: Option<AstIndex>,
+}
+
 /// An arm in a match expression
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct MatchArm {
@@ -559,6 +660,8 @@ pub enum MetaKeyId {
 
     /// @display
     Display,
+    /// @format
+    Format,
     /// @iterator
     Iterator,
     /// @next
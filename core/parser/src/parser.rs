@@ -230,18 +230,14 @@ pub struct Parser<'source> {
     constants: ConstantPoolBuilder,
     lexer: Lexer<'source>,
     frame_stack: Vec<Frame>,
+    // The errors that have been recovered from, when parsing with recovery
+    recovered_errors: Option<Vec<ParserError>>,
 }
 
 impl<'source> Parser<'source> {
     /// Takes in a source script, and produces an Ast
     pub fn parse(source: &'source str) -> Result<Ast, ParserError> {
-        let capacity_guess = source.len() / 4;
-        let mut parser = Parser {
-            ast: Ast::with_capacity(capacity_guess),
-            constants: ConstantPoolBuilder::default(),
-            lexer: Lexer::new(source),
-            frame_stack: Vec::new(),
-        };
+        let mut parser = Self::new(source);
 
         let main_block = parser.parse_main_block()?;
         parser.ast.set_entry_point(main_block);
@@ -250,6 +246,41 @@ impl<'source> Parser<'source> {
         Ok(parser.ast)
     }
 
+    /// Takes in a source script, and produces an Ast along with any errors that were encountered
+    ///
+    /// Rather than stopping at the first error, the parser skips past lines that fail to parse,
+    /// resuming at the next line that has the same or lower indentation as the failed line.
+    ///
+    /// The resulting Ast contains the expressions that were parsed successfully, and can be used
+    /// by tools that need to inspect scripts that contain errors (e.g. language servers).
+    /// An Ast that was produced with errors shouldn't be compiled.
+    pub fn parse_with_recovery(source: &'source str) -> (Ast, Vec<ParserError>) {
+        let mut parser = Self::new(source);
+        parser.recovered_errors = Some(Vec::new());
+
+        let main_block = parser.parse_main_block();
+        let mut errors = parser.recovered_errors.take().unwrap_or_default();
+
+        match main_block {
+            Ok(main_block) => parser.ast.set_entry_point(main_block),
+            Err(error) => errors.push(error),
+        }
+        parser.ast.set_constants(parser.constants.build());
+
+        (parser.ast, errors)
+    }
+
+    fn new(source: &'source str) -> Self {
+        let capacity_guess = source.len() / 4;
+        Self {
+            ast: Ast::with_capacity(capacity_guess),
+            constants: ConstantPoolBuilder::default(),
+            lexer: Lexer::new(source),
+            frame_stack: Vec::new(),
+            recovered_errors: None,
+        }
+    }
+
     // Parses the main 'top-level' block
     fn parse_main_block(&mut self) -> Result<AstIndex, ParserError> {
         self.frame_stack.push(Frame::default());
@@ -260,28 +291,32 @@ impl<'source> Parser<'source> {
         context.expected_indentation = Indentation::Equal(0);
 
         let mut body = Vec::new();
-        while self.peek_token_with_context(&context).is_some() {
-            self.consume_until_token_with_context(&context);
+        loop {
+            let recovery_point = self.recovery_point(0);
 
-            let Some(expression) = self.parse_line(&ExpressionContext::permissive())? else {
-                return self.consume_token_and_error(SyntaxError::ExpectedExpression);
-            };
+            let line = if self.peek_token_with_context(&context).is_some() {
+                self.consume_until_token_with_context(&context);
 
-            body.push(expression);
+                match self.parse_block_line(&ExpressionContext::permissive()) {
+                    Ok(Some(expression)) => Ok(expression),
+                    Ok(None) => self.consume_token_and_error(SyntaxError::ExpectedExpression),
+                    Err(error) => Err(error),
+                }
+            } else {
+                // Check that all tokens were consumed
+                self.consume_until_token_with_context(&ExpressionContext::permissive());
+                if self.peek_token().is_none() {
+                    break;
+                }
+                self.consume_token_and_error(SyntaxError::UnexpectedToken)
+            };
 
-            match self.peek_next_token_on_same_line() {
-                Some(Token::NewLine | Token::NewLineIndented) => continue,
-                None => break,
-                _ => return self.consume_token_and_error(SyntaxError::UnexpectedToken),
+            match line {
+                Ok(expression) => body.push(expression),
+                Err(error) => self.recover_from_error(error, recovery_point)?,
             }
         }
 
-        // Check that all tokens were consumed
-        self.consume_until_token_with_context(&ExpressionContext::permissive());
-        if self.peek_token().is_some() {
-            return self.consume_token_and_error(SyntaxError::UnexpectedToken);
-        }
-
         let result = self.push_node_with_start_span(
             Node::MainBlock {
                 body,
@@ -313,24 +348,20 @@ impl<'source> Parser<'source> {
             .consume_until_token_with_context(&block_context)
             .unwrap(); // Safe to unwrap here given that we've just peeked
         let start_span = self.current_span();
+        let block_indent = self.current_indent();
 
         let mut block = Vec::new();
         loop {
+            let recovery_point = self.recovery_point(block_indent);
             let line_context = ExpressionContext {
                 allow_map_block: block.is_empty(),
                 ..ExpressionContext::permissive()
             };
 
-            let Some(expression) = self.parse_line(&line_context)? else {
-                break;
-            };
-
-            block.push(expression);
-
-            match self.peek_next_token_on_same_line() {
-                None => break,
-                Some(Token::NewLine | Token::NewLineIndented) => {}
-                _ => return self.consume_token_and_error(SyntaxError::UnexpectedToken),
+            match self.parse_block_line(&line_context) {
+                Ok(Some(expression)) => block.push(expression),
+                Ok(None) => break,
+                Err(error) => self.recover_from_error(error, recovery_point)?,
             }
 
             // Peek ahead to see if the indented block continues after this line
@@ -350,6 +381,21 @@ impl<'source> Parser<'source> {
         }
     }
 
+    // Parses a line in a block, checking that the line is followed by a newline or the end of input
+    fn parse_block_line(
+        &mut self,
+        context: &ExpressionContext,
+    ) -> Result<Option<AstIndex>, ParserError> {
+        let Some(expression) = self.parse_line(context)? else {
+            return Ok(None);
+        };
+
+        match self.peek_next_token_on_same_line() {
+            None | Some(Token::NewLine | Token::NewLineIndented) => Ok(Some(expression)),
+            _ => self.consume_token_and_error(SyntaxError::UnexpectedToken),
+        }
+    }
+
     // Parses expressions from the start of a line
     fn parse_line(&mut self, context: &ExpressionContext) -> Result<Option<AstIndex>, ParserError> {
         self.parse_expressions(context, TempResult::No)
@@ -662,10 +708,13 @@ impl<'source> Parser<'source> {
         for lhs_expression in previous_lhs.iter().chain(std::iter::once(&lhs)) {
             // Note which identifiers are being assigned to
             match self.ast.node(*lhs_expression).node.clone() {
-                Node::Id(id_index) => {
+                Node::Id(id_index, ..) => {
                     self.frame_mut()?.add_local_id_assignment(id_index);
                 }
-                Node::Meta { .. } | Node::Lookup(_) | Node::Wildcard(_) => {}
+                Node::Meta { .. }
+                | Node::Lookup(_)
+                | Node::Wildcard(_)
+                | Node::MapPattern { .. } => {}
                 _ => return self.error(SyntaxError::ExpectedAssignmentTarget),
             }
 
@@ -735,7 +784,7 @@ impl<'source> Parser<'source> {
             Token::DoubleQuote | Token::SingleQuote => {
                 let (string, span, string_context) = self.parse_string(context)?.unwrap();
 
-                if self.peek_token() == Some(Token::Colon) {
+                if self.next_token_is_map_block_colon() {
                     self.parse_braceless_map_start(MapKey::Str(string), start_span, &string_context)
                 } else {
                     let string_node = self.push_node_with_span(Str(string), span)?;
@@ -776,6 +825,11 @@ impl<'source> Parser<'source> {
             }
             Token::Wildcard => self.parse_wildcard(context),
             Token::SquareOpen => self.parse_list(context),
+            Token::CurlyOpen if self.next_map_is_assignment_target(peeked.peek_count) => {
+                // e.g. `{x, y} = foo`
+                self.consume_token_with_context(context);
+                self.parse_map_pattern(MapPatternContext::Assignment)
+            }
             Token::CurlyOpen => self.parse_map_with_braces(context),
             Token::If => self.parse_if_expression(context),
             Token::Match => self.parse_match_expression(context),
@@ -870,6 +924,10 @@ impl<'source> Parser<'source> {
 
         let span_start = self.current_span().start;
 
+        // The function's frame is pushed before parsing the args so that ids accessed in default
+        // values for destructured args are treated as accesses from within the function
+        self.frame_stack.push(Frame::default());
+
         // Parse function's args
         let mut arg_nodes = Vec::new();
         let mut arg_ids = Vec::new();
@@ -883,7 +941,11 @@ impl<'source> Parser<'source> {
             match self.parse_id_or_wildcard(context)? {
                 Some(IdOrWildcard::Id(constant_index)) => {
                     arg_ids.push(constant_index);
-                    arg_nodes.push(self.push_node(Node::Id(constant_index))?);
+                    let arg_span = self.current_span();
+                    let type_hint = self.parse_type_hint()?;
+                    arg_nodes.push(
+                        self.push_node_with_span(Node::Id(constant_index, type_hint), arg_span)?,
+                    );
 
                     if self.peek_token() == Some(Token::Ellipsis) {
                         self.consume_token();
@@ -932,6 +994,12 @@ impl<'source> Parser<'source> {
                                 nested_span_start,
                             )?);
                         }
+                        Some(Token::CurlyOpen) => {
+                            // e.g. `|{x, y}| x + y`
+                            self.consume_token();
+                            arg_nodes
+                                .push(self.parse_map_pattern(MapPatternContext::FunctionArgs)?);
+                        }
                         _ => break,
                     }
                 }
@@ -954,10 +1022,21 @@ impl<'source> Parser<'source> {
             return self.error(SyntaxError::ExpectedFunctionArgsEnd);
         }
 
+        // Optional output type, e.g. `|x| -> Number`
+        let output_type = if self.peek_next_token_on_same_line() == Some(Token::Arrow) {
+            self.consume_next_token_on_same_line();
+            match self.parse_type()? {
+                Some(type_hint) => Some(type_hint),
+                None => return self.consume_token_and_error(SyntaxError::ExpectedType),
+            }
+        } else {
+            None
+        };
+
         // body
-        let mut function_frame = Frame::default();
-        function_frame.ids_assigned_in_frame.extend(arg_ids.iter());
-        self.frame_stack.push(function_frame);
+        self.frame_mut()?
+            .ids_assigned_in_frame
+            .extend(arg_ids.iter());
 
         let body = if let Some(block) = self.parse_indented_block()? {
             block
@@ -990,6 +1069,7 @@ impl<'source> Parser<'source> {
                 body,
                 is_variadic,
                 is_generator: function_frame.contains_yield,
+                output_type,
             }),
             Span {
                 start: span_start,
@@ -1022,7 +1102,7 @@ impl<'source> Parser<'source> {
                         self.consume_token();
                         Node::Ellipsis(Some(constant_index))
                     } else {
-                        Node::Id(constant_index)
+                        Node::Id(constant_index, None)
                     };
 
                     nested_args.push(self.push_node(arg_node)?);
@@ -1062,6 +1142,10 @@ impl<'source> Parser<'source> {
                             self.push_node_with_start_span(Node::Tuple(tuple_args), span_start)?,
                         );
                     }
+                    Some(Token::CurlyOpen) => {
+                        self.consume_token();
+                        nested_args.push(self.parse_map_pattern(MapPatternContext::FunctionArgs)?);
+                    }
                     Some(Token::Ellipsis) => {
                         self.consume_token();
                         nested_args.push(self.push_node(Node::Ellipsis(None))?);
@@ -1202,21 +1286,29 @@ impl<'source> Parser<'source> {
             return self.consume_token_and_error(InternalError::UnexpectedToken);
         };
 
-        if self.peek_token() == Some(Token::Colon) {
+        // Where a map block is allowed, `x: y = 42` is parsed as a map entry with an assignment
+        // as its value, so typed assignments are only parsed outside of map block positions.
+        if !id_context.allow_map_block && self.next_tokens_are_typed_assignment() {
+            // e.g. `x: Number = 42`
+            let id_span = self.current_span();
+            self.frame_mut()?.add_id_access(constant_index);
+            let type_hint = self.parse_type_hint()?;
+            self.push_node_with_span(Node::Id(constant_index, type_hint), id_span)
+        } else if self.next_token_is_map_block_colon() {
             self.parse_braceless_map_start(MapKey::Id(constant_index), start_span, &id_context)
         } else {
             self.frame_mut()?.add_id_access(constant_index);
 
             let lookup_context = id_context.lookup_start();
             if self.next_token_is_lookup_start(&lookup_context) {
-                let id_index = self.push_node(Node::Id(constant_index))?;
+                let id_index = self.push_node(Node::Id(constant_index, None))?;
                 self.parse_lookup(id_index, &lookup_context)
             } else {
                 let start_span = self.current_span();
                 let args = self.parse_call_args(&id_context)?;
 
                 if args.is_empty() {
-                    self.push_node(Node::Id(constant_index))
+                    self.push_node(Node::Id(constant_index, None))
                 } else {
                     self.push_node_with_start_span(
                         Node::NamedCall {
@@ -1230,6 +1322,66 @@ impl<'source> Parser<'source> {
         }
     }
 
+    // Parses an optional type hint following a `:`, e.g. `x: Number`
+    //                                                       ^ You are here
+    fn parse_type_hint(&mut self) -> Result<Option<AstIndex>, ParserError> {
+        if self.peek_next_token_on_same_line() != Some(Token::Colon) {
+            return Ok(None);
+        }
+
+        self.consume_next_token_on_same_line();
+        match self.parse_type()? {
+            Some(type_hint) => Ok(Some(type_hint)),
+            None => self.consume_token_and_error(SyntaxError::ExpectedType),
+        }
+    }
+
+    // Parses a type id, e.g. `Number` in `x: Number` or `|| -> Number`
+    fn parse_type(&mut self) -> Result<Option<AstIndex>, ParserError> {
+        if self.peek_next_token_on_same_line() != Some(Token::Id) {
+            return Ok(None);
+        }
+
+        self.consume_next_token_on_same_line();
+        let type_index = self.add_string_constant(self.lexer.slice())?;
+        self.push_node(Node::Type(type_index)).map(Some)
+    }
+
+    // Returns true if the next token is a ':' that starts a map block, rather than a ':' that
+    // introduces formatting options in a string template, e.g. '${x:>8.2}'
+    fn next_token_is_map_block_colon(&mut self) -> bool {
+        self.peek_token() == Some(Token::Colon)
+            && !matches!(
+                self.peek_token_n(1),
+                Some(Token::StringFormat | Token::CurlyClose)
+            )
+    }
+
+    // Checks if the tokens following an id make up a type hint followed by an assignment,
+    // e.g. `x: Number = 42`
+    //        ^ You are here
+    //
+    // Without the check, `x: Number` would be parsed as the start of a map block.
+    fn next_tokens_are_typed_assignment(&mut self) -> bool {
+        let expected = [Token::Colon, Token::Id, Token::Assign];
+        let mut peek_index = 0;
+
+        for expected_token in expected {
+            loop {
+                match self.peek_token_n(peek_index) {
+                    Some(token) if token.is_whitespace() => peek_index += 1,
+                    Some(token) if token == expected_token => {
+                        peek_index += 1;
+                        break;
+                    }
+                    _ => return false,
+                }
+            }
+        }
+
+        true
+    }
+
     fn parse_self_expression(
         &mut self,
         context: &ExpressionContext,
@@ -2009,6 +2161,7 @@ impl<'source> Parser<'source> {
             Some(Token::Not) => MetaKeyId::Not,
             Some(Token::Id) => match self.lexer.slice() {
                 "display" => MetaKeyId::Display,
+                "format" => MetaKeyId::Format,
                 "iterator" => MetaKeyId::Iterator,
                 "next" => MetaKeyId::Next,
                 "next_back" => MetaKeyId::NextBack,
@@ -2061,7 +2214,7 @@ impl<'source> Parser<'source> {
             match id_or_wildcard {
                 IdOrWildcard::Id(id) => {
                     self.frame_mut()?.ids_assigned_in_frame.insert(id);
-                    args.push(self.push_node(Node::Id(id))?);
+                    args.push(self.push_node(Node::Id(id, None))?);
                 }
                 IdOrWildcard::Wildcard(maybe_id) => {
                     args.push(self.push_node(Node::Wildcard(maybe_id))?);
@@ -2500,13 +2653,15 @@ impl<'source> Parser<'source> {
                                     .error(SyntaxError::MatchEllipsisOutsideOfNestedPatterns);
                             }
                         } else {
-                            let id_node = self.push_node(Node::Id(id))?;
                             if self.next_token_is_lookup_start(&pattern_context) {
+                                let id_node = self.push_node(Node::Id(id, None))?;
                                 self.frame_mut()?.add_id_access(id);
                                 self.parse_lookup(id_node, &pattern_context)?
                             } else {
+                                // e.g. `x: String then ...`
+                                let type_hint = self.parse_type_hint()?;
                                 self.frame_mut()?.ids_assigned_in_frame.insert(id);
-                                id_node
+                                self.push_node(Node::Id(id, type_hint))?
                             }
                         };
                         Some(result)
@@ -2525,6 +2680,10 @@ impl<'source> Parser<'source> {
 
                     Some(self.push_node(Node::List(list_patterns))?)
                 }
+                CurlyOpen => {
+                    self.consume_token_with_context(&pattern_context);
+                    Some(self.parse_map_pattern(MapPatternContext::Match)?)
+                }
                 RoundOpen => {
                     self.consume_token_with_context(&pattern_context);
 
@@ -2553,6 +2712,234 @@ impl<'source> Parser<'source> {
         Ok(result)
     }
 
+    // Parses the entries of a map pattern, following the opening `{`
+    //
+    // e.g.
+    //   match x
+    //     {type: 'click', x, y, rest...} then ...
+    //   #  ^ You are here
+    //
+    //   {x, y = 0} = point
+    //   #^ ...or here
+    fn parse_map_pattern(
+        &mut self,
+        pattern_context: MapPatternContext,
+    ) -> Result<AstIndex, ParserError> {
+        let start_indent = self.current_indent();
+        let start_span = self.current_span();
+
+        let mut entries = Vec::new();
+        let mut rest = None;
+        let mut entry_context = ExpressionContext::braced_items_start();
+
+        while self.peek_token_with_context(&entry_context).is_some() {
+            self.consume_until_token_with_context(&entry_context);
+
+            if self.peek_token() == Some(Token::Ellipsis) {
+                // e.g. `{x, ...}`
+                self.consume_token();
+                rest = Some(self.push_node(Node::Ellipsis(None))?);
+                break;
+            }
+
+            let Some(key) = self.parse_map_key()? else {
+                break;
+            };
+
+            match &key {
+                MapKey::Id(_) => {}
+                MapKey::Str(string)
+                    if matches!(string.nodes.as_slice(), [StringNode::Literal(_)]) => {}
+                _ => return self.error(SyntaxError::InvalidMapPatternKey),
+            }
+
+            let pattern = match self.peek_token() {
+                Some(Token::Colon) => {
+                    // e.g. `{x: 0}`
+                    self.consume_token();
+                    let pattern = match pattern_context {
+                        MapPatternContext::Match => self.parse_match_pattern(false)?,
+                        _ => self.parse_map_pattern_target(pattern_context)?,
+                    };
+                    match pattern {
+                        Some(pattern) => Some(pattern),
+                        None if pattern_context == MapPatternContext::Match => {
+                            return self.consume_token_and_error(SyntaxError::ExpectedMatchPattern)
+                        }
+                        None => {
+                            return self
+                                .consume_token_and_error(SyntaxError::ExpectedMapPatternTarget)
+                        }
+                    }
+                }
+                Some(Token::Ellipsis) => {
+                    // e.g. `{x, rest...}`
+                    let MapKey::Id(id) = key else {
+                        return self.error(SyntaxError::InvalidMapPatternKey);
+                    };
+                    self.consume_token();
+                    self.add_map_pattern_id(id, pattern_context)?;
+                    rest = Some(self.push_node(Node::Ellipsis(Some(id)))?);
+                    break;
+                }
+                _ => {
+                    // e.g. `{x}`
+                    let MapKey::Id(id) = key else {
+                        return self.error(SyntaxError::ExpectedMapColon);
+                    };
+                    self.add_map_pattern_id(id, pattern_context)?;
+                    None
+                }
+            };
+
+            let default = if pattern_context != MapPatternContext::Match
+                && self.peek_next_token_on_same_line() == Some(Token::Assign)
+            {
+                // e.g. `{x, y = 0}`
+                self.consume_next_token_on_same_line();
+                let default_context = ExpressionContext::permissive();
+                if self.peek_token_with_context(&default_context).is_none() {
+                    return self.error(SyntaxError::ExpectedMapPatternDefault);
+                }
+                self.consume_until_token_with_context(&default_context);
+                match self.parse_expression(&default_context)? {
+                    Some(default) => Some(default),
+                    None => {
+                        return self.consume_token_and_error(SyntaxError::ExpectedMapPatternDefault)
+                    }
+                }
+            } else {
+                None
+            };
+
+            entries.push(MapPatternEntry {
+                key,
+                pattern,
+                default,
+            });
+
+            if matches!(
+                self.peek_token_with_context(&entry_context),
+                Some(PeekInfo {
+                    token: Token::Comma,
+                    ..
+                })
+            ) {
+                self.consume_token_with_context(&entry_context);
+                entry_context = ExpressionContext::braced_items_continued();
+            } else {
+                break;
+            }
+        }
+
+        let mut map_end_context = ExpressionContext::permissive();
+        map_end_context.expected_indentation = Indentation::Equal(start_indent);
+        if !matches!(
+            self.consume_token_with_context(&map_end_context),
+            Some((Token::CurlyClose, _))
+        ) {
+            return self.error(SyntaxError::ExpectedMapEnd);
+        }
+
+        self.push_node_with_start_span(Node::MapPattern { entries, rest }, start_span)
+    }
+
+    // Parses the target of an entry in a map pattern that's being used for destructuring
+    //
+    // e.g.
+    //   {x: a, y: {z}} = foo
+    //   #   ^ You are here
+    //   #         ^ ...or here
+    fn parse_map_pattern_target(
+        &mut self,
+        pattern_context: MapPatternContext,
+    ) -> Result<Option<AstIndex>, ParserError> {
+        let target_context = ExpressionContext::inline();
+
+        let result = match self.parse_id_or_wildcard(&target_context)? {
+            Some(IdOrWildcard::Id(id)) => {
+                self.add_map_pattern_id(id, pattern_context)?;
+                Some(self.push_node(Node::Id(id, None))?)
+            }
+            Some(IdOrWildcard::Wildcard(maybe_id)) => {
+                Some(self.push_node(Node::Wildcard(maybe_id))?)
+            }
+            None => match self.peek_token_with_context(&target_context) {
+                Some(PeekInfo {
+                    token: Token::CurlyOpen,
+                    ..
+                }) => {
+                    self.consume_token_with_context(&target_context);
+                    Some(self.parse_map_pattern(pattern_context)?)
+                }
+                _ => None,
+            },
+        };
+
+        Ok(result)
+    }
+
+    // Declares an id that's assigned by a map pattern
+    fn add_map_pattern_id(
+        &mut self,
+        id: u32,
+        pattern_context: MapPatternContext,
+    ) -> Result<(), ParserError> {
+        let frame = self.frame_mut()?;
+        match pattern_context {
+            // Assignments are committed once the RHS has been parsed
+            MapPatternContext::Assignment => frame.add_local_id_assignment(id),
+            _ => {
+                frame.ids_assigned_in_frame.insert(id);
+            }
+        }
+        Ok(())
+    }
+
+    // Checks if the map starting at the peeked `{` is the target of an assignment
+    //
+    // e.g.
+    //   {x, y} = foo
+    //   {a}, b = foo, bar
+    fn next_map_is_assignment_target(&mut self, curly_open_peek_count: usize) -> bool {
+        use Token::*;
+
+        // Find the end of the map
+        let mut depth = 0;
+        let mut peek_count = curly_open_peek_count;
+        while let Some(token) = self.peek_token_n(peek_count) {
+            peek_count += 1;
+            match token {
+                RoundOpen | SquareOpen | CurlyOpen => depth += 1,
+                RoundClose | SquareClose | CurlyClose => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // The map should be followed by `=`, or by further targets in a multi-assignment
+        let mut in_target_list = false;
+        while let Some(token) = self.peek_token_n(peek_count) {
+            peek_count += 1;
+            match token {
+                Assign if depth == 0 => return true,
+                Whitespace | CommentMulti => {}
+                Comma if depth == 0 => in_target_list = true,
+                NewLine | NewLineIndented if depth == 0 => return false,
+                RoundOpen | SquareOpen | CurlyOpen if in_target_list => depth += 1,
+                RoundClose | SquareClose | CurlyClose if in_target_list && depth > 0 => depth -= 1,
+                _ if in_target_list => {}
+                _ => return false,
+            }
+        }
+
+        false
+    }
+
     // Recursively parses nested match patterns
     //
     // e.g.
@@ -2582,10 +2969,10 @@ impl<'source> Parser<'source> {
             _ => return self.error(InternalError::UnexpectedToken),
         };
 
-        let start_span = self.current_span();
         let from_context = ExpressionContext::restricted();
 
         self.consume_token_with_context(&from_context);
+        let start_span = self.current_span();
 
         let from = if importing_from {
             // Parse the from module path: a nested path is allowed, but only a single path
@@ -2720,7 +3107,7 @@ impl<'source> Parser<'source> {
         let catch_arg = match self.parse_id_or_wildcard(&ExpressionContext::restricted())? {
             Some(IdOrWildcard::Id(id)) => {
                 self.frame_mut()?.ids_assigned_in_frame.insert(id);
-                self.push_node(Node::Id(id))?
+                self.push_node(Node::Id(id, None))?
             }
             Some(IdOrWildcard::Wildcard(maybe_id)) => self.push_node(Node::Wildcard(maybe_id))?,
             None => return self.consume_token_and_error(SyntaxError::ExpectedCatchArgument),
@@ -2871,18 +3258,31 @@ impl<'source> Parser<'source> {
                         self.consume_token();
                         let id = self.add_string_constant(self.lexer.slice())?;
                         self.frame_mut()?.add_id_access(id);
-                        let id_node = self.push_node(Node::Id(id))?;
+                        let id_node = self.push_node(Node::Id(id, None))?;
                         nodes.push(StringNode::Expr(id_node));
                     }
                     Some(CurlyOpen) => {
                         self.consume_token();
 
-                        if let Some(expression) =
+                        let Some(expression) =
                             self.parse_expressions(&ExpressionContext::inline(), TempResult::No)?
-                        {
-                            nodes.push(StringNode::Expr(expression));
-                        } else {
+                        else {
                             return self.consume_token_and_error(ExpectedExpression);
+                        };
+
+                        if self.peek_token() == Some(Colon) {
+                            self.consume_token();
+
+                            let format = if self.peek_token() == Some(StringFormat) {
+                                self.consume_token();
+                                self.parse_string_format_options(self.lexer.slice())?
+                            } else {
+                                StringFormatOptions::default()
+                            };
+
+                            nodes.push(StringNode::FormattedExpr(expression, format));
+                        } else {
+                            nodes.push(StringNode::Expr(expression));
                         }
 
                         if self.consume_token() != Some(CurlyClose) {
@@ -2921,6 +3321,88 @@ impl<'source> Parser<'source> {
         self.error(UnterminatedString)
     }
 
+    // Parses the formatting options in a string template, e.g. '>8.2' in '${x:>8.2}'
+    //
+    // The options follow the same rules as the format strings used in `string.format`,
+    // i.e. `[[fill]alignment][min_width][.precision]`.
+    fn parse_string_format_options(
+        &mut self,
+        format: &str,
+    ) -> Result<StringFormatOptions, ParserError> {
+        let mut result = StringFormatOptions::default();
+        let mut chars = format.chars().peekable();
+
+        let alignment = |c| match c {
+            '<' => Some(StringAlignment::Left),
+            '^' => Some(StringAlignment::Center),
+            '>' => Some(StringAlignment::Right),
+            _ => None,
+        };
+
+        // A fill character can only be used when it's followed by an alignment character
+        let mut lookahead = chars.clone();
+        if let (Some(fill), Some(_)) = (lookahead.next(), lookahead.next().and_then(alignment)) {
+            chars.next();
+            result.fill_character = Some(self.add_string_constant(fill.encode_utf8(&mut [0; 4]))?);
+        }
+
+        if let Some(alignment) = chars.peek().and_then(|c| alignment(*c)) {
+            chars.next();
+            result.alignment = alignment;
+        }
+
+        result.plus_sign = chars.next_if_eq(&'+').is_some();
+        result.alternate_form = chars.next_if_eq(&'#').is_some();
+
+        // A '0' is only treated as the zero padding flag when it's followed by the minimum width
+        let mut lookahead = chars.clone();
+        if lookahead.next() == Some('0') && lookahead.next().is_some_and(|c| c.is_ascii_digit()) {
+            chars.next();
+            result.zero_padding = true;
+        }
+
+        let consume_u32 = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+            let mut digits = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            digits.parse::<u32>().ok()
+        };
+
+        if chars.peek().is_some_and(char::is_ascii_digit) {
+            match consume_u32(&mut chars) {
+                Some(min_width) => result.min_width = Some(min_width),
+                None => return self.error(SyntaxError::InvalidStringFormat),
+            }
+        }
+
+        result.grouping = chars.next_if(|c| matches!(c, ',' | '_'));
+
+        if chars.next_if_eq(&'.').is_some() {
+            match consume_u32(&mut chars) {
+                Some(precision) => result.precision = Some(precision),
+                None => return self.error(SyntaxError::InvalidStringFormat),
+            }
+        }
+
+        result.representation = match chars.next() {
+            None => StringRepresentation::Default,
+            Some('x') => StringRepresentation::Hex,
+            Some('X') => StringRepresentation::HexUpper,
+            Some('b') => StringRepresentation::Binary,
+            Some('o') => StringRepresentation::Octal,
+            Some('e') => StringRepresentation::Exponent,
+            Some('E') => StringRepresentation::ExponentUpper,
+            Some(_) => return self.error(SyntaxError::InvalidStringFormat),
+        };
+
+        if chars.next().is_some() {
+            return self.error(SyntaxError::InvalidStringFormat);
+        }
+
+        Ok(result)
+    }
+
     //// Error helpers
 
     fn error<E, T>(&mut self, error_type: E) -> Result<T, ParserError>
@@ -2943,6 +3425,55 @@ impl<'source> Parser<'source> {
         error
     }
 
+    // Captures the parser's state at the start of a line, see recover_from_error()
+    fn recovery_point(&self, indent: usize) -> RecoveryPoint {
+        RecoveryPoint {
+            indent,
+            frame_depth: self.frame_stack.len(),
+            span: self.current_span(),
+        }
+    }
+
+    // Recovers from an error that occurred while parsing a line, when parsing with recovery
+    //
+    // The error is recorded, and then tokens are skipped until the start of a line with the
+    // recovery point's indentation or lower. If recovery isn't enabled then the error is returned.
+    fn recover_from_error(
+        &mut self,
+        error: ParserError,
+        recovery_point: RecoveryPoint,
+    ) -> Result<(), ParserError> {
+        let Some(errors) = self.recovered_errors.as_mut() else {
+            return Err(error);
+        };
+        // The error may have been produced after consuming the line's trailing newline,
+        // so the error's line is used as the line where skipping starts.
+        let error_line = error.span.start.line;
+        errors.push(error);
+
+        // Functions that were being parsed when the error occurred are abandoned
+        self.frame_stack.truncate(recovery_point.frame_depth);
+
+        // Ensure that progress is made if the error was produced before any tokens were consumed
+        if self.current_span() == recovery_point.span {
+            self.consume_token();
+        }
+
+        let context =
+            ExpressionContext::permissive().with_expected_indentation(Indentation::Flexible);
+        while let Some(peeked) = self.peek_token_with_context(&context) {
+            if peeked.line > error_line && peeked.indent <= recovery_point.indent {
+                break;
+            }
+
+            for _ in 0..=peeked.peek_count {
+                self.consume_token();
+            }
+        }
+
+        Ok(())
+    }
+
     fn consume_token_on_same_line_and_error<E, T>(
         &mut self,
         error_type: E,
@@ -3252,6 +3783,16 @@ fn operator_precedence(op: Token) -> Option<(u8, u8)> {
     Some(priority)
 }
 
+// Returned by Parser::recovery_point()
+struct RecoveryPoint {
+    // Lines with this indentation or lower are where parsing can resume after an error
+    indent: usize,
+    // The number of frames in the frame stack at the start of the line
+    frame_depth: usize,
+    // The parser's span at the start of the line
+    span: Span,
+}
+
 // Returned by Parser::peek_token_with_context()
 #[derive(Debug)]
 struct PeekInfo {
@@ -3261,6 +3802,17 @@ struct PeekInfo {
     peek_count: usize,
 }
 
+// Used by Parser::parse_map_pattern() to determine which kind of entries are allowed
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MapPatternContext {
+    // e.g. `match x\n  {y: 0} then ...`
+    Match,
+    // e.g. `{x, y = 0} = foo`
+    Assignment,
+    // e.g. `f = |{x, y = 0}| ...`
+    FunctionArgs,
+}
+
 // Returned by Parser::parse_id_or_wildcard()
 enum IdOrWildcard {
     Id(u32),
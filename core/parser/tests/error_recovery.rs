@@ -0,0 +1,118 @@
+mod error_recovery {
+    use koto_parser::{Ast, Node, Parser};
+
+    // Parses the source with recovery, and checks the errors that were reported along with the
+    // lines of the main block's expressions
+    fn check_recovery(source: &str, expected_errors: &[(&str, u32)], expected_lines: &[u32]) {
+        println!("{source}");
+
+        let (ast, errors) = Parser::parse_with_recovery(source);
+
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|error| (error.to_string(), error.span.start.line))
+            .collect();
+        let expected_errors: Vec<_> = expected_errors
+            .iter()
+            .map(|(error, line)| (error.to_string(), *line))
+            .collect();
+        assert_eq!(errors, expected_errors);
+
+        assert_eq!(main_block_lines(&ast), expected_lines);
+    }
+
+    fn main_block_lines(ast: &Ast) -> Vec<u32> {
+        match ast.entry_point().map(|entry_point| &entry_point.node) {
+            Some(Node::MainBlock { body, .. }) => body
+                .iter()
+                .map(|index| ast.span(ast.node(*index).span).start.line)
+                .collect(),
+            Some(other) => panic!("Expected MainBlock, found {other:?}"),
+            None => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn no_errors() {
+        let source = "
+x = 1
+y = x + 1
+";
+        check_recovery(source, &[], &[2, 3]);
+    }
+
+    #[test]
+    fn errors_on_separate_lines() {
+        let source = "\
+x = 1 +
+y = 2
+z = * 3
+w = 4
+";
+        check_recovery(
+            source,
+            &[
+                ("Expected expression after binary operator", 1),
+                ("Expected expression after assignment operator", 3),
+            ],
+            &[2, 4],
+        );
+    }
+
+    #[test]
+    fn unexpected_tokens_at_end_of_lines() {
+        let source = "\
+x = 1 2
+y = 3
+z = 4 5
+";
+        check_recovery(
+            source,
+            &[("Unexpected token", 1), ("Unexpected token", 3)],
+            &[2],
+        );
+    }
+
+    #[test]
+    fn errors_in_indented_block() {
+        let source = "\
+f = |x|
+  a = x +
+  b = )
+  a
+g = 1
+";
+        check_recovery(
+            source,
+            &[
+                ("Expected expression after binary operator", 2),
+                ("Expected expression after assignment operator", 3),
+            ],
+            &[1, 5],
+        );
+    }
+
+    #[test]
+    fn unterminated_list() {
+        let source = "\
+x = [1, 2
+y = 3
+z = 4
+";
+        check_recovery(source, &[("Expected List end ']'", 2)], &[3]);
+    }
+
+    #[test]
+    fn parse_returns_the_first_error() {
+        let source = "\
+x = 1 +
+y = * 2
+";
+        let error = Parser::parse(source).unwrap_err();
+        let (_, errors) = Parser::parse_with_recovery(source);
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].to_string(), error.to_string());
+        assert_eq!(errors[0].span, error.span);
+    }
+}
@@ -87,7 +87,7 @@ null"#;
                     Float(constant(0)),
                     string_literal(1, QuotationMark::Double),
                     string_literal(2, QuotationMark::Single),
-                    Id(constant(3)),
+                    Id(constant(3), None),
                     Null,
                     MainBlock {
                         body: vec![0, 1, 2, 3, 4, 5, 6, 7],
@@ -192,7 +192,7 @@ null"#;
             check_ast(
                 source,
                 &[
-                    Id(constant(1)),
+                    Id(constant(1), None),
                     Str(AstString {
                         quotation_mark: QuotationMark::Single,
                         nodes: vec![
@@ -201,13 +201,13 @@ null"#;
                             StringNode::Literal(constant(2)),
                         ],
                     }),
-                    Id(constant(3)),
+                    Id(constant(3), None),
                     Str(AstString {
                         quotation_mark: QuotationMark::Double,
                         nodes: vec![StringNode::Expr(2)],
                     }),
-                    Id(constant(4)),
-                    Id(constant(6)), // 5
+                    Id(constant(4), None),
+                    Id(constant(6), None), // 5
                     Str(AstString {
                         quotation_mark: QuotationMark::Single,
                         nodes: vec![
@@ -261,6 +261,108 @@ null"#;
             )
         }
 
+        #[test]
+        fn string_with_formatted_expressions() {
+            let source = "
+'${x:_>8.2}${y + 1:^5}${z:}'
+";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0), None),
+                    Id(constant(2), None),
+                    SmallInt(1),
+                    BinaryOp {
+                        op: AstBinaryOp::Add,
+                        lhs: 1,
+                        rhs: 2,
+                    },
+                    Id(constant(3), None),
+                    Str(AstString {
+                        quotation_mark: QuotationMark::Single,
+                        nodes: vec![
+                            StringNode::FormattedExpr(
+                                0,
+                                StringFormatOptions {
+                                    alignment: StringAlignment::Right,
+                                    min_width: Some(8),
+                                    precision: Some(2),
+                                    fill_character: Some(constant(1)),
+                                    ..Default::default()
+                                },
+                            ),
+                            StringNode::FormattedExpr(
+                                3,
+                                StringFormatOptions {
+                                    alignment: StringAlignment::Center,
+                                    min_width: Some(5),
+                                    ..Default::default()
+                                },
+                            ),
+                            StringNode::FormattedExpr(4, StringFormatOptions::default()),
+                        ],
+                    }), // 5
+                    MainBlock {
+                        body: vec![5],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("x"),
+                    Constant::Str("_"),
+                    Constant::Str("y"),
+                    Constant::Str("z"),
+                ]),
+            )
+        }
+
+        #[test]
+        fn string_with_extended_format_options() {
+            let source = "
+'${x:+#010_x}${x:0>,.3e}'
+";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0), None),
+                    Id(constant(0), None),
+                    Str(AstString {
+                        quotation_mark: QuotationMark::Single,
+                        nodes: vec![
+                            StringNode::FormattedExpr(
+                                0,
+                                StringFormatOptions {
+                                    min_width: Some(10),
+                                    plus_sign: true,
+                                    alternate_form: true,
+                                    zero_padding: true,
+                                    grouping: Some('_'),
+                                    representation: StringRepresentation::Hex,
+                                    ..Default::default()
+                                },
+                            ),
+                            StringNode::FormattedExpr(
+                                1,
+                                StringFormatOptions {
+                                    alignment: StringAlignment::Right,
+                                    precision: Some(3),
+                                    fill_character: Some(constant(1)),
+                                    grouping: Some(','),
+                                    representation: StringRepresentation::Exponent,
+                                    ..Default::default()
+                                },
+                            ),
+                        ],
+                    }),
+                    MainBlock {
+                        body: vec![2],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[Constant::Str("x"), Constant::Str("0")]),
+            )
+        }
+
         #[test]
         fn negatives() {
             let source = "\
@@ -272,12 +374,12 @@ null"#;
                 source,
                 &[
                     Float(constant(0)),
-                    Id(constant(1)),
+                    Id(constant(1), None),
                     UnaryOp {
                         op: AstUnaryOp::Negate,
                         value: 1,
                     },
-                    Id(constant(2)),
+                    Id(constant(2), None),
                     SmallInt(0),
                     Lookup((LookupNode::Index(4), None)), // 5
                     Lookup((LookupNode::Root(3), Some(5))),
@@ -320,9 +422,9 @@ null"#;
                 source,
                 &[
                     SmallInt(0),
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     string_literal(1, QuotationMark::Double),
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(-1),
                     List(vec![0, 1, 2, 3, 4]),
                     List(vec![]),
@@ -397,7 +499,7 @@ x = [
             check_ast_for_equivalent_sources(
                 &sources,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(0),
                     SmallInt(1),
                     SmallInt(0),
@@ -449,7 +551,7 @@ x =
                 &sources,
                 &[
                     Map(vec![]),
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(42),
                     string_literal(4, QuotationMark::Single),
                     SmallInt(99),
@@ -523,7 +625,7 @@ x"#;
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // x
+                    Id(constant(0), None), // x
                     SmallInt(42),
                     SmallInt(0),
                     Map(vec![(MapKey::Id(constant(1)), Some(2))]), // foo, 0
@@ -537,7 +639,7 @@ x"#;
                         target: 0,
                         expression: 5,
                     },
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     MainBlock {
                         body: vec![6, 7],
                         local_count: 1,
@@ -560,7 +662,7 @@ x =
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // x
+                    Id(constant(0), None), // x
                     SmallInt(42),
                     Map(vec![(
                         string_literal_map_key(1, QuotationMark::Double),
@@ -589,7 +691,7 @@ x =
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // x
+                    Id(constant(0), None), // x
                     SmallInt(42),
                     Map(vec![
                         (MapKey::Id(constant(2)), Some(1)), // bar: 42
@@ -625,7 +727,7 @@ x =
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // x
+                    Id(constant(0), None), // x
                     SmallInt(0),
                     SmallInt(1),
                     SmallInt(0),
@@ -760,20 +862,20 @@ min..max
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(0),
                     Assign {
                         target: 0,
                         expression: 1,
                     },
-                    Id(constant(1)),
+                    Id(constant(1), None),
                     SmallInt(10),
                     Assign {
                         target: 3,
                         expression: 4,
                     }, // 5
-                    Id(constant(0)),
-                    Id(constant(1)),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
                     Range {
                         start: 6,
                         end: 7,
@@ -794,10 +896,10 @@ min..max
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Lookup((LookupNode::Id(constant(1)), None)),
                     Lookup((LookupNode::Root(0), Some(1))),
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Lookup((LookupNode::Id(constant(2)), None)),
                     Lookup((LookupNode::Root(3), Some(4))), // 5
                     Range {
@@ -974,7 +1076,7 @@ min..max
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(1),
                     Assign {
                         target: 0,
@@ -995,7 +1097,7 @@ min..max
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(1),
                     SmallInt(0),
                     Tuple(vec![1, 2]),
@@ -1018,7 +1120,7 @@ min..max
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(0),
                     SmallInt(1),
                     Tuple(vec![1, 2]),
@@ -1045,8 +1147,8 @@ min..max
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
-                    Id(constant(1)),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
                     SmallInt(0),
                     Lookup((LookupNode::Index(2), None)),
                     Lookup((LookupNode::Root(1), Some(3))),
@@ -1066,6 +1168,85 @@ min..max
             )
         }
 
+        #[test]
+        fn unpack_map() {
+            let source = "{x, y: z, w = 1, rest...} = foo";
+            check_ast(
+                source,
+                &[
+                    Id(constant(2), None),
+                    SmallInt(1),
+                    Ellipsis(Some(constant(4))),
+                    MapPattern {
+                        entries: vec![
+                            MapPatternEntry {
+                                key: MapKey::Id(constant(0)),
+                                pattern: None,
+                                default: None,
+                            },
+                            MapPatternEntry {
+                                key: MapKey::Id(constant(1)),
+                                pattern: Some(0),
+                                default: None,
+                            },
+                            MapPatternEntry {
+                                key: MapKey::Id(constant(3)),
+                                pattern: None,
+                                default: Some(1),
+                            },
+                        ],
+                        rest: Some(2),
+                    },
+                    Id(constant(5), None),
+                    Assign {
+                        target: 3,
+                        expression: 4,
+                    }, // 5
+                    MainBlock {
+                        body: vec![5],
+                        local_count: 4,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("x"),
+                    Constant::Str("y"),
+                    Constant::Str("z"),
+                    Constant::Str("w"),
+                    Constant::Str("rest"),
+                    Constant::Str("foo"), // 5
+                ]),
+            )
+        }
+
+        #[test]
+        fn unpack_map_in_multi_assignment() {
+            let source = "{x}, y = foo";
+            check_ast(
+                source,
+                &[
+                    MapPattern {
+                        entries: vec![MapPatternEntry {
+                            key: MapKey::Id(constant(0)),
+                            pattern: None,
+                            default: None,
+                        }],
+                        rest: None,
+                    },
+                    Id(constant(1), None),
+                    Id(constant(2), None),
+                    MultiAssign {
+                        targets: vec![0, 1],
+                        expression: 2,
+                    },
+                    MainBlock {
+                        body: vec![3],
+                        local_count: 2,
+                    },
+                ],
+                Some(&[Constant::Str("x"), Constant::Str("y"), Constant::Str("foo")]),
+            )
+        }
+
         #[test]
         fn tuple_with_linebreaks() {
             let source = "\
@@ -1076,8 +1257,8 @@ x";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
-                    Id(constant(1)),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
                     SmallInt(1),
                     SmallInt(0),
                     TempTuple(vec![2, 3]),
@@ -1085,7 +1266,7 @@ x";
                         targets: vec![0, 1],
                         expression: 4,
                     }, // 5
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     MainBlock {
                         body: vec![5, 6],
                         local_count: 2,
@@ -1101,10 +1282,10 @@ x";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Wildcard(None),
                     Wildcard(Some(constant(1))),
-                    Id(constant(2)),
+                    Id(constant(2), None),
                     Lookup((
                         LookupNode::Call {
                             args: vec![],
@@ -1137,35 +1318,35 @@ x %= 4";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(0),
                     BinaryOp {
                         op: AstBinaryOp::AddAssign,
                         lhs: 0,
                         rhs: 1,
                     },
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(1),
                     BinaryOp {
                         op: AstBinaryOp::SubtractAssign,
                         lhs: 3,
                         rhs: 4,
                     }, // 5
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(2),
                     BinaryOp {
                         op: AstBinaryOp::MultiplyAssign,
                         lhs: 6,
                         rhs: 7,
                     },
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(3), // 10
                     BinaryOp {
                         op: AstBinaryOp::DivideAssign,
                         lhs: 9,
                         rhs: 10,
                     },
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(4),
                     BinaryOp {
                         op: AstBinaryOp::RemainderAssign,
@@ -1200,7 +1381,7 @@ export a =
             check_ast_for_equivalent_sources(
                 &sources,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(1),
                     SmallInt(1),
                     BinaryOp {
@@ -1399,7 +1580,7 @@ export
                 source,
                 &[
                     string_literal(0, QuotationMark::Single),
-                    Id(constant(1)),
+                    Id(constant(1), None),
                     BinaryOp {
                         op: AstBinaryOp::Add,
                         lhs: 0,
@@ -1420,9 +1601,9 @@ export
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // x
+                    Id(constant(0), None), // x
                     SmallInt(1),
-                    Id(constant(2)), // y
+                    Id(constant(2), None), // y
                     NamedCall {
                         id: constant(1), // f
                         args: vec![2],
@@ -1474,7 +1655,7 @@ a =
             check_ast_for_equivalent_sources(
                 &sources,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(1),
                     SmallInt(2),
                     SmallInt(3),
@@ -1527,7 +1708,7 @@ a = (1
             check_ast_for_equivalent_sources(
                 &sources,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(1),
                     SmallInt(2),
                     BinaryOp {
@@ -1699,7 +1880,7 @@ a",
             check_ast_for_equivalent_sources(
                 &sources,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     BoolFalse,
                     SmallInt(0),
                     BoolTrue,
@@ -1717,7 +1898,7 @@ a",
                         target: 0,
                         expression: 8,
                     },
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     MainBlock {
                         body: vec![9, 10],
                         local_count: 1,
@@ -1733,8 +1914,8 @@ a",
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
-                    Id(constant(1)),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
                     BoolTrue,
                     SmallInt(0),
                     SmallInt(1),
@@ -1781,7 +1962,7 @@ a",
                         else_if_blocks: vec![],
                         else_node: None,
                     }),
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Block(vec![2, 3]),
                     Function(koto_parser::Function {
                         args: vec![],
@@ -1790,6 +1971,7 @@ a",
                         body: 4,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }), // 5
                     MainBlock {
                         body: vec![5],
@@ -1812,12 +1994,12 @@ for x, _, _y, z in foo
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // x
+                    Id(constant(0), None), // x
                     Wildcard(None),
                     Wildcard(Some(constant(1))), // _y
-                    Id(constant(2)),             // z
-                    Id(constant(3)),             // foo
-                    Id(constant(0)),             // x - 5
+                    Id(constant(2), None),       // z
+                    Id(constant(3), None),       // foo
+                    Id(constant(0), None),       // x - 5
                     NamedCall {
                         id: constant(2), // z
                         args: vec![5],
@@ -1849,14 +2031,14 @@ while x > y
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // x
-                    Id(constant(1)), // y
+                    Id(constant(0), None), // x
+                    Id(constant(1), None), // y
                     BinaryOp {
                         op: AstBinaryOp::Greater,
                         lhs: 0,
                         rhs: 1,
                     },
-                    Id(constant(0)), // x
+                    Id(constant(0), None), // x
                     NamedCall {
                         id: constant(2), // f
                         args: vec![3],
@@ -1882,14 +2064,14 @@ until x < y
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // x
-                    Id(constant(1)), // y
+                    Id(constant(0), None), // x
+                    Id(constant(1), None), // y
                     BinaryOp {
                         op: AstBinaryOp::Less,
                         lhs: 0,
                         rhs: 1,
                     },
-                    Id(constant(1)), // y
+                    Id(constant(1), None), // y
                     NamedCall {
                         id: constant(2), // f
                         args: vec![3],
@@ -1919,9 +2101,9 @@ for x in y
                 source,
                 &[
                     List(vec![]),
-                    Id(constant(0)), // x
-                    Id(constant(1)), // y
-                    Id(constant(0)), // x
+                    Id(constant(0), None), // x
+                    Id(constant(1), None), // y
+                    Id(constant(0), None), // x
                     For(AstFor {
                         args: vec![1],
                         iterable: 2,
@@ -1945,9 +2127,9 @@ for a in x.zip y
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // a
-                    Id(constant(1)), // x
-                    Id(constant(3)), // y
+                    Id(constant(0), None), // a
+                    Id(constant(1), None), // x
+                    Id(constant(3), None), // y
                     Lookup((
                         LookupNode::Call {
                             args: vec![2],
@@ -1957,7 +2139,7 @@ for a in x.zip y
                     )),
                     Lookup((LookupNode::Id(constant(2)), Some(3))),
                     Lookup((LookupNode::Root(1), Some(4))), // ast 5
-                    Id(constant(0)),                        // a
+                    Id(constant(0), None),                  // a
                     For(AstFor {
                         args: vec![0],
                         iterable: 5,
@@ -1989,7 +2171,7 @@ a()";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(42),
                     Function(koto_parser::Function {
                         args: vec![],
@@ -1998,12 +2180,13 @@ a()";
                         body: 1,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }),
                     Assign {
                         target: 0,
                         expression: 2,
                     },
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Lookup((
                         LookupNode::Call {
                             args: vec![],
@@ -2037,10 +2220,10 @@ a()";
             check_ast_for_equivalent_sources(
                 &sources,
                 &[
-                    Id(constant(0)),
-                    Id(constant(1)),
-                    Id(constant(0)),
-                    Id(constant(1)),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
                     BinaryOp {
                         op: AstBinaryOp::Add,
                         lhs: 2,
@@ -2053,6 +2236,7 @@ a()";
                         body: 4,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }), // 5
                     MainBlock {
                         body: vec![5],
@@ -2069,10 +2253,10 @@ a()";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
-                    Id(constant(1)),
-                    Id(constant(0)),
-                    Id(constant(1)),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
                     Lookup((
                         LookupNode::Call {
                             args: vec![],
@@ -2094,6 +2278,7 @@ a()";
                         body: 7,
                         is_variadic: true,
                         is_generator: false,
+                        output_type: None,
                     }),
                     MainBlock {
                         body: vec![8],
@@ -2118,15 +2303,15 @@ f 42";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // f
-                    Id(constant(1)), // x
-                    Id(constant(2)), // y
-                    Id(constant(1)), // x
+                    Id(constant(0), None), // f
+                    Id(constant(1), None), // x
+                    Id(constant(2), None), // y
+                    Id(constant(1), None), // x
                     Assign {
                         target: 2,
                         expression: 3,
                     },
-                    Id(constant(2)), // 5
+                    Id(constant(2), None), // 5
                     Block(vec![4, 5]),
                     Function(koto_parser::Function {
                         args: vec![1],
@@ -2135,6 +2320,7 @@ f 42";
                         body: 6,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }),
                     Assign {
                         target: 0,
@@ -2165,11 +2351,11 @@ f 42";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // f
-                    Id(constant(1)), // x
-                    Id(constant(2)), // y
-                    Id(constant(3)), // z
-                    Id(constant(3)), // z
+                    Id(constant(0), None), // f
+                    Id(constant(1), None), // x
+                    Id(constant(2), None), // y
+                    Id(constant(3), None), // z
+                    Id(constant(3), None), // z
                     Function(koto_parser::Function {
                         args: vec![3],
                         local_count: 1,
@@ -2177,12 +2363,13 @@ f 42";
                         body: 4,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }), // 5
                     Assign {
                         target: 2,
                         expression: 5,
                     },
-                    Id(constant(1)), // x
+                    Id(constant(1), None), // x
                     NamedCall {
                         id: constant(2), // y
                         args: vec![7],
@@ -2195,6 +2382,7 @@ f 42";
                         body: 9,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }), // 10
                     Assign {
                         target: 0,
@@ -2225,8 +2413,8 @@ f 42";
             check_ast(
                 source,
                 &[
-                    Id(constant(1)),
-                    Id(constant(1)),
+                    Id(constant(1), None),
+                    Id(constant(1), None),
                     UnaryOp {
                         op: AstUnaryOp::Negate,
                         value: 1,
@@ -2250,7 +2438,7 @@ f 42";
             check_ast(
                 source,
                 &[
-                    Id(constant(1)),
+                    Id(constant(1), None),
                     SmallInt(1),
                     BinaryOp {
                         op: AstBinaryOp::Subtract,
@@ -2290,9 +2478,9 @@ f(x,
             check_ast_for_equivalent_sources(
                 &sources,
                 &[
-                    Id(constant(0)),
-                    Id(constant(1)),
-                    Id(constant(1)),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
+                    Id(constant(1), None),
                     UnaryOp {
                         op: AstUnaryOp::Negate,
                         value: 2,
@@ -2323,8 +2511,8 @@ foo
             check_ast(
                 source,
                 &[
-                    Id(constant(1)),
-                    Id(constant(2)),
+                    Id(constant(1), None),
+                    Id(constant(2), None),
                     NamedCall {
                         id: constant(0), // foo
                         args: vec![0, 1],
@@ -2347,9 +2535,9 @@ foo
             check_ast(
                 source,
                 &[
-                    Id(constant(1)),
-                    Id(constant(2)),
-                    Id(constant(2)),
+                    Id(constant(1), None),
+                    Id(constant(2), None),
+                    Id(constant(2), None),
                     Function(koto_parser::Function {
                         args: vec![1],
                         local_count: 1,
@@ -2357,6 +2545,7 @@ foo
                         body: 2,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }),
                     NamedCall {
                         id: constant(0), // foo
@@ -2380,12 +2569,12 @@ f x";
             check_ast(
                 source,
                 &[
-                    Id(constant(1)),
+                    Id(constant(1), None),
                     NamedCall {
                         id: constant(0),
                         args: vec![0],
                     },
-                    Id(constant(1)),
+                    Id(constant(1), None),
                     NamedCall {
                         id: constant(0),
                         args: vec![2],
@@ -2405,9 +2594,9 @@ f x";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // f
-                    Id(constant(1)), // x
-                    Id(constant(1)), // x
+                    Id(constant(0), None), // f
+                    Id(constant(1), None), // x
+                    Id(constant(1), None), // x
                     NamedCall {
                         id: constant(0), // f
                         args: vec![2],
@@ -2419,6 +2608,7 @@ f x";
                         body: 3,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }),
                     Assign {
                         target: 0,
@@ -2439,10 +2629,10 @@ f x";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // f
-                    Id(constant(1)), // g
-                    Id(constant(2)), // x
-                    Id(constant(2)),
+                    Id(constant(0), None), // f
+                    Id(constant(1), None), // g
+                    Id(constant(2), None), // x
+                    Id(constant(2), None),
                     NamedCall {
                         id: constant(0),
                         args: vec![3],
@@ -2454,10 +2644,11 @@ f x";
                         body: 4,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }), // 5
                     Nested(5),
-                    Id(constant(2)), // x
-                    Id(constant(2)), // x
+                    Id(constant(2), None), // x
+                    Id(constant(2), None), // x
                     NamedCall {
                         id: constant(1), // g
                         args: vec![8],
@@ -2469,6 +2660,7 @@ f x";
                         body: 9,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }), // 10
                     Nested(10),
                     TempTuple(vec![6, 11]),
@@ -2491,18 +2683,18 @@ f x";
             check_ast(
                 source,
                 &[
-                    Id(constant(1)), // x
+                    Id(constant(1), None), // x
                     NamedCall {
                         id: constant(0), // f
                         args: vec![0],
                     },
-                    Id(constant(2)), // g
+                    Id(constant(2), None), // g
                     BinaryOp {
                         op: AstBinaryOp::Pipe,
                         lhs: 1,
                         rhs: 2,
                     },
-                    Id(constant(3)), // h
+                    Id(constant(3), None), // h
                     BinaryOp {
                         op: AstBinaryOp::Pipe,
                         lhs: 3,
@@ -2532,8 +2724,8 @@ foo.bar x
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // foo
-                    Id(constant(2)), // x
+                    Id(constant(0), None), // foo
+                    Id(constant(2), None), // x
                     Lookup((
                         LookupNode::Call {
                             args: vec![1],
@@ -2543,13 +2735,13 @@ foo.bar x
                     )),
                     Lookup((LookupNode::Id(constant(1)), Some(2))),
                     Lookup((LookupNode::Root(0), Some(3))),
-                    Id(constant(3)), // 5 - y
+                    Id(constant(3), None), // 5 - y
                     BinaryOp {
                         op: AstBinaryOp::Pipe,
                         lhs: 4,
                         rhs: 5,
                     },
-                    Id(constant(4)), // z
+                    Id(constant(4), None), // z
                     BinaryOp {
                         op: AstBinaryOp::Pipe,
                         lhs: 6,
@@ -2577,11 +2769,11 @@ foo.bar x
                 source,
                 &[
                     SmallInt(42),
-                    Id(constant(2)), // x
-                    Self_,           // self
+                    Id(constant(2), None), // x
+                    Self_,                 // self
                     Lookup((LookupNode::Id(constant(0)), None)),
                     Lookup((LookupNode::Root(2), Some(3))),
-                    Id(constant(2)), // 5
+                    Id(constant(2), None), // 5
                     Assign {
                         target: 4,
                         expression: 5,
@@ -2593,6 +2785,7 @@ foo.bar x
                         body: 6,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }),
                     Map(vec![
                         (MapKey::Id(constant(0)), Some(0)),
@@ -2621,8 +2814,8 @@ f = ||
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
-                    Id(constant(2)),
+                    Id(constant(0), None),
+                    Id(constant(2), None),
                     SmallInt(0),
                     Map(vec![
                         (MapKey::Id(constant(1)), Some(1)),
@@ -2635,6 +2828,7 @@ f = ||
                         body: 3,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }),
                     Assign {
                         target: 0,
@@ -2665,8 +2859,8 @@ f = ||
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // f
-                    Id(constant(3)), // x
+                    Id(constant(0), None), // f
+                    Id(constant(3), None), // x
                     Map(vec![
                         (MapKey::Id(constant(2)), Some(1)), // bar: x
                     ]),
@@ -2682,6 +2876,7 @@ f = ||
                         body: 4,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }), // 5
                     Assign {
                         target: 0,
@@ -2712,13 +2907,13 @@ f()";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(42),
-                    Id(constant(3)), // x
+                    Id(constant(3), None), // x
                     Self_,
                     Lookup((LookupNode::Id(constant(1)), None)),
                     Lookup((LookupNode::Root(3), Some(4))), // 5
-                    Id(constant(3)),
+                    Id(constant(3), None),
                     Assign {
                         target: 5,
                         expression: 6,
@@ -2730,6 +2925,7 @@ f()";
                         body: 7,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }), // 10
                     Map(vec![
                         (MapKey::Id(constant(1)), Some(1)),
@@ -2742,12 +2938,13 @@ f()";
                         body: 9,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }),
                     Assign {
                         target: 0,
                         expression: 10,
                     },
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Lookup((
                         LookupNode::Call {
                             args: vec![],
@@ -2783,26 +2980,26 @@ f = |n|
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // f
-                    Id(constant(1)), // n
-                    Id(constant(2)), // f2
-                    Id(constant(1)),
-                    Id(constant(3)), // i
-                    SmallInt(0),     // ast 5
+                    Id(constant(0), None), // f
+                    Id(constant(1), None), // n
+                    Id(constant(2), None), // f2
+                    Id(constant(1), None),
+                    Id(constant(3), None), // i
+                    SmallInt(0),           // ast 5
                     SmallInt(1),
                     Range {
                         start: 5,
                         end: 6,
                         inclusive: false,
                     },
-                    Id(constant(3)), // i
-                    Id(constant(1)),
+                    Id(constant(3), None), // i
+                    Id(constant(1), None),
                     BinaryOp {
                         op: AstBinaryOp::Equal,
                         lhs: 8,
                         rhs: 9,
                     }, // ast 10
-                    Id(constant(3)),
+                    Id(constant(3), None),
                     Return(Some(11)),
                     If(AstIf {
                         condition: 10,
@@ -2822,12 +3019,13 @@ f = |n|
                         body: 14,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }), // ast 15
                     Assign {
                         target: 2,
                         expression: 15,
                     },
-                    Id(constant(2)),
+                    Id(constant(2), None),
                     Block(vec![16, 17]),
                     Function(koto_parser::Function {
                         args: vec![1],
@@ -2836,6 +3034,7 @@ f = |n|
                         body: 18,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }),
                     Assign {
                         target: 0,
@@ -2865,8 +3064,8 @@ f = |n|
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
-                    Id(constant(0)),
+                    Id(constant(0), None),
+                    Id(constant(0), None),
                     SmallInt(1),
                     BinaryOp {
                         op: AstBinaryOp::Add,
@@ -2877,7 +3076,7 @@ f = |n|
                         target: 0,
                         expression: 3,
                     },
-                    Id(constant(0)), // 5
+                    Id(constant(0), None), // 5
                     Block(vec![4, 5]),
                     Function(koto_parser::Function {
                         args: vec![],
@@ -2886,6 +3085,7 @@ f = |n|
                         body: 6,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }),
                     MainBlock {
                         body: vec![7],
@@ -2905,15 +3105,15 @@ f = |n|
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
-                    Id(constant(1)),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
                     SmallInt(1),
                     Assign {
                         target: 1,
                         expression: 2,
                     },
                     Nested(3),
-                    Id(constant(1)), // 5
+                    Id(constant(1), None), // 5
                     Tuple(vec![4, 5]),
                     Assign {
                         target: 0,
@@ -2926,6 +3126,7 @@ f = |n|
                         body: 7,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }),
                     MainBlock {
                         body: vec![8],
@@ -2944,7 +3145,7 @@ f = |n|
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(1),
                     BinaryOp {
                         op: AstBinaryOp::AddAssign,
@@ -2958,6 +3159,7 @@ f = |n|
                         body: 2,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }),
                     MainBlock {
                         body: vec![3],
@@ -2976,7 +3178,7 @@ y z";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // z
+                    Id(constant(0), None), // z
                     SmallInt(0),
                     SmallInt(20),
                     Range {
@@ -2985,8 +3187,8 @@ y z";
                         inclusive: false,
                     },
                     List(vec![3]),
-                    Id(constant(2)), // 5 - x
-                    Id(constant(2)),
+                    Id(constant(2), None), // 5 - x
+                    Id(constant(2), None),
                     SmallInt(1),
                     BinaryOp {
                         op: AstBinaryOp::Greater,
@@ -3000,6 +3202,7 @@ y z";
                         body: 8,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }),
                     NamedCall {
                         id: constant(1), // y
@@ -3009,7 +3212,7 @@ y z";
                         target: 0,
                         expression: 10,
                     },
-                    Id(constant(0)), // z
+                    Id(constant(0), None), // z
                     NamedCall {
                         id: constant(1), // y
                         args: vec![12],
@@ -3038,6 +3241,7 @@ y z";
                         body: 1,
                         is_variadic: false,
                         is_generator: true,
+                        output_type: None,
                     }),
                     MainBlock {
                         body: vec![2],
@@ -3065,6 +3269,7 @@ y z";
                         body: 3,
                         is_variadic: false,
                         is_generator: true,
+                        output_type: None,
                     }),
                     MainBlock {
                         body: vec![4],
@@ -3095,6 +3300,7 @@ y z";
                         body: 2,
                         is_variadic: false,
                         is_generator: true,
+                        output_type: None,
                     }),
                     MainBlock {
                         body: vec![3],
@@ -3125,15 +3331,15 @@ y z";
             check_ast_for_equivalent_sources(
                 &sources,
                 &[
-                    Id(constant(0)), // a
+                    Id(constant(0), None), // a
                     Wildcard(None),
                     Ellipsis(Some(constant(1))), // others
-                    Id(constant(2)),             // c
+                    Id(constant(2), None),       // c
                     Wildcard(Some(constant(3))), // d
                     Tuple(vec![2, 3, 4]),        // ast index 5
                     Tuple(vec![1, 5]),
                     Wildcard(Some(constant(4))), // e
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Function(koto_parser::Function {
                         args: vec![0, 6, 7],
                         local_count: 3,
@@ -3141,6 +3347,7 @@ y z";
                         body: 8,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }),
                     MainBlock {
                         body: vec![9],
@@ -3157,6 +3364,63 @@ y z";
             )
         }
 
+        #[test]
+        fn unpack_call_args_map() {
+            let source = "f = |{x, y = z}| x + y";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0), None),
+                    Id(constant(3), None),
+                    MapPattern {
+                        entries: vec![
+                            MapPatternEntry {
+                                key: MapKey::Id(constant(1)),
+                                pattern: None,
+                                default: None,
+                            },
+                            MapPatternEntry {
+                                key: MapKey::Id(constant(2)),
+                                pattern: None,
+                                default: Some(1),
+                            },
+                        ],
+                        rest: None,
+                    },
+                    Id(constant(1), None),
+                    Id(constant(2), None),
+                    BinaryOp {
+                        op: AstBinaryOp::Add,
+                        lhs: 3,
+                        rhs: 4,
+                    }, // 5
+                    Function(koto_parser::Function {
+                        args: vec![2],
+                        local_count: 2,
+                        accessed_non_locals: vec![constant(3)],
+                        body: 5,
+                        is_variadic: false,
+                        is_generator: false,
+                        output_type: None,
+                    }),
+                    Assign {
+                        target: 0,
+                        expression: 6,
+                    },
+                    MainBlock {
+                        body: vec![7],
+                        local_count: 1,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("f"),
+                    Constant::Str("x"),
+                    Constant::Str("y"),
+                    Constant::Str("z"),
+                ]),
+            )
+        }
+
         #[test]
         fn unpack_call_args_list() {
             let sources = [
@@ -3177,15 +3441,15 @@ y z";
             check_ast_for_equivalent_sources(
                 &sources,
                 &[
-                    Id(constant(0)), // a
+                    Id(constant(0), None), // a
                     Wildcard(None),
-                    Id(constant(1)),             // c
+                    Id(constant(1), None),       // c
                     Wildcard(Some(constant(2))), // d
                     Ellipsis(None),              // ...
                     List(vec![2, 3, 4]),         // ast index 5
                     List(vec![1, 5]),
-                    Id(constant(3)), // e
-                    Id(constant(0)),
+                    Id(constant(3), None), // e
+                    Id(constant(0), None),
                     Function(koto_parser::Function {
                         args: vec![0, 6, 7],
                         local_count: 3,
@@ -3193,6 +3457,7 @@ y z";
                         body: 8,
                         is_variadic: false,
                         is_generator: false,
+                        output_type: None,
                     }),
                     MainBlock {
                         body: vec![9],
@@ -3219,11 +3484,11 @@ y z";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(0),
                     Lookup((LookupNode::Index(1), None)),
                     Lookup((LookupNode::Root(0), Some(2))),
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(1), // 5
                     Lookup((LookupNode::Index(5), None)),
                     Lookup((LookupNode::Root(4), Some(6))),
@@ -3246,7 +3511,7 @@ y z";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     RangeFull,
                     Lookup((LookupNode::Index(1), None)),
                     Lookup((LookupNode::Root(0), Some(2))),
@@ -3265,7 +3530,7 @@ y z";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(3),
                     RangeTo {
                         end: 1,
@@ -3288,7 +3553,7 @@ y z";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(10),
                     RangeFrom { start: 1 },
                     SmallInt(0),
@@ -3310,7 +3575,7 @@ y z";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Lookup((LookupNode::Id(constant(1)), None)),
                     Lookup((LookupNode::Root(0), Some(1))),
                     MainBlock {
@@ -3328,7 +3593,7 @@ y z";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Lookup((
                         LookupNode::Call {
                             args: vec![],
@@ -3353,7 +3618,7 @@ y z";
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Lookup((
                         LookupNode::Call {
                             args: vec![],
@@ -3386,7 +3651,7 @@ x.bar()."baz" = 1
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Lookup((
                         LookupNode::Str(AstString {
                             quotation_mark: QuotationMark::Double,
@@ -3427,7 +3692,7 @@ x.bar()."baz" = 1
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(42),
                     Lookup((
                         LookupNode::Call {
@@ -3456,7 +3721,7 @@ x.foo
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(42),
                     Lookup((
                         LookupNode::Call {
@@ -3485,7 +3750,7 @@ x.takes_a_map
             check_ast(
                 source,
                 &[
-                    Id(constant(0)), // x
+                    Id(constant(0), None), // x
                     SmallInt(42),
                     Map(vec![
                         (MapKey::Id(constant(2)), Some(1)), // foo: 42
@@ -3535,10 +3800,10 @@ x.takes_a_map
             check_ast_for_equivalent_sources(
                 &sources,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Lookup((LookupNode::Id(constant(1)), None)),
                     Lookup((LookupNode::Root(0), Some(1))),
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Lookup((LookupNode::Id(constant(2)), None)),
                     Lookup((LookupNode::Root(3), Some(4))), // 5
                     List(vec![2, 5]),
@@ -3561,7 +3826,7 @@ x.takes_a_map
             check_ast(
                 source,
                 &[
-                    Id(constant(1)), // x
+                    Id(constant(1), None), // x
                     NamedCall {
                         id: constant(0), // f
                         args: vec![0],
@@ -3584,7 +3849,7 @@ x.takes_a_map
             check_ast(
                 source,
                 &[
-                    Id(constant(1)), // x
+                    Id(constant(1), None), // x
                     NamedCall {
                         id: constant(0), // f
                         args: vec![0],
@@ -3608,13 +3873,13 @@ x.takes_a_map
             check_ast(
                 source,
                 &[
-                    Id(constant(1)), // x
+                    Id(constant(1), None), // x
                     NamedCall {
                         id: constant(0), // f
                         args: vec![0],
                     },
                     Nested(1),
-                    Id(constant(2)), // y
+                    Id(constant(2), None), // y
                     Lookup((
                         LookupNode::Call {
                             args: vec![3],
@@ -3664,7 +3929,7 @@ x.takes_a_map
                 source,
                 &[
                     string_literal(0, QuotationMark::Single),
-                    Id(constant(2)),
+                    Id(constant(2), None),
                     Lookup((
                         LookupNode::Call {
                             args: vec![1],
@@ -3706,11 +3971,11 @@ x = ( 0
             check_ast_for_equivalent_sources(
                 &sources,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(0),
                     SmallInt(1),
                     Tuple(vec![1, 2]),
-                    Id(constant(2)),
+                    Id(constant(2), None),
                     Lookup((
                         LookupNode::Call {
                             args: vec![4],
@@ -3755,11 +4020,11 @@ x = [ 0
             check_ast_for_equivalent_sources(
                 &sources,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(0),
                     SmallInt(1),
                     List(vec![1, 2]),
-                    Id(constant(2)),
+                    Id(constant(2), None),
                     Lookup((
                         LookupNode::Call {
                             args: vec![4],
@@ -3810,7 +4075,7 @@ x = { y
             check_ast_for_equivalent_sources(
                 &sources,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Map(vec![
                         (MapKey::Id(constant(1)), None),
                         (MapKey::Id(constant(2)), None),
@@ -3914,9 +4179,9 @@ x = { y
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Nested(0),
-                    Id(constant(2)),
+                    Id(constant(2), None),
                     Lookup((
                         LookupNode::Call {
                             args: vec![2],
@@ -3950,7 +4215,7 @@ x.iter()
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(1),
                     Lookup((
                         LookupNode::Call {
@@ -4001,10 +4266,10 @@ foo.bar
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Lookup((LookupNode::Id(constant(1)), None)),
                     Lookup((LookupNode::Root(0), Some(1))),
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Lookup((LookupNode::Id(constant(2)), None)),
                     Lookup((LookupNode::Root(3), Some(4))), // 5
                     BinaryOp {
@@ -4074,8 +4339,8 @@ assert_eq x, "hello"
                         op: AstUnaryOp::Not,
                         value: 0,
                     },
-                    Id(constant(0)),
-                    Id(constant(0)),
+                    Id(constant(0), None),
+                    Id(constant(0), None),
                     BinaryOp {
                         op: AstBinaryOp::Add,
                         lhs: 2,
@@ -4085,7 +4350,7 @@ assert_eq x, "hello"
                         expression_string: constant(1),
                         expression: 4,
                     }, // 5
-                    Id(constant(0)), // x
+                    Id(constant(0), None), // x
                     string_literal(3, QuotationMark::Double),
                     NamedCall {
                         id: constant(2),
@@ -4164,7 +4429,7 @@ assert_eq x, "hello"
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Import {
                         from: vec![import_id(1)],
                         items: vec![import_id(2)],
@@ -4298,7 +4563,7 @@ catch e
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Lookup((
                         LookupNode::Call {
                             args: vec![],
@@ -4307,8 +4572,8 @@ catch e
                         None,
                     )),
                     Lookup((LookupNode::Root(0), Some(1))),
-                    Id(constant(1)), // e
-                    Id(constant(1)),
+                    Id(constant(1), None), // e
+                    Id(constant(1), None),
                     Debug {
                         expression_string: constant(1),
                         expression: 4,
@@ -4339,9 +4604,9 @@ catch _
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Wildcard(None),
-                    Id(constant(1)),
+                    Id(constant(1), None),
                     Try(AstTry {
                         try_block: 0,
                         catch_arg: 1,
@@ -4368,9 +4633,9 @@ catch _error
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),             // x
+                    Id(constant(0), None),       // x
                     Wildcard(Some(constant(1))), // error
-                    Id(constant(2)),             // y
+                    Id(constant(2), None),       // y
                     Try(AstTry {
                         try_block: 0,
                         catch_arg: 1,
@@ -4403,7 +4668,7 @@ finally
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Lookup((
                         LookupNode::Call {
                             args: vec![],
@@ -4412,8 +4677,8 @@ finally
                         None,
                     )),
                     Lookup((LookupNode::Root(0), Some(1))),
-                    Id(constant(1)), // e
-                    Id(constant(1)),
+                    Id(constant(1), None), // e
+                    Id(constant(1), None),
                     Debug {
                         expression_string: constant(1),
                         expression: 4,
@@ -4440,7 +4705,7 @@ finally
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Throw(0),
                     MainBlock {
                         body: vec![1],
@@ -4478,7 +4743,7 @@ throw
             check_ast(
                 source,
                 &[
-                    Id(constant(1)),
+                    Id(constant(1), None),
                     string_literal(3, QuotationMark::Double),
                     Map(vec![
                         (MapKey::Id(constant(0)), Some(0)),
@@ -4513,12 +4778,12 @@ x = match y
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
-                    Id(constant(1)),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
                     SmallInt(0),
                     SmallInt(1),
                     SmallInt(42),
-                    Id(constant(2)), // 5
+                    Id(constant(2), None), // 5
                     SmallInt(-1),
                     Match {
                         expression: 1,
@@ -4558,7 +4823,7 @@ match x
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     string_literal(1, QuotationMark::Single),
                     SmallInt(99),
                     string_literal(2, QuotationMark::Double),
@@ -4603,18 +4868,18 @@ match (x, y, z)
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
-                    Id(constant(1)),
-                    Id(constant(2)),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
+                    Id(constant(2), None),
                     Tuple(vec![0, 1, 2]),
                     SmallInt(0),
-                    Id(constant(3)), // 5
+                    Id(constant(3), None), // 5
                     Wildcard(None),
                     Tuple(vec![4, 5, 6]),
-                    Id(constant(3)),
+                    Id(constant(3), None),
                     Wildcard(None),
                     SmallInt(0), // 10
-                    Id(constant(4)),
+                    Id(constant(4), None),
                     Tuple(vec![10, 11]),
                     Wildcard(Some(constant(5))),
                     Tuple(vec![9, 12, 13]),
@@ -4660,7 +4925,7 @@ match x
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Ellipsis(None),
                     SmallInt(0),
                     Tuple(vec![1, 2]),
@@ -4703,7 +4968,7 @@ match y
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Ellipsis(Some(constant(1))),
                     SmallInt(0),
                     SmallInt(1),
@@ -4755,9 +5020,9 @@ match x
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
-                    Id(constant(1)),
-                    Id(constant(1)),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
+                    Id(constant(1), None),
                     SmallInt(5),
                     BinaryOp {
                         op: AstBinaryOp::Greater,
@@ -4765,8 +5030,8 @@ match x
                         rhs: 3,
                     },
                     SmallInt(0), // 5
-                    Id(constant(1)),
-                    Id(constant(1)),
+                    Id(constant(1), None),
+                    Id(constant(1), None),
                     SmallInt(10),
                     BinaryOp {
                         op: AstBinaryOp::Less,
@@ -4774,7 +5039,7 @@ match x
                         rhs: 8,
                     },
                     SmallInt(1), // 10
-                    Id(constant(1)),
+                    Id(constant(1), None),
                     SmallInt(-1),
                     Match {
                         expression: 0,
@@ -4817,8 +5082,8 @@ match x, y
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
-                    Id(constant(1)),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
                     TempTuple(vec![0, 1]),
                     SmallInt(0),
                     SmallInt(1),
@@ -4826,12 +5091,12 @@ match x, y
                     SmallInt(2),
                     SmallInt(3),
                     TempTuple(vec![6, 7]),
-                    Id(constant(2)),
+                    Id(constant(2), None),
                     SmallInt(0), // 10
-                    Id(constant(3)),
+                    Id(constant(3), None),
                     Null,
                     TempTuple(vec![11, 12]),
-                    Id(constant(3)),
+                    Id(constant(3), None),
                     SmallInt(0), // 15
                     Match {
                         expression: 2,
@@ -4877,7 +5142,7 @@ match x.foo 42
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(42),
                     Lookup((
                         LookupNode::Call {
@@ -4924,8 +5189,8 @@ match x
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
-                    Id(constant(1)),
+                    Id(constant(0), None),
+                    Id(constant(1), None),
                     Lookup((LookupNode::Id(constant(2)), None)),
                     Lookup((LookupNode::Root(1), Some(2))),
                     SmallInt(0),
@@ -4956,7 +5221,7 @@ match x
             check_ast(
                 source,
                 &[
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     SmallInt(0),
                     SmallInt(1),
                     string_literal(1, QuotationMark::Single),
@@ -4985,6 +5250,114 @@ match x
             )
         }
 
+        #[test]
+        fn match_map_pattern() {
+            let source = "
+match x
+  {type: 'click', 'pos': (a, b), rest...} then a
+  {y} then y
+";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0), None),
+                    string_literal(2, QuotationMark::Single),
+                    Id(constant(4), None),
+                    Id(constant(5), None),
+                    Tuple(vec![2, 3]),
+                    Ellipsis(Some(constant(6))), // 5
+                    MapPattern {
+                        entries: vec![
+                            MapPatternEntry {
+                                key: MapKey::Id(constant(1)),
+                                pattern: Some(1),
+                                default: None,
+                            },
+                            MapPatternEntry {
+                                key: string_literal_map_key(3, QuotationMark::Single),
+                                pattern: Some(4),
+                                default: None,
+                            },
+                        ],
+                        rest: Some(5),
+                    },
+                    Id(constant(4), None),
+                    MapPattern {
+                        entries: vec![MapPatternEntry {
+                            key: MapKey::Id(constant(7)),
+                            pattern: None,
+                            default: None,
+                        }],
+                        rest: None,
+                    },
+                    Id(constant(7), None),
+                    Match {
+                        expression: 0,
+                        arms: vec![
+                            MatchArm {
+                                patterns: vec![6],
+                                condition: None,
+                                expression: 7,
+                            },
+                            MatchArm {
+                                patterns: vec![8],
+                                condition: None,
+                                expression: 9,
+                            },
+                        ],
+                    }, // 10
+                    MainBlock {
+                        body: vec![10],
+                        local_count: 4,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("x"),
+                    Constant::Str("type"),
+                    Constant::Str("click"),
+                    Constant::Str("pos"),
+                    Constant::Str("a"),
+                    Constant::Str("b"), // 5
+                    Constant::Str("rest"),
+                    Constant::Str("y"),
+                ]),
+            )
+        }
+
+        #[test]
+        fn match_typed_id_pattern() {
+            let source = "
+match x
+  n: Number then n
+";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0), None),
+                    Type(constant(2)),
+                    Id(constant(1), Some(1)),
+                    Id(constant(1), None),
+                    Match {
+                        expression: 0,
+                        arms: vec![MatchArm {
+                            patterns: vec![2],
+                            condition: None,
+                            expression: 3,
+                        }],
+                    },
+                    MainBlock {
+                        body: vec![4],
+                        local_count: 1,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("x"),
+                    Constant::Str("n"),
+                    Constant::Str("Number"),
+                ]),
+            )
+        }
+
         #[test]
         fn switch_expression() {
             let source = "
@@ -5004,15 +5377,15 @@ switch
                         rhs: 1,
                     },
                     SmallInt(0),
-                    Id(constant(0)),
-                    Id(constant(1)), // 5
+                    Id(constant(0), None),
+                    Id(constant(1), None), // 5
                     BinaryOp {
                         op: AstBinaryOp::Greater,
                         lhs: 4,
                         rhs: 5,
                     },
                     SmallInt(1),
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Switch(vec![
                         SwitchArm {
                             condition: Some(2),
@@ -5048,7 +5421,7 @@ switch
                 &[
                     BoolTrue,
                     SmallInt(1),
-                    Id(constant(0)),
+                    Id(constant(0), None),
                     Debug {
                         expression_string: constant(0),
                         expression: 2,
@@ -5072,4 +5445,186 @@ switch
             )
         }
     }
+
+    mod type_hints {
+        use super::*;
+
+        #[test]
+        fn function_args_and_output() {
+            let source = "
+|x: Number, y| -> String
+  x
+";
+            check_ast(
+                source,
+                &[
+                    Type(constant(1)),
+                    Id(constant(0), Some(0)),
+                    Id(constant(2), None),
+                    Type(constant(3)),
+                    Id(constant(0), None),
+                    Function(koto_parser::Function {
+                        args: vec![1, 2],
+                        local_count: 2,
+                        accessed_non_locals: vec![],
+                        body: 4,
+                        is_variadic: false,
+                        is_generator: false,
+                        output_type: Some(3),
+                    }), // 5
+                    MainBlock {
+                        body: vec![5],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("x"),
+                    Constant::Str("Number"),
+                    Constant::Str("y"),
+                    Constant::Str("String"),
+                ]),
+            )
+        }
+
+        #[test]
+        fn assignment() {
+            let source = "x: String = 'hello'";
+            check_ast(
+                source,
+                &[
+                    Type(constant(1)),
+                    Id(constant(0), Some(0)),
+                    string_literal(2, QuotationMark::Single),
+                    Assign {
+                        target: 1,
+                        expression: 2,
+                    },
+                    MainBlock {
+                        body: vec![3],
+                        local_count: 1,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("x"),
+                    Constant::Str("String"),
+                    Constant::Str("hello"),
+                ]),
+            )
+        }
+
+        #[test]
+        fn map_block_with_id_value() {
+            let source = "
+x =
+  foo: bar
+";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0), None),
+                    Id(constant(2), None),
+                    Map(vec![(MapKey::Id(constant(1)), Some(1))]),
+                    Assign {
+                        target: 0,
+                        expression: 2,
+                    },
+                    MainBlock {
+                        body: vec![3],
+                        local_count: 1,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("x"),
+                    Constant::Str("foo"),
+                    Constant::Str("bar"),
+                ]),
+            )
+        }
+
+        #[test]
+        fn map_block_with_assignment_value() {
+            let source = "
+x =
+  foo: bar = 1
+";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0), None),
+                    Id(constant(2), None),
+                    SmallInt(1),
+                    Assign {
+                        target: 1,
+                        expression: 2,
+                    },
+                    Map(vec![(MapKey::Id(constant(1)), Some(3))]),
+                    Assign {
+                        target: 0,
+                        expression: 4,
+                    }, // 5
+                    MainBlock {
+                        body: vec![5],
+                        local_count: 2,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("x"),
+                    Constant::Str("foo"),
+                    Constant::Str("bar"),
+                ]),
+            )
+        }
+
+        #[test]
+        fn assignment_in_indented_block() {
+            let source = "
+f = ||
+  x = 0
+  y: Number = x
+";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0), None),
+                    Id(constant(1), None),
+                    SmallInt(0),
+                    Assign {
+                        target: 1,
+                        expression: 2,
+                    },
+                    Type(constant(3)),
+                    Id(constant(2), Some(4)), // 5
+                    Id(constant(1), None),
+                    Assign {
+                        target: 5,
+                        expression: 6,
+                    },
+                    Block(vec![3, 7]),
+                    Function(koto_parser::Function {
+                        args: vec![],
+                        local_count: 2,
+                        accessed_non_locals: vec![],
+                        body: 8,
+                        is_variadic: false,
+                        is_generator: false,
+                        output_type: None,
+                    }),
+                    Assign {
+                        target: 0,
+                        expression: 9,
+                    }, // 10
+                    MainBlock {
+                        body: vec![10],
+                        local_count: 1,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("f"),
+                    Constant::Str("x"),
+                    Constant::Str("y"),
+                    Constant::Str("Number"),
+                ]),
+            )
+        }
+    }
 }
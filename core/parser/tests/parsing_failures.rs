@@ -222,6 +222,28 @@ x = {'y'}
             }
         }
 
+        mod map_destructuring {
+            use super::*;
+
+            #[test]
+            fn literal_as_target() {
+                let source = "{x: 1} = foo";
+                check_parsing_fails(source);
+            }
+
+            #[test]
+            fn missing_default_value() {
+                let source = "{x, y =} = foo";
+                check_parsing_fails(source);
+            }
+
+            #[test]
+            fn missing_default_value_in_function_arg() {
+                let source = "f = |{x =}| x";
+                check_parsing_fails(source);
+            }
+        }
+
         mod lists {
             use super::*;
 
@@ -300,6 +322,24 @@ match x
 match
   0 if true then 1
   else 2
+";
+                check_parsing_fails(source);
+            }
+
+            #[test]
+            fn map_pattern_with_template_string_key() {
+                let source = "
+match x
+  {'foo${y}': 1} then 1
+";
+                check_parsing_fails(source);
+            }
+
+            #[test]
+            fn map_pattern_with_rest_not_in_last_position() {
+                let source = "
+match x
+  {rest..., y} then 1
 ";
                 check_parsing_fails(source);
             }
@@ -342,6 +382,21 @@ switch
                 check_parsing_fails("'hello, ${name'");
             }
 
+            #[test]
+            fn invalid_format_options() {
+                check_parsing_fails("'${x:>8q}'");
+            }
+
+            #[test]
+            fn unknown_representation_in_format_options() {
+                check_parsing_fails("'${x:08.2z}'");
+            }
+
+            #[test]
+            fn missing_precision_in_format_options() {
+                check_parsing_fails("'${x:8.}'");
+            }
+
             #[test]
             fn incomplete_template_expression() {
                 check_parsing_fails("'${1 + }'");
@@ -358,6 +413,37 @@ switch
             }
         }
 
+        mod type_hints {
+            use super::*;
+
+            #[test]
+            fn missing_arg_type() {
+                check_parsing_fails("|x: | x");
+            }
+
+            #[test]
+            fn missing_output_type() {
+                check_parsing_fails("|x| -> 42");
+            }
+
+            #[test]
+            fn number_as_type() {
+                check_parsing_fails("|x: 42| x");
+            }
+
+            #[test]
+            fn typed_assignment_in_map_block_position() {
+                // The first line of a block is parsed as a map block entry,
+                // so the following line needs to be a map entry
+                let source = "
+f = ||
+  x: Number = 1
+  x
+";
+                check_parsing_fails(source);
+            }
+        }
+
         mod import {
             use super::*;
 
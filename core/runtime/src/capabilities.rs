@@ -0,0 +1,141 @@
+use crate::{runtime_error, Result};
+use koto_bytecode::ImportPolicy;
+use std::path::{Path, PathBuf};
+
+/// The capabilities that are made available to scripts by the runtime
+///
+/// By default scripts have full access to the file system and the host environment,
+/// and are able to import modules. [Capabilities::sandboxed] disables all capabilities,
+/// and is a good starting point when running untrusted scripts.
+///
+/// Core library functions that need a capability that isn't available throw a permission error.
+///
+/// See [VmSettings::capabilities](crate::VmSettings::capabilities).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The access that scripts have to the file system
+    pub file_system: FileSystemAccess,
+    /// Whether or not scripts can access information about the host environment
+    ///
+    /// e.g. `io.current_dir`, `io.temp_dir`, and `os.name`.
+    pub environment: bool,
+    /// Whether or not scripts can import modules from the file system
+    ///
+    /// Imports are also limited by `file_system`: modules can only be imported from the readable
+    /// directory when access is read-only, and imports are disabled when there's no access.
+    ///
+    /// Items in the prelude can always be imported.
+    pub imports: bool,
+}
+
+impl Capabilities {
+    /// Capabilities that give scripts full access to the file system and the environment
+    pub fn unrestricted() -> Self {
+        Self {
+            file_system: FileSystemAccess::Full,
+            environment: true,
+            imports: true,
+        }
+    }
+
+    /// Capabilities that don't give scripts access to the file system or the environment
+    pub fn sandboxed() -> Self {
+        Self {
+            file_system: FileSystemAccess::None,
+            environment: false,
+            imports: false,
+        }
+    }
+
+    /// Returns the policy that the runtime's module [Loader](koto_bytecode::Loader) should use
+    pub fn import_policy(&self) -> ImportPolicy {
+        if !self.imports {
+            return ImportPolicy::Disabled;
+        }
+
+        match &self.file_system {
+            FileSystemAccess::Full => ImportPolicy::Unrestricted,
+            FileSystemAccess::ReadOnly(root) => ImportPolicy::WithinDirectory(root.clone()),
+            FileSystemAccess::None => ImportPolicy::Disabled,
+        }
+    }
+
+    // Checks that a file can be read by a core library function
+    //
+    // When access is read-only then relative paths are resolved from the readable directory,
+    // and the resolved path is returned.
+    pub(crate) fn check_read(&self, function: &str, path: &str) -> Result<PathBuf> {
+        match &self.file_system {
+            FileSystemAccess::Full => Ok(PathBuf::from(path)),
+            FileSystemAccess::ReadOnly(root) => {
+                let root = match dunce::canonicalize(root) {
+                    Ok(root) => root,
+                    Err(error) => {
+                        return runtime_error!(
+                            "{function}: Unable to access the readable directory: {error}"
+                        )
+                    }
+                };
+                let resolved = match dunce::canonicalize(root.join(path)) {
+                    Ok(resolved) => resolved,
+                    Err(error) => {
+                        return runtime_error!("{function}: Unable to access '{path}': {error}")
+                    }
+                };
+                if resolved.starts_with(&root) {
+                    Ok(resolved)
+                } else {
+                    runtime_error!(
+                        "{function}: Permission denied, '{path}' is outside of the readable directory"
+                    )
+                }
+            }
+            FileSystemAccess::None => {
+                runtime_error!("{function}: Permission denied, file system access is disabled")
+            }
+        }
+    }
+
+    // Checks that a file can be created, modified, or removed by a core library function
+    pub(crate) fn check_write<'a>(&self, function: &str, path: &'a str) -> Result<&'a Path> {
+        match &self.file_system {
+            FileSystemAccess::Full => Ok(Path::new(path)),
+            FileSystemAccess::ReadOnly(_) => {
+                runtime_error!("{function}: Permission denied, file system access is read-only")
+            }
+            FileSystemAccess::None => {
+                runtime_error!("{function}: Permission denied, file system access is disabled")
+            }
+        }
+    }
+
+    // Checks that information about the environment can be accessed by a core library function
+    pub(crate) fn check_environment(&self, function: &str) -> Result<()> {
+        if self.environment {
+            Ok(())
+        } else {
+            runtime_error!("{function}: Permission denied, environment access is disabled")
+        }
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::unrestricted()
+    }
+}
+
+/// The access that scripts have to the file system
+///
+/// See [Capabilities].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileSystemAccess {
+    /// Files can be created, read, and removed anywhere
+    Full,
+    /// Files within the given directory can be read, but not modified
+    ///
+    /// Relative paths are resolved from the directory.
+    ReadOnly(PathBuf),
+    /// The file system can't be accessed
+    None,
+}
@@ -1,13 +1,13 @@
 //! The `io` core library module
 
 use super::string::format;
-use crate::{prelude::*, BufferedFile, Error, Result};
+use crate::{prelude::*, BufferedFile, Error, FileSystemAccess, Result};
 use std::{
     cell::RefCell,
     fmt, fs,
     io::{self, BufRead, Read, Seek, SeekFrom, Write},
     ops::Deref,
-    path::{Path, PathBuf},
+    path::PathBuf,
     rc::Rc,
 };
 
@@ -20,7 +20,11 @@ pub fn make_module() -> KMap {
     result.add_fn("create", {
         move |ctx| match ctx.args() {
             [Str(path)] => {
-                let path = Path::new(path.as_str()).to_path_buf();
+                let path = ctx
+                    .vm
+                    .capabilities()
+                    .check_write("io.create", path)?
+                    .to_path_buf();
                 match fs::File::create(&path) {
                     Ok(file) => Ok(File::system_file(file, path)),
                     Err(error) => runtime_error!("io.create: Error while creating file: {error}"),
@@ -30,7 +34,8 @@ pub fn make_module() -> KMap {
         }
     });
 
-    result.add_fn("current_dir", |_| {
+    result.add_fn("current_dir", |ctx| {
+        ctx.vm.capabilities().check_environment("io.current_dir")?;
         let result = match std::env::current_dir() {
             Ok(path) => Str(path.to_string_lossy().to_string().into()),
             Err(_) => Null,
@@ -39,7 +44,17 @@ pub fn make_module() -> KMap {
     });
 
     result.add_fn("exists", |ctx| match ctx.args() {
-        [Str(path)] => Ok(Bool(fs::canonicalize(path.as_str()).is_ok())),
+        [Str(path)] => {
+            let capabilities = ctx.vm.capabilities();
+            match capabilities.check_read("io.exists", path) {
+                Ok(path) => Ok(Bool(fs::canonicalize(path).is_ok())),
+                // Paths that can't be resolved within a readable directory don't exist
+                Err(_) if matches!(capabilities.file_system, FileSystemAccess::ReadOnly(_)) => {
+                    Ok(Bool(false))
+                }
+                Err(error) => Err(error),
+            }
+        }
         unexpected => type_error_with_slice("a path String as argument", unexpected),
     });
 
@@ -67,13 +82,15 @@ pub fn make_module() -> KMap {
 
     result.add_fn("open", {
         |ctx| match ctx.args() {
-            [Str(path)] => match fs::canonicalize(path.as_str()) {
-                Ok(path) => match fs::File::open(&path) {
-                    Ok(file) => Ok(File::system_file(file, path)),
-                    Err(error) => runtime_error!("io.open: Error while opening path: {error}"),
-                },
-                Err(_) => runtime_error!("io.open: Failed to canonicalize path"),
-            },
+            [Str(path)] => {
+                match fs::canonicalize(ctx.vm.capabilities().check_read("io.open", path)?) {
+                    Ok(path) => match fs::File::open(&path) {
+                        Ok(file) => Ok(File::system_file(file, path)),
+                        Err(error) => runtime_error!("io.open: Error while opening path: {error}"),
+                    },
+                    Err(_) => runtime_error!("io.open: Failed to canonicalize path"),
+                }
+            }
             unexpected => type_error_with_slice("a path String as argument", unexpected),
         }
     });
@@ -118,7 +135,11 @@ pub fn make_module() -> KMap {
     });
 
     result.add_fn("read_to_string", |ctx| match ctx.args() {
-        [Str(path)] => match fs::read_to_string(Path::new(path.as_str())) {
+        [Str(path)] => match fs::read_to_string(
+            ctx.vm
+                .capabilities()
+                .check_read("io.read_to_string", path)?,
+        ) {
             Ok(result) => Ok(result.into()),
             Err(error) => {
                 runtime_error!("io.read_to_string: Unable to read file '{path}': {error}")
@@ -130,7 +151,7 @@ pub fn make_module() -> KMap {
     result.add_fn("remove_file", {
         |ctx| match ctx.args() {
             [Str(path)] => {
-                let path = Path::new(path.as_str());
+                let path = ctx.vm.capabilities().check_write("io.remove_file", path)?;
                 match fs::remove_file(path) {
                     Ok(_) => Ok(Value::Null),
                     Err(error) => runtime_error!(
@@ -147,8 +168,9 @@ pub fn make_module() -> KMap {
     result.add_fn("stdin", |ctx| Ok(File::stdin(ctx.vm)));
     result.add_fn("stdout", |ctx| Ok(File::stdout(ctx.vm)));
 
-    result.add_fn("temp_dir", {
-        |_| Ok(std::env::temp_dir().to_string_lossy().as_ref().into())
+    result.add_fn("temp_dir", |ctx| {
+        ctx.vm.capabilities().check_environment("io.temp_dir")?;
+        Ok(std::env::temp_dir().to_string_lossy().as_ref().into())
     });
 
     result
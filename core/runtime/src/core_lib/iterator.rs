@@ -4,7 +4,11 @@ pub mod adaptors;
 pub mod generators;
 pub mod peekable;
 
-use crate::{prelude::*, KIteratorOutput as Output, Result};
+use crate::{
+    execution_limits::{MAP_ENTRY_SIZE, VALUE_SIZE},
+    prelude::*,
+    KIteratorOutput as Output, Result,
+};
 
 /// Initializes the `iterator` core library module
 pub fn make_module() -> KMap {
@@ -115,7 +119,7 @@ pub fn make_module() -> KMap {
         match ctx.instance_and_args(Value::is_iterable, expected_error)? {
             (iterable, [Value::Number(n)]) => {
                 let iterable = iterable.clone();
-                let n = *n;
+                let n = n.clone();
                 match adaptors::Chunks::new(ctx.vm.make_iterator(iterable)?, n.into()) {
                     Ok(result) => Ok(KIterator::new(result).into()),
                     Err(e) => runtime_error!("iterator.chunks: {}", e),
@@ -647,7 +651,7 @@ pub fn make_module() -> KMap {
         match ctx.instance_and_args(Value::is_iterable, expected_error)? {
             (iterable, [Value::Number(n)]) if *n >= 0.0 => {
                 let iterable = iterable.clone();
-                let n = *n;
+                let n = n.clone();
                 let mut iter = ctx.vm.make_iterator(iterable)?;
 
                 for _ in 0..n.into() {
@@ -698,7 +702,7 @@ pub fn make_module() -> KMap {
         match ctx.instance_and_args(Value::is_iterable, expected_error)? {
             (iterable, [Value::Number(n)]) if *n >= 0.0 => {
                 let iterable = iterable.clone();
-                let n = *n;
+                let n = n.clone();
                 let result = adaptors::Take::new(ctx.vm.make_iterator(iterable)?, n.into());
                 Ok(KIterator::new(result).into())
             }
@@ -724,11 +728,19 @@ pub fn make_module() -> KMap {
                 let iterable = iterable.clone();
                 let iterator = ctx.vm.make_iterator(iterable)?;
                 let (size_hint, _) = iterator.size_hint();
+                ctx.vm
+                    .reserve_memory(size_hint.saturating_mul(VALUE_SIZE))?;
                 let mut result = ValueVec::with_capacity(size_hint);
 
                 for output in iterator.map(collect_pair) {
                     match output {
-                        Output::Value(value) => result.push(value),
+                        Output::Value(value) => {
+                            // Values beyond the size hint are reserved as they're added
+                            if result.len() >= size_hint {
+                                ctx.vm.reserve_memory(VALUE_SIZE)?;
+                            }
+                            result.push(value)
+                        }
                         Output::Error(error) => return Err(error),
                         _ => unreachable!(),
                     }
@@ -748,6 +760,8 @@ pub fn make_module() -> KMap {
                 let iterable = iterable.clone();
                 let iterator = ctx.vm.make_iterator(iterable)?;
                 let (size_hint, _) = iterator.size_hint();
+                ctx.vm
+                    .reserve_memory(size_hint.saturating_mul(MAP_ENTRY_SIZE))?;
                 let mut result = ValueMap::with_capacity(size_hint);
 
                 for output in iterator {
@@ -762,6 +776,9 @@ pub fn make_module() -> KMap {
                         Output::Error(error) => return Err(error),
                     };
 
+                    if result.len() >= size_hint {
+                        ctx.vm.reserve_memory(MAP_ENTRY_SIZE)?;
+                    }
                     result.insert(ValueKey::try_from(key)?, value);
                 }
 
@@ -779,14 +796,17 @@ pub fn make_module() -> KMap {
                 let iterable = iterable.clone();
                 let iterator = ctx.vm.make_iterator(iterable)?;
                 let (size_hint, _) = iterator.size_hint();
+                ctx.vm.reserve_memory(size_hint)?;
                 let mut display_context = DisplayContext::with_vm_and_capacity(ctx.vm, size_hint);
                 for output in iterator.map(collect_pair) {
+                    let len_before = display_context.len();
                     match output {
                         Output::Value(Value::Str(s)) => display_context.append(s),
                         Output::Value(value) => value.display(&mut display_context)?,
                         Output::Error(error) => return Err(error),
                         _ => unreachable!(),
                     };
+                    ctx.vm.reserve_memory(display_context.len() - len_before)?;
                 }
 
                 Ok(display_context.result().into())
@@ -803,11 +823,18 @@ pub fn make_module() -> KMap {
                 let iterable = iterable.clone();
                 let iterator = ctx.vm.make_iterator(iterable)?;
                 let (size_hint, _) = iterator.size_hint();
+                ctx.vm
+                    .reserve_memory(size_hint.saturating_mul(VALUE_SIZE))?;
                 let mut result = Vec::with_capacity(size_hint);
 
                 for output in iterator.map(collect_pair) {
                     match output {
-                        Output::Value(value) => result.push(value),
+                        Output::Value(value) => {
+                            if result.len() >= size_hint {
+                                ctx.vm.reserve_memory(VALUE_SIZE)?;
+                            }
+                            result.push(value)
+                        }
                         Output::Error(error) => return Err(error),
                         _ => unreachable!(),
                     }
@@ -825,7 +852,7 @@ pub fn make_module() -> KMap {
         match ctx.instance_and_args(Value::is_iterable, expected_error)? {
             (iterable, [Value::Number(n)]) => {
                 let iterable = iterable.clone();
-                let n = *n;
+                let n = n.clone();
                 match adaptors::Windows::new(ctx.vm.make_iterator(iterable)?, n.into()) {
                     Ok(result) => Ok(KIterator::new(result).into()),
                     Err(e) => runtime_error!("iterator.windows: {}", e),
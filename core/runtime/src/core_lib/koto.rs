@@ -1,6 +1,9 @@
 //! The `koto` core library module
 
-use crate::prelude::*;
+use crate::{
+    execution_limits::{MAP_ENTRY_SIZE, VALUE_SIZE},
+    prelude::*,
+};
 use std::hash::{Hash, Hasher};
 
 /// Initializes the `koto` core library module
@@ -11,8 +14,12 @@ pub fn make_module() -> KMap {
 
     result.add_fn("copy", |ctx| match ctx.args() {
         [Value::Iterator(iter)] => Ok(iter.make_copy()?.into()),
-        [Value::List(l)] => Ok(KList::with_data(l.data().clone()).into()),
+        [Value::List(l)] => {
+            ctx.vm.reserve_memory(l.len() * VALUE_SIZE)?;
+            Ok(KList::with_data(l.data().clone()).into())
+        }
         [Value::Map(m)] => {
+            ctx.vm.reserve_memory(m.len() * MAP_ENTRY_SIZE)?;
             let result = KMap::with_contents(
                 m.data().clone(),
                 m.meta_map().map(|meta| meta.borrow().clone()),
@@ -4,7 +4,7 @@ use super::{
     iterator::collect_pair,
     value_sort::{compare_values, sort_values},
 };
-use crate::prelude::*;
+use crate::{execution_limits::VALUE_SIZE, prelude::*};
 use std::{cmp::Ordering, ops::DerefMut};
 
 /// Initializes the `list` core library module
@@ -57,10 +57,12 @@ pub fn make_module() -> KMap {
 
         match ctx.instance_and_args(is_list, expected_error)? {
             (Value::List(l), [Value::List(other)]) => {
+                ctx.vm.reserve_memory(other.len() * VALUE_SIZE)?;
                 l.data_mut().extend(other.data().iter().cloned());
                 Ok(Value::List(l.clone()))
             }
             (Value::List(l), [Value::Tuple(other)]) => {
+                ctx.vm.reserve_memory(other.len() * VALUE_SIZE)?;
                 l.data_mut().extend(other.iter().cloned());
                 Ok(Value::List(l.clone()))
             }
@@ -72,11 +74,19 @@ pub fn make_module() -> KMap {
                 {
                     let mut list_data = l.data_mut();
                     let (size_hint, _) = iterator.size_hint();
+                    ctx.vm
+                        .reserve_memory(size_hint.saturating_mul(VALUE_SIZE))?;
                     list_data.reserve(size_hint);
 
-                    for value in iterator.map(collect_pair) {
+                    for (i, value) in iterator.map(collect_pair).enumerate() {
                         match value {
-                            KIteratorOutput::Value(value) => list_data.push(value.clone()),
+                            KIteratorOutput::Value(value) => {
+                                // Values beyond the size hint are reserved as they're added
+                                if i >= size_hint {
+                                    ctx.vm.reserve_memory(VALUE_SIZE)?;
+                                }
+                                list_data.push(value.clone())
+                            }
                             KIteratorOutput::Error(error) => return Err(error),
                             _ => unreachable!(),
                         }
@@ -142,6 +152,7 @@ pub fn make_module() -> KMap {
                     return runtime_error!("list.insert: Index out of bounds");
                 }
 
+                ctx.vm.reserve_memory(VALUE_SIZE)?;
                 l.data_mut().insert(index, value.clone());
                 Ok(Value::List(l.clone()))
             }
@@ -187,6 +198,7 @@ pub fn make_module() -> KMap {
 
         match ctx.instance_and_args(is_list, expected_error)? {
             (Value::List(l), [value]) => {
+                ctx.vm.reserve_memory(VALUE_SIZE)?;
                 l.data_mut().push(value.clone());
                 Ok(Value::List(l.clone()))
             }
@@ -219,10 +231,12 @@ pub fn make_module() -> KMap {
 
         match ctx.instance_and_args(is_list, expected_error)? {
             (Value::List(l), [Value::Number(n)]) if *n >= 0.0 => {
+                reserve_growth(ctx.vm, l, n.into())?;
                 l.data_mut().resize(n.into(), Value::Null);
                 Ok(Value::List(l.clone()))
             }
             (Value::List(l), [Value::Number(n), value]) if *n >= 0.0 => {
+                reserve_growth(ctx.vm, l, n.into())?;
                 l.data_mut().resize(n.into(), value.clone());
                 Ok(Value::List(l.clone()))
             }
@@ -243,6 +257,7 @@ pub fn make_module() -> KMap {
                 match len.cmp(&new_size) {
                     Ordering::Greater => l.data_mut().truncate(new_size),
                     Ordering::Less => {
+                        reserve_growth(ctx.vm, &l, new_size)?;
                         l.data_mut().reserve(new_size);
                         for _ in 0..new_size - len {
                             let new_value = ctx.vm.run_function(f.clone(), CallArgs::None)?;
@@ -431,7 +446,10 @@ pub fn make_module() -> KMap {
         let expected_error = "a List";
 
         match ctx.instance_and_args(is_list, expected_error)? {
-            (Value::List(l), []) => Ok(Value::Tuple(l.data().as_slice().into())),
+            (Value::List(l), []) => {
+                ctx.vm.reserve_memory(l.len() * VALUE_SIZE)?;
+                Ok(Value::Tuple(l.data().as_slice().into()))
+            }
             (_, unexpected) => type_error_with_slice(expected_error, unexpected),
         }
     });
@@ -466,3 +484,9 @@ pub fn make_module() -> KMap {
 fn is_list(value: &Value) -> bool {
     matches!(value, Value::List(_))
 }
+
+// Reserves memory for the values that will be added when resizing a list to a larger size
+fn reserve_growth(vm: &Vm, list: &KList, new_size: usize) -> crate::Result<()> {
+    let growth = new_size.saturating_sub(list.len());
+    vm.reserve_memory(growth.saturating_mul(VALUE_SIZE))
+}
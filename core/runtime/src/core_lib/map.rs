@@ -1,7 +1,7 @@
 //! The `map` core library module
 
 use super::{iterator::adaptors, value_sort::compare_values};
-use crate::{prelude::*, Result};
+use crate::{execution_limits::MAP_ENTRY_SIZE, prelude::*, Result};
 use std::cmp::Ordering;
 
 /// Initializes the `map` core library module
@@ -37,6 +37,7 @@ pub fn make_module() -> KMap {
 
         match map_instance_and_args(ctx, expected_error)? {
             (Value::Map(m), [Value::Map(other)]) => {
+                ctx.vm.reserve_memory(other.len() * MAP_ENTRY_SIZE)?;
                 m.data_mut().extend(
                     other
                         .data()
@@ -53,9 +54,11 @@ pub fn make_module() -> KMap {
                 {
                     let mut map_data = m.data_mut();
                     let (size_hint, _) = iterator.size_hint();
+                    ctx.vm
+                        .reserve_memory(size_hint.saturating_mul(MAP_ENTRY_SIZE))?;
                     map_data.reserve(size_hint);
 
-                    for output in iterator {
+                    for (i, output) in iterator.enumerate() {
                         use KIteratorOutput as Output;
                         let (key, value) = match output {
                             Output::ValuePair(key, value) => (key, value),
@@ -68,6 +71,10 @@ pub fn make_module() -> KMap {
                             Output::Error(error) => return Err(error),
                         };
 
+                        // Entries beyond the size hint are reserved as they're added
+                        if i >= size_hint {
+                            ctx.vm.reserve_memory(MAP_ENTRY_SIZE)?;
+                        }
                         map_data.insert(ValueKey::try_from(key.clone())?, value);
                     }
                 }
@@ -141,7 +148,10 @@ pub fn make_module() -> KMap {
                 .insert(ValueKey::try_from(key.clone())?, Value::Null)
             {
                 Some(old_value) => Ok(old_value),
-                None => Ok(Value::Null),
+                None => {
+                    ctx.vm.reserve_memory(MAP_ENTRY_SIZE)?;
+                    Ok(Value::Null)
+                }
             },
             (Value::Map(m), [key, value]) => {
                 match m
@@ -149,7 +159,10 @@ pub fn make_module() -> KMap {
                     .insert(ValueKey::try_from(key.clone())?, value.clone())
                 {
                     Some(old_value) => Ok(old_value),
-                    None => Ok(Value::Null),
+                    None => {
+                        ctx.vm.reserve_memory(MAP_ENTRY_SIZE)?;
+                        Ok(Value::Null)
+                    }
                 }
             }
             (_, unexpected) => type_error_with_slice(expected_error, unexpected),
@@ -1,6 +1,6 @@
 //! The `number` core library module
 
-use crate::prelude::*;
+use crate::{prelude::*, Decimal};
 
 /// Initializes the `number` core library module
 pub fn make_module() -> KMap {
@@ -17,7 +17,7 @@ pub fn make_module() -> KMap {
                 let expected_error = "a Number";
 
                 match ctx.instance_and_args(is_number, expected_error)? {
-                    (Number(n), []) => Ok(Number(n.$fn())),
+                    (Number(n), []) => Ok(Number(ctx.vm.check_integer_overflow(n.$fn(), &[n])?)),
                     (_, unexpected) => type_error_with_slice(expected_error, unexpected),
                 }
             });
@@ -93,13 +93,29 @@ pub fn make_module() -> KMap {
         let expected_error = "three Numbers";
 
         match ctx.instance_and_args(is_number, expected_error)? {
-            (Number(x), [Number(a), Number(b)]) => Ok(Number(*a.max(b.min(x)))),
+            (Number(x), [Number(a), Number(b)]) => Ok(Number(a.max(b.min(x)).clone())),
             (_, unexpected) => type_error_with_slice(expected_error, unexpected),
         }
     });
 
     number_f64_fn!(cos);
     number_f64_fn!(cosh);
+
+    result.add_fn("decimal", |ctx| {
+        let expected_error = "a Number or String";
+
+        match ctx.instance_and_args(is_number_or_string, expected_error)? {
+            (Number(n), []) => match n.to_decimal() {
+                Some(result) => Ok(result.into()),
+                None => runtime_error!("'{n}' can't be represented as a Decimal"),
+            },
+            (Value::Str(s), []) => match Decimal::from_str_exact(s.trim()) {
+                Ok(result) => Ok(result.into()),
+                Err(_) => Ok(Value::Null),
+            },
+            (_, unexpected) => type_error_with_slice(expected_error, unexpected),
+        }
+    });
     number_f64_fn!("degrees", to_degrees);
 
     result.add_value("e", std::f64::consts::E.into());
@@ -134,7 +150,7 @@ pub fn make_module() -> KMap {
 
         match ctx.instance_and_args(is_number, expected_error)? {
             (Number(a), [Number(b), Number(t)]) => {
-                let result = *a + (b - a) * *t;
+                let result = a + &(&(b - a) * t);
                 Ok(result.into())
             }
             (_, unexpected) => type_error_with_slice(expected_error, unexpected),
@@ -149,7 +165,7 @@ pub fn make_module() -> KMap {
         let expected_error = "two Numbers";
 
         match ctx.instance_and_args(is_number, expected_error)? {
-            (Number(a), [Number(b)]) => Ok(Number(*a.max(b))),
+            (Number(a), [Number(b)]) => Ok(Number(a.max(b).clone())),
             (_, unexpected) => type_error_with_slice(expected_error, unexpected),
         }
     });
@@ -158,7 +174,7 @@ pub fn make_module() -> KMap {
         let expected_error = "two Numbers";
 
         match ctx.instance_and_args(is_number, expected_error)? {
-            (Number(a), [Number(b)]) => Ok(Number(*a.min(b))),
+            (Number(a), [Number(b)]) => Ok(Number(a.min(b).clone())),
             (_, unexpected) => type_error_with_slice(expected_error, unexpected),
         }
     });
@@ -176,7 +192,10 @@ pub fn make_module() -> KMap {
         let expected_error = "two Numbers";
 
         match ctx.instance_and_args(is_number, expected_error)? {
-            (Number(a), [Number(b)]) => Ok(Number(a.pow(*b))),
+            (Number(a), [Number(b)]) => {
+                ctx.vm.check_integer_size(a.pow_bits(b))?;
+                Ok(Number(ctx.vm.check_integer_overflow(a.pow(b), &[a, b])?))
+            }
             (_, unexpected) => type_error_with_slice(expected_error, unexpected),
         }
     });
@@ -209,6 +228,10 @@ pub fn make_module() -> KMap {
         let expected_error = "a Number";
 
         match ctx.instance_and_args(is_number, expected_error)? {
+            (Number(n @ KNumber::BigInt(_)), []) => Ok(Number(n.clone())),
+            (Number(KNumber::Decimal(d)), []) => {
+                Ok(Number(KNumber::from_integral_decimal(d.trunc())))
+            }
             (Number(n), []) => Ok(i64::from(n).into()),
             (_, unexpected) => type_error_with_slice(expected_error, unexpected),
         }
@@ -223,6 +246,10 @@ fn is_number(value: &Value) -> bool {
     matches!(value, Value::Number(_))
 }
 
+fn is_number_or_string(value: &Value) -> bool {
+    matches!(value, Value::Number(_) | Value::Str(_))
+}
+
 fn is_integer(value: &Value) -> bool {
     matches!(value, Value::Number(KNumber::I64(_)))
 }
@@ -11,7 +11,10 @@ pub fn make_module() -> KMap {
 
     let result = KMap::with_type("core.os");
 
-    result.add_fn("name", |_| Ok(std::env::consts::OS.into()));
+    result.add_fn("name", |ctx| {
+        ctx.vm.capabilities().check_environment("os.name")?;
+        Ok(std::env::consts::OS.into())
+    });
 
     result.add_fn("start_timer", |_| Ok(Timer::now()));
 
@@ -10,7 +10,7 @@ pub fn make_module() -> KMap {
         let expected_error = "a Range, and a Number or another Range";
 
         match ctx.instance_and_args(is_range, expected_error)? {
-            (Value::Range(r), [Value::Number(n)]) => Ok(r.contains(*n).into()),
+            (Value::Range(r), [Value::Number(n)]) => Ok(r.contains(n.clone()).into()),
             (Value::Range(a), [Value::Range(b)]) => {
                 let r_a = a.as_sorted_range();
                 let r_b = b.as_sorted_range();
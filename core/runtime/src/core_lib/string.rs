@@ -4,8 +4,8 @@ pub mod format;
 pub mod iterators;
 
 use super::iterator::collect_pair;
-use crate::prelude::*;
-use std::convert::TryFrom;
+use crate::{prelude::*, BigInt, Result};
+use std::{convert::TryFrom, num::IntErrorKind};
 use unicode_segmentation::UnicodeSegmentation;
 
 /// Initializes the `string` core library module
@@ -71,7 +71,10 @@ pub fn make_module() -> KMap {
                 let format = format.clone();
                 let format_args = format_args.to_vec();
                 match format::format_string(ctx.vm, &format, &format_args) {
-                    Ok(result) => Ok(result.into()),
+                    Ok(result) => {
+                        ctx.vm.reserve_memory(result.len())?;
+                        Ok(result.into())
+                    }
                     Err(error) => Err(error),
                 }
             }
@@ -133,6 +136,12 @@ pub fn make_module() -> KMap {
 
         match ctx.instance_and_args(is_string, expected_error)? {
             (Value::Str(input), [Value::Str(pattern), Value::Str(replace)]) => {
+                // Replacements can grow the string, so the result's size needs to be reserved
+                if replace.len() > pattern.len() {
+                    let growth =
+                        input.matches(pattern.as_str()).count() * (replace.len() - pattern.len());
+                    ctx.vm.reserve_memory(input.len() + growth)?;
+                }
                 Ok(input.replace(pattern.as_str(), replace).into())
             }
             (_, unexpected) => type_error_with_slice(expected_error, unexpected),
@@ -201,16 +210,16 @@ pub fn make_module() -> KMap {
         match ctx.instance_and_args(is_string, expected_error)? {
             (Value::Str(s), []) => {
                 let maybe_integer = if let Some(hex) = s.strip_prefix("0x") {
-                    i64::from_str_radix(hex, 16)
+                    parse_integer(ctx.vm, hex, 16)?
                 } else if let Some(octal) = s.strip_prefix("0o") {
-                    i64::from_str_radix(octal, 8)
+                    parse_integer(ctx.vm, octal, 8)?
                 } else if let Some(binary) = s.strip_prefix("0b") {
-                    i64::from_str_radix(binary, 2)
+                    parse_integer(ctx.vm, binary, 2)?
                 } else {
-                    s.parse::<i64>()
+                    parse_integer(ctx.vm, s, 10)?
                 };
 
-                if let Ok(integer) = maybe_integer {
+                if let Some(integer) = maybe_integer {
                     Ok(integer.into())
                 } else if let Ok(float) = s.parse::<f64>() {
                     Ok(float.into())
@@ -224,7 +233,7 @@ pub fn make_module() -> KMap {
                     return runtime_error!("Number base must be within 2..=36");
                 }
 
-                if let Ok(result) = i64::from_str_radix(s, base) {
+                if let Some(result) = parse_integer(ctx.vm, s, base)? {
                     Ok(result.into())
                 } else {
                     Ok(Value::Null)
@@ -268,6 +277,29 @@ pub fn make_module() -> KMap {
     result
 }
 
+// Parses an integer with the given radix, producing a big integer if the result overflows an i64
+fn parse_integer(vm: &Vm, s: &str, radix: u32) -> Result<Option<KNumber>> {
+    match i64::from_str_radix(s, radix) {
+        Ok(n) => Ok(Some(n.into())),
+        Err(e)
+            if matches!(
+                e.kind(),
+                IntErrorKind::PosOverflow | IntErrorKind::NegOverflow
+            ) =>
+        {
+            // Each digit needs at most as many bits as the largest digit in the radix
+            let digit_bits = u64::from(u32::BITS - (radix - 1).leading_zeros());
+            vm.check_integer_size((s.len() as u64).saturating_mul(digit_bits))?;
+
+            match BigInt::parse_bytes(s.as_bytes(), radix) {
+                Some(n) => vm.check_integer_overflow(n.into(), &[]).map(Some),
+                None => Ok(None),
+            }
+        }
+        Err(_) => Ok(None),
+    }
+}
+
 fn is_string(value: &Value) -> bool {
     matches!(value, Value::Str(_))
 }
@@ -2,9 +2,14 @@
 
 use unicode_segmentation::UnicodeSegmentation;
 
-use crate::{runtime_error, UnaryOp, Value, Vm};
+use crate::{
+    runtime_error, type_error, CallArgs, DisplayContext, KNumber, MetaKey, UnaryOp, Value, Vm,
+};
 use koto_lexer::{is_id_continue, is_id_start};
-use std::{iter::Peekable, str::Chars};
+use koto_parser::{StringAlignment, StringFormatOptions, StringRepresentation};
+use num_bigint::BigUint;
+use num_traits::Signed;
+use std::{fmt, iter::Peekable, str::Chars};
 
 #[derive(Debug, PartialEq, Eq)]
 enum FormatToken<'a> {
@@ -15,21 +20,111 @@ enum FormatToken<'a> {
     Error(String),
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
-struct FormatSpec {
-    fill: Option<char>,
-    alignment: Option<FormatAlign>,
-    min_width: Option<u32>,
-    precision: Option<u32>,
+/// The formatting options used by `string.format` and string templates
+///
+/// The options follow the syntax `[[fill]align][+][#][0][width][grouping][.precision][type]`,
+/// e.g. `'{:_>+10,.2}'`.
+///
+/// Values that define `@format`, or objects that implement
+/// [KotoObject::format_with_spec](crate::KotoObject::format_with_spec), receive the spec so that
+/// they can render themselves accordingly. The spec's [Display](fmt::Display) implementation
+/// produces the spec's string form.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FormatSpec {
+    /// The character used to fill the formatted value up to the minimum width
+    pub fill: Option<char>,
+    /// The alignment of the formatted value
+    pub alignment: Option<FormatAlign>,
+    /// The minimum width of the formatted value
+    pub min_width: Option<u32>,
+    /// The number of decimal places for numbers, or the maximum width for other values
+    pub precision: Option<u32>,
+    /// `+`, positive numbers are shown with a `+` sign
+    pub plus_sign: bool,
+    /// `#`, numbers use their alternate form, e.g. `0x` prefixes for hex numbers
+    pub alternate_form: bool,
+    /// `0`, numbers are padded with zeros after their sign up to the minimum width
+    pub zero_padding: bool,
+    /// The separator used for grouping digits, either `,` or `_`
+    pub grouping: Option<char>,
+    /// The representation used for numbers
+    pub representation: Option<FormatRepresentation>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum FormatAlign {
+/// The alignment used for formatted values, see [FormatSpec]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatAlign {
+    /// `<`
     Left,
+    /// `^`
     Center,
+    /// `>`
     Right,
 }
 
+/// The representation used for formatted numbers, see [FormatSpec]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatRepresentation {
+    /// `x`
+    Hex,
+    /// `X`
+    HexUpper,
+    /// `b`
+    Binary,
+    /// `o`
+    Octal,
+    /// `e`
+    Exponent,
+    /// `E`
+    ExponentUpper,
+}
+
+impl fmt::Display for FormatSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(alignment) = self.alignment {
+            if let Some(fill) = self.fill {
+                write!(f, "{fill}")?;
+            }
+            match alignment {
+                FormatAlign::Left => write!(f, "<")?,
+                FormatAlign::Center => write!(f, "^")?,
+                FormatAlign::Right => write!(f, ">")?,
+            }
+        }
+        if self.plus_sign {
+            write!(f, "+")?;
+        }
+        if self.alternate_form {
+            write!(f, "#")?;
+        }
+        if let Some(min_width) = self.min_width {
+            if self.zero_padding {
+                write!(f, "0")?;
+            }
+            write!(f, "{min_width}")?;
+        }
+        if let Some(grouping) = self.grouping {
+            write!(f, "{grouping}")?;
+        }
+        if let Some(precision) = self.precision {
+            write!(f, ".{precision}")?;
+        }
+        if let Some(representation) = self.representation {
+            use FormatRepresentation::*;
+            let c = match representation {
+                Hex => 'x',
+                HexUpper => 'X',
+                Binary => 'b',
+                Octal => 'o',
+                Exponent => 'e',
+                ExponentUpper => 'E',
+            };
+            write!(f, "{c}")?;
+        }
+        Ok(())
+    }
+}
+
 struct FormatLexer<'a> {
     format_string: &'a str,
     position: usize,
@@ -75,16 +170,54 @@ impl<'a> FormatLexer<'a> {
             _ => {}
         }
 
+        if chars.next_if_eq(&'+').is_some() {
+            self.position += 1;
+            result.plus_sign = true;
+        }
+
+        if chars.next_if_eq(&'#').is_some() {
+            self.position += 1;
+            result.alternate_form = true;
+        }
+
+        // A '0' is only treated as the zero padding flag when it's followed by the minimum width
+        let mut lookahead = chars.clone();
+        if lookahead.next() == Some('0') && matches!(lookahead.next(), Some('0'..='9')) {
+            chars.next();
+            self.position += 1;
+            result.zero_padding = true;
+        }
+
         if matches!(chars.peek(), Some('0'..='9')) {
             result.min_width = Some(self.consume_u32(chars)?);
         }
 
+        if let Some(grouping) = chars.next_if(|c| matches!(c, ',' | '_')) {
+            self.position += 1;
+            result.grouping = Some(grouping);
+        }
+
         if matches!(chars.peek(), Some('.')) {
             chars.next();
             self.position += 1;
             result.precision = Some(self.consume_u32(chars)?);
         }
 
+        let representation = match chars.peek() {
+            Some('x') => Some(FormatRepresentation::Hex),
+            Some('X') => Some(FormatRepresentation::HexUpper),
+            Some('b') => Some(FormatRepresentation::Binary),
+            Some('o') => Some(FormatRepresentation::Octal),
+            Some('e') => Some(FormatRepresentation::Exponent),
+            Some('E') => Some(FormatRepresentation::ExponentUpper),
+            _ => None,
+        };
+        if representation.is_some() {
+            chars.next();
+            self.position += 1;
+            result.representation = representation;
+        }
+
         match chars.peek() {
             Some('}') => {
                 chars.next();
@@ -334,18 +467,99 @@ pub fn format_string(
     Ok(result)
 }
 
+/// Formats a value with the options from an interpolated string expression, e.g. `'${x:>8.2}'`
+pub(crate) fn format_value(
+    vm: &mut Vm,
+    value: &Value,
+    options: &StringFormatOptions,
+    fill: Option<char>,
+) -> crate::Result<String> {
+    let alignment = match options.alignment {
+        StringAlignment::Default => None,
+        StringAlignment::Left => Some(FormatAlign::Left),
+        StringAlignment::Center => Some(FormatAlign::Center),
+        StringAlignment::Right => Some(FormatAlign::Right),
+    };
+
+    let representation = match options.representation {
+        StringRepresentation::Default => None,
+        StringRepresentation::Hex => Some(FormatRepresentation::Hex),
+        StringRepresentation::HexUpper => Some(FormatRepresentation::HexUpper),
+        StringRepresentation::Binary => Some(FormatRepresentation::Binary),
+        StringRepresentation::Octal => Some(FormatRepresentation::Octal),
+        StringRepresentation::Exponent => Some(FormatRepresentation::Exponent),
+        StringRepresentation::ExponentUpper => Some(FormatRepresentation::ExponentUpper),
+    };
+
+    let format_spec = FormatSpec {
+        fill,
+        alignment,
+        min_width: options.min_width,
+        precision: options.precision,
+        plus_sign: options.plus_sign,
+        alternate_form: options.alternate_form,
+        zero_padding: options.zero_padding,
+        grouping: options.grouping,
+        representation,
+    };
+
+    value_to_string(vm, value, format_spec)
+}
+
 fn value_to_string(vm: &mut Vm, value: &Value, format_spec: FormatSpec) -> crate::Result<String> {
+    // The memory needed for padding and for a number's precision is checked before formatting,
+    // so that a large width or precision can't allocate beyond the memory limit.
+    let fill_size = format_spec.fill.map_or(1, char::len_utf8);
+    let mut spec_size = (format_spec.min_width.unwrap_or(0) as usize).saturating_mul(fill_size);
+    if matches!(value, Value::Number(_)) {
+        spec_size = spec_size.saturating_add(format_spec.precision.unwrap_or(0) as usize);
+    }
+    vm.check_memory(spec_size)?;
+
+    // Values that format themselves are given the spec, and their output is used as-is.
+    // Without a spec, values are displayed as usual.
+    if format_spec != FormatSpec::default() {
+        match value {
+            Value::Map(_) if value.contains_meta_key(&MetaKey::Format) => {
+                let format_fn = value.get_meta_value(&MetaKey::Format).unwrap();
+                let spec = Value::Str(format_spec.to_string().into());
+                return match vm.run_instance_function(
+                    value.clone(),
+                    format_fn,
+                    CallArgs::Single(spec),
+                )? {
+                    Value::Str(result) => Ok(result.to_string()),
+                    unexpected => type_error("String as @format result", &unexpected),
+                };
+            }
+            Value::Object(o) => {
+                let mut ctx = DisplayContext::with_vm(vm);
+                if o.try_borrow()?.format_with_spec(&mut ctx, &format_spec)? {
+                    return Ok(ctx.result());
+                }
+            }
+            _ => {}
+        }
+    }
+
     let result = match value {
-        Value::Number(n) => match format_spec.precision {
-            Some(precision) => {
-                if n.is_f64() || n.is_i64_in_f64_range() {
-                    format!("{:.*}", precision as usize, f64::from(n))
-                } else {
-                    n.to_string()
+        Value::Number(n) => {
+            let (prefix, digits) = format_number(n, &format_spec)?;
+            match format_spec.min_width {
+                // Zero padding is inserted between the sign/prefix and the number's digits
+                Some(min_width) if format_spec.zero_padding => {
+                    let zeros = (min_width as usize).saturating_sub(prefix.len() + digits.len());
+                    return Ok(format!("{prefix}{}{digits}", "0".repeat(zeros)));
                 }
+                _ => prefix + &digits,
             }
-            None => n.to_string(),
-        },
+        }
+        _ if format_spec.representation.is_some() => {
+            return runtime_error!(
+                "Expected a Number for '{format_spec}' formatting, found '{}'",
+                value.type_as_string()
+            );
+        }
         _ => match vm.run_unary_op(UnaryOp::Display, value.clone())? {
             Value::Str(result) => {
                 match format_spec.precision {
@@ -408,6 +622,103 @@ fn value_to_string(vm: &mut Vm, value: &Value, format_spec: FormatSpec) -> crate
     Ok(result)
 }
 
+// Formats a number, returning its sign and prefix separately from its digits
+fn format_number(n: &KNumber, format_spec: &FormatSpec) -> crate::Result<(String, String)> {
+    use FormatRepresentation::*;
+
+    let (negative, digits, radix_prefix) = match format_spec.representation {
+        Some(representation @ (Hex | HexUpper | Binary | Octal)) => {
+            let (negative, magnitude) = match n {
+                KNumber::I64(i) => (*i < 0, BigUint::from(i.unsigned_abs())),
+                KNumber::BigInt(i) => (i.is_negative(), i.magnitude().clone()),
+                _ => {
+                    return runtime_error!(
+                        "Expected an integer for '{format_spec}' formatting, found '{n}'"
+                    );
+                }
+            };
+            let (digits, prefix, group_size) = match representation {
+                Hex => (format!("{magnitude:x}"), "0x", 4),
+                HexUpper => (format!("{magnitude:X}"), "0x", 4),
+                Binary => (format!("{magnitude:b}"), "0b", 4),
+                _ => (format!("{magnitude:o}"), "0o", 3),
+            };
+            let digits = match format_spec.grouping {
+                Some(separator) => group_digits(&digits, separator, group_size),
+                None => digits,
+            };
+            let prefix = if format_spec.alternate_form {
+                prefix
+            } else {
+                ""
+            };
+            (negative, digits, prefix)
+        }
+        Some(representation @ (Exponent | ExponentUpper)) => {
+            let f = f64::from(n);
+            let digits = match format_spec.precision {
+                Some(precision) => format!("{:.*e}", precision as usize, f.abs()),
+                None => format!("{:e}", f.abs()),
+            };
+            let digits = if representation == ExponentUpper {
+                digits.to_uppercase()
+            } else {
+                digits
+            };
+            (f.is_sign_negative() && !f.is_nan(), digits, "")
+        }
+        None => {
+            let formatted = match (format_spec.precision, n) {
+                (Some(precision), KNumber::Decimal(d)) => {
+                    format!("{:.*}", precision as usize, d.round_dp(precision))
+                }
+                (Some(precision), _) if n.is_f64() || n.is_i64_in_f64_range() => {
+                    format!("{:.*}", precision as usize, f64::from(n))
+                }
+                _ => n.to_string(),
+            };
+            let (negative, digits) = match formatted.strip_prefix('-') {
+                Some(digits) => (true, digits.to_string()),
+                None => (false, formatted),
+            };
+            let digits = match format_spec.grouping {
+                Some(separator) => {
+                    // Only the integer part of the number is grouped
+                    let integer_end = digits
+                        .find(|c: char| !c.is_ascii_digit())
+                        .unwrap_or(digits.len());
+                    let (integer, rest) = digits.split_at(integer_end);
+                    group_digits(integer, separator, 3) + rest
+                }
+                None => digits,
+            };
+            (negative, digits, "")
+        }
+    };
+
+    let sign = if negative {
+        "-"
+    } else if format_spec.plus_sign {
+        "+"
+    } else {
+        ""
+    };
+
+    Ok((format!("{sign}{radix_prefix}"), digits))
+}
+
+// Inserts a separator between groups of digits, counting from the right
+fn group_digits(digits: &str, separator: char, group_size: usize) -> String {
+    let mut result = String::with_capacity(digits.len() + digits.len() / group_size);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(group_size) {
+            result.push(separator);
+        }
+        result.push(c);
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -514,6 +825,7 @@ mod tests {
                             alignment: Some(FormatAlign::Right),
                             min_width: Some(5),
                             precision: Some(3),
+                            ..Default::default()
                         },
                     ),
                     FormatToken::String(" bar"),
@@ -521,6 +833,33 @@ mod tests {
             )
         }
 
+        #[test]
+        fn extended_format_spec() {
+            let input = "{:0>+#08_.3x}{:,E}";
+
+            check_lexer_output(
+                input,
+                &[
+                    FormatToken::Placeholder(FormatSpec {
+                        fill: Some('0'),
+                        alignment: Some(FormatAlign::Right),
+                        min_width: Some(8),
+                        precision: Some(3),
+                        plus_sign: true,
+                        alternate_form: true,
+                        zero_padding: true,
+                        grouping: Some('_'),
+                        representation: Some(FormatRepresentation::Hex),
+                    }),
+                    FormatToken::Placeholder(FormatSpec {
+                        grouping: Some(','),
+                        representation: Some(FormatRepresentation::ExponentUpper),
+                        ..Default::default()
+                    }),
+                ],
+            )
+        }
+
         #[test]
         fn identifier_placeholders() {
             let input = "x = {foo}, y = {bar:.2}";
@@ -614,5 +953,41 @@ mod tests {
             check_format_output("{:-<8.2}", n_negative, "-0.33---");
             check_format_output("{:8.3}", n_negative, "  -0.333");
         }
+
+        #[test]
+        fn number_representations() {
+            let n = &[Value::Number(255.into())];
+            check_format_output("{0:x} {0:X} {0:b} {0:o}", n, "ff FF 11111111 377");
+            check_format_output("{0:#x} {0:#b} {0:#o}", n, "0xff 0b11111111 0o377");
+            check_format_output("{:#_b}", n, "0b1111_1111");
+            check_format_output("{:#x}", &[Value::Number((-255).into())], "-0xff");
+        }
+
+        #[test]
+        fn exponents() {
+            let n = &[Value::Number(1234.5.into())];
+            check_format_output("{:e}", n, "1.2345e3");
+            check_format_output("{:.2e}", n, "1.23e3");
+            check_format_output("{:E}", n, "1.2345E3");
+        }
+
+        #[test]
+        fn signs_and_zero_padding() {
+            let n = &[Value::Number(1.5.into())];
+            let n_negative = &[Value::Number((-1.5).into())];
+            check_format_output("{:+}", n, "+1.5");
+            check_format_output("{:+}", n_negative, "-1.5");
+            check_format_output("{:08.3}", n, "0001.500");
+            check_format_output("{:08.3}", n_negative, "-001.500");
+            check_format_output("{:+#06x}", &[Value::Number(10.into())], "+0x00a");
+        }
+
+        #[test]
+        fn digit_grouping() {
+            check_format_output("{:,}", &[Value::Number(1234567.into())], "1,234,567");
+            check_format_output("{:_}", &[Value::Number((-1234).into())], "-1_234");
+            check_format_output("{:,.2}", &[Value::Number(1234.5.into())], "1,234.50");
+            check_format_output("{:,}", &[Value::Number(123.into())], "123");
+        }
     }
 }
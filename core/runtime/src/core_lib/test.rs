@@ -65,9 +65,9 @@ pub fn make_module() -> KMap {
     });
 
     result.add_fn("assert_near", |ctx| match ctx.args() {
-        [Value::Number(a), Value::Number(b)] => number_near(*a, *b, 1.0e-12),
+        [Value::Number(a), Value::Number(b)] => number_near(a, b, 1.0e-12),
         [Value::Number(a), Value::Number(b), Value::Number(allowed_diff)] => {
-            number_near(*a, *b, allowed_diff.into())
+            number_near(a, b, allowed_diff.into())
         }
         unexpected => type_error_with_slice(
             "two Numbers as arguments, \
@@ -91,7 +91,7 @@ fn f64_near(a: f64, b: f64, allowed_diff: f64) -> bool {
     (a - b).abs() <= allowed_diff
 }
 
-fn number_near(a: KNumber, b: KNumber, allowed_diff: f64) -> Result<Value> {
+fn number_near(a: &KNumber, b: &KNumber, allowed_diff: f64) -> Result<Value> {
     if f64_near(a.into(), b.into(), allowed_diff) {
         Ok(Value::Null)
     } else {
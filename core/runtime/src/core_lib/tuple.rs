@@ -1,7 +1,7 @@
 //! The `tuple` core library module
 
 use super::value_sort::sort_values;
-use crate::prelude::*;
+use crate::{execution_limits::VALUE_SIZE, prelude::*};
 
 /// Initializes the `tuple` core library module
 pub fn make_module() -> KMap {
@@ -105,7 +105,10 @@ pub fn make_module() -> KMap {
         let expected_error = "a Tuple";
 
         match ctx.instance_and_args(is_tuple, expected_error)? {
-            (Value::Tuple(t), []) => Ok(Value::List(KList::from_slice(t))),
+            (Value::Tuple(t), []) => {
+                ctx.vm.reserve_memory(t.len() * VALUE_SIZE)?;
+                Ok(Value::List(KList::from_slice(t)))
+            }
             (_, unexpected) => type_error_with_slice(expected_error, unexpected),
         }
     });
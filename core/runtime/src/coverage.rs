@@ -0,0 +1,350 @@
+use crate::{
+    profiler::{function_name, source_line},
+    Ptr,
+};
+use koto_bytecode::{Chunk, Instruction, InstructionReader};
+use koto_memory::Address;
+use koto_parser::{MetaKeyId, Span};
+use rustc_hash::FxHasher;
+use std::{collections::HashMap, fmt::Write, hash::BuildHasherDefault};
+
+/// The code coverage that was recorded by the runtime
+///
+/// See [VmSettings::enable_coverage](crate::VmSettings::enable_coverage).
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    /// The coverage for each chunk that was executed, in the order in which they were first run
+    pub chunks: Vec<ChunkCoverage>,
+}
+
+impl Coverage {
+    /// Renders the coverage in the `lcov` tracefile format
+    ///
+    /// Chunks without a source path are omitted, and chunks that share a source path are merged.
+    pub fn to_lcov(&self) -> String {
+        let mut result = String::new();
+        let mut written_paths = Vec::new();
+
+        for chunk in self.chunks.iter() {
+            let Some(path) = &chunk.chunk.source_path else {
+                continue;
+            };
+            if written_paths.contains(&path) {
+                continue;
+            }
+            written_paths.push(path);
+
+            let chunks: Vec<_> = self
+                .chunks
+                .iter()
+                .filter(|other| other.chunk.source_path.as_ref() == Some(path))
+                .collect();
+
+            let _ = writeln!(result, "TN:\nSF:{}", path.display());
+
+            let functions: Vec<_> = chunks
+                .iter()
+                .flat_map(|chunk| chunk.functions.iter())
+                .collect();
+            // Function names aren't unique, so their lines are included in the names given to lcov
+            for function in functions.iter() {
+                let line = function.line;
+                let _ = writeln!(result, "FN:{line},{}:{line}", function.name);
+            }
+            for function in functions.iter() {
+                let _ = writeln!(
+                    result,
+                    "FNDA:{},{}:{}",
+                    function.calls, function.name, function.line
+                );
+            }
+            let _ = writeln!(result, "FNF:{}", functions.len());
+            let _ = writeln!(
+                result,
+                "FNH:{}",
+                functions
+                    .iter()
+                    .filter(|function| function.calls > 0)
+                    .count()
+            );
+
+            let mut branches_found = 0;
+            let mut branches_hit = 0;
+            for chunk in chunks.iter() {
+                for branch in chunk.branches.iter() {
+                    let line = branch.span.map_or(0, |span| span.start.line);
+                    let outcomes = [branch.hits - branch.taken, branch.taken];
+                    for (outcome, count) in outcomes.iter().enumerate() {
+                        branches_found += 1;
+                        if branch.hits == 0 {
+                            let _ = writeln!(result, "BRDA:{line},{},{outcome},-", branch.ip);
+                        } else {
+                            if *count > 0 {
+                                branches_hit += 1;
+                            }
+                            let _ = writeln!(result, "BRDA:{line},{},{outcome},{count}", branch.ip);
+                        }
+                    }
+                }
+            }
+            let _ = writeln!(result, "BRF:{branches_found}\nBRH:{branches_hit}");
+
+            let mut lines: Vec<LineCoverage> = Vec::new();
+            for chunk in chunks.iter() {
+                for line in chunk.lines() {
+                    match lines.iter_mut().find(|existing| existing.line == line.line) {
+                        Some(existing) => existing.hits = existing.hits.max(line.hits),
+                        None => lines.push(line),
+                    }
+                }
+            }
+            lines.sort_by_key(|line| line.line);
+            for line in lines.iter() {
+                let _ = writeln!(result, "DA:{},{}", line.line, line.hits);
+            }
+            let _ = writeln!(
+                result,
+                "LF:{}\nLH:{}\nend_of_record",
+                lines.len(),
+                lines.iter().filter(|line| line.hits > 0).count()
+            );
+        }
+
+        result
+    }
+}
+
+/// The code coverage for a [Chunk]
+#[derive(Clone, Debug)]
+pub struct ChunkCoverage {
+    /// The chunk that the coverage was recorded for
+    pub chunk: Ptr<Chunk>,
+    /// Each of the chunk's instructions, in ip order
+    pub instructions: Vec<InstructionCoverage>,
+    /// The chunk's conditional jumps, in ip order
+    pub branches: Vec<BranchCoverage>,
+    /// The functions that are defined in the chunk, in ip order
+    pub functions: Vec<FunctionCoverage>,
+}
+
+impl ChunkCoverage {
+    /// Returns the source lines that contain instructions, in line order
+    ///
+    /// A line's hit count is the hit count of the line's first instruction.
+    /// Later instructions can't be used, e.g. a function's final `Return` instruction is
+    /// attributed to the function's last line, even if the line itself wasn't executed.
+    pub fn lines(&self) -> Vec<LineCoverage> {
+        let mut result: Vec<LineCoverage> = Vec::new();
+
+        for instruction in self.instructions.iter() {
+            let Some(span) = instruction.span else {
+                continue;
+            };
+            if !result.iter().any(|line| line.line == span.start.line) {
+                result.push(LineCoverage {
+                    line: span.start.line,
+                    hits: instruction.hits,
+                });
+            }
+        }
+
+        result.sort_by_key(|line| line.line);
+        result
+    }
+}
+
+/// The number of times that an instruction was executed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstructionCoverage {
+    /// The instruction's ip
+    pub ip: u32,
+    /// The instruction's source span
+    pub span: Option<Span>,
+    /// The number of times the instruction was executed
+    pub hits: u64,
+}
+
+/// The outcomes of a conditional jump
+///
+/// Conditional jumps are produced for `if`, `match`, `switch`, loops, and logical operators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BranchCoverage {
+    /// The instruction's ip
+    pub ip: u32,
+    /// The instruction's source span
+    pub span: Option<Span>,
+    /// The number of times the instruction was executed
+    pub hits: u64,
+    /// The number of times the jump was taken
+    ///
+    /// The number of times execution continued to the next instruction is `hits - taken`.
+    pub taken: u64,
+}
+
+/// The number of times that a function was called
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionCoverage {
+    /// The function's name
+    ///
+    /// Functions are named after the local they're assigned to, or after their `@test` key for
+    /// test functions, otherwise `<anonymous>` is used.
+    pub name: String,
+    /// The line on which the function is defined
+    pub line: u32,
+    /// The number of times the function's first instruction was executed
+    pub calls: u64,
+}
+
+/// The number of times that a source line was executed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineCoverage {
+    /// The line number
+    pub line: u32,
+    /// The number of times the line was executed
+    pub hits: u64,
+}
+
+type FxHashMap<K, V> = HashMap<K, V, BuildHasherDefault<FxHasher>>;
+
+// The hit counts of a chunk's instructions
+struct RecordedChunk {
+    chunk: Ptr<Chunk>,
+    // The number of times each instruction was executed, indexed by ip
+    hits: Vec<u64>,
+    // The number of times each conditional jump was taken
+    branches_taken: FxHashMap<u32, u64>,
+}
+
+// The coverage state that's shared between VMs in a runtime
+#[derive(Default)]
+pub(crate) struct CoverageRecorder {
+    chunks: Vec<RecordedChunk>,
+    chunk_indices: FxHashMap<Address, usize>,
+}
+
+impl CoverageRecorder {
+    // Called before an instruction is executed
+    pub fn record_instruction(&mut self, chunk: &Ptr<Chunk>, ip: u32) {
+        let index = self.chunk_index(chunk);
+        if let Some(hits) = self.chunks[index].hits.get_mut(ip as usize) {
+            *hits += 1;
+        }
+    }
+
+    // Called after a conditional jump has been executed
+    pub fn record_branch(&mut self, chunk: &Ptr<Chunk>, ip: u32, taken: bool) {
+        let index = self.chunk_index(chunk);
+        let taken_count = self.chunks[index].branches_taken.entry(ip).or_default();
+        if taken {
+            *taken_count += 1;
+        }
+    }
+
+    fn chunk_index(&mut self, chunk: &Ptr<Chunk>) -> usize {
+        *self
+            .chunk_indices
+            .entry(Ptr::address(chunk))
+            .or_insert_with(|| {
+                self.chunks.push(RecordedChunk {
+                    chunk: chunk.clone(),
+                    hits: vec![0; chunk.bytes.len()],
+                    branches_taken: FxHashMap::default(),
+                });
+                self.chunks.len() - 1
+            })
+    }
+
+    // Returns the coverage that has been recorded so far
+    pub fn coverage(&self) -> Coverage {
+        Coverage {
+            chunks: self.chunks.iter().map(chunk_coverage).collect(),
+        }
+    }
+}
+
+fn chunk_coverage(recorded: &RecordedChunk) -> ChunkCoverage {
+    let chunk = &recorded.chunk;
+    let hits = |ip: u32| recorded.hits.get(ip as usize).copied().unwrap_or(0);
+
+    let mut instructions = Vec::new();
+    let mut branches = Vec::new();
+    let mut functions = Vec::new();
+
+    let mut reader = InstructionReader::new(chunk.clone());
+    let mut ip = 0;
+    while let Some(instruction) = reader.next() {
+        let span = chunk.debug_info.get_source_span(ip);
+
+        instructions.push(InstructionCoverage {
+            ip,
+            span,
+            hits: hits(ip),
+        });
+
+        match instruction {
+            Instruction::JumpIfTrue { .. }
+            | Instruction::JumpIfFalse { .. }
+            | Instruction::IterNext { .. }
+            | Instruction::AccessKey {
+                jump_offset: Some(_),
+                ..
+            } => branches.push(BranchCoverage {
+                ip,
+                span,
+                hits: hits(ip),
+                taken: recorded.branches_taken.get(&ip).copied().unwrap_or(0),
+            }),
+            Instruction::Function { register, size, .. } => functions.push(FunctionCoverage {
+                name: test_function_name(chunk, register, reader.ip + size as usize)
+                    .unwrap_or_else(|| function_name(chunk, ip, register)),
+                line: source_line(chunk, ip),
+                // The function's body starts at the instruction following the Function instruction
+                calls: hits(reader.ip as u32),
+            }),
+            _ => {}
+        }
+
+        ip = reader.ip as u32;
+    }
+
+    ChunkCoverage {
+        chunk: chunk.clone(),
+        instructions,
+        branches,
+        functions,
+    }
+}
+
+// Returns the name of a function's `@test` key if the function is a test
+//
+// Test functions are assigned to their `@test` key immediately after the function's body,
+// with the key's name being loaded into a register first.
+fn test_function_name(chunk: &Ptr<Chunk>, register: u8, body_end: usize) -> Option<String> {
+    let mut reader = InstructionReader::new(chunk.clone());
+    reader.ip = body_end;
+
+    let Some(Instruction::LoadString {
+        register: name_register,
+        constant,
+    }) = reader.next()
+    else {
+        return None;
+    };
+
+    match reader.next()? {
+        Instruction::MetaInsertNamed {
+            id: MetaKeyId::Test,
+            name,
+            value,
+            ..
+        }
+        | Instruction::MetaExportNamed {
+            id: MetaKeyId::Test,
+            name,
+            value,
+        } if name == name_register && value == register => {
+            Some(chunk.constants.get_str(constant).to_string())
+        }
+        _ => None,
+    }
+}
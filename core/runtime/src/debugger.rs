@@ -0,0 +1,178 @@
+use crate::{Ptr, Value, Vm};
+use koto_bytecode::Chunk;
+use koto_parser::Span;
+use std::path::Path;
+
+/// A hook that allows scripts to be debugged, see
+/// [VmSettings::debug_hook](crate::VmSettings::debug_hook)
+///
+/// The hook is consulted each time execution reaches a new line in a script, and execution is
+/// paused when a breakpoint is hit or when a step has been completed. The hook is also polled
+/// periodically while a script is running, see [DebugHook::poll].
+///
+/// While paused, the runtime blocks until [DebugHook::paused] returns, so a debugger that
+/// communicates with a client (e.g. a Debug Adapter Protocol server) should wait for the client's
+/// next command inside the hook.
+pub trait DebugHook {
+    /// Returns true if execution should pause on the given line
+    ///
+    /// `path` is the script's source path, the paths of imported modules are canonicalized.
+    /// Lines count from 1.
+    fn is_breakpoint(&mut self, path: Option<&Path>, line: u32) -> bool;
+
+    /// Called periodically while a script is running, returns true if execution should pause
+    ///
+    /// The hook is polled every few hundred instructions whether or not the line has changed,
+    /// which allows execution to be paused while a script is stuck on a single line,
+    /// e.g. in response to a request from a client.
+    fn poll(&mut self) -> bool {
+        false
+    }
+
+    /// Called when execution has been paused
+    ///
+    /// `frames` contains the VM's call stack, with the frame that's currently being executed
+    /// first. `vm` is a VM that shares the paused VM's context, and can be used to inspect values
+    /// (e.g. with [Vm::value_to_string]). Functions that are run while paused aren't debugged.
+    ///
+    /// The returned [StepMode] determines when execution will next be paused.
+    fn paused(&mut self, reason: PauseReason, frames: &[DebugFrame], vm: &mut Vm) -> StepMode;
+}
+
+/// The reason that execution was paused by the debugger
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseReason {
+    /// A line was reached for which [DebugHook::is_breakpoint] returned true
+    Breakpoint,
+    /// A step requested by the [StepMode] returned from [DebugHook::paused] was completed
+    Step,
+    /// [DebugHook::poll] returned true
+    Requested,
+}
+
+/// Defines when execution should next be paused after resuming
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StepMode {
+    /// Continue until the next breakpoint is reached
+    #[default]
+    Continue,
+    /// Pause at the next line, entering any functions that are called
+    StepIn,
+    /// Pause at the next line in the current function, or after returning from the function
+    StepOver,
+    /// Pause after returning from the current function
+    StepOut,
+}
+
+/// A frame in the VM's call stack, made available while execution is paused
+#[derive(Clone)]
+pub struct DebugFrame {
+    /// The chunk containing the frame's bytecode
+    pub chunk: Ptr<Chunk>,
+    /// The ip of the instruction that the frame is executing
+    pub ip: u32,
+    /// The frame's registers
+    ///
+    /// Register 0 contains the frame's instance (or Null), followed by the function's
+    /// arguments, local values, and temporary values.
+    pub registers: Vec<Value>,
+    /// The frame's named local values
+    pub locals: Vec<DebugLocal>,
+}
+
+impl DebugFrame {
+    /// The path of the frame's source file
+    pub fn source_path(&self) -> Option<&Path> {
+        self.chunk.source_path.as_deref()
+    }
+
+    /// The source span of the instruction that the frame is executing
+    pub fn span(&self) -> Option<Span> {
+        self.chunk.debug_info.get_source_span(self.ip)
+    }
+}
+
+/// A named local value in a [DebugFrame]
+#[derive(Clone)]
+pub struct DebugLocal {
+    /// The local's name
+    pub name: String,
+    /// The frame register that contains the local's value
+    pub register: u8,
+    /// The local's value
+    pub value: Value,
+}
+
+// The number of instructions that are executed between calls to DebugHook::poll
+const POLL_INTERVAL: u32 = 256;
+
+// The debugger state that's shared between VMs in a runtime
+pub(crate) struct Debugger {
+    hook: Box<dyn DebugHook>,
+    step_mode: StepMode,
+    // The call depth at which the current step was requested
+    step_depth: usize,
+    // The chunk, line, and call depth of the previously checked instruction
+    previous_location: Option<(Ptr<Chunk>, u32, usize)>,
+    // The number of instructions that have been executed since the hook was last polled
+    instructions_since_poll: u32,
+}
+
+impl Debugger {
+    pub fn new(hook: Box<dyn DebugHook>) -> Self {
+        Self {
+            hook,
+            step_mode: StepMode::default(),
+            step_depth: 0,
+            previous_location: None,
+            instructions_since_poll: 0,
+        }
+    }
+
+    // Called before an instruction is executed, polls the hook at regular intervals
+    pub fn poll(&mut self) -> Option<PauseReason> {
+        self.instructions_since_poll += 1;
+        if self.instructions_since_poll < POLL_INTERVAL {
+            return None;
+        }
+        self.instructions_since_poll = 0;
+
+        self.hook.poll().then_some(PauseReason::Requested)
+    }
+
+    // Called before an instruction is executed, returns a reason if execution should pause
+    pub fn check_location(
+        &mut self,
+        chunk: &Ptr<Chunk>,
+        line: u32,
+        depth: usize,
+    ) -> Option<PauseReason> {
+        if matches!(&self.previous_location,
+            Some((previous_chunk, previous_line, previous_depth))
+                if *previous_line == line
+                    && *previous_depth == depth
+                    && Ptr::ptr_eq(previous_chunk, chunk))
+        {
+            return None;
+        }
+        self.previous_location = Some((chunk.clone(), line, depth));
+
+        if self.hook.is_breakpoint(chunk.source_path.as_deref(), line) {
+            return Some(PauseReason::Breakpoint);
+        }
+
+        let step_completed = match self.step_mode {
+            StepMode::Continue => false,
+            StepMode::StepIn => true,
+            StepMode::StepOver => depth <= self.step_depth,
+            StepMode::StepOut => depth < self.step_depth,
+        };
+
+        step_completed.then_some(PauseReason::Step)
+    }
+
+    pub fn pause(&mut self, reason: PauseReason, frames: &[DebugFrame], depth: usize, vm: &mut Vm) {
+        self.step_mode = self.hook.paused(reason, frames, vm);
+        self.step_depth = depth;
+    }
+}
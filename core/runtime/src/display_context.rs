@@ -39,6 +39,16 @@ impl<'a> DisplayContext<'a> {
         s.into().append(&mut self.result);
     }
 
+    /// Returns the length in bytes of the string that has been built so far
+    pub fn len(&self) -> usize {
+        self.result.len()
+    }
+
+    /// Returns true if nothing has been appended to the string
+    pub fn is_empty(&self) -> bool {
+        self.result.is_empty()
+    }
+
     /// Returns the resulting string and consumes the context
     pub fn result(self) -> String {
         self.result
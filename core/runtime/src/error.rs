@@ -1,4 +1,4 @@
-use crate::prelude::*;
+use crate::{prelude::*, StopReason};
 use koto_bytecode::Chunk;
 use koto_parser::format_source_excerpt;
 use std::{error, fmt};
@@ -38,6 +38,8 @@ pub(crate) enum ErrorKind {
     MissingSequenceBuilder,
     #[error("Missing string builder")]
     MissingStringBuilder,
+    #[error("Execution stopped: {0}")]
+    ExecutionStopped(StopReason),
 }
 
 fn display_thrown_value(value: &Value, vm: &Vm) -> String {
@@ -79,7 +81,59 @@ impl Error {
 
     /// Extends the error stack with the given [Chunk] and ip
     pub(crate) fn extend_trace(&mut self, chunk: Ptr<Chunk>, instruction: u32) {
-        self.trace.push(ErrorFrame { chunk, instruction });
+        self.extend_trace_with_locals(chunk, instruction, Vec::new());
+    }
+
+    /// Extends the error stack with the given [Chunk] and ip, along with the frame's locals
+    ///
+    /// The locals are provided as name/value pairs, see [Error::render_trace_locals].
+    pub(crate) fn extend_trace_with_locals(
+        &mut self,
+        chunk: Ptr<Chunk>,
+        instruction: u32,
+        locals: Vec<(String, TraceLocal)>,
+    ) {
+        self.trace.push(ErrorFrame {
+            chunk,
+            instruction,
+            locals,
+            elided_frames: 0,
+        });
+    }
+
+    /// Renders the values of the locals that were recorded in the error's trace
+    ///
+    /// Locals are recorded as values when the error is thrown, and then rendered when the error
+    /// is returned to the host, so that errors that get caught don't need to be rendered.
+    pub(crate) fn render_trace_locals(&mut self, render: impl Fn(&Value) -> String) {
+        for (_, local) in self
+            .trace
+            .iter_mut()
+            .flat_map(|frame| frame.locals.iter_mut())
+        {
+            if let TraceLocal::Value(value) = local {
+                *local = TraceLocal::Rendered(render(value));
+            }
+        }
+    }
+
+    /// Notes the number of frames that were elided by tail calls below the most recent frame in
+    /// the trace
+    pub(crate) fn note_elided_frames(&mut self, elided_frames: u32) {
+        if let Some(frame) = self.trace.last_mut() {
+            frame.elided_frames = elided_frames;
+        }
+    }
+
+    /// Returns the reason that execution was stopped, if the error was caused by the runtime
+    /// stopping execution
+    ///
+    /// See [StopReason].
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        match self.error {
+            ErrorKind::ExecutionStopped(reason) => Some(reason),
+            _ => None,
+        }
     }
 
     /// Modifies string errors to include the given prefix
@@ -100,7 +154,13 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.error)?;
 
-        for ErrorFrame { chunk, instruction } in self.trace.iter() {
+        for ErrorFrame {
+            chunk,
+            instruction,
+            locals,
+            elided_frames,
+        } in self.trace.iter()
+        {
             write!(f, "\n--- ")?;
 
             match chunk.debug_info.get_source_span(*instruction) {
@@ -111,6 +171,25 @@ impl fmt::Display for Error {
                 ))?,
                 None => write!(f, "Runtime error at instruction {}", instruction)?,
             }
+
+            if !locals.is_empty() {
+                write!(f, "\nLocals:")?;
+                for (name, local) in locals.iter() {
+                    match local {
+                        TraceLocal::Rendered(rendered) => write!(f, "\n  {name}: {rendered}")?,
+                        // Values that haven't been rendered are shown with their type
+                        TraceLocal::Value(value) => {
+                            write!(f, "\n  {name}: {}", value.type_as_string())?
+                        }
+                    }
+                }
+            }
+
+            match elided_frames {
+                0 => {}
+                1 => write!(f, "\n--- 1 frame elided by a tail call")?,
+                n => write!(f, "\n--- {n} frames elided by tail calls")?,
+            }
         }
 
         Ok(())
@@ -144,6 +223,26 @@ impl From<ErrorKind> for Error {
 pub struct ErrorFrame {
     chunk: Ptr<Chunk>,
     instruction: u32,
+    // The frame's named locals, see VmSettings::include_locals_in_error_traces
+    locals: Vec<(String, TraceLocal)>,
+    // The number of frames that were elided by tail calls below this frame
+    elided_frames: u32,
+}
+
+// A local in an error trace, see [Error::render_trace_locals]
+#[derive(Clone)]
+pub(crate) enum TraceLocal {
+    Value(Value),
+    Rendered(String),
+}
+
+impl fmt::Debug for TraceLocal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(value) => write!(f, "{}", value.type_as_string()),
+            Self::Rendered(rendered) => f.write_str(rendered),
+        }
+    }
 }
 
 /// The Result type used by the Koto Runtime
@@ -0,0 +1,221 @@
+use crate::{error::ErrorKind, runtime_error, Result, Value, ValueKey};
+use instant::Instant;
+use std::{
+    cell::Cell,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// A handle that can be used to interrupt a running [Vm](crate::Vm)
+///
+/// The handle can be sent to other threads, e.g. a watchdog thread, or a signal handler that
+/// interrupts a script when Ctrl-C is pressed.
+///
+/// After the handle has been triggered, the VM will stop executing at the next check with an
+/// error with [StopReason::Interrupted], and the handle will be reset when execution returns to
+/// the host.
+///
+/// See [Vm::interrupt_handle](crate::Vm::interrupt_handle).
+#[derive(Clone, Debug, Default)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    /// Requests that the VM stops execution
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if an interruption has been requested
+    pub fn is_interrupted(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Returns the handle's underlying flag
+    ///
+    /// This is useful for APIs that expect an atomic flag to set,
+    /// e.g. `signal_hook::flag::register`.
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.0.clone()
+    }
+
+    fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// The reason that execution was stopped by the runtime
+///
+/// Errors that stop execution can't be caught by scripts, they're always returned to the host.
+///
+/// See [Error::stop_reason](crate::Error::stop_reason).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The number of executed instructions exceeded
+    /// [VmSettings::instruction_limit](crate::VmSettings::instruction_limit)
+    InstructionLimit,
+    /// The execution time exceeded
+    /// [VmSettings::execution_time_limit](crate::VmSettings::execution_time_limit)
+    TimeLimit,
+    /// Execution was interrupted via an [InterruptHandle]
+    Interrupted,
+    /// The memory allocated by the script exceeded
+    /// [VmSettings::memory_limit](crate::VmSettings::memory_limit)
+    MemoryLimit,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::InstructionLimit => f.write_str("the instruction limit was exceeded"),
+            StopReason::TimeLimit => f.write_str("the execution time limit was exceeded"),
+            StopReason::Interrupted => f.write_str("execution was interrupted"),
+            StopReason::MemoryLimit => f.write_str("the memory limit was exceeded"),
+        }
+    }
+}
+
+// The number of bytes that are reserved for each value in a list, tuple, or sequence builder
+pub(crate) const VALUE_SIZE: usize = std::mem::size_of::<Value>();
+// The number of bytes that are reserved for each entry in a map
+pub(crate) const MAP_ENTRY_SIZE: usize = std::mem::size_of::<(ValueKey, Value)>();
+
+// The interval (in instructions) between checks of the interrupt handle and the time limit,
+// a power of two so that the check can be performed with a mask.
+const CHECK_INTERVAL_MASK: u64 = 256 - 1;
+
+// Tracks the execution of instructions against the limits defined in the VM's settings
+//
+// The limits apply to the outermost execution in a runtime, so nested executions (e.g. functions
+// that are called from external functions, or generators) count towards the same limits.
+pub(crate) struct ExecutionLimits {
+    instruction_limit: Option<u64>,
+    time_limit: Option<Duration>,
+    memory_limit: Option<usize>,
+    interrupt: InterruptHandle,
+    // The number of active calls to Vm::execute_instructions
+    depth: Cell<usize>,
+    // The number of instructions executed since the outermost execution started
+    instruction_count: Cell<u64>,
+    // The time at which the outermost execution should stop
+    deadline: Cell<Option<Instant>>,
+    // The number of bytes reserved for values since the outermost execution started
+    memory_used: Cell<usize>,
+    // Set when execution has been stopped, so that attempts to catch the error are also stopped
+    stopped: Cell<Option<StopReason>>,
+}
+
+impl ExecutionLimits {
+    pub fn new(
+        instruction_limit: Option<u64>,
+        time_limit: Option<Duration>,
+        memory_limit: Option<usize>,
+    ) -> Self {
+        Self {
+            instruction_limit,
+            time_limit,
+            memory_limit,
+            interrupt: InterruptHandle::default(),
+            depth: Cell::new(0),
+            instruction_count: Cell::new(0),
+            deadline: Cell::new(None),
+            memory_used: Cell::new(0),
+            stopped: Cell::new(None),
+        }
+    }
+
+    pub fn interrupt_handle(&self) -> &InterruptHandle {
+        &self.interrupt
+    }
+
+    // Called when starting an execution, resets the limits for outermost executions
+    pub fn enter(&self) {
+        if self.depth.get() == 0 {
+            self.instruction_count.set(0);
+            self.deadline
+                .set(self.time_limit.map(|limit| Instant::now() + limit));
+            self.memory_used.set(0);
+            self.stopped.set(None);
+        }
+        self.depth.set(self.depth.get() + 1);
+    }
+
+    // Returns true if the current execution was started by the host, rather than by a nested call
+    pub fn is_outermost_execution(&self) -> bool {
+        self.depth.get() == 1
+    }
+
+    // Called when an execution is finished, resets the interrupt handle when returning to the host
+    pub fn exit(&self) {
+        let depth = self.depth.get() - 1;
+        self.depth.set(depth);
+        if depth == 0 {
+            self.interrupt.reset();
+        }
+    }
+
+    // Called before each instruction is executed
+    #[inline]
+    pub fn check(&self) -> Result<()> {
+        if let Some(reason) = self.stopped.get() {
+            return runtime_error!(ErrorKind::ExecutionStopped(reason));
+        }
+
+        let count = self.instruction_count.get() + 1;
+        self.instruction_count.set(count);
+
+        if matches!(self.instruction_limit, Some(limit) if count > limit) {
+            return self.stop(StopReason::InstructionLimit);
+        }
+
+        if count & CHECK_INTERVAL_MASK == 0 {
+            if self.interrupt.is_interrupted() {
+                return self.stop(StopReason::Interrupted);
+            }
+
+            if matches!(self.deadline.get(), Some(deadline) if Instant::now() >= deadline) {
+                return self.stop(StopReason::TimeLimit);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Called before memory is allocated for values, e.g. when a list or string grows
+    pub fn reserve_memory(&self, bytes: usize) -> Result<()> {
+        if self.memory_limit.is_some() {
+            self.check_memory(bytes)?;
+            self.memory_used
+                .set(self.memory_used.get().saturating_add(bytes));
+        }
+
+        Ok(())
+    }
+
+    // Called before making a temporary allocation, e.g. when padding a formatted value
+    //
+    // The allocation is checked against the limit without being reserved.
+    pub fn check_memory(&self, bytes: usize) -> Result<()> {
+        let Some(limit) = self.memory_limit else {
+            return Ok(());
+        };
+
+        if let Some(reason) = self.stopped.get() {
+            return runtime_error!(ErrorKind::ExecutionStopped(reason));
+        }
+
+        if self.memory_used.get().saturating_add(bytes) > limit {
+            return self.stop(StopReason::MemoryLimit);
+        }
+
+        Ok(())
+    }
+
+    fn stop(&self, reason: StopReason) -> Result<()> {
+        self.stopped.set(Some(reason));
+        runtime_error!(ErrorKind::ExecutionStopped(reason))
+    }
+}
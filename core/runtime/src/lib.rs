@@ -2,9 +2,14 @@
 
 #![warn(missing_docs)]
 
+mod capabilities;
+mod coverage;
+mod debugger;
 mod display_context;
 mod error;
+mod execution_limits;
 mod io;
+mod profiler;
 mod types;
 mod vm;
 
@@ -12,9 +17,20 @@ pub mod core_lib;
 pub mod prelude;
 
 pub use crate::{
+    capabilities::{Capabilities, FileSystemAccess},
+    core_lib::string::format::{FormatAlign, FormatRepresentation, FormatSpec},
+    coverage::{
+        BranchCoverage, ChunkCoverage, Coverage, FunctionCoverage, InstructionCoverage,
+        LineCoverage,
+    },
+    debugger::{DebugFrame, DebugHook, DebugLocal, PauseReason, StepMode},
     display_context::DisplayContext,
     error::{type_error, type_error_with_slice, Error, Result},
+    execution_limits::{InterruptHandle, StopReason},
     io::{BufferedFile, DefaultStderr, DefaultStdin, DefaultStdout, KotoFile, KotoRead, KotoWrite},
+    profiler::{
+        CallTreeNode, Profile, ProfileMetric, ProfileStats, ProfiledFunction, ProfiledLine,
+    },
     types::{
         BinaryOp, CallContext, IsIterable, KCaptureFunction, KFunction, KIterator, KIteratorOutput,
         KList, KMap, KNativeFunction, KNumber, KObject, KRange, KString, KTuple, KotoHasher,
@@ -23,4 +39,9 @@ pub use crate::{
     },
     vm::{CallArgs, ModuleImportedCallback, Vm, VmSettings},
 };
-pub use koto_memory::{Borrow, BorrowMut, Ptr, PtrMut};
+pub use koto_memory::{
+    cycle_collector::{CollectionStats, Tracer},
+    Borrow, BorrowMut, Ptr, PtrMut,
+};
+pub use num_bigint::BigInt;
+pub use rust_decimal::Decimal;
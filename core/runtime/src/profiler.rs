@@ -0,0 +1,555 @@
+use crate::Ptr;
+use instant::Instant;
+use koto_bytecode::{Chunk, Instruction, InstructionReader};
+use koto_memory::Address;
+use rustc_hash::FxHasher;
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt::{self, Write},
+    hash::BuildHasherDefault,
+    path::PathBuf,
+    time::Duration,
+};
+
+/// The instruction count and time that were recorded for part of a script by the profiler
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProfileStats {
+    /// The number of instructions that were executed
+    pub instructions: u64,
+    /// The time spent executing instructions, including time spent in native functions
+    pub time: Duration,
+}
+
+impl ProfileStats {
+    fn add(&mut self, other: &Self) {
+        self.instructions += other.instructions;
+        self.time += other.time;
+    }
+}
+
+/// The metric used to weight the stacks produced by [Profile::folded_stacks]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProfileMetric {
+    /// Stacks are weighted by the time spent in them, in microseconds
+    #[default]
+    Time,
+    /// Stacks are weighted by the number of instructions executed in them
+    Instructions,
+}
+
+impl ProfileMetric {
+    fn weight(&self, stats: &ProfileStats) -> u64 {
+        match self {
+            Self::Time => stats.time.as_micros() as u64,
+            Self::Instructions => stats.instructions,
+        }
+    }
+}
+
+/// A function that was called while profiling
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProfiledFunction {
+    /// The function's name
+    ///
+    /// Functions are named after the local they're assigned to, otherwise `<anonymous>` is used.
+    /// The top level of a script or module is named `<main>`.
+    pub name: String,
+    /// The path of the function's source file
+    pub source_path: Option<PathBuf>,
+    /// The line on which the function is defined
+    pub line: u32,
+    /// The number of times the function was called
+    pub calls: u64,
+    /// The stats for the function's own instructions
+    pub self_stats: ProfileStats,
+    /// The stats for the function's instructions along with the functions that it called
+    pub total_stats: ProfileStats,
+}
+
+impl fmt::Display for ProfiledFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({})",
+            self.name,
+            SourceLocation(&self.source_path, self.line)
+        )
+    }
+}
+
+/// A source line that was executed while profiling
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProfiledLine {
+    /// The path of the line's source file
+    pub source_path: Option<PathBuf>,
+    /// The line number
+    pub line: u32,
+    /// The stats for the instructions that were executed for the line
+    pub stats: ProfileStats,
+}
+
+/// A node in a [Profile]'s call tree
+///
+/// Each node represents a function that was called from a particular call stack.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallTreeNode {
+    /// The index of the node's function in [Profile::functions]
+    pub function: usize,
+    /// The index of the node's parent in [Profile::call_tree]
+    pub parent: Option<usize>,
+    /// The indices of the node's children in [Profile::call_tree]
+    pub children: Vec<usize>,
+    /// The number of times the function was called from the node's call stack
+    pub calls: u64,
+    /// The stats for the function's own instructions
+    pub self_stats: ProfileStats,
+    /// The stats for the function's instructions along with the functions that it called
+    pub total_stats: ProfileStats,
+}
+
+/// The results recorded by the runtime's profiler
+///
+/// See [VmSettings::enable_profiler](crate::VmSettings::enable_profiler).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Profile {
+    /// The functions that were called, in the order in which they were first called
+    pub functions: Vec<ProfiledFunction>,
+    /// The lines that were executed, in the order in which they were first executed
+    pub lines: Vec<ProfiledLine>,
+    /// The call tree, with callers preceding the functions that they called
+    pub call_tree: Vec<CallTreeNode>,
+}
+
+impl Profile {
+    /// Returns a report of the time spent in each function and line, sorted by self time
+    pub fn flat_report(&self) -> String {
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by_key(|function| Reverse(function.self_stats.time));
+
+        let mut lines: Vec<_> = self.lines.iter().collect();
+        lines.sort_by_key(|line| Reverse(line.stats.time));
+
+        let mut result = String::new();
+
+        result.push_str("Functions\n---------\n");
+        let _ = writeln!(
+            result,
+            "{:>10} {:>10} {:>12} {:>12} {:>8}  function",
+            "self ms", "total ms", "self instr", "total instr", "calls"
+        );
+        for function in functions {
+            let _ = writeln!(
+                result,
+                "{:>10} {:>10} {:>12} {:>12} {:>8}  {function}",
+                Millis(function.self_stats.time),
+                Millis(function.total_stats.time),
+                function.self_stats.instructions,
+                function.total_stats.instructions,
+                function.calls,
+            );
+        }
+
+        result.push_str("\nLines\n-----\n");
+        let _ = writeln!(result, "{:>10} {:>12}  line", "ms", "instr");
+        for line in lines {
+            let _ = writeln!(
+                result,
+                "{:>10} {:>12}  {}",
+                Millis(line.stats.time),
+                line.stats.instructions,
+                SourceLocation(&line.source_path, line.line),
+            );
+        }
+
+        result
+    }
+
+    /// Returns a report of the call tree, with each function's callees indented below it
+    ///
+    /// The callees of each function are sorted by total time.
+    pub fn call_tree_report(&self) -> String {
+        let mut result = String::new();
+
+        result.push_str("Call tree\n---------\n");
+        let _ = writeln!(
+            result,
+            "{:>10} {:>10} {:>12} {:>8}  function",
+            "total ms", "self ms", "total instr", "calls"
+        );
+
+        let mut stack: Vec<(usize, usize)> = self
+            .sorted_nodes(self.roots())
+            .into_iter()
+            .rev()
+            .map(|node| (node, 0))
+            .collect();
+
+        while let Some((index, depth)) = stack.pop() {
+            let node = &self.call_tree[index];
+            let _ = writeln!(
+                result,
+                "{:>10} {:>10} {:>12} {:>8}  {:indent$}{}",
+                Millis(node.total_stats.time),
+                Millis(node.self_stats.time),
+                node.total_stats.instructions,
+                node.calls,
+                "",
+                self.functions[node.function],
+                indent = depth * 2
+            );
+
+            stack.extend(
+                self.sorted_nodes(node.children.iter().copied())
+                    .into_iter()
+                    .rev()
+                    .map(|child| (child, depth + 1)),
+            );
+        }
+
+        result
+    }
+
+    /// Returns the call tree's stacks in the 'folded' format used by flame graph tools
+    ///
+    /// Each line contains a call stack, with functions separated by `;`, followed by the stack's
+    /// weight (e.g. `<main> (script.koto:1);foo (script.koto:3) 42`).
+    /// Stacks with a weight of zero are omitted.
+    pub fn folded_stacks(&self, metric: ProfileMetric) -> String {
+        let mut result = String::new();
+
+        for (index, node) in self.call_tree.iter().enumerate() {
+            let weight = metric.weight(&node.self_stats);
+            if weight == 0 {
+                continue;
+            }
+
+            let mut stack = Vec::new();
+            let mut next = Some(index);
+            while let Some(index) = next {
+                let node = &self.call_tree[index];
+                stack.push(self.functions[node.function].to_string());
+                next = node.parent;
+            }
+            stack.reverse();
+
+            let _ = writeln!(result, "{} {weight}", stack.join(";"));
+        }
+
+        result
+    }
+
+    fn roots(&self) -> impl Iterator<Item = usize> + '_ {
+        self.call_tree
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(index, _)| index)
+    }
+
+    // Sorts the given nodes by total time, falling back to total instruction count
+    fn sorted_nodes(&self, nodes: impl Iterator<Item = usize>) -> Vec<usize> {
+        let mut result: Vec<_> = nodes.collect();
+        result.sort_by(|a, b| {
+            let a = &self.call_tree[*a].total_stats;
+            let b = &self.call_tree[*b].total_stats;
+            b.time
+                .cmp(&a.time)
+                .then(b.instructions.cmp(&a.instructions))
+        });
+        result
+    }
+}
+
+// Displays a source location, e.g. `script.koto:42`
+struct SourceLocation<'a>(&'a Option<PathBuf>, u32);
+
+impl fmt::Display for SourceLocation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(path) => write!(f, "{}:{}", path.display(), self.1),
+            None => write!(f, "line {}", self.1),
+        }
+    }
+}
+
+// Displays a duration in milliseconds
+struct Millis(Duration);
+
+impl fmt::Display for Millis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Padding the formatted number allows the column widths in the reports to be respected
+        f.pad(&format!("{:.3}", self.0.as_secs_f64() * 1000.0))
+    }
+}
+
+type FxHashMap<K, V> = HashMap<K, V, BuildHasherDefault<FxHasher>>;
+
+// A function that's been called while profiling, identified by its chunk and ip
+struct Function {
+    chunk: Ptr<Chunk>,
+    ip: u32,
+}
+
+// A function in the call tree, along with the stats for its own instructions
+struct Node {
+    function: u32,
+    parent: Option<u32>,
+    calls: u64,
+    stats: ProfileStats,
+}
+
+// A source line, along with the stats for its instructions
+struct Line {
+    chunk: Ptr<Chunk>,
+    line: u32,
+    stats: ProfileStats,
+}
+
+// The call tree node and line of an executed instruction
+#[derive(Clone, Copy)]
+pub(crate) struct Sample {
+    node: u32,
+    line: u32,
+}
+
+// The profiler state that's shared between VMs in a runtime
+//
+// Each executed instruction is sampled, with its instruction count attributed to its call tree
+// node and line. The time until the next sample is then attributed to the same node and line,
+// so that time spent in native functions is attributed to the instruction that called them.
+#[derive(Default)]
+pub(crate) struct Profiler {
+    functions: Vec<Function>,
+    function_ids: FxHashMap<(Address, u32), u32>,
+    nodes: Vec<Node>,
+    node_ids: FxHashMap<(Option<u32>, u32), u32>,
+    lines: Vec<Line>,
+    line_ids: FxHashMap<(u32, u32), u32>,
+    // The most recently executed instruction, along with the time it was executed
+    previous_sample: Option<(Sample, Instant)>,
+}
+
+impl Profiler {
+    // Called when a function is called, returns the call tree node for the function's frame
+    //
+    // If the caller's node isn't known (e.g. the function is being called from a new VM) then
+    // the node of the most recently executed instruction is used as the parent.
+    pub fn enter_function(&mut self, chunk: &Ptr<Chunk>, ip: u32, caller: Option<u32>) -> u32 {
+        let function = *self
+            .function_ids
+            .entry((Ptr::address(chunk), ip))
+            .or_insert_with(|| {
+                self.functions.push(Function {
+                    chunk: chunk.clone(),
+                    ip,
+                });
+                self.functions.len() as u32 - 1
+            });
+
+        let parent = caller.or(self.previous_sample.map(|(sample, _)| sample.node));
+        let node = *self.node_ids.entry((parent, function)).or_insert_with(|| {
+            self.nodes.push(Node {
+                function,
+                parent,
+                calls: 0,
+                stats: ProfileStats::default(),
+            });
+            self.nodes.len() as u32 - 1
+        });
+
+        self.nodes[node as usize].calls += 1;
+        node
+    }
+
+    // Called before an instruction is executed
+    pub fn sample(&mut self, node: u32, line: u32) {
+        let now = Instant::now();
+        self.flush(now);
+
+        let sample = Sample { node, line };
+        self.line_stats(sample).instructions += 1;
+        self.nodes[node as usize].stats.instructions += 1;
+        self.previous_sample = Some((sample, now));
+    }
+
+    // Called when a VM starts executing instructions
+    //
+    // The returned sample should be passed to `exit` when execution has finished.
+    pub fn enter_execution(&self) -> Option<Sample> {
+        self.previous_sample.map(|(sample, _)| sample)
+    }
+
+    // Called when a VM has finished executing instructions
+    //
+    // Time spent after execution has finished is attributed to the instruction that was executing
+    // when execution started, e.g. the call to a native function that called back into the VM.
+    pub fn exit_execution(&mut self, previous_sample: Option<Sample>) {
+        let now = Instant::now();
+        self.flush(now);
+        self.previous_sample = previous_sample.map(|sample| (sample, now));
+    }
+
+    // Attributes the time since the previous sample to the previous sample's node and line
+    fn flush(&mut self, now: Instant) {
+        if let Some((sample, time)) = self.previous_sample {
+            let elapsed = now.saturating_duration_since(time);
+            self.line_stats(sample).time += elapsed;
+            self.nodes[sample.node as usize].stats.time += elapsed;
+        }
+    }
+
+    fn line_stats(&mut self, sample: Sample) -> &mut ProfileStats {
+        let function = &self.functions[self.nodes[sample.node as usize].function as usize];
+        let line_id = *self
+            .line_ids
+            .entry((self.nodes[sample.node as usize].function, sample.line))
+            .or_insert_with(|| {
+                self.lines.push(Line {
+                    chunk: function.chunk.clone(),
+                    line: sample.line,
+                    stats: ProfileStats::default(),
+                });
+                self.lines.len() as u32 - 1
+            });
+        &mut self.lines[line_id as usize].stats
+    }
+
+    // Returns the results that have been recorded so far
+    pub fn profile(&self) -> Profile {
+        let mut call_tree: Vec<CallTreeNode> = self
+            .nodes
+            .iter()
+            .map(|node| CallTreeNode {
+                function: node.function as usize,
+                parent: node.parent.map(|parent| parent as usize),
+                children: Vec::new(),
+                calls: node.calls,
+                self_stats: node.stats,
+                total_stats: node.stats,
+            })
+            .collect();
+
+        // Nodes are always added after their parents,
+        // so the totals can be accumulated by iterating through the nodes in reverse.
+        for index in (0..call_tree.len()).rev() {
+            if let Some(parent) = call_tree[index].parent {
+                let total = call_tree[index].total_stats;
+                call_tree[parent].total_stats.add(&total);
+                call_tree[parent].children.push(index);
+            }
+        }
+        for node in call_tree.iter_mut() {
+            node.children.reverse();
+        }
+
+        let mut functions: Vec<ProfiledFunction> = self
+            .functions
+            .iter()
+            .map(|function| {
+                let (name, line) = function_name_and_line(&function.chunk, function.ip);
+                ProfiledFunction {
+                    name,
+                    source_path: function.chunk.source_path.clone(),
+                    line,
+                    calls: 0,
+                    self_stats: ProfileStats::default(),
+                    total_stats: ProfileStats::default(),
+                }
+            })
+            .collect();
+
+        for node in call_tree.iter() {
+            let function = &mut functions[node.function];
+            function.calls += node.calls;
+            function.self_stats.add(&node.self_stats);
+
+            // Recursive calls are already included in the outermost call's total
+            let mut ancestor = node.parent;
+            let mut is_recursive = false;
+            while let Some(index) = ancestor {
+                if call_tree[index].function == node.function {
+                    is_recursive = true;
+                    break;
+                }
+                ancestor = call_tree[index].parent;
+            }
+            if !is_recursive {
+                function.total_stats.add(&node.total_stats);
+            }
+        }
+
+        // Lines are merged by chunk, so that lines shared by nested functions are combined
+        let mut lines: Vec<ProfiledLine> = Vec::new();
+        let mut line_indices: FxHashMap<(Address, u32), usize> = FxHashMap::default();
+        for line in self.lines.iter() {
+            let index = *line_indices
+                .entry((Ptr::address(&line.chunk), line.line))
+                .or_insert_with(|| {
+                    lines.push(ProfiledLine {
+                        source_path: line.chunk.source_path.clone(),
+                        line: line.line,
+                        stats: ProfileStats::default(),
+                    });
+                    lines.len() - 1
+                });
+            lines[index].stats.add(&line.stats);
+        }
+
+        Profile {
+            functions,
+            lines,
+            call_tree,
+        }
+    }
+}
+
+// Returns the line number of the given ip, or 0 if the ip doesn't have a source span
+pub(crate) fn source_line(chunk: &Chunk, ip: u32) -> u32 {
+    chunk
+        .debug_info
+        .get_source_span(ip)
+        .map_or(0, |span| span.start.line)
+}
+
+// Returns the name of the function that starts at the given ip, along with its line number
+//
+// The chunk's instructions are scanned to find the instruction that creates the function.
+fn function_name_and_line(chunk: &Ptr<Chunk>, ip: u32) -> (String, u32) {
+    if ip == 0 {
+        return ("<main>".into(), source_line(chunk, ip));
+    }
+
+    let mut reader = InstructionReader::new(chunk.clone());
+    let mut instruction_ip = 0;
+    while let Some(instruction) = reader.next() {
+        if reader.ip as u32 > ip {
+            break;
+        }
+
+        if let Instruction::Function { register, .. } = instruction {
+            if reader.ip as u32 == ip {
+                return (
+                    function_name(chunk, instruction_ip, register),
+                    source_line(chunk, instruction_ip),
+                );
+            }
+        }
+
+        instruction_ip = reader.ip as u32;
+    }
+
+    ("<anonymous>".into(), source_line(chunk, ip))
+}
+
+// Returns the name of the function that's created by a Function instruction
+//
+// If the function is assigned to a local then the local's name is used.
+pub(crate) fn function_name(chunk: &Chunk, instruction_ip: u32, register: u8) -> String {
+    chunk
+        .debug_info
+        .locals_at(instruction_ip)
+        .find(|local| local.register == register)
+        .map(|local| chunk.constants.get_str(local.name).to_string())
+        .unwrap_or_else(|| "<anonymous>".into())
+}
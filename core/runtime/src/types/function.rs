@@ -1,6 +1,6 @@
-use crate::KList;
+use crate::{KList, Value};
 use koto_bytecode::Chunk;
-use koto_memory::Ptr;
+use koto_memory::{cycle_collector, Ptr};
 
 /// A Koto function
 ///
@@ -55,3 +55,13 @@ pub struct KCaptureFunction {
     // A. These could be worth investigating, but for now the KList will do.
     pub captures: KList,
 }
+
+impl From<KCaptureFunction> for Value {
+    fn from(function: KCaptureFunction) -> Self {
+        let function = Ptr::new(function);
+        cycle_collector::track_ptr(&function, |function, tracer| {
+            function.captures.trace(tracer)
+        });
+        Self::CaptureFunction(function)
+    }
+}
@@ -1,26 +1,29 @@
 use crate::{prelude::*, Result};
+use koto_memory::cycle_collector::{self, Tracer};
 
 /// The underlying Vec type used by [KList]
 pub type ValueVec = smallvec::SmallVec<[Value; 4]>;
 
 /// The Koto runtime's List type
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct KList(PtrMut<ValueVec>);
 
 impl KList {
     /// Creates an empty list with the given capacity
     pub fn with_capacity(capacity: usize) -> Self {
-        Self(ValueVec::with_capacity(capacity).into())
+        Self::with_data(ValueVec::with_capacity(capacity))
     }
 
     /// Creates a list containing the provided data
     pub fn with_data(data: ValueVec) -> Self {
-        Self(data.into())
+        let data = PtrMut::from(data);
+        cycle_collector::track_ptr_mut(&data, trace_values, |data| data.clear());
+        Self(data)
     }
 
     /// Creates a list containing the provided slice of [Values](crate::Value)
     pub fn from_slice(data: &[Value]) -> Self {
-        Self(data.iter().cloned().collect::<ValueVec>().into())
+        Self::with_data(data.iter().cloned().collect())
     }
 
     /// Returns the number of entries of the list
@@ -66,4 +69,21 @@ impl KList {
         ctx.append(']');
         Ok(())
     }
+
+    // Reports the list's data to the cycle collector, see [Value::trace]
+    pub(crate) fn trace(&self, tracer: &mut Tracer) {
+        tracer.visit_ptr_mut(&self.0);
+    }
+}
+
+impl Default for KList {
+    fn default() -> Self {
+        Self::with_data(ValueVec::new())
+    }
+}
+
+fn trace_values(values: &ValueVec, tracer: &mut Tracer) {
+    for value in values.iter() {
+        value.trace(tracer);
+    }
 }
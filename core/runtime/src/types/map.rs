@@ -1,7 +1,9 @@
 use crate::{prelude::*, Error, Result};
 use indexmap::IndexMap;
+use koto_memory::cycle_collector::{self, Tracer};
 use rustc_hash::FxHasher;
 use std::{
+    cell::Cell,
     hash::BuildHasherDefault,
     iter::IntoIterator,
     ops::{Deref, DerefMut},
@@ -12,44 +14,88 @@ pub type KotoHasher = FxHasher;
 
 type ValueMapType = IndexMap<ValueKey, Value, BuildHasherDefault<KotoHasher>>;
 
+thread_local! {
+    static NEXT_MAP_VERSION: Cell<u64> = const { Cell::new(1) };
+}
+
+// Returns a version number that hasn't been used by any other map
+//
+// Maps are given a new version whenever they're created or modified, which allows the runtime to
+// cache the results of lookups, see koto_bytecode::LookupCache.
+pub(crate) fn next_map_version() -> u64 {
+    NEXT_MAP_VERSION.with(|next| {
+        let version = next.get();
+        next.set(version + 1);
+        version
+    })
+}
+
 /// The (ValueKey -> Value) 'data' hashmap used by the Koto runtime
 ///
 /// See also: [KMap]
-#[derive(Clone, Default)]
-pub struct ValueMap(ValueMapType);
+pub struct ValueMap {
+    entries: ValueMapType,
+    version: u64,
+}
 
 impl ValueMap {
     /// Creates a new DataMap with the given capacity
     pub fn with_capacity(capacity: usize) -> Self {
-        Self(ValueMapType::with_capacity_and_hasher(
+        Self::from_entries(ValueMapType::with_capacity_and_hasher(
             capacity,
             Default::default(),
         ))
     }
+
+    fn from_entries(entries: ValueMapType) -> Self {
+        Self {
+            entries,
+            version: next_map_version(),
+        }
+    }
+
+    // The map's version, which changes whenever the map is modified
+    pub(crate) fn version(&self) -> u64 {
+        self.version
+    }
+}
+
+impl Clone for ValueMap {
+    fn clone(&self) -> Self {
+        Self::from_entries(self.entries.clone())
+    }
+}
+
+impl Default for ValueMap {
+    fn default() -> Self {
+        Self::from_entries(ValueMapType::default())
+    }
 }
 
 impl Deref for ValueMap {
     type Target = ValueMapType;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.entries
     }
 }
 
 impl DerefMut for ValueMap {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        // Any mutable access is treated as a modification
+        self.version = next_map_version();
+        &mut self.entries
     }
 }
 
 impl FromIterator<(ValueKey, Value)> for ValueMap {
     fn from_iter<T: IntoIterator<Item = (ValueKey, Value)>>(iter: T) -> ValueMap {
-        Self(ValueMapType::from_iter(iter))
+        Self::from_entries(ValueMapType::from_iter(iter))
     }
 }
 
 /// The core hashmap value type used in Koto, containing a [ValueMap] and a [MetaMap]
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct KMap {
     data: PtrMut<ValueMap>,
     meta: Option<PtrMut<MetaMap>>,
@@ -81,8 +127,8 @@ impl KMap {
     /// Creates a KMap initialized with the provided data and meta map
     pub fn with_contents(data: ValueMap, meta: Option<MetaMap>) -> Self {
         Self {
-            data: data.into(),
-            meta: meta.map(PtrMut::from),
+            data: make_data_ptr(data),
+            meta: meta.map(make_meta_ptr),
         }
     }
 
@@ -113,7 +159,7 @@ impl KMap {
 
     /// Sets the KMap's meta map
     pub fn set_meta_map(&mut self, meta: Option<MetaMap>) {
-        self.meta = meta.map(PtrMut::from)
+        self.meta = meta.map(make_meta_ptr)
     }
 
     /// Returns true if the meta map contains an entry with the given key
@@ -138,7 +184,7 @@ impl KMap {
     /// Inserts a value into the meta map, initializing the meta map if it doesn't yet exist
     pub fn insert_meta(&mut self, key: MetaKey, value: Value) {
         self.meta
-            .get_or_insert_with(Default::default)
+            .get_or_insert_with(|| make_meta_ptr(MetaMap::default()))
             .borrow_mut()
             .insert(key, value);
     }
@@ -221,6 +267,49 @@ impl KMap {
 
         Ok(())
     }
+
+    // Reports the map's data and meta map to the cycle collector, see [Value::trace]
+    pub(crate) fn trace(&self, tracer: &mut Tracer) {
+        tracer.visit_ptr_mut(&self.data);
+        if let Some(meta) = &self.meta {
+            tracer.visit_ptr_mut(meta);
+        }
+    }
+}
+
+impl Default for KMap {
+    fn default() -> Self {
+        Self::with_data(ValueMap::default())
+    }
+}
+
+fn make_data_ptr(data: ValueMap) -> PtrMut<ValueMap> {
+    let result = PtrMut::from(data);
+    cycle_collector::track_ptr_mut(
+        &result,
+        |data, tracer| {
+            for (key, value) in data.iter() {
+                key.value().trace(tracer);
+                value.trace(tracer);
+            }
+        },
+        |data| data.clear(),
+    );
+    result
+}
+
+fn make_meta_ptr(meta: MetaMap) -> PtrMut<MetaMap> {
+    let result = PtrMut::from(meta);
+    cycle_collector::track_ptr_mut(
+        &result,
+        |meta, tracer| {
+            for value in meta.values() {
+                value.trace(tracer);
+            }
+        },
+        |meta| meta.clear(),
+    );
+    result
 }
 
 #[cfg(test)]
@@ -234,7 +323,10 @@ mod tests {
         assert!(m.data().get("test").is_none());
         m.add_value("test", Value::Null);
         assert!(m.data().get("test").is_some());
-        assert!(matches!(m.data_mut().remove("test"), Some(Value::Null)));
+        assert!(matches!(
+            m.data_mut().shift_remove("test"),
+            Some(Value::Null)
+        ));
         assert!(m.data().get("test").is_none());
     }
 }
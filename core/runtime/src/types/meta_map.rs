@@ -1,4 +1,4 @@
-use crate::{prelude::*, Error, Result};
+use crate::{prelude::*, types::map::next_map_version, Error, Result};
 use indexmap::{Equivalent, IndexMap};
 use koto_parser::MetaKeyId;
 use std::{
@@ -13,13 +13,15 @@ type MetaMapType = IndexMap<MetaKey, Value, BuildHasherDefault<KotoHasher>>;
 ///
 /// Each KMap contains a metamap, which allows for customized value behaviour by implementing
 /// [MetaKeys](crate::MetaKey).
-#[derive(Clone, Default)]
-pub struct MetaMap(MetaMapType);
+pub struct MetaMap {
+    entries: MetaMapType,
+    version: u64,
+}
 
 impl MetaMap {
     /// Extends the MetaMap with clones of another MetaMap's entries
     pub fn extend(&mut self, other: &MetaMap) {
-        self.0.extend(other.0.clone());
+        self.deref_mut().extend(other.entries.clone());
     }
 
     /// Adds a function to the meta map
@@ -28,8 +30,31 @@ impl MetaMap {
         key: MetaKey,
         f: impl Fn(&mut CallContext) -> Result<Value> + 'static,
     ) {
-        self.0
-            .insert(key, Value::NativeFunction(KNativeFunction::new(f)));
+        self.insert(key, Value::NativeFunction(KNativeFunction::new(f)));
+    }
+
+    fn from_entries(entries: MetaMapType) -> Self {
+        Self {
+            entries,
+            version: next_map_version(),
+        }
+    }
+
+    // The meta map's version, which changes whenever the meta map is modified
+    pub(crate) fn version(&self) -> u64 {
+        self.version
+    }
+}
+
+impl Clone for MetaMap {
+    fn clone(&self) -> Self {
+        Self::from_entries(self.entries.clone())
+    }
+}
+
+impl Default for MetaMap {
+    fn default() -> Self {
+        Self::from_entries(MetaMapType::default())
     }
 }
 
@@ -37,13 +62,15 @@ impl Deref for MetaMap {
     type Target = MetaMapType;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.entries
     }
 }
 
 impl DerefMut for MetaMap {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        // Any mutable access is treated as a modification
+        self.version = next_map_version();
+        &mut self.entries
     }
 }
 
@@ -62,6 +89,10 @@ pub enum MetaKey {
     ///
     /// Defines the behaviour when performing a function call on the value.
     Call,
+    /// `@format`
+    ///
+    /// Used to define a function that formats the value with a format spec, e.g. `'{:>8}'`.
+    Format,
     /// A named key
     ///
     /// e.g. `@meta my_named_key`
@@ -242,6 +273,7 @@ pub fn meta_id_to_key(id: MetaKeyId, name: Option<KString>) -> Result<MetaKey> {
         MetaKeyId::Negate => MetaKey::UnaryOp(Negate),
         MetaKeyId::Not => MetaKey::UnaryOp(Not),
         MetaKeyId::Display => MetaKey::UnaryOp(Display),
+        MetaKeyId::Format => MetaKey::Format,
         MetaKeyId::Call => MetaKey::Call,
         MetaKeyId::Named => {
             MetaKey::Named(name.ok_or_else(|| Error::from("Missing name for named meta entry"))?)
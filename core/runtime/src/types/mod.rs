@@ -29,3 +29,5 @@ pub use self::{
     value::Value,
     value_key::ValueKey,
 };
+
+pub(crate) use self::number::MAX_BIG_INT_BITS;
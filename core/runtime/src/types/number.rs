@@ -1,4 +1,7 @@
-use crate::Value;
+use crate::{Ptr, Value};
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Signed, ToPrimitive, Zero};
+use rust_decimal::{Decimal, MathematicalOps};
 use std::{
     cmp::Ordering,
     fmt,
@@ -6,62 +9,99 @@ use std::{
     ops,
 };
 
+/// The maximum size of a big integer, in bits
+///
+/// Integer operations that would produce a larger result throw an error.
+pub(crate) const MAX_BIG_INT_BITS: u64 = 1 << 22;
+
 /// The Number type used by the Koto runtime
 ///
 /// The number can be either an `f64` or an `i64` depending on usage.
+///
+/// Integer operations that overflow an `i64` produce a [BigInt](KNumber::BigInt),
+/// and exact decimal numbers can be created with `number.decimal`.
 #[allow(missing_docs)]
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub enum KNumber {
     F64(f64),
     I64(i64),
+    /// An integer that's outside of the range of an `i64`
+    ///
+    /// Integers that fit in an `i64` are always represented with [I64](KNumber::I64).
+    BigInt(Ptr<BigInt>),
+    /// An exact decimal number
+    Decimal(Ptr<Decimal>),
 }
 
 impl KNumber {
     /// Returns the absolute value of the number
     #[must_use]
-    pub fn abs(self) -> Self {
+    pub fn abs(&self) -> Self {
         match self {
             Self::F64(n) => Self::F64(n.abs()),
-            Self::I64(n) => Self::I64(n.abs()),
+            Self::I64(n) => match n.checked_abs() {
+                Some(n) => Self::I64(n),
+                None => BigInt::from(*n).abs().into(),
+            },
+            Self::BigInt(n) => n.abs().into(),
+            Self::Decimal(n) => n.abs().into(),
         }
     }
 
     /// Returns the smallest integer greater than or equal to the number
     #[must_use]
-    pub fn ceil(self) -> Self {
+    pub fn ceil(&self) -> Self {
         match self {
             Self::F64(n) => Self::I64(n.ceil() as i64),
-            Self::I64(n) => Self::I64(n),
+            Self::Decimal(n) => Self::from_integral_decimal(n.ceil()),
+            _ => self.clone(),
         }
     }
 
     /// Returns the largest integer less than or equal to the number
     #[must_use]
-    pub fn floor(self) -> Self {
-        Self::I64(self.as_i64())
+    pub fn floor(&self) -> Self {
+        match self {
+            Self::F64(n) => Self::I64(n.floor() as i64),
+            Self::Decimal(n) => Self::from_integral_decimal(n.floor()),
+            _ => self.clone(),
+        }
     }
 
     /// Returns the integer closest to the number
     ///
     /// Half-way values get rounded away from zero.
     #[must_use]
-    pub fn round(self) -> Self {
+    pub fn round(&self) -> Self {
         match self {
             Self::F64(n) => Self::I64(n.round() as i64),
-            Self::I64(n) => Self::I64(n),
+            Self::Decimal(n) => Self::from_integral_decimal(
+                n.round_dp_with_strategy(0, rust_decimal::RoundingStrategy::MidpointAwayFromZero),
+            ),
+            _ => self.clone(),
         }
     }
 
     /// Returns true if the number is represented by an `f64`
-    pub fn is_f64(self) -> bool {
+    pub fn is_f64(&self) -> bool {
         matches!(self, Self::F64(_))
     }
 
     /// Returns true if the number is represented by an `i64`
-    pub fn is_i64(self) -> bool {
+    pub fn is_i64(&self) -> bool {
         matches!(self, Self::I64(_))
     }
 
+    /// Returns true if the number is a big integer
+    pub fn is_big_int(&self) -> bool {
+        matches!(self, Self::BigInt(_))
+    }
+
+    /// Returns true if the number is an exact decimal
+    pub fn is_decimal(&self) -> bool {
+        matches!(self, Self::Decimal(_))
+    }
+
     /// Returns true if the integer version of the number is representable by an `f64`
     pub fn is_i64_in_f64_range(&self) -> bool {
         if let Self::I64(n) = *self {
@@ -72,59 +112,217 @@ impl KNumber {
     }
 
     /// Returns true if the number is not NaN or infinity
-    pub fn is_finite(self) -> bool {
+    pub fn is_finite(&self) -> bool {
         match self {
             Self::F64(n) => n.is_finite(),
-            Self::I64(_) => true,
+            _ => true,
         }
     }
 
     /// Returns true if the number is NaN
-    pub fn is_nan(self) -> bool {
+    pub fn is_nan(&self) -> bool {
         match self {
             Self::F64(n) => n.is_nan(),
-            Self::I64(_) => false,
+            _ => false,
         }
     }
 
     /// Returns the result of raising self to the power of `other`
     ///
-    /// If both inputs are i64s then the result will also be an i64,
+    /// If both inputs are integers and the exponent isn't negative then the result will also be
+    /// an integer, decimals raised to integer powers produce decimals,
     /// otherwise the result will be an f64.
     #[must_use]
-    pub fn pow(self, other: Self) -> Self {
+    pub fn pow(&self, other: &Self) -> Self {
         use KNumber::*;
 
+        let float_pow = || F64(f64::from(self).powf(f64::from(other)));
+
         match (self, other) {
-            (F64(a), F64(b)) => F64(a.powf(b)),
-            (F64(a), I64(b)) => F64(a.powf(b as f64)),
-            (I64(a), F64(b)) => F64((a as f64).powf(b)),
-            (I64(a), I64(b)) => I64(a.pow(b as u32)),
+            (I64(a), I64(b)) => match u32::try_from(*b) {
+                Ok(b) => match a.checked_pow(b) {
+                    Some(result) => I64(result),
+                    None => num_bigint::BigInt::from(*a).pow(b).into(),
+                },
+                Err(_) => float_pow(),
+            },
+            (BigInt(a), I64(b)) => match u32::try_from(*b) {
+                Ok(b) => a.pow(b).into(),
+                Err(_) => float_pow(),
+            },
+            (Decimal(a), I64(b)) => match a.checked_powi(*b) {
+                Some(result) => result.into(),
+                None => float_pow(),
+            },
+            _ => float_pow(),
         }
     }
 
     /// Returns the value transmuted to a `u64`
-    pub fn to_bits(self) -> u64 {
+    ///
+    /// Big integers and decimals are converted to an `f64` before being transmuted.
+    pub fn to_bits(&self) -> u64 {
         match self {
             Self::F64(n) => n.to_bits(),
-            Self::I64(n) => n as u64,
+            Self::I64(n) => *n as u64,
+            _ => f64::from(self).to_bits(),
         }
     }
 
-    /// Returns the number as an `i64`, calling `floor` if the number is an `f64`
-    pub fn as_i64(self) -> i64 {
-        match self {
-            Self::F64(n) => n.floor() as i64,
+    /// Returns the number as an `i64`, calling `floor` if the number isn't an integer
+    ///
+    /// Big integers that are out of range are clamped to `i64::MIN` or `i64::MAX`.
+    pub fn as_i64(&self) -> i64 {
+        match self.floor() {
+            Self::F64(n) => n as i64,
             Self::I64(n) => n,
+            Self::BigInt(n) => {
+                if n.is_negative() {
+                    i64::MIN
+                } else {
+                    i64::MAX
+                }
+            }
+            Self::Decimal(n) => n.to_i64().unwrap_or_default(),
+        }
+    }
+
+    /// Returns the number as a [Decimal], if the number can be represented as a decimal
+    pub fn to_decimal(&self) -> Option<Decimal> {
+        match self {
+            Self::F64(n) => Decimal::from_f64(*n),
+            Self::I64(n) => Some((*n).into()),
+            Self::BigInt(n) => n.to_i128().and_then(Decimal::from_i128),
+            Self::Decimal(n) => Some(**n),
+        }
+    }
+
+    // Returns the number as a BigInt, integers only
+    fn to_big_int(&self) -> Option<BigInt> {
+        match self {
+            Self::I64(n) => Some((*n).into()),
+            Self::BigInt(n) => Some((**n).clone()),
+            _ => None,
+        }
+    }
+
+    // Returns the number of bits needed for the integer's magnitude, or 0 for non-integers
+    pub(crate) fn integer_bits(&self) -> u64 {
+        match self {
+            Self::I64(n) => u64::from(64 - n.unsigned_abs().leading_zeros()),
+            Self::BigInt(n) => n.bits(),
+            _ => 0,
+        }
+    }
+
+    // Returns an upper bound for the number of bits in the result of multiplying two integers
+    pub(crate) fn multiply_bits(&self, other: &Self) -> u64 {
+        self.integer_bits().saturating_add(other.integer_bits())
+    }
+
+    // Returns an upper bound for the number of bits in the result of [KNumber::pow]
+    pub(crate) fn pow_bits(&self, other: &Self) -> u64 {
+        match (self.integer_bits(), other) {
+            // 0, 1, and -1 don't grow when raised to a power
+            (bits @ (0 | 1), _) => bits,
+            (bits, Self::I64(exponent)) if *exponent > 0 => bits.saturating_mul(*exponent as u64),
+            _ => 0,
+        }
+    }
+
+    // Converts a decimal with no fractional part into an integer
+    pub(crate) fn from_integral_decimal(n: Decimal) -> Self {
+        match n.to_i64() {
+            Some(n) => Self::I64(n),
+            None => n
+                .to_i128()
+                .map_or(Self::F64(f64::from(&n.into())), |n| BigInt::from(n).into()),
+        }
+    }
+
+    // Compares two numbers, using the most precise representation that's shared by both numbers
+    fn compare(&self, other: &Self) -> Option<Ordering> {
+        use KNumber::*;
+
+        match (self, other) {
+            (I64(a), I64(b)) => a.partial_cmp(b),
+            (F64(_), _) | (_, F64(_)) => f64::from(self).partial_cmp(&f64::from(other)),
+            (Decimal(_), _) | (_, Decimal(_)) => match (self.to_decimal(), other.to_decimal()) {
+                (Some(a), Some(b)) => a.partial_cmp(&b),
+                _ => f64::from(self).partial_cmp(&f64::from(other)),
+            },
+            _ => self.to_big_int().partial_cmp(&other.to_big_int()),
+        }
+    }
+
+    // Performs an arithmetic operation, promoting integers to big integers on overflow
+    //
+    // If an operation isn't possible with integers or decimals (e.g. division by zero),
+    // then the operation falls back to using floats.
+    fn arithmetic_op(
+        &self,
+        other: &Self,
+        float_op: fn(f64, f64) -> f64,
+        int_op: fn(i64, i64) -> Option<i64>,
+        big_int_op: fn(BigInt, BigInt) -> Option<BigInt>,
+        decimal_op: fn(Decimal, Decimal) -> Option<Decimal>,
+    ) -> Self {
+        use KNumber::*;
+
+        let float_result = || F64(float_op(f64::from(self), f64::from(other)));
+
+        match (self, other) {
+            (I64(a), I64(b)) => match int_op(*a, *b) {
+                Some(result) => I64(result),
+                None => big_int_op((*a).into(), (*b).into()).map_or_else(float_result, Self::from),
+            },
+            (F64(_), _) | (_, F64(_)) => float_result(),
+            (Decimal(_), _) | (_, Decimal(_)) => match (self.to_decimal(), other.to_decimal()) {
+                (Some(a), Some(b)) => decimal_op(a, b).map_or_else(float_result, Self::from),
+                _ => float_result(),
+            },
+            _ => match (self.to_big_int(), other.to_big_int()) {
+                (Some(a), Some(b)) => big_int_op(a, b).map_or_else(float_result, Self::from),
+                _ => float_result(),
+            },
         }
     }
 }
 
+impl From<BigInt> for KNumber {
+    fn from(n: BigInt) -> Self {
+        match n.to_i64() {
+            Some(n) => Self::I64(n),
+            None => Self::BigInt(n.into()),
+        }
+    }
+}
+
+impl From<Decimal> for KNumber {
+    fn from(n: Decimal) -> Self {
+        Self::Decimal(n.into())
+    }
+}
+
+impl From<BigInt> for Value {
+    fn from(n: BigInt) -> Self {
+        Self::Number(n.into())
+    }
+}
+
+impl From<Decimal> for Value {
+    fn from(n: Decimal) -> Self {
+        Self::Number(n.into())
+    }
+}
+
 impl fmt::Debug for KNumber {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             KNumber::F64(n) => write!(f, "Float({n})"),
             KNumber::I64(n) => write!(f, "Int({n})"),
+            KNumber::BigInt(n) => write!(f, "BigInt({n})"),
+            KNumber::Decimal(n) => write!(f, "Decimal({n})"),
         }
     }
 }
@@ -140,26 +338,32 @@ impl fmt::Display for KNumber {
                 }
             }
             KNumber::I64(n) => write!(f, "{n}"),
+            KNumber::BigInt(n) => write!(f, "{n}"),
+            KNumber::Decimal(n) => write!(f, "{n}"),
         }
     }
 }
 
 impl Hash for KNumber {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        state.write_u64(self.to_bits())
+        // Numbers of different kinds can be equal, e.g. `1 == 1.0`, and comparisons with floats
+        // are made via `f64`, so all numbers are hashed via their `f64` representation.
+        let n = f64::from(self);
+        let bits = if n == 0.0 {
+            // -0.0 is equal to 0.0
+            0
+        } else if n.is_nan() {
+            f64::NAN.to_bits()
+        } else {
+            n.to_bits()
+        };
+        state.write_u64(bits)
     }
 }
 
 impl PartialEq for KNumber {
     fn eq(&self, other: &Self) -> bool {
-        use KNumber::*;
-
-        match (self, other) {
-            (F64(a), F64(b)) => a == b,
-            (F64(a), I64(b)) => *a == *b as f64,
-            (I64(a), F64(b)) => *a as f64 == *b,
-            (I64(a), I64(b)) => a == b,
-        }
+        self.compare(other) == Some(Ordering::Equal)
     }
 }
 
@@ -173,16 +377,7 @@ impl PartialOrd for KNumber {
 
 impl Ord for KNumber {
     fn cmp(&self, other: &Self) -> Ordering {
-        use KNumber::*;
-
-        let result = match (self, other) {
-            (F64(a), F64(b)) => a.partial_cmp(b),
-            (F64(a), I64(b)) => a.partial_cmp(&(*b as f64)),
-            (I64(a), F64(b)) => (*a as f64).partial_cmp(b),
-            (I64(a), I64(b)) => a.partial_cmp(b),
-        };
-
-        match result {
+        match self.compare(other) {
             Some(result) => result,
             None => match (self.is_nan(), other.is_nan()) {
                 (false, true) => Ordering::Less,
@@ -197,12 +392,7 @@ impl ops::Neg for KNumber {
     type Output = KNumber;
 
     fn neg(self) -> KNumber {
-        use KNumber::*;
-
-        match self {
-            F64(n) => F64(-n),
-            I64(n) => I64(-n),
-        }
+        -&self
     }
 }
 
@@ -212,9 +402,14 @@ impl ops::Neg for &KNumber {
     fn neg(self) -> KNumber {
         use KNumber::*;
 
-        match *self {
+        match self {
             F64(n) => F64(-n),
-            I64(n) => I64(-n),
+            I64(n) => match n.checked_neg() {
+                Some(n) => I64(n),
+                None => (-num_bigint::BigInt::from(*n)).into(),
+            },
+            BigInt(n) => (-&**n).into(),
+            Decimal(n) => (-**n).into(),
         }
     }
 }
@@ -247,21 +442,13 @@ macro_rules! number_traits_float {
 
         impl PartialEq<$type> for KNumber {
             fn eq(&self, b: &$type) -> bool {
-                let b = *b as f64;
-                match self {
-                    KNumber::F64(a) => *a == b,
-                    KNumber::I64(a) => *a as f64 == b,
-                }
+                self.compare(&KNumber::from(b)) == Some(Ordering::Equal)
             }
         }
 
         impl PartialOrd<$type> for KNumber {
             fn partial_cmp(&self, b: &$type) -> Option<Ordering> {
-                let b = *b as f64;
-                match self {
-                    KNumber::F64(a) => a.partial_cmp(&b),
-                    KNumber::I64(a) => (*a as f64).partial_cmp(&b),
-                }
+                self.compare(&KNumber::from(b))
             }
         }
     };
@@ -299,6 +486,7 @@ macro_rules! number_traits_int {
                 match self {
                     KNumber::F64(a) => (*a as i64) == b,
                     KNumber::I64(a) => *a == b,
+                    _ => self.compare(&KNumber::I64(b)) == Some(Ordering::Equal),
                 }
             }
         }
@@ -309,6 +497,7 @@ macro_rules! number_traits_int {
                 match self {
                     KNumber::F64(a) => (*a as i64).partial_cmp(&b),
                     KNumber::I64(a) => a.partial_cmp(&b),
+                    _ => self.compare(&KNumber::I64(b)),
                 }
             }
         }
@@ -329,52 +518,77 @@ number_traits_int!(u64);
 number_traits_int!(isize);
 number_traits_int!(usize);
 
-macro_rules! from_number {
+// Converts a decimal to an `f64`, with equal decimals always producing the same result
+//
+// Integral decimals are converted in the same way as integers, which keeps hashing consistent
+// with comparisons between decimals and integers.
+fn decimal_to_f64(d: &Decimal) -> f64 {
+    let d = d.normalize();
+    if d.scale() == 0 {
+        d.mantissa() as f64
+    } else {
+        d.to_f64().unwrap_or(f64::NAN)
+    }
+}
+
+macro_rules! from_number_float {
     ($type:ident) => {
         impl From<KNumber> for $type {
             fn from(n: KNumber) -> $type {
+                $type::from(&n)
+            }
+        }
+
+        impl From<&KNumber> for $type {
+            fn from(n: &KNumber) -> $type {
                 match n {
-                    KNumber::F64(f) => f as $type,
-                    KNumber::I64(i) => i as $type,
+                    KNumber::F64(f) => *f as $type,
+                    KNumber::I64(i) => *i as $type,
+                    KNumber::BigInt(i) => i.to_f64().unwrap_or(f64::NAN) as $type,
+                    KNumber::Decimal(d) => decimal_to_f64(d) as $type,
                 }
             }
         }
+    };
+}
+
+macro_rules! from_number_int {
+    ($type:ident) => {
+        impl From<KNumber> for $type {
+            fn from(n: KNumber) -> $type {
+                $type::from(&n)
+            }
+        }
 
         impl From<&KNumber> for $type {
             fn from(n: &KNumber) -> $type {
                 match n {
                     KNumber::F64(f) => *f as $type,
                     KNumber::I64(i) => *i as $type,
+                    _ => n.as_i64() as $type,
                 }
             }
         }
     };
 }
 
-from_number!(f32);
-from_number!(f64);
+from_number_float!(f32);
+from_number_float!(f64);
 
-from_number!(i32);
-from_number!(u32);
-from_number!(i64);
-from_number!(u64);
-from_number!(isize);
-from_number!(usize);
+from_number_int!(i32);
+from_number_int!(u32);
+from_number_int!(i64);
+from_number_int!(u64);
+from_number_int!(isize);
+from_number_int!(usize);
 
 macro_rules! number_op {
-    ($trait:ident, $fn:ident, $op:tt) => {
+    ($trait:ident, $fn:ident, $op:tt, $checked_fn:ident) => {
         impl ops::$trait for KNumber {
             type Output = KNumber;
 
             fn $fn(self, other: KNumber) -> KNumber {
-                use KNumber::*;
-
-                match (self, other) {
-                    (F64(a), F64(b)) => F64(a $op b),
-                    (F64(a), I64(b)) => F64(a $op b as f64),
-                    (I64(a), F64(b)) => F64(a as f64 $op b),
-                    (I64(a), I64(b)) => I64(a $op b),
-                }
+                &self $op &other
             }
         }
 
@@ -382,36 +596,49 @@ macro_rules! number_op {
             type Output = KNumber;
 
             fn $fn(self, other: &KNumber) -> KNumber {
-                use KNumber::*;
-
-                match (*self, *other) {
-                    (F64(a), F64(b)) => F64(a $op b),
-                    (F64(a), I64(b)) => F64(a $op b as f64),
-                    (I64(a), F64(b)) => F64(a as f64 $op b),
-                    (I64(a), I64(b)) => I64(a $op b),
-                }
+                self.arithmetic_op(
+                    other,
+                    |a, b| a $op b,
+                    |a, b| a.$checked_fn(b),
+                    |a, b| Some(a $op b),
+                    |a, b| a.$checked_fn(b),
+                )
             }
         }
     };
 }
 
-number_op!(Add, add, +);
-number_op!(Sub, sub, -);
-number_op!(Mul, mul, *);
-number_op!(Rem, rem, %);
+number_op!(Add, add, +, checked_add);
+number_op!(Sub, sub, -, checked_sub);
+number_op!(Mul, mul, *, checked_mul);
+
+impl ops::Rem for KNumber {
+    type Output = KNumber;
+
+    fn rem(self, other: KNumber) -> KNumber {
+        &self % &other
+    }
+}
+
+impl ops::Rem for &KNumber {
+    type Output = KNumber;
+
+    fn rem(self, other: &KNumber) -> KNumber {
+        self.arithmetic_op(
+            other,
+            |a, b| a % b,
+            |a, b| a.checked_rem(b),
+            |a, b| if b.is_zero() { None } else { Some(a % b) },
+            |a, b| a.checked_rem(b),
+        )
+    }
+}
 
 impl ops::Div for KNumber {
     type Output = KNumber;
 
     fn div(self, other: KNumber) -> KNumber {
-        use KNumber::*;
-
-        match (self, other) {
-            (F64(a), F64(b)) => F64(a / b),
-            (F64(a), I64(b)) => F64(a / b as f64),
-            (I64(a), F64(b)) => F64(a as f64 / b),
-            (I64(a), I64(b)) => F64(a as f64 / b as f64),
-        }
+        &self / &other
     }
 }
 
@@ -421,11 +648,18 @@ impl ops::Div for &KNumber {
     fn div(self, other: &KNumber) -> KNumber {
         use KNumber::*;
 
-        match (*self, *other) {
-            (F64(a), F64(b)) => F64(a / b),
-            (F64(a), I64(b)) => F64(a / b as f64),
-            (I64(a), F64(b)) => F64(a as f64 / b),
-            (I64(a), I64(b)) => F64(a as f64 / b as f64),
+        match (self, other) {
+            // Integer division produces a float, unless a decimal is involved
+            (Decimal(_), I64(_) | BigInt(_) | Decimal(_)) | (I64(_) | BigInt(_), Decimal(_)) => {
+                match (self.to_decimal(), other.to_decimal()) {
+                    (Some(a), Some(b)) => match a.checked_div(b) {
+                        Some(result) => result.into(),
+                        None => F64(f64::from(self) / f64::from(other)),
+                    },
+                    _ => F64(f64::from(self) / f64::from(other)),
+                }
+            }
+            _ => F64(f64::from(self) / f64::from(other)),
         }
     }
 }
@@ -1,5 +1,6 @@
 use crate::{prelude::*, KNativeFunction, Result};
 use downcast_rs::{impl_downcast, Downcast};
+use koto_memory::cycle_collector::{self, Tracer};
 use std::{cell::RefCell, marker::PhantomData, rc::Rc};
 
 /// A trait for implementing objects that can be added to the Koto runtime
@@ -66,6 +67,17 @@ pub trait KotoObject: Downcast {
         Ok(())
     }
 
+    /// Called when the object is formatted with a format spec, e.g. `'{:>8.2}'.format x`
+    ///
+    /// The formatted object should be appended to the [DisplayContext], with `true` returned to
+    /// indicate that the object has been formatted.
+    ///
+    /// By default, `false` is returned, and the object's display string is formatted using the
+    /// spec's fill, alignment, width, and precision.
+    fn format_with_spec(&self, _ctx: &mut DisplayContext, _spec: &FormatSpec) -> Result<bool> {
+        Ok(false)
+    }
+
     /// Returns a [Value] corresponding to the specified key within the object
     ///
     /// This method is used to retrieve a named entry attached to an object, providing a way to
@@ -211,6 +223,17 @@ pub trait KotoObject: Downcast {
     fn iterator_next_back(&mut self, _vm: &mut Vm) -> Option<KIteratorOutput> {
         None
     }
+
+    /// Reports the Koto values held by the object to the cycle collector
+    ///
+    /// Objects that hold Koto values should call [Value::trace] for each of them, otherwise any
+    /// cycles that pass through the object won't be found by [Vm::collect_garbage].
+    fn trace(&self, _tracer: &mut Tracer) {}
+
+    /// Called by the cycle collector when the object is part of an unreachable cycle
+    ///
+    /// The object should drop any Koto values that it holds, which breaks the cycle.
+    fn release_values(&mut self) {}
 }
 
 impl_downcast!(KotoObject);
@@ -260,13 +283,22 @@ impl KObject {
     pub fn is_same_instance(&self, other: &Self) -> bool {
         PtrMut::ptr_eq(&self.object, &other.object)
     }
+
+    // Reports the object to the cycle collector, see [Value::trace]
+    pub(crate) fn trace(&self, tracer: &mut Tracer) {
+        tracer.visit_ptr_mut(&self.object);
+    }
 }
 
 impl<T: KotoObject> From<T> for KObject {
     fn from(object: T) -> Self {
-        Self {
-            object: PtrMut::from(Rc::new(RefCell::new(object)) as Rc<RefCell<dyn KotoObject>>),
-        }
+        let object = PtrMut::from(Rc::new(RefCell::new(object)) as Rc<RefCell<dyn KotoObject>>);
+        cycle_collector::track_ptr_mut(
+            &object,
+            |object: &dyn KotoObject, tracer| object.trace(tracer),
+            |object: &mut dyn KotoObject| object.release_values(),
+        );
+        Self { object }
     }
 }
 
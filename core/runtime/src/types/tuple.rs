@@ -1,4 +1,5 @@
 use crate::{prelude::*, Result};
+use koto_memory::cycle_collector::{self, Tracer};
 use std::ops::{Deref, Range};
 
 /// The Tuple type used by the Koto runtime
@@ -22,6 +23,16 @@ struct TupleSlice {
 }
 
 impl KTuple {
+    // Makes a tuple from newly allocated data, registering the data with the cycle collector
+    fn with_data(data: Ptr<[Value]>) -> Self {
+        cycle_collector::track_ptr(&data, |values, tracer| {
+            for value in values.iter() {
+                value.trace(tracer);
+            }
+        });
+        Self(Inner::Full(data))
+    }
+
     /// Returns a new tuple with shared data and with restricted bounds
     ///
     /// The provided bounds should have indices relative to the current tuple's bounds
@@ -129,6 +140,16 @@ impl KTuple {
 
         Ok(())
     }
+
+    // Reports the tuple's data to the cycle collector, see [Value::trace]
+    //
+    // Slices aren't tracked by the collector, so their data is treated as being externally
+    // referenced.
+    pub(crate) fn trace(&self, tracer: &mut Tracer) {
+        if let Inner::Full(data) = &self.0 {
+            tracer.visit_ptr(data);
+        }
+    }
 }
 
 impl Deref for KTuple {
@@ -150,13 +171,13 @@ impl Default for KTuple {
 
 impl From<&[Value]> for KTuple {
     fn from(data: &[Value]) -> Self {
-        Self(Inner::Full(data.into()))
+        Self::with_data(data.into())
     }
 }
 
 impl From<Vec<Value>> for KTuple {
     fn from(data: Vec<Value>) -> Self {
-        Self(Inner::Full(data.into()))
+        Self::with_data(data.into())
     }
 }
 
@@ -1,6 +1,7 @@
 //! The core value type used in the Koto runtime
 
 use crate::{prelude::*, KCaptureFunction, KFunction, KMap, KNativeFunction, Result};
+use koto_memory::cycle_collector::Tracer;
 use std::fmt::Write;
 
 /// The core Value type for Koto
@@ -169,7 +170,8 @@ impl Value {
             Null => TYPE_NULL.with(|x| x.clone()),
             Bool(_) => TYPE_BOOL.with(|x| x.clone()),
             Number(KNumber::F64(_)) => TYPE_FLOAT.with(|x| x.clone()),
-            Number(KNumber::I64(_)) => TYPE_INT.with(|x| x.clone()),
+            Number(KNumber::I64(_) | KNumber::BigInt(_)) => TYPE_INT.with(|x| x.clone()),
+            Number(KNumber::Decimal(_)) => TYPE_DECIMAL.with(|x| x.clone()),
             List(_) => TYPE_LIST.with(|x| x.clone()),
             Range { .. } => TYPE_RANGE.with(|x| x.clone()),
             Map(m) if m.meta_map().is_some() => match m.get_meta_value(&MetaKey::Type) {
@@ -194,6 +196,32 @@ impl Value {
         }
     }
 
+    /// Returns true if the value matches the given type name
+    ///
+    /// Along with the type names returned by [Value::type_as_string], the following names are
+    /// accepted:
+    ///   - `Any`: matches any value.
+    ///   - `Number`: matches `Int`, `Float`, and `Decimal` values.
+    ///   - `Map`: matches any map, including maps that define a `@type`.
+    ///   - `Tuple`: also matches temporary tuples.
+    ///   - `Function`: matches any function, including generators and native functions.
+    ///   - `Iterable`: matches any value that can be iterated over.
+    ///
+    /// Used when checking type hints, see
+    /// [Op::AssertType](koto_bytecode::Op::AssertType).
+    pub fn matches_type(&self, type_name: &str) -> bool {
+        use Value::*;
+        match type_name {
+            "Any" => true,
+            "Number" => matches!(self, Number(_)),
+            "Map" => matches!(self, Map(_)),
+            "Tuple" => matches!(self, Tuple(_) | TemporaryTuple(_)),
+            "Function" => matches!(self, Function(_) | CaptureFunction(_) | NativeFunction(_)),
+            "Iterable" => self.is_iterable(),
+            _ => self.type_as_string() == type_name,
+        }
+    }
+
     /// Returns true if the value is a Map or an External that contains the given meta key
     pub fn contains_meta_key(&self, key: &MetaKey) -> bool {
         use Value::*;
@@ -212,6 +240,22 @@ impl Value {
         }
     }
 
+    /// Reports the allocations held by the value to the cycle collector
+    ///
+    /// This should be called by [KotoObject::trace] implementations for each value that's held by
+    /// the object, see [Vm::collect_garbage].
+    pub fn trace(&self, tracer: &mut Tracer) {
+        use Value::*;
+        match self {
+            List(l) => l.trace(tracer),
+            Tuple(t) => t.trace(tracer),
+            Map(m) => m.trace(tracer),
+            CaptureFunction(f) => tracer.visit_ptr(f),
+            Object(o) => o.trace(tracer),
+            _ => {}
+        }
+    }
+
     /// Renders the value into the provided display context
     pub fn display(&self, ctx: &mut DisplayContext) -> Result<()> {
         use Value::*;
@@ -245,6 +289,7 @@ thread_local! {
     static TYPE_BOOL: KString = "Bool".into();
     static TYPE_FLOAT: KString = "Float".into();
     static TYPE_INT: KString = "Int".into();
+    static TYPE_DECIMAL: KString = "Decimal".into();
     static TYPE_LIST: KString = "List".into();
     static TYPE_RANGE: KString = "Range".into();
     static TYPE_MAP: KString = "Map".into();
@@ -82,7 +82,7 @@ impl PartialOrd for ValueKey {
             (Null, _) => Some(Ordering::Less),
             (_, Null) => Some(Ordering::Greater),
             (Number(a), Number(b)) => a.partial_cmp(b),
-            (Str(a), Str(b)) => a.partial_cmp(b),
+            (Str(a), Str(b)) => a.as_str().partial_cmp(b.as_str()),
             (Tuple(a), Tuple(b)) => match a.len().cmp(&b.len()) {
                 Ordering::Equal => {
                     for (value_a, value_b) in a.iter().zip(b.iter()) {
@@ -1,12 +1,21 @@
 use crate::{
-    core_lib::CoreLib,
-    error::{Error, ErrorKind},
+    core_lib::{string::format, CoreLib},
+    coverage::{Coverage, CoverageRecorder},
+    debugger::{DebugFrame, DebugHook, DebugLocal, Debugger},
+    error::{Error, ErrorKind, TraceLocal},
+    execution_limits::{ExecutionLimits, MAP_ENTRY_SIZE, VALUE_SIZE},
     prelude::*,
-    types::{meta_id_to_key, value::RegisterSlice},
-    DefaultStderr, DefaultStdin, DefaultStdout, KCaptureFunction, KFunction, Result,
+    profiler::{source_line, Profile, Profiler},
+    types::{meta_id_to_key, value::RegisterSlice, MAX_BIG_INT_BITS},
+    Capabilities, DefaultStderr, DefaultStdin, DefaultStdout, InterruptHandle, KCaptureFunction,
+    KFunction, Result,
 };
-use koto_bytecode::{Chunk, Instruction, InstructionReader, Loader, TypeId};
-use koto_parser::{ConstantIndex, MetaKeyId};
+use indexmap::map::Entry;
+use koto_bytecode::{
+    CachedLookupEntry, CachedLookupStep, Chunk, Instruction, InstructionReader, Loader, TypeId,
+};
+use koto_memory::cycle_collector::{self, CollectionStats, TrackingGuard};
+use koto_parser::{ConstantIndex, MetaKeyId, StringFormatOptions};
 use rustc_hash::FxHasher;
 use std::{
     cell::RefCell,
@@ -15,8 +24,12 @@ use std::{
     hash::BuildHasherDefault,
     path::{Path, PathBuf},
     rc::Rc,
+    time::Duration,
 };
 
+// The maximum number of characters in a rendered local in an error trace
+const MAX_TRACE_LOCAL_LENGTH: usize = 80;
+
 macro_rules! call_binary_op_or_else {
     ($vm:expr,
      $result_register:expr,
@@ -54,6 +67,16 @@ struct VmContext {
     loader: RefCell<Loader>,
     // The cached export maps of imported modules
     imported_modules: RefCell<ModuleCache>,
+    // Keeps the cycle collector's tracking enabled while the context is alive
+    _cycle_tracking: Option<TrackingGuard>,
+    // The limits that are checked while executing instructions
+    execution_limits: ExecutionLimits,
+    // The debugger that's consulted while executing instructions, if a debug hook was provided
+    debugger: Option<RefCell<Debugger>>,
+    // The profiler that records executed instructions, if profiling is enabled
+    profiler: Option<RefCell<Profiler>>,
+    // The recorder of executed instructions, if coverage is enabled
+    coverage: Option<RefCell<CoverageRecorder>>,
 }
 
 impl Default for VmContext {
@@ -63,15 +86,39 @@ impl Default for VmContext {
 }
 
 impl VmContext {
-    fn with_settings(settings: VmSettings) -> Self {
+    fn with_settings(mut settings: VmSettings) -> Self {
+        let cycle_tracking = settings
+            .enable_cycle_collector
+            .then(cycle_collector::enable_tracking);
         let core_lib = CoreLib::default();
+        let execution_limits = ExecutionLimits::new(
+            settings.instruction_limit,
+            settings.execution_time_limit,
+            settings.memory_limit,
+        );
+        let loader = Loader::with_import_policy(settings.capabilities.import_policy());
+        let debugger = settings
+            .debug_hook
+            .take()
+            .map(|hook| RefCell::new(Debugger::new(hook)));
+        let profiler = settings
+            .enable_profiler
+            .then(|| RefCell::new(Profiler::default()));
+        let coverage = settings
+            .enable_coverage
+            .then(|| RefCell::new(CoverageRecorder::default()));
 
         Self {
             settings,
             prelude: core_lib.prelude(),
             core_lib,
-            loader: RefCell::new(Loader::default()),
+            loader: RefCell::new(loader),
             imported_modules: RefCell::new(ModuleCache::default()),
+            _cycle_tracking: cycle_tracking,
+            execution_limits,
+            debugger,
+            profiler,
+            coverage,
         }
     }
 }
@@ -97,12 +144,85 @@ pub struct VmSettings {
     pub stdout: Rc<dyn KotoFile>,
     /// The runtime's stderr
     pub stderr: Rc<dyn KotoFile>,
+    /// Whether or not values should be tracked by the cycle collector
+    ///
+    /// Reference counting alone can't reclaim values that refer to each other in a cycle,
+    /// e.g. `x = {}; x.self = x`. When enabled, containers that are created while the runtime is
+    /// alive are tracked, and unreachable cycles can be reclaimed by calling
+    /// [Vm::collect_garbage].
+    ///
+    /// Tracking adds some overhead to each allocation, so it's disabled by default.
+    pub enable_cycle_collector: bool,
+    /// An optional limit on the number of instructions that can be executed
+    ///
+    /// The limit applies to each execution started by the host (e.g. [Vm::run] or
+    /// [Vm::run_function]), including any nested executions (e.g. functions called by external
+    /// functions). When the limit is exceeded then execution stops with an error that can't be
+    /// caught by scripts, see [Error::stop_reason].
+    pub instruction_limit: Option<u64>,
+    /// An optional limit on the time that can be spent executing instructions
+    ///
+    /// The time limit is checked periodically while executing instructions, and applies to
+    /// each execution started by the host in the same way as `instruction_limit`.
+    pub execution_time_limit: Option<Duration>,
+    /// An optional limit (in bytes) on the memory that can be allocated for values
+    ///
+    /// The memory that's reserved when creating or growing lists, maps, tuples, strings, and
+    /// sequence/string builders is counted against the limit, which applies to each execution
+    /// started by the host in the same way as `instruction_limit`. Memory that's released during
+    /// an execution isn't returned to the quota, so the limit bounds the total amount of memory
+    /// that a script can allocate before control returns to the host.
+    ///
+    /// External functions can reserve memory against the limit with [Vm::reserve_memory].
+    pub memory_limit: Option<usize>,
+    /// The capabilities that are made available to scripts
+    ///
+    /// By default scripts have full access to the file system and the host environment,
+    /// see [Capabilities::sandboxed] for running untrusted scripts.
+    pub capabilities: Capabilities,
+    /// Whether or not runtime errors should include the values of locals in the failing frame
+    ///
+    /// When enabled, the error's trace includes the names and values of the locals that were
+    /// available in the frame where the error was thrown. The values are rendered when the error
+    /// is returned to the host, with long values being truncated.
+    pub include_locals_in_error_traces: bool,
+    /// An optional hook that's used to debug scripts
+    ///
+    /// When a hook is provided, execution can be paused at breakpoints and stepped through line
+    /// by line, see [DebugHook]. Checking for breakpoints adds overhead to each instruction.
+    pub debug_hook: Option<Box<dyn DebugHook>>,
+    /// Whether or not the runtime should profile the scripts that it runs
+    ///
+    /// When enabled, the number of executed instructions and the time spent executing them are
+    /// recorded for each function and source line, see [Vm::profile].
+    /// Profiling adds overhead to each instruction.
+    pub enable_profiler: bool,
+    /// Whether or not the runtime should record code coverage
+    ///
+    /// When enabled, the number of times each instruction is executed is recorded, along with the
+    /// outcomes of conditional jumps, see [Vm::coverage].
+    pub enable_coverage: bool,
+    /// Whether or not integer overflow should be treated as an error
+    ///
+    /// By default, integer arithmetic that overflows an `i64` produces a big integer.
+    /// When enabled, overflowing arithmetic throws an error instead.
+    pub strict_integer_overflow: bool,
 }
 
 impl Default for VmSettings {
     fn default() -> Self {
         Self {
             run_import_tests: true,
+            enable_cycle_collector: false,
+            instruction_limit: None,
+            execution_time_limit: None,
+            memory_limit: None,
+            capabilities: Capabilities::default(),
+            include_locals_in_error_traces: false,
+            debug_hook: None,
+            enable_profiler: false,
+            enable_coverage: false,
+            strict_integer_overflow: false,
             module_imported_callback: None,
             stdin: Rc::new(DefaultStdin::default()),
             stdout: Rc::new(DefaultStdout::default()),
@@ -130,6 +250,9 @@ pub struct Vm {
     string_builders: Vec<String>,
     // The ip that produced the most recently read instruction, used for debug and error traces
     instruction_ip: u32,
+    // The call depth of the VM that spawned this VM, used by the debugger when stepping.
+    // A u32 is used so that the field fits alongside instruction_ip without growing the VM.
+    parent_call_depth: u32,
 }
 
 impl Default for Vm {
@@ -150,6 +273,7 @@ impl Vm {
             sequence_builders: Vec::new(),
             string_builders: Vec::new(),
             instruction_ip: 0,
+            parent_call_depth: 0,
         }
     }
 
@@ -170,6 +294,7 @@ impl Vm {
             sequence_builders: Vec::new(),
             string_builders: Vec::new(),
             instruction_ip: 0,
+            parent_call_depth: self.call_depth() as u32,
         }
     }
 
@@ -191,6 +316,11 @@ impl Vm {
         &self.exports
     }
 
+    /// The capabilities that are made available to scripts by the VM
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.context.settings.capabilities
+    }
+
     /// The stdin wrapper used by the VM
     pub fn stdin(&self) -> &Rc<dyn KotoFile> {
         &self.context.settings.stdin
@@ -222,6 +352,60 @@ impl Vm {
         }
     }
 
+    /// Returns a handle that can be used to interrupt execution from another thread
+    ///
+    /// The handle is shared by all VMs in the runtime.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.context.execution_limits.interrupt_handle().clone()
+    }
+
+    /// Reserves memory for values that are about to be allocated
+    ///
+    /// An error is returned if the reservation would exceed the runtime's memory limit (see
+    /// [VmSettings::memory_limit]), in which case the allocation shouldn't be made.
+    pub fn reserve_memory(&self, bytes: usize) -> Result<()> {
+        self.context.execution_limits.reserve_memory(bytes)
+    }
+
+    // Checks that a temporary allocation fits within the memory limit, without reserving it
+    pub(crate) fn check_memory(&self, bytes: usize) -> Result<()> {
+        self.context.execution_limits.check_memory(bytes)
+    }
+
+    /// Reclaims values that are only referenced by each other in unreachable cycles
+    ///
+    /// Cycle collection needs to be enabled in the runtime's settings (see
+    /// [VmSettings::enable_cycle_collector]), otherwise no values will be tracked and nothing
+    /// will be reclaimed.
+    ///
+    /// Note that the collector's tracking is shared by all runtimes on the current thread,
+    /// so cycles created by other runtimes will also be reclaimed.
+    pub fn collect_garbage(&self) -> CollectionStats {
+        cycle_collector::collect_cycles()
+    }
+
+    /// Returns the results that have been recorded by the runtime's profiler
+    ///
+    /// None is returned if profiling hasn't been enabled (see [VmSettings::enable_profiler]).
+    /// The profiler is shared by all VMs in the runtime.
+    pub fn profile(&self) -> Option<Profile> {
+        self.context
+            .profiler
+            .as_ref()
+            .map(|profiler| profiler.borrow().profile())
+    }
+
+    /// Returns the code coverage that has been recorded by the runtime
+    ///
+    /// None is returned if coverage hasn't been enabled (see [VmSettings::enable_coverage]).
+    /// Coverage is shared by all VMs in the runtime, and includes imported modules and tests.
+    pub fn coverage(&self) -> Option<Coverage> {
+        self.context
+            .coverage
+            .as_ref()
+            .map(|coverage| coverage.borrow().coverage())
+    }
+
     /// Runs the provided [Chunk], returning the resulting [Value]
     pub fn run(&mut self, chunk: Ptr<Chunk>) -> Result<Value> {
         // Set up an execution frame to run the chunk in
@@ -624,12 +808,55 @@ impl Vm {
     }
 
     fn execute_instructions(&mut self) -> Result<Value> {
+        self.context.execution_limits.enter();
+        let profiler_sample = self
+            .context
+            .profiler
+            .as_ref()
+            .and_then(|profiler| profiler.borrow().enter_execution());
+
+        let mut result = self.execute_instructions_until_exit();
+
+        if let Err(error) = &mut result {
+            if self.context.execution_limits.is_outermost_execution() {
+                error.render_trace_locals(|value| self.render_trace_local(value));
+            }
+        }
+
+        if let Some(profiler) = &self.context.profiler {
+            profiler.borrow_mut().exit_execution(profiler_sample);
+        }
+        self.context.execution_limits.exit();
+        result
+    }
+
+    fn execute_instructions_until_exit(&mut self) -> Result<Value> {
         let mut result = Value::Null;
 
         self.instruction_ip = self.ip();
 
         while let Some(instruction) = self.reader.next() {
-            match self.execute_instruction(instruction) {
+            let instruction_result = match self.context.execution_limits.check() {
+                Ok(_) => {
+                    if self.context.debugger.is_some() {
+                        self.run_debugger();
+                    }
+                    if let Some(profiler) = &self.context.profiler {
+                        profiler.borrow_mut().sample(
+                            self.frame().profile_node,
+                            source_line(&self.reader.chunk, self.instruction_ip),
+                        );
+                    }
+                    if self.context.coverage.is_some() {
+                        self.execute_instruction_with_coverage(instruction)
+                    } else {
+                        self.execute_instruction(instruction)
+                    }
+                }
+                Err(error) => Err(error),
+            };
+
+            match instruction_result {
                 Ok(ControlFlow::Continue) => {}
                 Ok(ControlFlow::Return(value)) => {
                     result = value;
@@ -641,22 +868,37 @@ impl Vm {
                 }
                 Err(mut error) => {
                     let mut recover_register_and_ip = None;
-
-                    error.extend_trace(self.chunk(), self.instruction_ip);
+                    // Errors that stop execution can't be caught by scripts
+                    let catchable = error.stop_reason().is_none();
+
+                    if self.context.settings.include_locals_in_error_traces {
+                        error.extend_trace_with_locals(
+                            self.chunk(),
+                            self.instruction_ip,
+                            self.error_trace_locals(),
+                        );
+                    } else {
+                        error.extend_trace(self.chunk(), self.instruction_ip);
+                    }
+                    error.note_elided_frames(self.frame().elided_frames);
 
                     while let Some(frame) = self.call_stack.last() {
-                        if let Some((error_register, catch_ip)) = frame.catch_stack.last() {
-                            recover_register_and_ip = Some((*error_register, *catch_ip));
-                            break;
-                        } else {
-                            if frame.execution_barrier {
-                                return Err(error);
+                        match frame.catch_stack.last() {
+                            Some((error_register, catch_ip)) if catchable => {
+                                recover_register_and_ip = Some((*error_register, *catch_ip));
+                                break;
                             }
+                            _ => {
+                                if frame.execution_barrier {
+                                    return Err(error);
+                                }
 
-                            self.pop_frame(Value::Null)?;
+                                self.pop_frame(Value::Null)?;
 
-                            if !self.call_stack.is_empty() {
-                                error.extend_trace(self.chunk(), self.instruction_ip);
+                                if !self.call_stack.is_empty() {
+                                    error.extend_trace(self.chunk(), self.instruction_ip);
+                                    error.note_elided_frames(self.frame().elided_frames);
+                                }
                             }
                         }
                     }
@@ -680,6 +922,48 @@ impl Vm {
         Ok(result)
     }
 
+    // Executes an instruction while recording coverage, see VmSettings::enable_coverage
+    fn execute_instruction_with_coverage(
+        &mut self,
+        instruction: Instruction,
+    ) -> Result<ControlFlow> {
+        // The context is shared so that the recorder can be used while the VM is borrowed mutably
+        let context = self.context.clone();
+        let Some(coverage) = &context.coverage else {
+            return self.execute_instruction(instruction);
+        };
+        coverage
+            .borrow_mut()
+            .record_instruction(&self.reader.chunk, self.instruction_ip);
+
+        let is_branch = matches!(
+            instruction,
+            Instruction::JumpIfTrue { .. }
+                | Instruction::JumpIfFalse { .. }
+                | Instruction::IterNext { .. }
+                | Instruction::AccessKey {
+                    jump_offset: Some(_),
+                    ..
+                }
+        );
+        let chunk = self.reader.chunk.clone();
+        let next_ip = self.ip();
+        let frame_count = self.call_stack.len();
+
+        let result = self.execute_instruction(instruction);
+
+        if is_branch && matches!(result, Ok(ControlFlow::Continue)) {
+            // AccessKey pushes a new frame when calling an overloaded `@[]` function,
+            // in which case the jump isn't taken.
+            let taken = self.call_stack.len() == frame_count && self.ip() != next_ip;
+            coverage
+                .borrow_mut()
+                .record_branch(&chunk, self.instruction_ip, taken);
+        }
+
+        result
+    }
+
     fn execute_instruction(&mut self, instruction: Instruction) -> Result<ControlFlow> {
         use Instruction::*;
 
@@ -720,10 +1004,15 @@ impl Vm {
             MakeMap {
                 register,
                 size_hint,
-            } => self.set_register(register, KMap::with_capacity(size_hint as usize).into()),
-            SequenceStart { size_hint } => self
-                .sequence_builders
-                .push(Vec::with_capacity(size_hint as usize)),
+            } => {
+                self.reserve_memory(size_hint as usize * MAP_ENTRY_SIZE)?;
+                self.set_register(register, KMap::with_capacity(size_hint as usize).into())
+            }
+            SequenceStart { size_hint } => {
+                self.reserve_memory(size_hint as usize * VALUE_SIZE)?;
+                self.sequence_builders
+                    .push(Vec::with_capacity(size_hint as usize))
+            }
             SequencePush { value } => self.run_sequence_push(value)?,
             SequencePushN { start, count } => {
                 for value_register in start..(start + count) {
@@ -732,10 +1021,13 @@ impl Vm {
             }
             SequenceToList { register } => self.run_sequence_to_list(register)?,
             SequenceToTuple { register } => self.run_sequence_to_tuple(register)?,
-            StringStart { size_hint } => self
-                .string_builders
-                .push(String::with_capacity(size_hint as usize)),
+            StringStart { size_hint } => {
+                self.reserve_memory(size_hint as usize)?;
+                self.string_builders
+                    .push(String::with_capacity(size_hint as usize))
+            }
             StringPush { value } => self.run_string_push(value)?,
+            StringPushFormat { value, options } => self.run_string_push_format(value, &options)?,
             StringFinish { register } => self.run_string_finish(register)?,
             Range {
                 register,
@@ -819,6 +1111,35 @@ impl Vm {
                 self.clone_register(function),
                 None,
             )?,
+            TailCall {
+                result,
+                function,
+                frame_base,
+                arg_count,
+            } => self.tail_call_callable(
+                &CallInfo {
+                    result_register: result,
+                    frame_base,
+                    arg_count,
+                    instance_register: None,
+                },
+                self.clone_register(function),
+            )?,
+            TailCallInstance {
+                result,
+                function,
+                frame_base,
+                arg_count,
+                instance,
+            } => self.tail_call_callable(
+                &CallInfo {
+                    result_register: result,
+                    frame_base,
+                    arg_count,
+                    instance_register: Some(instance),
+                },
+                self.clone_register(function),
+            )?,
             Return { register } => {
                 if let Some(return_value) = self.pop_frame(self.clone_register(register))? {
                     // If pop_frame returns a new return_value, then execution should stop.
@@ -851,6 +1172,31 @@ impl Vm {
                 let result = matches!(self.get_register(value), Value::List(_));
                 self.set_register(register, result.into());
             }
+            IsMap { register, value } => {
+                let result = matches!(self.get_register(value), Value::Map(_));
+                self.set_register(register, result.into());
+            }
+            MatchesType {
+                register,
+                value,
+                type_constant,
+            } => {
+                let type_name = self.get_constant_str(type_constant);
+                let result = self.get_register(value).matches_type(type_name);
+                self.set_register(register, result.into());
+            }
+            MapRest {
+                register,
+                map,
+                start,
+                count,
+            } => self.run_map_rest(register, map, start, count)?,
+            AccessKey {
+                register,
+                map,
+                key,
+                jump_offset,
+            } => self.run_access_key(register, map, key, jump_offset)?,
             IterNext {
                 result,
                 iterator,
@@ -904,7 +1250,10 @@ impl Vm {
                 register,
                 value,
                 key,
-            } => self.run_access(register, value, self.value_string_from_constant(key))?,
+            } => {
+                let accessed = self.access_with_constant_key(value, key)?;
+                self.set_register(register, accessed);
+            }
             AccessString {
                 register,
                 value,
@@ -914,7 +1263,27 @@ impl Vm {
                     Value::Str(s) => s,
                     other => return type_error("a String", &other),
                 };
-                self.run_access(register, value, key_string)?;
+                let accessed = self.run_access(value, key_string, false)?;
+                self.set_register(register, accessed);
+            }
+            AccessCall {
+                result,
+                instance,
+                frame_base,
+                arg_count,
+                key,
+            } => {
+                let function = self.access_with_constant_key(instance, key)?;
+                self.call_callable(
+                    &CallInfo {
+                        result_register: result,
+                        frame_base,
+                        arg_count,
+                        instance_register: Some(instance),
+                    },
+                    function,
+                    None,
+                )?;
             }
             TryStart {
                 arg_register,
@@ -930,6 +1299,10 @@ impl Vm {
             CheckType { register, type_id } => self.run_check_type(register, type_id)?,
             CheckSizeEqual { register, size } => self.run_check_size_equal(register, size)?,
             CheckSizeMin { register, size } => self.run_check_size_min(register, size)?,
+            AssertType {
+                register,
+                type_constant,
+            } => self.run_assert_type(register, type_constant)?,
         }
 
         Ok(control_flow)
@@ -967,7 +1340,9 @@ impl Vm {
                     KTuple::from(self.register_slice(temp_registers.start, temp_registers.count));
                 self.set_register(register, Value::Tuple(tuple));
             }
-            _ => unreachable!(),
+            // The compiler only uses temporary tuples as the source,
+            // but chunks from other sources could contain anything.
+            unexpected => return type_error("TemporaryTuple", &unexpected),
         }
         Ok(())
     }
@@ -1274,10 +1649,11 @@ impl Vm {
                     // Initialize the function's captures with Null
                     let mut captures = ValueVec::new();
                     captures.resize(capture_count as usize, Null);
-                    CaptureFunction(Ptr::new(KCaptureFunction {
+                    KCaptureFunction {
                         info,
                         captures: KList::with_data(captures),
-                    }))
+                    }
+                    .into()
                 } else {
                     Function(info)
                 };
@@ -1300,8 +1676,13 @@ impl Vm {
 
         match function {
             Value::CaptureFunction(f) => {
-                f.captures.data_mut()[capture_index as usize] = self.clone_register(value);
-                Ok(())
+                match f.captures.data_mut().get_mut(capture_index as usize) {
+                    Some(capture) => {
+                        *capture = self.clone_register(value);
+                        Ok(())
+                    }
+                    None => runtime_error!("Capture index {capture_index} is out of range"),
+                }
             }
             unexpected => type_error("Function while capturing value", unexpected),
         }
@@ -1312,7 +1693,7 @@ impl Vm {
         use Value::*;
 
         let result_value = match self.clone_register(value) {
-            Number(n) => Number(-n),
+            Number(n) => Number(self.check_integer_overflow(-&n, &[&n])?),
             v if v.contains_meta_key(&Negate.into()) => {
                 let op = v.get_meta_value(&Negate.into()).unwrap();
                 return self.call_overloaded_unary_op(result, value, op);
@@ -1325,6 +1706,44 @@ impl Vm {
         Ok(())
     }
 
+    // Checks the result of an integer operation for overflow when strict overflow is enabled
+    //
+    // Integer results that overflow an i64 are promoted to big integers, unless
+    // `strict_integer_overflow` is enabled, in which case an error is thrown instead.
+    // Big integer results are charged against the memory limit.
+    pub(crate) fn check_integer_overflow(
+        &self,
+        result: KNumber,
+        operands: &[&KNumber],
+    ) -> Result<KNumber> {
+        if !result.is_big_int() {
+            return Ok(result);
+        }
+
+        if self.context.settings.strict_integer_overflow && operands.iter().all(|n| n.is_i64()) {
+            return runtime_error!("Integer overflow");
+        }
+
+        let bits = result.integer_bits();
+        self.check_integer_size(bits)?;
+        self.reserve_memory(bits.div_ceil(8) as usize)?;
+        Ok(result)
+    }
+
+    // Checks that an integer operation with a result of the given size can be performed
+    //
+    // Big integer operations like multiplication are performed in a single instruction,
+    // so the result's size is checked before the operation is run.
+    pub(crate) fn check_integer_size(&self, bits: u64) -> Result<()> {
+        if bits <= 64 {
+            Ok(())
+        } else if bits > MAX_BIG_INT_BITS {
+            runtime_error!("Integer result is too large")
+        } else {
+            self.check_memory(bits.div_ceil(8) as usize)
+        }
+    }
+
     fn run_not(&mut self, result: u8, value: u8) -> Result<()> {
         use UnaryOp::Not;
         use Value::*;
@@ -1371,16 +1790,19 @@ impl Vm {
         let lhs_value = self.get_register(lhs);
         let rhs_value = self.get_register(rhs);
         let result_value = match (lhs_value, rhs_value) {
-            (Number(a), Number(b)) => Number(a + b),
+            (Number(a), Number(b)) => Number(self.check_integer_overflow(a + b, &[a, b])?),
             (Str(a), Str(b)) => {
+                self.reserve_memory(a.len() + b.len())?;
                 let result = a.to_string() + b.as_ref();
                 Str(result.into())
             }
             (List(a), List(b)) => {
+                self.reserve_memory((a.len() + b.len()) * VALUE_SIZE)?;
                 let result: ValueVec = a.data().iter().chain(b.data().iter()).cloned().collect();
                 List(KList::with_data(result))
             }
             (Tuple(a), Tuple(b)) => {
+                self.reserve_memory((a.len() + b.len()) * VALUE_SIZE)?;
                 let result: Vec<_> = a.iter().chain(b.iter()).cloned().collect();
                 Tuple(result.into())
             }
@@ -1390,6 +1812,7 @@ impl Vm {
                 return self.call_overloaded_binary_op(result, lhs, rhs_value, op);
             }
             (Map(a), Map(b)) => {
+                self.reserve_memory((a.len() + b.len()) * MAP_ENTRY_SIZE)?;
                 let mut data = a.data().clone();
                 data.extend(b.data().iter().map(|(k, v)| (k.clone(), v.clone())));
                 let meta = match (a.meta_map(), b.meta_map()) {
@@ -1419,7 +1842,7 @@ impl Vm {
         let lhs_value = self.get_register(lhs);
         let rhs_value = self.get_register(rhs);
         let result_value = match (lhs_value, rhs_value) {
-            (Number(a), Number(b)) => Number(a - b),
+            (Number(a), Number(b)) => Number(self.check_integer_overflow(a - b, &[a, b])?),
             (v, _) if v.contains_meta_key(&Subtract.into()) => {
                 let op = v.get_meta_value(&Subtract.into()).unwrap();
                 let rhs_value = rhs_value.clone();
@@ -1441,7 +1864,10 @@ impl Vm {
         let rhs_value = self.get_register(rhs);
 
         let result_value = match (lhs_value, rhs_value) {
-            (Number(a), Number(b)) => Number(a * b),
+            (Number(a), Number(b)) => {
+                self.check_integer_size(a.multiply_bits(b))?;
+                Number(self.check_integer_overflow(a * b, &[a, b])?)
+            }
             (v, _) if v.contains_meta_key(&Multiply.into()) => {
                 let op = v.get_meta_value(&Multiply.into()).unwrap();
                 let rhs_value = rhs_value.clone();
@@ -1488,7 +1914,7 @@ impl Vm {
                 // avoid a panic and return NaN instead.
                 Number(f64::NAN.into())
             }
-            (Number(a), Number(b)) => Number(a % b),
+            (Number(a), Number(b)) => Number(self.check_integer_overflow(a % b, &[a, b])?),
             (v, _) if v.contains_meta_key(&Remainder.into()) => {
                 let op = v.get_meta_value(&Remainder.into()).unwrap();
                 let rhs_value = rhs_value.clone();
@@ -1510,7 +1936,8 @@ impl Vm {
         let rhs_value = self.get_register(rhs);
         match (lhs_value, rhs_value) {
             (Number(a), Number(b)) => {
-                self.set_register(lhs, Number(a + b));
+                let result = self.check_integer_overflow(a + b, &[a, b])?;
+                self.set_register(lhs, Number(result));
                 Ok(())
             }
             (v, _) if v.contains_meta_key(&AddAssign.into()) => {
@@ -1537,7 +1964,8 @@ impl Vm {
         let rhs_value = self.get_register(rhs);
         match (lhs_value, rhs_value) {
             (Number(a), Number(b)) => {
-                self.set_register(lhs, Number(a - b));
+                let result = self.check_integer_overflow(a - b, &[a, b])?;
+                self.set_register(lhs, Number(result));
                 Ok(())
             }
             (v, _) if v.contains_meta_key(&SubtractAssign.into()) => {
@@ -1564,7 +1992,9 @@ impl Vm {
         let rhs_value = self.get_register(rhs);
         match (lhs_value, rhs_value) {
             (Number(a), Number(b)) => {
-                self.set_register(lhs, Number(a * b));
+                self.check_integer_size(a.multiply_bits(b))?;
+                let result = self.check_integer_overflow(a * b, &[a, b])?;
+                self.set_register(lhs, Number(result));
                 Ok(())
             }
             (v, _) if v.contains_meta_key(&MultiplyAssign.into()) => {
@@ -1618,7 +2048,8 @@ impl Vm {
         let rhs_value = self.get_register(rhs);
         match (lhs_value, rhs_value) {
             (Number(a), Number(b)) => {
-                self.set_register(lhs, Number(a % b));
+                let result = self.check_integer_overflow(a % b, &[a, b])?;
+                self.set_register(lhs, Number(result));
                 Ok(())
             }
             (v, _) if v.contains_meta_key(&RemainderAssign.into()) => {
@@ -2119,7 +2550,7 @@ impl Vm {
                 let list_len = list_data.len();
                 match index_value {
                     Number(index) => {
-                        let u_index = usize::from(index);
+                        let u_index = usize::from(&index);
                         if index >= 0.0 && u_index < list_len {
                             list_data[u_index] = value;
                         } else {
@@ -2141,7 +2572,7 @@ impl Vm {
     }
 
     fn validate_index(&self, n: KNumber, size: Option<usize>) -> Result<usize> {
-        let index = usize::from(n);
+        let index = usize::from(&n);
 
         if n < 0.0 {
             return runtime_error!("Negative indices aren't allowed ('{n}')");
@@ -2223,11 +2654,19 @@ impl Vm {
         let key = ValueKey::try_from(self.clone_register(key_register))?;
         let value = self.clone_register(value_register);
 
-        match self.get_register_mut(map_register) {
-            Value::Map(map) => {
-                map.data_mut().insert(key, value);
-                Ok(())
-            }
+        match self.get_register(map_register) {
+            Value::Map(map) => match map.data_mut().entry(key) {
+                Entry::Occupied(mut entry) => {
+                    entry.insert(value);
+                    Ok(())
+                }
+                Entry::Vacant(entry) => {
+                    // Memory only needs to be reserved when a new entry is added
+                    self.reserve_memory(MAP_ENTRY_SIZE)?;
+                    entry.insert(value);
+                    Ok(())
+                }
+            },
             unexpected => type_error("Map", unexpected),
         }
     }
@@ -2305,30 +2744,34 @@ impl Vm {
         Ok(())
     }
 
+    // Accesses a value using a constant key, e.g. `foo.bar`
+    fn access_with_constant_key(&self, value_register: u8, key: ConstantIndex) -> Result<Value> {
+        self.run_access(value_register, self.value_string_from_constant(key), true)
+    }
+
     fn run_access(
-        &mut self,
-        result_register: u8,
+        &self,
         value_register: u8,
         key_string: KString,
-    ) -> Result<()> {
+        cache_lookup: bool,
+    ) -> Result<Value> {
         use Value::*;
 
-        let accessed_value = self.clone_register(value_register);
+        let accessed_value = self.get_register(value_register);
         let key = ValueKey::from(key_string.clone());
 
         macro_rules! core_op {
             ($module:ident, $iterator_fallback:expr) => {{
-                let op = self.get_core_op(
+                self.get_core_op(
                     &key,
                     &self.context.core_lib.$module,
                     $iterator_fallback,
                     stringify!($module),
-                )?;
-                self.set_register(result_register, op);
+                )
             }};
         }
 
-        match &accessed_value {
+        match accessed_value {
             List(_) => core_op!(list, true),
             Number(_) => core_op!(number, false),
             Range(_) => core_op!(range, true),
@@ -2336,78 +2779,124 @@ impl Vm {
             Tuple(_) => core_op!(tuple, true),
             Iterator(_) => core_op!(iterator, false),
             Map(map) => {
+                // Lookups with constant keys make use of the chunk's lookup cache,
+                // see koto_bytecode::LookupCache.
+                let mut cached =
+                    cache_lookup.then(|| self.reader.chunk.lookup_cache.entry(self.instruction_ip));
+
                 let mut lookup_map = map.clone();
-                let mut access_result = None;
-                while access_result.is_none() {
-                    let maybe_value = lookup_map.data().get(&key).cloned();
-                    match maybe_value {
-                        Some(value) => access_result = Some(value),
-                        // Fallback to the map module when there's no metamap
-                        None if lookup_map.meta_map().is_none() => {
-                            core_op!(map, true);
-                            return Ok(());
-                        }
-                        _ => match lookup_map.get_meta_value(&MetaKey::Named(key_string.clone())) {
-                            Some(value) => access_result = Some(value),
-                            None => match lookup_map.get_meta_value(&MetaKey::Base) {
-                                Some(Map(base)) => {
-                                    // Attempt the lookup again with the base map
-                                    lookup_map = base;
-                                }
-                                Some(unexpected) => {
-                                    return type_error("Map as base value", &unexpected)
+                let mut is_accessed_map = true;
+                let access_result = loop {
+                    let data_version = lookup_map.data().version();
+                    let meta_version = lookup_map
+                        .meta_map()
+                        .map_or(0, |meta| meta.borrow().version());
+
+                    let cached_entry = cached
+                        .as_ref()
+                        .and_then(|cached| cached.get(is_accessed_map, data_version, meta_version));
+
+                    let entry = match cached_entry {
+                        Some(entry) => entry,
+                        None => {
+                            let entry = if let Some(index) = lookup_map.data().get_index_of(&key) {
+                                CachedLookupEntry::Data(index as u32)
+                            } else {
+                                let Some(meta) = lookup_map.meta_map() else {
+                                    // Fallback to the map module when there's no metamap
+                                    return core_op!(map, true);
+                                };
+                                let meta = meta.borrow();
+                                if let Some(index) =
+                                    meta.get_index_of(&MetaKey::Named(key_string.clone()))
+                                {
+                                    CachedLookupEntry::Meta(index as u32)
+                                } else {
+                                    match meta.get_full(&MetaKey::Base) {
+                                        Some((index, _, Map(_))) => {
+                                            CachedLookupEntry::Base(index as u32)
+                                        }
+                                        Some((_, _, unexpected)) => {
+                                            return type_error("Map as base value", unexpected)
+                                        }
+                                        None => break None,
+                                    }
                                 }
-                                None => break,
-                            },
+                            };
+
+                            if let Some(cached) = cached.as_mut() {
+                                cached.insert(
+                                    is_accessed_map,
+                                    CachedLookupStep {
+                                        data_version,
+                                        meta_version,
+                                        entry,
+                                    },
+                                );
+                            }
+
+                            entry
+                        }
+                    };
+
+                    let meta_value = |index: u32| {
+                        lookup_map.meta_map().and_then(|meta| {
+                            meta.borrow().get_index(index as usize).map(clone_entry)
+                        })
+                    };
+
+                    match entry {
+                        CachedLookupEntry::Data(index) => {
+                            break lookup_map.data().get_index(index as usize).map(clone_entry);
+                        }
+                        CachedLookupEntry::Meta(index) => break meta_value(index),
+                        CachedLookupEntry::Base(index) => match meta_value(index) {
+                            // Continue the lookup with the base map
+                            Some(Map(base)) => {
+                                lookup_map = base;
+                                is_accessed_map = false;
+                            }
+                            _ => break None,
                         },
                     }
+                };
+
+                if let Some(value) = access_result {
+                    return Ok(value);
                 }
 
                 // Iterator fallback?
-                if access_result.is_none()
-                    && (map.contains_meta_key(&UnaryOp::Iterator.into())
-                        || map.contains_meta_key(&UnaryOp::Next.into()))
+                if map.contains_meta_key(&UnaryOp::Iterator.into())
+                    || map.contains_meta_key(&UnaryOp::Next.into())
                 {
-                    access_result = Some(self.get_core_op(
+                    return self.get_core_op(
                         &key,
                         &self.context.core_lib.iterator,
                         false,
                         &accessed_value.type_as_string(),
-                    )?);
-                }
-
-                let Some(value) = access_result else {
-                    return runtime_error!(
-                        "'{key}' not found in '{}'",
-                        accessed_value.type_as_string()
                     );
-                };
+                }
 
-                self.set_register(result_register, value);
+                runtime_error!("'{key}' not found in '{}'", accessed_value.type_as_string())
             }
             Object(o) => {
                 let o = o.try_borrow()?;
                 if let Some(value) = o.lookup(&key) {
-                    self.set_register(result_register, value);
+                    Ok(value)
+                } else if !matches!(o.is_iterable(), IsIterable::NotIterable) {
+                    // Iterator fallback
+                    self.get_core_op(
+                        &key,
+                        &self.context.core_lib.iterator,
+                        false,
+                        &accessed_value.type_as_string(),
+                    )
                 } else {
-                    // Iterator fallback?
-                    if !matches!(o.is_iterable(), IsIterable::NotIterable) {
-                        let iterator_op = self.get_core_op(
-                            &key,
-                            &self.context.core_lib.iterator,
-                            false,
-                            &accessed_value.type_as_string(),
-                        )?;
-                        self.set_register(result_register, iterator_op);
-                    } else {
-                        return runtime_error!("'{key}' not found in '{}'", o.object_type());
-                    }
+                    runtime_error!("'{key}' not found in '{}'", o.object_type())
                 }
             }
-            unexpected => return type_error("Value that supports '.' access", unexpected),
+            unexpected => type_error("Value that supports '.' access", unexpected),
         }
-
-        Ok(())
     }
 
     fn get_core_op(
@@ -2557,6 +3046,52 @@ impl Vm {
             return self.call_generator(call_info, f, captures, temp_tuple_values);
         }
 
+        self.prepare_function_registers(call_info, f, captures, temp_tuple_values);
+
+        // Set up a new frame for the called function
+        self.push_frame(
+            f.chunk.clone(),
+            f.ip,
+            call_info.frame_base,
+            call_info.result_register,
+        );
+
+        Ok(())
+    }
+
+    // Calls a function in tail position, replacing the current frame if possible
+    //
+    // Koto functions replace the current frame, while other callables are called as usual, with
+    // their results then returned by the instructions that follow the tail call.
+    fn tail_call_callable(&mut self, info: &CallInfo, function: Value) -> Result<()> {
+        // The frame can't be replaced while its catch blocks are active
+        if !self.frame().catch_stack.is_empty() {
+            return self.call_callable(info, function, None);
+        }
+
+        match &function {
+            Value::Function(f) if !f.generator => {
+                self.prepare_function_registers(info, f, None, None);
+                self.replace_frame(f.chunk.clone(), f.ip, info.frame_base);
+                Ok(())
+            }
+            Value::CaptureFunction(f) if !f.info.generator => {
+                self.prepare_function_registers(info, &f.info, Some(&f.captures), None);
+                self.replace_frame(f.info.chunk.clone(), f.info.ip, info.frame_base);
+                Ok(())
+            }
+            _ => self.call_callable(info, function, None),
+        }
+    }
+
+    // Prepares the instance, args, and captures of a function call, starting at the frame base
+    fn prepare_function_registers(
+        &mut self,
+        call_info: &CallInfo,
+        f: &KFunction,
+        captures: Option<&KList>,
+        temp_tuple_values: Option<&[Value]>,
+    ) {
         let expected_arg_count = if f.variadic {
             f.arg_count - 1
         } else {
@@ -2604,16 +3139,6 @@ impl Vm {
         if let Some(temp_tuple_values) = temp_tuple_values {
             self.registers.extend_from_slice(temp_tuple_values);
         }
-
-        // Set up a new frame for the called function
-        self.push_frame(
-            f.chunk.clone(),
-            f.ip,
-            call_info.frame_base,
-            call_info.result_register,
-        );
-
-        Ok(())
     }
 
     fn call_callable(
@@ -2681,6 +3206,118 @@ impl Vm {
             .write_line(&format!("{prefix}{expression_string}: {value_string}"))
     }
 
+    // Checks if execution should be paused by the debugger before executing an instruction
+    fn run_debugger(&self) {
+        let Some(debugger) = &self.context.debugger else {
+            return;
+        };
+        // The debugger is already borrowed when running functions while execution is paused
+        let Ok(mut debugger) = debugger.try_borrow_mut() else {
+            return;
+        };
+
+        let depth = self.call_depth();
+        let pause_reason = debugger.poll().or_else(|| {
+            let span = self
+                .reader
+                .chunk
+                .debug_info
+                .get_source_span(self.instruction_ip)?;
+            debugger.check_location(&self.reader.chunk, span.start.line, depth)
+        });
+
+        if let Some(reason) = pause_reason {
+            let frames = self.debug_frames();
+            debugger.pause(reason, &frames, depth, &mut self.spawn_shared_vm());
+        }
+    }
+
+    // Returns the VM's call stack for the debugger, with the innermost frame first
+    fn debug_frames(&self) -> Vec<DebugFrame> {
+        let mut result = Vec::with_capacity(self.call_stack.len());
+        let mut registers_end = self.registers.len();
+
+        for (i, frame) in self.call_stack.iter().rev().enumerate() {
+            let ip = if i == 0 {
+                self.instruction_ip
+            } else {
+                frame.return_instruction_ip
+            };
+            let registers_start = frame.register_base.min(registers_end);
+            let registers = self.registers[registers_start..registers_end].to_vec();
+            let locals = frame
+                .chunk
+                .debug_info
+                .locals_at(ip)
+                .filter_map(|local| {
+                    registers
+                        .get(local.register as usize)
+                        .map(|value| DebugLocal {
+                            name: frame.chunk.constants.get_str(local.name).into(),
+                            register: local.register,
+                            value: value.clone(),
+                        })
+                })
+                .collect();
+
+            result.push(DebugFrame {
+                chunk: frame.chunk.clone(),
+                ip,
+                registers,
+                locals,
+            });
+
+            registers_end = registers_start;
+        }
+
+        result
+    }
+
+    // Returns the names and values of the active frame's locals for an error trace
+    //
+    // The values are rendered if the error is returned to the host, see `execute_instructions`.
+    fn error_trace_locals(&self) -> Vec<(String, TraceLocal)> {
+        let chunk = &self.reader.chunk;
+        chunk
+            .debug_info
+            .locals_at(self.instruction_ip)
+            .filter_map(|local| {
+                let index = self.register_index(local.register);
+                let value = self.registers.get(index)?;
+                Some((
+                    chunk.constants.get_str(local.name).to_string(),
+                    TraceLocal::Value(value.clone()),
+                ))
+            })
+            .collect()
+    }
+
+    // Renders a local's value for an error trace, truncating long values
+    fn render_trace_local(&self, value: &Value) -> String {
+        let mut rendered = match value {
+            Value::Str(s) => format!("'{s}'"),
+            _ => {
+                let mut display_context = DisplayContext::with_vm(self);
+                match value.display(&mut display_context) {
+                    Ok(_) => display_context.result(),
+                    Err(_) => value.type_as_string().to_string(),
+                }
+            }
+        };
+
+        if let Some((end, _)) = rendered.char_indices().nth(MAX_TRACE_LOCAL_LENGTH) {
+            rendered.truncate(end);
+            rendered.push_str("...");
+        }
+
+        rendered
+    }
+
+    // The number of frames in the call stack, including the frames of parent VMs
+    fn call_depth(&self) -> usize {
+        self.parent_call_depth as usize + self.call_stack.len()
+    }
+
     fn run_check_type(&self, register: u8, type_id: TypeId) -> Result<()> {
         let value = self.get_register(register);
         match type_id {
@@ -2698,6 +3335,97 @@ impl Vm {
         Ok(())
     }
 
+    fn run_assert_type(&self, register: u8, type_constant: ConstantIndex) -> Result<()> {
+        let value = self.get_register(register);
+        let type_name = self.get_constant_str(type_constant);
+        if value.matches_type(type_name) {
+            Ok(())
+        } else {
+            type_error(type_name, value)
+        }
+    }
+
+    fn run_map_rest(&mut self, register: u8, map: u8, start: u8, count: u8) -> Result<()> {
+        let mut data = match self.get_register(map) {
+            Value::Map(m) => m.data().clone(),
+            unexpected => return type_error("Map", unexpected),
+        };
+
+        for key in self.register_slice(start, count) {
+            if let Value::Str(key) = key {
+                data.shift_remove(key.as_str());
+            }
+        }
+
+        self.reserve_memory(data.len() * MAP_ENTRY_SIZE)?;
+        self.set_register(register, KMap::with_data(data).into());
+
+        Ok(())
+    }
+
+    // Accesses a map entry when destructuring
+    //
+    // The map's entries and metamap are checked before falling back to the map's `@[]` override.
+    // If the key is missing then either the jump offset is taken (so that the entry's default value
+    // can be assigned), or an error is thrown.
+    fn run_access_key(
+        &mut self,
+        register: u8,
+        map_register: u8,
+        key: ConstantIndex,
+        jump_offset: Option<u16>,
+    ) -> Result<()> {
+        use Value::Map;
+
+        let map = match self.get_register(map_register) {
+            Map(map) => map.clone(),
+            unexpected => return type_error("Map", unexpected),
+        };
+        let key_string = self.value_string_from_constant(key);
+        let key = ValueKey::from(key_string.clone());
+
+        let mut lookup_map = map.clone();
+        let found = loop {
+            if let Some(value) = lookup_map.data().get(&key) {
+                break Some(value.clone());
+            }
+
+            let base = match lookup_map.meta_map() {
+                Some(meta) => {
+                    let meta = meta.borrow();
+                    if let Some(value) = meta.get(&MetaKey::Named(key_string.clone())) {
+                        break Some(value.clone());
+                    }
+                    match meta.get(&MetaKey::Base) {
+                        Some(Map(base)) => base.clone(),
+                        Some(unexpected) => return type_error("Map as base value", unexpected),
+                        None => break None,
+                    }
+                }
+                None => break None,
+            };
+
+            lookup_map = base;
+        };
+
+        if let Some(value) = found {
+            self.set_register(register, value);
+        } else if let Some(index_op) = map.get_meta_value(&MetaKey::BinaryOp(BinaryOp::Index)) {
+            return self.call_overloaded_binary_op(
+                register,
+                map_register,
+                key_string.into(),
+                index_op,
+            );
+        } else if let Some(jump_offset) = jump_offset {
+            self.jump_ip(jump_offset as u32);
+        } else {
+            return runtime_error!("'{key_string}' is missing from the destructured map");
+        }
+
+        Ok(())
+    }
+
     fn run_check_size_equal(&self, register: u8, expected_size: usize) -> Result<()> {
         let value_size = self.get_register(register).size();
 
@@ -2723,6 +3451,7 @@ impl Vm {
     }
 
     fn run_sequence_push(&mut self, value_register: u8) -> Result<()> {
+        self.reserve_memory(VALUE_SIZE)?;
         let value = self.clone_register(value_register);
         if let Some(builder) = self.sequence_builders.last_mut() {
             builder.push(value);
@@ -2756,6 +3485,7 @@ impl Vm {
 
         match self.run_unary_op(UnaryOp::Display, value)? {
             Value::Str(string) => {
+                self.reserve_memory(string.len())?;
                 if let Some(builder) = self.string_builders.last_mut() {
                     builder.push_str(&string);
                     Ok(())
@@ -2767,6 +3497,28 @@ impl Vm {
         }
     }
 
+    fn run_string_push_format(
+        &mut self,
+        value_register: u8,
+        options: &StringFormatOptions,
+    ) -> Result<()> {
+        let value = self.clone_register(value_register);
+        let fill = options
+            .fill_character
+            .and_then(|fill| self.get_constant_str(fill).chars().next());
+
+        // The memory needed for the value's padding is checked before formatting,
+        // and then the formatted string is reserved before it's added to the builder.
+        let string = format::format_value(self, &value, options, fill)?;
+        self.reserve_memory(string.len())?;
+        if let Some(builder) = self.string_builders.last_mut() {
+            builder.push_str(&string);
+            Ok(())
+        } else {
+            runtime_error!(ErrorKind::MissingStringBuilder)
+        }
+    }
+
     fn run_string_finish(&mut self, register: u8) -> Result<()> {
         // Move the string builder out of its register to avoid cloning the string data
         if let Some(result) = self.string_builders.pop() {
@@ -2824,8 +3576,41 @@ impl Vm {
         };
         let new_frame_base = previous_frame_base + frame_base as usize;
 
-        self.call_stack
-            .push(Frame::new(chunk.clone(), new_frame_base));
+        let mut frame = Frame::new(chunk.clone(), new_frame_base);
+        if let Some(profiler) = &self.context.profiler {
+            let caller = self.call_stack.last().map(|frame| frame.profile_node);
+            frame.profile_node = profiler.borrow_mut().enter_function(&chunk, ip, caller);
+        }
+
+        self.call_stack.push(frame);
+        self.set_chunk_and_ip(chunk, ip);
+    }
+
+    // Replaces the current frame with the frame of a function that's called in tail position
+    //
+    // The called function's registers have been prepared starting from the frame base, and are
+    // moved to the start of the current frame.
+    fn replace_frame(&mut self, chunk: Ptr<Chunk>, ip: u32, frame_base: u8) {
+        let register_base = self.register_base();
+        self.registers
+            .drain(register_base..register_base + frame_base as usize);
+
+        let profile_node = self.context.profiler.as_ref().map(|profiler| {
+            let caller = self
+                .call_stack
+                .iter()
+                .nth_back(1)
+                .map(|frame| frame.profile_node);
+            profiler.borrow_mut().enter_function(&chunk, ip, caller)
+        });
+
+        let frame = self.frame_mut();
+        frame.chunk = chunk.clone();
+        frame.elided_frames = frame.elided_frames.saturating_add(1);
+        if let Some(profile_node) = profile_node {
+            frame.profile_node = profile_node;
+        }
+
         self.set_chunk_and_ip(chunk, ip);
     }
 
@@ -2941,6 +3726,10 @@ impl Vm {
     }
 }
 
+fn clone_entry<K>((_, value): (&K, &Value)) -> Value {
+    value.clone()
+}
+
 impl fmt::Debug for Vm {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str("Vm")
@@ -3028,6 +3817,10 @@ struct Frame {
     //   - an external function is calling back into the VM with a functor
     //   - a module is being imported
     pub execution_barrier: bool,
+    // The frame's node in the profiler's call tree, see VmSettings::enable_profiler
+    pub profile_node: u32,
+    // The number of frames that have been replaced by tail calls, see Vm::replace_frame
+    pub elided_frames: u32,
 }
 
 impl Frame {
@@ -3039,6 +3832,8 @@ impl Frame {
             return_instruction_ip: 0,
             catch_stack: vec![],
             execution_barrier: false,
+            profile_node: 0,
+            elided_frames: 0,
         }
     }
 }
@@ -0,0 +1,186 @@
+use koto_bytecode::{CompilerSettings, Loader};
+use koto_runtime::{prelude::*, Capabilities, FileSystemAccess};
+use std::path::PathBuf;
+
+fn koto_tests_dir() -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "..", "..", "koto", "tests"]
+        .iter()
+        .collect()
+}
+
+fn make_vm(capabilities: Capabilities) -> Vm {
+    Vm::with_settings(VmSettings {
+        capabilities,
+        ..Default::default()
+    })
+}
+
+fn run_script(
+    vm: &mut Vm,
+    script: &str,
+    script_path: Option<PathBuf>,
+) -> koto_runtime::Result<Value> {
+    let mut loader = Loader::default();
+    let chunk = loader
+        .compile_script(script, &script_path, CompilerSettings::default())
+        .expect("Failed to compile script");
+    vm.run(chunk)
+}
+
+fn check_permission_error(vm: &mut Vm, script: &str, script_path: Option<PathBuf>) {
+    match run_script(vm, script, script_path) {
+        Ok(_) => panic!("Expected a permission error"),
+        Err(error) => {
+            let message = error.to_string();
+            assert!(
+                message.contains("Permission denied"),
+                "Unexpected error message: {message}"
+            );
+        }
+    }
+}
+
+fn read_only_vm() -> Vm {
+    make_vm(Capabilities {
+        file_system: FileSystemAccess::ReadOnly(koto_tests_dir().join("data")),
+        ..Capabilities::sandboxed()
+    })
+}
+
+mod sandboxed {
+    use super::*;
+
+    #[test]
+    fn open_file() {
+        let mut vm = make_vm(Capabilities::sandboxed());
+        let path = koto_tests_dir().join("data").join("test.txt");
+        check_permission_error(&mut vm, &format!("io.open '{}'", path.display()), None);
+    }
+
+    #[test]
+    fn create_file() {
+        let mut vm = make_vm(Capabilities::sandboxed());
+        check_permission_error(&mut vm, "io.create 'foo.txt'", None);
+    }
+
+    #[test]
+    fn remove_file() {
+        let mut vm = make_vm(Capabilities::sandboxed());
+        check_permission_error(&mut vm, "io.remove_file 'foo.txt'", None);
+    }
+
+    #[test]
+    fn current_dir() {
+        let mut vm = make_vm(Capabilities::sandboxed());
+        check_permission_error(&mut vm, "io.current_dir()", None);
+    }
+
+    #[test]
+    fn temp_dir() {
+        let mut vm = make_vm(Capabilities::sandboxed());
+        check_permission_error(&mut vm, "io.temp_dir()", None);
+    }
+
+    #[test]
+    fn os_name() {
+        let mut vm = make_vm(Capabilities::sandboxed());
+        check_permission_error(&mut vm, "os.name()", None);
+    }
+
+    #[test]
+    fn import_module() {
+        let mut vm = make_vm(Capabilities::sandboxed());
+        check_permission_error(
+            &mut vm,
+            "import test_module",
+            Some(koto_tests_dir().join("import.koto")),
+        );
+    }
+
+    #[test]
+    fn prelude_imports_are_allowed() {
+        let mut vm = make_vm(Capabilities::sandboxed());
+        let result = run_script(&mut vm, "from string import size\nsize 'abc'", None).unwrap();
+        assert!(matches!(result, Value::Number(n) if n == 3));
+    }
+}
+
+mod read_only {
+    use super::*;
+
+    #[test]
+    fn read_file_within_root() {
+        let mut vm = read_only_vm();
+        let result = run_script(
+            &mut vm,
+            "io.read_to_string('test.txt').lines().next()",
+            None,
+        )
+        .unwrap();
+        assert!(matches!(result, Value::Str(s) if s.as_str() == "aaa"));
+    }
+
+    #[test]
+    fn open_file_within_root() {
+        let mut vm = read_only_vm();
+        let result = run_script(&mut vm, "io.open('test.txt').read_line()", None).unwrap();
+        assert!(matches!(result, Value::Str(s) if s.as_str() == "aaa"));
+    }
+
+    #[test]
+    fn exists() {
+        let mut vm = read_only_vm();
+        let result = run_script(
+            &mut vm,
+            "io.exists('test.txt'), io.exists('missing.txt'), io.exists('../import.koto')",
+            None,
+        )
+        .unwrap();
+        match result {
+            Value::Tuple(t) => {
+                assert!(matches!(
+                    t.as_ref(),
+                    [Value::Bool(true), Value::Bool(false), Value::Bool(false)]
+                ));
+            }
+            unexpected => panic!("Unexpected result: {}", unexpected.type_as_string()),
+        }
+    }
+
+    #[test]
+    fn read_file_outside_of_root() {
+        let mut vm = read_only_vm();
+        check_permission_error(&mut vm, "io.read_to_string '../import.koto'", None);
+    }
+
+    #[test]
+    fn create_file() {
+        let mut vm = read_only_vm();
+        check_permission_error(&mut vm, "io.create 'foo.txt'", None);
+    }
+
+    #[test]
+    fn import_module_outside_of_root() {
+        let mut vm = make_vm(Capabilities {
+            file_system: FileSystemAccess::ReadOnly(koto_tests_dir().join("data")),
+            imports: true,
+            ..Capabilities::sandboxed()
+        });
+        check_permission_error(
+            &mut vm,
+            "import test_module",
+            Some(koto_tests_dir().join("import.koto")),
+        );
+    }
+
+    #[test]
+    fn import_module_within_root() {
+        let mut vm = make_vm(Capabilities {
+            file_system: FileSystemAccess::ReadOnly(koto_tests_dir().join("test_module")),
+            imports: true,
+            ..Capabilities::sandboxed()
+        });
+        let result = run_script(&mut vm, "import baz\nbaz.qux", None).unwrap();
+        assert!(matches!(result, Value::Str(_)));
+    }
+}
@@ -0,0 +1,160 @@
+use koto_bytecode::{CompilerSettings, Loader};
+use koto_runtime::{prelude::*, Coverage};
+use std::path::PathBuf;
+
+fn script_coverage(script: &str) -> Coverage {
+    let mut vm = Vm::with_settings(VmSettings {
+        enable_coverage: true,
+        ..Default::default()
+    });
+    let chunk = Loader::default()
+        .compile_script(
+            script,
+            &Some(PathBuf::from("coverage_test.koto")),
+            CompilerSettings::default(),
+        )
+        .expect("Failed to compile script");
+    vm.run(chunk).expect("Failed to run script");
+    vm.coverage().expect("Missing coverage")
+}
+
+const SCRIPT: &str = "\
+check = |n|
+  if n > 0
+    'positive'
+  else
+    'not positive'
+unused = |n| n * 2
+for i in 1..=3
+  check i
+";
+
+#[test]
+fn lines() {
+    let coverage = script_coverage(SCRIPT);
+    let chunk = &coverage.chunks[0];
+
+    let lines: Vec<_> = chunk
+        .lines()
+        .iter()
+        .map(|line| (line.line, line.hits))
+        .collect();
+    assert_eq!(
+        lines,
+        [(1, 1), (2, 3), (3, 3), (5, 0), (6, 1), (7, 1), (8, 3)]
+    );
+}
+
+#[test]
+fn branches() {
+    let coverage = script_coverage(SCRIPT);
+    let chunk = &coverage.chunks[0];
+
+    let branches: Vec<_> = chunk
+        .branches
+        .iter()
+        .map(|branch| (branch.span.unwrap().start.line, branch.hits, branch.taken))
+        .collect();
+    assert_eq!(
+        branches,
+        [
+            // The `if` condition is always true, so the jump to the else branch is never taken
+            (2, 3, 0),
+            // The loop's iterator is exhausted after 3 iterations
+            (7, 4, 1),
+        ]
+    );
+}
+
+#[test]
+fn destructuring_defaults() {
+    let script = "\
+f = |{x, y = 0}| x + y
+f {x: 1}
+f {x: 1, y: 2}
+f {x: 1, y: 3}
+f {x: 1, @[]: |_| 4}
+";
+    let coverage = script_coverage(script);
+    let chunk = &coverage.chunks[0];
+
+    let branches: Vec<_> = chunk
+        .branches
+        .iter()
+        .map(|branch| (branch.span.unwrap().start.line, branch.hits, branch.taken))
+        .collect();
+    // The jump to `y`'s default value is only taken when `y` is missing,
+    // and isn't provided by an overloaded `@[]`
+    assert_eq!(branches, [(1, 4, 1)]);
+}
+
+#[test]
+fn functions() {
+    let coverage = script_coverage(SCRIPT);
+    let chunk = &coverage.chunks[0];
+
+    let functions: Vec<_> = chunk
+        .functions
+        .iter()
+        .map(|function| (function.name.as_str(), function.line, function.calls))
+        .collect();
+    assert_eq!(functions, [("check", 1, 3), ("unused", 6, 0)]);
+}
+
+#[test]
+fn test_functions() {
+    let script = "\
+@tests =
+  @test foo: ||
+    1
+  @test bar: || 2
+@test baz = || 3
+";
+    let coverage = script_coverage(script);
+    let chunk = &coverage.chunks[0];
+
+    let functions: Vec<_> = chunk
+        .functions
+        .iter()
+        .map(|function| (function.name.as_str(), function.line, function.calls))
+        .collect();
+    assert_eq!(functions, [("foo", 2, 0), ("bar", 4, 0), ("baz", 5, 0)]);
+}
+
+#[test]
+fn lcov() {
+    let coverage = script_coverage(SCRIPT);
+
+    let expected = "\
+TN:
+SF:coverage_test.koto
+FN:1,check:1
+FN:6,unused:6
+FNDA:3,check:1
+FNDA:0,unused:6
+FNF:2
+FNH:1
+BRDA:2,13,0,3
+BRDA:2,13,1,0
+BRDA:7,58,0,3
+BRDA:7,58,1,1
+BRF:4
+BRH:3
+DA:1,1
+DA:2,3
+DA:3,3
+DA:5,0
+DA:6,1
+DA:7,1
+DA:8,3
+LF:7
+LH:6
+end_of_record
+";
+    assert_eq!(coverage.to_lcov(), expected);
+}
+
+#[test]
+fn coverage_is_disabled_by_default() {
+    assert!(Vm::default().coverage().is_none());
+}
@@ -0,0 +1,124 @@
+use koto_bytecode::{CompilerSettings, Loader};
+use koto_runtime::{prelude::*, CollectionStats};
+
+fn run_script(vm: &mut Vm, script: &str) -> Value {
+    let mut loader = Loader::default();
+    let chunk = loader
+        .compile_script(script, &None, CompilerSettings::default())
+        .expect("Failed to compile script");
+    vm.run(chunk).expect("Failed to run script")
+}
+
+fn make_vm() -> Vm {
+    Vm::with_settings(VmSettings {
+        enable_cycle_collector: true,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn self_referencing_map_is_reclaimed() {
+    let mut vm = make_vm();
+
+    run_script(
+        &mut vm,
+        "
+x = {}
+x.self = x
+null
+",
+    );
+
+    let stats = vm.collect_garbage();
+    assert_eq!(stats.reclaimed, 1);
+}
+
+#[test]
+fn parent_child_maps_are_reclaimed() {
+    let mut vm = make_vm();
+
+    run_script(
+        &mut vm,
+        "
+parent = {children: []}
+for i in 0..3
+  parent.children.push {parent, i}
+null
+",
+    );
+
+    // The parent's data map, the children list, and the three child data maps
+    let stats = vm.collect_garbage();
+    assert_eq!(stats.reclaimed, 5);
+}
+
+#[test]
+fn function_capturing_its_container_is_reclaimed() {
+    let mut vm = make_vm();
+
+    run_script(
+        &mut vm,
+        "
+x = [1, 2, 3]
+f = || x.size()
+x.push f
+null
+",
+    );
+
+    // The list, the function, and the function's captures list
+    let stats = vm.collect_garbage();
+    assert_eq!(stats.reclaimed, 3);
+}
+
+#[test]
+fn cycle_through_tuple_is_reclaimed() {
+    let mut vm = make_vm();
+
+    run_script(
+        &mut vm,
+        "
+x = []
+x.push (1, x)
+null
+",
+    );
+
+    let stats = vm.collect_garbage();
+    assert_eq!(stats.reclaimed, 2);
+}
+
+#[test]
+fn reachable_cycles_are_retained() {
+    let mut vm = make_vm();
+
+    run_script(
+        &mut vm,
+        "
+export x = {foo: 42}
+x.self = x
+",
+    );
+
+    let stats = vm.collect_garbage();
+    assert_eq!(stats.reclaimed, 0);
+
+    let result = run_script(&mut vm, "x.self.self.foo");
+    assert!(matches!(result, Value::Number(n) if n == 42));
+}
+
+#[test]
+fn nothing_is_reclaimed_when_disabled() {
+    let mut vm = Vm::default();
+
+    run_script(
+        &mut vm,
+        "
+x = {}
+x.self = x
+null
+",
+    );
+
+    assert_eq!(vm.collect_garbage(), CollectionStats::default());
+}
@@ -0,0 +1,212 @@
+use koto_bytecode::{CompilerSettings, Loader};
+use koto_runtime::{prelude::*, DebugFrame, DebugHook, PauseReason, StepMode, StopReason};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+#[derive(Debug, PartialEq)]
+struct Pause {
+    reason: PauseReason,
+    line: u32,
+    depth: usize,
+    locals: Vec<(String, String)>,
+}
+
+struct TestHook {
+    breakpoints: Vec<u32>,
+    steps: VecDeque<StepMode>,
+    pauses: Rc<RefCell<Vec<Pause>>>,
+}
+
+impl DebugHook for TestHook {
+    fn is_breakpoint(&mut self, path: Option<&Path>, line: u32) -> bool {
+        assert_eq!(path, Some(script_path().as_path()));
+        self.breakpoints.contains(&line)
+    }
+
+    fn paused(&mut self, reason: PauseReason, frames: &[DebugFrame], vm: &mut Vm) -> StepMode {
+        let frame = frames.first().expect("Missing frame");
+        let locals = frame
+            .locals
+            .iter()
+            .map(|local| {
+                (
+                    local.name.clone(),
+                    vm.value_to_string(&local.value).unwrap(),
+                )
+            })
+            .collect();
+
+        self.pauses.borrow_mut().push(Pause {
+            reason,
+            line: frame.span().unwrap().start.line,
+            depth: frames.len(),
+            locals,
+        });
+
+        self.steps.pop_front().unwrap_or_default()
+    }
+}
+
+fn script_path() -> PathBuf {
+    PathBuf::from("debugger_test.koto")
+}
+
+fn run_script(script: &str, breakpoints: &[u32], steps: &[StepMode]) -> Vec<Pause> {
+    let pauses = Rc::new(RefCell::new(Vec::new()));
+    let hook = TestHook {
+        breakpoints: breakpoints.to_vec(),
+        steps: steps.iter().copied().collect(),
+        pauses: pauses.clone(),
+    };
+
+    let mut vm = Vm::with_settings(VmSettings {
+        debug_hook: Some(Box::new(hook)),
+        ..Default::default()
+    });
+    let chunk = Loader::default()
+        .compile_script(script, &Some(script_path()), CompilerSettings::default())
+        .expect("Failed to compile script");
+    vm.run(chunk).expect("Failed to run script");
+
+    pauses.take()
+}
+
+fn pause(reason: PauseReason, line: u32, depth: usize, locals: &[(&str, &str)]) -> Pause {
+    Pause {
+        reason,
+        line,
+        depth,
+        locals: locals
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+    }
+}
+
+const SCRIPT: &str = "\
+f = |n|
+  m = n * 2
+  m + 1
+x = f 1
+y = f x
+z = x + y
+";
+
+#[test]
+fn breakpoints() {
+    let pauses = run_script(SCRIPT, &[3, 6], &[]);
+
+    assert_eq!(
+        pauses,
+        [
+            pause(PauseReason::Breakpoint, 3, 2, &[("n", "1"), ("m", "2")]),
+            pause(PauseReason::Breakpoint, 3, 2, &[("n", "3"), ("m", "6")]),
+            pause(
+                PauseReason::Breakpoint,
+                6,
+                1,
+                &[("f", "||"), ("x", "3"), ("y", "7"), ("z", "null")]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn step_over() {
+    let pauses = run_script(SCRIPT, &[4], &[StepMode::StepOver, StepMode::StepOver]);
+
+    assert_eq!(
+        pauses,
+        [
+            pause(PauseReason::Breakpoint, 4, 1, &[("f", "||")]),
+            pause(
+                PauseReason::Step,
+                5,
+                1,
+                &[("f", "||"), ("x", "3"), ("y", "null")]
+            ),
+            pause(
+                PauseReason::Step,
+                6,
+                1,
+                &[("f", "||"), ("x", "3"), ("y", "7"), ("z", "null")]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn step_in_and_out() {
+    let pauses = run_script(
+        SCRIPT,
+        &[4],
+        &[StepMode::StepIn, StepMode::StepIn, StepMode::StepOut],
+    );
+
+    assert_eq!(
+        pauses,
+        [
+            pause(PauseReason::Breakpoint, 4, 1, &[("f", "||")]),
+            pause(PauseReason::Step, 2, 2, &[("n", "1")]),
+            pause(PauseReason::Step, 3, 2, &[("n", "1"), ("m", "2")]),
+            // The call's result is assigned directly to `x`, so stepping out continues to line 5
+            pause(
+                PauseReason::Step,
+                5,
+                1,
+                &[("f", "||"), ("x", "3"), ("y", "null")]
+            ),
+        ]
+    );
+}
+
+// A hook that asks for execution to be paused when it's polled, and then stops the script
+struct PollingHook {
+    pauses: Rc<RefCell<Vec<(PauseReason, u32)>>>,
+}
+
+impl DebugHook for PollingHook {
+    fn is_breakpoint(&mut self, _path: Option<&Path>, _line: u32) -> bool {
+        false
+    }
+
+    fn poll(&mut self) -> bool {
+        true
+    }
+
+    fn paused(&mut self, reason: PauseReason, frames: &[DebugFrame], vm: &mut Vm) -> StepMode {
+        let line = frames[0].span().unwrap().start.line;
+        self.pauses.borrow_mut().push((reason, line));
+        vm.interrupt_handle().interrupt();
+        StepMode::Continue
+    }
+}
+
+#[test]
+fn poll_while_running_on_a_single_line() {
+    let pauses = Rc::new(RefCell::new(Vec::new()));
+    let mut vm = Vm::with_settings(VmSettings {
+        debug_hook: Some(Box::new(PollingHook {
+            pauses: pauses.clone(),
+        })),
+        ..Default::default()
+    });
+    // The tail call keeps the call depth fixed, so the line and depth never change
+    let script = "\
+f = |n| if n > 0 then f n - 1 else f 1000000
+f 1
+";
+    let chunk = Loader::default()
+        .compile_script(script, &Some(script_path()), CompilerSettings::default())
+        .expect("Failed to compile script");
+
+    match vm.run(chunk) {
+        Ok(_) => panic!("Expected the script to be stopped"),
+        Err(error) => assert_eq!(error.stop_reason(), Some(StopReason::Interrupted)),
+    }
+    assert_eq!(pauses.take(), [(PauseReason::Requested, 1)]);
+}
@@ -0,0 +1,88 @@
+use koto_bytecode::{CompilerSettings, Loader};
+use koto_runtime::prelude::*;
+
+fn run_failing_script(script: &str, include_locals: bool) -> String {
+    let mut vm = Vm::with_settings(VmSettings {
+        include_locals_in_error_traces: include_locals,
+        ..Default::default()
+    });
+    let chunk = Loader::default()
+        .compile_script(script, &None, CompilerSettings::default())
+        .expect("Failed to compile script");
+    match vm.run(chunk) {
+        Ok(_) => panic!("Expected the script to fail"),
+        Err(error) => error.to_string(),
+    }
+}
+
+const SCRIPT: &str = "\
+f = |n|
+  name = 'abc'
+  items = [n, name]
+  n + name
+f 42
+";
+
+#[test]
+fn locals_are_included_in_error_traces() {
+    let error = run_failing_script(SCRIPT, true);
+
+    assert!(
+        error.contains("Locals:\n  n: 42\n  name: 'abc'\n  items: [42, 'abc']"),
+        "{error}"
+    );
+}
+
+#[test]
+fn locals_are_excluded_from_error_traces_by_default() {
+    let error = run_failing_script(SCRIPT, false);
+
+    assert!(!error.contains("Locals:"), "{error}");
+}
+
+#[test]
+fn long_values_are_truncated() {
+    let script = "\
+f = |n|
+  items = (1..1000).to_list()
+  n + items
+f 42
+";
+    let error = run_failing_script(script, true);
+
+    let items = error
+        .lines()
+        .find_map(|line| line.strip_prefix("  items: "))
+        .expect("Missing items local");
+    assert!(items.starts_with("[1, 2, 3"), "{error}");
+    assert!(items.ends_with("..."), "{error}");
+    assert!(items.chars().count() < 100, "{error}");
+}
+
+#[test]
+fn locals_arent_rendered_for_caught_errors() {
+    let mut vm = Vm::with_settings(VmSettings {
+        include_locals_in_error_traces: true,
+        ..Default::default()
+    });
+    let script = "\
+renders = {count: 0}
+f = ||
+  x =
+    @display: ||
+      renders.count += 1
+      'x'
+  x + 1
+try
+  f()
+catch _
+  null
+renders.count
+";
+    let chunk = Loader::default()
+        .compile_script(script, &None, CompilerSettings::default())
+        .expect("Failed to compile script");
+
+    let result = vm.run(chunk).unwrap();
+    assert!(matches!(result, Value::Number(n) if n == 0));
+}
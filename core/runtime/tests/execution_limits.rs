@@ -0,0 +1,315 @@
+use koto_bytecode::{CompilerSettings, Loader};
+use koto_runtime::{prelude::*, StopReason};
+use std::{thread, time::Duration};
+
+fn run_script(vm: &mut Vm, script: &str) -> koto_runtime::Result<Value> {
+    let mut loader = Loader::default();
+    let chunk = loader
+        .compile_script(script, &None, CompilerSettings::default())
+        .expect("Failed to compile script");
+    vm.run(chunk)
+}
+
+fn check_stop_reason(result: koto_runtime::Result<Value>, expected: StopReason) {
+    match result {
+        Ok(_) => panic!("Expected execution to be stopped"),
+        Err(error) => assert_eq!(error.stop_reason(), Some(expected), "{error}"),
+    }
+}
+
+#[test]
+fn instruction_limit_stops_infinite_loop() {
+    let mut vm = Vm::with_settings(VmSettings {
+        instruction_limit: Some(1000),
+        ..Default::default()
+    });
+
+    check_stop_reason(
+        run_script(&mut vm, "loop\n  x = 1"),
+        StopReason::InstructionLimit,
+    );
+}
+
+#[test]
+fn instruction_limit_allows_short_scripts() {
+    let mut vm = Vm::with_settings(VmSettings {
+        instruction_limit: Some(1000),
+        ..Default::default()
+    });
+
+    let result = run_script(&mut vm, "(1..10).sum()").unwrap();
+    assert!(matches!(result, Value::Number(n) if n == 45));
+}
+
+#[test]
+fn instruction_limit_applies_to_each_run() {
+    let mut vm = Vm::with_settings(VmSettings {
+        instruction_limit: Some(1000),
+        ..Default::default()
+    });
+
+    check_stop_reason(
+        run_script(&mut vm, "loop\n  x = 1"),
+        StopReason::InstructionLimit,
+    );
+
+    // The VM should be usable again after the limit was hit
+    let result = run_script(&mut vm, "1 + 1").unwrap();
+    assert!(matches!(result, Value::Number(n) if n == 2));
+}
+
+#[test]
+fn stopped_execution_cant_be_caught() {
+    let mut vm = Vm::with_settings(VmSettings {
+        instruction_limit: Some(1000),
+        ..Default::default()
+    });
+
+    let script = "
+loop
+  try
+    loop
+      x = 1
+  catch _
+    x = 2
+";
+    check_stop_reason(run_script(&mut vm, script), StopReason::InstructionLimit);
+}
+
+#[test]
+fn instruction_limit_includes_nested_calls() {
+    let mut vm = Vm::with_settings(VmSettings {
+        instruction_limit: Some(1000),
+        ..Default::default()
+    });
+
+    // The function is called by the core library's iterator adaptors
+    let script = "
+f = |n| n + 1
+loop
+  (1..10).each(f).consume()
+";
+    check_stop_reason(run_script(&mut vm, script), StopReason::InstructionLimit);
+}
+
+#[test]
+fn time_limit_stops_infinite_loop() {
+    let mut vm = Vm::with_settings(VmSettings {
+        execution_time_limit: Some(Duration::from_millis(10)),
+        ..Default::default()
+    });
+
+    check_stop_reason(run_script(&mut vm, "loop\n  x = 1"), StopReason::TimeLimit);
+}
+
+#[test]
+fn interrupt_handle_stops_execution() {
+    let mut vm = Vm::default();
+    let handle = vm.interrupt_handle();
+
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(10));
+        handle.interrupt();
+    });
+
+    check_stop_reason(
+        run_script(&mut vm, "loop\n  x = 1"),
+        StopReason::Interrupted,
+    );
+    interrupter.join().unwrap();
+
+    // The handle is reset when execution is stopped
+    assert!(!vm.interrupt_handle().is_interrupted());
+    let result = run_script(&mut vm, "1 + 1").unwrap();
+    assert!(matches!(result, Value::Number(n) if n == 2));
+}
+
+mod memory_limit {
+    use super::*;
+
+    fn make_vm() -> Vm {
+        Vm::with_settings(VmSettings {
+            memory_limit: Some(100_000),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn small_allocations_are_allowed() {
+        let mut vm = make_vm();
+
+        let script = "
+x = (1..100).to_list()
+y = {foo: x, bar: 'hello'}
+'${y.bar}, ${x.size()}'
+";
+        let result = run_script(&mut vm, script).unwrap();
+        assert!(matches!(result, Value::Str(s) if s.as_str() == "hello, 99"));
+    }
+
+    #[test]
+    fn growing_list() {
+        let mut vm = make_vm();
+
+        check_stop_reason(
+            run_script(&mut vm, "x = []\nloop\n  x.push 1"),
+            StopReason::MemoryLimit,
+        );
+    }
+
+    #[test]
+    fn list_resize() {
+        let mut vm = make_vm();
+
+        check_stop_reason(
+            run_script(&mut vm, "[].resize 1e12"),
+            StopReason::MemoryLimit,
+        );
+    }
+
+    #[test]
+    fn repeat_to_list() {
+        let mut vm = make_vm();
+
+        check_stop_reason(
+            run_script(&mut vm, "iterator.repeat(42, 1e12).to_list()"),
+            StopReason::MemoryLimit,
+        );
+    }
+
+    #[test]
+    fn infinite_repeat_to_list() {
+        let mut vm = make_vm();
+
+        check_stop_reason(
+            run_script(&mut vm, "iterator.repeat(42).to_list()"),
+            StopReason::MemoryLimit,
+        );
+    }
+
+    #[test]
+    fn growing_map() {
+        let mut vm = make_vm();
+
+        check_stop_reason(
+            run_script(&mut vm, "x = {}\nfor i in 0..1e12\n  x.insert i, i"),
+            StopReason::MemoryLimit,
+        );
+    }
+
+    #[test]
+    fn string_concatenation() {
+        let mut vm = make_vm();
+
+        check_stop_reason(
+            run_script(&mut vm, "x = 'x'\nloop\n  x = x + x"),
+            StopReason::MemoryLimit,
+        );
+    }
+
+    #[test]
+    fn string_builder() {
+        let mut vm = make_vm();
+
+        check_stop_reason(
+            run_script(&mut vm, "x = 'x'\nloop\n  x = '$x$x'"),
+            StopReason::MemoryLimit,
+        );
+    }
+
+    #[test]
+    fn string_replace() {
+        let mut vm = make_vm();
+
+        check_stop_reason(
+            run_script(&mut vm, "x = 'x'\nloop\n  x = x.replace 'x', 'xx'"),
+            StopReason::MemoryLimit,
+        );
+    }
+
+    #[test]
+    fn string_format_with_large_width() {
+        let mut vm = make_vm();
+
+        check_stop_reason(
+            run_script(&mut vm, "'{:4000000000}'.format 1"),
+            StopReason::MemoryLimit,
+        );
+    }
+
+    #[test]
+    fn string_format_with_large_precision() {
+        let mut vm = make_vm();
+
+        check_stop_reason(
+            run_script(&mut vm, "'{:.4000000000}'.format 1.5"),
+            StopReason::MemoryLimit,
+        );
+    }
+
+    #[test]
+    fn string_template_with_large_width() {
+        let mut vm = make_vm();
+
+        check_stop_reason(
+            run_script(&mut vm, "x = 1\n'${x:4000000000}'"),
+            StopReason::MemoryLimit,
+        );
+    }
+
+    #[test]
+    fn big_int_multiplication() {
+        let mut vm = make_vm();
+
+        check_stop_reason(
+            run_script(&mut vm, "x = 3\nloop\n  x = x * x"),
+            StopReason::MemoryLimit,
+        );
+    }
+
+    #[test]
+    fn big_int_pow() {
+        let mut vm = make_vm();
+
+        check_stop_reason(
+            run_script(&mut vm, "3.pow 1000000"),
+            StopReason::MemoryLimit,
+        );
+    }
+
+    #[test]
+    fn overwriting_map_entries_doesnt_use_memory() {
+        let mut vm = make_vm();
+
+        let script = "
+x = {count: 0}
+for i in 0..100000
+  x.count = i
+x.count
+";
+        let result = run_script(&mut vm, script).unwrap();
+        assert!(matches!(result, Value::Number(n) if n == 99999));
+    }
+
+    #[test]
+    fn memory_limit_cant_be_caught() {
+        let mut vm = make_vm();
+
+        let script = "
+try
+  [].resize 1e12
+catch _
+  'caught'
+";
+        check_stop_reason(run_script(&mut vm, script), StopReason::MemoryLimit);
+    }
+
+    #[test]
+    fn memory_limit_applies_to_each_run() {
+        let mut vm = make_vm();
+
+        for _ in 0..10 {
+            run_script(&mut vm, "(1..1000).to_list()").unwrap();
+        }
+    }
+}
@@ -3,7 +3,7 @@ mod runtime_test_utils;
 mod objects {
     use crate::runtime_test_utils::*;
     use koto_runtime::prelude::*;
-    use koto_runtime::Result;
+    use koto_runtime::{FormatRepresentation, Result};
 
     #[derive(Clone, Copy, Debug)]
     struct TestObject {
@@ -98,6 +98,15 @@ mod objects {
             Ok(())
         }
 
+        fn format_with_spec(&self, ctx: &mut DisplayContext, spec: &FormatSpec) -> Result<bool> {
+            if spec.representation == Some(FormatRepresentation::Hex) {
+                ctx.append(format!("{}: {:#x}", Self::TYPE, self.x));
+                Ok(true)
+            } else {
+                Ok(false)
+            }
+        }
+
         fn lookup(&self, key: &ValueKey) -> Option<Value> {
             TEST_OBJECT_ENTRIES.with(|entries| entries.get(key).cloned())
         }
@@ -338,6 +347,15 @@ x.to_number()
             test_object_script(script, string("TestObject: 42"));
         }
 
+        #[test]
+        fn format_with_spec() {
+            let script = "
+x = make_object 42
+'${x:x}|${x:>16}'
+";
+            test_object_script(script, string("TestObject: 0x2a|  TestObject: 42"));
+        }
+
         #[test]
         fn negate() {
             let script = "
@@ -0,0 +1,150 @@
+use koto_bytecode::{CompilerSettings, Loader};
+use koto_runtime::{prelude::*, Profile, ProfileMetric, ProfiledFunction};
+
+fn profile_script(script: &str) -> Profile {
+    let mut vm = Vm::with_settings(VmSettings {
+        enable_profiler: true,
+        ..Default::default()
+    });
+    let chunk = Loader::default()
+        .compile_script(script, &None, CompilerSettings::default())
+        .expect("Failed to compile script");
+    vm.run(chunk).expect("Failed to run script");
+    vm.profile().expect("Missing profile")
+}
+
+const SCRIPT: &str = "\
+fib = |n|
+  if n < 2
+    n
+  else
+    (fib n - 1) + (fib n - 2)
+x = fib 5
+y = (1, 2, 3)
+  .each |n| n * 2
+  .to_tuple()
+";
+
+fn function<'a>(profile: &'a Profile, name: &str) -> &'a ProfiledFunction {
+    profile
+        .functions
+        .iter()
+        .find(|function| function.name == name)
+        .unwrap_or_else(|| panic!("Missing function '{name}'"))
+}
+
+#[test]
+fn functions() {
+    let profile = profile_script(SCRIPT);
+
+    let names: Vec<_> = profile
+        .functions
+        .iter()
+        .map(|function| (function.name.as_str(), function.line, function.calls))
+        .collect();
+    assert_eq!(
+        names,
+        [("<main>", 1, 1), ("fib", 1, 15), ("<anonymous>", 8, 3)]
+    );
+
+    // The main function's total includes all executed instructions
+    let main = function(&profile, "<main>");
+    let self_instructions: u64 = profile
+        .functions
+        .iter()
+        .map(|function| function.self_stats.instructions)
+        .sum();
+    assert_eq!(main.total_stats.instructions, self_instructions);
+
+    // Recursive calls are only counted once in a function's total
+    let fib = function(&profile, "fib");
+    assert_eq!(fib.total_stats, fib.self_stats);
+}
+
+#[test]
+fn lines() {
+    let profile = profile_script(SCRIPT);
+
+    let line_instructions: u64 = profile
+        .lines
+        .iter()
+        .map(|line| line.stats.instructions)
+        .sum();
+    let main = function(&profile, "<main>");
+    assert_eq!(line_instructions, main.total_stats.instructions);
+
+    let mut lines: Vec<_> = profile.lines.iter().map(|line| line.line).collect();
+    lines.sort();
+    assert_eq!(lines, [1, 2, 3, 5, 6, 7, 8, 9]);
+}
+
+#[test]
+fn call_tree() {
+    let profile = profile_script(SCRIPT);
+
+    // The recursive calls to fib produce a node for each depth of recursion
+    let depths: Vec<_> = profile
+        .call_tree
+        .iter()
+        .map(|node| {
+            let mut depth = 0;
+            let mut parent = node.parent;
+            while let Some(index) = parent {
+                depth += 1;
+                parent = profile.call_tree[index].parent;
+            }
+            (
+                profile.functions[node.function].name.as_str(),
+                depth,
+                node.calls,
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        depths,
+        [
+            ("<main>", 0, 1),
+            ("fib", 1, 1),
+            ("fib", 2, 2),
+            ("fib", 3, 4),
+            ("fib", 4, 6),
+            ("fib", 5, 2),
+            // Functions called from native functions are children of the calling function
+            ("<anonymous>", 1, 3),
+        ]
+    );
+
+    let report = profile.call_tree_report();
+    assert!(report.contains("  <main> (line 1)\n"), "{report}");
+    assert!(report.contains("    <anonymous> (line 8)\n"), "{report}");
+}
+
+#[test]
+fn folded_stacks() {
+    let profile = profile_script(SCRIPT);
+
+    let stacks = profile.folded_stacks(ProfileMetric::Instructions);
+    let stacks: Vec<_> = stacks
+        .lines()
+        .map(|line| line.rsplit_once(' ').unwrap().0)
+        .collect();
+
+    assert_eq!(
+        stacks,
+        [
+            "<main> (line 1)",
+            "<main> (line 1);fib (line 1)",
+            "<main> (line 1);fib (line 1);fib (line 1)",
+            "<main> (line 1);fib (line 1);fib (line 1);fib (line 1)",
+            "<main> (line 1);fib (line 1);fib (line 1);fib (line 1);fib (line 1)",
+            "<main> (line 1);fib (line 1);fib (line 1);fib (line 1);fib (line 1);fib (line 1)",
+            "<main> (line 1);<anonymous> (line 8)",
+        ]
+    );
+}
+
+#[test]
+fn profiling_is_disabled_by_default() {
+    assert!(Vm::default().profile().is_none());
+}
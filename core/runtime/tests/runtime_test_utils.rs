@@ -5,30 +5,51 @@ use koto_runtime::{prelude::*, Result, Value::*};
 use std::{cell::RefCell, rc::Rc};
 
 pub fn test_script(script: &str, expected_output: impl Into<Value>) {
-    let output = PtrMut::from(String::new());
-
-    let vm = Vm::with_settings(VmSettings {
-        stdout: Rc::new(TestStdout {
-            output: output.clone(),
-        }),
-        stderr: Rc::new(TestStdout {
-            output: output.clone(),
-        }),
-        ..Default::default()
-    });
-
-    if let Err(e) = run_script_with_vm(vm, script, expected_output.into()) {
-        let output = output.borrow();
-        if !output.is_empty() {
-            println!("Stdout:\n-------\n\n{output}\n-------\n");
+    let expected_output = expected_output.into();
+
+    // Scripts are run with and without the optimizer to check that the results match
+    for enable_optimizer in [false, true] {
+        let output = PtrMut::from(String::new());
+
+        let vm = Vm::with_settings(VmSettings {
+            stdout: Rc::new(TestStdout {
+                output: output.clone(),
+            }),
+            stderr: Rc::new(TestStdout {
+                output: output.clone(),
+            }),
+            ..Default::default()
+        });
+
+        let compiler_settings = CompilerSettings {
+            enable_optimizer,
+            ..Default::default()
+        };
+
+        if let Err(e) =
+            run_script_with_vm_and_settings(vm, script, expected_output.clone(), compiler_settings)
+        {
+            let output = output.borrow();
+            if !output.is_empty() {
+                println!("Stdout:\n-------\n\n{output}\n-------\n");
+            }
+            panic!("{e} (optimizer enabled: {enable_optimizer})");
         }
-        panic!("{e}");
     }
 }
 
-pub fn run_script_with_vm(mut vm: Vm, script: &str, expected_output: Value) -> Result<()> {
+pub fn run_script_with_vm(vm: Vm, script: &str, expected_output: Value) -> Result<()> {
+    run_script_with_vm_and_settings(vm, script, expected_output, CompilerSettings::default())
+}
+
+pub fn run_script_with_vm_and_settings(
+    mut vm: Vm,
+    script: &str,
+    expected_output: Value,
+    compiler_settings: CompilerSettings,
+) -> Result<()> {
     let mut loader = Loader::default();
-    let chunk = match loader.compile_script(script, &None, CompilerSettings::default()) {
+    let chunk = match loader.compile_script(script, &None, compiler_settings) {
         Ok(chunk) => chunk,
         Err(error) => {
             print_chunk(script, vm.chunk());
@@ -36,6 +57,12 @@ pub fn run_script_with_vm(mut vm: Vm, script: &str, expected_output: Value) -> R
         }
     };
 
+    // The compiler's output should always pass verification
+    if let Err(error) = Chunk::verify(chunk.clone()) {
+        print_chunk(script, chunk);
+        return Err(format!("Error while verifying chunk: {error}").into());
+    }
+
     match vm.run(chunk) {
         Ok(result) => {
             match vm.run_binary_op(BinaryOp::Equal, result.clone(), expected_output.clone()) {
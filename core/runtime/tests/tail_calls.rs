@@ -0,0 +1,57 @@
+use koto_bytecode::{CompilerSettings, Loader};
+use koto_runtime::prelude::*;
+
+fn run_failing_script(script: &str) -> String {
+    let mut vm = Vm::default();
+    let chunk = Loader::default()
+        .compile_script(script, &None, CompilerSettings::default())
+        .expect("Failed to compile script");
+    match vm.run(chunk) {
+        Ok(_) => panic!("Expected the script to fail"),
+        Err(error) => error.to_string(),
+    }
+}
+
+#[test]
+fn elided_frames_are_noted_in_error_traces() {
+    let script = "
+f = |n|
+  if n == 0
+    throw 'error'
+  f n - 1
+f 10
+";
+    let error = run_failing_script(script);
+
+    assert!(
+        error.contains("--- 10 frames elided by tail calls"),
+        "{error}"
+    );
+}
+
+#[test]
+fn a_single_elided_frame_is_noted_in_error_traces() {
+    let script = "
+f = |n| throw 'error'
+g = |n| f n
+g 1
+";
+    let error = run_failing_script(script);
+
+    assert!(
+        error.contains("--- 1 frame elided by a tail call"),
+        "{error}"
+    );
+}
+
+#[test]
+fn frames_with_non_tail_calls_are_included_in_error_traces() {
+    let script = "
+f = |n| throw 'error'
+g = |n| (f n) + 1
+g 1
+";
+    let error = run_failing_script(script);
+
+    assert!(!error.contains("elided"), "{error}");
+}
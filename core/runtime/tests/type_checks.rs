@@ -0,0 +1,143 @@
+use koto_bytecode::{CompilerSettings, Loader};
+use koto_runtime::{prelude::*, Result};
+
+fn run_script(script: &str, enable_type_checks: bool) -> Result<Value> {
+    let mut vm = Vm::default();
+    let chunk = Loader::default()
+        .compile_script(
+            script,
+            &None,
+            CompilerSettings {
+                enable_type_checks,
+                ..Default::default()
+            },
+        )
+        .expect("Failed to compile script");
+    vm.run(chunk)
+}
+
+fn check_type_error(script: &str, expected_error: &str) {
+    match run_script(script, true) {
+        Ok(_) => panic!("Expected the script to fail:\n{script}"),
+        Err(error) => {
+            let error = error.to_string();
+            assert!(error.starts_with(expected_error), "{error}\n{script}");
+        }
+    }
+
+    // The type hints are ignored when type checks aren't enabled
+    if let Err(error) = run_script(script, false) {
+        panic!("Unexpected error with type checks disabled: {error}\n{script}");
+    }
+}
+
+fn check_type_checks_pass(script: &str) {
+    if let Err(error) = run_script(script, true) {
+        panic!("Unexpected error: {error}\n{script}");
+    }
+}
+
+mod type_checks {
+    use super::*;
+
+    #[test]
+    fn function_arg() {
+        let script = "
+f = |x: Number, name: String| '$name: $x'
+f 'abc', 'xyz'
+";
+        check_type_error(script, "Expected Number, but found String");
+    }
+
+    #[test]
+    fn type_hint_after_unpacked_arg() {
+        check_type_checks_pass("f = |(a, b), c: Tuple| a + b\nf (1, 2), (3, 4)");
+    }
+
+    #[test]
+    fn function_output() {
+        let script = "
+f = |x| -> String
+  x * 2
+f 21
+";
+        check_type_error(script, "Expected String, but found Int");
+    }
+
+    #[test]
+    fn function_output_from_call_in_tail_position() {
+        let script = "
+g = |x| x * 2
+f = |x| -> String
+  g x
+f 21
+";
+        check_type_error(script, "Expected String, but found Int");
+    }
+
+    #[test]
+    fn explicit_return() {
+        let script = "
+f = |x| -> Number
+  if x < 0
+    return 'negative'
+  x
+f 1
+f -1
+";
+        check_type_error(script, "Expected Number, but found String");
+    }
+
+    #[test]
+    fn empty_return() {
+        let script = "
+f = || -> Bool
+  return
+f()
+";
+        check_type_error(script, "Expected Bool, but found Null");
+    }
+
+    #[test]
+    fn generator_output() {
+        let script = "
+f = || -> Int
+  yield 1
+  yield 'two'
+f().to_list()
+";
+        check_type_error(script, "Expected Int, but found String");
+    }
+
+    #[test]
+    fn assignment() {
+        check_type_error("x: List = (1, 2)", "Expected List, but found Tuple");
+    }
+
+    #[test]
+    fn object_type() {
+        let script = "
+make_foo = || {@type: 'Foo'}
+f = |x: Bar| x
+f make_foo()
+";
+        check_type_error(script, "Expected Bar, but found Foo");
+    }
+
+    #[test]
+    fn matching_types() {
+        let script = "
+make_foo = || {@type: 'Foo'}
+f = |a: Number, b: Int, c: Float, d: Map, e: Foo, g: Any, h: Function| a
+f 1, 2, 3.0, make_foo(), make_foo(), null, f
+x: Iterable = 'abc'
+y: Tuple = 1, 2, 3
+z: Null = null
+gen = || -> Number
+  yield 1
+  yield 2.5
+gen().to_list()
+";
+        check_type_checks_pass(script);
+    }
+}
@@ -0,0 +1,116 @@
+mod runtime_test_utils;
+
+use koto_bytecode::{Chunk, CompilerSettings, Loader};
+use koto_runtime::{prelude::*, Capabilities};
+use runtime_test_utils::TestStdout;
+use std::{
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    rc::Rc,
+};
+
+// The number of mutated chunks that are generated for each script
+const MUTATIONS_PER_SCRIPT: usize = 300;
+
+fn koto_tests_dir() -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "..", "..", "koto", "tests"]
+        .iter()
+        .collect()
+}
+
+// A small deterministic random number generator, so that failures can be reproduced
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn make_vm() -> Vm {
+    let output = PtrMut::from(String::new());
+    Vm::with_settings(VmSettings {
+        capabilities: Capabilities::sandboxed(),
+        instruction_limit: Some(100_000),
+        memory_limit: Some(16 * 1024 * 1024),
+        stdout: Rc::new(TestStdout {
+            output: output.clone(),
+        }),
+        stderr: Rc::new(TestStdout { output }),
+        ..Default::default()
+    })
+}
+
+// Chunks that pass verification should never cause the runtime to panic
+//
+// Chunks are compiled from the scripts in koto/tests, and then have some of their bytes mutated.
+// Mutated chunks that pass verification are run, with any errors being ignored.
+#[test]
+fn mutated_chunks_that_pass_verification_dont_panic() {
+    let mut scripts: Vec<_> = fs::read_dir(koto_tests_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "koto")
+        })
+        .collect();
+    scripts.sort();
+
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+    let mut failures = Vec::new();
+    let mut verified_count = 0;
+
+    // Panics are expected to be caught, so the default hook's output is suppressed
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    for script_path in scripts {
+        let script = fs::read_to_string(&script_path).unwrap();
+        let chunk = Loader::default()
+            .compile_script(&script, &None, CompilerSettings::default())
+            .unwrap();
+
+        for mutation in 0..MUTATIONS_PER_SCRIPT {
+            let mut mutated = Chunk::clone(&chunk);
+            for _ in 0..1 + rng.below(3) {
+                let index = rng.below(mutated.bytes.len());
+                mutated.bytes[index] = rng.next() as u8;
+            }
+            let mutated = Ptr::from(mutated);
+
+            if Chunk::verify(mutated.clone()).is_err() {
+                continue;
+            }
+            verified_count += 1;
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                let _ = make_vm().run(mutated);
+            }));
+            if let Err(error) = result {
+                let message = error
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| error.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_default();
+                failures.push(format!(
+                    "{} (mutation {mutation}): {message}",
+                    script_path.file_name().unwrap().to_string_lossy()
+                ));
+            }
+        }
+    }
+
+    panic::set_hook(default_hook);
+
+    assert!(verified_count > 0);
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
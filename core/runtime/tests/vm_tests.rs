@@ -96,6 +96,276 @@ a += b *= c";
         }
     }
 
+    mod big_numbers {
+        use super::*;
+
+        #[test]
+        fn integer_overflow_produces_a_big_int() {
+            let script = "
+x = 9223372036854775807
+'${x + 1}'
+";
+            test_script(script, string("9223372036854775808"));
+        }
+
+        #[test]
+        fn big_int_multiplication() {
+            let script = "
+x = 4294967296
+y = x * x * x
+'$y'
+";
+            test_script(script, string("79228162514264337593543950336"));
+        }
+
+        #[test]
+        fn big_int_results_that_fit_in_an_int_are_normalized() {
+            let script = "
+x = 9223372036854775807
+y = (x + 10) - 20
+y == x - 10 and koto.type(y) == 'Int'
+";
+            test_script(script, true);
+        }
+
+        #[test]
+        fn negating_the_smallest_int() {
+            let script = "
+x = -9223372036854775807 - 1
+'${-x}'
+";
+            test_script(script, string("9223372036854775808"));
+        }
+
+        #[test]
+        fn big_int_compound_assignment() {
+            let script = "
+x = 9223372036854775807
+x *= 2
+x -= 1
+'$x'
+";
+            test_script(script, string("18446744073709551613"));
+        }
+
+        #[test]
+        fn big_int_multiplication_that_is_too_large() {
+            let script = "
+x = 2.pow 2000000
+try
+  x * x * x
+catch error
+  error.starts_with 'Integer result is too large'
+";
+            test_script(script, true);
+        }
+
+        #[test]
+        fn big_int_pow_that_is_too_large() {
+            let script = "
+try
+  3.pow 10000000
+catch error
+  error.starts_with 'Integer result is too large'
+";
+            test_script(script, true);
+        }
+
+        #[test]
+        fn big_int_parsing_that_is_too_large() {
+            let script = "
+x = '{:9>1100000}'.format ''
+try
+  x.to_number()
+catch error
+  error.starts_with 'Integer result is too large'
+";
+            test_script(script, true);
+        }
+
+        #[test]
+        fn big_int_comparisons() {
+            let script = "
+x = 9223372036854775807 + 1
+x > 9223372036854775807 and x > 1.0 and x == x + 0
+";
+            test_script(script, true);
+        }
+
+        #[test]
+        fn big_int_map_keys() {
+            let script = "
+x = 9223372036854775807
+m = {}
+m.insert x + 1, 'big'
+m.get x + 1
+";
+            test_script(script, string("big"));
+        }
+
+        #[test]
+        fn decimal_addition() {
+            let script = "
+a = number.decimal '0.1'
+b = number.decimal '0.2'
+a + b == number.decimal '0.3'
+";
+            test_script(script, true);
+        }
+
+        #[test]
+        fn decimal_arithmetic_with_ints() {
+            let script = "
+x = number.decimal '19.99'
+'${x * 3 - 1}'
+";
+            test_script(script, string("58.97"));
+        }
+
+        #[test]
+        fn decimal_division() {
+            let script = "
+x = number.decimal('1') / 8
+'$x'
+";
+            test_script(script, string("0.125"));
+        }
+
+        #[test]
+        fn decimal_with_floats_produces_a_float() {
+            let script = "
+x = number.decimal('1.5') + 0.25
+koto.type x
+";
+            test_script(script, string("Float"));
+        }
+
+        #[test]
+        fn decimal_map_keys() {
+            let script = "
+m = {}
+m.insert (number.decimal '2.0'), 'two'
+m.insert (number.decimal '2.5'), 'two and a half'
+'${m.get 2}, ${m.get number.decimal '2.50'}'
+";
+            test_script(script, string("two, two and a half"));
+        }
+
+        #[test]
+        fn map_keys_with_mixed_number_kinds() {
+            let script = "
+m = {}
+m.insert (number.decimal '1.5'), 'decimal'
+m.insert 2.0, 'float'
+m.insert 9223372036854775807 + 1, 'big'
+m.get(1.5), m.get(number.decimal '2'), m.get(2), m.get(9223372036854775808.0)
+";
+            test_script(
+                script,
+                tuple(&[
+                    string("decimal"),
+                    string("float"),
+                    string("float"),
+                    string("big"),
+                ]),
+            );
+        }
+    }
+
+    mod strict_integer_overflow {
+        use super::*;
+
+        fn test_strict_script(script: &str, expected_output: impl Into<Value>) {
+            let vm = Vm::with_settings(VmSettings {
+                strict_integer_overflow: true,
+                ..Default::default()
+            });
+
+            if let Err(e) = run_script_with_vm(vm, script, expected_output.into()) {
+                panic!("{e}");
+            }
+        }
+
+        #[test]
+        fn add() {
+            let script = "
+x = 9223372036854775807
+try
+  x + 1
+catch error
+  error.starts_with 'Integer overflow'
+";
+            test_strict_script(script, true);
+        }
+
+        #[test]
+        fn multiply_assign() {
+            let script = "
+x = 4294967296
+try
+  x *= x
+catch error
+  error.starts_with 'Integer overflow'
+";
+            test_strict_script(script, true);
+        }
+
+        #[test]
+        fn negate() {
+            let script = "
+x = -9223372036854775807 - 1
+try
+  -x
+catch error
+  error.starts_with 'Integer overflow'
+";
+            test_strict_script(script, true);
+        }
+
+        #[test]
+        fn abs() {
+            let script = "
+x = -9223372036854775807 - 1
+try
+  x.abs()
+catch error
+  error.starts_with 'Integer overflow'
+";
+            test_strict_script(script, true);
+        }
+
+        #[test]
+        fn pow() {
+            let script = "
+try
+  2.pow 64
+catch error
+  error.starts_with 'Integer overflow'
+";
+            test_strict_script(script, true);
+        }
+
+        #[test]
+        fn to_number() {
+            let script = "
+try
+  '9223372036854775808'.to_number()
+catch error
+  error.starts_with 'Integer overflow'
+";
+            test_strict_script(script, true);
+        }
+
+        #[test]
+        fn non_overflowing_arithmetic() {
+            let script = "
+x = 9223372036854775806
+x + 1
+";
+            test_strict_script(script, i64::MAX);
+        }
+    }
+
     mod logic {
         use super::*;
 
@@ -503,6 +773,147 @@ c
         }
     }
 
+    mod map_destructuring {
+        use super::*;
+
+        #[test]
+        fn assign_entries() {
+            let script = "
+{name, age} = {name: 'Ada', age: 36}
+'$name $age'
+";
+            test_script(script, string("Ada 36"));
+        }
+
+        #[test]
+        fn assign_entry_to_new_id() {
+            let script = "
+{x: a, 'y': b} = {x: 1, y: 2}
+a, b
+";
+            test_script(script, number_tuple(&[1, 2]));
+        }
+
+        #[test]
+        fn assign_from_own_map() {
+            let script = "
+foo = {foo: 1, x: 2}
+{foo, x} = foo
+foo, x
+";
+            test_script(script, number_tuple(&[1, 2]));
+        }
+
+        #[test]
+        fn assignment_result_is_the_map() {
+            let script = "
+m = ({x} = {x: 1, y: 2})
+m.y
+";
+            test_script(script, 2);
+        }
+
+        #[test]
+        fn default_values() {
+            let script = "
+calls = []
+f = |n|
+  calls.push n
+  n
+{x = f(1), y = f(2)} = {x: 42}
+x, y, calls.size()
+";
+            test_script(script, number_tuple(&[42, 2, 1]));
+        }
+
+        #[test]
+        fn nested_map_pattern() {
+            let script = "
+{pos: {x, y}, id = -1} = {pos: {x: 1, y: 2}}
+x, y, id
+";
+            test_script(script, number_tuple(&[1, 2, -1]));
+        }
+
+        #[test]
+        fn rest() {
+            let script = "
+{x, rest...} = {x: 1, y: 2, z: 3}
+rest.keys().to_tuple()
+";
+            test_script(script, tuple(&["y".into(), "z".into()]));
+        }
+
+        #[test]
+        fn entries_from_base_map() {
+            let script = "
+base = {x: 1}
+{x, y} = {@base: base, y: 2}
+x, y
+";
+            test_script(script, number_tuple(&[1, 2]));
+        }
+
+        #[test]
+        fn entries_from_overloaded_index() {
+            let script = "
+{x, y} =
+  y: 0
+  @[]: |key| 'indexed $key'
+x, y
+";
+            test_script(script, tuple(&["indexed x".into(), 0.into()]));
+        }
+
+        #[test]
+        fn missing_key_throws() {
+            let script = r#"
+try
+  {x, y} = {x: 1}
+catch error
+  error.starts_with "'y' is missing from the destructured map"
+"#;
+            test_script(script, true);
+        }
+
+        #[test]
+        fn multi_assignment() {
+            let script = "
+{a}, b, {c} = {a: 1}, 2, {c: 3}
+a, b, c
+";
+            test_script(script, number_tuple(&[1, 2, 3]));
+        }
+
+        #[test]
+        fn function_arg() {
+            let script = "
+f = |{x, y = 10}| x + y
+f({x: 1}), f({x: 1, y: 2})
+";
+            test_script(script, number_tuple(&[11, 3]));
+        }
+
+        #[test]
+        fn nested_function_arg() {
+            let script = "
+f = |a, ({b}, c)| a + b + c
+f 1, ({b: 2}, 3)
+";
+            test_script(script, 6);
+        }
+
+        #[test]
+        fn function_arg_default_with_capture() {
+            let script = "
+z = 100
+f = |a, {b = a + z}| b
+f 1, {}
+";
+            test_script(script, 101);
+        }
+    }
+
     mod if_expressions {
         use super::*;
 
@@ -865,6 +1276,134 @@ x
 "#;
             test_script(script, 100);
         }
+
+        #[test]
+        fn nested_pattern_in_first_alternative() {
+            // A bug meant that a nested match in the first alternative would skip over the
+            // remaining patterns
+            let script = "
+x = [1], 5
+match x
+  ([a], 3) or (a, 2) then 'first'
+  else 'second'
+";
+            test_script(script, string("second"));
+        }
+
+        #[test]
+        fn map_pattern_with_literal_value() {
+            let script = "
+match {type: 'click', x: 10, y: 20}
+  {type: 'key', code} then code
+  {type: 'click', x, y} then x + y
+  else -1
+";
+            test_script(script, 30);
+        }
+
+        #[test]
+        fn map_pattern_with_string_key() {
+            let script = "
+match {'foo bar': 42}
+  {'foo bar': x} then x
+  else -1
+";
+            test_script(script, 42);
+        }
+
+        #[test]
+        fn map_pattern_with_missing_key() {
+            let script = "
+match {x: 1}
+  {x, y} then x + y
+  {x} then x
+  else -1
+";
+            test_script(script, 1);
+        }
+
+        #[test]
+        fn map_pattern_with_non_map_value() {
+            let script = "
+match (1, 2)
+  {} then -1
+  (a, b) then a + b
+  else -2
+";
+            test_script(script, 3);
+        }
+
+        #[test]
+        fn map_pattern_with_rest() {
+            let script = "
+match {x: 1, y: 2, z: 3}
+  {x, rest...} then x, rest.keys().to_tuple()
+  else -1
+";
+            test_script(
+                script,
+                tuple(&[number(1), tuple(&["y".into(), "z".into()])]),
+            );
+        }
+
+        #[test]
+        fn map_pattern_with_nested_patterns() {
+            let script = "
+f = |event|
+  match event
+    {pos: (x, 0)} or {pos: [x, 0]} then x
+    {pos: {x, y}} then x * y
+    else -1
+f({pos: (1, 0)}), f({pos: [2, 0]}), f({pos: {x: 3, y: 4}}), f({pos: (1, 1)})
+";
+            test_script(script, number_tuple(&[1, 2, 12, -1]));
+        }
+
+        #[test]
+        fn map_pattern_with_entries_from_base_map() {
+            let script = "
+match {@base: {type: 'click'}, x: 5, y: 6}
+  {type: 'click', x, y} then x + y
+  else -1
+";
+            test_script(script, 11);
+        }
+
+        #[test]
+        fn map_pattern_with_entries_from_overloaded_index() {
+            let script = "
+match {x: 1, @[]: |key| if key == 'y' then 10}
+  {x, y: 10} then x + 10
+  else -1
+";
+            test_script(script, 11);
+        }
+
+        #[test]
+        fn map_pattern_in_tuple() {
+            let script = "
+match 1, {x: 2}
+  0, {x} then -1
+  1, {y} then -2
+  1, {x} then x
+  else -3
+";
+            test_script(script, 2);
+        }
+
+        #[test]
+        fn typed_id_pattern() {
+            let script = "
+f = |x|
+  match x
+    n: Number then n
+    s: String then s.size()
+    ([a, b], l: List) then a + b + l.size()
+    else -1
+f(1), f('abc'), f(([1, 2], [1, 2, 3])), f(([1, 2], (1, 2, 3))), f(null)
+";
+            test_script(script, number_tuple(&[1, 3, 6, -1, -1]));
+        }
     }
 
     mod switch_expressions {
@@ -1530,6 +2069,139 @@ calls
         }
     }
 
+    mod tail_calls {
+        use super::*;
+
+        #[test]
+        fn deep_recursion() {
+            let script = "
+count = |n, total|
+  if n == 0
+    total
+  else
+    count n - 1, total + 1
+count 100000, 0
+";
+            test_script(script, 100000);
+        }
+
+        #[test]
+        fn explicit_return() {
+            let script = "
+f = |n|
+  if n > 0
+    return f(n - 1)
+  'done'
+f 10
+";
+            test_script(script, "done");
+        }
+
+        #[test]
+        fn match_arm() {
+            let script = "
+sum = |values, total|
+  match values
+    [first, rest...] then sum rest, total + first
+    [] then total
+sum [1, 2, 3, 4], 0
+";
+            test_script(script, 10);
+        }
+
+        #[test]
+        fn switch_arm() {
+            let script = "
+f = |n|
+  switch
+    n > 100 then n
+    else f n * 2
+f 3
+";
+            test_script(script, 192);
+        }
+
+        #[test]
+        fn instance_functions() {
+            let script = "
+checks =
+  is_even: |n| if n == 0 then true else self.is_odd n - 1
+  is_odd: |n| if n == 0 then false else self.is_even n - 1
+checks.is_even 10001
+";
+            test_script(script, false);
+        }
+
+        #[test]
+        fn captured_values() {
+            let script = "
+step = 2
+f = |n| if n <= 0 then n else f n - step
+f 11
+";
+            test_script(script, -1);
+        }
+
+        #[test]
+        fn variadic_args() {
+            let script = "
+f = |n, values...|
+  if n == 0
+    values.to_list()
+  else
+    f n - 1, n, 42
+f 3
+";
+            test_script(script, number_list(&[1, 42]));
+        }
+
+        #[test]
+        fn native_function() {
+            let script = "
+f = |s| s.to_uppercase()
+f 'abc'
+";
+            test_script(script, "ABC");
+        }
+
+        #[test]
+        fn generator() {
+            let script = "
+gen = |n|
+  yield n
+  yield n + 1
+f = |n| gen n
+f(1).to_list()
+";
+            test_script(script, number_list(&[1, 2]));
+        }
+
+        #[test]
+        fn call_in_try_block() {
+            let script = "
+g = |n| throw 'error'
+f = |n|
+  try
+    return g n
+  catch _
+    -1
+f 42
+";
+            test_script(script, -1);
+        }
+
+        #[test]
+        fn result_of_call_in_nested_function() {
+            let script = "
+f = |n|
+  g = |x| x * 2
+  (g n) + 1
+f 20
+";
+            test_script(script, 41);
+        }
+    }
+
     mod for_loops {
         use super::*;
 
@@ -1907,6 +2579,16 @@ m.bar";
             test_script(script, 99);
         }
 
+        #[test]
+        fn map_block_entry_with_assignment_value() {
+            let script = "
+m =
+  x: y = 2
+m.x + y
+";
+            test_script(script, 4);
+        }
+
         #[test]
         fn implicit_values() {
             let script = "
@@ -2606,6 +3288,71 @@ foo = {@display: || 'Foo'}
             test_script(script, string("(1, 2, 3)"));
         }
 
+        #[test]
+        fn formatted_expression_with_precision() {
+            let script = "
+x = 1 / 3
+'${x:.2}'
+";
+            test_script(script, string("0.33"));
+        }
+
+        #[test]
+        fn formatted_expression_with_alignment() {
+            let script = "
+x = 42
+'[${x:<5}][${x:^6}][${x:>5}][${x:5}]'
+";
+            test_script(script, string("[42   ][  42  ][   42][   42]"));
+        }
+
+        #[test]
+        fn formatted_expression_with_fill_and_precision() {
+            let script = "
+x = 1.5
+'${x:_>8.3}'
+";
+            test_script(script, string("___1.500"));
+        }
+
+        #[test]
+        fn formatted_string_expression() {
+            let script = "
+x = 'hello'
+'${x:-^9}|${x:.3}|${x:7}|${x:}'
+";
+            test_script(script, string("--hello--|hel|hello  |hello"));
+        }
+
+        #[test]
+        fn formatted_value_with_overloaded_display() {
+            let script = "
+foo = {@display: || 'Foo'}
+'${foo:>5}'
+";
+            test_script(script, string("  Foo"));
+        }
+
+        #[test]
+        fn formatted_expression_with_number_representations() {
+            let script = "
+x = 255
+'${x:#x}|${x:08b}|${x:+,}|${x:.2e}|${1234567:_}'
+";
+            test_script(script, string("0xff|11111111|+255|2.55e2|1_234_567"));
+        }
+
+        #[test]
+        fn formatted_value_with_overloaded_format() {
+            let script = "
+foo =
+  @format: |spec| 'Foo($spec)'
+  @display: || 'Foo'
+'${foo:>+08.2} ${foo} {:x}'.format foo
+";
+            test_script(script, string("Foo(>+08.2) Foo Foo(x)"));
+        }
+
         #[test]
         fn recursive_list() {
             let script = "
@@ -3184,6 +3931,108 @@ dog('Fido').speak()
         }
     }
 
+    mod cached_lookups {
+        use super::*;
+
+        #[test]
+        fn modified_map() {
+            let script = "
+x = {foo: 1, bar: 2}
+result = []
+for i in 0..3
+  result.push x.bar
+  x.remove 'foo'
+  x.bar = i * 10
+result
+";
+            test_script(script, number_list(&[2, 0, 10]));
+        }
+
+        #[test]
+        fn different_maps_at_the_same_instruction() {
+            let script = "
+get_foo = |x| x.foo
+a = {foo: 1}
+b = {bar: 0, foo: 2}
+get_foo(a) + get_foo(b) + get_foo(a)
+";
+            test_script(script, 4);
+        }
+
+        #[test]
+        fn value_added_before_base_value() {
+            let script = "
+base = {foo: || 'base'}
+x = {@base: base}
+result = []
+for i in 0..3
+  result.push x.foo()
+  if i == 0
+    x.foo = || 'data'
+result
+";
+            test_script(
+                script,
+                Value::List(KList::from_slice(&[
+                    "base".into(),
+                    "data".into(),
+                    "data".into(),
+                ])),
+            );
+        }
+
+        #[test]
+        fn modified_base() {
+            let script = "
+base = {foo: || 1}
+x = {@base: base}
+result = 0
+for i in 0..3
+  result += x.foo()
+  base.foo = || 10
+result
+";
+            test_script(script, 21);
+        }
+
+        #[test]
+        fn modified_meta_map() {
+            let script = "
+x = {@meta foo: 1}
+y = {@meta foo: 2}
+result = []
+for z in (x, y, x)
+  result.push z.foo
+result
+";
+            test_script(script, number_list(&[1, 2, 1]));
+        }
+
+        #[test]
+        fn fused_access_and_call() {
+            let script = "
+x =
+  foo: |a, b| self.offset + a + b
+  offset: 100
+a = 1
+x.foo(a, 2) + x.foo(3, 4).abs()
+";
+            test_script(script, 210);
+        }
+
+        #[test]
+        fn function_accessed_before_call_args_are_evaluated() {
+            let script = "
+x = {foo: |n| n}
+replace_foo = |n|
+  x.foo = |n| n * 100
+  n
+x.foo(replace_foo 1) + x.foo(2)
+";
+            test_script(script, 201);
+        }
+    }
+
     mod import {
         use super::*;
 